@@ -0,0 +1,452 @@
+//! A library API for using reqq collections from Rust, like from an integration test harness,
+//! without the CLI.
+//!
+//! ```no_run
+//! use reqq::api::ReqqClient;
+//! use std::collections::HashMap;
+//!
+//! let client = ReqqClient::new(".reqq")?;
+//! let mut args = HashMap::new();
+//! args.insert("id".to_owned(), serde_json::json!(1));
+//! let resp = client.send("users/get", Some("staging"), args)?;
+//! assert!(resp.is_success());
+//! let user: serde_json::Value = resp.json()?;
+//! # Ok::<(), reqq::api::Error>(())
+//! ```
+//!
+//! With the `async` feature, `ReqqClient::send_async` sends requests with reqwest's async
+//! client instead, so it can be awaited inside a tokio runtime.
+
+use crate::{
+    client::ClientOpts,
+    executor::RetryPolicy,
+    reqq::{Reqq, ReqqOpts},
+    request::{self, TrailingNewline},
+    response::{self, ResponseParts},
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// The errors of the library API.
+pub use crate::error::ReqqError as Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A request parsed with its env and args applied, as it would be sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    /// Headers in the order the request file has them.
+    pub headers: Vec<(String, String)>,
+    /// The encoded body, with the content type it's sent with.
+    pub body: Option<(Option<String>, Vec<u8>)>,
+    /// The request file with its templates rendered.
+    pub rendered: String,
+}
+
+/// A received response.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    /// Headers in the order they were received, with lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    /// Whether the status is 2xx.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// The first value of a header, by case-insensitive name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Deserializes the body from JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_str(&self.body)
+            .map_err(|e| Error::Other(anyhow::Error::new(e).context("The body isn't valid JSON.")))
+    }
+}
+
+/// An environment and its variables, with those of the envs it extends.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Env {
+    pub name: String,
+    pub variables: HashMap<String, Value>,
+}
+
+/// Sends the requests of a reqq collection. Nothing is recorded in the history, and no
+/// cookies are kept, unless asked for.
+#[derive(Clone, Debug)]
+pub struct ReqqClient {
+    dir: String,
+    client: ClientOpts,
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    history: bool,
+    cookies: bool,
+}
+
+impl ReqqClient {
+    /// A client for the collection in a directory, like `.reqq`.
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            return Err(Error::DirNotFound(dir.to_owned()));
+        }
+        Ok(ReqqClient {
+            dir: dir.to_string_lossy().trim_end_matches('/').to_owned(),
+            client: ClientOpts::default(),
+            timeout: None,
+            retry: RetryPolicy {
+                retries: 0,
+                ..RetryPolicy::default()
+            },
+            history: false,
+            cookies: false,
+        })
+    }
+
+    /// Sets the settings of the HTTP client, like a proxy or TLS certificates. Client settings
+    /// in an env apply unless they're set here.
+    pub fn with_client_opts(mut self, client: ClientOpts) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Sets the timeout of requests whose files don't set their own.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets when and how often failed requests are sent again.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Records sent requests in the history of the collection, like the CLI does.
+    pub fn with_history(mut self, history: bool) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Keeps the cookies responses set, and sends them with later requests, like `--cookies`.
+    pub fn with_cookies(mut self, cookies: bool) -> Self {
+        self.cookies = cookies;
+        self
+    }
+
+    /// The names of the requests in the collection, like `users/get`.
+    pub fn requests(&self) -> Result<Vec<String>> {
+        let mut names = self.reqq()?.list_reqs();
+        names.sort();
+        Ok(names)
+    }
+
+    /// The names of the envs in the collection.
+    pub fn envs(&self) -> Result<Vec<String>> {
+        Ok(self.reqq()?.list_envs())
+    }
+
+    /// Loads an env.
+    pub fn env(&self, name: &str) -> Result<Env> {
+        let reqq = self.reqq()?;
+        if !reqq.list_envs().iter().any(|env| env == name) {
+            return Err(Error::EnvNotFound(name.to_owned()));
+        }
+        Ok(Env {
+            name: name.to_owned(),
+            variables: reqq.env_variables(name)?,
+        })
+    }
+
+    /// Parses a request with an env and args applied, without sending it. Without an env,
+    /// the `default` env is used if there is one.
+    pub fn prepare(
+        &self,
+        name: &str,
+        env: Option<&str>,
+        args: HashMap<String, Value>,
+    ) -> Result<Request> {
+        let reqq = self.reqq()?;
+        self.check(&reqq, name, env, &args)?;
+        let req = reqq.parsed(name, env.map(str::to_owned), args)?;
+        Ok(to_api_request(&req)?)
+    }
+
+    /// Sends a request, after the requests it depends on, with an env and args applied.
+    /// Without an env, the `default` env is used if there is one. Responses with 4xx and 5xx
+    /// statuses aren't errors.
+    pub fn send(
+        &self,
+        name: &str,
+        env: Option<&str>,
+        args: HashMap<String, Value>,
+    ) -> Result<Response> {
+        let reqq = self.reqq()?;
+        self.check(&reqq, name, env, &args)?;
+        let resp = reqq.fetch(name, env.map(str::to_owned), args)?;
+        Ok(to_api_response(&resp))
+    }
+
+    /// Like `send`, but sends the requests with reqwest's async client, for use inside an
+    /// async runtime. Requests with OAuth2 auth can't be sent this way.
+    #[cfg(feature = "async")]
+    pub async fn send_async(
+        &self,
+        name: &str,
+        env: Option<&str>,
+        args: HashMap<String, Value>,
+    ) -> Result<Response> {
+        let reqq = self.reqq()?;
+        self.check(&reqq, name, env, &args)?;
+        let resp = reqq.fetch_async(name, env.map(str::to_owned), args).await?;
+        Ok(to_api_response(&resp))
+    }
+
+    /// Fails with a typed error when the request or env don't exist, or variables are missing.
+    fn check(
+        &self,
+        reqq: &Reqq,
+        name: &str,
+        env: Option<&str>,
+        args: &HashMap<String, Value>,
+    ) -> Result<()> {
+        if !reqq.list_reqs().iter().any(|req| req == name) {
+            return Err(Error::RequestNotFound(name.to_owned()));
+        }
+        if let Some(env) = env.filter(|env| !reqq.list_envs().iter().any(|e| e == env)) {
+            return Err(Error::EnvNotFound(env.to_owned()));
+        }
+        let names = reqq.missing_variables(name, env.map(str::to_owned), args.clone())?;
+        if !names.is_empty() {
+            return Err(Error::Template {
+                request: Some(name.to_owned()),
+                variables: names,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reqq reads the collection when it's created, so a new one picks up changed files.
+    fn reqq(&self) -> Result<Reqq<'_>> {
+        Ok(Reqq::new(ReqqOpts {
+            dir: &self.dir,
+            parts: ResponseParts::Body,
+            raw: true,
+            html_text: false,
+            color: false,
+            verbose: false,
+            trailing_newline: TrailingNewline::default(),
+            timeout: self.timeout,
+            client: self.client.clone(),
+            retry: self.retry.clone(),
+            history: self.history,
+            cookies: self.cookies,
+            check: false,
+            output: None,
+            gql_variables: HashMap::new(),
+            filter: None,
+            ws_count: None,
+            timings: false,
+            compressed: false,
+            infer_content_type: true,
+            validate_body: true,
+            delay: Duration::ZERO,
+            cache: false,
+        })?)
+    }
+}
+
+fn to_api_request(req: &request::Request) -> anyhow::Result<Request> {
+    let inner = req
+        .inner()
+        .ok_or_else(|| anyhow::anyhow!("Request has not been parsed."))?;
+    let body = match inner.body() {
+        Some(body) => {
+            let encoded = body.encode()?;
+            Some((encoded.content_type, encoded.bytes))
+        }
+        None => None,
+    };
+    Ok(Request {
+        method: inner.method().to_string(),
+        url: inner.url().to_string(),
+        headers: inner
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), lossy(value.as_bytes())))
+            .collect(),
+        body,
+        rendered: req.rendered().unwrap_or_default().to_owned(),
+    })
+}
+
+fn to_api_response(resp: &response::Response) -> Response {
+    Response {
+        status: resp.status.as_u16(),
+        headers: resp
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), lossy(value.as_bytes())))
+            .collect(),
+        body: resp.body.clone(),
+    }
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[cfg(test)]
+fn test_collection(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("reqq-api-{}-{}", name, std::process::id()));
+    for (fpath, fstr) in files {
+        let fpath = dir.join(fpath);
+        std::fs::create_dir_all(fpath.parent().unwrap()).unwrap();
+        std::fs::write(fpath, fstr).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_client_prepare() {
+    let dir = test_collection(
+        "prepare",
+        &[
+            ("users/create.reqq", "POST {{ baseUrl }}/users\nContent-Type: application/json\n\n{\"name\": \"{{ name }}\"}"),
+            ("envs/staging.json", "{ \"baseUrl\": \"https://staging.example.com\" }"),
+        ],
+    );
+    let client = ReqqClient::new(&dir).unwrap();
+    assert_eq!(client.requests().unwrap(), vec!["users/create"]);
+    assert_eq!(
+        client.env("staging").unwrap().variables["baseUrl"],
+        "https://staging.example.com"
+    );
+
+    let args = HashMap::from([("name".to_owned(), Value::from("Ada"))]);
+    let req = client
+        .prepare("users/create", Some("staging"), args)
+        .unwrap();
+    assert_eq!(req.method, "POST");
+    assert_eq!(req.url, "https://staging.example.com/users");
+    assert_eq!(
+        req.headers,
+        vec![("content-type".to_owned(), "application/json".to_owned())]
+    );
+    assert_eq!(req.body, Some((None, b"{\"name\": \"Ada\"}".to_vec())));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_client_errors() {
+    let dir = test_collection(
+        "errors",
+        &[
+            ("login.reqq", "POST https://example.com/login"),
+            ("me.reqq", "@depends login\nGET https://example.com/me\nAuthorization: Bearer {{ login.body.token }}\nX-Trace: {{ trace }}"),
+        ],
+    );
+    let client = ReqqClient::new(&dir).unwrap();
+    assert!(matches!(
+        ReqqClient::new(dir.join("nope")),
+        Err(Error::DirNotFound(_))
+    ));
+    assert!(
+        matches!(client.prepare("nope", None, HashMap::new()), Err(Error::RequestNotFound(name)) if name == "nope")
+    );
+    assert!(matches!(client.env("nope"), Err(Error::EnvNotFound(_))));
+    assert!(matches!(
+        client.prepare("login", Some("nope"), HashMap::new()),
+        Err(Error::EnvNotFound(_))
+    ));
+    // Variables from the responses of dependencies aren't missing.
+    let err = client.send("me", None, HashMap::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Request 'me' uses undefined variables: trace"
+    );
+    assert!(
+        matches!(err, Error::Template { request: Some(_), variables } if variables == ["trace"])
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_response() {
+    let resp = Response {
+        status: 201,
+        headers: vec![("content-type".to_owned(), "application/json".to_owned())],
+        body: "{\"id\": 7}".to_owned(),
+    };
+    assert!(resp.is_success());
+    assert_eq!(resp.header("Content-Type"), Some("application/json"));
+    assert_eq!(resp.json::<Value>().unwrap()["id"], 7);
+    assert!(resp.json::<Vec<u8>>().is_err());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_client_send_async() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        for body in ["{\"token\": \"abc\"}", "{\"name\": \"Ada\"}"] {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let n = stream.read(&mut buf).unwrap();
+            let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+            let status = if head.starts_with("post") || head.contains("authorization: bearer abc") {
+                "200 OK"
+            } else {
+                "401 Unauthorized"
+            };
+            let resp = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(resp.as_bytes()).unwrap();
+        }
+    });
+
+    let dir = test_collection(
+        "async",
+        &[
+            ("login.reqq", "POST http://127.0.0.1:{{ port }}/login"),
+            ("me.reqq", "@depends login\nGET http://127.0.0.1:{{ port }}/me\nAuthorization: Bearer {{ login.body.token }}"),
+        ],
+    );
+    let client = ReqqClient::new(&dir).unwrap();
+    let args = HashMap::from([("port".to_owned(), Value::from(port))]);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let resp = runtime
+        .block_on(client.send_async("me", None, args))
+        .unwrap();
+    assert_eq!(resp.status, 200);
+    assert_eq!(resp.json::<Value>().unwrap()["name"], "Ada");
+
+    server.join().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
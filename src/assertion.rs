@@ -0,0 +1,366 @@
+use crate::{jsonpath, response::Response};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::fmt;
+
+/// An expectation about a response, declared in a request file with an `@assert` directive:
+/// `@assert <target> <op> [expected]`.
+///
+/// ```text
+/// @assert status == 201
+/// @assert header content-type contains json
+/// @assert $.data.id exists
+/// @assert $.data.items[0].name == "first"
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assertion {
+    target: Target,
+    op: Op,
+    expected: Option<Value>,
+    /// The assertion as written, for reports.
+    source: String,
+}
+
+/// The part of a response an assertion checks.
+#[derive(Clone, Debug, PartialEq)]
+enum Target {
+    Status,
+    Header(String),
+    Body,
+    /// A JSONPath expression evaluated against the JSON body.
+    Json(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    Matches,
+    Exists,
+}
+
+/// The outcome of evaluating an assertion against a response.
+#[derive(Clone, Debug)]
+pub struct AssertionResult {
+    pub assertion: String,
+    pub passed: bool,
+    /// What the assertion expected, for failure reports.
+    pub expected: String,
+    /// What the response actually had, for failure reports.
+    pub actual: String,
+}
+
+/// The outcomes of all assertions of a request.
+#[derive(Clone, Debug, Default)]
+pub struct CheckReport {
+    pub results: Vec<AssertionResult>,
+}
+
+impl Assertion {
+    /// Parses the value of an `@assert` directive.
+    pub fn parse(value: &str) -> Result<Assertion> {
+        let invalid = |reason: &str| anyhow!("Invalid assertion '{}': {}.", value, reason);
+
+        let (target, rest) = match next_token(value) {
+            ("status", rest) => (Target::Status, rest),
+            ("body", rest) => (Target::Body, rest),
+            ("header", rest) => match next_token(rest) {
+                ("", _) => return Err(invalid("expected a header name")),
+                (name, rest) => (Target::Header(name.to_lowercase()), rest),
+            },
+            (path, rest) if path.starts_with('$') => {
+                jsonpath::query(path, &Value::Null).map_err(|e| invalid(&e.to_string()))?;
+                (Target::Json(path.to_owned()), rest)
+            }
+            _ => {
+                return Err(invalid(
+                    "expected status, header <name>, body or a JSONPath",
+                ))
+            }
+        };
+
+        let (op, rest) = next_token(rest);
+        let op = match op {
+            "==" => Op::Eq,
+            "!=" => Op::Ne,
+            "<" => Op::Lt,
+            "<=" => Op::Le,
+            ">" => Op::Gt,
+            ">=" => Op::Ge,
+            "contains" => Op::Contains,
+            "matches" => Op::Matches,
+            "exists" => Op::Exists,
+            _ => return Err(invalid("expected an operator like ==, contains or exists")),
+        };
+
+        let expected = match (op, rest.trim()) {
+            (Op::Exists, "") => None,
+            (Op::Exists, _) => return Err(invalid("exists doesn't take a value")),
+            (_, "") => return Err(invalid("expected a value to compare with")),
+            (Op::Matches, pattern) => {
+                Regex::new(pattern).map_err(|e| invalid(&e.to_string()))?;
+                Some(Value::String(pattern.to_owned()))
+            }
+            (_, raw) => {
+                Some(serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_owned())))
+            }
+        };
+
+        Ok(Assertion {
+            target,
+            op,
+            expected,
+            source: value.trim().to_owned(),
+        })
+    }
+
+    pub fn evaluate(&self, resp: &Response) -> AssertionResult {
+        let actual = self.actual(resp);
+        let passed = match (&actual, &self.expected) {
+            (actual, _) if self.op == Op::Exists => actual.is_some(),
+            (Some(actual), Some(expected)) => compare(self.op, actual, expected),
+            _ => false,
+        };
+
+        AssertionResult {
+            assertion: self.source.clone(),
+            passed,
+            expected: match &self.expected {
+                Some(expected) => format!("{} {}", self.op, render(expected)),
+                None => "exists".to_owned(),
+            },
+            actual: actual.as_ref().map_or("nothing".to_owned(), render),
+        }
+    }
+
+    fn actual(&self, resp: &Response) -> Option<Value> {
+        match &self.target {
+            Target::Status => Some(Value::from(resp.status.as_u16())),
+            Target::Header(name) => {
+                let values: Vec<String> = resp
+                    .headers
+                    .get_all(name.as_str())
+                    .iter()
+                    .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                    .collect();
+                (!values.is_empty()).then(|| Value::String(values.join(", ")))
+            }
+            Target::Body => Some(Value::String(resp.body.clone())),
+            Target::Json(path) => {
+                let body: Value = serde_json::from_str(&resp.body).ok()?;
+                jsonpath::extract(path, &body).ok()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Contains => "contains",
+            Op::Matches => "matches",
+            Op::Exists => "exists",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl CheckReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.iter().filter(|r| !r.passed).count()
+    }
+
+    /// One line per assertion, with the expected and actual values of failed ones, followed
+    /// by a summary line.
+    pub fn report(&self) -> String {
+        let mut lines = vec![];
+        for result in self.results.iter() {
+            if result.passed {
+                lines.push(format!("PASS  {}", result.assertion));
+            } else {
+                lines.push(format!("FAIL  {}", result.assertion));
+                lines.push(format!("      - expected: {}", result.expected));
+                lines.push(format!("      + actual:   {}", result.actual));
+            }
+        }
+        lines.push(format!(
+            "{} assertions, {} failed.",
+            self.results.len(),
+            self.failed()
+        ));
+        lines.join("\n")
+    }
+}
+
+/// Splits off the first whitespace separated token. Brackets in JSONPath expressions can
+/// contain whitespace, like `$['a key']`.
+fn next_token(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            c if c.is_whitespace() && depth <= 0 => return (&s[..i], &s[i..]),
+            _ => {}
+        }
+    }
+    (s, "")
+}
+
+fn compare(op: Op, actual: &Value, expected: &Value) -> bool {
+    match op {
+        Op::Eq => loosely_equal(actual, expected),
+        Op::Ne => !loosely_equal(actual, expected),
+        Op::Lt | Op::Le | Op::Gt | Op::Ge => match (as_number(actual), as_number(expected)) {
+            (Some(a), Some(e)) => match op {
+                Op::Lt => a < e,
+                Op::Le => a <= e,
+                Op::Gt => a > e,
+                _ => a >= e,
+            },
+            _ => false,
+        },
+        Op::Contains => match actual {
+            Value::Array(items) => items.iter().any(|item| loosely_equal(item, expected)),
+            Value::Object(map) => map.contains_key(&render(expected)),
+            _ => render(actual).contains(&render(expected)),
+        },
+        Op::Matches => Regex::new(&render(expected)).is_ok_and(|re| re.is_match(&render(actual))),
+        Op::Exists => true,
+    }
+}
+
+/// Values are equal if they're the same JSON, or if either is a string and they render the
+/// same, since header values are always strings.
+fn loosely_equal(actual: &Value, expected: &Value) -> bool {
+    actual == expected
+        || ((actual.is_string() || expected.is_string()) && render(actual) == render(expected))
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn render(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+fn test_response() -> Response {
+    let mut headers = http::HeaderMap::new();
+    headers.insert(
+        "content-type",
+        "application/json; charset=utf-8".parse().unwrap(),
+    );
+    headers.insert("x-total", "42".parse().unwrap());
+    Response {
+        status: reqwest::StatusCode::CREATED,
+        version: reqwest::Version::HTTP_11,
+        headers,
+        body: r#"{"data": {"id": 7, "name": "Ada Lovelace", "tags": ["a", "b"]}}"#.to_owned(),
+        binary: None,
+    }
+}
+
+#[test]
+fn test_assertions_pass() {
+    let resp = test_response();
+    for source in [
+        "status == 201",
+        "status >= 200",
+        "status < 300",
+        "status != 200",
+        "header content-type contains json",
+        "header Content-Type matches ^application/json",
+        "header x-total == 42",
+        "header x-total > 40",
+        "body contains Ada",
+        "$.data.id == 7",
+        "$.data.id exists",
+        "$.data.name == \"Ada Lovelace\"",
+        "$.data.name == Ada Lovelace",
+        "$.data.tags contains b",
+        "$.data contains name",
+        "$['data']['tags'] == [\"a\", \"b\"]",
+    ] {
+        let result = Assertion::parse(source).unwrap().evaluate(&resp);
+        assert!(result.passed, "{} failed, actual {}", source, result.actual);
+    }
+}
+
+#[test]
+fn test_assertions_fail() {
+    let resp = test_response();
+    for source in [
+        "status == 200",
+        "header x-missing exists",
+        "header x-missing == 1",
+        "$.data.nope exists",
+        "$.data.name < 5",
+        "$.data.tags contains c",
+    ] {
+        let result = Assertion::parse(source).unwrap().evaluate(&resp);
+        assert!(!result.passed, "{} passed", source);
+    }
+}
+
+#[test]
+fn test_assertion_parse_errors() {
+    for source in [
+        "",
+        "stat == 200",
+        "status",
+        "status is 200",
+        "status ==",
+        "header",
+        "$.data exists 1",
+        "body matches (",
+        "$.data[ exists",
+    ] {
+        assert!(Assertion::parse(source).is_err(), "'{}' parsed", source);
+    }
+}
+
+#[test]
+fn test_check_report() {
+    let resp = test_response();
+    let report = CheckReport {
+        results: ["status == 201", "$.data.id == 8"]
+            .iter()
+            .map(|source| Assertion::parse(source).unwrap().evaluate(&resp))
+            .collect(),
+    };
+    assert!(!report.passed());
+    assert_eq!(
+        report.report(),
+        "PASS  status == 201
+FAIL  $.data.id == 8
+      - expected: == 8
+      + actual:   7
+2 assertions, 1 failed."
+    );
+}
@@ -0,0 +1,371 @@
+use crate::{
+    sigv4::{self, AwsCredentials},
+    time, yaml,
+};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// Credentials a request is sent with, from an `@auth` directive or the `auth` variable of
+/// the environment.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Auth {
+    /// HTTP Basic authentication, Base64 encoded into the `Authorization` header.
+    Basic { user: String, pass: String },
+    /// A token sent as `Authorization: Bearer <token>`.
+    Bearer { token: String },
+    /// An API key sent as a header or a query parameter.
+    ApiKey {
+        name: String,
+        value: String,
+        location: KeyLocation,
+    },
+    /// A Bearer token fetched from an OAuth2 token endpoint, and cached until it expires.
+    OAuth2(OAuth2),
+    /// AWS Signature Version 4, computed over the final request.
+    Aws(AwsCredentials),
+    /// No authentication, to opt a request out of the environment's `auth`.
+    None,
+}
+
+/// Settings for fetching access tokens from an OAuth2 token endpoint, with the client
+/// credentials grant, or the refresh token grant when a refresh token is given.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OAuth2 {
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scope: Option<String>,
+    pub refresh_token: Option<String>,
+}
+
+/// Where an API key is sent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyLocation {
+    Header,
+    Query,
+}
+
+impl Auth {
+    /// Parses the value of an `@auth` directive, either in short form, like `basic <user>
+    /// <pass>`, `bearer <token>`, `apikey <header> <key>` or `none`, or as a mapping like
+    /// `{ type: basic, user: alice, pass: secret }`.
+    pub fn parse(value: &str) -> Result<Self> {
+        if value.starts_with('{') {
+            let mapping = yaml::parse(value).map_err(|e| anyhow!("Invalid @auth: {}", e))?;
+            return Auth::from_value(&mapping);
+        }
+
+        let parts: Vec<&str> = value.split_whitespace().collect();
+        match parts[..] {
+            ["basic", user] => Ok(basic(user, "")),
+            ["basic", user, pass] => Ok(basic(user, pass)),
+            ["bearer", token] => Ok(Auth::Bearer {
+                token: token.to_owned(),
+            }),
+            ["apikey", name, value] => Ok(api_key(name, value, KeyLocation::Header)),
+            ["apikey", "header", name, value] => Ok(api_key(name, value, KeyLocation::Header)),
+            ["apikey", "query", name, value] => Ok(api_key(name, value, KeyLocation::Query)),
+            ["none"] => Ok(Auth::None),
+            _ => Err(anyhow!(
+                "Invalid @auth '{}', expected e.g. 'basic <user> <pass>', 'bearer <token>' or \
+                 'apikey <header> <key>'.",
+                value
+            )),
+        }
+    }
+
+    /// Reads an auth mapping with a `type` of `basic` (with `user` and `pass`), `bearer` (with
+    /// `token`), `apikey` (with `name`, `value` and optionally `in: query`), `oauth2` (with
+    /// `token_url`, `client_id`, `client_secret` and optionally `scope` and `refresh_token`),
+    /// `aws` (with `access_key`, `secret_key`, `region`, `service` and optionally
+    /// `session_token`) or `none`.
+    pub fn from_value(value: &Value) -> Result<Self> {
+        let field = |key: &str| -> Result<String> {
+            match &value[key] {
+                Value::String(s) => Ok(s.clone()),
+                Value::Null => Err(anyhow!(
+                    "Auth of type '{}' needs a '{}'.",
+                    value["type"].as_str().unwrap_or_default(),
+                    key
+                )),
+                other => Ok(other.to_string()),
+            }
+        };
+
+        match value["type"].as_str() {
+            Some("basic") => Ok(basic(
+                &field("user")?,
+                value["pass"].as_str().unwrap_or_default(),
+            )),
+            Some("bearer") => Ok(Auth::Bearer {
+                token: field("token")?,
+            }),
+            Some("apikey") => {
+                let location = match value["in"].as_str() {
+                    None | Some("header") => KeyLocation::Header,
+                    Some("query") => KeyLocation::Query,
+                    Some(other) => {
+                        return Err(anyhow!(
+                            "API keys go in a header or the query, not '{}'.",
+                            other
+                        ))
+                    }
+                };
+                Ok(api_key(&field("name")?, &field("value")?, location))
+            }
+            Some("oauth2") => Ok(Auth::OAuth2(OAuth2 {
+                token_url: field("token_url")?,
+                client_id: field("client_id")?,
+                client_secret: field("client_secret")?,
+                scope: value["scope"].as_str().map(str::to_owned),
+                refresh_token: value["refresh_token"].as_str().map(str::to_owned),
+            })),
+            Some("aws") => Ok(Auth::Aws(AwsCredentials {
+                access_key: field("access_key")?,
+                secret_key: field("secret_key")?,
+                session_token: value["session_token"]
+                    .as_str()
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_owned),
+                region: field("region")?,
+                service: field("service")?,
+            })),
+            Some("none") => Ok(Auth::None),
+            Some(other) => Err(anyhow!(
+                "Unknown auth type '{}', expected basic, bearer, apikey, oauth2, aws or none.",
+                other
+            )),
+            None => Err(anyhow!(
+                "Auth needs a 'type': basic, bearer, apikey, oauth2, aws or none."
+            )),
+        }
+    }
+
+    /// Whether the credentials go in the `Authorization` header.
+    pub fn uses_authorization(&self) -> bool {
+        matches!(
+            self,
+            Auth::Basic { .. } | Auth::Bearer { .. } | Auth::OAuth2(_) | Auth::Aws(_)
+        )
+    }
+
+    /// Adds the credentials to a request. OAuth2 tokens have to be fetched first, so OAuth2
+    /// auth is replaced with a Bearer token before requests are sent.
+    pub fn apply<B: Authorize>(&self, req: B) -> Result<B> {
+        Ok(match self {
+            Auth::Basic { user, pass } => req.basic(user, pass),
+            Auth::Bearer { token } => req.bearer(token),
+            Auth::ApiKey {
+                name,
+                value,
+                location: KeyLocation::Header,
+            } => req.add_header(name, value),
+            Auth::ApiKey {
+                name,
+                value,
+                location: KeyLocation::Query,
+            } => req.add_query(name, value),
+            Auth::Aws(creds) => req.sign_aws(creds, time::now_millis())?,
+            Auth::OAuth2(_) | Auth::None => req,
+        })
+    }
+}
+
+/// The request builders credentials can be added to: reqwest's blocking one, and its async one
+/// with the `async` feature.
+pub trait Authorize: Sized {
+    fn basic(self, user: &str, pass: &str) -> Self;
+    fn bearer(self, token: &str) -> Self;
+    fn add_header(self, name: &str, value: &str) -> Self;
+    fn add_query(self, name: &str, value: &str) -> Self;
+    /// Adds the headers of an AWS SigV4 signature, which covers the whole request.
+    fn sign_aws(self, creds: &AwsCredentials, now_millis: u64) -> Result<Self>;
+}
+
+/// Both builders have the same methods, but no trait in common.
+macro_rules! impl_authorize {
+    ($builder:ty) => {
+        impl Authorize for $builder {
+            fn basic(self, user: &str, pass: &str) -> Self {
+                self.basic_auth(user, Some(pass))
+            }
+
+            fn bearer(self, token: &str) -> Self {
+                self.bearer_auth(token)
+            }
+
+            fn add_header(self, name: &str, value: &str) -> Self {
+                self.header(name, value)
+            }
+
+            fn add_query(self, name: &str, value: &str) -> Self {
+                self.query(&[(name, value)])
+            }
+
+            fn sign_aws(self, creds: &AwsCredentials, now_millis: u64) -> Result<Self> {
+                let built = self
+                    .try_clone()
+                    .ok_or_else(|| anyhow!("Streamed requests can't be signed."))?
+                    .build()?;
+                let body = match built.body() {
+                    Some(body) => body
+                        .as_bytes()
+                        .ok_or_else(|| anyhow!("Streamed request bodies can't be signed."))?,
+                    None => &[],
+                };
+                Ok(sigv4::sign(
+                    creds,
+                    built.method(),
+                    built.url(),
+                    built.headers(),
+                    body,
+                    now_millis,
+                )?
+                .into_iter()
+                .fold(self, |req, (name, value)| req.header(name, value)))
+            }
+        }
+    };
+}
+
+impl_authorize!(reqwest::blocking::RequestBuilder);
+#[cfg(feature = "async")]
+impl_authorize!(reqwest::RequestBuilder);
+
+/// Writes an auth mapping as the value of an `@auth` directive: in short form when there is
+/// one and the values have no whitespace, otherwise as a mapping.
+pub fn directive(mapping: &Value) -> String {
+    let field = |key: &str| {
+        mapping[key]
+            .as_str()
+            .filter(|v| !v.is_empty() && !v.contains(char::is_whitespace))
+    };
+    let short = match mapping["type"].as_str() {
+        Some("basic") => match (field("user"), field("pass"), mapping["pass"].as_str()) {
+            (Some(user), _, None | Some("")) => Some(format!("basic {}", user)),
+            (Some(user), Some(pass), _) => Some(format!("basic {} {}", user, pass)),
+            _ => None,
+        },
+        Some("bearer") => field("token").map(|token| format!("bearer {}", token)),
+        Some("apikey") => match (field("name"), field("value"), mapping["in"].as_str()) {
+            (Some(name), Some(value), None | Some("header")) => {
+                Some(format!("apikey header {} {}", name, value))
+            }
+            (Some(name), Some(value), Some("query")) => {
+                Some(format!("apikey query {} {}", name, value))
+            }
+            _ => None,
+        },
+        Some("none") => Some("none".to_owned()),
+        _ => None,
+    };
+    short.unwrap_or_else(|| mapping.to_string())
+}
+
+fn basic(user: &str, pass: &str) -> Auth {
+    Auth::Basic {
+        user: user.to_owned(),
+        pass: pass.to_owned(),
+    }
+}
+
+fn api_key(name: &str, value: &str, location: KeyLocation) -> Auth {
+    Auth::ApiKey {
+        name: name.to_owned(),
+        value: value.to_owned(),
+        location,
+    }
+}
+
+#[test]
+fn test_auth_parse() {
+    assert_eq!(
+        Auth::parse("basic alice secret").unwrap(),
+        basic("alice", "secret")
+    );
+    assert_eq!(
+        Auth::parse("bearer abc").unwrap(),
+        Auth::Bearer {
+            token: "abc".to_owned()
+        }
+    );
+    assert_eq!(
+        Auth::parse("apikey X-Api-Key abc").unwrap(),
+        api_key("X-Api-Key", "abc", KeyLocation::Header)
+    );
+    assert_eq!(
+        Auth::parse("apikey query api_key abc").unwrap(),
+        api_key("api_key", "abc", KeyLocation::Query)
+    );
+    assert_eq!(Auth::parse("none").unwrap(), Auth::None);
+    assert_eq!(
+        Auth::parse("{ type: basic, user: alice, pass: \"s3cr3t pass\" }").unwrap(),
+        basic("alice", "s3cr3t pass")
+    );
+    assert!(Auth::parse("digest alice secret").is_err());
+    assert!(Auth::parse("bearer").is_err());
+}
+
+#[test]
+fn test_auth_from_value() {
+    let auth = Auth::from_value(
+        &serde_json::json!({ "type": "apikey", "name": "key", "value": 42, "in": "query" }),
+    );
+    assert_eq!(auth.unwrap(), api_key("key", "42", KeyLocation::Query));
+
+    let err = Auth::from_value(&serde_json::json!({ "type": "bearer" })).unwrap_err();
+    assert_eq!(err.to_string(), "Auth of type 'bearer' needs a 'token'.");
+    assert!(Auth::from_value(&serde_json::json!({ "user": "alice" })).is_err());
+}
+
+#[test]
+fn test_auth_apply() {
+    let client = reqwest::blocking::Client::new();
+    let req = basic("alice", "secret")
+        .apply(client.get("https://example.com/"))
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(req.headers()["authorization"], "Basic YWxpY2U6c2VjcmV0");
+
+    let req = Auth::Bearer {
+        token: "abc".to_owned(),
+    }
+    .apply(client.get("https://example.com/"))
+    .unwrap()
+    .build()
+    .unwrap();
+    assert_eq!(req.headers()["authorization"], "Bearer abc");
+
+    let key = api_key("api_key", "a b", KeyLocation::Query);
+    let req = key
+        .apply(client.get("https://example.com/?page=2"))
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(
+        req.url().as_str(),
+        "https://example.com/?page=2&api_key=a+b"
+    );
+}
+
+#[test]
+fn test_auth_directive() {
+    let roundtrip = |mapping: Value| Auth::parse(&directive(&mapping)).unwrap();
+
+    let mapping = serde_json::json!({ "type": "basic", "user": "alice", "pass": "secret" });
+    assert_eq!(directive(&mapping), "basic alice secret");
+    assert_eq!(roundtrip(mapping), basic("alice", "secret"));
+
+    let mapping = serde_json::json!({ "type": "basic", "user": "alice", "pass": "two words" });
+    assert_eq!(
+        directive(&mapping),
+        r#"{"pass":"two words","type":"basic","user":"alice"}"#
+    );
+    assert_eq!(roundtrip(mapping), basic("alice", "two words"));
+
+    let mapping =
+        serde_json::json!({ "type": "apikey", "name": "key", "value": "k1", "in": "query" });
+    assert_eq!(directive(&mapping), "apikey query key k1");
+    assert_eq!(roundtrip(mapping), api_key("key", "k1", KeyLocation::Query));
+}
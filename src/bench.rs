@@ -0,0 +1,168 @@
+//! Sending a request many times and summarizing how it went: latency percentiles, throughput
+//! and errors.
+
+use reqwest::StatusCode;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// The outcome of sending the request once.
+#[derive(Clone, Debug)]
+pub enum Sample {
+    /// A response was received, after this long.
+    Response {
+        status: StatusCode,
+        latency: Duration,
+    },
+    /// The request couldn't be sent, or its response couldn't be read.
+    Error(String),
+}
+
+/// The outcome of a benchmark.
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub concurrency: usize,
+    /// How long sending all the requests took.
+    pub elapsed: Duration,
+    pub samples: Vec<Sample>,
+}
+
+impl BenchResult {
+    /// The latencies of the responses received, fastest first.
+    fn latencies(&self) -> Vec<Duration> {
+        let mut latencies: Vec<Duration> = self
+            .samples
+            .iter()
+            .filter_map(|s| match s {
+                Sample::Response { latency, .. } => Some(*latency),
+                Sample::Error(_) => None,
+            })
+            .collect();
+        latencies.sort();
+        latencies
+    }
+
+    /// Requests that failed: those that couldn't be sent, and responses with a 4xx or 5xx
+    /// status.
+    pub fn errors(&self) -> usize {
+        self.samples
+            .iter()
+            .filter(|s| match s {
+                Sample::Response { status, .. } => {
+                    status.is_client_error() || status.is_server_error()
+                }
+                Sample::Error(_) => true,
+            })
+            .count()
+    }
+
+    /// Requests completed per second.
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.samples.len() as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let mut lines = vec![format!(
+            "{} requests, {} at a time, in {:.2}s",
+            self.samples.len(),
+            self.concurrency,
+            self.elapsed.as_secs_f64()
+        )];
+        lines.push(format!("Throughput: {:.1} requests/s", self.throughput()));
+
+        let latencies = self.latencies();
+        if let (Some(min), Some(max)) = (latencies.first(), latencies.last()) {
+            let stats: Vec<String> = [("p50", 50.0), ("p95", 95.0), ("p99", 99.0)]
+                .iter()
+                .map(|(label, p)| format!("{} {}", label, millis(percentile(&latencies, *p))))
+                .collect();
+            lines.push(format!(
+                "Latency:    min {}, {}, max {}",
+                millis(*min),
+                stats.join(", "),
+                millis(*max)
+            ));
+        }
+
+        let mut statuses: BTreeMap<StatusCode, usize> = BTreeMap::new();
+        for sample in self.samples.iter() {
+            if let Sample::Response { status, .. } = sample {
+                *statuses.entry(*status).or_default() += 1;
+            }
+        }
+        if !statuses.is_empty() {
+            let statuses: Vec<String> = statuses
+                .iter()
+                .map(|(status, count)| format!("{} x {}", count, status))
+                .collect();
+            lines.push(format!("Statuses:   {}", statuses.join(", ")));
+        }
+
+        let failed: Vec<&String> = self
+            .samples
+            .iter()
+            .filter_map(|s| match s {
+                Sample::Error(error) => Some(error),
+                Sample::Response { .. } => None,
+            })
+            .collect();
+        lines.push(format!("Errors:     {}", self.errors()));
+        if let Some(first) = failed.first() {
+            lines.push(format!(
+                "            {} failed to send, like: {}",
+                failed.len(),
+                first
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+/// The nearest-rank percentile of sorted values, which must not be empty.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[test]
+fn test_percentile() {
+    let values: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+    assert_eq!(percentile(&values, 50.0), Duration::from_millis(50));
+    assert_eq!(percentile(&values, 99.0), Duration::from_millis(99));
+    assert_eq!(percentile(&values[..1], 95.0), Duration::from_millis(1));
+    assert_eq!(percentile(&values[..3], 0.0), Duration::from_millis(1));
+}
+
+#[test]
+fn test_bench_report() {
+    let response = |status: u16, ms: u64| Sample::Response {
+        status: StatusCode::from_u16(status).unwrap(),
+        latency: Duration::from_millis(ms),
+    };
+    let result = BenchResult {
+        concurrency: 2,
+        elapsed: Duration::from_millis(500),
+        samples: vec![
+            response(200, 10),
+            response(200, 20),
+            response(503, 30),
+            Sample::Error("refused".to_owned()),
+        ],
+    };
+    assert_eq!(result.errors(), 2);
+    assert_eq!(
+        result.report(),
+        "4 requests, 2 at a time, in 0.50s
+Throughput: 8.0 requests/s
+Latency:    min 10.0 ms, p50 20.0 ms, p95 30.0 ms, p99 30.0 ms, max 30.0 ms
+Statuses:   2 x 200 OK, 1 x 503 Service Unavailable
+Errors:     2
+            1 failed to send, like: refused"
+    );
+}
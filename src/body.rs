@@ -0,0 +1,510 @@
+use crate::helpers;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+/// The body of a request, as given in the request file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Body {
+    /// Text sent as it is.
+    Text(String),
+    /// `key=value` lines sent URL-encoded, from `@form`.
+    Form(Vec<(String, String)>),
+    /// `name=value` and `name=@path` lines sent as `multipart/form-data`, from `@multipart`.
+    Multipart(Vec<Part>),
+    /// A file sent as it is, from `@body-file`, relative to the request file.
+    File(PathBuf),
+    /// A query and its variables sent in a JSON envelope, from `@graphql`.
+    GraphQL {
+        query: String,
+        variables: Option<Map<String, Value>>,
+    },
+}
+
+/// How the body of a request file is sent, set with the `@form` and `@multipart` directives.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BodyFormat {
+    #[default]
+    Text,
+    Form,
+    Multipart,
+    GraphQL,
+}
+
+/// A field of a multipart body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Part {
+    pub name: String,
+    pub value: PartValue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PartValue {
+    Text(String),
+    /// A file to upload, relative to the request file.
+    File(PathBuf),
+}
+
+/// A body ready to be sent, with the content type it needs, if any.
+pub struct EncodedBody {
+    pub content_type: Option<String>,
+    pub bytes: Vec<u8>,
+}
+
+impl Body {
+    /// Parses `key=value` lines. Blank lines are skipped.
+    pub fn parse_form(text: &str) -> Result<Self> {
+        let fields = key_value_lines(text, "@form")?
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect();
+        Ok(Body::Form(fields))
+    }
+
+    /// Parses `name=value` and `name=@path` lines, with paths relative to `base_dir`. Blank
+    /// lines are skipped.
+    pub fn parse_multipart(text: &str, base_dir: &Path) -> Result<Self> {
+        let parts = key_value_lines(text, "@multipart")?
+            .into_iter()
+            .map(|(name, value)| Part {
+                name: name.to_owned(),
+                value: match value.strip_prefix('@') {
+                    Some(path) => PartValue::File(base_dir.join(path)),
+                    None => PartValue::Text(value.to_owned()),
+                },
+            })
+            .collect();
+        Ok(Body::Multipart(parts))
+    }
+
+    /// Parses a GraphQL query, optionally followed by a blank line and a JSON object of
+    /// variables.
+    pub fn parse_graphql(text: &str) -> Result<Self> {
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            offset += line.len();
+            if !line.trim().is_empty() || text[..offset].trim().is_empty() {
+                continue;
+            }
+            let rest = text[offset..].trim();
+            if !rest.starts_with('{') {
+                continue;
+            }
+            // A blank line followed by `{` could also be part of the query, so only valid
+            // JSON counts as variables.
+            if let Ok(Value::Object(variables)) = serde_json::from_str(rest) {
+                return Ok(Body::GraphQL {
+                    query: text[..offset].trim().to_owned(),
+                    variables: Some(variables),
+                });
+            }
+        }
+
+        match text.trim() {
+            "" => Err(anyhow!("@graphql needs a query.")),
+            query => Ok(Body::GraphQL {
+                query: query.to_owned(),
+                variables: None,
+            }),
+        }
+    }
+
+    /// Sets the given GraphQL variables, replacing those of the same name. Other bodies are
+    /// left as they are.
+    pub fn override_variables(&mut self, overrides: &HashMap<String, Value>) {
+        if let (Body::GraphQL { variables, .. }, false) = (self, overrides.is_empty()) {
+            let variables = variables.get_or_insert_with(Map::new);
+            for (name, value) in overrides {
+                variables.insert(name.clone(), value.clone());
+            }
+        }
+    }
+
+    /// The files the body is read from: a `@body-file`, or the file parts of a multipart body.
+    pub fn files(&self) -> Vec<&Path> {
+        match self {
+            Body::File(path) => vec![path.as_path()],
+            Body::Multipart(parts) => parts
+                .iter()
+                .filter_map(|part| match &part.value {
+                    PartValue::File(path) => Some(path.as_path()),
+                    PartValue::Text(_) => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Converts the body into one reqwest can send. With `stream`, `@body-file` bodies are
+    /// streamed from disk rather than read into memory first.
+    pub fn to_reqwest(&self, stream: bool) -> Result<(Option<String>, reqwest::blocking::Body)> {
+        match self {
+            Body::File(path) if stream => {
+                let file = File::open(path)
+                    .map_err(|e| anyhow!("Couldn't read body file '{}': {}", path.display(), e))?;
+                Ok((None, file.into()))
+            }
+            _ => {
+                let encoded = self.encode()?;
+                Ok((encoded.content_type, encoded.bytes.into()))
+            }
+        }
+    }
+
+    /// A content type for bodies that don't come with one: from the extension of a body file,
+    /// or for text, JSON, XML or HTML, and form data when it's a single `key=value&...` line.
+    /// Other text is sent as plain text.
+    pub fn inferred_content_type(&self) -> Option<&'static str> {
+        let text = match self {
+            Body::File(path) => return Some(content_type(path)),
+            Body::Text(text) => text.trim(),
+            _ => return None,
+        };
+        if text.is_empty() {
+            return None;
+        }
+        let lower = text.to_ascii_lowercase();
+        Some(
+            if (text.starts_with('{') || text.starts_with('['))
+                && serde_json::from_str::<Value>(text).is_ok()
+            {
+                "application/json"
+            } else if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+                "text/html"
+            } else if text.starts_with('<') && text.ends_with('>') {
+                "application/xml"
+            } else if is_form_data(text) {
+                "application/x-www-form-urlencoded"
+            } else {
+                "text/plain; charset=utf-8"
+            },
+        )
+    }
+
+    /// The size of the body as it's sent, in bytes.
+    pub fn size(&self) -> Result<u64> {
+        match self {
+            Body::File(path) => Ok(fs::metadata(path)
+                .map_err(|e| anyhow!("Couldn't read body file '{}': {}", path.display(), e))?
+                .len()),
+            _ => Ok(self.encode()?.bytes.len() as u64),
+        }
+    }
+
+    /// Encodes the body for sending, reading any files it refers to.
+    pub fn encode(&self) -> Result<EncodedBody> {
+        match self {
+            Body::File(path) => Ok(EncodedBody {
+                content_type: None,
+                bytes: fs::read(path)
+                    .map_err(|e| anyhow!("Couldn't read body file '{}': {}", path.display(), e))?,
+            }),
+            Body::Text(text) => Ok(EncodedBody {
+                content_type: None,
+                bytes: text.clone().into_bytes(),
+            }),
+            Body::Form(fields) => Ok(EncodedBody {
+                content_type: Some("application/x-www-form-urlencoded".to_owned()),
+                bytes: serde_urlencoded::to_string(fields)?.into_bytes(),
+            }),
+            Body::Multipart(parts) => {
+                let boundary = format!("reqq-{:016x}", helpers::random_u64());
+                Ok(EncodedBody {
+                    content_type: Some(format!("multipart/form-data; boundary={}", boundary)),
+                    bytes: encode_multipart(parts, &boundary)?,
+                })
+            }
+            Body::GraphQL { query, variables } => {
+                let mut envelope = json!({ "query": query });
+                if let Some(variables) = variables {
+                    envelope["variables"] = Value::Object(variables.clone());
+                }
+                Ok(EncodedBody {
+                    content_type: Some("application/json".to_owned()),
+                    bytes: serde_json::to_vec(&envelope)?,
+                })
+            }
+        }
+    }
+}
+
+/// Writes the body the way it's written in the request file, or `<path>` for a body file.
+impl fmt::Display for Body {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Body::Text(text) => write!(f, "{}", text),
+            Body::Form(fields) => {
+                let lines: Vec<String> =
+                    fields.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Body::Multipart(parts) => {
+                let lines: Vec<String> = parts
+                    .iter()
+                    .map(|part| match &part.value {
+                        PartValue::Text(value) => format!("{}={}", part.name, value),
+                        PartValue::File(path) => format!("{}=@{}", part.name, path.display()),
+                    })
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Body::File(path) => write!(f, "<{}>", path.display()),
+            Body::GraphQL {
+                query,
+                variables: None,
+            } => write!(f, "{}", query),
+            Body::GraphQL {
+                query,
+                variables: Some(variables),
+            } => {
+                let variables = serde_json::to_string_pretty(variables).map_err(|_| fmt::Error)?;
+                write!(f, "{}\n\n{}", query, variables)
+            }
+        }
+    }
+}
+
+fn key_value_lines<'a>(text: &'a str, directive: &str) -> Result<Vec<(&'a str, &'a str)>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Expected 'key=value' lines with {}, got '{}'.",
+                        directive,
+                        line
+                    )
+                })
+        })
+        .collect()
+}
+
+fn encode_multipart(parts: &[Part], boundary: &str) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for part in parts {
+        bytes.extend(format!("--{}\r\n", boundary).into_bytes());
+        let name = part.name.replace('"', "%22");
+        match &part.value {
+            PartValue::Text(value) => {
+                bytes.extend(
+                    format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name)
+                        .into_bytes(),
+                );
+                bytes.extend(value.as_bytes());
+            }
+            PartValue::File(path) => {
+                let contents = fs::read(path).map_err(|e| {
+                    anyhow!(
+                        "Couldn't read '{}' for part '{}': {}",
+                        path.display(),
+                        part.name,
+                        e
+                    )
+                })?;
+                let filename = path
+                    .file_name()
+                    .map(|f| f.to_string_lossy().replace('"', "%22"))
+                    .unwrap_or_default();
+                bytes.extend(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                         Content-Type: {}\r\n\r\n",
+                        name,
+                        filename,
+                        content_type(path)
+                    )
+                    .into_bytes(),
+                );
+                bytes.extend(contents);
+            }
+        }
+        bytes.extend(b"\r\n");
+    }
+    bytes.extend(format!("--{}--\r\n", boundary).into_bytes());
+    Ok(bytes)
+}
+
+/// Where a JSON text is invalid, if it is: the line and column, 1-based, and serde's message.
+pub fn json_error(text: &str) -> Option<(usize, usize, String)> {
+    let e = serde_json::from_str::<serde::de::IgnoredAny>(text).err()?;
+    // serde adds the position to its message, which is reported separately here.
+    let message = e.to_string();
+    let message = message
+        .rsplit_once(" at line ")
+        .map_or(message.as_str(), |(message, _)| message)
+        .to_owned();
+    Some((e.line().max(1), e.column().max(1), message))
+}
+
+/// Whether text looks like URL-encoded form data: `key=value` pairs joined by `&`, without
+/// whitespace.
+fn is_form_data(text: &str) -> bool {
+    text.split('&').all(|pair| match pair.split_once('=') {
+        Some((key, value)) => {
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_-.%[]".contains(c))
+                && !value.chars().any(|c| c.is_whitespace() || c == '=')
+        }
+        None => false,
+    })
+}
+
+/// Guesses the content type of a file from its extension, for the common cases.
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[test]
+fn test_form_body() {
+    let body = Body::parse_form("name=Ada Lovelace\n\nrole=admin&root\n").unwrap();
+    assert_eq!(body.to_string(), "name=Ada Lovelace\nrole=admin&root");
+
+    let encoded = body.encode().unwrap();
+    assert_eq!(
+        encoded.content_type.as_deref(),
+        Some("application/x-www-form-urlencoded")
+    );
+    assert_eq!(encoded.bytes, b"name=Ada+Lovelace&role=admin%26root");
+
+    assert!(Body::parse_form("just text").is_err());
+}
+
+#[test]
+fn test_multipart_body() {
+    let dir = std::env::temp_dir().join(format!("reqq-body-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("avatar.png"), b"\x89PNG").unwrap();
+
+    let body = Body::parse_multipart("title=Me\navatar=@avatar.png", &dir).unwrap();
+    let encoded = body.encode().unwrap();
+    let content_type = encoded.content_type.unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/form-data; boundary=")
+        .unwrap();
+
+    let mut expected = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nMe\r\n\
+         --{b}\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"avatar.png\"\r\n\
+         Content-Type: image/png\r\n\r\n",
+        b = boundary
+    )
+    .into_bytes();
+    expected.extend(b"\x89PNG");
+    expected.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+    assert_eq!(encoded.bytes, expected);
+
+    let missing = Body::parse_multipart("doc=@missing.pdf", &dir).unwrap();
+    assert!(missing.encode().is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_graphql_body() {
+    let body =
+        Body::parse_graphql("query User($id: ID!) {\n  user(id: $id) { name }\n}\n\n{\"id\": 1}\n")
+            .unwrap();
+    assert_eq!(
+        body,
+        Body::GraphQL {
+            query: "query User($id: ID!) {\n  user(id: $id) { name }\n}".to_owned(),
+            variables: Some(json!({"id": 1}).as_object().unwrap().clone()),
+        }
+    );
+
+    let encoded = body.encode().unwrap();
+    assert_eq!(encoded.content_type.as_deref(), Some("application/json"));
+    let envelope: Value = serde_json::from_slice(&encoded.bytes).unwrap();
+    assert_eq!(envelope["variables"], json!({"id": 1}));
+
+    // Without variables, a blank line followed by `{` is part of the query.
+    let body = Body::parse_graphql("{ users { id } }\n\n{ posts { id } }").unwrap();
+    assert_eq!(body.to_string(), "{ users { id } }\n\n{ posts { id } }");
+    let envelope: Value = serde_json::from_slice(&body.encode().unwrap().bytes).unwrap();
+    assert_eq!(
+        envelope,
+        json!({"query": "{ users { id } }\n\n{ posts { id } }"})
+    );
+
+    assert!(Body::parse_graphql("\n").is_err());
+}
+
+#[test]
+fn test_graphql_override_variables() {
+    let mut body =
+        Body::parse_graphql("query { me { id } }\n\n{\"id\": 1, \"full\": true}").unwrap();
+    body.override_variables(&HashMap::from([("id".to_owned(), json!("42"))]));
+    assert_eq!(
+        body.to_string(),
+        "query { me { id } }\n\n{\n  \"full\": true,\n  \"id\": \"42\"\n}"
+    );
+
+    let mut body = Body::parse_graphql("query { me { id } }").unwrap();
+    body.override_variables(&HashMap::from([("id".to_owned(), json!(7))]));
+    let envelope: Value = serde_json::from_slice(&body.encode().unwrap().bytes).unwrap();
+    assert_eq!(envelope["variables"], json!({"id": 7}));
+}
+
+#[test]
+fn test_inferred_content_type() {
+    let text = |text: &str| Body::Text(text.to_owned()).inferred_content_type();
+    assert_eq!(text("{\"name\": \"ada\"}\n"), Some("application/json"));
+    assert_eq!(text("[1, 2]"), Some("application/json"));
+    assert_eq!(text("{not json"), Some("text/plain; charset=utf-8"));
+    assert_eq!(
+        text("<?xml version=\"1.0\"?><user/>"),
+        Some("application/xml")
+    );
+    assert_eq!(text("<!DOCTYPE html><html></html>"), Some("text/html"));
+    assert_eq!(
+        text("name=ada&role=admin&tags[]=a%20b"),
+        Some("application/x-www-form-urlencoded")
+    );
+    assert_eq!(text("a = b"), Some("text/plain; charset=utf-8"));
+    assert_eq!(text("hello"), Some("text/plain; charset=utf-8"));
+    assert_eq!(text("  \n"), None);
+    assert_eq!(
+        Body::File(PathBuf::from("payload.JSON")).inferred_content_type(),
+        Some("application/json")
+    );
+    assert_eq!(Body::Form(vec![]).inferred_content_type(), None);
+}
+
+#[test]
+fn test_json_error() {
+    assert_eq!(json_error("{\"a\": [1, 2]}"), None);
+    assert_eq!(
+        json_error("{\n  \"name\": Ada,\n}"),
+        Some((2, 11, "expected value".to_owned()))
+    );
+    assert_eq!(
+        json_error("{\"a\": 1"),
+        Some((1, 7, "EOF while parsing an object".to_owned()))
+    );
+}
@@ -0,0 +1,127 @@
+//! Responses cached with `--cache`, and sent again with `If-None-Match` and `If-Modified-Since`
+//! so the server can answer `304 Not Modified` instead of the whole response.
+
+use crate::{history, response::Response};
+use anyhow::{anyhow, Result};
+use openssl::sha::sha256;
+use reqwest::header::{
+    HeaderName, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The folder responses are cached in, relative to the reqq directory.
+pub const FOLDER: &str = "cache";
+
+/// Responses that have an `ETag` or `Last-Modified` header, cached in the `cache` folder of a
+/// reqq directory by their rendered request.
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    pub fn new(reqq_dir: &str) -> Self {
+        ResponseCache {
+            dir: Path::new(reqq_dir).join(FOLDER),
+        }
+    }
+
+    /// The cached response to a rendered request, if there is one.
+    pub fn get(&self, rendered: &str) -> Result<Option<Response>> {
+        let fpath = self.fpath(rendered);
+        let json = match fs::read_to_string(&fpath) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        history::response_from_json(&serde_json::from_str(&json)?)
+            .map(Some)
+            .ok_or_else(|| anyhow!("Malformed cached response '{}'.", fpath.display()))
+    }
+
+    /// Caches the response to a rendered request, if it's a success the server can tell is
+    /// still current. Returns whether it was cached.
+    pub fn store(&self, rendered: &str, resp: &Response) -> Result<bool> {
+        if !resp.status.is_success() || validators(resp).is_empty() {
+            return Ok(false);
+        }
+        fs::create_dir_all(&self.dir)?;
+        fs::write(
+            self.fpath(rendered),
+            serde_json::to_string_pretty(&history::response_to_json(resp))?,
+        )?;
+        Ok(true)
+    }
+
+    /// Removes every cached response.
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn fpath(&self, rendered: &str) -> PathBuf {
+        let hash: String = sha256(rendered.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        self.dir.join(format!("{}.json", hash))
+    }
+}
+
+/// The conditional headers that ask the server whether a cached response is still current.
+pub fn validators(cached: &Response) -> Vec<(HeaderName, HeaderValue)> {
+    let mut headers = vec![];
+    if let Some(etag) = cached.headers.get(ETAG) {
+        headers.push((IF_NONE_MATCH, etag.clone()));
+    }
+    if let Some(last_modified) = cached.headers.get(LAST_MODIFIED) {
+        headers.push((IF_MODIFIED_SINCE, last_modified.clone()));
+    }
+    headers
+}
+
+#[test]
+fn test_response_cache() {
+    use reqwest::{StatusCode, Version};
+
+    let dir = std::env::temp_dir().join(format!("reqq-cache-{}", std::process::id()));
+    let cache = ResponseCache::new(dir.to_str().unwrap());
+    let mut resp = Response {
+        status: StatusCode::OK,
+        version: Version::HTTP_11,
+        headers: Default::default(),
+        body: "{\"id\": 1}".to_owned(),
+        binary: None,
+    };
+    assert!(!cache
+        .store("GET https://example.com/users/1", &resp)
+        .unwrap());
+
+    resp.headers.insert(ETAG, "\"v1\"".parse().unwrap());
+    assert!(cache
+        .store("GET https://example.com/users/1", &resp)
+        .unwrap());
+    let cached = cache
+        .get("GET https://example.com/users/1")
+        .unwrap()
+        .unwrap();
+    assert_eq!(cached.body, resp.body);
+    assert_eq!(
+        validators(&cached),
+        vec![(IF_NONE_MATCH, "\"v1\"".parse().unwrap())]
+    );
+    assert!(cache
+        .get("GET https://example.com/users/2")
+        .unwrap()
+        .is_none());
+
+    cache.clear().unwrap();
+    assert!(cache
+        .get("GET https://example.com/users/1")
+        .unwrap()
+        .is_none());
+    cache.clear().unwrap();
+}
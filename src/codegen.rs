@@ -0,0 +1,631 @@
+//! `reqq codegen`: a parsed request written as a program that sends it, with the usual HTTP
+//! library of a language: reqwest for Rust, urllib for Python, fetch for JavaScript and
+//! net/http for Go. Snippets send the method, URL, headers, auth, body and timeout, and print
+//! the status and body of the response.
+
+use crate::{
+    auth::{Auth, KeyLocation},
+    body::{Body, Part, PartValue},
+    request::Request,
+};
+use anyhow::{anyhow, Result};
+use reqwest::header::CONTENT_TYPE;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The languages snippets can be written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lang {
+    Rust,
+    Python,
+    Js,
+    Go,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rust" => Ok(Lang::Rust),
+            "python" => Ok(Lang::Python),
+            "js" => Ok(Lang::Js),
+            "go" => Ok(Lang::Go),
+            _ => Err(format!(
+                "unknown language '{}', expected rust, python, js or go",
+                s
+            )),
+        }
+    }
+}
+
+/// What a secret variable is rendered as, so snippets don't hold secrets: its name in
+/// uppercase, like `${API_KEY}`.
+pub fn placeholder(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("${{{}}}", name)
+}
+
+/// The parts of a request that snippets send.
+#[derive(Debug, Default, PartialEq)]
+struct Snippet {
+    method: String,
+    url: String,
+    /// Headers with repeated names are joined into one, since some languages take a map.
+    headers: Vec<(String, String)>,
+    basic_auth: Option<(String, String)>,
+    body: Option<SnippetBody>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug, PartialEq)]
+enum SnippetBody {
+    Text(String),
+    File(String),
+    Multipart(Vec<Part>),
+}
+
+/// Writes a parsed request as a program in a language.
+pub fn generate(req: &Request, lang: Lang) -> Result<String> {
+    let snippet = snippet(req)?;
+    match lang {
+        Lang::Rust => Ok(rust(&snippet)),
+        Lang::Python => python(&snippet),
+        Lang::Js => Ok(js(&snippet)),
+        Lang::Go => Ok(go(&snippet)),
+    }
+}
+
+fn snippet(req: &Request) -> Result<Snippet> {
+    let inner = req
+        .inner()
+        .ok_or_else(|| anyhow!("Request has not been parsed."))?;
+    let mut url = inner.url().clone();
+    let mut snippet = Snippet {
+        method: inner.method().to_string(),
+        timeout: req.timeout(),
+        ..Default::default()
+    };
+    for (name, value) in inner.headers() {
+        add_header(
+            &mut snippet.headers,
+            name.as_str(),
+            &String::from_utf8_lossy(value.as_bytes()),
+        );
+    }
+
+    match req.auth() {
+        Some(Auth::Basic { user, pass }) => snippet.basic_auth = Some((user.clone(), pass.clone())),
+        Some(Auth::Bearer { token }) => add_header(
+            &mut snippet.headers,
+            "Authorization",
+            &format!("Bearer {}", token),
+        ),
+        Some(Auth::ApiKey {
+            name,
+            value,
+            location: KeyLocation::Header,
+        }) => add_header(&mut snippet.headers, name, value),
+        Some(Auth::ApiKey {
+            name,
+            value,
+            location: KeyLocation::Query,
+        }) => {
+            url.query_pairs_mut().append_pair(name, value);
+        }
+        Some(Auth::Aws(_)) => {
+            return Err(anyhow!(
+            "AWS signatures are computed when a request is sent, so they can't be written as code."
+        ))
+        }
+        Some(Auth::OAuth2(_)) => {
+            return Err(anyhow!(
+                "OAuth2 auth needs a token before it can be written as code."
+            ))
+        }
+        Some(Auth::None) | None => {}
+    }
+    snippet.url = url.to_string();
+
+    let has_content_type = snippet
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()));
+    snippet.body = match inner.body() {
+        // The libraries write the content type of multipart bodies, with their boundary.
+        Some(Body::Multipart(parts)) => {
+            snippet
+                .headers
+                .retain(|(name, _)| !name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()));
+            Some(SnippetBody::Multipart(parts.clone()))
+        }
+        Some(Body::File(path)) => Some(SnippetBody::File(path.display().to_string())),
+        Some(body) => {
+            let encoded = body.encode()?;
+            if let (Some(content_type), false) = (encoded.content_type, has_content_type) {
+                add_header(&mut snippet.headers, CONTENT_TYPE.as_str(), &content_type);
+            }
+            Some(SnippetBody::Text(
+                String::from_utf8_lossy(&encoded.bytes).into_owned(),
+            ))
+        }
+        None => None,
+    };
+    Ok(snippet)
+}
+
+fn add_header(headers: &mut Vec<(String, String)>, name: &str, value: &str) {
+    match headers
+        .iter_mut()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+    {
+        Some((_, existing)) => {
+            existing.push_str(", ");
+            existing.push_str(value);
+        }
+        None => headers.push((name.to_owned(), value.to_owned())),
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map_or_else(
+        || path.to_owned(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// A string literal that JavaScript, Python and Go all read back the same.
+fn json_str(s: &str) -> String {
+    serde_json::Value::String(s.to_owned()).to_string()
+}
+
+fn rust_str(s: &str) -> String {
+    if s.contains('\n') && !s.contains("\"#") {
+        format!("r#\"{}\"#", s)
+    } else {
+        format!("{:?}", s)
+    }
+}
+
+fn rust(snippet: &Snippet) -> String {
+    let url = rust_str(&snippet.url);
+    let mut calls = vec![match snippet.method.as_str() {
+        "GET" | "POST" | "PUT" | "PATCH" | "DELETE" | "HEAD" => {
+            format!(".{}({})", snippet.method.to_ascii_lowercase(), url)
+        }
+        method => format!(
+            ".request(reqwest::Method::from_bytes(b{:?})?, {})",
+            method, url
+        ),
+    }];
+    for (name, value) in &snippet.headers {
+        calls.push(format!(".header({}, {})", rust_str(name), rust_str(value)));
+    }
+    if let Some((user, pass)) = &snippet.basic_auth {
+        calls.push(format!(
+            ".basic_auth({}, Some({}))",
+            rust_str(user),
+            rust_str(pass)
+        ));
+    }
+    if let Some(timeout) = snippet.timeout {
+        calls.push(format!(
+            ".timeout(Duration::from_millis({}))",
+            timeout.as_millis()
+        ));
+    }
+
+    let mut form = String::new();
+    match &snippet.body {
+        Some(SnippetBody::Text(text)) => calls.push(format!(".body({})", rust_str(text))),
+        Some(SnippetBody::File(path)) => {
+            calls.push(format!(".body(std::fs::read({})?)", rust_str(path)))
+        }
+        Some(SnippetBody::Multipart(parts)) => {
+            form.push_str("    let form = reqwest::blocking::multipart::Form::new()");
+            for part in parts {
+                form.push_str(&match &part.value {
+                    PartValue::Text(value) => format!(
+                        "\n        .text({}, {})",
+                        rust_str(&part.name),
+                        rust_str(value)
+                    ),
+                    PartValue::File(path) => format!(
+                        "\n        .file({}, {:?})?",
+                        rust_str(&part.name),
+                        path.display().to_string()
+                    ),
+                });
+            }
+            form.push_str(";\n");
+            calls.push(".multipart(form)".to_owned());
+        }
+        None => {}
+    }
+    calls.push(".send()?;".to_owned());
+
+    let mut out = String::new();
+    if snippet.timeout.is_some() {
+        out.push_str("use std::time::Duration;\n\n");
+    }
+    out.push_str("fn main() -> Result<(), Box<dyn std::error::Error>> {\n");
+    out.push_str("    let client = reqwest::blocking::Client::new();\n");
+    out.push_str(&form);
+    out.push_str("    let response = client\n");
+    for call in calls {
+        out.push_str(&format!("        {}\n", call));
+    }
+    out.push_str("    println!(\"{}\", response.status());\n");
+    out.push_str("    println!(\"{}\", response.text()?);\n");
+    out.push_str("    Ok(())\n}\n");
+    out
+}
+
+fn python_str(s: &str) -> String {
+    if s.contains('\n') && !s.contains('\\') && !s.contains("\"\"\"") && !s.ends_with('"') {
+        format!("\"\"\"{}\"\"\"", s)
+    } else {
+        json_str(s)
+    }
+}
+
+fn python(snippet: &Snippet) -> Result<String> {
+    let mut headers: Vec<(String, String)> = snippet
+        .headers
+        .iter()
+        .map(|(name, value)| (python_str(name), python_str(value)))
+        .collect();
+    if let Some((user, pass)) = &snippet.basic_auth {
+        let credentials = python_str(&format!("{}:{}", user, pass));
+        headers.push((
+            "\"Authorization\"".to_owned(),
+            format!(
+                "\"Basic \" + base64.b64encode({}.encode()).decode()",
+                credentials
+            ),
+        ));
+    }
+    let data = match &snippet.body {
+        Some(SnippetBody::Text(text)) => Some(format!("{}.encode()", python_str(text))),
+        Some(SnippetBody::File(path)) => Some(format!("open({}, \"rb\").read()", python_str(path))),
+        Some(SnippetBody::Multipart(_)) => {
+            return Err(anyhow!(
+                "urllib can't send multipart bodies, so they can't be written as Python."
+            ))
+        }
+        None => None,
+    };
+
+    let mut out = String::new();
+    if snippet.basic_auth.is_some() {
+        out.push_str("import base64\n");
+    }
+    out.push_str("import urllib.request\n\n");
+    out.push_str("request = urllib.request.Request(\n");
+    out.push_str(&format!("    {},\n", python_str(&snippet.url)));
+    out.push_str(&format!("    method={},\n", json_str(&snippet.method)));
+    if !headers.is_empty() {
+        out.push_str("    headers={\n");
+        for (name, value) in headers {
+            out.push_str(&format!("        {}: {},\n", name, value));
+        }
+        out.push_str("    },\n");
+    }
+    if let Some(data) = data {
+        out.push_str(&format!("    data={},\n", data));
+    }
+    out.push_str(")\n");
+    let timeout = snippet
+        .timeout
+        .map(|t| format!(", timeout={}", t.as_secs_f64()))
+        .unwrap_or_default();
+    out.push_str(&format!(
+        "with urllib.request.urlopen(request{}) as response:\n",
+        timeout
+    ));
+    out.push_str("    print(response.status)\n");
+    out.push_str("    print(response.read().decode())\n");
+    Ok(out)
+}
+
+fn js_str(s: &str) -> String {
+    if s.contains('\n') && !s.contains(['`', '\\']) && !s.contains("${") {
+        format!("`{}`", s)
+    } else {
+        json_str(s)
+    }
+}
+
+fn js(snippet: &Snippet) -> String {
+    let mut options = vec![format!("method: {},", json_str(&snippet.method))];
+    let mut headers: Vec<String> = snippet
+        .headers
+        .iter()
+        .map(|(name, value)| format!("{}: {},", json_str(name), js_str(value)))
+        .collect();
+    if let Some((user, pass)) = &snippet.basic_auth {
+        headers.push(format!(
+            "\"Authorization\": \"Basic \" + btoa({}),",
+            js_str(&format!("{}:{}", user, pass))
+        ));
+    }
+    if !headers.is_empty() {
+        options.push(format!(
+            "headers: {{\n    {}\n  }},",
+            headers.join("\n    ")
+        ));
+    }
+
+    let mut reads_files = false;
+    let mut form = String::new();
+    match &snippet.body {
+        Some(SnippetBody::Text(text)) => options.push(format!("body: {},", js_str(text))),
+        Some(SnippetBody::File(path)) => {
+            reads_files = true;
+            options.push(format!("body: await readFile({}),", json_str(path)));
+        }
+        Some(SnippetBody::Multipart(parts)) => {
+            form.push_str("const form = new FormData();\n");
+            for part in parts {
+                form.push_str(&match &part.value {
+                    PartValue::Text(value) => format!(
+                        "form.append({}, {});\n",
+                        json_str(&part.name),
+                        js_str(value)
+                    ),
+                    PartValue::File(path) => {
+                        reads_files = true;
+                        let path = path.display().to_string();
+                        format!(
+                            "form.append({}, new Blob([await readFile({})]), {});\n",
+                            json_str(&part.name),
+                            json_str(&path),
+                            json_str(&file_name(&path))
+                        )
+                    }
+                });
+            }
+            form.push('\n');
+            options.push("body: form,".to_owned());
+        }
+        None => {}
+    }
+    if let Some(timeout) = snippet.timeout {
+        options.push(format!(
+            "signal: AbortSignal.timeout({}),",
+            timeout.as_millis()
+        ));
+    }
+
+    let mut out = String::new();
+    if reads_files {
+        out.push_str("import { readFile } from \"node:fs/promises\";\n\n");
+    }
+    out.push_str(&form);
+    out.push_str(&format!(
+        "const response = await fetch({}, {{\n",
+        js_str(&snippet.url)
+    ));
+    for option in options {
+        out.push_str(&format!("  {}\n", option));
+    }
+    out.push_str("});\n");
+    out.push_str("console.log(response.status);\n");
+    out.push_str("console.log(await response.text());\n");
+    out
+}
+
+fn go_str(s: &str) -> String {
+    if s.contains('\n') && !s.contains(['`', '\r']) {
+        format!("`{}`", s)
+    } else {
+        json_str(s)
+    }
+}
+
+/// Go's check of `err`, for a line indented with a number of tabs.
+fn go_panic(depth: usize) -> String {
+    let tabs = "\t".repeat(depth);
+    format!("if err != nil {{\n{}\tpanic(err)\n{}}}", tabs, tabs)
+}
+
+fn go(snippet: &Snippet) -> String {
+    let mut imports = vec!["fmt", "io", "net/http"];
+    let mut setup = String::new();
+    let body = match &snippet.body {
+        Some(SnippetBody::Text(text)) => {
+            imports.push("strings");
+            setup.push_str(&format!("\tbody := strings.NewReader({})\n", go_str(text)));
+            "body"
+        }
+        Some(SnippetBody::File(path)) => {
+            imports.push("os");
+            setup.push_str(&format!(
+                "\tbody, err := os.Open({})\n\t{}\n\tdefer body.Close()\n",
+                go_str(path),
+                go_panic(1)
+            ));
+            "body"
+        }
+        Some(SnippetBody::Multipart(parts)) => {
+            imports.extend(["bytes", "mime/multipart"]);
+            setup.push_str("\tvar body bytes.Buffer\n\tform := multipart.NewWriter(&body)\n");
+            for part in parts {
+                match &part.value {
+                    PartValue::Text(value) => setup.push_str(&format!(
+                        "\tform.WriteField({}, {})\n",
+                        go_str(&part.name),
+                        go_str(value)
+                    )),
+                    PartValue::File(path) => {
+                        imports.push("os");
+                        let path = path.display().to_string();
+                        setup.push_str(&format!(
+                            "\t{{\n\t\tfile, err := os.Open({})\n\t\t{}\n\
+                             \t\tpart, err := form.CreateFormFile({}, {})\n\t\t{}\n\
+                             \t\tif _, err := io.Copy(part, file); err != nil {{\n\
+                             \t\t\tpanic(err)\n\t\t}}\n\t\tfile.Close()\n\t}}\n",
+                            go_str(&path),
+                            go_panic(2),
+                            go_str(&part.name),
+                            go_str(&file_name(&path)),
+                            go_panic(2),
+                        ));
+                    }
+                }
+            }
+            setup.push_str("\tform.Close()\n");
+            "&body"
+        }
+        None => "nil",
+    };
+    if snippet.timeout.is_some() {
+        imports.push("time");
+    }
+    imports.sort();
+    imports.dedup();
+
+    let method = match snippet.method.as_str() {
+        "GET" => "http.MethodGet".to_owned(),
+        "POST" => "http.MethodPost".to_owned(),
+        "PUT" => "http.MethodPut".to_owned(),
+        "PATCH" => "http.MethodPatch".to_owned(),
+        "DELETE" => "http.MethodDelete".to_owned(),
+        "HEAD" => "http.MethodHead".to_owned(),
+        "OPTIONS" => "http.MethodOptions".to_owned(),
+        method => json_str(method),
+    };
+
+    let mut out = String::from("package main\n\nimport (\n");
+    for import in imports {
+        out.push_str(&format!("\t\"{}\"\n", import));
+    }
+    out.push_str(")\n\nfunc main() {\n");
+    out.push_str(&setup);
+    out.push_str(&format!(
+        "\treq, err := http.NewRequest({}, {}, {})\n\t{}\n",
+        method,
+        go_str(&snippet.url),
+        body,
+        go_panic(1)
+    ));
+    for (name, value) in &snippet.headers {
+        out.push_str(&format!(
+            "\treq.Header.Set({}, {})\n",
+            go_str(name),
+            go_str(value)
+        ));
+    }
+    if let Some(SnippetBody::Multipart(_)) = &snippet.body {
+        out.push_str("\treq.Header.Set(\"Content-Type\", form.FormDataContentType())\n");
+    }
+    if let Some((user, pass)) = &snippet.basic_auth {
+        out.push_str(&format!(
+            "\treq.SetBasicAuth({}, {})\n",
+            go_str(user),
+            go_str(pass)
+        ));
+    }
+    match snippet.timeout {
+        Some(timeout) => out.push_str(&format!(
+            "\n\tclient := &http.Client{{Timeout: {} * time.Millisecond}}\n",
+            timeout.as_millis()
+        )),
+        None => out.push_str("\n\tclient := &http.Client{}\n"),
+    }
+    out.push_str(&format!(
+        "\tresp, err := client.Do(req)\n\t{}\n\tdefer resp.Body.Close()\n",
+        go_panic(1)
+    ));
+    out.push_str(&format!(
+        "\trespBody, err := io.ReadAll(resp.Body)\n\t{}\n",
+        go_panic(1)
+    ));
+    out.push_str("\tfmt.Println(resp.Status)\n\tfmt.Println(string(respBody))\n}\n");
+    out
+}
+
+#[cfg(test)]
+fn parsed(fstr: &str) -> Request {
+    let mut req = Request::from_rendered("users/create".to_owned(), fstr.to_owned());
+    req.parse(None, Default::default()).unwrap();
+    req
+}
+
+#[test]
+fn test_codegen_lang() {
+    assert_eq!("go".parse(), Ok(Lang::Go));
+    assert!("ruby".parse::<Lang>().is_err());
+    assert_eq!(placeholder("api-key"), "${API_KEY}");
+}
+
+#[test]
+fn test_codegen_generate() {
+    let req = parsed("---\ntimeout: 5s\n---\nPOST https://api.example.com/users?dry=1\nX-Team: core\nX-Team: web\n\n{\n  \"name\": \"x\"\n}\n");
+    assert_eq!(
+        generate(&req, Lang::Rust).unwrap(),
+        r##"use std::time::Duration;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .post("https://api.example.com/users?dry=1")
+        .header("x-team", "core, web")
+        .timeout(Duration::from_millis(5000))
+        .body(r#"{
+  "name": "x"
+}
+"#)
+        .send()?;
+    println!("{}", response.status());
+    println!("{}", response.text()?);
+    Ok(())
+}
+"##
+    );
+    assert_eq!(
+        generate(&req, Lang::Python).unwrap(),
+        r#"import urllib.request
+
+request = urllib.request.Request(
+    "https://api.example.com/users?dry=1",
+    method="POST",
+    headers={
+        "x-team": "core, web",
+    },
+    data="""{
+  "name": "x"
+}
+""".encode(),
+)
+with urllib.request.urlopen(request, timeout=5) as response:
+    print(response.status)
+    print(response.read().decode())
+"#
+    );
+
+    let req = parsed("@multipart\nPUT https://api.example.com/avatar\nContent-Type: multipart/form-data\n\nname=x\nphoto=@/tmp/photo.png\n");
+    let js = generate(&req, Lang::Js).unwrap();
+    assert!(js.starts_with("import { readFile } from \"node:fs/promises\";\n\nconst form = new FormData();\nform.append(\"name\", \"x\");\n"));
+    assert!(js.contains(
+        "form.append(\"photo\", new Blob([await readFile(\"/tmp/photo.png\")]), \"photo.png\");\n"
+    ));
+    assert!(js.contains("  method: \"PUT\",\n  body: form,\n});\n"));
+    let go = generate(&req, Lang::Go).unwrap();
+    assert!(go.contains("import (\n\t\"bytes\"\n\t\"fmt\"\n\t\"io\"\n\t\"mime/multipart\"\n\t\"net/http\"\n\t\"os\"\n)\n"));
+    assert!(go.contains(
+        "req, err := http.NewRequest(http.MethodPut, \"https://api.example.com/avatar\", &body)\n"
+    ));
+    assert!(!go.contains("multipart/form-data\")"));
+    assert!(generate(&req, Lang::Python).is_err());
+}
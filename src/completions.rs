@@ -0,0 +1,479 @@
+//! Shell completion scripts, generated from the clap definition of the CLI.
+//!
+//! Args with the value name `REQUEST` complete request names, and args with the value name
+//! `ENV` complete env names, both by calling back into reqq's hidden `__complete-requests` and
+//! `__complete-envs` commands.
+
+use clap::Command;
+use std::str::FromStr;
+
+/// The shells completion scripts can be generated for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("unknown shell '{}', expected bash, zsh or fish", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ValueKind {
+    None,
+    Env,
+    Other,
+}
+
+struct Opt {
+    short: Option<char>,
+    long: Option<String>,
+    help: String,
+    value: ValueKind,
+}
+
+/// A command or subcommand, like `reqq import curl`, with what can follow it.
+struct Node {
+    path: String,
+    options: Vec<Opt>,
+    subcommands: Vec<(String, String)>,
+    /// The hidden command listing the names its positional args can be.
+    names_hook: Option<&'static str>,
+}
+
+/// Generates the completion script for a shell.
+pub fn generate(cmd: &Command, shell: Shell) -> String {
+    let mut cmd = cmd.clone();
+    // Building propagates global options to the subcommands.
+    cmd.build();
+    let mut nodes = vec![];
+    collect(&cmd, cmd.get_name(), &mut nodes);
+    match shell {
+        Shell::Bash => bash(cmd.get_name(), &nodes),
+        Shell::Zsh => zsh(cmd.get_name(), &nodes),
+        Shell::Fish => fish(cmd.get_name(), &nodes),
+    }
+}
+
+fn collect(cmd: &Command, path: &str, nodes: &mut Vec<Node>) {
+    let mut options = vec![];
+    let mut names_hook = None;
+    for arg in cmd.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        let value_names = arg.get_value_names().unwrap_or_default();
+        if arg.is_positional() {
+            if value_names.iter().any(|name| name == "REQUEST") {
+                names_hook = Some("__complete-requests");
+            } else if value_names.iter().any(|name| name == "ENV") {
+                names_hook = Some("__complete-envs");
+            }
+            continue;
+        }
+        let value = match (
+            arg.get_action().takes_values(),
+            value_names.iter().any(|name| name == "ENV"),
+        ) {
+            (false, _) => ValueKind::None,
+            (true, true) => ValueKind::Env,
+            (true, false) => ValueKind::Other,
+        };
+        options.push(Opt {
+            short: arg.get_short(),
+            long: arg.get_long().map(str::to_owned),
+            help: arg
+                .get_help()
+                .map(|help| first_line(&help.to_string()))
+                .unwrap_or_default(),
+            value,
+        });
+    }
+
+    let subcommands: Vec<&Command> = cmd
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+        .collect();
+    nodes.push(Node {
+        path: path.to_owned(),
+        options,
+        subcommands: subcommands
+            .iter()
+            .map(|sub| {
+                let about = sub
+                    .get_about()
+                    .map(|about| first_line(&about.to_string()))
+                    .unwrap_or_default();
+                (sub.get_name().to_owned(), about)
+            })
+            .collect(),
+        names_hook,
+    });
+    for sub in subcommands {
+        collect(sub, &format!("{} {}", path, sub.get_name()), nodes);
+    }
+}
+
+/// The option spellings, like `-e` and `--env`, of every option with the given kind of value.
+fn option_words(nodes: &[Node], kinds: &[ValueKind]) -> Vec<String> {
+    let mut words = vec![];
+    for opt in nodes
+        .iter()
+        .flat_map(|node| node.options.iter())
+        .filter(|opt| kinds.contains(&opt.value))
+    {
+        for word in spellings(opt) {
+            if !words.contains(&word) {
+                words.push(word);
+            }
+        }
+    }
+    words
+}
+
+fn spellings(opt: &Opt) -> Vec<String> {
+    let short = opt.short.map(|short| format!("-{}", short));
+    let long = opt.long.as_ref().map(|long| format!("--{}", long));
+    short.into_iter().chain(long).collect()
+}
+
+/// The `case` branches that walk the words typed so far to the subcommand being completed.
+fn subcommand_cases(nodes: &[Node], indent: &str) -> String {
+    let mut cases = vec![];
+    for node in nodes {
+        for (name, _) in node.subcommands.iter() {
+            cases.push(format!(
+                "{}\"{},{}\") cmd=\"{} {}\" ;;",
+                indent, node.path, name, node.path, name
+            ));
+        }
+    }
+    cases.join("\n")
+}
+
+/// A `case` pattern matching any of the words, or nothing if there are none.
+fn case_pattern(words: &[String]) -> String {
+    if words.is_empty() {
+        // Options can't start with a space, so this never matches.
+        "' '".to_owned()
+    } else {
+        words.join("|")
+    }
+}
+
+fn bash(bin: &str, nodes: &[Node]) -> String {
+    let value_options = case_pattern(&option_words(nodes, &[ValueKind::Env, ValueKind::Other]));
+    let env_options = case_pattern(&option_words(nodes, &[ValueKind::Env]));
+    let other_options = case_pattern(&option_words(nodes, &[ValueKind::Other]));
+    let mut branches = vec![];
+    for node in nodes {
+        let options: Vec<String> = node.options.iter().flat_map(spellings).collect();
+        let words: Vec<&str> = node
+            .subcommands
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        branches.push(format!(
+            "        \"{}\")\n            opts=\"{}\"\n            words=\"{}\"\n\
+             \x20           hook=\"{}\"\n            ;;",
+            node.path,
+            options.join(" "),
+            words.join(" "),
+            node.names_hook.unwrap_or_default(),
+        ));
+    }
+
+    format!(
+        r#"_{bin}() {{
+    local cur prev cmd opts words hook i
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    cmd="{bin}"
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${{COMP_WORDS[i]}}" in
+            {value_options}) ((i++)); continue ;;
+        esac
+        case "$cmd,${{COMP_WORDS[i]}}" in
+{cases}
+        esac
+    done
+
+    case "$prev" in
+        {env_options})
+            COMPREPLY=($(compgen -W "$({bin} __complete-envs 2>/dev/null)" -- "$cur"))
+            return ;;
+        {other_options})
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return ;;
+    esac
+
+    case "$cmd" in
+{branches}
+    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "$opts" -- "$cur"))
+    else
+        [[ -n "$hook" ]] && words="$words $({bin} $hook 2>/dev/null)"
+        COMPREPLY=($(compgen -W "$words" -- "$cur"))
+    fi
+}}
+
+complete -o default -F _{bin} {bin}
+"#,
+        bin = bin,
+        value_options = value_options,
+        env_options = env_options,
+        other_options = other_options,
+        cases = subcommand_cases(nodes, "            "),
+        branches = branches.join("\n"),
+    )
+}
+
+fn zsh(bin: &str, nodes: &[Node]) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', r"'\''"));
+    let value_options = case_pattern(&option_words(nodes, &[ValueKind::Env, ValueKind::Other]));
+    let env_options = case_pattern(&option_words(nodes, &[ValueKind::Env]));
+    let other_options = case_pattern(&option_words(nodes, &[ValueKind::Other]));
+    let mut branches = vec![];
+    for node in nodes {
+        let options: Vec<String> = node
+            .options
+            .iter()
+            .flat_map(|opt| {
+                spellings(opt)
+                    .into_iter()
+                    .map(|word| quote(&format!("{}:{}", word, opt.help)))
+            })
+            .collect();
+        let words: Vec<String> = node
+            .subcommands
+            .iter()
+            .map(|(name, about)| quote(&format!("{}:{}", name, about)))
+            .collect();
+        branches.push(format!(
+            "        \"{}\")\n            opts=({})\n            subwords=({})\n\
+             \x20           hook=\"{}\"\n            ;;",
+            node.path,
+            options.join(" "),
+            words.join(" "),
+            node.names_hook.unwrap_or_default(),
+        ));
+    }
+
+    format!(
+        r#"#compdef {bin}
+
+_{bin}() {{
+    local cmd="{bin}" hook i
+    # `words` is zsh's array of the words typed, so subcommands go in `subwords`.
+    local -a opts subwords
+    for ((i = 2; i < CURRENT; i++)); do
+        case "${{words[i]}}" in
+            {value_options}) ((i++)); continue ;;
+        esac
+        case "$cmd,${{words[i]}}" in
+{cases}
+        esac
+    done
+
+    case "${{words[CURRENT-1]}}" in
+        {env_options})
+            compadd -- ${{(f)"$({bin} __complete-envs 2>/dev/null)"}}
+            return ;;
+        {other_options})
+            _files
+            return ;;
+    esac
+
+    case "$cmd" in
+{branches}
+    esac
+    if [[ "$PREFIX" == -* ]]; then
+        _describe 'option' opts
+    else
+        _describe 'command' subwords
+        [[ -n "$hook" ]] && compadd -- ${{(f)"$({bin} $hook 2>/dev/null)"}}
+    fi
+}}
+
+if [ "$funcstack[1]" = "_{bin}" ]; then
+    _{bin} "$@"
+else
+    compdef _{bin} {bin}
+fi
+"#,
+        bin = bin,
+        value_options = value_options,
+        env_options = env_options,
+        other_options = other_options,
+        cases = subcommand_cases(nodes, "            "),
+        branches = branches.join("\n"),
+    )
+}
+
+fn fish(bin: &str, nodes: &[Node]) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'"));
+    let value_options = option_words(nodes, &[ValueKind::Env, ValueKind::Other]).join(" ");
+    let mut cases = vec![];
+    for node in nodes {
+        for (name, _) in node.subcommands.iter() {
+            cases.push(format!(
+                "                    case '{},{}'\n                        set cmd '{} {}'",
+                node.path, name, node.path, name
+            ));
+        }
+    }
+
+    let mut lines = vec![
+        format!("function __{}_cmd", bin),
+        format!("    set -l cmd {}", bin),
+        "    set -l skip 0".to_owned(),
+        "    for word in (commandline -opc)[2..-1]".to_owned(),
+        "        if test $skip = 1".to_owned(),
+        "            set skip 0".to_owned(),
+        "            continue".to_owned(),
+        "        end".to_owned(),
+        "        switch $word".to_owned(),
+        format!("            case {}", value_options),
+        "                set skip 1".to_owned(),
+        "            case '*'".to_owned(),
+        "                switch \"$cmd,$word\"".to_owned(),
+        cases.join("\n"),
+        "                end".to_owned(),
+        "        end".to_owned(),
+        "    end".to_owned(),
+        "    echo $cmd".to_owned(),
+        "end".to_owned(),
+        String::new(),
+        format!("complete -c {} -f", bin),
+    ];
+    for node in nodes {
+        let condition = format!("-n 'test \"$(__{}_cmd)\" = \"{}\"'", bin, node.path);
+        for (name, about) in node.subcommands.iter() {
+            lines.push(format!(
+                "complete -c {} {} -a {} -d {}",
+                bin,
+                condition,
+                quote(name),
+                quote(about)
+            ));
+        }
+        for opt in node.options.iter() {
+            let mut line = format!("complete -c {} {}", bin, condition);
+            if let Some(short) = opt.short {
+                line.push_str(&format!(" -s {}", short));
+            }
+            if let Some(long) = &opt.long {
+                line.push_str(&format!(" -l {}", long));
+            }
+            match opt.value {
+                ValueKind::None => {}
+                ValueKind::Env => {
+                    line.push_str(&format!(" -x -a '({} __complete-envs 2>/dev/null)'", bin))
+                }
+                ValueKind::Other => line.push_str(" -r -F"),
+            }
+            line.push_str(&format!(" -d {}", quote(&opt.help)));
+            lines.push(line);
+        }
+        if let Some(hook) = node.names_hook {
+            lines.push(format!(
+                "complete -c {} {} -a '({} {} 2>/dev/null)'",
+                bin, condition, bin, hook
+            ));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+fn first_line(help: &str) -> String {
+    help.lines().next().unwrap_or_default().to_owned()
+}
+
+#[cfg(test)]
+fn test_command() -> Command {
+    use clap::Arg;
+
+    Command::new("reqq")
+        .arg(Arg::new("request").value_name("REQUEST"))
+        .arg(
+            Arg::new("env")
+                .short('e')
+                .long("env")
+                .value_name("ENV")
+                .global(true)
+                .help("The env to load."),
+        )
+        .arg(
+            Arg::new("raw")
+                .short('r')
+                .long("raw")
+                .action(clap::ArgAction::SetTrue)
+                .help("Don't format it."),
+        )
+        .subcommand(Command::new("list").about("Lists available requests."))
+        .subcommand(
+            Command::new("import")
+                .about("Creates request files.")
+                .subcommand(
+                    Command::new("har")
+                        .about("From a HAR file.")
+                        .arg(Arg::new("file")),
+                ),
+        )
+        .subcommand(Command::new("__complete-requests").hide(true))
+}
+
+#[test]
+fn test_collect() {
+    let mut cmd = test_command();
+    cmd.build();
+    let mut nodes = vec![];
+    collect(&cmd, "reqq", &mut nodes);
+    let paths: Vec<&str> = nodes.iter().map(|node| node.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec!["reqq", "reqq list", "reqq import", "reqq import har"]
+    );
+    assert_eq!(nodes[0].names_hook, Some("__complete-requests"));
+    assert_eq!(nodes[1].names_hook, None);
+    let names: Vec<&str> = nodes[0]
+        .subcommands
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(names, vec!["list", "import"]);
+    // The global `--env` reaches the subcommands too.
+    assert!(nodes[3]
+        .options
+        .iter()
+        .any(|opt| opt.long.as_deref() == Some("env")));
+    assert_eq!(option_words(&nodes, &[ValueKind::Env]), vec!["-e", "--env"]);
+}
+
+#[test]
+fn test_generate() {
+    let bash = generate(&test_command(), Shell::Bash);
+    assert!(bash.contains(r#""reqq import,har") cmd="reqq import har" ;;"#));
+    assert!(bash.contains("complete -o default -F _reqq reqq"));
+    assert!(!bash.contains("__complete-requests\")"));
+
+    let zsh = generate(&test_command(), Shell::Zsh);
+    assert!(zsh.starts_with("#compdef reqq"));
+    assert!(zsh.contains("'list:Lists available requests.'"));
+
+    let fish = generate(&test_command(), Shell::Fish);
+    assert!(fish.contains(r#"complete -c reqq -n 'test "$(__reqq_cmd)" = "reqq"' -s e -l env -x -a '(reqq __complete-envs 2>/dev/null)' -d 'The env to load.'"#));
+    assert!(fish.contains(r#"complete -c reqq -n 'test "$(__reqq_cmd)" = "reqq"' -a '(reqq __complete-requests 2>/dev/null)'"#));
+    assert_eq!(
+        "powershell".parse::<Shell>(),
+        Err("unknown shell 'powershell', expected bash, zsh or fish".to_owned())
+    );
+}
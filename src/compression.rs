@@ -0,0 +1,439 @@
+//! Compressed response bodies. Bodies with a `Content-Encoding` of `gzip` or `deflate` are
+//! decompressed as they're read, and `--compressed` asks for them with `Accept-Encoding`, like
+//! curl. reqwest is built without its decompression features, so DEFLATE is decoded here.
+//! Other encodings, like `br` and `zstd`, are left as they were received.
+
+use crate::download::format_bytes;
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderMap, CONTENT_ENCODING, CONTENT_LENGTH};
+
+/// The encodings that can be decoded, as sent with `--compressed`.
+pub const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// The most a body is decoded to, so a small body that decodes to a huge one fails instead.
+const MAX_DECODED_SIZE: usize = 1 << 30;
+
+/// A body that was decoded, with its sizes before and after.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoded {
+    pub encoding: String,
+    pub encoded_size: usize,
+    pub decoded_size: usize,
+}
+
+impl Decoded {
+    pub fn report(&self) -> String {
+        format!(
+            "{} bytes, {} bytes {} encoded",
+            self.decoded_size, self.encoded_size, self.encoding
+        )
+    }
+}
+
+/// Whether a body has a `Content-Encoding` that `decode` decodes.
+pub fn decodable(headers: &HeaderMap) -> bool {
+    encodings(headers).is_some()
+}
+
+/// The encodings of a body, in the order they were applied, if they can all be decoded.
+fn encodings(headers: &HeaderMap) -> Option<Vec<String>> {
+    let encoding = headers
+        .get(CONTENT_ENCODING)?
+        .to_str()
+        .ok()?
+        .to_ascii_lowercase();
+    let encodings: Vec<String> = encoding
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty() && *e != "identity")
+        .map(str::to_owned)
+        .collect();
+    let known = encodings
+        .iter()
+        .all(|e| matches!(e.as_str(), "gzip" | "x-gzip" | "deflate"));
+    (known && !encodings.is_empty()).then_some(encodings)
+}
+
+/// Decodes a body by its `Content-Encoding`. When it's decoded, that header and
+/// `Content-Length` are removed, since they no longer describe the body, as reqwest does.
+/// Bodies in other encodings are returned as they are, with their headers.
+pub fn decode(headers: &mut HeaderMap, body: Vec<u8>) -> Result<(Vec<u8>, Option<Decoded>)> {
+    let Some(encodings) = encodings(headers) else {
+        return Ok((body, None));
+    };
+    let encoded_size = body.len();
+    let mut decoded = body;
+    for encoding in encodings.iter().rev() {
+        decoded = match encoding.as_str() {
+            "deflate" => zlib(&decoded, MAX_DECODED_SIZE),
+            _ => gzip(&decoded, MAX_DECODED_SIZE),
+        }
+        .map_err(|e| anyhow!("Couldn't decode the {} response body: {}", encoding, e))?;
+    }
+    headers.remove(CONTENT_ENCODING);
+    headers.remove(CONTENT_LENGTH);
+    let decoded_size = decoded.len();
+    Ok((
+        decoded,
+        Some(Decoded {
+            encoding: encodings.join(", "),
+            encoded_size,
+            decoded_size,
+        }),
+    ))
+}
+
+/// Decodes gzip data, which can be several gzip members one after another, to at most `limit`
+/// bytes.
+fn gzip(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut out = vec![];
+    let mut rest = data;
+    while !rest.is_empty() {
+        if rest.len() < 18 || rest[..3] != [0x1f, 0x8b, 8] {
+            return Err(anyhow!("not gzip data"));
+        }
+        let flags = rest[3];
+        let mut pos = 10;
+        if flags & 4 != 0 {
+            let extra = u16::from_le_bytes([rest[pos], rest[pos + 1]]) as usize;
+            pos += 2 + extra;
+        }
+        // The file name and comment end with a zero byte.
+        for flag in [8, 16] {
+            if flags & flag != 0 {
+                pos += rest
+                    .get(pos..)
+                    .and_then(|r| r.iter().position(|b| *b == 0))
+                    .ok_or_else(|| anyhow!("truncated header"))?
+                    + 1;
+            }
+        }
+        if flags & 2 != 0 {
+            pos += 2;
+        }
+        let member = rest.get(pos..).ok_or_else(|| anyhow!("truncated header"))?;
+        let (inflated, len) = inflate(member, limit - out.len())?;
+        out.extend(inflated);
+        // Each member ends with a CRC-32 and the size.
+        rest = rest.get(pos + len + 8..).unwrap_or_default();
+    }
+    Ok(out)
+}
+
+/// Decodes `deflate` data, which is zlib data, or raw DEFLATE from servers that send that, to at
+/// most `limit` bytes.
+fn zlib(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    match data {
+        [cmf, flg, rest @ ..]
+            if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+        {
+            Ok(inflate(rest, limit)?.0)
+        }
+        _ => Ok(inflate(data, limit)?.0),
+    }
+}
+
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32> {
+        while self.count < n {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| anyhow!("unexpected end of data"))?;
+            self.buf |= u32::from(byte) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Skips to the next byte, as stored blocks start on one.
+    fn align(&mut self) {
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code: how many codes there are of each length, and the symbols in
+/// the order of their codes.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate().filter(|(_, len)| **len != 0) {
+            symbols[offsets[*len as usize] as usize] = symbol as u16;
+            offsets[*len as usize] += 1;
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(self.counts[len]);
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(anyhow!("invalid Huffman code"))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order the lengths of the code length code are given in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decodes raw DEFLATE data (RFC 1951), failing if it's more than `limit` bytes. Returns the data
+/// and the number of bytes it took up.
+fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize)> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        buf: 0,
+        count: 0,
+    };
+    let mut out = vec![];
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = data
+                    .get(bits.pos..bits.pos + 4)
+                    .ok_or_else(|| anyhow!("unexpected end of data"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let start = bits.pos + 4;
+                check_size(out.len() + len, limit)?;
+                out.extend_from_slice(
+                    data.get(start..start + len)
+                        .ok_or_else(|| anyhow!("unexpected end of data"))?,
+                );
+                bits.pos = start + len;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                inflate_block(
+                    &mut bits,
+                    &mut out,
+                    limit,
+                    &Huffman::new(&lengths),
+                    &Huffman::new(&[5; 30]),
+                )?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &mut out, limit, &literals, &distances)?;
+            }
+            _ => return Err(anyhow!("invalid block type")),
+        }
+        if last {
+            return Ok((out, bits.pos));
+        }
+    }
+}
+
+/// Reads the literal/length and distance codes a dynamic block starts with.
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman)> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+    let mut lengths = [0; 19];
+    for i in CODE_LENGTH_ORDER.iter().take(code_lengths) {
+        lengths[*i] = bits.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths);
+
+    let mut lengths = vec![];
+    while lengths.len() < literals + distances {
+        let (len, repeat) = match code_length_code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths
+                    .last()
+                    .ok_or_else(|| anyhow!("repeated length without a first one"))?,
+                3 + bits.bits(2)?,
+            ),
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return Err(anyhow!("too many code lengths"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+fn check_size(size: usize, limit: usize) -> Result<()> {
+    if size > limit {
+        return Err(anyhow!(
+            "it's larger than {} decoded",
+            format_bytes(limit as u64)
+        ));
+    }
+    Ok(())
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => {
+                check_size(out.len() + 1, limit)?;
+                out.push(symbol as u8)
+            }
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                let base = *LENGTH_BASE
+                    .get(i)
+                    .ok_or_else(|| anyhow!("invalid length"))?;
+                let len = base as usize + bits.bits(u32::from(LENGTH_EXTRA[i]))? as usize;
+                let i = distances.decode(bits)? as usize;
+                let base = *DISTANCE_BASE
+                    .get(i)
+                    .ok_or_else(|| anyhow!("invalid distance"))?;
+                let distance = base as usize + bits.bits(u32::from(DISTANCE_EXTRA[i]))? as usize;
+                let start = out
+                    .len()
+                    .checked_sub(distance)
+                    .ok_or_else(|| anyhow!("distance too far back"))?;
+                check_size(out.len() + len, limit)?;
+                // The copy can overlap what it writes, repeating the last bytes.
+                for i in start..start + len {
+                    out.push(out[i]);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_compression_decode() {
+    let gzipped = vec![
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 203, 72, 205, 201, 201, 87, 200, 64, 39, 185, 0, 0, 136,
+        89, 11, 24, 0, 0, 0,
+    ];
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+    headers.insert(CONTENT_LENGTH, "29".parse().unwrap());
+    let (body, decoded) = decode(&mut headers, gzipped).unwrap();
+    assert_eq!(body, b"hello hello hello hello\n");
+    assert_eq!(decoded.unwrap().report(), "24 bytes, 29 bytes gzip encoded");
+    assert!(headers.is_empty());
+
+    // A stored block, in zlib data.
+    headers.insert(CONTENT_ENCODING, "deflate".parse().unwrap());
+    let (body, _) = decode(
+        &mut headers,
+        vec![120, 1, 1, 3, 0, 252, 255, 97, 98, 99, 2, 77, 1, 39],
+    )
+    .unwrap();
+    assert_eq!(body, b"abc");
+
+    headers.insert(CONTENT_ENCODING, "br".parse().unwrap());
+    assert_eq!(
+        decode(&mut headers, vec![1, 2, 3]).unwrap(),
+        (vec![1, 2, 3], None)
+    );
+    assert!(headers.contains_key(CONTENT_ENCODING));
+    headers.insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+    assert!(decode(&mut headers, b"plain".to_vec()).is_err());
+}
+
+#[test]
+fn test_compression_inflate_dynamic() {
+    let raw = [
+        125, 209, 59, 10, 128, 48, 16, 132, 225, 171, 132, 173, 45, 140, 111, 189, 138, 88, 4, 141,
+        32, 168, 133, 175, 70, 188, 187, 17, 44, 116, 148, 233, 150, 229, 227, 111, 166, 220, 165,
+        107, 164, 80, 190, 167, 100, 52, 131, 117, 167, 172, 179, 157, 124, 113, 15, 83, 47, 221,
+        118, 189, 150, 105, 181, 135, 167, 110, 171, 193, 234, 151, 109, 77, 63, 63, 112, 0, 56,
+        32, 225, 16, 108, 200, 194, 17, 224, 136, 132, 99, 176, 49, 11, 39, 128, 19, 18, 78, 193,
+        166, 44, 156, 1, 206, 72, 56, 7, 155, 179, 176, 198, 245, 52, 157, 239, 179, 223, 223, 128,
+        213, 9,
+    ];
+    let (inflated, len) = inflate(&raw, MAX_DECODED_SIZE).unwrap();
+    assert_eq!(len, raw.len());
+    let users: serde_json::Value = serde_json::from_slice(&inflated).unwrap();
+    assert_eq!(users.as_array().unwrap().len(), 12);
+    assert_eq!(
+        users[11],
+        serde_json::json!({"id": 11, "name": "user11", "active": false})
+    );
+}
+
+#[test]
+fn test_compression_limit() {
+    // 24 bytes of text, and a stored block of 3.
+    let gzipped = [
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 203, 72, 205, 201, 201, 87, 200, 64, 39, 185, 0, 0, 136,
+        89, 11, 24, 0, 0, 0,
+    ];
+    assert_eq!(gzip(&gzipped, 24).unwrap().len(), 24);
+    assert_eq!(
+        gzip(&gzipped, 23).unwrap_err().to_string(),
+        "it's larger than 23 bytes decoded"
+    );
+    let stored = [120, 1, 1, 3, 0, 252, 255, 97, 98, 99, 2, 77, 1, 39];
+    assert!(zlib(&stored, 3).is_ok());
+    assert!(zlib(&stored, 2).is_err());
+
+    // Two gzip members together count against the limit.
+    let twice = [&gzipped[..], &gzipped[..]].concat();
+    assert_eq!(gzip(&twice, 48).unwrap().len(), 48);
+    assert!(gzip(&twice, 47).is_err());
+}
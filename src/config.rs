@@ -0,0 +1,227 @@
+use crate::{client::Redirects, secrets::Provider, time, yaml};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// The file the collection config is read from, relative to the reqq directory.
+pub const FILE_NAME: &str = "config.yaml";
+
+/// Settings for every request of a collection, from `config.yaml` in a reqq directory. Request
+/// files, environments and options take precedence over them.
+///
+/// ```yaml
+/// env: staging
+/// base_url: https://api.example.com/v1
+/// headers:
+///   User-Agent: reqq
+/// timeout: 10s
+/// max_redirects: 3
+/// proxy: http://127.0.0.1:8080
+/// secret_pattern: (?i)token|password
+/// secret_store: keyring
+/// pre: ./scripts/refresh-token.sh
+/// post:
+///   - ./scripts/audit.sh
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    /// The environment used when none is given, instead of `default`.
+    pub env: Option<String>,
+    /// What request URLs starting with `/` are relative to.
+    pub base_url: Option<String>,
+    /// Headers added to every request that doesn't set them itself.
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// The timeout of requests without a `@timeout` directive.
+    pub timeout: Option<Duration>,
+    /// From `follow_redirects: false` or `max_redirects`.
+    pub redirects: Option<Redirects>,
+    /// The URL of an HTTP proxy to send requests through.
+    pub proxy: Option<String>,
+    /// A regex matching the names of variables whose values are masked in output, like those
+    /// in the `secrets` section of an env.
+    pub secret_pattern: Option<String>,
+    /// Where `reqq secret` keeps secrets: `vault`, `keyring` or `env`.
+    pub secret_store: Provider,
+    /// Commands run before each request, relative to the reqq directory.
+    pub pre: Vec<String>,
+    /// Commands run after each response, relative to the reqq directory.
+    pub post: Vec<String>,
+}
+
+impl Config {
+    /// Loads the config of a reqq directory. Without a config file, nothing is configured.
+    pub fn load(dir: &str) -> Result<Config> {
+        let fpath = Path::new(dir).join(FILE_NAME);
+        if !fpath.is_file() {
+            return Ok(Config::default());
+        }
+        Config::parse(&fs::read_to_string(&fpath)?)
+            .map_err(|e| anyhow!("Failed to parse config '{}': {}", fpath.display(), e))
+    }
+
+    fn parse(fstr: &str) -> Result<Config> {
+        let value = match yaml::parse(fstr)? {
+            Value::Null => return Ok(Config::default()),
+            value @ Value::Object(_) => value,
+            _ => return Err(anyhow!("expected a mapping")),
+        };
+        Ok(Config {
+            env: string(&value, "env")?,
+            base_url: string(&value, "base_url")?,
+            headers: headers(&value["headers"])?,
+            timeout: match &value["timeout"] {
+                Value::Null => None,
+                Value::Number(secs) if secs.is_u64() => secs.as_u64().map(Duration::from_secs),
+                Value::String(timeout) => Some(
+                    time::parse_duration(timeout).map_err(|e| anyhow!("'timeout' is an {}", e))?,
+                ),
+                _ => return Err(anyhow!("'timeout' must be a duration like 30s")),
+            },
+            redirects: match (&value["follow_redirects"], &value["max_redirects"]) {
+                (Value::Null | Value::Bool(true), Value::Null) => None,
+                (Value::Bool(false), Value::Null) => Some(Redirects::None),
+                (Value::Null | Value::Bool(true), Value::Number(max)) => match max.as_u64() {
+                    Some(max) => Some(Redirects::Follow(max as usize)),
+                    None => return Err(anyhow!("'max_redirects' must be a number")),
+                },
+                (Value::Bool(false), _) => {
+                    return Err(anyhow!(
+                        "'max_redirects' can't be set when 'follow_redirects' is false"
+                    ))
+                }
+                (Value::Bool(_) | Value::Null, _) => {
+                    return Err(anyhow!("'max_redirects' must be a number"))
+                }
+                _ => return Err(anyhow!("'follow_redirects' must be true or false")),
+            },
+            proxy: string(&value, "proxy")?,
+            secret_pattern: match string(&value, "secret_pattern")? {
+                Some(pattern) => Some(
+                    Regex::new(&pattern)
+                        .map(|_| pattern)
+                        .map_err(|e| anyhow!("'secret_pattern' is invalid: {}", e))?,
+                ),
+                None => None,
+            },
+            secret_store: match string(&value, "secret_store")? {
+                Some(store) => store
+                    .parse()
+                    .map_err(|e| anyhow!("'secret_store' is invalid: {}", e))?,
+                None => Provider::default(),
+            },
+            pre: commands(&value["pre"])
+                .ok_or_else(|| anyhow!("'pre' must be a command or a list of them"))?,
+            post: commands(&value["post"])
+                .ok_or_else(|| anyhow!("'post' must be a command or a list of them"))?,
+        })
+    }
+}
+
+fn string(value: &Value, key: &str) -> Result<Option<String>> {
+    match &value[key] {
+        Value::Null => Ok(None),
+        Value::String(s) => Ok(Some(s.clone())),
+        _ => Err(anyhow!("'{}' must be a string", key)),
+    }
+}
+
+/// A mapping of header names to values.
+fn headers(value: &Value) -> Result<Vec<(HeaderName, HeaderValue)>> {
+    let headers = match value {
+        Value::Null => return Ok(vec![]),
+        Value::Object(headers) => headers,
+        _ => return Err(anyhow!("'headers' must be a mapping of names to values")),
+    };
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(_) | Value::Bool(_) => value.to_string(),
+                _ => return Err(anyhow!("header '{}' must have a single value", name)),
+            };
+            Ok((
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(&value)?,
+            ))
+        })
+        .collect()
+}
+
+/// A single command, or a list of them.
+fn commands(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Null => Some(vec![]),
+        Value::String(command) => Some(vec![command.clone()]),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_owned))
+            .collect(),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_parse_config() {
+    let config =
+        Config::parse("pre: ./scripts/refresh-token.sh\npost:\n  - ./a.sh\n  - ./b.sh\n").unwrap();
+    assert_eq!(config.pre, vec!["./scripts/refresh-token.sh"]);
+    assert_eq!(config.post, vec!["./a.sh", "./b.sh"]);
+
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+    assert!(Config::parse("pre:\n  nope: 1\n").is_err());
+    assert!(Config::parse("- a\n").is_err());
+}
+
+#[test]
+fn test_parse_config_defaults() {
+    let config = Config::parse(
+        "env: staging\nbase_url: https://api.example.com/v1\nheaders:\n  User-Agent: reqq\n  X-Version: 2\ntimeout: 10s\nmax_redirects: 3\nproxy: http://127.0.0.1:8080\n",
+    )
+    .unwrap();
+    assert_eq!(config.env.as_deref(), Some("staging"));
+    assert_eq!(
+        config.base_url.as_deref(),
+        Some("https://api.example.com/v1")
+    );
+    assert_eq!(config.headers.len(), 2);
+    assert_eq!(
+        config.headers[1],
+        (
+            HeaderName::from_static("x-version"),
+            HeaderValue::from_static("2")
+        )
+    );
+    assert_eq!(config.timeout, Some(Duration::from_secs(10)));
+    assert_eq!(config.redirects, Some(Redirects::Follow(3)));
+    assert_eq!(config.proxy.as_deref(), Some("http://127.0.0.1:8080"));
+
+    assert_eq!(
+        Config::parse("timeout: 5\n").unwrap().timeout,
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(
+        Config::parse("follow_redirects: false\n")
+            .unwrap()
+            .redirects,
+        Some(Redirects::None)
+    );
+    assert!(Config::parse("follow_redirects: false\nmax_redirects: 3\n").is_err());
+    assert!(Config::parse("timeout: soon\n").is_err());
+    assert!(Config::parse("headers:\n  - a\n").is_err());
+    assert!(Config::parse("env: [a]\n").is_err());
+    assert!(Config::parse("secret_pattern: (token\n").is_err());
+
+    assert_eq!(config.secret_store, Provider::Vault);
+    assert_eq!(
+        Config::parse("secret_store: keyring\n")
+            .unwrap()
+            .secret_store,
+        Provider::Keyring
+    );
+    assert!(Config::parse("secret_store: pass\n").is_err());
+}
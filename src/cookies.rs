@@ -0,0 +1,350 @@
+use crate::time;
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use serde_json::{json, Value};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The file the cookie jar is stored in, relative to the reqq directory.
+pub const FILE_NAME: &str = "cookies.json";
+
+/// A cookie set by a response.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// The host the cookie was set by, or the domain it was set for.
+    pub domain: String,
+    /// Only send the cookie to `domain` itself, not to its subdomains.
+    pub host_only: bool,
+    pub path: String,
+    /// When the cookie expires, in seconds since the unix epoch. Cookies without an expiry are
+    /// kept until the jar is cleared.
+    pub expires: Option<u64>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header value received from `url`. Returns `None` for malformed
+    /// cookies and cookies the server isn't allowed to set.
+    pub fn parse(header: &str, url: &Url) -> Option<Self> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut attrs = header.split(';');
+        let (name, value) = attrs.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_owned(),
+            value: value.trim().trim_matches('"').to_owned(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url),
+            expires: None,
+            secure: false,
+            http_only: false,
+        };
+        let mut max_age = None;
+        for attr in attrs {
+            let (key, val) = attr.split_once('=').unwrap_or((attr, ""));
+            let val = val.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "domain" if !val.is_empty() => {
+                    let domain = val.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain_matches(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if val.starts_with('/') => cookie.path = val.to_owned(),
+                "expires" => cookie.expires = time::parse_http_date(val).or(cookie.expires),
+                "max-age" => max_age = val.parse::<i64>().ok(),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                _ => {}
+            }
+        }
+        // Max-Age takes precedence over Expires, and zero or less expires the cookie right away.
+        if let Some(max_age) = max_age {
+            cookie.expires = Some((now_secs() as i64 + max_age).max(0) as u64);
+        }
+        Some(cookie)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Whether the cookie should be sent with a request to `url`.
+    pub fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str().map(str::to_ascii_lowercase) else {
+            return false;
+        };
+        let host_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+        host_matches
+            && path_matches(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+    }
+
+    /// Whether this cookie replaces `other` when stored.
+    fn same_as(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "value": self.value,
+            "domain": self.domain,
+            "host_only": self.host_only,
+            "path": self.path,
+            "expires": self.expires,
+            "secure": self.secure,
+            "http_only": self.http_only,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let string = |key: &str| value[key].as_str().map(str::to_owned);
+        let flag = |key: &str| value[key].as_bool().unwrap_or(false);
+        Some(Cookie {
+            name: string("name")?,
+            value: string("value")?,
+            domain: string("domain")?,
+            host_only: flag("host_only"),
+            path: string("path").unwrap_or_else(|| "/".to_owned()),
+            expires: value["expires"].as_u64(),
+            secure: flag("secure"),
+            http_only: flag("http_only"),
+        })
+    }
+}
+
+/// Lists a cookie as `domain  path  name=value`, followed by when it expires, if it does.
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}  {}  {}={}",
+            self.domain, self.path, self.name, self.value
+        )?;
+        if let Some(expires) = self.expires {
+            write!(f, "  expires {}", time::format_iso8601(expires * 1000))?;
+        }
+        Ok(())
+    }
+}
+
+/// Cookies set by responses, stored in `cookies.json` in a reqq directory so they're sent with
+/// later requests, like a browser session.
+pub struct CookieJar {
+    fpath: PathBuf,
+}
+
+impl CookieJar {
+    pub fn new(reqq_dir: &str) -> Self {
+        CookieJar {
+            fpath: Path::new(reqq_dir).join(FILE_NAME),
+        }
+    }
+
+    /// The cookies in the jar that haven't expired.
+    pub fn load(&self) -> Result<Vec<Cookie>> {
+        if !self.fpath.is_file() {
+            return Ok(vec![]);
+        }
+
+        let malformed = || anyhow!("Malformed cookie jar '{}'.", self.fpath.display());
+        let cookies = match serde_json::from_str(&fs::read_to_string(&self.fpath)?)? {
+            Value::Array(cookies) => cookies
+                .iter()
+                .map(Cookie::from_json)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(malformed)?,
+            _ => return Err(malformed()),
+        };
+        let now = now_secs();
+        Ok(cookies.into_iter().filter(|c| !c.is_expired(now)).collect())
+    }
+
+    /// The value of the `Cookie` header to send with a request to `url`, if any cookies match.
+    pub fn header_for(&self, url: &Url) -> Result<Option<String>> {
+        let mut cookies: Vec<Cookie> = self
+            .load()?
+            .into_iter()
+            .filter(|c| c.matches(url))
+            .collect();
+        if cookies.is_empty() {
+            return Ok(None);
+        }
+        // Cookies with more specific paths go first.
+        cookies.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect();
+        Ok(Some(pairs.join("; ")))
+    }
+
+    /// Stores the cookies set by a response to a request to `url`. Expired cookies replace, and
+    /// so remove, the ones they match.
+    pub fn store(&self, url: &Url, headers: &HeaderMap) -> Result<()> {
+        let received: Vec<Cookie> = headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| Cookie::parse(value, url))
+            .collect();
+        if received.is_empty() {
+            return Ok(());
+        }
+
+        let now = now_secs();
+        let mut cookies = self.load()?;
+        for cookie in received {
+            cookies.retain(|c| !c.same_as(&cookie));
+            if !cookie.is_expired(now) {
+                cookies.push(cookie);
+            }
+        }
+        self.save(&cookies)
+    }
+
+    /// Removes every cookie.
+    pub fn clear(&self) -> Result<()> {
+        if self.fpath.is_file() {
+            fs::remove_file(&self.fpath)?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, cookies: &[Cookie]) -> Result<()> {
+        let cookies: Vec<Value> = cookies.iter().map(Cookie::to_json).collect();
+        fs::write(&self.fpath, serde_json::to_string_pretty(&cookies)?)?;
+        Ok(())
+    }
+}
+
+fn now_secs() -> u64 {
+    time::now_millis() / 1000
+}
+
+/// Whether `host` is `domain` or one of its subdomains.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Whether a request path is inside a cookie path.
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+/// The path a cookie applies to when it doesn't set one: the "directory" of the request path.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_owned(),
+        Some(i) => url.path()[..i].to_owned(),
+    }
+}
+
+#[test]
+fn test_cookie_parse() {
+    let url = Url::parse("https://api.example.com/auth/login").unwrap();
+
+    let cookie = Cookie::parse("session=abc123; Path=/; Secure; HttpOnly", &url).unwrap();
+    assert_eq!(cookie.name, "session");
+    assert_eq!(cookie.value, "abc123");
+    assert_eq!(cookie.domain, "api.example.com");
+    assert!(cookie.host_only);
+    assert_eq!(cookie.path, "/");
+    assert!(cookie.secure && cookie.http_only);
+    assert_eq!(cookie.expires, None);
+
+    let cookie = Cookie::parse(
+        "theme=dark; Domain=.example.com; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+        &url,
+    )
+    .unwrap();
+    assert_eq!(cookie.domain, "example.com");
+    assert!(!cookie.host_only);
+    assert_eq!(cookie.path, "/auth");
+    assert_eq!(cookie.expires, Some(1_445_412_480));
+
+    assert!(Cookie::parse("a=b; Domain=other.com", &url).is_none());
+    assert!(Cookie::parse("no-value", &url).is_none());
+    assert!(Cookie::parse("gone=; Max-Age=0", &url)
+        .unwrap()
+        .is_expired(now_secs()));
+}
+
+#[test]
+fn test_cookie_matches() {
+    let login = Url::parse("https://api.example.com/auth/login").unwrap();
+    let cookie = Cookie::parse("sid=1; Domain=example.com; Path=/users; Secure", &login).unwrap();
+
+    assert!(cookie.matches(&Url::parse("https://example.com/users").unwrap()));
+    assert!(cookie.matches(&Url::parse("https://www.example.com/users/1").unwrap()));
+    assert!(!cookie.matches(&Url::parse("https://example.com/usersx").unwrap()));
+    assert!(!cookie.matches(&Url::parse("http://example.com/users").unwrap()));
+    assert!(!cookie.matches(&Url::parse("https://notexample.com/users").unwrap()));
+
+    let host_only = Cookie::parse("sid=1; Path=/", &login).unwrap();
+    assert!(host_only.matches(&Url::parse("http://api.example.com/").unwrap()));
+    assert!(!host_only.matches(&Url::parse("http://v2.api.example.com/").unwrap()));
+}
+
+#[test]
+fn test_cookie_jar() {
+    let dir = std::env::temp_dir().join(format!("reqq-cookies-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let jar = CookieJar::new(dir.to_str().unwrap());
+    let url = Url::parse("http://localhost:8080/login").unwrap();
+    let set_cookies = |values: &[&str]| {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(SET_COOKIE, value.parse().unwrap());
+        }
+        headers
+    };
+
+    assert_eq!(jar.header_for(&url).unwrap(), None);
+    jar.store(
+        &url,
+        &set_cookies(&["session=abc; Path=/", "theme=dark; Path=/login"]),
+    )
+    .unwrap();
+    assert_eq!(jar.load().unwrap().len(), 2);
+    assert_eq!(
+        jar.header_for(&url).unwrap().as_deref(),
+        Some("theme=dark; session=abc")
+    );
+
+    jar.store(
+        &url,
+        &set_cookies(&["session=def; Path=/", "theme=; Path=/login; Max-Age=0"]),
+    )
+    .unwrap();
+    assert_eq!(
+        jar.header_for(&url).unwrap().as_deref(),
+        Some("session=def")
+    );
+
+    jar.clear().unwrap();
+    assert!(jar.load().unwrap().is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,661 @@
+//! Converting between curl commands and request files.
+
+use crate::{
+    auth::{self, Auth, KeyLocation},
+    body::{Body, PartValue},
+    request::Request,
+};
+use anyhow::{anyhow, Result};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Method;
+use serde_json::json;
+
+/// The parts of a curl command that make up a request.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CurlCommand {
+    pub method: Option<String>,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// `-d` and similar values, joined with `&` when sent, as curl does.
+    pub data: Vec<String>,
+    /// A file sent as the whole body, from `-d @path` or `-T path`.
+    pub data_file: Option<String>,
+    /// `-F` fields, as `name=value` or `name=@path`.
+    pub form: Vec<String>,
+    /// `-u` credentials, as `user:pass`.
+    pub user: Option<String>,
+    /// `-G`, which sends the data in the query instead of the body.
+    pub get: bool,
+    /// `-I`, which sends a HEAD request.
+    pub head: bool,
+}
+
+/// Short options that take a value, whether or not they're handled.
+const SHORT_WITH_VALUE: &str = "AbcCdDeEFHKmoPQrTuUwxXyYz";
+
+/// Long options that take a value but don't affect the request file.
+const IGNORED_WITH_VALUE: [&str; 14] = [
+    "--output",
+    "--max-time",
+    "--connect-timeout",
+    "--proxy",
+    "--cacert",
+    "--cert",
+    "--key",
+    "--write-out",
+    "--retry",
+    "--retry-delay",
+    "--resolve",
+    "--cookie-jar",
+    "--range",
+    "--max-redirs",
+];
+
+impl CurlCommand {
+    /// Parses a curl command as it would be pasted into a shell, with or without the leading
+    /// `curl`. Options that don't change the request, like `-s` or `-L`, are ignored.
+    pub fn parse(command: &str) -> Result<Self> {
+        let mut words = split_words(command)?.into_iter().peekable();
+        words.next_if(|word| word == "curl");
+
+        let mut curl = CurlCommand::default();
+        while let Some(word) = words.next() {
+            let (option, attached, takes_value) = match word.as_str() {
+                long if long.starts_with("--") => match long.split_once('=') {
+                    Some((option, value)) => (option.to_owned(), Some(value.to_owned()), true),
+                    None => (long.to_owned(), None, takes_value(long)),
+                },
+                short if short.starts_with('-') && short.len() > 1 => {
+                    // Flags can be combined, like `-sSL`, and the last may take a value, like
+                    // `-sXPOST`.
+                    let flags = &short[1..];
+                    match flags.find(|c| SHORT_WITH_VALUE.contains(c)) {
+                        Some(i) => {
+                            let value = &flags[i + 1..];
+                            (
+                                format!("-{}", &flags[i..i + 1]),
+                                (!value.is_empty()).then(|| value.to_owned()),
+                                true,
+                            )
+                        }
+                        None => {
+                            curl.set_flags(flags);
+                            continue;
+                        }
+                    }
+                }
+                _ => {
+                    curl.url = word;
+                    continue;
+                }
+            };
+
+            let value = match (attached, takes_value) {
+                (Some(value), _) => value,
+                (None, true) => words
+                    .next()
+                    .ok_or_else(|| anyhow!("'{}' needs a value.", option))?,
+                (None, false) => {
+                    curl.set_flags(option.trim_start_matches('-'));
+                    continue;
+                }
+            };
+            curl.set_option(&option, value)?;
+        }
+
+        if curl.url.is_empty() {
+            return Err(anyhow!("The curl command has no URL."));
+        }
+        if !curl.url.contains("://") {
+            curl.url = format!("http://{}", curl.url);
+        }
+        Ok(curl)
+    }
+
+    fn set_flags(&mut self, flags: &str) {
+        match flags {
+            "get" => self.get = true,
+            "head" => self.head = true,
+            _ => {
+                for flag in flags.chars() {
+                    match flag {
+                        'G' => self.get = true,
+                        'I' => self.head = true,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_option(&mut self, option: &str, value: String) -> Result<()> {
+        match option {
+            "-X" | "--request" => self.method = Some(value),
+            "-H" | "--header" => {
+                // `Name;` sends an empty header and `Name:` removes one, neither of which a
+                // request file can express.
+                if let Some((name, value)) =
+                    value.split_once(':').filter(|(_, v)| !v.trim().is_empty())
+                {
+                    self.headers
+                        .push((name.trim().to_owned(), value.trim().to_owned()));
+                }
+            }
+            "-d" | "--data" | "--data-binary" | "--data-ascii" => match value.strip_prefix('@') {
+                Some(path) => self.set_data_file(path)?,
+                None => self.data.push(value),
+            },
+            "--data-raw" => self.data.push(value),
+            "--data-urlencode" => self.data.push(urlencode_data(&value)?),
+            "--json" => {
+                self.data.push(value);
+                for name in ["Content-Type", "Accept"] {
+                    if self.header(name).is_none() {
+                        self.headers
+                            .push((name.to_owned(), "application/json".to_owned()));
+                    }
+                }
+            }
+            "-T" | "--upload-file" => {
+                self.set_data_file(&value)?;
+                self.method.get_or_insert_with(|| "PUT".to_owned());
+            }
+            "-F" | "--form" | "--form-string" => self.form.push(value),
+            "-u" | "--user" => self.user = Some(value),
+            "-A" | "--user-agent" => self.headers.push(("User-Agent".to_owned(), value)),
+            "-e" | "--referer" => self.headers.push(("Referer".to_owned(), value)),
+            // Without a `=`, the value is a file of cookies to read.
+            "-b" | "--cookie" if value.contains('=') => {
+                self.headers.push(("Cookie".to_owned(), value))
+            }
+            "--url" => self.url = value,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn set_data_file(&mut self, path: &str) -> Result<()> {
+        if self.data_file.is_some() {
+            return Err(anyhow!("Only one file can be sent as the body."));
+        }
+        self.data_file = Some(path.to_owned());
+        Ok(())
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn method(&self) -> String {
+        match &self.method {
+            Some(method) => method.to_uppercase(),
+            None if self.head => "HEAD".to_owned(),
+            None if !self.form.is_empty() || (!self.get && !self.data.is_empty()) => {
+                "POST".to_owned()
+            }
+            None => "GET".to_owned(),
+        }
+    }
+
+    /// A request name for the command, from the last segment of the URL path.
+    pub fn default_name(&self) -> String {
+        let path = self.url.split("://").nth(1).unwrap_or(&self.url);
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let segment = path
+            .split_once('/')
+            .map_or("", |(_, path)| path)
+            .rsplit('/')
+            .find(|s| !s.is_empty())
+            .unwrap_or_default();
+        let name: String = segment
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        match name.trim_matches('-') {
+            "" => "request".to_owned(),
+            name => name.to_owned(),
+        }
+    }
+
+    /// Writes the request file the command amounts to.
+    pub fn to_request_file(&self) -> Result<String> {
+        if self.data_file.is_some() && !(self.data.is_empty() && self.form.is_empty()) {
+            return Err(anyhow!(
+                "A file can't be sent as the body along with other data."
+            ));
+        }
+        if !self.data.is_empty() && !self.form.is_empty() {
+            return Err(anyhow!("curl can't send both -d data and -F fields."));
+        }
+
+        let mut lines = vec![];
+        if let Some(user) = &self.user {
+            let (user, pass) = user.split_once(':').unwrap_or((user, ""));
+            let mapping = json!({ "type": "basic", "user": user, "pass": pass });
+            lines.push(format!("@auth {}", auth::directive(&mapping)));
+        }
+        if !self.form.is_empty() {
+            lines.push("@multipart".to_owned());
+        }
+        if let Some(path) = &self.data_file {
+            lines.push(format!("@body-file {}", path));
+        }
+
+        let mut url = self.url.clone();
+        if self.get && !self.data.is_empty() {
+            url.push(if url.contains('?') { '&' } else { '?' });
+            url.push_str(&self.data.join("&"));
+        }
+        lines.push(format!("{} {}", self.method(), url));
+
+        for (name, value) in &self.headers {
+            lines.push(format!("{}: {}", name, value));
+        }
+        let body = match (&self.form[..], &self.data[..]) {
+            ([], []) => None,
+            ([], _) if self.get => None,
+            ([], data) => {
+                // curl sends data as a form unless told otherwise.
+                if self.header("Content-Type").is_none() {
+                    lines.push("Content-Type: application/x-www-form-urlencoded".to_owned());
+                }
+                Some(data.join("&"))
+            }
+            (form, _) => Some(form.join("\n")),
+        };
+
+        // The body is sent as written, so it doesn't get a final newline it didn't have.
+        Ok(match body {
+            Some(body) => format!("{}\n\n{}", lines.join("\n"), body),
+            None => format!("{}\n", lines.join("\n")),
+        })
+    }
+}
+
+/// Writes a curl command that sends a parsed request the way reqq would, one option per
+/// line. OAuth2 auth has to be replaced with the token it yields first.
+pub fn to_curl(req: &Request) -> Result<String> {
+    let inner = req
+        .inner()
+        .ok_or_else(|| anyhow!("Request has not been parsed."))?;
+    let mut url = inner.url().clone();
+    let mut args: Vec<String> = vec![];
+    let mut push =
+        |option: &str, value: &str| args.push(format!("{} {}", option, shell_quote(value)));
+
+    for (name, value) in inner.headers() {
+        push(
+            "-H",
+            &format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes())),
+        );
+    }
+
+    match req.auth() {
+        Some(Auth::Basic { user, pass }) => push("-u", &format!("{}:{}", user, pass)),
+        Some(Auth::Bearer { token }) => push("-H", &format!("Authorization: Bearer {}", token)),
+        Some(Auth::ApiKey {
+            name,
+            value,
+            location: KeyLocation::Header,
+        }) => push("-H", &format!("{}: {}", name, value)),
+        Some(Auth::ApiKey {
+            name,
+            value,
+            location: KeyLocation::Query,
+        }) => {
+            url.query_pairs_mut().append_pair(name, value);
+        }
+        Some(Auth::Aws(creds)) => {
+            push(
+                "--aws-sigv4",
+                &format!("aws:amz:{}:{}", creds.region, creds.service),
+            );
+            push("-u", &format!("{}:{}", creds.access_key, creds.secret_key));
+            if let Some(token) = &creds.session_token {
+                push("-H", &format!("x-amz-security-token: {}", token));
+            }
+        }
+        Some(Auth::OAuth2(_)) => {
+            return Err(anyhow!(
+                "OAuth2 auth needs a token before it can be written as curl."
+            ))
+        }
+        Some(Auth::None) | None => {}
+    }
+
+    let has_content_type = inner.headers().iter().any(|(name, _)| name == CONTENT_TYPE);
+    match inner.body() {
+        Some(Body::Multipart(parts)) => {
+            for part in parts {
+                match &part.value {
+                    PartValue::Text(value) => {
+                        push("--form-string", &format!("{}={}", part.name, value))
+                    }
+                    PartValue::File(path) => {
+                        push("-F", &format!("{}=@{}", part.name, path.display()))
+                    }
+                }
+            }
+        }
+        Some(Body::File(path)) => push("--data-binary", &format!("@{}", path.display())),
+        Some(body) => {
+            let encoded = body.encode()?;
+            if let (Some(content_type), false) = (encoded.content_type, has_content_type) {
+                push("-H", &format!("{}: {}", CONTENT_TYPE, content_type));
+            }
+            push("--data-raw", &String::from_utf8_lossy(&encoded.bytes));
+        }
+        None => {}
+    }
+
+    if let Some(timeout) = req.timeout() {
+        push("--max-time", &format!("{}", timeout.as_secs_f64()));
+    }
+    let client = req.client_opts();
+    if let Some(proxy) = &client.proxy {
+        push("-x", proxy);
+    }
+    for (option, path) in [
+        ("--cacert", &client.cacert),
+        ("--cert", &client.cert),
+        ("--key", &client.key),
+    ] {
+        if let Some(path) = path {
+            push(option, &path.display().to_string());
+        }
+    }
+    if client.insecure {
+        args.push("-k".to_owned());
+    }
+
+    // curl picks the method from the options, so it's only given when they'd pick another.
+    let has_body = inner.body().is_some();
+    let method = match (inner.method(), has_body) {
+        (&Method::GET, false) | (&Method::POST, true) => None,
+        (&Method::HEAD, false) => Some("-I".to_owned()),
+        (method, _) => Some(format!("-X {}", method)),
+    };
+
+    let mut first = vec!["curl".to_owned()];
+    first.extend(method);
+    first.push(shell_quote(url.as_str()));
+    args.insert(0, first.join(" "));
+    Ok(args.join(" \\\n  "))
+}
+
+/// Quotes a word for a POSIX shell, unless it's made of characters that don't need it.
+fn shell_quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@,+%".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        return word.to_owned();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+fn takes_value(option: &str) -> bool {
+    IGNORED_WITH_VALUE.contains(&option)
+        || matches!(
+            option,
+            "--request"
+                | "--header"
+                | "--data"
+                | "--data-raw"
+                | "--data-binary"
+                | "--data-ascii"
+                | "--data-urlencode"
+                | "--json"
+                | "--form"
+                | "--form-string"
+                | "--user"
+                | "--user-agent"
+                | "--referer"
+                | "--cookie"
+                | "--url"
+                | "--upload-file"
+        )
+}
+
+/// Encodes a `--data-urlencode` value: `content`, `=content` or `name=content`.
+fn urlencode_data(value: &str) -> Result<String> {
+    let encode = |s: &str| url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+    if value.starts_with('@')
+        || value
+            .split_once('=')
+            .map_or(value, |(name, _)| name)
+            .contains('@')
+    {
+        return Err(anyhow!(
+            "--data-urlencode with a file isn't supported: '{}'.",
+            value
+        ));
+    }
+    Ok(match value.split_once('=') {
+        Some(("", content)) => encode(content),
+        Some((name, content)) => format!("{}={}", name, encode(content)),
+        None => encode(value),
+    })
+}
+
+/// Splits a command into words the way a POSIX shell would, handling quotes, escapes, line
+/// continuations and the `$'...'` quotes browsers use in "Copy as cURL".
+fn split_words(command: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                words.extend(word.take());
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some(escaped) => word.get_or_insert_with(String::new).push(escaped),
+                None => return Err(anyhow!("The curl command ends with a lone '\\'.")),
+            },
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("The curl command has an unclosed ' quote.")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '\\' | '$' | '`')) => word.push(escaped),
+                            Some('\n') => {}
+                            Some(other) => {
+                                word.push('\\');
+                                word.push(other);
+                            }
+                            None => {
+                                return Err(anyhow!("The curl command has an unclosed \" quote."))
+                            }
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("The curl command has an unclosed \" quote.")),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => word.push('\n'),
+                            Some('t') => word.push('\t'),
+                            Some('r') => word.push('\r'),
+                            Some(other) => word.push(other),
+                            None => {
+                                return Err(anyhow!("The curl command has an unclosed $' quote."))
+                            }
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("The curl command has an unclosed $' quote.")),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[test]
+fn test_split_words() {
+    let words = split_words(
+        "curl -H 'X-A: 1' \\\n  -d \"{\\\"a\\\": \\\"$x\\\"}\" $'line\\nnext' it\\'s ''",
+    )
+    .unwrap();
+    assert_eq!(
+        words,
+        vec![
+            "curl",
+            "-H",
+            "X-A: 1",
+            "-d",
+            "{\"a\": \"$x\"}",
+            "line\nnext",
+            "it's",
+            ""
+        ]
+    );
+    assert!(split_words("curl 'open").is_err());
+}
+
+#[test]
+fn test_parse_curl() {
+    let curl = CurlCommand::parse(
+        "curl -sSL -XPOST https://api.example.com/v1/users?active=1 -H 'Content-Type: application/json' \
+         -H 'X-Empty;' --data-raw '{\"name\": \"Ada\"}' -u alice:secret --compressed",
+    )
+    .unwrap();
+    assert_eq!(curl.method.as_deref(), Some("POST"));
+    assert_eq!(curl.url, "https://api.example.com/v1/users?active=1");
+    assert_eq!(
+        curl.headers,
+        vec![("Content-Type".to_owned(), "application/json".to_owned())]
+    );
+    assert_eq!(curl.default_name(), "users");
+    assert_eq!(
+        curl.to_request_file().unwrap(),
+        "@auth basic alice secret\nPOST https://api.example.com/v1/users?active=1\n\
+         Content-Type: application/json\n\n{\"name\": \"Ada\"}"
+    );
+
+    assert!(CurlCommand::parse("curl -s").is_err());
+    assert!(CurlCommand::parse("curl -H").is_err());
+}
+
+#[test]
+fn test_curl_request_file() {
+    let file = |command: &str| {
+        CurlCommand::parse(command)
+            .unwrap()
+            .to_request_file()
+            .unwrap()
+    };
+
+    assert_eq!(file("example.com"), "GET http://example.com\n");
+    assert_eq!(
+        file("curl -I https://example.com/"),
+        "HEAD https://example.com/\n"
+    );
+    assert_eq!(
+        file(
+            "curl https://example.com/login -d user=ada -d 'pass=a b' --data-urlencode 'note=x&y'"
+        ),
+        "POST https://example.com/login\nContent-Type: application/x-www-form-urlencoded\n\n\
+         user=ada&pass=a b&note=x%26y"
+    );
+    assert_eq!(
+        file("curl -G https://example.com/search?sort=asc -d q=reqq"),
+        "GET https://example.com/search?sort=asc&q=reqq\n"
+    );
+    assert_eq!(
+        file("curl -F title=Me -F avatar=@me.png https://example.com/avatars"),
+        "@multipart\nPOST https://example.com/avatars\n\ntitle=Me\navatar=@me.png"
+    );
+    assert_eq!(
+        file("curl -T firmware.bin https://example.com/upload -u 'ada:two words'"),
+        "@auth {\"pass\":\"two words\",\"type\":\"basic\",\"user\":\"ada\"}\n@body-file firmware.bin\nPUT https://example.com/upload\n"
+    );
+    assert_eq!(
+        file("curl --json '{\"a\": 1}' https://example.com/items"),
+        "POST https://example.com/items\nContent-Type: application/json\nAccept: application/json\n\n{\"a\": 1}"
+    );
+
+    let both = CurlCommand::parse("curl -d a=1 -F b=2 https://example.com").unwrap();
+    assert!(both.to_request_file().is_err());
+}
+
+#[test]
+fn test_shell_quote() {
+    assert_eq!(
+        shell_quote("https://example.com/a?b=c"),
+        "'https://example.com/a?b=c'"
+    );
+    assert_eq!(shell_quote("alice:secret"), "alice:secret");
+    assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    assert_eq!(shell_quote(""), "''");
+    assert_eq!(
+        split_words(&shell_quote("a 'b' $c")).unwrap(),
+        vec!["a 'b' $c"]
+    );
+}
+
+#[test]
+fn test_to_curl() {
+    let curl = |fstr: &str| {
+        let mut req = Request::from_rendered(".reqq/users/create.reqq".to_owned(), fstr.to_owned());
+        req.parse(None, std::collections::HashMap::new()).unwrap();
+        to_curl(&req).unwrap()
+    };
+
+    assert_eq!(
+        curl("GET https://example.com/users"),
+        "curl https://example.com/users"
+    );
+    assert_eq!(
+        curl("@auth basic alice secret\n@timeout 2500ms\nPUT https://example.com/users/1\nX-Trace: a b\n\n{\"name\": \"Ada's\"}"),
+        "curl -X PUT https://example.com/users/1 \\\n  \
+         -H 'x-trace: a b' \\\n  \
+         -u alice:secret \\\n  \
+         --data-raw '{\"name\": \"Ada'\\''s\"}' \\\n  \
+         --max-time 2.5"
+    );
+    assert_eq!(
+        curl("@form\nPOST https://example.com/login\n\nuser=ada\npass=a b"),
+        "curl https://example.com/login \\\n  \
+         -H 'content-type: application/x-www-form-urlencoded' \\\n  \
+         --data-raw 'user=ada&pass=a+b'"
+    );
+
+    // The exported command is parsed back into the same request.
+    let exported = curl("@auth apikey query key k1\nDELETE https://example.com/users/1");
+    let parsed = CurlCommand::parse(&exported).unwrap();
+    assert_eq!(parsed.method.as_deref(), Some("DELETE"));
+    assert_eq!(parsed.url, "https://example.com/users/1?key=k1");
+}
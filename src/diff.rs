@@ -0,0 +1,298 @@
+//! Comparing the responses of a request sent to two environments.
+
+use crate::response::Response;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Headers that differ between any two responses, whatever the environment.
+const IGNORED_HEADERS: [&str; 4] = ["date", "age", "expires", "content-length"];
+
+/// One way two responses differ.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// Something only the second response has, like a header or a field of the body.
+    Added { path: String, value: String },
+    /// Something only the first response has.
+    Removed { path: String, value: String },
+    /// Something both responses have, with different values.
+    Changed {
+        path: String,
+        left: String,
+        right: String,
+    },
+}
+
+/// How the responses of a request differ between two environments.
+#[derive(Clone, Debug)]
+pub struct ResponseDiff {
+    pub left: String,
+    pub right: String,
+    pub differences: Vec<Difference>,
+}
+
+impl ResponseDiff {
+    /// Compares the status, headers and body of two responses. Bodies are compared field by
+    /// field when both are JSON, and line by line otherwise.
+    pub fn new(left: (&str, &Response), right: (&str, &Response)) -> Self {
+        let (left_name, left) = left;
+        let (right_name, right) = right;
+        let mut differences = vec![];
+        if left.status != right.status {
+            differences.push(Difference::Changed {
+                path: "status".to_owned(),
+                left: left.status.to_string(),
+                right: right.status.to_string(),
+            });
+        }
+        differences.extend(headers(left, right));
+        match (
+            serde_json::from_str(&left.body),
+            serde_json::from_str(&right.body),
+        ) {
+            (Ok(left), Ok(right)) => json(&left, &right, "$".to_owned(), &mut differences),
+            _ => differences.extend(lines(&left.body, &right.body)),
+        }
+        ResponseDiff {
+            left: left_name.to_owned(),
+            right: right_name.to_owned(),
+            differences,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// A line per difference: `-` for what only the first env has, `+` for what only the
+    /// second has, and `~` for changed values.
+    pub fn report(&self) -> String {
+        let mut lines = vec![format!("--- {}", self.left), format!("+++ {}", self.right)];
+        for difference in self.differences.iter() {
+            lines.push(match difference {
+                Difference::Added { path, value } => format!("+ {}: {}", path, value),
+                Difference::Removed { path, value } => format!("- {}: {}", path, value),
+                Difference::Changed { path, left, right } => {
+                    format!("~ {}: {} -> {}", path, left, right)
+                }
+            });
+        }
+        if self.is_empty() {
+            lines.push("The responses are the same.".to_owned());
+        }
+        lines.join("\n")
+    }
+}
+
+fn headers(left: &Response, right: &Response) -> Vec<Difference> {
+    let names: BTreeSet<&str> = left
+        .headers
+        .keys()
+        .chain(right.headers.keys())
+        .map(|name| name.as_str())
+        .filter(|name| !IGNORED_HEADERS.contains(name))
+        .collect();
+
+    let values = |resp: &Response, name: &str| -> Option<String> {
+        let values: Vec<String> = resp
+            .headers
+            .get_all(name)
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+            .collect();
+        (!values.is_empty()).then(|| values.join(", "))
+    };
+
+    let mut differences = vec![];
+    for name in names {
+        let path = format!("header {}", name);
+        match (values(left, name), values(right, name)) {
+            (Some(left), Some(right)) if left != right => {
+                differences.push(Difference::Changed { path, left, right })
+            }
+            (Some(value), None) => differences.push(Difference::Removed { path, value }),
+            (None, Some(value)) => differences.push(Difference::Added { path, value }),
+            _ => {}
+        }
+    }
+    differences
+}
+
+/// Compares JSON values, descending into objects and arrays, with paths like `$.items[0].id`.
+fn json(left: &Value, right: &Value, path: String, differences: &mut Vec<Difference>) {
+    match (left, right) {
+        (Value::Object(left), Value::Object(right)) => {
+            let keys: BTreeSet<&String> = left.keys().chain(right.keys()).collect();
+            for key in keys {
+                let path = format!("{}{}", path, key_segment(key));
+                match (left.get(key), right.get(key)) {
+                    (Some(l), Some(r)) => json(l, r, path, differences),
+                    (Some(value), None) => differences.push(Difference::Removed {
+                        path,
+                        value: value.to_string(),
+                    }),
+                    (None, Some(value)) => differences.push(Difference::Added {
+                        path,
+                        value: value.to_string(),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Value::Array(left), Value::Array(right)) => {
+            for i in 0..left.len().max(right.len()) {
+                let path = format!("{}[{}]", path, i);
+                match (left.get(i), right.get(i)) {
+                    (Some(l), Some(r)) => json(l, r, path, differences),
+                    (Some(value), None) => differences.push(Difference::Removed {
+                        path,
+                        value: value.to_string(),
+                    }),
+                    (None, Some(value)) => differences.push(Difference::Added {
+                        path,
+                        value: value.to_string(),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if left != right => differences.push(Difference::Changed {
+            path,
+            left: left.to_string(),
+            right: right.to_string(),
+        }),
+        _ => {}
+    }
+}
+
+/// `.key` for plain keys, and `['key']` for those JSONPath needs quoted.
+fn key_segment(key: &str) -> String {
+    let plain = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if plain {
+        format!(".{}", key)
+    } else {
+        format!("['{}']", key.replace('\'', "\\'"))
+    }
+}
+
+/// The lines of two texts that aren't part of their longest common subsequence, with their
+/// line numbers.
+fn lines(left: &str, right: &str) -> Vec<Difference> {
+    let left: Vec<&str> = left.lines().collect();
+    let right: Vec<&str> = right.lines().collect();
+
+    // lengths[i][j] is the length of the longest common subsequence of left[i..] and right[j..].
+    let mut lengths = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+    for i in (0..left.len()).rev() {
+        for j in (0..right.len()).rev() {
+            lengths[i][j] = if left[i] == right[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut differences = vec![];
+    while i < left.len() || j < right.len() {
+        if i < left.len() && j < right.len() && left[i] == right[j] {
+            i += 1;
+            j += 1;
+        } else if j == right.len() || (i < left.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            differences.push(Difference::Removed {
+                path: format!("line {}", i + 1),
+                value: left[i].to_owned(),
+            });
+            i += 1;
+        } else {
+            differences.push(Difference::Added {
+                path: format!("line {}", j + 1),
+                value: right[j].to_owned(),
+            });
+            j += 1;
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+fn test_response(status: u16, headers: &[(&str, &str)], body: &str) -> Response {
+    let mut map = http::HeaderMap::new();
+    for (name, value) in headers {
+        map.append(
+            http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+    }
+    Response {
+        status: reqwest::StatusCode::from_u16(status).unwrap(),
+        version: reqwest::Version::HTTP_11,
+        headers: map,
+        body: body.to_owned(),
+        binary: None,
+    }
+}
+
+#[test]
+fn test_diff_json() {
+    let left = test_response(
+        200,
+        &[
+            ("content-type", "application/json"),
+            ("date", "Mon"),
+            ("x-debug", "1"),
+        ],
+        r#"{"version": "1.2", "items": [{"id": 1}, {"id": 2}], "a b": 1, "same": true}"#,
+    );
+    let right = test_response(
+        201,
+        &[("content-type", "application/json"), ("date", "Tue")],
+        r#"{"version": "1.3", "items": [{"id": 1}], "a b": 1, "same": true, "beta": false}"#,
+    );
+
+    let diff = ResponseDiff::new(("staging", &left), ("prod", &right));
+    assert_eq!(
+        diff.report(),
+        "--- staging
++++ prod
+~ status: 200 OK -> 201 Created
+- header x-debug: 1
++ $.beta: false
+- $.items[1]: {\"id\":2}
+~ $.version: \"1.2\" -> \"1.3\""
+    );
+}
+
+#[test]
+fn test_diff_same() {
+    let resp = test_response(200, &[("date", "Mon")], "ok");
+    let diff = ResponseDiff::new(
+        ("a", &resp),
+        ("b", &test_response(200, &[("date", "Tue")], "ok")),
+    );
+    assert!(diff.is_empty());
+    assert_eq!(diff.report(), "--- a\n+++ b\nThe responses are the same.");
+}
+
+#[test]
+fn test_diff_lines() {
+    assert_eq!(
+        lines("a\nb\nc", "a\nc\nd"),
+        vec![
+            Difference::Removed {
+                path: "line 2".to_owned(),
+                value: "b".to_owned()
+            },
+            Difference::Added {
+                path: "line 3".to_owned(),
+                value: "d".to_owned()
+            },
+        ]
+    );
+    assert_eq!(key_segment("a b"), "['a b']");
+    assert_eq!(key_segment("user_id"), ".user_id");
+}
@@ -0,0 +1,185 @@
+use crate::{response::Response, stream};
+use anyhow::{anyhow, Result};
+use percent_encoding::percent_decode_str;
+use reqwest::header::{HeaderMap, CONTENT_DISPOSITION, CONTENT_LENGTH};
+use std::fs::File;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Where a response body goes instead of being read into memory and printed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    /// A given path, from `-o/--output`.
+    File(PathBuf),
+    /// A file in the current folder named after the `Content-Disposition` header or the URL,
+    /// from `-O`.
+    Infer,
+    /// Stdout, printed as it arrives, from `--stream`.
+    Stream,
+}
+
+/// Responses at least this large get a progress bar, when stderr is a terminal.
+const PROGRESS_THRESHOLD: u64 = 1024 * 1024;
+
+/// How often the progress bar is redrawn.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Streams the body of a response to a file, or stdout for `Output::Stream`, and returns the
+/// response without its body.
+pub fn save(mut resp: reqwest::blocking::Response, output: &Output) -> Result<Response> {
+    let path = match output {
+        Output::File(path) => path.clone(),
+        Output::Infer => PathBuf::from(infer_filename(resp.url(), resp.headers())),
+        Output::Stream => return stream::print(resp),
+    };
+    let total = resp
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let show_progress =
+        std::io::stderr().is_terminal() && total.is_none_or(|total| total >= PROGRESS_THRESHOLD);
+
+    let mut file =
+        File::create(&path).map_err(|e| anyhow!("Couldn't create '{}': {}", path.display(), e))?;
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut written = 0u64;
+    let mut last_drawn = Instant::now();
+    loop {
+        let n = resp.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n])?;
+        written += n as u64;
+        if show_progress && last_drawn.elapsed() >= PROGRESS_INTERVAL {
+            eprint!("\r{}", progress_line(written, total));
+            last_drawn = Instant::now();
+        }
+    }
+    if show_progress {
+        eprintln!("\r{}", progress_line(written, total));
+    }
+    eprintln!("Saved {} to {}", format_bytes(written), path.display());
+
+    Ok(Response {
+        status: resp.status(),
+        version: resp.version(),
+        headers: resp.headers().clone(),
+        body: String::new(),
+        binary: None,
+    })
+}
+
+/// The file name given by a `Content-Disposition` header, or else the last segment of the URL
+/// path. Only the final path component is used, so a server can't pick where the file goes.
+fn infer_filename(url: &Url, headers: &HeaderMap) -> String {
+    let from_header = headers
+        .get(CONTENT_DISPOSITION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(disposition_filename);
+    let from_url = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned());
+
+    [from_header, from_url]
+        .into_iter()
+        .flatten()
+        .filter_map(|name| {
+            Path::new(&name)
+                .file_name()
+                .map(|f| f.to_string_lossy().into_owned())
+        })
+        .find(|name| name != "." && name != "..")
+        .unwrap_or_else(|| "download".to_owned())
+}
+
+/// Reads the file name from a `Content-Disposition` header, preferring the RFC 5987
+/// `filename*=UTF-8''...` form.
+fn disposition_filename(header: &str) -> Option<String> {
+    let params: Vec<(String, &str)> = header
+        .split(';')
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim()))
+        .collect();
+
+    let extended = params
+        .iter()
+        .find(|(key, _)| key == "filename*")
+        .and_then(|(_, value)| value.split_once("''"))
+        .map(|(_, encoded)| percent_decode_str(encoded).decode_utf8_lossy().into_owned());
+    let plain = params
+        .iter()
+        .find(|(key, _)| key == "filename")
+        .map(|(_, value)| value.trim_matches('"').to_owned());
+    extended.or(plain).filter(|name| !name.is_empty())
+}
+
+fn progress_line(written: u64, total: Option<u64>) -> String {
+    const WIDTH: u64 = 30;
+    match total {
+        Some(total) if total > 0 => {
+            let filled = (written.min(total) * WIDTH / total) as usize;
+            format!(
+                "[{}{}] {:>3}% {} / {}",
+                "#".repeat(filled),
+                "-".repeat(WIDTH as usize - filled),
+                written.min(total) * 100 / total,
+                format_bytes(written),
+                format_bytes(total)
+            )
+        }
+        _ => format!("{} downloaded", format_bytes(written)),
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 * 1024 => format!("{:.1} GB", b as f64 / (1024.0 * 1024.0 * 1024.0)),
+        b if b >= 1024 * 1024 => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{} bytes", b),
+    }
+}
+
+#[test]
+fn test_infer_filename() {
+    let url = Url::parse("https://example.com/files/report%202026.pdf?v=2").unwrap();
+    let mut headers = HeaderMap::new();
+    assert_eq!(infer_filename(&url, &headers), "report 2026.pdf");
+
+    headers.insert(
+        CONTENT_DISPOSITION,
+        "attachment; filename=\"summary.csv\"".parse().unwrap(),
+    );
+    assert_eq!(infer_filename(&url, &headers), "summary.csv");
+
+    headers.insert(
+        CONTENT_DISPOSITION,
+        "attachment; filename=\"fallback.txt\"; filename*=UTF-8''r%C3%A9sum%C3%A9.txt"
+            .parse()
+            .unwrap(),
+    );
+    assert_eq!(infer_filename(&url, &headers), "résumé.txt");
+
+    headers.insert(
+        CONTENT_DISPOSITION,
+        "attachment; filename=\"../../etc/passwd\"".parse().unwrap(),
+    );
+    assert_eq!(infer_filename(&url, &headers), "passwd");
+
+    let root = Url::parse("https://example.com/").unwrap();
+    assert_eq!(infer_filename(&root, &HeaderMap::new()), "download");
+}
+
+#[test]
+fn test_progress_line() {
+    assert_eq!(
+        progress_line(512 * 1024, Some(2 * 1024 * 1024)),
+        "[#######-----------------------]  25% 512.0 KB / 2.0 MB"
+    );
+    assert_eq!(progress_line(2048, None), "2.0 KB downloaded");
+}
@@ -1,5 +1,10 @@
+use crate::{redact, yaml};
+use anyhow::anyhow;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::collections::HashMap;
+use std::path::Path;
+use walkdir::WalkDir;
 
 #[derive(Clone)]
 pub struct Env {
@@ -10,17 +15,45 @@ pub struct Env {
 
 type Result<T> = std::result::Result<T, anyhow::Error>;
 
+/// The section of an environment whose variables are secrets, masked in what reqq prints and
+/// records.
+const SECRETS: &str = "secrets";
+
+/// File extensions of the supported environment formats.
+const EXTENSIONS: [&str; 4] = [".json", ".yaml", ".yml", ".env"];
+
 impl Env {
     pub fn new(fpath: String) -> Self {
         Env { fpath, fstr: None }
     }
 
+    /// Finds all environment files in the `envs` folder of a reqq directory.
+    pub fn find_all(dir: &str) -> Vec<Env> {
+        let mut envs: Vec<Env> = WalkDir::new(format!("{}/envs", dir))
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| {
+                EXTENSIONS
+                    .iter()
+                    .any(|ext| e.path().to_string_lossy().ends_with(ext))
+            })
+            .map(|e| Env::new(e.path().display().to_string()))
+            .collect();
+        envs.sort_by(|a, b| a.fpath.cmp(&b.fpath));
+        envs
+    }
+
     // TODO: Pull this into some kind of Namer trait?
     pub fn name(&self, dir: &str) -> String {
-        self.fpath
+        let name = self
+            .fpath
             .trim_start_matches(dir)
-            .trim_start_matches("/envs/")
-            .trim_end_matches(".json")
+            .trim_start_matches("/envs/");
+        EXTENSIONS
+            .iter()
+            .find_map(|ext| name.strip_suffix(ext))
+            .unwrap_or(name)
             .into()
     }
 
@@ -32,8 +65,324 @@ impl Env {
         Ok(())
     }
 
+    /// The environment's variables, including those inherited through `extends`. An env can
+    /// name another env in the same folder with `"extends": "base"` to use its variables as
+    /// defaults, and override just the keys that differ. The variables of its `secrets`
+    /// section are variables like any other.
     pub fn to_hashmap(&self) -> Result<HashMap<String, serde_json::Value>> {
-        let v:HashMap<String, serde_json::Value> = serde_json::from_str(self.fstr.clone().unwrap().as_str()).unwrap();
-        Ok(v)
+        let mut vars = self.resolve(&mut vec![])?;
+        if let Some(serde_json::Value::Object(secrets)) = vars.get(SECRETS).cloned() {
+            vars.remove(SECRETS);
+            vars.extend(secrets);
+        }
+        Ok(vars)
+    }
+
+    /// The names of the variables in the environment's `secrets` section.
+    pub fn secret_names(&self) -> Result<Vec<String>> {
+        match self.resolve(&mut vec![])?.remove(SECRETS) {
+            Some(serde_json::Value::Object(secrets)) => {
+                Ok(secrets.into_iter().map(|(name, _)| name).collect())
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn resolve(&self, chain: &mut Vec<String>) -> Result<HashMap<String, serde_json::Value>> {
+        if chain.contains(&self.fpath) {
+            chain.push(self.fpath.clone());
+            return Err(anyhow!(
+                "Environments extend each other in a cycle: {}",
+                chain.join(" -> ")
+            ));
+        }
+        chain.push(self.fpath.clone());
+
+        let mut vars = self.own_variables()?;
+        match vars.remove("extends") {
+            Some(serde_json::Value::String(base)) => {
+                let mut base_env = self.sibling(&base)?;
+                base_env.load()?;
+                let mut merged = base_env.resolve(chain)?;
+                // Secrets are merged too, rather than replaced as a whole.
+                if let (
+                    Some(serde_json::Value::Object(base)),
+                    Some(serde_json::Value::Object(own)),
+                ) = (merged.get(SECRETS), vars.get_mut(SECRETS))
+                {
+                    for (name, value) in base {
+                        own.entry(name.clone()).or_insert_with(|| value.clone());
+                    }
+                }
+                merged.extend(vars);
+                Ok(merged)
+            }
+            Some(_) => Err(anyhow!(
+                "'extends' in environment '{}' must be a name.",
+                self.fpath
+            )),
+            None => Ok(vars),
+        }
+    }
+
+    /// Finds an env by name in the same folder as this one.
+    fn sibling(&self, name: &str) -> Result<Env> {
+        let folder = Path::new(&self.fpath)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        EXTENSIONS
+            .iter()
+            .map(|ext| folder.join(format!("{}{}", name, ext)))
+            .find(|path| path.is_file())
+            .map(|path| Env::new(path.display().to_string()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Environment '{}' extends '{}', which wasn't found.",
+                    self.fpath,
+                    name
+                )
+            })
     }
+
+    fn own_variables(&self) -> Result<HashMap<String, serde_json::Value>> {
+        let fstr = self
+            .fstr
+            .as_deref()
+            .ok_or_else(|| anyhow!("Environment '{}' has not been loaded.", self.fpath))?;
+        let parsed = if self.fpath.ends_with(".yaml") || self.fpath.ends_with(".yml") {
+            yaml::parse(fstr).and_then(to_variables)
+        } else if self.fpath.ends_with(".env") {
+            parse_dotenv(fstr)
+        } else {
+            serde_json::from_str(fstr).map_err(anyhow::Error::from)
+        };
+        parsed.map_err(|e| anyhow!("Failed to parse environment '{}': {}", self.fpath, e))
+    }
+
+    /// Lists the loaded variables as `key: value` lines, sorted by key, with values in JSON
+    /// so their types are visible. Secrets, and variables whose names match the pattern, are
+    /// masked.
+    pub fn to_display_string(&self, secret_pattern: Option<&Regex>) -> Result<String> {
+        let vars: BTreeMap<String, serde_json::Value> = self.to_hashmap()?.into_iter().collect();
+        let secrets = self.secret_names()?;
+        let lines: Vec<String> = vars
+            .iter()
+            .map(|(k, v)| {
+                if secrets.contains(k) || secret_pattern.is_some_and(|p| p.is_match(k)) {
+                    format!("{}: {}", k, redact::MASK)
+                } else {
+                    format!("{}: {}", k, v)
+                }
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+}
+
+fn to_variables(value: serde_json::Value) -> Result<HashMap<String, serde_json::Value>> {
+    match value {
+        serde_json::Value::Object(map) => Ok(map.into_iter().collect()),
+        serde_json::Value::Null => Ok(HashMap::new()),
+        _ => Err(anyhow!("expected a mapping of variables")),
+    }
+}
+
+/// Parses a dotenv file: `KEY=value` lines, optionally prefixed with `export`, with `#`
+/// comments. Single-quoted values are taken literally, double-quoted values support `\n`,
+/// `\t`, `\"` and `\\` escapes, and unquoted values end at an inline ` #` comment.
+fn parse_dotenv(fstr: &str) -> Result<HashMap<String, serde_json::Value>> {
+    let mut vars = HashMap::new();
+
+    for (i, line) in fstr.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected KEY=value", i + 1))?;
+        let value = value.trim();
+
+        let value = if let Some(quoted) = value.strip_prefix('\'') {
+            quoted
+                .split_once('\'')
+                .map(|(v, _)| v.to_owned())
+                .ok_or_else(|| anyhow!("line {}: unterminated quote", i + 1))?
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            unescape_dotenv(quoted).ok_or_else(|| anyhow!("line {}: unterminated quote", i + 1))?
+        } else {
+            value.split(" #").next().unwrap_or("").trim_end().to_owned()
+        };
+
+        vars.insert(key.trim().to_owned(), serde_json::Value::String(value));
+    }
+
+    Ok(vars)
+}
+
+/// Unescapes a double-quoted dotenv value, given the text after the opening quote.
+fn unescape_dotenv(quoted: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                other => value.push(other),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[test]
+fn test_env_name() {
+    let env = Env::new(".reqq/envs/nested/staging.json".to_owned());
+    assert_eq!(env.name(".reqq"), "nested/staging");
+
+    let env = Env::new(".reqq/envs/local.env".to_owned());
+    assert_eq!(env.name(".reqq"), "local");
+
+    let env = Env::new(".reqq/envs/prod.yml".to_owned());
+    assert_eq!(env.name(".reqq"), "prod");
+}
+
+#[test]
+fn test_env_yaml() {
+    let env = Env {
+        fpath: ".reqq/envs/staging.yaml".to_owned(),
+        fstr: Some("baseUrl: https://example.com\nretries: 3\n".to_owned()),
+    };
+    let vars = env.to_hashmap().unwrap();
+    assert_eq!(vars["baseUrl"], serde_json::json!("https://example.com"));
+    assert_eq!(vars["retries"], serde_json::json!(3));
+
+    let env = Env {
+        fpath: ".reqq/envs/list.yml".to_owned(),
+        fstr: Some("- a\n- b\n".to_owned()),
+    };
+    assert!(env.to_hashmap().is_err());
+}
+
+#[test]
+fn test_env_dotenv() {
+    let fstr = "# Secrets.
+API_TOKEN=abc123
+export SECRET = 'lit $ral # kept'
+GREETING=\"hello\\nworld\"
+PLAIN=value # comment
+EMPTY=
+"
+    .to_owned();
+    let env = Env {
+        fpath: ".reqq/envs/local.env".to_owned(),
+        fstr: Some(fstr),
+    };
+    let vars = env.to_hashmap().unwrap();
+    assert_eq!(vars.len(), 5);
+    assert_eq!(vars["API_TOKEN"], serde_json::json!("abc123"));
+    assert_eq!(vars["SECRET"], serde_json::json!("lit $ral # kept"));
+    assert_eq!(vars["GREETING"], serde_json::json!("hello\nworld"));
+    assert_eq!(vars["PLAIN"], serde_json::json!("value"));
+    assert_eq!(vars["EMPTY"], serde_json::json!(""));
+
+    let env = Env {
+        fpath: ".reqq/envs/broken.env".to_owned(),
+        fstr: Some("NO_EQUALS".to_owned()),
+    };
+    assert!(env.to_hashmap().is_err());
+}
+
+#[test]
+fn test_env_display() {
+    let env = Env {
+        fpath: ".reqq/envs/test.json".to_owned(),
+        fstr: Some(
+            "{\"secret\": \"lolol\", \"baseUrl\": \"https://example.com\", \"retries\": 3}"
+                .to_owned(),
+        ),
+    };
+    assert_eq!(
+        env.to_display_string(None).unwrap(),
+        "baseUrl: \"https://example.com\"\nretries: 3\nsecret: \"lolol\""
+    );
+    let pattern = Regex::new("(?i)secret").unwrap();
+    assert_eq!(
+        env.to_display_string(Some(&pattern)).unwrap(),
+        "baseUrl: \"https://example.com\"\nretries: 3\nsecret: *****"
+    );
+}
+
+#[test]
+fn test_env_secrets() {
+    let env = Env {
+        fpath: ".reqq/envs/prod.yaml".to_owned(),
+        fstr: Some("baseUrl: https://example.com\nsecrets:\n  token: abc123\n".to_owned()),
+    };
+    let vars = env.to_hashmap().unwrap();
+    assert_eq!(vars.len(), 2);
+    assert_eq!(vars["token"], serde_json::json!("abc123"));
+    assert_eq!(env.secret_names().unwrap(), vec!["token"]);
+    assert_eq!(
+        env.to_display_string(None).unwrap(),
+        "baseUrl: \"https://example.com\"\ntoken: *****"
+    );
+}
+
+#[test]
+fn test_env_invalid() {
+    let env = Env {
+        fpath: ".reqq/envs/broken.json".to_owned(),
+        fstr: Some("{ nope".to_owned()),
+    };
+    assert!(env.to_hashmap().is_err());
+    assert!(Env::new(".reqq/envs/unloaded.json".to_owned())
+        .to_hashmap()
+        .is_err());
+}
+
+#[test]
+fn test_env_extends() {
+    let dir = std::env::temp_dir().join(format!("reqq-env-extends-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("base.json"), "{\"baseUrl\": \"https://example.com\", \"secret\": \"base\", \"retries\": 1, \"secrets\": {\"a\": 1, \"b\": 2}}").unwrap();
+    fs::write(
+        dir.join("staging.yaml"),
+        "extends: base\nsecret: staging\nsecrets:\n  b: 3\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("local.json"),
+        "{\"extends\": \"staging\", \"retries\": 5}",
+    )
+    .unwrap();
+    fs::write(dir.join("a.json"), "{\"extends\": \"b\"}").unwrap();
+    fs::write(dir.join("b.json"), "{\"extends\": \"a\"}").unwrap();
+    fs::write(dir.join("orphan.json"), "{\"extends\": \"missing\"}").unwrap();
+
+    let load = |name: &str| {
+        let mut env = Env::new(dir.join(name).display().to_string());
+        env.load().unwrap();
+        env
+    };
+
+    let vars = load("local.json").to_hashmap().unwrap();
+    assert_eq!(vars.len(), 5);
+    assert_eq!(vars["b"], serde_json::json!(3));
+    assert_eq!(load("local.json").secret_names().unwrap(), vec!["a", "b"]);
+    assert_eq!(vars["baseUrl"], serde_json::json!("https://example.com"));
+    assert_eq!(vars["secret"], serde_json::json!("staging"));
+    assert_eq!(vars["retries"], serde_json::json!(5));
+
+    let err = load("a.json").to_hashmap().unwrap_err().to_string();
+    assert!(err.contains("cycle"), "{}", err);
+    assert!(load("orphan.json").to_hashmap().is_err());
+
+    fs::remove_dir_all(dir).unwrap();
 }
@@ -0,0 +1,230 @@
+//! Errors by kind, so library users can match on them and the CLI can print hints for them.
+//! Inside reqq they travel as `anyhow` errors, and converting one back into a `ReqqError`
+//! finds its kind again.
+
+use crate::redact;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// The kinds of errors reqq reports.
+#[derive(Debug)]
+pub enum ReqqError {
+    /// The collection directory doesn't exist.
+    DirNotFound(PathBuf),
+    /// No request has this name.
+    RequestNotFound(String),
+    /// No env has this name.
+    EnvNotFound(String),
+    /// A request file that can't be parsed, at a line and column of the request as rendered,
+    /// which are those of the file unless templates before them render to more or fewer lines
+    /// or characters. `text` is that line, and `len` how many of its characters are at fault.
+    Parse {
+        file: String,
+        line: usize,
+        column: usize,
+        len: usize,
+        text: String,
+        message: String,
+    },
+    /// A template uses variables that neither the env nor the args define. The variables of
+    /// templates of more than one line, like request files, have the line they're on.
+    Template {
+        request: Option<String>,
+        variables: Vec<String>,
+    },
+    /// A request couldn't be sent, or its response couldn't be read.
+    Http(reqwest::Error),
+    /// A file or socket couldn't be read or written.
+    Io(io::Error),
+    /// Anything else.
+    Other(anyhow::Error),
+}
+
+impl ReqqError {
+    /// A parse error about the `len` bytes of a rendered request file at byte `at`, with the
+    /// line and column they start at. Secrets on the line are masked, before, in and after the
+    /// text at fault, so the column still points at it.
+    pub(crate) fn parse(
+        file: &str,
+        rendered: &str,
+        at: usize,
+        len: usize,
+        message: String,
+    ) -> Self {
+        let at = at.min(rendered.len());
+        let start = rendered[..at].rfind('\n').map_or(0, |i| i + 1);
+        let end = rendered[at..].find('\n').map_or(rendered.len(), |i| at + i);
+        let end = start + rendered[start..end].trim_end_matches('\r').len();
+        let fault_end = (at + len).min(end);
+        let (before, fault, after) = (
+            redact::redact(&rendered[start..at]),
+            redact::redact(&rendered[at..fault_end]),
+            redact::redact(&rendered[fault_end..end]),
+        );
+        ReqqError::Parse {
+            file: file.to_owned(),
+            line: rendered[..at].matches('\n').count() + 1,
+            column: before.chars().count() + 1,
+            len: fault.chars().count(),
+            text: format!("{}{}{}", before, fault, after),
+            message,
+        }
+    }
+
+    /// What to try next, for the CLI to print after the error.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            ReqqError::DirNotFound(_) => {
+                Some("Point reqq at a collection with -d/--dir or REQQ_DIR.")
+            }
+            ReqqError::RequestNotFound(_) => {
+                Some("Run `reqq list` to see the requests of the collection.")
+            }
+            ReqqError::EnvNotFound(_) => Some("Run `reqq envs` to see the envs of the collection."),
+            ReqqError::Parse { .. } => Some(
+                "A request starts with a method and URL, like `GET https://example.com`, then \
+                 headers, a blank line and the body.",
+            ),
+            ReqqError::Template { .. } => Some(
+                "Define them in an env, pass them with -a name=value, or use --prompt to be \
+                 asked for them.",
+            ),
+            ReqqError::Http(_) | ReqqError::Io(_) | ReqqError::Other(_) => None,
+        }
+    }
+
+    /// The hint of the first `ReqqError` in an error's chain.
+    pub fn hint_for(e: &anyhow::Error) -> Option<&'static str> {
+        e.chain()
+            .find_map(|cause| cause.downcast_ref::<ReqqError>())
+            .and_then(ReqqError::hint)
+    }
+}
+
+impl fmt::Display for ReqqError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReqqError::DirNotFound(dir) => write!(f, "Directory '{}' not found.", dir.display()),
+            ReqqError::RequestNotFound(name) => write!(f, "Request '{}' not found.", name),
+            ReqqError::EnvNotFound(name) => write!(f, "Environment '{}' not found.", name),
+            ReqqError::Parse {
+                file,
+                line,
+                column,
+                len,
+                text,
+                message,
+            } => {
+                // Like rustc, with a caret under the text at fault.
+                let gutter = " ".repeat(line.to_string().len());
+                let indent: String = text
+                    .chars()
+                    .take(column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                writeln!(f, "{}", message)?;
+                writeln!(f, "{}--> {}:{}:{}", gutter, file, line, column)?;
+                writeln!(f, "{} |", gutter)?;
+                writeln!(f, "{} | {}", line, text)?;
+                write!(f, "{} | {}{}", gutter, indent, "^".repeat((*len).max(1)))
+            }
+            ReqqError::Template {
+                request: Some(request),
+                variables,
+            } => {
+                write!(
+                    f,
+                    "Request '{}' uses undefined variables: {}",
+                    request,
+                    variables.join(", ")
+                )
+            }
+            ReqqError::Template {
+                request: None,
+                variables,
+            } => write!(f, "Unresolved template variables: {}", variables.join(", ")),
+            ReqqError::Http(e) => write!(f, "{}", e),
+            ReqqError::Io(e) => write!(f, "{}", e),
+            ReqqError::Other(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReqqError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReqqError::Http(e) => Some(e),
+            ReqqError::Io(e) => Some(e),
+            ReqqError::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the kind of an error: a `ReqqError` it was made from, or one made from its cause.
+/// I/O errors keep the messages they were wrapped in.
+impl From<anyhow::Error> for ReqqError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<ReqqError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<reqwest::Error>() {
+            Ok(e) => return ReqqError::Http(e),
+            Err(e) => e,
+        };
+        match e
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<io::Error>())
+        {
+            Some(cause) => ReqqError::Io(io::Error::new(cause.kind(), format!("{:#}", e))),
+            None => ReqqError::Other(e),
+        }
+    }
+}
+
+#[test]
+fn test_error_from_anyhow() {
+    let e = anyhow::Error::new(ReqqError::RequestNotFound("users/get".to_owned()))
+        .context("While running the flow");
+    let e = ReqqError::from(e);
+    assert!(matches!(&e, ReqqError::RequestNotFound(name) if name == "users/get"));
+    assert_eq!(
+        e.hint(),
+        Some("Run `reqq list` to see the requests of the collection.")
+    );
+
+    let missing = io::Error::new(io::ErrorKind::NotFound, "No such file");
+    let e = ReqqError::from(anyhow::Error::new(missing).context("Couldn't read 'body.json'"));
+    assert!(matches!(&e, ReqqError::Io(io) if io.kind() == io::ErrorKind::NotFound));
+    assert_eq!(e.to_string(), "Couldn't read 'body.json': No such file");
+
+    assert!(matches!(
+        ReqqError::from(anyhow::anyhow!("Nope.")),
+        ReqqError::Other(_)
+    ));
+
+    let rendered = "GET https://example.com\nAccept: */*\nX-Id:\t\x7f\n";
+    let e = ReqqError::parse(
+        ".reqq/users.reqq",
+        rendered,
+        42,
+        1,
+        "Invalid value of header 'x-id'.".to_owned(),
+    );
+    assert!(matches!(
+        &e,
+        ReqqError::Parse {
+            line: 3,
+            column: 7,
+            len: 1,
+            ..
+        }
+    ));
+    assert_eq!(
+        e.to_string(),
+        "Invalid value of header 'x-id'.\n --> .reqq/users.reqq:3:7\n  |\n3 | X-Id:\t\x7f\n  |      \t^"
+    );
+    assert!(ReqqError::hint_for(&anyhow::Error::new(e).context("Dependency failed")).is_some());
+}
@@ -0,0 +1,456 @@
+use crate::{
+    client::ClientPool,
+    compression::Decoded,
+    download::{self, Output},
+    request::Request,
+    response::Response,
+    time, unix_socket,
+};
+use anyhow::{anyhow, Result};
+use reqwest::header::CONTENT_ENCODING;
+use std::io::{self, ErrorKind};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Kinds of failures that can be retried.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetryOn {
+    /// A response with a 5xx status.
+    ServerError,
+    /// A response with a specific status, like 429.
+    Status(u16),
+    /// Failing to connect to the server.
+    Connection,
+    /// Hitting the request or connect timeout.
+    Timeout,
+}
+
+impl FromStr for RetryOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "5xx" => Ok(RetryOn::ServerError),
+            "connection" => Ok(RetryOn::Connection),
+            "timeout" => Ok(RetryOn::Timeout),
+            status => status
+                .parse()
+                .ok()
+                .filter(|code| (100..600).contains(code))
+                .map(RetryOn::Status)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown retry condition '{}', expected 5xx, connection, timeout or a \
+                         status code",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+/// When and how often failed requests are sent again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// How many times a request is retried after the first attempt.
+    pub retries: u32,
+    /// The delay before the first retry, doubled for every retry after it.
+    pub delay: Duration,
+    pub on: Vec<RetryOn>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 0,
+            delay: Duration::from_secs(1),
+            on: vec![RetryOn::ServerError, RetryOn::Connection],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before the given retry, starting at 1: exponential backoff from `delay`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+    }
+
+    /// Why the outcome of an attempt should be retried, if it should.
+    fn reason(&self, outcome: &Result<Response>) -> Option<String> {
+        let matches = |kind: RetryOn| self.on.contains(&kind);
+        match outcome {
+            Ok(resp) if resp.status.is_server_error() && matches(RetryOn::ServerError) => {
+                Some(resp.status.to_string())
+            }
+            Ok(resp) if matches(RetryOn::Status(resp.status.as_u16())) => {
+                Some(resp.status.to_string())
+            }
+            Ok(_) => None,
+            Err(e) => {
+                let source = e.downcast_ref::<reqwest::Error>()?;
+                if source.is_timeout() && matches(RetryOn::Timeout) {
+                    Some("timed out".to_owned())
+                } else if source.is_connect()
+                    && !source.is_timeout()
+                    && matches(RetryOn::Connection)
+                {
+                    Some("connection failed".to_owned())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Whether an error is from the network rather than the collection: the server couldn't be
+/// reached, the connection broke, or a timeout was hit.
+pub fn is_network_error(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return e.is_connect() || e.is_timeout() || e.is_request() || e.is_body();
+        }
+        cause.downcast_ref::<io::Error>().is_some_and(|e| {
+            matches!(
+                e.kind(),
+                ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::NotConnected
+                    | ErrorKind::AddrNotAvailable
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::TimedOut
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::UnexpectedEof
+            )
+        })
+    })
+}
+
+/// The most requests to start in a period, like `10/s` or `100/m`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    /// The time between the start of one request and the next.
+    pub fn interval(&self) -> Duration {
+        self.per / self.requests
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid rate limit '{}', expected e.g. 10/s, 100/m or 1000/h",
+                s
+            )
+        };
+        let (requests, per) = s.trim().split_once('/').unwrap_or((s.trim(), "s"));
+        let per = match per.trim() {
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(3600),
+            _ => return Err(invalid()),
+        };
+        match requests.trim().parse() {
+            Ok(requests) if requests > 0 => Ok(RateLimit { requests, per }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Spaces out the requests that are sent, from any thread, so that at least an interval passes
+/// between the start of one and the next.
+pub struct Pacer {
+    delay: Duration,
+    /// The current interval, and when the next request may start.
+    state: Mutex<(Duration, Option<Instant>)>,
+}
+
+impl Pacer {
+    pub fn new(delay: Duration) -> Self {
+        Pacer {
+            delay,
+            state: Mutex::new((delay, None)),
+        }
+    }
+
+    /// Keeps to a rate limit on top of the delay, whichever is slower, until called again
+    /// without one.
+    pub fn limit(&self, rate_limit: Option<RateLimit>) {
+        let interval = rate_limit.map_or(self.delay, |limit| limit.interval().max(self.delay));
+        if let Ok(mut state) = self.state.lock() {
+            state.0 = interval;
+        }
+    }
+
+    /// Takes the next slot to start a request in, and returns how long to wait for it.
+    pub fn reserve(&self) -> Duration {
+        let Ok(mut state) = self.state.lock() else {
+            return Duration::ZERO;
+        };
+        let now = Instant::now();
+        let slot = state.1.map_or(now, |next| next.max(now));
+        state.1 = Some(slot + state.0);
+        slot - now
+    }
+}
+
+/// Sends requests, retrying them according to a policy.
+pub struct Executor {
+    pub retry: RetryPolicy,
+    pub pacer: Pacer,
+    /// The clients requests are sent with, shared so connections are kept alive between them.
+    pub clients: ClientPool,
+    pub verbose: bool,
+}
+
+impl Executor {
+    /// Waits until the pacer lets the next request start.
+    pub fn pace(&self) {
+        thread::sleep(self.waiting());
+    }
+
+    /// Like `pace`, without blocking the async runtime.
+    #[cfg(feature = "async")]
+    pub async fn pace_async(&self) {
+        tokio::time::sleep(self.waiting()).await;
+    }
+
+    /// The wait before the next request, which is reported when verbose.
+    fn waiting(&self) -> Duration {
+        let wait = self.pacer.reserve();
+        if self.verbose && !wait.is_zero() {
+            eprintln!("* Waiting {} before sending", time::format_duration(wait));
+        }
+        wait
+    }
+
+    /// Sends a parsed request and reads its whole response, retrying failed attempts. With an
+    /// output, a successful response's body is saved to a file instead of read.
+    pub fn send(&self, req: &Request, output: Option<&Output>) -> Result<Response> {
+        let mut retry = 0;
+        loop {
+            let outcome = self.attempt(req, output);
+            let reason = match self.retry.reason(&outcome) {
+                Some(reason) if retry < self.retry.retries => reason,
+                _ => return outcome,
+            };
+
+            retry += 1;
+            thread::sleep(self.retrying(&reason, retry));
+        }
+    }
+
+    /// Sends a parsed request with reqwest's async client and reads its whole response,
+    /// retrying failed attempts.
+    #[cfg(feature = "async")]
+    pub async fn send_async(&self, req: &Request) -> Result<Response> {
+        let mut retry = 0;
+        loop {
+            let outcome = self.attempt_async(req).await;
+            let reason = match self.retry.reason(&outcome) {
+                Some(reason) if retry < self.retry.retries => reason,
+                _ => return outcome,
+            };
+
+            retry += 1;
+            tokio::time::sleep(self.retrying(&reason, retry)).await;
+        }
+    }
+
+    /// The delay before a retry, which is reported when verbose.
+    fn retrying(&self, reason: &str, retry: u32) -> Duration {
+        let delay = self.retry.backoff(retry);
+        if self.verbose {
+            eprintln!(
+                "* Attempt failed ({}), retrying in {} ({} of {})",
+                reason,
+                time::format_duration(delay),
+                retry,
+                self.retry.retries
+            );
+        }
+        delay
+    }
+
+    fn attempt(&self, req: &Request, output: Option<&Output>) -> Result<Response> {
+        let start = Instant::now();
+        let client = self.clients.get(&req.url_client_opts())?;
+        if let Some(socket) = req.unix_socket() {
+            if output.is_some() {
+                return Err(anyhow!(
+                    "Responses over a Unix socket can't be saved to a file."
+                ));
+            }
+            let (resp, decoded) = unix_socket::send(req, &socket, &client)?;
+            if self.verbose {
+                eprintln!(
+                    "* Received {} after {} ms",
+                    resp.status,
+                    start.elapsed().as_millis()
+                );
+            }
+            return Ok(self.report_decoding(resp, decoded));
+        }
+        let resp = req.send(&client)?;
+        if self.verbose {
+            eprintln!(
+                "* Received {} after {} ms",
+                resp.status(),
+                start.elapsed().as_millis()
+            );
+        }
+        match output {
+            Some(output) if resp.status().is_success() => download::save(resp, output),
+            _ => Response::read(resp).map(|(resp, decoded)| self.report_decoding(resp, decoded)),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn attempt_async(&self, req: &Request) -> Result<Response> {
+        let start = Instant::now();
+        let resp = req
+            .send_async(&self.clients.get_async(&req.url_client_opts())?)
+            .await?;
+        if self.verbose {
+            eprintln!(
+                "* Received {} after {} ms",
+                resp.status(),
+                start.elapsed().as_millis()
+            );
+        }
+        let (resp, decoded) = Response::read_async(resp).await?;
+        Ok(self.report_decoding(resp, decoded))
+    }
+
+    /// Reports when verbose how a response's body was decoded, or that it couldn't be.
+    fn report_decoding(&self, resp: Response, decoded: Option<Decoded>) -> Response {
+        if self.verbose {
+            let encoding = resp
+                .headers
+                .get(CONTENT_ENCODING)
+                .and_then(|v| v.to_str().ok());
+            match (decoded, encoding) {
+                (Some(decoded), _) => eprintln!("* Decoded the body: {}", decoded.report()),
+                (None, Some(encoding)) if !encoding.eq_ignore_ascii_case("identity") => {
+                    eprintln!(
+                        "* The body is {} encoded, which can't be decoded, so it's left as \
+                         received",
+                        encoding
+                    )
+                }
+                _ => {}
+            }
+        }
+        resp
+    }
+}
+
+#[test]
+fn test_retry_on_from_str() {
+    assert_eq!("5xx".parse(), Ok(RetryOn::ServerError));
+    assert_eq!("connection".parse(), Ok(RetryOn::Connection));
+    assert_eq!(" timeout".parse(), Ok(RetryOn::Timeout));
+    assert_eq!("429".parse(), Ok(RetryOn::Status(429)));
+    assert!("4xx".parse::<RetryOn>().is_err());
+    assert!("999".parse::<RetryOn>().is_err());
+}
+
+#[test]
+fn test_rate_limit_from_str() {
+    let limit: RateLimit = "10/s".parse().unwrap();
+    assert_eq!(limit.interval(), Duration::from_millis(100));
+    assert_eq!(
+        "120/m".parse::<RateLimit>().unwrap().interval(),
+        Duration::from_millis(500)
+    );
+    assert_eq!(
+        "5".parse(),
+        Ok(RateLimit {
+            requests: 5,
+            per: Duration::from_secs(1)
+        })
+    );
+    assert!("0/s".parse::<RateLimit>().is_err());
+    assert!("10/d".parse::<RateLimit>().is_err());
+}
+
+#[test]
+fn test_pacer() {
+    let pacer = Pacer::new(Duration::from_secs(1));
+    assert_eq!(pacer.reserve(), Duration::ZERO);
+    assert!(pacer.reserve() > Duration::from_millis(900));
+
+    pacer.limit(Some("1/m".parse().unwrap()));
+    assert!(pacer.reserve() > Duration::from_secs(1));
+    assert!(pacer.reserve() > Duration::from_secs(60));
+    assert_eq!(Pacer::new(Duration::ZERO).reserve(), Duration::ZERO);
+}
+
+#[test]
+fn test_retry_backoff() {
+    let policy = RetryPolicy {
+        retries: 3,
+        delay: Duration::from_millis(200),
+        ..RetryPolicy::default()
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(200));
+    assert_eq!(policy.backoff(2), Duration::from_millis(400));
+    assert_eq!(policy.backoff(3), Duration::from_millis(800));
+}
+
+#[test]
+fn test_retry_reason() {
+    let resp = |status: u16| -> Result<Response> {
+        Ok(Response {
+            status: reqwest::StatusCode::from_u16(status).unwrap(),
+            version: reqwest::Version::HTTP_11,
+            headers: http::HeaderMap::new(),
+            body: String::new(),
+            binary: None,
+        })
+    };
+    let policy = RetryPolicy {
+        on: vec![RetryOn::ServerError, RetryOn::Status(429)],
+        ..RetryPolicy::default()
+    };
+    assert_eq!(
+        policy.reason(&resp(503)).as_deref(),
+        Some("503 Service Unavailable")
+    );
+    assert_eq!(
+        policy.reason(&resp(429)).as_deref(),
+        Some("429 Too Many Requests")
+    );
+    assert_eq!(policy.reason(&resp(404)), None);
+    assert_eq!(policy.reason(&resp(200)), None);
+    assert_eq!(
+        policy.reason(&Err(anyhow::anyhow!("not a network error"))),
+        None
+    );
+}
+
+#[test]
+fn test_is_network_error() {
+    use anyhow::Context;
+
+    let refused = Err::<(), _>(io::Error::from(ErrorKind::ConnectionRefused))
+        .context("Couldn't connect to localhost:1");
+    assert!(is_network_error(&refused.unwrap_err()));
+    let missing = Err::<(), _>(io::Error::from(ErrorKind::NotFound))
+        .context("Couldn't read the request file");
+    assert!(!is_network_error(&missing.unwrap_err()));
+    assert!(!is_network_error(&anyhow!(
+        "Request 'users/get' doesn't exist."
+    )));
+}
@@ -0,0 +1,173 @@
+//! `@extends`: a request file can extend another request, inheriting its method and URL, query
+//! params, headers, body and directives, and replacing the parts it gives itself. The files are
+//! merged before they're templated, so both can use variables and partials.
+
+use crate::frontmatter;
+use anyhow::{anyhow, Result};
+
+/// The parts of a request file after its front matter, as written.
+#[derive(Debug, Default, PartialEq)]
+pub struct Parts<'a> {
+    /// The method and URL line, which a file extending another can leave out.
+    pub request_line: Option<&'a str>,
+    pub params: Vec<&'a str>,
+    /// Header lines, and lines that are only a template tag, like `{{> common/headers }}`.
+    pub headers: Vec<&'a str>,
+    pub body: &'a str,
+}
+
+/// Merges a request file with the one it extends. Its method and URL line and its body
+/// replace the other's, and its query params and headers replace those with the same names.
+pub fn merge(base: &str, fstr: &str) -> Result<String> {
+    let (mut merged, base_rest, rest) = frontmatter::merge(base, fstr)?;
+    let (base, parts) = (parts(base_rest), parts(rest));
+
+    let request_line = parts.request_line.or(base.request_line).ok_or_else(|| {
+        anyhow!("Neither the request nor the one it extends has a method and URL line.")
+    })?;
+    let lines = [request_line]
+        .into_iter()
+        .chain(replace_named(&base.params, &parts.params, param_name))
+        .chain(replace_named(&base.headers, &parts.headers, header_name));
+    for line in lines {
+        merged.push_str(line);
+        merged.push('\n');
+    }
+
+    let body = if parts.body.trim().is_empty() {
+        base.body
+    } else {
+        parts.body
+    };
+    if !body.is_empty() {
+        merged.push('\n');
+        merged.push_str(body);
+    }
+    Ok(merged)
+}
+
+/// Splits what follows the front matter of a request file into its parts. A file without a
+/// method and URL line starts with its query params or headers, or with a blank line before
+/// its body.
+pub fn parts(rest: &str) -> Parts<'_> {
+    let mut parts = Parts::default();
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        if param_name(content).is_some() && parts.headers.is_empty() {
+            parts.params.push(content);
+        } else if header_name(content).is_some() || (offset > 0 && is_tag(content)) {
+            parts.headers.push(content);
+        } else if offset == 0 && !content.trim().is_empty() {
+            parts.request_line = Some(content);
+        } else {
+            // A blank line separates the body and belongs to neither part.
+            let start = if content.trim().is_empty() {
+                offset + line.len()
+            } else {
+                offset
+            };
+            parts.body = &rest[start..];
+            break;
+        }
+        offset += line.len();
+    }
+    parts
+}
+
+/// The lines of the base, except those named like one of the others, followed by the others.
+fn replace_named<'a>(
+    base: &[&'a str],
+    lines: &[&'a str],
+    name: fn(&str) -> Option<String>,
+) -> Vec<&'a str> {
+    let names: Vec<String> = lines.iter().filter_map(|line| name(line)).collect();
+    let inherited = base
+        .iter()
+        .filter(|line| name(line).is_none_or(|n| !names.contains(&n)));
+    inherited.chain(lines.iter()).copied().collect()
+}
+
+/// The name of the param on a `?key=value` or `&key=value` line.
+fn param_name(line: &str) -> Option<String> {
+    let param = line.trim().strip_prefix(['?', '&'])?;
+    Some(param.split('=').next().unwrap_or_default().to_owned())
+}
+
+/// The name of the header on a `Name: value` line, lowercased since header names aren't case
+/// sensitive.
+fn header_name(line: &str) -> Option<String> {
+    let (name, value) = line.split_once(':')?;
+    let is_name = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    (is_name && !value.trim().is_empty()).then(|| name.to_ascii_lowercase())
+}
+
+/// Whether a line is only a template tag, which may render to headers.
+pub fn is_tag(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("{{") && line.ends_with("}}")
+}
+
+#[test]
+fn test_extends_parts() {
+    let rest = "POST {{ baseUrl }}/users\n?dry=1\nAccept: application/json\n{{> common/headers }}\n\n{\"name\": \"x\"}\n";
+    assert_eq!(
+        parts(rest),
+        Parts {
+            request_line: Some("POST {{ baseUrl }}/users"),
+            params: vec!["?dry=1"],
+            headers: vec!["Accept: application/json", "{{> common/headers }}"],
+            body: "{\"name\": \"x\"}\n",
+        }
+    );
+    assert_eq!(parts("x-team: core\n").headers, vec!["x-team: core"]);
+    assert_eq!(parts("\n{}").body, "{}");
+}
+
+#[test]
+fn test_extends_merge() {
+    let base = "# The users API.
+@timeout 5s
+@tags users
+@status 200
+GET {{ baseUrl }}/users
+?limit=10
+&sort=name
+Authorization: Bearer {{ token }}
+Accept: application/json
+
+{\"all\": true}
+";
+    let fstr = "@extends users/base
+@tags smoke
+@status 201
+POST {{ baseUrl }}/users
+&sort=id
+accept: text/plain
+x-request-id: {{ uuid }}
+";
+    assert_eq!(
+        merge(base, fstr).unwrap(),
+        "@timeout 5s
+@tags users
+@tags smoke
+@status 201
+POST {{ baseUrl }}/users
+?limit=10
+&sort=id
+Authorization: Bearer {{ token }}
+accept: text/plain
+x-request-id: {{ uuid }}
+
+{\"all\": true}
+"
+    );
+
+    let fstr = "---\nextends: users/base\ntimeout: 30s\n---\n\n{\"all\": false}";
+    assert_eq!(
+        merge(base, fstr).unwrap(),
+        "---\ntimeout: 30s\n---\n@tags users\n@status 200\nGET {{ baseUrl }}/users\n?limit=10\n&sort=name\n\
+         Authorization: Bearer {{ token }}\nAccept: application/json\n\n{\"all\": false}"
+    );
+    assert!(merge("Accept: */*\n", "x-team: core\n").is_err());
+}
@@ -0,0 +1,263 @@
+use crate::{executor::RateLimit, yaml};
+use anyhow::{anyhow, Result};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File extensions of the supported flow formats.
+const EXTENSIONS: [&str; 3] = [".yaml", ".yml", ".json"];
+
+/// A named sequence of requests, defined in the `flows` folder of a reqq directory.
+///
+/// ```yaml
+/// rate_limit: 10/s
+/// steps:
+///   - request: auth/signup
+///     args:
+///       plan: free
+///     extract:
+///       userId: $.data.id
+///   - users/get
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flow {
+    pub name: String,
+    /// The most requests to start in a period while the flow runs, like `10/s`.
+    pub rate_limit: Option<RateLimit>,
+    pub steps: Vec<Step>,
+}
+
+/// One request of a flow.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// The name of the request to execute.
+    pub request: String,
+    /// Args for this step only, on top of the flow's env and args.
+    pub args: HashMap<String, Value>,
+    /// Variables to set for later steps, as variable names and JSONPath expressions that are
+    /// evaluated against the response body.
+    pub extract: Vec<(String, String)>,
+}
+
+impl Flow {
+    /// Loads a flow by name from the `flows` folder of a reqq directory.
+    pub fn load(dir: &str, name: &str) -> Result<Flow> {
+        let (fpath, ext) = find(dir, name).ok_or_else(|| anyhow!("Flow '{}' not found.", name))?;
+
+        let fstr = fs::read_to_string(&fpath)?;
+        Flow::parse(name, &fstr, ext)
+            .map_err(|e| anyhow!("Failed to parse flow '{}': {}", fpath.display(), e))
+    }
+
+    /// Whether a reqq directory has a flow with this name.
+    pub fn exists(dir: &str, name: &str) -> bool {
+        find(dir, name).is_some()
+    }
+
+    fn parse(name: &str, fstr: &str, ext: &str) -> Result<Flow> {
+        let value = if ext == ".json" {
+            serde_json::from_str(fstr)?
+        } else {
+            yaml::parse(fstr)?
+        };
+
+        let steps = value["steps"]
+            .as_array()
+            .ok_or_else(|| anyhow!("expected a list of steps"))?
+            .iter()
+            .enumerate()
+            .map(|(i, step)| parse_step(step).ok_or_else(|| anyhow!("step {} is invalid", i + 1)))
+            .collect::<Result<_>>()?;
+        let rate_limit = match &value["rate_limit"] {
+            Value::Null => None,
+            Value::String(limit) => Some(limit.parse().map_err(|e| anyhow!("{}", e))?),
+            Value::Number(limit) => Some(limit.to_string().parse().map_err(|e| anyhow!("{}", e))?),
+            _ => return Err(anyhow!("expected a rate limit like 10/s")),
+        };
+
+        Ok(Flow {
+            name: name.to_owned(),
+            rate_limit,
+            steps,
+        })
+    }
+}
+
+/// The file of a flow, and its extension.
+fn find(dir: &str, name: &str) -> Option<(PathBuf, &'static str)> {
+    let folder = Path::new(dir).join("flows");
+    EXTENSIONS
+        .iter()
+        .map(|ext| (folder.join(format!("{}{}", name, ext)), *ext))
+        .find(|(path, _)| path.is_file())
+}
+
+/// A step is either just a request name, or a mapping with a `request` and optional `args`
+/// and `extract` mappings.
+fn parse_step(value: &Value) -> Option<Step> {
+    if let Value::String(request) = value {
+        return Some(Step {
+            request: request.clone(),
+            args: HashMap::new(),
+            extract: vec![],
+        });
+    }
+
+    let args = match &value["args"] {
+        Value::Object(map) => map.clone().into_iter().collect(),
+        Value::Null => HashMap::new(),
+        _ => return None,
+    };
+    let extract = match &value["extract"] {
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| Some((k.clone(), v.as_str()?.to_owned())))
+            .collect::<Option<_>>()?,
+        Value::Null => vec![],
+        _ => return None,
+    };
+
+    Some(Step {
+        request: value["request"].as_str()?.to_owned(),
+        args,
+        extract,
+    })
+}
+
+/// The outcome of one step of a flow.
+#[derive(Clone, Debug)]
+pub struct StepResult {
+    pub request: String,
+    /// The response status, if a response was received.
+    pub status: Option<StatusCode>,
+    pub duration_ms: u64,
+    /// Why the step failed, if it did.
+    pub error: Option<String>,
+}
+
+/// The outcome of running a flow. Steps after a failed one aren't executed.
+#[derive(Clone, Debug)]
+pub struct FlowResult {
+    pub name: String,
+    /// How many steps the flow has, including any that weren't executed.
+    pub total: usize,
+    pub steps: Vec<StepResult>,
+}
+
+impl FlowResult {
+    pub fn passed(&self) -> bool {
+        self.steps.len() == self.total && self.steps.iter().all(|s| s.error.is_none())
+    }
+
+    /// One line per executed step, followed by a summary line.
+    pub fn report(&self) -> String {
+        let mut lines: Vec<String> = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| {
+                let status = step.status.map_or("-".to_owned(), |s| s.to_string());
+                let mut line = format!(
+                    "[{}/{}] {}  {}  {}  {} ms",
+                    i + 1,
+                    self.total,
+                    if step.error.is_none() { "PASS" } else { "FAIL" },
+                    step.request,
+                    status,
+                    step.duration_ms
+                );
+                if let Some(error) = &step.error {
+                    line.push_str(&format!("\n      {}", error));
+                }
+                line
+            })
+            .collect();
+
+        lines.push(if self.passed() {
+            format!("Flow '{}' passed, {} steps.", self.name, self.total)
+        } else {
+            format!(
+                "Flow '{}' failed at step {} of {}.",
+                self.name,
+                self.steps.len(),
+                self.total
+            )
+        });
+        lines.join("\n")
+    }
+}
+
+#[test]
+fn test_parse_flow() {
+    let fstr = "steps:
+  - request: auth/signup
+    args:
+      plan: free
+    extract:
+      userId: $.data.id
+  - users/get
+";
+    let flow = Flow::parse("signup", fstr, ".yaml").unwrap();
+    assert_eq!(flow.steps.len(), 2);
+    assert_eq!(flow.steps[0].request, "auth/signup");
+    assert_eq!(flow.steps[0].args["plan"], "free");
+    assert_eq!(
+        flow.steps[0].extract,
+        vec![("userId".to_owned(), "$.data.id".to_owned())]
+    );
+    assert_eq!(flow.steps[1].request, "users/get");
+    assert!(flow.steps[1].extract.is_empty());
+    assert_eq!(flow.rate_limit, None);
+
+    let flow = Flow::parse("slow", "rate_limit: 2/s\nsteps:\n  - a", ".yaml").unwrap();
+    assert_eq!(
+        flow.rate_limit.unwrap().interval(),
+        std::time::Duration::from_millis(500)
+    );
+    assert!(Flow::parse("bad", "rate_limit: fast\nsteps:\n  - a", ".yaml").is_err());
+
+    let flow = Flow::parse(
+        "signup",
+        "{\"steps\": [\"a\", {\"request\": \"b\"}]}",
+        ".json",
+    )
+    .unwrap();
+    assert_eq!(flow.steps[1].request, "b");
+
+    assert!(Flow::parse("bad", "steps: nope", ".yaml").is_err());
+    assert!(Flow::parse("bad", "steps:\n  - args: {}", ".yaml").is_err());
+}
+
+#[test]
+fn test_flow_report() {
+    let step = |request: &str, status, error: Option<&str>| StepResult {
+        request: request.to_owned(),
+        status,
+        duration_ms: 12,
+        error: error.map(str::to_owned),
+    };
+    let mut result = FlowResult {
+        name: "signup".to_owned(),
+        total: 2,
+        steps: vec![step("a", Some(StatusCode::CREATED), None)],
+    };
+    result.steps.push(step(
+        "b",
+        Some(StatusCode::NOT_FOUND),
+        Some("Request failed with status 404 Not Found."),
+    ));
+    assert!(!result.passed());
+    assert_eq!(
+        result.report(),
+        "[1/2] PASS  a  201 Created  12 ms
+[2/2] FAIL  b  404 Not Found  12 ms
+      Request failed with status 404 Not Found.
+Flow 'signup' failed at step 2 of 2."
+    );
+
+    result.steps[1] = step("b", Some(StatusCode::OK), None);
+    assert!(result.passed());
+    assert!(result.report().ends_with("Flow 'signup' passed, 2 steps."));
+}
@@ -1,52 +1,542 @@
+use crate::{download::format_bytes, sigv4};
 use anyhow::Result;
-use http::HeaderMap;
-use reqwest::blocking::Response;
+use http::{header::CONTENT_TYPE, HeaderMap};
+use openssl::sha::sha256;
 
 enum ContentType {
     Json,
+    Html,
+    Xml,
     Unknown,
 }
 
-// TODO: Look at the content-type header and attempt to parse based on content.
-pub fn format_response(resp: Response, raw: bool) -> Result<String> {
-    let status = resp.status();
-    let headers = resp.headers().clone();
+const KEY_COLOR: &str = "\x1b[34m";
+const STRING_COLOR: &str = "\x1b[32m";
+const NUMBER_COLOR: &str = "\x1b[33m";
+const LITERAL_COLOR: &str = "\x1b[35m";
+const RESET: &str = "\x1b[0m";
+
+/// HTML elements that have no closing tag.
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// HTML elements whose contents aren't markup, or whose whitespace matters, kept as they are.
+const RAW_ELEMENTS: [&str; 4] = ["script", "style", "pre", "textarea"];
+
+/// HTML elements that start a new line of text with `--html-text`.
+const BLOCK_ELEMENTS: [&str; 32] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// Media types of binary bodies, besides `image/*`, `audio/*`, `video/*` and `font/*`.
+const BINARY_TYPES: [&str; 9] = [
+    "application/octet-stream",
+    "application/pdf",
+    "application/zip",
+    "application/gzip",
+    "application/x-gzip",
+    "application/x-tar",
+    "application/x-7z-compressed",
+    "application/x-protobuf",
+    "application/wasm",
+];
+
+/// Formats a response body for printing, pretty-printing known content types and adding
+/// syntax highlighting if `color` is set. With `html_text`, HTML bodies are printed as just
+/// their text.
+pub fn format_body(
+    headers: &HeaderMap,
+    body: String,
+    color: bool,
+    html_text: bool,
+) -> Result<String> {
     let content_type = get_content_type(headers.clone())?;
+    if let (ContentType::Html, true) = (&content_type, html_text) {
+        return Ok(extract_text(&body).unwrap_or(body));
+    }
+    Ok(format_content_type(content_type, body, color))
+}
+
+/// Whether a body is binary, like an image or a PDF, by its `Content-Type`. Bodies without a
+/// type that says whether they're text are binary when they aren't UTF-8 or have NUL bytes.
+pub fn is_binary(headers: &HeaderMap, body: &[u8]) -> bool {
+    match media_type(headers).as_deref() {
+        Some(t) if t.ends_with("+xml") || t.ends_with("+json") => false,
+        Some(t)
+            if BINARY_TYPES.contains(&t)
+                || ["image/", "audio/", "video/", "font/"]
+                    .iter()
+                    .any(|p| t.starts_with(p)) =>
+        {
+            true
+        }
+        Some(t)
+            if t.starts_with("text/")
+                || t.ends_with("/json")
+                || t.ends_with("/xml")
+                || t.ends_with("javascript") =>
+        {
+            false
+        }
+        Some("application/x-www-form-urlencoded") => false,
+        _ => !body.is_empty() && (body.contains(&0) || std::str::from_utf8(body).is_err()),
+    }
+}
+
+/// Sums up a binary body, since printing it would fill a terminal with garbage: its type, size
+/// and SHA-256 hash. With the `image-preview` feature, images are shown too on terminals that
+/// can.
+pub fn format_binary(headers: &HeaderMap, body: &[u8]) -> String {
+    let media_type = media_type(headers);
+    let summary = format!(
+        "Binary body: {}, {}, sha256 {}\nSave it with -o/--output <file>.",
+        media_type.as_deref().unwrap_or("unknown type"),
+        format_bytes(body.len() as u64),
+        sigv4::hex(&sha256(body)),
+    );
+    #[cfg(feature = "image-preview")]
+    if let Some(image) = media_type
+        .as_deref()
+        .and_then(|t| crate::preview::image(t, body))
+    {
+        return format!("{}\n{}", summary, image);
+    }
+    summary
+}
 
-    let raw_body: String = resp.text()?;
-    let body = format_content_type(content_type, raw_body);
+/// The lowercased media type of a `Content-Type` header, without its parameters.
+fn media_type(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    Some(
+        value
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase(),
+    )
+}
 
-    if raw {
-        Ok(body)
+/// Pretty-prints a JSON value, adding syntax highlighting if `color` is set.
+pub fn format_json(value: &serde_json::Value, color: bool) -> String {
+    let out = serde_json::to_string_pretty(value).unwrap_or_default();
+    if color {
+        colorize_json(&out)
     } else {
-        let header_lines: Vec<String> = headers
-            .iter()
-            .map(|(k, v)| format!("{}: {}", k, v.to_str().unwrap()))
-            .collect();
-
-        let mut r = format!(
-            "Status: {}\n{}\n\n",
-            status.as_str(),
-            header_lines.join("\n")
-        );
-        r.push_str(body.as_str());
-        Ok(r)
+        out
     }
 }
 
-fn format_content_type(content_type: ContentType, content: String) -> String {
+fn format_content_type(content_type: ContentType, content: String, color: bool) -> String {
     match content_type {
         ContentType::Json => match serde_json::from_str::<serde_json::Value>(&content) {
             Ok(v) => match serde_json::to_string_pretty(&v) {
+                Ok(out) if color => colorize_json(&out),
                 Ok(out) => out,
                 Err(_) => content,
             },
             Err(_) => content,
         },
+        ContentType::Html => format_markup(&content, true, color).unwrap_or(content),
+        ContentType::Xml => format_markup(&content, false, color).unwrap_or(content),
         ContentType::Unknown => content,
     }
 }
 
+/// A piece of an HTML or XML document, as written.
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// An opening tag, and whether it closes itself, like `<br/>`.
+    Open {
+        name: &'a str,
+        tag: &'a str,
+        closed: bool,
+    },
+    Close {
+        name: &'a str,
+        tag: &'a str,
+    },
+    /// A comment, doctype, processing instruction or CDATA section.
+    Other(&'a str),
+    Text(&'a str),
+    /// The contents of an HTML element like `<script>` or `<pre>`.
+    Raw(&'a str),
+}
+
+/// Splits a document into tags and the text between them. Returns `None` if a tag, comment
+/// or raw element isn't closed.
+fn tokenize(doc: &str, html: bool) -> Option<Vec<Token<'_>>> {
+    let mut tokens = vec![];
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < doc.len() {
+        if doc.as_bytes()[i] != b'<' {
+            i += 1;
+            continue;
+        }
+        let rest = &doc[i..];
+        let len = if rest.starts_with("<!--") {
+            rest.find("-->").map(|end| end + 3)
+        } else if rest.starts_with("<![CDATA[") {
+            rest.find("]]>").map(|end| end + 3)
+        } else if rest.starts_with("<?") {
+            rest.find("?>").map(|end| end + 2)
+        } else if rest.starts_with("<!") || rest.starts_with("</") {
+            rest.find('>').map(|end| end + 1)
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            tag_len(rest)
+        } else {
+            // A `<` that starts no tag, like in `a < b`, is text.
+            i += 1;
+            continue;
+        };
+        let end = i + len?;
+        if text_start < i {
+            tokens.push(Token::Text(&doc[text_start..i]));
+        }
+
+        let tag = &doc[i..end];
+        i = end;
+        if let Some(name) = tag.strip_prefix("</") {
+            tokens.push(Token::Close {
+                name: name.trim_end_matches('>').trim(),
+                tag,
+            });
+        } else if tag.starts_with("<!") || tag.starts_with("<?") {
+            tokens.push(Token::Other(tag));
+        } else {
+            let name = tag[1..]
+                .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                .next()
+                .unwrap_or_default();
+            let closed = tag.ends_with("/>");
+            tokens.push(Token::Open { name, tag, closed });
+            let name = name.to_ascii_lowercase();
+            if html && !closed && RAW_ELEMENTS.contains(&name.as_str()) {
+                // Byte offsets are the same in the lowercased document.
+                let raw_len = doc[end..]
+                    .to_ascii_lowercase()
+                    .find(&format!("</{}", name))?;
+                if !doc[end..end + raw_len].trim().is_empty() {
+                    tokens.push(Token::Raw(&doc[end..end + raw_len]));
+                }
+                i = end + raw_len;
+            }
+        }
+        text_start = i;
+    }
+    if text_start < doc.len() {
+        tokens.push(Token::Text(&doc[text_start..]));
+    }
+    Some(tokens)
+}
+
+/// The length of the tag a string starts with, up to the `>` that isn't in a quoted
+/// attribute value.
+fn tag_len(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i + 1),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Indents an HTML or XML document, a tag per line, with elements that hold only text on a
+/// single line. Returns `None` if it can't be read.
+fn format_markup(doc: &str, html: bool, color: bool) -> Option<String> {
+    let tokens = tokenize(doc, html)?;
+    let tag = |tag: &str| {
+        if color {
+            colorize_tag(tag)
+        } else {
+            tag.to_owned()
+        }
+    };
+    let mut lines = vec![];
+    let mut open: Vec<&str> = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let indent = "  ".repeat(open.len());
+        match &tokens[i] {
+            Token::Open {
+                name,
+                tag: open_tag,
+                closed,
+            } => {
+                let is_void = *closed
+                    || (html && VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()));
+                let inline = match (tokens.get(i + 1), tokens.get(i + 2)) {
+                    (
+                        Some(Token::Close {
+                            name: close,
+                            tag: close_tag,
+                        }),
+                        _,
+                    ) if close.eq_ignore_ascii_case(name) => Some((String::new(), close_tag, 2)),
+                    (
+                        Some(Token::Text(text)),
+                        Some(Token::Close {
+                            name: close,
+                            tag: close_tag,
+                        }),
+                    ) if close.eq_ignore_ascii_case(name) => Some((collapse(text), close_tag, 3)),
+                    (
+                        Some(Token::Raw(raw)),
+                        Some(Token::Close {
+                            name: close,
+                            tag: close_tag,
+                        }),
+                    ) if close.eq_ignore_ascii_case(name) && !raw.contains('\n') => {
+                        Some((raw.to_string(), close_tag, 3))
+                    }
+                    _ => None,
+                };
+                match inline {
+                    Some((text, close_tag, len)) if !is_void => {
+                        lines.push(format!(
+                            "{}{}{}{}",
+                            indent,
+                            tag(open_tag),
+                            text,
+                            tag(close_tag)
+                        ));
+                        i += len;
+                        continue;
+                    }
+                    _ => lines.push(format!("{}{}", indent, tag(open_tag))),
+                }
+                if !is_void {
+                    open.push(name);
+                }
+            }
+            Token::Close {
+                name,
+                tag: close_tag,
+            } => {
+                // Closing an element closes those left open in it, like `<li>`s without `</li>`.
+                if let Some(pos) = open
+                    .iter()
+                    .rposition(|open| open.eq_ignore_ascii_case(name))
+                {
+                    open.truncate(pos);
+                }
+                lines.push(format!("{}{}", "  ".repeat(open.len()), tag(close_tag)));
+            }
+            Token::Other(other) if color => {
+                lines.push(format!("{}{}{}{}", indent, LITERAL_COLOR, other, RESET))
+            }
+            Token::Other(other) => lines.push(format!("{}{}", indent, other)),
+            Token::Text(text) => {
+                let text = collapse(text);
+                if !text.is_empty() {
+                    lines.push(format!("{}{}", indent, text));
+                }
+            }
+            Token::Raw(raw) => lines.push(raw.trim_matches(['\r', '\n']).to_owned()),
+        }
+        i += 1;
+    }
+    Some(lines.join("\n"))
+}
+
+/// Colors a tag, with its attribute values in the color of strings.
+fn colorize_tag(tag: &str) -> String {
+    let mut out = String::from(KEY_COLOR);
+    let mut quote = None;
+    for c in tag.chars() {
+        match quote {
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                out.push_str(STRING_COLOR);
+                out.push(c);
+            }
+            Some(q) if c == q => {
+                out.push(c);
+                out.push_str(KEY_COLOR);
+                quote = None;
+            }
+            _ => out.push(c),
+        }
+    }
+    out.push_str(RESET);
+    out
+}
+
+/// Trims text and collapses the whitespace in it to single spaces.
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The text of an HTML document, without its tags, scripts and styles, with a line for each
+/// block, like a paragraph or list item. Returns `None` if it can't be read.
+fn extract_text(doc: &str) -> Option<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+    let mut raw_element = "";
+    for token in tokenize(doc, true)? {
+        let mut text = match token {
+            Token::Text(text) => collapse(&decode_entities(text)),
+            Token::Raw(raw) if raw_element == "pre" || raw_element == "textarea" => {
+                decode_entities(raw)
+            }
+            Token::Open { name, .. } | Token::Close { name, .. } => {
+                let name = name.to_ascii_lowercase();
+                if BLOCK_ELEMENTS.contains(&name.as_str()) && !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                if let Token::Open { name, .. } = token {
+                    raw_element = name;
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if text.is_empty() {
+            continue;
+        }
+        if !line.is_empty() {
+            text.insert(0, ' ');
+        }
+        line.push_str(&text);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    Some(lines.join("\n"))
+}
+
+/// Decodes the character references in HTML text, like `&amp;` and `&#39;`.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => ' ',
+                entity => {
+                    let code = entity.strip_prefix('#')?;
+                    let code = match code.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => code.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Adds ANSI colors to a valid JSON document, leaving its layout untouched.
+fn colorize_json(json: &str) -> String {
+    let mut out = String::with_capacity(json.len() * 2);
+    let mut chars = json.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '"' => {
+                let mut escaped = false;
+                let mut end = json.len();
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => {
+                            end = i + 1;
+                            break;
+                        }
+                        _ => escaped = false,
+                    }
+                }
+
+                let is_key = json[end..].trim_start().starts_with(':');
+                let color = if is_key { KEY_COLOR } else { STRING_COLOR };
+                out.push_str(color);
+                out.push_str(&json[start..end]);
+                out.push_str(RESET);
+            }
+            '-' | '0'..='9' | 't' | 'f' | 'n' => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-')) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+
+                let color = if c.is_ascii_alphabetic() {
+                    LITERAL_COLOR
+                } else {
+                    NUMBER_COLOR
+                };
+                out.push_str(color);
+                out.push_str(&json[start..end]);
+                out.push_str(RESET);
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
 fn get_content_type(headers: HeaderMap) -> Result<ContentType> {
     let content_type_header = headers
         .iter()
@@ -58,9 +548,120 @@ fn get_content_type(headers: HeaderMap) -> Result<ContentType> {
             let tokens: Vec<&str> = v.split(';').collect();
             match tokens[0] {
                 "application/json" => Ok(ContentType::Json),
+                "text/html" | "application/xhtml+xml" => Ok(ContentType::Html),
+                "application/xml" | "text/xml" => Ok(ContentType::Xml),
+                // Like `application/atom+xml` and `image/svg+xml`.
+                other if other.ends_with("+xml") => Ok(ContentType::Xml),
+                // gRPC responses are decoded to JSON when they're read.
+                "application/grpc" | "application/grpc+proto" => Ok(ContentType::Json),
                 _ => Ok(ContentType::Unknown),
             }
         }
         None => Ok(ContentType::Unknown),
     }
 }
+
+#[test]
+fn test_colorize_json() {
+    let json =
+        "{\n  \"name\": \"a \\\"quoted\\\" value:\",\n  \"n\": -1.5e3,\n  \"ok\": [true, null]\n}";
+    let expected = format!(
+        "{{\n  {k}\"name\"{r}: {s}\"a \\\"quoted\\\" value:\"{r},\n  {k}\"n\"{r}: {n}-1.5e3{r},\n  {k}\"ok\"{r}: [{l}true{r}, {l}null{r}]\n}}",
+        k = KEY_COLOR,
+        s = STRING_COLOR,
+        n = NUMBER_COLOR,
+        l = LITERAL_COLOR,
+        r = RESET,
+    );
+    assert_eq!(colorize_json(json), expected);
+}
+
+#[test]
+fn test_format_content_type() {
+    let pretty = format_content_type(ContentType::Json, "{\"a\":1}".to_owned(), false);
+    assert_eq!(pretty, "{\n  \"a\": 1\n}");
+
+    let invalid = format_content_type(ContentType::Json, "{nope".to_owned(), true);
+    assert_eq!(invalid, "{nope");
+}
+
+#[test]
+fn test_format_markup() {
+    let html = "<!DOCTYPE html><html><head><title>Users</title><script>if (a < b) { go(); }</script></head>\n<body>\n<ul><li class=\"a\">Ada</li><li>Bob<br>Smith</ul><p>1 < 2</p></body></html>";
+    assert_eq!(
+        format_markup(html, true, false).unwrap(),
+        "<!DOCTYPE html>
+<html>
+  <head>
+    <title>Users</title>
+    <script>if (a < b) { go(); }</script>
+  </head>
+  <body>
+    <ul>
+      <li class=\"a\">Ada</li>
+      <li>
+        Bob
+        <br>
+        Smith
+    </ul>
+    <p>1 < 2</p>
+  </body>
+</html>"
+    );
+
+    let xml = "<?xml version=\"1.0\"?><feed><entry id='1'><title>A &amp; B</title><empty/></entry></feed>";
+    assert_eq!(
+        format_markup(xml, false, false).unwrap(),
+        "<?xml version=\"1.0\"?>\n<feed>\n  <entry id='1'>\n    <title>A &amp; B</title>\n    <empty/>\n  </entry>\n</feed>"
+    );
+    assert_eq!(
+        format_markup("<a href=\"/\">x</a>", true, true).unwrap(),
+        format!(
+            "{k}<a href={s}\"/\"{k}>{r}x{k}</a>{r}",
+            k = KEY_COLOR,
+            s = STRING_COLOR,
+            r = RESET
+        )
+    );
+    assert_eq!(format_markup("<a><!-- open", false, false), None);
+}
+
+#[test]
+fn test_extract_text() {
+    let html = "<html><head><title>Users</title><style>p { color: red }</style></head><body><h1>All   users</h1><p>Ada &amp; Bob&#39;s <b>list</b></p><pre>a\n  b</pre></body></html>";
+    assert_eq!(
+        extract_text(html).unwrap(),
+        "Users\nAll users\nAda & Bob's list\na\n  b"
+    );
+    assert_eq!(
+        decode_entities("&lt;&#x41;&unknown; & &#65;"),
+        "<A&unknown; & A"
+    );
+}
+
+#[test]
+fn test_format_binary() {
+    let headers = |content_type: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+        headers
+    };
+    assert!(is_binary(&headers("image/png"), b"\x89PNG"));
+    assert!(is_binary(
+        &headers("Application/PDF; version=1.7"),
+        b"%PDF-1.7"
+    ));
+    assert!(!is_binary(&headers("image/svg+xml"), b"<svg/>"));
+    assert!(!is_binary(
+        &headers("text/plain; charset=latin1"),
+        b"caf\xe9"
+    ));
+    assert!(is_binary(&HeaderMap::new(), b"\x00\x01"));
+    assert!(!is_binary(&HeaderMap::new(), b"plain"));
+
+    assert_eq!(
+        format_binary(&headers("image/png"), b"abc"),
+        "Binary body: image/png, 3 bytes, sha256 ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n\
+         Save it with -o/--output <file>."
+    );
+}
@@ -0,0 +1,706 @@
+use crate::{assertion::Assertion, auth::Auth, body::BodyFormat, time, yaml};
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+/// Directives that a request file extending another adds to the other's, instead of replacing
+/// them.
+const ADDITIVE: [&str; 5] = ["depends", "tags", "assert", "pre", "post"];
+
+/// Directives given at the top of a request file, before the method and URL line.
+///
+/// Each directive sits on its own line and starts with `@`, e.g. `@timeout: 60`. Lines starting
+/// with `#` are comments, unless what follows is a directive, as in `# @tags smoke`. The same
+/// directives can be given as a YAML mapping between `---` lines, above any others.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrontMatter {
+    /// A human readable name for the request, besides the one its path gives it.
+    pub name: Option<String>,
+    /// What the request is for.
+    pub description: Option<String>,
+    /// Overrides the global timeout for this request only.
+    pub timeout: Option<Duration>,
+    /// Requests that must be executed first, so their responses can be used in this one.
+    pub depends: Vec<String>,
+    /// Expectations about the response, checked with `--check`. `@status` adds one.
+    pub asserts: Vec<Assertion>,
+    /// The statuses the response may have, given with `@expect`. Any other fails the request,
+    /// with or without `--check`.
+    pub expect: Vec<u16>,
+    /// Labels for selecting requests, e.g. with `reqq test --tag smoke`.
+    pub tags: Vec<String>,
+    /// Credentials to send the request with, overriding the environment's `auth`.
+    pub auth: Option<Auth>,
+    /// How the body is sent: as it is, URL-encoded with `@form`, with `@multipart`, or as a
+    /// GraphQL query with `@graphql`.
+    pub body: BodyFormat,
+    /// A file to send as the body, relative to the request file.
+    pub body_file: Option<String>,
+    /// The `.proto` file describing the service of a gRPC request, relative to the request
+    /// file.
+    pub proto: Option<String>,
+    /// A JSON Schema file the response body is checked against, relative to the request file.
+    pub schema: Option<String>,
+    /// Commands run before the request is rendered, whose output is added to its variables.
+    pub pre: Vec<String>,
+    /// Commands run after the response is received, with it on their stdin.
+    pub post: Vec<String>,
+    /// A script run before the request is rendered, which can set its variables.
+    pub pre_script: Option<String>,
+    /// A script run after the response is received, which can check it and save values from it.
+    pub post_script: Option<String>,
+}
+
+/// Splits the front-matter off a request file, returning it with the rest of the file.
+pub fn parse(fstr: &str) -> Result<(FrontMatter, &str)> {
+    let mut front_matter = FrontMatter::default();
+    let mut rest = fstr;
+
+    if let Some((block, after)) = yaml_block(fstr) {
+        let mapping = yaml::parse(block).map_err(|e| anyhow!("Invalid front matter: {}", e))?;
+        match mapping {
+            Value::Object(map) => {
+                for (key, value) in map.iter() {
+                    front_matter.apply_yaml(key, value)?;
+                }
+            }
+            Value::Null => {}
+            _ => {
+                return Err(anyhow!(
+                    "The front matter between `---` lines must be a mapping."
+                ))
+            }
+        }
+        rest = after;
+    } else if fstr.starts_with("---") {
+        return Err(anyhow!(
+            "The front matter is missing its closing `---` line."
+        ));
+    }
+
+    while rest.starts_with(['@', '#']) {
+        let (line, remainder) = split_line(rest);
+        rest = remainder;
+        let Some(directive) = line_directive(line) else {
+            continue;
+        };
+        let (key, value) = split_directive(directive);
+        front_matter.apply(key, value)?;
+    }
+
+    Ok((front_matter, rest))
+}
+
+/// Merges the front matter of a request file with that of the request it extends, before
+/// either is templated, returning it with the rest of each file. Directives the file gives
+/// replace the other's, except those like `@tags` that add to them, and `@form`, `@multipart`
+/// and `@graphql` replace each other. Plain comments are left out, and so is `@extends`.
+pub fn merge<'a>(base: &'a str, fstr: &'a str) -> Result<(String, &'a str, &'a str)> {
+    let (base_yaml, base_lines, base_rest) = split(base)?;
+    let (yaml, lines, rest) = split(fstr)?;
+
+    let overridden: Vec<String> = yaml
+        .keys()
+        .map(String::as_str)
+        .chain(lines.iter().map(|(key, _)| *key))
+        .map(body_group)
+        .filter(|key| !ADDITIVE.contains(key))
+        .map(str::to_owned)
+        .collect();
+    let inherited =
+        |key: &str| key != "extends" && !overridden.iter().any(|k| k == body_group(key));
+
+    let mut merged_yaml = Map::new();
+    let base_entries = base_yaml.into_iter().filter(|(key, _)| inherited(key));
+    for (key, value) in base_entries.chain(yaml.into_iter().filter(|(key, _)| key != "extends")) {
+        let value = match merged_yaml.remove(&key) {
+            Some(existing) => Value::Array(
+                as_list(existing)
+                    .into_iter()
+                    .chain(as_list(value))
+                    .collect(),
+            ),
+            None => value,
+        };
+        merged_yaml.insert(key, value);
+    }
+
+    let mut merged = String::new();
+    if !merged_yaml.is_empty() {
+        merged.push_str("---\n");
+        merged.push_str(&yaml::to_string(&Value::Object(merged_yaml)));
+        merged.push_str("---\n");
+    }
+    let base_lines = base_lines.into_iter().filter(|(key, _)| inherited(key));
+    for (_, line) in base_lines.chain(lines.into_iter().filter(|(key, _)| *key != "extends")) {
+        merged.push_str(line);
+        merged.push('\n');
+    }
+    Ok((merged, base_rest, rest))
+}
+
+/// The directives of a request file that hasn't been templated yet, with their values, those
+/// of the YAML front matter first, and the rest of the file. YAML mappings are given as JSON.
+pub fn directives(fstr: &str) -> Result<(Vec<(String, String)>, &str)> {
+    let (yaml, lines, rest) = split(fstr)?;
+    let mut directives = vec![];
+    for (key, value) in yaml {
+        for value in as_list(value) {
+            match value {
+                Value::Bool(false) => {}
+                Value::Bool(true) | Value::Null => directives.push((key.clone(), String::new())),
+                Value::String(s) => directives.push((key.clone(), s)),
+                value => directives.push((key.clone(), value.to_string())),
+            }
+        }
+    }
+    for (_, line) in lines {
+        if let Some((key, value)) = line_directive(line).map(split_directive) {
+            directives.push((key.to_owned(), value.to_owned()));
+        }
+    }
+    Ok((directives, rest))
+}
+
+/// Directive lines with their keys.
+type Directives<'a> = Vec<(&'a str, &'a str)>;
+
+/// The YAML front matter of a request file, its directive lines, and the rest of it.
+fn split(fstr: &str) -> Result<(Map<String, Value>, Directives<'_>, &str)> {
+    let (yaml, mut rest) = match yaml_block(fstr) {
+        Some((block, rest)) => {
+            match yaml::parse(block).map_err(|e| anyhow!("Invalid front matter: {}", e))? {
+                Value::Object(map) => (map, rest),
+                Value::Null => (Map::new(), rest),
+                _ => {
+                    return Err(anyhow!(
+                        "The front matter between `---` lines must be a mapping."
+                    ))
+                }
+            }
+        }
+        None => (Map::new(), fstr),
+    };
+    let mut lines = vec![];
+    while rest.starts_with(['@', '#']) {
+        let (line, remainder) = split_line(rest);
+        rest = remainder;
+        if let Some(directive) = line_directive(line) {
+            lines.push((split_directive(directive).0, line));
+        }
+    }
+    Ok((yaml, lines, rest))
+}
+
+fn split_line(s: &str) -> (&str, &str) {
+    match s.find('\n') {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => (s, ""),
+    }
+}
+
+/// The body directives replace each other, since a request can only use one.
+fn body_group(key: &str) -> &str {
+    match key {
+        "form" | "multipart" | "graphql" => "body",
+        key => key,
+    }
+}
+
+fn as_list(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items,
+        value => vec![value],
+    }
+}
+
+impl FrontMatter {
+    fn apply(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "name" => self.name = Some(value.to_owned()),
+            "description" => self.description = Some(value.to_owned()),
+            "timeout" => self.timeout = Some(parse_timeout(value)?),
+            "depends" => self.depends.extend(parse_list(value)),
+            "tags" => self.tags.extend(parse_list(value)),
+            "assert" => self.asserts.push(Assertion::parse(value)?),
+            "status" => self.asserts.push(parse_status(value)?),
+            "expect" => {
+                for status in parse_list(value) {
+                    self.expect.push(parse_expect(&status)?);
+                }
+            }
+            "auth" => self.auth = Some(Auth::parse(value)?),
+            "form" => self.set_body(BodyFormat::Form)?,
+            "multipart" => self.set_body(BodyFormat::Multipart)?,
+            "graphql" => self.set_body(BodyFormat::GraphQL)?,
+            "body-file" if !value.is_empty() => self.body_file = Some(value.to_owned()),
+            "body-file" => return Err(anyhow!("@body-file needs a path.")),
+            "proto" if !value.is_empty() => self.proto = Some(value.to_owned()),
+            "proto" => return Err(anyhow!("@proto needs a path.")),
+            "schema" if !value.is_empty() => self.schema = Some(value.to_owned()),
+            "schema" => return Err(anyhow!("@schema needs a path.")),
+            "pre" | "post" if value.is_empty() => return Err(anyhow!("@{} needs a command.", key)),
+            "pre" => self.pre.push(value.to_owned()),
+            "post" => self.post.push(value.to_owned()),
+            "pre-script" | "post-script" if value.trim().is_empty() => {
+                return Err(anyhow!("@{} needs a script.", key))
+            }
+            "pre-script" => append_line(&mut self.pre_script, value),
+            "post-script" => append_line(&mut self.post_script, value),
+            _ => return Err(anyhow!("Unknown directive '@{}'.", key)),
+        }
+        Ok(())
+    }
+
+    /// Applies a key of the YAML front matter. Lists give a directive several times, `true`
+    /// gives a flag like `form`, and `auth` takes a mapping too.
+    fn apply_yaml(&mut self, key: &str, value: &Value) -> Result<()> {
+        match value {
+            Value::Object(_) if key == "auth" => self.auth = Some(Auth::from_value(value)?),
+            Value::Array(items) => {
+                for item in items {
+                    self.apply_yaml(key, item)?;
+                }
+            }
+            Value::Bool(false) => {}
+            Value::Bool(true) | Value::Null => self.apply(key, "")?,
+            Value::String(s) => self.apply(key, s)?,
+            Value::Number(n) => self.apply(key, &n.to_string())?,
+            Value::Object(_) => {
+                return Err(anyhow!(
+                    "Invalid front matter: '{}' can't be a mapping.",
+                    key
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn set_body(&mut self, body: BodyFormat) -> Result<()> {
+        if self.body != BodyFormat::Text && self.body != body {
+            return Err(anyhow!(
+                "A request can only use one of @form, @multipart and @graphql."
+            ));
+        }
+        self.body = body;
+        Ok(())
+    }
+}
+
+/// Reads the `@depends` directives of a request file that hasn't been templated yet, since
+/// its dependencies have to be executed before it can be.
+pub fn dependencies(fstr: &str) -> Vec<String> {
+    raw_list_directive(fstr, "depends")
+}
+
+/// Reads the `@extends` directive of a request file that hasn't been templated yet, naming
+/// the request it extends.
+pub fn extends(fstr: &str) -> Option<String> {
+    raw_directive(fstr, "extends")
+        .into_iter()
+        .find(|name| !name.is_empty())
+}
+
+/// Reads the `@tags` directives of a request file that hasn't been templated yet, so requests
+/// can be selected by tag without rendering them.
+pub fn tags(fstr: &str) -> Vec<String> {
+    raw_list_directive(fstr, "tags")
+}
+
+/// Reads the `@pre` and `@post` hook commands of a request file that hasn't been templated
+/// yet, since pre hooks give variables for its templates.
+pub fn hooks(fstr: &str) -> (Vec<String>, Vec<String>) {
+    (raw_directive(fstr, "pre"), raw_directive(fstr, "post"))
+}
+
+/// Reads the `@pre-script` and `@post-script` of a request file that hasn't been templated
+/// yet. A script given on several directive lines is joined into one.
+pub fn scripts(fstr: &str) -> (Option<String>, Option<String>) {
+    let script = |directive| {
+        Some(raw_directive(fstr, directive).join("\n")).filter(|s| !s.trim().is_empty())
+    };
+    (script("pre-script"), script("post-script"))
+}
+
+/// The `@description` of a request file that hasn't been templated yet, or else the first
+/// comment of its front matter that isn't a directive.
+pub fn description(fstr: &str) -> Option<String> {
+    raw_directive(fstr, "description")
+        .into_iter()
+        .next()
+        .or_else(|| {
+            lines(fstr)
+                .filter(|line| line_directive(line).is_none())
+                .map(|line| line.trim_start_matches('#').trim())
+                .find(|text| !text.is_empty())
+                .map(str::to_owned)
+        })
+}
+
+/// The line after the front matter of a request file, with the method and URL.
+pub fn request_line(fstr: &str) -> &str {
+    after_yaml_block(fstr)
+        .lines()
+        .find(|line| !line.starts_with(['@', '#']))
+        .unwrap_or_default()
+}
+
+/// The directive lines of the front matter of a request file, after any YAML block.
+fn lines(fstr: &str) -> impl Iterator<Item = &str> {
+    after_yaml_block(fstr)
+        .lines()
+        .take_while(|line| line.starts_with(['@', '#']))
+}
+
+/// Adds a line to a script given over several directives.
+fn append_line(script: &mut Option<String>, line: &str) {
+    match script {
+        Some(script) => {
+            script.push('\n');
+            script.push_str(line);
+        }
+        None => *script = Some(line.to_owned()),
+    }
+}
+
+fn raw_list_directive(fstr: &str, directive: &str) -> Vec<String> {
+    raw_directive(fstr, directive)
+        .iter()
+        .flat_map(|value| parse_list(value))
+        .collect()
+}
+
+/// The values given for a directive, in the YAML block and then in directive lines. A YAML
+/// block that doesn't parse before templating is skipped.
+fn raw_directive(fstr: &str, directive: &str) -> Vec<String> {
+    let mut values = vec![];
+    if let Some(Ok(Value::Object(map))) = yaml_block(fstr).map(|(block, _)| yaml::parse(block)) {
+        match map.get(directive) {
+            Some(Value::Array(items)) => {
+                values.extend(items.iter().filter_map(Value::as_str).map(str::to_owned))
+            }
+            Some(Value::String(value)) => values.push(value.clone()),
+            _ => {}
+        }
+    }
+    values.extend(
+        lines(fstr)
+            .filter_map(line_directive)
+            .map(split_directive)
+            .filter(|(key, _)| *key == directive)
+            .map(|(_, value)| value.to_owned()),
+    );
+    values
+}
+
+/// Splits the YAML between the `---` line a request file starts with and the next one off
+/// the rest of the file.
+fn yaml_block(fstr: &str) -> Option<(&str, &str)> {
+    let body = fstr
+        .strip_prefix("---\n")
+        .or_else(|| fstr.strip_prefix("---\r\n"))?;
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Some((&body[..offset], &body[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn after_yaml_block(fstr: &str) -> &str {
+    yaml_block(fstr).map_or(fstr, |(_, rest)| rest)
+}
+
+/// The directive on a front-matter line, without any `#` in front of it. Plain comments have
+/// none.
+fn line_directive(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    let line = line.strip_prefix('#').map_or(line, str::trim_start);
+    line.starts_with('@').then_some(line)
+}
+
+/// Splits `@key: value` or `@key value` into its key and value.
+fn split_directive(line: &str) -> (&str, &str) {
+    let line = line.trim_start_matches('@');
+    let end = line
+        .find(|c: char| c == ':' || c.is_whitespace())
+        .unwrap_or(line.len());
+    let (key, value) = line.split_at(end);
+    (key, value.trim_start_matches(':').trim())
+}
+
+/// Lists of names, like dependencies and tags, are separated by commas or whitespace.
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn parse_timeout(value: &str) -> Result<Duration> {
+    time::parse_duration(value).map_err(|e| anyhow!("Invalid timeout: {}.", e))
+}
+
+/// `@status 201` expects the response to have that status, like `@assert status == 201`.
+fn parse_status(value: &str) -> Result<Assertion> {
+    match value.parse::<u16>() {
+        Ok(status) if (100..600).contains(&status) => {
+            Assertion::parse(&format!("status == {}", status))
+        }
+        _ => Err(anyhow!(
+            "Invalid @status '{}', expected a status code like 200.",
+            value
+        )),
+    }
+}
+
+/// A status code given with `@expect`.
+fn parse_expect(value: &str) -> Result<u16> {
+    match value.parse::<u16>() {
+        Ok(status) if (100..600).contains(&status) => Ok(status),
+        _ => Err(anyhow!(
+            "Invalid @expect '{}', expected status codes like 201.",
+            value
+        )),
+    }
+}
+
+#[test]
+fn test_front_matter_expect() {
+    let (front_matter, _) = parse(
+        "@expect 201, 202
+POST https://example.com/jobs",
+    )
+    .unwrap();
+    assert_eq!(front_matter.expect, vec![201, 202]);
+    let (front_matter, _) = parse(
+        "---
+expect: [404]
+---
+GET https://example.com/gone",
+    )
+    .unwrap();
+    assert_eq!(front_matter.expect, vec![404]);
+    assert!(parse(
+        "@expect created
+POST https://example.com/jobs"
+    )
+    .is_err());
+    assert!(parse(
+        "@expect 999
+POST https://example.com/jobs"
+    )
+    .is_err());
+}
+
+#[test]
+fn test_front_matter_timeout() {
+    let (front_matter, rest) = parse("@timeout: 60\nGET https://example.com\n").unwrap();
+    assert_eq!(front_matter.timeout, Some(Duration::from_secs(60)));
+    assert_eq!(rest, "GET https://example.com\n");
+
+    let (front_matter, _) = parse("@timeout 5s\nGET https://example.com").unwrap();
+    assert_eq!(front_matter.timeout, Some(Duration::from_secs(5)));
+
+    let (front_matter, _) = parse("@timeout: 250ms\nGET https://example.com").unwrap();
+    assert_eq!(front_matter.timeout, Some(Duration::from_millis(250)));
+}
+
+#[test]
+fn test_front_matter_depends() {
+    let fstr =
+        "@depends login\n@timeout: {{ timeout }}\n@depends: auth/token, users/me\nGET {{ url }}";
+    assert_eq!(dependencies(fstr), vec!["login", "auth/token", "users/me"]);
+    assert!(dependencies("GET https://example.com\n@depends login").is_empty());
+    assert_eq!(
+        tags("@tags: smoke, users\n@depends login\nGET {{ url }}"),
+        vec!["smoke", "users"]
+    );
+
+    let (front_matter, _) = parse("@depends login\nGET https://example.com").unwrap();
+    assert_eq!(front_matter.depends, vec!["login"]);
+}
+
+#[test]
+fn test_front_matter_hooks() {
+    let fstr = "@pre ./scripts/token.sh --scope {{ scope }}\n@post: jq .status\nGET {{ url }}";
+    assert_eq!(
+        hooks(fstr),
+        (
+            vec!["./scripts/token.sh --scope {{ scope }}".to_owned()],
+            vec!["jq .status".to_owned()]
+        )
+    );
+
+    let (front_matter, _) = parse("@pre ./a.sh\n@pre ./b.sh\nGET https://example.com").unwrap();
+    assert_eq!(front_matter.pre, vec!["./a.sh", "./b.sh"]);
+    assert!(parse("@post\nGET https://example.com").is_err());
+}
+
+#[test]
+fn test_front_matter_scripts() {
+    let fstr = "@pre-script let ts = now();\n@pre-script vars.ts = ts;\nGET {{ url }}";
+    assert_eq!(
+        scripts(fstr),
+        (Some("let ts = now();\nvars.ts = ts;".to_owned()), None)
+    );
+
+    let fstr = "---\npost-script: |\n  assert response.status == 200\n  vars.id = response.body.id\n---\nGET {{ url }}";
+    assert_eq!(
+        scripts(fstr).1.as_deref(),
+        Some("assert response.status == 200\nvars.id = response.body.id\n")
+    );
+    let (front_matter, _) = parse(fstr).unwrap();
+    assert!(front_matter.post_script.is_some());
+    assert!(parse("@pre-script\nGET https://example.com").is_err());
+}
+
+#[test]
+fn test_front_matter_asserts() {
+    let (front_matter, rest) =
+        parse("@assert status == 200\n@assert: $.id exists\nGET https://example.com").unwrap();
+    assert_eq!(front_matter.asserts.len(), 2);
+    assert_eq!(rest, "GET https://example.com");
+
+    assert!(parse("@assert status is 200\nGET https://example.com").is_err());
+}
+
+#[test]
+fn test_front_matter_auth() {
+    let (front_matter, _) = parse("@auth: bearer abc\nGET https://example.com").unwrap();
+    assert_eq!(
+        front_matter.auth,
+        Some(Auth::Bearer {
+            token: "abc".to_owned()
+        })
+    );
+}
+
+#[test]
+fn test_front_matter_body() {
+    let (front_matter, _) = parse("@form\nPOST https://example.com").unwrap();
+    assert_eq!(front_matter.body, BodyFormat::Form);
+    assert!(parse("@form\n@multipart\nPOST https://example.com").is_err());
+    assert!(parse("@graphql\n@form\nPOST https://example.com").is_err());
+
+    let (front_matter, _) = parse("@graphql\nPOST https://example.com/graphql").unwrap();
+    assert_eq!(front_matter.body, BodyFormat::GraphQL);
+
+    let (front_matter, _) = parse("@body-file ./payload.bin\nPOST https://example.com").unwrap();
+    assert_eq!(front_matter.body_file.as_deref(), Some("./payload.bin"));
+    assert!(parse("@body-file\nPOST https://example.com").is_err());
+
+    let (front_matter, _) =
+        parse("@proto ../files/shop.proto\nGRPC localhost:50051 shop.Orders/Get").unwrap();
+    assert_eq!(front_matter.proto.as_deref(), Some("../files/shop.proto"));
+    assert!(parse("@proto\nGRPC localhost:50051 shop.Orders/Get").is_err());
+
+    let (front_matter, _) =
+        parse("@schema ../schemas/user.json\nGET https://example.com/users/7").unwrap();
+    assert_eq!(front_matter.schema.as_deref(), Some("../schemas/user.json"));
+    assert!(parse("@schema\nGET https://example.com/users/7").is_err());
+}
+
+#[test]
+fn test_front_matter_comments() {
+    let fstr = "# Creates a user.\n# @tags auth, smoke\n@timeout 5s\n#\nPOST https://example.com";
+    let (front_matter, rest) = parse(fstr).unwrap();
+    assert_eq!(front_matter.tags, vec!["auth", "smoke"]);
+    assert_eq!(front_matter.timeout, Some(Duration::from_secs(5)));
+    assert_eq!(rest, "POST https://example.com");
+    assert_eq!(tags(fstr), vec!["auth", "smoke"]);
+    assert!(parse("# @nope\nGET https://example.com").is_err());
+
+    assert_eq!(description(fstr).as_deref(), Some("Creates a user."));
+    assert_eq!(
+        description("# @tags smoke\n#\nGET https://example.com"),
+        None
+    );
+    assert_eq!(request_line(fstr), "POST https://example.com");
+}
+
+#[test]
+fn test_front_matter_none() {
+    let (front_matter, rest) = parse("GET https://example.com").unwrap();
+    assert_eq!(front_matter, FrontMatter::default());
+    assert_eq!(rest, "GET https://example.com");
+}
+
+#[test]
+fn test_front_matter_invalid() {
+    assert!(parse("@timeout: soon\nGET https://example.com").is_err());
+    assert!(parse("@nope: 1\nGET https://example.com").is_err());
+}
+
+#[test]
+fn test_front_matter_yaml() {
+    let fstr = "---
+name: Create user
+description: Creates a user with a random email.
+tags: [users, smoke]
+timeout: 5s
+status: 201
+depends:
+  - login
+form: true
+auth:
+  type: bearer
+  token: abc
+---
+@tags slow
+POST https://example.com/users";
+    let (front_matter, rest) = parse(fstr).unwrap();
+    assert_eq!(front_matter.name.as_deref(), Some("Create user"));
+    assert_eq!(
+        front_matter.description.as_deref(),
+        Some("Creates a user with a random email.")
+    );
+    assert_eq!(front_matter.tags, vec!["users", "smoke", "slow"]);
+    assert_eq!(front_matter.timeout, Some(Duration::from_secs(5)));
+    assert_eq!(
+        front_matter.asserts,
+        vec![Assertion::parse("status == 201").unwrap()]
+    );
+    assert_eq!(front_matter.depends, vec!["login"]);
+    assert_eq!(front_matter.body, BodyFormat::Form);
+    assert_eq!(
+        front_matter.auth,
+        Some(Auth::Bearer {
+            token: "abc".to_owned()
+        })
+    );
+    assert_eq!(rest, "POST https://example.com/users");
+
+    assert_eq!(tags(fstr), vec!["users", "smoke", "slow"]);
+    assert_eq!(dependencies(fstr), vec!["login"]);
+    assert_eq!(
+        description(fstr).as_deref(),
+        Some("Creates a user with a random email.")
+    );
+    assert_eq!(request_line(fstr), "POST https://example.com/users");
+
+    assert!(parse("---\ntimeout: 5s\nGET https://example.com").is_err());
+    assert!(parse("---\n- a\n---\nGET https://example.com").is_err());
+    assert!(parse("---\nnope: 1\n---\nGET https://example.com").is_err());
+    assert!(parse("@status: ok\nGET https://example.com").is_err());
+}
+
+#[test]
+fn test_front_matter_merge() {
+    let base = "---\ntags: [users]\ntimeout: 5s\nmultipart: true\n---\n# The users API.\n@depends login\n@auth bearer {{ token }}\nPOST {{ baseUrl }}/users\n";
+    let fstr = "# Creates an admin.\n@extends users/create\n@tags admin\n@form\n@auth basic {{ user }}:{{ password }}\n\n{}";
+    let (merged, base_rest, rest) = merge(base, fstr).unwrap();
+    assert_eq!(merged, "---\ntags:\n  - users\ntimeout: 5s\n---\n@depends login\n@tags admin\n@form\n@auth basic {{ user }}:{{ password }}\n");
+    assert_eq!((base_rest, rest), ("POST {{ baseUrl }}/users\n", "\n{}"));
+
+    let (merged, _, _) = merge(base, "---\ntags: admin\ntimeout: 30s\n---\n\n{}").unwrap();
+    assert_eq!(merged, "---\nmultipart: true\ntags:\n  - users\n  - admin\ntimeout: 30s\n---\n@depends login\n@auth bearer {{ token }}\n");
+    assert_eq!(
+        extends("# @extends users/create\nGET {{ url }}").as_deref(),
+        Some("users/create")
+    );
+    assert_eq!(extends("GET {{ url }}"), None);
+}
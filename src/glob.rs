@@ -0,0 +1,63 @@
+/// Matches a request name against a glob pattern. `*` matches any characters except `/`, `**`
+/// matches across folders, and `?` matches a single character other than `/`.
+///
+/// `users/*` matches `users/get` but not `users/admin/get`, while `users/**` matches both.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches_from(&pattern, &name)
+}
+
+fn matches_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            // `**/` also matches no folders at all.
+            matches_from(rest, name)
+                || (0..name.len()).any(|i| name[i] == '/' && matches_from(rest, &name[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=name.len()).any(|i| matches_from(rest, &name[i..])),
+        ['*', rest @ ..] => {
+            for i in 0..=name.len() {
+                if matches_from(rest, &name[i..]) {
+                    return true;
+                }
+                if i < name.len() && name[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => match name {
+            [c, name_rest @ ..] if *c != '/' => matches_from(rest, name_rest),
+            _ => false,
+        },
+        [p, rest @ ..] => match name {
+            [c, name_rest @ ..] if c == p => matches_from(rest, name_rest),
+            _ => false,
+        },
+    }
+}
+
+#[test]
+fn test_glob_matches() {
+    assert!(matches("users/get", "users/get"));
+    assert!(!matches("users/get", "users/get-all"));
+
+    assert!(matches("users/*", "users/get"));
+    assert!(!matches("users/*", "users/admin/get"));
+    assert!(matches("*/get", "users/get"));
+    assert!(matches("users/get-*", "users/get-all"));
+    assert!(matches("*", "login"));
+    assert!(!matches("*", "auth/login"));
+
+    assert!(matches("users/**", "users/get"));
+    assert!(matches("users/**", "users/admin/get"));
+    assert!(matches("**/get", "get"));
+    assert!(matches("**/get", "users/admin/get"));
+    assert!(!matches("**/get", "users/forget"));
+    assert!(matches("**", "a/b/c"));
+
+    assert!(matches("users/?et", "users/get"));
+    assert!(!matches("users?get", "users/get"));
+}
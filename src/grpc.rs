@@ -0,0 +1,183 @@
+//! gRPC requests, whose first line is `GRPC host:port package.Service/Method`. The JSON body is
+//! encoded with the message types of the request's `@proto` file, and the response message is
+//! decoded back to JSON.
+
+use crate::{proto::Schema, request::Request, response::Response};
+use anyhow::{anyhow, Result};
+use percent_encoding::percent_decode_str;
+use reqwest::header::{CONTENT_TYPE, TE};
+use serde_json::{json, Value};
+use url::Url;
+
+/// The method of gRPC request files.
+pub const METHOD: &str = "GRPC";
+
+/// The names of gRPC status codes, by code.
+const STATUS_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+/// The URL a gRPC call is posted to, from what follows `GRPC` on the first line: the server
+/// and the method, as in `localhost:50051 shop.v1.Orders/GetOrder`, or both in one URL.
+pub fn url(target: &str) -> Result<Url> {
+    let (server, method) = target
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((target.trim(), ""));
+    let server = if server.contains("://") {
+        server.to_owned()
+    } else {
+        format!("http://{}", server)
+    };
+    let mut url = Url::parse(&server)?;
+    if !method.trim().is_empty() {
+        url.set_path(method.trim());
+    }
+    Ok(url)
+}
+
+/// Makes the unary call of a parsed `GRPC` request, and returns the response with its message
+/// as pretty-printed JSON for a body.
+pub fn call(req: &Request) -> Result<Response> {
+    let inner = req
+        .inner()
+        .ok_or_else(|| anyhow!("Request has not been parsed."))?;
+    let proto = inner
+        .proto()
+        .ok_or_else(|| anyhow!("A gRPC request needs a @proto file describing its service."))?;
+    if inner.url().scheme() != "http" {
+        return Err(anyhow!(
+            "gRPC calls can only be made without TLS, to http:// servers."
+        ));
+    }
+    let (service, method) = inner
+        .url()
+        .path()
+        .trim_start_matches('/')
+        .split_once('/')
+        .ok_or_else(|| {
+            anyhow!("Expected the gRPC method after the server, like package.Service/Method.")
+        })?;
+    let schema = Schema::load(proto)?;
+    let rpc = schema.rpc(service, method)?;
+    if rpc.streaming {
+        return Err(anyhow!(
+            "{}/{} streams messages, and only unary calls are supported.",
+            service,
+            method
+        ));
+    }
+
+    let body: Value = match inner.body() {
+        Some(body) => serde_json::from_slice(&body.encode()?.bytes)
+            .map_err(|e| anyhow!("The body of a gRPC request must be JSON: {}", e))?,
+        None => json!({}),
+    };
+    let message = schema.encode(&rpc.input, &body)?;
+    // Each message is prefixed with whether it's compressed and its length.
+    let mut frame = vec![0];
+    frame.extend((message.len() as u32).to_be_bytes());
+    frame.extend(message);
+
+    let client = req.url_client_opts().build_http2()?;
+    let mut builder = client
+        .post(inner.url().clone())
+        .header(CONTENT_TYPE, "application/grpc")
+        .header(TE, "trailers");
+    for (name, value) in inner.headers() {
+        builder = builder.header(name, value);
+    }
+    if let Some(auth) = req.auth() {
+        builder = auth.apply(builder)?;
+    }
+    if let Some(timeout) = req.timeout() {
+        builder = builder.timeout(timeout);
+    }
+    let resp = builder.body(frame).send()?;
+    let (status, version, headers) = (resp.status(), resp.version(), resp.headers().clone());
+    let bytes = resp.bytes()?;
+    if !status.is_success() {
+        return Err(anyhow!(
+            "The server answered the gRPC call with {}.",
+            status
+        ));
+    }
+
+    // reqwest doesn't read trailers, where the status of a call usually is. Calls that fail
+    // before any message is sent have it in the headers instead.
+    if let Some(code) = headers
+        .get("grpc-status")
+        .and_then(|v| v.to_str().ok())
+        .filter(|code| *code != "0")
+    {
+        let name = code
+            .parse::<usize>()
+            .ok()
+            .and_then(|code| STATUS_NAMES.get(code))
+            .unwrap_or(&"UNKNOWN");
+        let message = headers
+            .get("grpc-message")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        return Err(anyhow!(
+            "The gRPC call failed with {} ({}): {}",
+            name,
+            code,
+            percent_decode_str(message).decode_utf8_lossy()
+        ));
+    }
+    let message = match bytes.as_ref() {
+        [] => {
+            return Err(anyhow!(
+                "The server answered the gRPC call without a message."
+            ))
+        }
+        [0, len @ ..] if len.len() >= 4 => {
+            let size = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+            len[4..]
+                .get(..size)
+                .ok_or_else(|| anyhow!("The gRPC response message is incomplete."))?
+        }
+        [1, ..] => {
+            return Err(anyhow!(
+                "The gRPC response message is compressed, which isn't supported."
+            ))
+        }
+        _ => return Err(anyhow!("Invalid gRPC response message.")),
+    };
+
+    Ok(Response {
+        status,
+        version,
+        headers,
+        body: serde_json::to_string_pretty(&schema.decode(&rpc.output, message)?)?,
+        binary: None,
+    })
+}
+
+#[test]
+fn test_url() {
+    let parsed = url("localhost:50051 shop.v1.Orders/GetOrder").unwrap();
+    assert_eq!(
+        parsed.as_str(),
+        "http://localhost:50051/shop.v1.Orders/GetOrder"
+    );
+    assert_eq!(url(parsed.as_str()).unwrap(), parsed);
+    assert!(url("").is_err());
+}
@@ -0,0 +1,284 @@
+//! Converting HAR (HTTP Archive) files into request files, and the history into HAR.
+//!
+//! See http://www.softwareishard.com/blog/har-12-spec/
+
+use crate::{
+    history::Entry,
+    import::{slug, Import},
+    request::Request,
+    time,
+};
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use serde_json::{json, Value};
+
+/// Headers a client sets on its own, which a request file shouldn't pin.
+const SKIPPED_HEADERS: [&str; 5] = [
+    "host",
+    "content-length",
+    "connection",
+    "accept-encoding",
+    "cookie",
+];
+
+/// Converts the requests recorded in a HAR file, in folders named after their hosts. Cookies
+/// are left out, since they're usually tied to the browser session.
+pub fn convert(har: &Value) -> Result<Import> {
+    let entries = har["log"]["entries"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Expected a HAR file, with a log of entries."))?;
+
+    let mut import = Import::default();
+    for entry in entries {
+        let request = &entry["request"];
+        let (method, url) = match (request["method"].as_str(), request["url"].as_str()) {
+            (Some(method), Some(url)) => (method, url),
+            _ => {
+                import
+                    .warnings
+                    .push("An entry without a method and URL was skipped.".to_owned());
+                continue;
+            }
+        };
+        let parsed = url::Url::parse(url).map_err(|e| anyhow!("Invalid URL '{}': {}", url, e))?;
+        let folder = slug(parsed.host_str().unwrap_or_default(), "requests");
+        let segment = parsed
+            .path_segments()
+            .and_then(|mut s| s.rfind(|s| !s.is_empty()))
+            .unwrap_or_default();
+        let name = format!("{}/{}", folder, slug(segment, "index"));
+
+        let mut directives = vec![];
+        let mut lines = vec![format!("{} {}", method, url)];
+        for header in request["headers"].as_array().into_iter().flatten() {
+            let (name, value) = (string(&header["name"]), string(&header["value"]));
+            // HTTP/2 pseudo-headers like `:authority` are part of the method and URL.
+            if name.starts_with(':')
+                || value.is_empty()
+                || SKIPPED_HEADERS.contains(&name.to_lowercase().as_str())
+            {
+                continue;
+            }
+            lines.push(format!("{}: {}", name, value));
+        }
+
+        let post_data = &request["postData"];
+        let body = match (post_data["text"].as_str(), post_data["params"].as_array()) {
+            (Some(text), _) if !text.is_empty() => Some(text.to_owned()),
+            (_, Some(params)) if !params.is_empty() => {
+                directives.push("@form".to_owned());
+                let fields: Vec<String> = params
+                    .iter()
+                    .map(|p| format!("{}={}", string(&p["name"]), string(&p["value"])))
+                    .collect();
+                Some(fields.join("\n"))
+            }
+            _ => None,
+        };
+
+        directives.extend(lines);
+        let fstr = match body {
+            Some(body) => format!("{}\n\n{}", directives.join("\n"), body),
+            None => format!("{}\n", directives.join("\n")),
+        };
+        import.add_request(&name, fstr);
+    }
+    Ok(import)
+}
+
+/// A HAR log of the given history entries, each with its request parsed.
+pub fn log(entries: &[(Entry, Request)]) -> Result<Value> {
+    let entries: Vec<Value> = entries
+        .iter()
+        .map(|(entry, req)| har_entry(entry, req))
+        .collect::<Result<_>>()?;
+    Ok(json!({
+        "log": {
+            "version": "1.2",
+            "creator": { "name": "reqq", "version": env!("CARGO_PKG_VERSION") },
+            "entries": entries,
+        }
+    }))
+}
+
+fn har_entry(entry: &Entry, req: &Request) -> Result<Value> {
+    let inner = req
+        .inner()
+        .ok_or_else(|| anyhow!("Request has not been parsed."))?;
+    let resp = &entry.response;
+    let version = format!("{:?}", resp.version);
+
+    let mut headers: Vec<Value> = inner
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name.as_str(),
+                "value": String::from_utf8_lossy(value.as_bytes()),
+            })
+        })
+        .collect();
+    let mut request = json!({
+        "method": inner.method().as_str(),
+        "url": inner.url().as_str(),
+        "httpVersion": version,
+        "cookies": [],
+        "queryString": inner
+            .url()
+            .query_pairs()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect::<Vec<_>>(),
+        "headersSize": -1,
+        "bodySize": 0,
+    });
+    // Files sent as the body may have changed or be gone, so they're left out if unreadable.
+    if let Some(encoded) = inner.body().and_then(|body| body.encode().ok()) {
+        let has_content_type = inner.headers().iter().any(|(name, _)| name == CONTENT_TYPE);
+        let mime_type = match (&encoded.content_type, has_content_type) {
+            (Some(content_type), false) => {
+                headers.push(json!({ "name": CONTENT_TYPE.as_str(), "value": content_type }));
+                content_type.clone()
+            }
+            _ => header(inner.headers(), CONTENT_TYPE.as_str()).unwrap_or_default(),
+        };
+        request["bodySize"] = json!(encoded.bytes.len());
+        request["postData"] =
+            json!({ "mimeType": mime_type, "text": String::from_utf8_lossy(&encoded.bytes) });
+    }
+    request["headers"] = json!(headers);
+
+    let response_headers: Vec<Value> = resp
+        .headers
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name.as_str(),
+                "value": String::from_utf8_lossy(value.as_bytes()),
+            })
+        })
+        .collect();
+    let mime_type = resp
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    Ok(json!({
+        "startedDateTime": time::format_iso8601(entry.timestamp),
+        "time": entry.duration_ms,
+        "request": request,
+        "response": {
+            "status": resp.status.as_u16(),
+            "statusText": resp.status.canonical_reason().unwrap_or_default(),
+            "httpVersion": version,
+            "cookies": [],
+            "headers": response_headers,
+            "content": { "size": resp.body.len(), "mimeType": mime_type, "text": resp.body },
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": resp.body.len(),
+        },
+        "cache": {},
+        "timings": { "send": 0, "wait": entry.duration_ms, "receive": 0 },
+        "comment": entry.request,
+    }))
+}
+
+fn header(headers: &[(HeaderName, HeaderValue)], name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|(key, _)| key.as_str() == name)
+        .map(|(_, value)| String::from_utf8_lossy(value.as_bytes()).into_owned())
+}
+
+fn string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[test]
+fn test_convert_har() {
+    let har = json!({ "log": { "entries": [
+        { "request": {
+            "method": "POST",
+            "url": "https://api.example.com/v1/users?notify=1",
+            "headers": [
+                { "name": ":authority", "value": "api.example.com" },
+                { "name": "Content-Type", "value": "application/json" },
+                { "name": "Content-Length", "value": "15" },
+                { "name": "Cookie", "value": "session=abc" }
+            ],
+            "postData": { "mimeType": "application/json", "text": "{\"name\":\"Ada\"}" }
+        } },
+        { "request": {
+            "method": "POST",
+            "url": "https://api.example.com/v1/users",
+            "headers": [],
+            "postData": { "mimeType": "application/x-www-form-urlencoded", "params": [{ "name": "name", "value": "Ada" }] }
+        } },
+        { "request": { "method": "GET", "url": "https://example.com/", "headers": [] } },
+        { "request": {} }
+    ] } });
+
+    let import = convert(&har).unwrap();
+    assert_eq!(
+        import.requests,
+        vec![
+            (
+                "api-example-com/users".to_owned(),
+                "POST https://api.example.com/v1/users?notify=1\nContent-Type: application/json\n\n{\"name\":\"Ada\"}".to_owned()
+            ),
+            ("api-example-com/users-2".to_owned(), "@form\nPOST https://api.example.com/v1/users\n\nname=Ada".to_owned()),
+            ("example-com/index".to_owned(), "GET https://example.com/\n".to_owned()),
+        ]
+    );
+    assert_eq!(import.warnings.len(), 1);
+    assert!(convert(&json!({})).is_err());
+}
+
+#[test]
+fn test_har_log() {
+    use reqwest::{header::HeaderMap, StatusCode, Version};
+
+    let rendered = "@form\nPOST https://example.com/login?next=%2Fhome\n\nuser=ada".to_owned();
+    let mut req = Request::from_rendered(".reqq/login.reqq".to_owned(), rendered.clone());
+    req.parse(None, std::collections::HashMap::new()).unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    let entry = Entry {
+        id: "1".to_owned(),
+        timestamp: 1_440_938_160_000,
+        request: "login".to_owned(),
+        env: None,
+        rendered,
+        duration_ms: 42,
+        response: crate::response::Response {
+            status: StatusCode::CREATED,
+            version: Version::HTTP_11,
+            headers,
+            body: "{}".to_owned(),
+            binary: None,
+        },
+        timings: None,
+    };
+
+    let log = log(&[(entry, req)]).unwrap();
+    let entry = &log["log"]["entries"][0];
+    assert_eq!(entry["startedDateTime"], "2015-08-30T12:36:00Z");
+    assert_eq!(
+        entry["request"]["queryString"],
+        json!([{ "name": "next", "value": "/home" }])
+    );
+    assert_eq!(
+        entry["request"]["postData"],
+        json!({ "mimeType": "application/x-www-form-urlencoded", "text": "user=ada" })
+    );
+    assert_eq!(entry["request"]["headers"][0]["name"], "content-type");
+    assert_eq!(entry["response"]["status"], 201);
+    assert_eq!(entry["response"]["statusText"], "Created");
+    assert_eq!(entry["response"]["content"]["text"], "{}");
+    assert_eq!(entry["timings"]["wait"], 42);
+}
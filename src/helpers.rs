@@ -0,0 +1,205 @@
+use crate::time;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use handlebars::{
+    Context, Handlebars, Helper, HelperResult, JsonRender, Output, RenderContext, RenderError,
+};
+use serde_json::Value;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Names of the helpers reqq registers on top of the handlebars built-ins.
+pub const NAMES: [&str; 6] = ["default", "uuid", "now", "epoch", "randomInt", "base64"];
+
+/// Registers reqq's template helpers:
+///
+/// - `{{ default var "fallback" }}`, `var` or the fallback if `var` is missing or null.
+/// - `{{ uuid }}`, a random v4 UUID.
+/// - `{{ now }}`, the current time, formatted as `"iso8601"` (default), `"epoch"` or
+///   `"epoch_ms"`.
+/// - `{{ epoch }}`, the current unix timestamp in seconds.
+/// - `{{ randomInt 1 100 }}`, a random integer between the two bounds, inclusive.
+/// - `{{ base64 var }}`, `var` encoded as base64.
+pub fn register(reg: &mut Handlebars) {
+    reg.register_helper("default", Box::new(default_helper));
+    reg.register_helper("uuid", Box::new(uuid_helper));
+    reg.register_helper("now", Box::new(now_helper));
+    reg.register_helper("epoch", Box::new(epoch_helper));
+    reg.register_helper("randomInt", Box::new(random_int_helper));
+    reg.register_helper("base64", Box::new(base64_helper));
+}
+
+fn default_helper(
+    h: &Helper,
+    r: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let rendered = h
+        .params()
+        .iter()
+        .map(|p| p.value())
+        .find(|v| !v.is_null())
+        .map(|v| v.render())
+        .unwrap_or_default();
+    out.write(&r.get_escape_fn()(&rendered))?;
+    Ok(())
+}
+
+fn uuid_helper(
+    _: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(&uuid_v4())?;
+    Ok(())
+}
+
+fn now_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let millis = time::now_millis();
+    let format = h
+        .param(0)
+        .and_then(|p| p.value().as_str())
+        .unwrap_or("iso8601");
+    let rendered = match format {
+        "iso8601" => time::format_iso8601(millis),
+        "epoch" => (millis / 1000).to_string(),
+        "epoch_ms" => millis.to_string(),
+        _ => {
+            return Err(RenderError::new(format!(
+                "Unknown time format '{}', expected iso8601, epoch or epoch_ms.",
+                format
+            )))
+        }
+    };
+    out.write(&rendered)?;
+    Ok(())
+}
+
+fn epoch_helper(
+    _: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(&(time::now_millis() / 1000).to_string())?;
+    Ok(())
+}
+
+fn random_int_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let bound = |idx: usize| {
+        h.param(idx)
+            .and_then(|p| p.value().as_i64())
+            .ok_or_else(|| {
+                RenderError::new("randomInt expects two integers, e.g. randomInt 1 100.")
+            })
+    };
+    let (min, max) = (bound(0)?, bound(1)?);
+    if min > max {
+        return Err(RenderError::new(format!(
+            "randomInt lower bound {} is greater than upper bound {}.",
+            min, max
+        )));
+    }
+
+    let span = (max as i128 - min as i128 + 1) as u128;
+    let value = min as i128 + (random_u64() as u128 % span) as i128;
+    out.write(&value.to_string())?;
+    Ok(())
+}
+
+fn base64_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let value = h
+        .param(0)
+        .map(|p| p.value())
+        .ok_or_else(|| RenderError::new("base64 expects a value to encode."))?;
+    let rendered = match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    out.write(&STANDARD.encode(rendered))?;
+    Ok(())
+}
+
+/// A random v4 UUID, e.g. `3b241101-e2bb-4255-8caf-4136c566a962`.
+pub(crate) fn uuid_v4() -> String {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&random_u64().to_be_bytes());
+    bytes[8..].copy_from_slice(&random_u64().to_be_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// A random number, good enough for test data but not for anything security related. The std
+/// `RandomState` is randomly seeded, which saves depending on a random number crate.
+pub(crate) fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u64(time::now_millis());
+    hasher.finish()
+}
+
+#[test]
+fn test_uuid_v4() {
+    let uuid = uuid_v4();
+    let re =
+        regex::Regex::new(r"^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$")
+            .unwrap();
+    assert!(re.is_match(&uuid), "{}", uuid);
+    assert_ne!(uuid, uuid_v4());
+}
+
+#[test]
+fn test_helpers() {
+    let data = serde_json::json!({ "creds": "user:pass", "n": 5 });
+    let render = |template: &str| crate::template::render(template, &data).unwrap();
+
+    assert_eq!(render("{{ base64 creds }}"), "dXNlcjpwYXNz");
+    assert_eq!(render("{{ base64 n }}"), "NQ==");
+    assert_eq!(render("{{ randomInt 3 3 }}"), "3");
+    let n: i64 = render("{{ randomInt -5 5 }}").parse().unwrap();
+    assert!((-5..=5).contains(&n));
+
+    assert!(render("{{ epoch }}").parse::<u64>().unwrap() > 1_700_000_000);
+    assert!(render("{{ now \"epoch_ms\" }}").parse::<u64>().unwrap() > 1_700_000_000_000);
+    assert!(render("{{ now }}").ends_with('Z'));
+    assert_eq!(render("{{ uuid }}").len(), 36);
+
+    assert!(crate::template::render("{{ randomInt 5 1 }}", &data).is_err());
+    assert!(crate::template::render("{{ now \"soon\" }}", &data).is_err());
+    assert!(crate::template::render("{{ base64 missing }}", &data).is_err());
+}
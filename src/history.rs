@@ -0,0 +1,236 @@
+use crate::{response::Response, time, timings::Timings};
+use anyhow::{anyhow, Result};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{StatusCode, Version};
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// A request that was executed, along with the response it got.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// Unique id of the entry, assigned when it's recorded.
+    pub id: String,
+    /// When the request was sent, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+    /// The name of the request that was executed.
+    pub request: String,
+    /// The name of the environment that was applied, if any.
+    pub env: Option<String>,
+    /// The request file contents with templating applied.
+    pub rendered: String,
+    /// How long it took to receive the whole response.
+    pub duration_ms: u64,
+    pub response: Response,
+    /// How long each phase of the request took, when it was timed. These aren't recorded.
+    pub timings: Option<Timings>,
+}
+
+impl Entry {
+    /// A one line summary: id, time, status, duration, request and env.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{}  {}  {}  {} ms  {}",
+            self.id,
+            time::format_iso8601(self.timestamp),
+            self.response.status.as_u16(),
+            self.duration_ms,
+            self.request
+        );
+        if let Some(env) = &self.env {
+            summary.push_str(&format!(" ({})", env));
+        }
+        summary
+    }
+}
+
+/// Past executions, stored as one JSON file per entry in the `history` folder of a reqq
+/// directory.
+pub struct History {
+    dir: PathBuf,
+}
+
+impl History {
+    pub fn new(reqq_dir: &str) -> Self {
+        History {
+            dir: Path::new(reqq_dir).join("history"),
+        }
+    }
+
+    /// Saves an entry and returns the id it was recorded under. Ids are the entry's timestamp,
+    /// bumped as needed to stay unique.
+    pub fn record(&self, mut entry: Entry) -> Result<String> {
+        fs::create_dir_all(&self.dir)?;
+
+        // Creating the file claims its id, so requests recorded at once get different ones.
+        let mut id = entry.timestamp;
+        let mut file = loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.path(&id.to_string()))
+            {
+                Ok(file) => break file,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => id += 1,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        entry.id = id.to_string();
+
+        let json = serde_json::to_string_pretty(&entry_to_json(&entry))?;
+        file.write_all(json.as_bytes())?;
+        Ok(entry.id)
+    }
+
+    /// All recorded entries, oldest first.
+    pub fn list(&self) -> Result<Vec<Entry>> {
+        if !self.dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut entries = vec![];
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                entries.push(read_entry(&path)?);
+            }
+        }
+        entries.sort_by_key(|e| e.id.parse::<u64>().unwrap_or(0));
+        Ok(entries)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Entry> {
+        let path = self.path(id);
+        if !path.is_file() {
+            return Err(anyhow!("History entry '{}' not found.", id));
+        }
+        read_entry(&path)
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+fn read_entry(path: &Path) -> Result<Entry> {
+    let value: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    entry_from_json(&value).ok_or_else(|| anyhow!("Malformed history entry '{}'.", path.display()))
+}
+
+fn entry_to_json(entry: &Entry) -> Value {
+    json!({
+        "id": entry.id,
+        "timestamp": entry.timestamp,
+        "request": entry.request,
+        "env": entry.env,
+        "rendered": entry.rendered,
+        "duration_ms": entry.duration_ms,
+        "response": response_to_json(&entry.response),
+    })
+}
+
+fn entry_from_json(value: &Value) -> Option<Entry> {
+    Some(Entry {
+        id: value["id"].as_str()?.to_owned(),
+        timestamp: value["timestamp"].as_u64()?,
+        request: value["request"].as_str()?.to_owned(),
+        env: value["env"].as_str().map(str::to_owned),
+        rendered: value["rendered"].as_str()?.to_owned(),
+        duration_ms: value["duration_ms"].as_u64()?,
+        response: response_from_json(&value["response"])?,
+        timings: None,
+    })
+}
+
+pub fn response_to_json(resp: &Response) -> Value {
+    let headers: Vec<Value> = resp
+        .headers
+        .iter()
+        .map(|(k, v)| json!([k.as_str(), String::from_utf8_lossy(v.as_bytes())]))
+        .collect();
+
+    json!({
+        "status": resp.status.as_u16(),
+        "version": format!("{:?}", resp.version),
+        "headers": headers,
+        "body": resp.body,
+    })
+}
+
+pub fn response_from_json(value: &Value) -> Option<Response> {
+    let mut headers = HeaderMap::new();
+    for pair in value["headers"].as_array()? {
+        let name = HeaderName::from_bytes(pair[0].as_str()?.as_bytes()).ok()?;
+        let val = HeaderValue::from_str(pair[1].as_str()?).ok()?;
+        headers.append(name, val);
+    }
+
+    let version = match value["version"].as_str()? {
+        "HTTP/0.9" => Version::HTTP_09,
+        "HTTP/1.0" => Version::HTTP_10,
+        "HTTP/2.0" => Version::HTTP_2,
+        "HTTP/3.0" => Version::HTTP_3,
+        _ => Version::HTTP_11,
+    };
+
+    Some(Response {
+        status: StatusCode::from_u16(value["status"].as_u64()? as u16).ok()?,
+        version,
+        headers,
+        body: value["body"].as_str()?.to_owned(),
+        binary: None,
+    })
+}
+
+#[test]
+fn test_history_round_trip() {
+    let dir = std::env::temp_dir().join(format!("reqq-history-{}", std::process::id()));
+    let history = History::new(dir.to_str().unwrap());
+    assert!(history.list().unwrap().is_empty());
+
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", HeaderValue::from_static("application/json"));
+    let entry = Entry {
+        id: String::new(),
+        timestamp: 1_792_054_701_999,
+        request: "nested/create-user".to_owned(),
+        env: Some("staging".to_owned()),
+        rendered: "POST https://example.com\n\n{}".to_owned(),
+        duration_ms: 42,
+        response: Response {
+            status: StatusCode::CREATED,
+            version: Version::HTTP_11,
+            headers,
+            body: "{\"id\": 1}".to_owned(),
+            binary: None,
+        },
+        timings: None,
+    };
+
+    let first = history.record(entry.clone()).unwrap();
+    let second = history.record(entry).unwrap();
+    assert_eq!(first, "1792054701999");
+    assert_eq!(second, "1792054702000");
+
+    let entries = history.list().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].id, first);
+
+    let got = history.get(&second).unwrap();
+    assert_eq!(got.request, "nested/create-user");
+    assert_eq!(got.env.as_deref(), Some("staging"));
+    assert_eq!(got.rendered, "POST https://example.com\n\n{}");
+    assert_eq!(got.duration_ms, 42);
+    assert_eq!(got.response.status, StatusCode::CREATED);
+    assert_eq!(got.response.headers["content-type"], "application/json");
+    assert_eq!(got.response.body, "{\"id\": 1}");
+
+    assert_eq!(
+        got.summary(),
+        "1792054702000  2026-10-15T08:58:21Z  201  42 ms  nested/create-user (staging)"
+    );
+
+    assert!(history.get("nope").is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,117 @@
+//! Commands run around a request, as an escape hatch for what reqq doesn't do itself. Pre
+//! hooks run before the request is rendered and print a JSON object of template variables,
+//! and post hooks read the response as JSON on stdin.
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// The folder inside the reqq directory that holds hook scripts rather than requests.
+pub const FOLDER: &str = "scripts";
+
+/// What a hook is run for, passed to it as `REQQ_REQUEST` and `REQQ_ENV`.
+pub struct Context<'a> {
+    pub request: &'a str,
+    pub env: Option<&'a str>,
+}
+
+/// Runs a pre hook in a folder, and returns the variables it printed.
+pub fn pre(command: &str, dir: &Path, context: &Context) -> Result<Map<String, Value>> {
+    let output = shell(command, dir, context)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| anyhow!("Failed to run pre hook '{}': {}", command, e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Pre hook '{}' failed with {}.",
+            command,
+            output.status
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match serde_json::from_str(&stdout) {
+        Ok(Value::Object(vars)) => Ok(vars),
+        _ => Err(anyhow!(
+            "Pre hook '{}' must print a JSON object of variables.",
+            command
+        )),
+    }
+}
+
+/// Runs a post hook in a folder, with the response on its stdin. What it prints goes to
+/// stderr, so it doesn't mix with the response.
+pub fn post(command: &str, dir: &Path, context: &Context, response: &Value) -> Result<()> {
+    let mut child = shell(command, dir, context)
+        .stdin(Stdio::piped())
+        .stdout(io::stderr())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run post hook '{}': {}", command, e))?;
+
+    // Writing on another thread keeps a hook that doesn't read all of stdin from blocking.
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Post hook '{}' has no stdin.", command))?;
+    let input = serde_json::to_vec(response)?;
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let status = child.wait()?;
+    let _ = writer.join();
+
+    if !status.success() {
+        return Err(anyhow!("Post hook '{}' failed with {}.", command, status));
+    }
+    Ok(())
+}
+
+fn shell(command: &str, dir: &Path, context: &Context) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command)
+        .current_dir(dir)
+        .env("REQQ_REQUEST", context.request);
+    match context.env {
+        Some(env) => cmd.env("REQQ_ENV", env),
+        None => cmd.env_remove("REQQ_ENV"),
+    };
+    cmd
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hooks() {
+    let dir = std::env::temp_dir();
+    let context = Context {
+        request: "users/get",
+        env: Some("staging"),
+    };
+    let vars = pre(
+        "printf '{\"token\": \"%s-%s\"}' \"$REQQ_REQUEST\" \"$REQQ_ENV\"",
+        &dir,
+        &context,
+    )
+    .unwrap();
+    assert_eq!(vars["token"], "users/get-staging");
+    assert!(pre("true", &dir, &context).unwrap().is_empty());
+    assert!(pre("echo nope", &dir, &context).is_err());
+    assert!(pre("exit 3", &dir, &context).is_err());
+
+    let response = serde_json::json!({"status": 200});
+    post("grep -q '\"status\":200'", &dir, &context, &response).unwrap();
+    assert!(post("grep -q 404", &dir, &context, &response).is_err());
+}
@@ -0,0 +1,248 @@
+//! HTTP/1.1 messages written and read by hand, for connections reqwest doesn't make.
+
+use crate::compression::{self, Decoded};
+use crate::response::Response;
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, HOST};
+use reqwest::{Method, StatusCode, Version};
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use url::{Host, Url};
+
+/// The largest chunk of a chunked body read, well above what servers send.
+const MAX_CHUNK: u64 = 1 << 30;
+
+/// The host to connect to for a URL, with IPv6 addresses out of the brackets URLs have them in.
+pub fn connect_host(url: &Url) -> Result<String> {
+    match url.host() {
+        Some(Host::Ipv6(addr)) => Ok(addr.to_string()),
+        Some(host) => Ok(host.to_string()),
+        None => Err(anyhow!("The URL '{}' has no host.", url)),
+    }
+}
+
+/// Writes a built request as HTTP/1.1, asking the server to close the connection afterwards.
+/// A body streamed from a file is copied from `body_file` to the connection as it's read.
+pub fn write_request(
+    stream: &mut impl Write,
+    built: &reqwest::blocking::Request,
+    body_file: Option<&Path>,
+) -> Result<()> {
+    let url = built.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_owned(),
+    };
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or("localhost"), port),
+        None => url.host_str().unwrap_or("localhost").to_owned(),
+    };
+    let (body, mut file): (&[u8], _) = match (built.body().map(|body| body.as_bytes()), body_file) {
+        (None, _) => (&[], None),
+        (Some(Some(bytes)), _) => (bytes, None),
+        (Some(None), Some(path)) => (
+            &[],
+            Some(
+                File::open(path)
+                    .with_context(|| format!("Couldn't read body file '{}'", path.display()))?,
+            ),
+        ),
+        (Some(None), None) => {
+            return Err(anyhow!("Streamed request bodies can't be written by hand."))
+        }
+    };
+    let length = match &file {
+        Some(file) => file.metadata()?.len(),
+        None => body.len() as u64,
+    };
+
+    let mut out = format!("{} {} HTTP/1.1\r\n", built.method(), target);
+    if !built.headers().contains_key(HOST) {
+        out.push_str(&format!("host: {}\r\n", host));
+    }
+    for (name, value) in built.headers() {
+        out.push_str(&format!(
+            "{}: {}\r\n",
+            name,
+            String::from_utf8_lossy(value.as_bytes())
+        ));
+    }
+    let has_length = built.headers().contains_key(CONTENT_LENGTH);
+    if !has_length
+        && (length > 0 || matches!(*built.method(), Method::POST | Method::PUT | Method::PATCH))
+    {
+        out.push_str(&format!("content-length: {}\r\n", length));
+    }
+    out.push_str("connection: close\r\n\r\n");
+    stream.write_all(out.as_bytes())?;
+    stream.write_all(body)?;
+    if let Some(file) = &mut file {
+        io::copy(file, stream)?;
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+/// Reads a response's status line, headers and body, which is as long as `Content-Length` says,
+/// chunked, or lasts until the connection is closed. Compressed bodies are decoded, as by
+/// `Response::read`.
+pub fn read_response(
+    reader: &mut impl BufRead,
+    method: &Method,
+) -> Result<(Response, Option<Decoded>)> {
+    let mut status_line = vec![];
+    reader.read_until(b'\n', &mut status_line)?;
+    let status_line = String::from_utf8_lossy(&status_line);
+    let mut parts = status_line.split_whitespace();
+    let version = match parts.next() {
+        Some("HTTP/1.0") => Version::HTTP_10,
+        Some("HTTP/1.1") => Version::HTTP_11,
+        _ => {
+            return Err(anyhow!(
+                "Invalid status line: '{}'.",
+                status_line.trim_end()
+            ))
+        }
+    };
+    let status: StatusCode = parts
+        .next()
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| anyhow!("Invalid status line: '{}'.", status_line.trim_end()))?;
+
+    let mut headers = HeaderMap::new();
+    loop {
+        let mut line = vec![];
+        if reader.read_until(b'\n', &mut line)? == 0 || line.trim_ascii().is_empty() {
+            break;
+        }
+        // Values may have bytes beyond ASCII, as reqwest allows.
+        if let Some(colon) = line.iter().position(|b| *b == b':') {
+            headers.append(
+                HeaderName::from_bytes(line[..colon].trim_ascii())?,
+                HeaderValue::from_bytes(line[colon + 1..].trim_ascii())?,
+            );
+        }
+    }
+
+    let chunked = headers
+        .get("transfer-encoding")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("chunked"));
+    let content_length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse().ok());
+    let mut body = vec![];
+    if *method == Method::HEAD
+        || status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+    {
+        // These responses have no body, whatever their headers say.
+    } else if chunked {
+        body = read_chunked(reader)?;
+    } else if let Some(length) = content_length {
+        reader.take(length).read_to_end(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    let (body, decoded) = compression::decode(&mut headers, body)?;
+    Ok((Response::new(status, version, headers, body), decoded))
+}
+
+/// Reads a chunked message body, up to the last, empty chunk. Chunks are read as they arrive,
+/// rather than into space for the size the server gives, which may be anything.
+pub fn read_chunked(reader: &mut impl BufRead) -> Result<Vec<u8>> {
+    let mut body = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.split(';').next().unwrap_or_default().trim();
+        let size =
+            u64::from_str_radix(line, 16).map_err(|_| anyhow!("Invalid chunk size '{}'.", line))?;
+        if size > MAX_CHUNK {
+            return Err(anyhow!("Chunk size '{}' is too large.", line));
+        }
+        let read = reader.take(size).read_to_end(&mut body)?;
+        if read as u64 != size {
+            return Err(anyhow!("The response ended in the middle of a chunk."));
+        }
+        let mut crlf = [0; 2];
+        reader.read_exact(&mut crlf)?;
+        if size == 0 {
+            return Ok(body);
+        }
+    }
+}
+
+#[test]
+fn test_connect_host() {
+    let host = |url: &str| connect_host(&Url::parse(url).unwrap()).unwrap();
+    assert_eq!(host("https://[::1]:8443/"), "::1");
+    assert_eq!(host("http://127.0.0.1/"), "127.0.0.1");
+    assert_eq!(host("https://API.example.com/"), "api.example.com");
+    assert!(connect_host(&Url::parse("unix:/run/app.sock").unwrap()).is_err());
+}
+
+#[test]
+fn test_read_response() {
+    let raw = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n3\r\n[1,\r\n2\r\n2]\r\n0\r\n\r\n";
+    let (resp, _) = read_response(&mut raw.as_bytes(), &Method::GET).unwrap();
+    assert_eq!(resp.status, StatusCode::OK);
+    assert_eq!(resp.headers["content-type"], "application/json");
+    assert_eq!(resp.body, "[1,2]");
+
+    let raw = "HTTP/1.0 404 Not Found\r\nContent-Length: 9\r\n\r\nnot found, and then some";
+    let (resp, _) = read_response(&mut raw.as_bytes(), &Method::GET).unwrap();
+    assert_eq!(
+        (resp.status, resp.version, resp.body.as_str()),
+        (StatusCode::NOT_FOUND, Version::HTTP_10, "not found")
+    );
+
+    let raw = "HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\n";
+    assert_eq!(
+        read_response(&mut raw.as_bytes(), &Method::HEAD)
+            .unwrap()
+            .0
+            .body,
+        ""
+    );
+
+    let raw = b"HTTP/1.1 200 OK\r\nX-Name: Andr\xe9\r\nContent-Length: 2\r\n\r\nok";
+    let (resp, _) = read_response(&mut raw.as_slice(), &Method::GET).unwrap();
+    assert_eq!(resp.headers["x-name"].as_bytes(), b"Andr\xe9");
+    assert_eq!(resp.body, "ok");
+}
+
+#[test]
+fn test_read_chunked() {
+    assert_eq!(
+        read_chunked(&mut "3;ext=1\r\nabc\r\n0\r\n\r\n".as_bytes()).unwrap(),
+        b"abc"
+    );
+    // Sizes a misbehaving server might send fail rather than overflow or allocate them.
+    for size in ["ffffffffffffffff", "7fffffffffffffff", "40000001", "zz"] {
+        assert!(read_chunked(&mut format!("{}\r\nabc\r\n0\r\n\r\n", size).as_bytes()).is_err());
+    }
+    assert!(read_chunked(&mut "10\r\nabc".as_bytes()).is_err());
+}
+
+#[test]
+fn test_write_request_body_file() {
+    let path = std::env::temp_dir().join(format!("reqq-http1-{}.bin", std::process::id()));
+    std::fs::write(&path, b"firmware").unwrap();
+    let built = reqwest::blocking::Client::new()
+        .put("http://localhost:8080/upload")
+        .body(File::open(&path).unwrap())
+        .build()
+        .unwrap();
+
+    let mut out = vec![];
+    write_request(&mut out, &built, Some(&path)).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("PUT /upload HTTP/1.1\r\nhost: localhost:8080\r\n"));
+    assert!(out.ends_with("content-length: 8\r\nconnection: close\r\n\r\nfirmware"));
+    assert!(write_request(&mut vec![], &built, None).is_err());
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,569 @@
+//! `.http` and `.rest` files, in the format of VS Code's REST Client, which hold several
+//! requests. Each request starts with a `###` line and is addressed as `<file>#<name>`, with
+//! the file's name being its path without the extension. Requests are named after their `###`,
+//! or else their `@name`.
+//!
+//! Files can define variables for all their requests with `@name = value` lines, and use
+//! `//` comments, a version after the URL, URLs without a method for GET requests, and
+//! REST Client's system variables like `{{$guid}}`. Variables aren't HTML-escaped, as in REST
+//! Client. reqq's own directives and templates work too.
+
+use crate::{extends, frontmatter, request_file::RequestFile, template::Partials};
+use anyhow::Result;
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// The extensions of files that can hold several requests.
+pub const EXTENSIONS: [&str; 2] = [".http", ".rest"];
+
+/// The contents of an `.http` or `.rest` file.
+pub struct HttpFile {
+    fstr: String,
+}
+
+/// A request in an `.http` file, with its name and text in reqq's format.
+#[derive(Debug, PartialEq)]
+struct Section {
+    name: String,
+    text: String,
+}
+
+/// A part of an `.http` file before or after a `###` line, converted to reqq's format.
+struct Chunk {
+    /// What follows the `###`, or `None` before the first one.
+    marker: Option<String>,
+    text: String,
+    variables: Vec<(String, String)>,
+    /// Whether there's more than comments and directives, like a request line.
+    has_request: bool,
+}
+
+impl HttpFile {
+    pub fn new(fstr: String) -> Self {
+        HttpFile { fstr }
+    }
+
+    /// The file split at its `###` lines.
+    fn chunks(&self) -> Vec<Chunk> {
+        let mut parts: Vec<(Option<&str>, String)> = vec![(None, String::new())];
+        for line in self.fstr.split_inclusive('\n') {
+            match marker(line) {
+                Some(name) => parts.push((Some(name), String::new())),
+                None => parts.last_mut().unwrap().1.push_str(line),
+            }
+        }
+        parts
+            .into_iter()
+            .map(|(marker, text)| {
+                let (text, variables, has_request) = convert(&text);
+                Chunk {
+                    marker: marker.map(str::to_owned),
+                    text,
+                    variables,
+                    has_request,
+                }
+            })
+            .collect()
+    }
+
+    /// The requests of the file, or none if it has no `###` lines. A request without a name is
+    /// named by its position, starting at 1. Parts with only comments, directives and
+    /// variables aren't requests.
+    fn sections(&self) -> Vec<Section> {
+        let chunks = self.chunks();
+        if chunks.len() == 1 {
+            return vec![];
+        }
+        chunks
+            .into_iter()
+            .filter(|chunk| chunk.has_request)
+            .enumerate()
+            .map(|(i, chunk)| Section {
+                name: chunk
+                    .marker
+                    .filter(|name| !name.is_empty())
+                    .or_else(|| name_directive(&chunk.text))
+                    .unwrap_or_else(|| (i + 1).to_string()),
+                text: format!("{}\n", chunk.text.trim_end()),
+            })
+            .collect()
+    }
+}
+
+impl RequestFile for HttpFile {
+    fn sections(&self) -> Vec<String> {
+        HttpFile::sections(self)
+            .into_iter()
+            .map(|section| section.name)
+            .collect()
+    }
+
+    fn request(&self, section: Option<&str>) -> Option<String> {
+        match section {
+            Some(name) => HttpFile::sections(self)
+                .into_iter()
+                .find(|s| s.name == name)
+                .map(|s| s.text),
+            None => Some(self.chunks().into_iter().map(|chunk| chunk.text).collect()),
+        }
+    }
+
+    fn variables(&self) -> Vec<(String, String)> {
+        self.chunks()
+            .into_iter()
+            .flat_map(|chunk| chunk.variables)
+            .collect()
+    }
+}
+
+/// The name on a `###` line, which may be empty, or a comment.
+pub fn marker(line: &str) -> Option<&str> {
+    let rest = line.trim_end().strip_prefix("###")?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim())
+}
+
+/// Converts the lines before and after a `###` line to reqq's format, returning the variables
+/// they define and whether there's a request. Lines before the request line that reqq's front
+/// matter can't have become comments, so line numbers stay the same. A request extending
+/// another may start with its query params or headers instead.
+fn convert(text: &str) -> (String, Vec<(String, String)>, bool) {
+    let mut converted = String::new();
+    let mut variables = vec![];
+    let header = Regex::new(r"^[A-Za-z0-9-]+:\s*\S").unwrap();
+    let mut lines = text.split_inclusive('\n');
+    for line in lines.by_ref() {
+        let content = line.trim();
+        if let Some((name, value)) = variable(content) {
+            variables.push((name.to_owned(), templates(value)));
+            converted.push_str("#\n");
+        } else if content.is_empty() {
+            converted.push_str("#\n");
+        } else if let Some(comment) = content.strip_prefix("//") {
+            converted.push_str(&format!("#{}\n", comment));
+        } else if content.starts_with(['#', '@']) {
+            converted.push_str(line);
+        } else {
+            let is_header = content.starts_with(['?', '&']) || header.is_match(content);
+            converted.push_str(&if is_header {
+                templates(line)
+            } else {
+                request_line(content)
+            });
+            converted.extend(lines.map(templates));
+            return (converted, variables, true);
+        }
+    }
+    (converted, variables, false)
+}
+
+/// A `@name = value` variable definition.
+fn variable(line: &str) -> Option<(&str, &str)> {
+    let (name, value) = line.strip_prefix('@')?.split_once('=')?;
+    let name = name.trim();
+    let is_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c));
+    is_name.then(|| (name, value.trim()))
+}
+
+/// The method and URL line, without a version after the URL, and with GET for URLs without a
+/// method.
+fn request_line(line: &str) -> String {
+    let mut parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() > 1 && parts.last().is_some_and(|part| part.starts_with("HTTP/")) {
+        parts.pop();
+    }
+    let has_method = parts.len() > 1 && parts[0].chars().all(|c| c.is_ascii_uppercase());
+    let line = if has_method {
+        parts.join(" ")
+    } else {
+        format!("GET {}", parts.join(" "))
+    };
+    format!("{}\n", templates(&line))
+}
+
+/// The value of a `@name` directive, which REST Client uses to name requests.
+fn name_directive(text: &str) -> Option<String> {
+    text.lines()
+        .take_while(|line| line.starts_with(['#', '@']))
+        .map(|line| line.trim_start_matches('#').trim())
+        .find_map(|line| line.strip_prefix("@name"))
+        .map(|name| name.trim_start_matches(':').trim().to_owned())
+        .filter(|name| !name.is_empty())
+}
+
+/// Rewrites the variables of a line as triple-brace templates, which aren't HTML-escaped, and
+/// REST Client's system variables as reqq's helpers. Block helpers, partials and templates
+/// that already have triple braces are left alone.
+fn templates(line: &str) -> String {
+    let expression = Regex::new(r"(\{?)\{\{\s*([^{}#/>!~\s][^{}]*?)\s*\}\}(\}?)").unwrap();
+    expression
+        .replace_all(line, |caps: &Captures| {
+            // A brace on only one side belongs to what's around the template, like a JSON object.
+            if (!caps[1].is_empty() && !caps[3].is_empty()) || &caps[2] == "else" {
+                return caps[0].to_owned();
+            }
+            format!(
+                "{}{{{{{{ {} }}}}}}{}",
+                &caps[1],
+                system_variable(&caps[2]),
+                &caps[3]
+            )
+        })
+        .into_owned()
+}
+
+/// The helper for one of REST Client's system variables, like `$guid`. Others are left as
+/// they are.
+fn system_variable(expression: &str) -> String {
+    let parts: Vec<&str> = expression.split_whitespace().collect();
+    match parts.as_slice() {
+        ["$guid"] => "uuid".to_owned(),
+        ["$timestamp"] => "epoch".to_owned(),
+        ["$datetime", "iso8601"] => "now".to_owned(),
+        ["$randomInt", min, max] => format!("randomInt {} {}", min, max),
+        ["$processEnv", name] if !name.starts_with('%') => format!("env.{}", name),
+        _ => expression.to_owned(),
+    }
+}
+
+/// Writes a request file as a request of an `.http` file, after a `### <name>` line, for
+/// editors' REST clients. Partials are included, `@auth`, `@body-file`, `@form` and
+/// `@graphql` become what the editors use instead, and reqq's other directives are kept as
+/// comments. Relative URLs are joined to a `_base_url` variable. What the editors can't do is
+/// left as it is, with a warning.
+pub fn export(name: &str, fstr: &str, partials: &Partials) -> Result<(String, Vec<String>)> {
+    let (directives, rest) = frontmatter::directives(fstr)?;
+    let mut warnings = vec![];
+    let mut comments = vec![];
+    let mut headers = vec![];
+    let (mut body_file, mut form) = (None, false);
+    for (key, value) in directives {
+        let value = export_templates(&value, name, &mut warnings);
+        match (
+            key.as_str(),
+            value.split_whitespace().collect::<Vec<_>>().as_slice(),
+        ) {
+            ("auth", ["basic", user, pass]) => {
+                headers.push(format!("Authorization: Basic {} {}", user, pass))
+            }
+            ("auth", ["bearer", token]) => headers.push(format!("Authorization: Bearer {}", token)),
+            ("auth", ["apikey", header, key] | ["apikey", "header", header, key]) => {
+                headers.push(format!("{}: {}", header, key))
+            }
+            ("auth", ["none"]) => {}
+            ("auth", _) => warnings.push(format!("{}: @auth {} can't be exported.", name, value)),
+            ("body-file", _) => body_file = Some(value),
+            ("form", _) => form = true,
+            ("graphql", _) => headers.push("X-REQUEST-TYPE: GraphQL".to_owned()),
+            ("multipart", _) => {
+                warnings.push(format!("{}: @multipart bodies can't be exported.", name))
+            }
+            ("name" | "description", _) => comments.push(format!("# {}", value)),
+            (key, _) => {
+                if ["pre", "post", "pre-script", "post-script"].contains(&key) {
+                    warnings.push(format!("{}: @{} doesn't run in editors.", name, key));
+                }
+                comments.push(format!("# @{} {}", key, value).trim_end().to_owned());
+            }
+        }
+    }
+
+    let rest = include_partials(rest, partials);
+    let parts = extends::parts(&rest);
+    let request_line = parts.request_line.unwrap_or_default();
+    let request_line = match request_line.split_once(' ') {
+        Some((method, url)) if url.starts_with('/') => {
+            format!("{} {{{{_base_url}}}}{}", method, url)
+        }
+        _ => request_line.to_owned(),
+    };
+
+    let mut out = format!(
+        "### {}
+",
+        name
+    );
+    for line in comments {
+        out.push_str(&format!(
+            "{}
+",
+            line
+        ));
+    }
+    out.push_str(&format!(
+        "{}
+",
+        export_templates(&request_line, name, &mut warnings)
+    ));
+    for param in parts.params {
+        out.push_str(&format!(
+            "    {}
+",
+            export_templates(param.trim(), name, &mut warnings)
+        ));
+    }
+    let has_content_type = parts
+        .headers
+        .iter()
+        .any(|header| header.to_ascii_lowercase().starts_with("content-type:"));
+    if form && !has_content_type {
+        headers.push("Content-Type: application/x-www-form-urlencoded".to_owned());
+    }
+    for header in parts.headers.iter().map(|h| h.to_string()).chain(headers) {
+        out.push_str(&format!(
+            "{}\n",
+            export_templates(&header, name, &mut warnings)
+        ));
+    }
+
+    let body = match (body_file, form) {
+        (Some(path), _) => format!("< {}\n", path),
+        (None, true) => {
+            let fields: Vec<&str> = parts
+                .body
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect();
+            format!(
+                "{}\n",
+                export_templates(&fields.join("&"), name, &mut warnings)
+            )
+        }
+        (None, false) => export_templates(parts.body, name, &mut warnings),
+    };
+    if !body.trim().is_empty() {
+        out.push('\n');
+        out.push_str(&body);
+        if !body.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    warnings.dedup();
+    Ok((out, warnings))
+}
+
+/// The variables of an env, for `http-client.env.json` or REST Client's settings, which only
+/// take strings. Secrets are left out, and so are values that aren't strings, numbers or
+/// booleans, with a warning.
+pub fn export_env(
+    name: &str,
+    vars: HashMap<String, Value>,
+    secrets: &[String],
+    warnings: &mut Vec<String>,
+) -> Map<String, Value> {
+    let mut exported = Map::new();
+    let mut names: Vec<String> = vars.keys().cloned().collect();
+    names.sort();
+    for key in names {
+        let value = match &vars[&key] {
+            _ if secrets.contains(&key) => {
+                warnings.push(format!("env {}: the secret '{}' is left out.", name, key));
+                continue;
+            }
+            Value::String(s) => export_templates(s, name, warnings),
+            value @ (Value::Number(_) | Value::Bool(_)) => value.to_string(),
+            _ => {
+                warnings.push(format!(
+                    "env {}: '{}' isn't a string, so it's left out.",
+                    name, key
+                ));
+                continue;
+            }
+        };
+        exported.insert(key, Value::String(value));
+    }
+    exported
+}
+
+/// Replaces the lines that include a partial with its contents.
+fn include_partials(text: &str, partials: &Partials) -> String {
+    let include = Regex::new(r"^\s*\{\{>\s*([^\s}]+)\s*\}\}\s*$").unwrap();
+    let mut out = String::new();
+    for line in text.split_inclusive('\n') {
+        let partial = include
+            .captures(line.trim_end())
+            .map(|caps| caps[1].to_owned());
+        match partial.and_then(|partial| partials.get(&partial)) {
+            Some(included) => out.push_str(&include_partials(included, partials)),
+            None => out.push_str(line),
+        }
+    }
+    out
+}
+
+/// Rewrites reqq's templates for the editors: variables as `{{name}}`, and helpers as the
+/// system variables like `{{$guid}}` they have instead. Others are left as they are.
+fn export_templates(text: &str, name: &str, warnings: &mut Vec<String>) -> String {
+    let expression = Regex::new(r"\{\{\{?\s*([^{}]*?)\s*\}?\}\}").unwrap();
+    let path = Regex::new(r"^[A-Za-z_][\w.-]*$").unwrap();
+    expression
+        .replace_all(text, |caps: &Captures| {
+            let parts: Vec<&str> = caps[1].split_whitespace().collect();
+            let exported = match parts.as_slice() {
+                ["uuid"] => "$guid".to_owned(),
+                ["epoch"] | ["now", "\"epoch\""] => "$timestamp".to_owned(),
+                ["now"] | ["now", "\"iso8601\""] => "$datetime iso8601".to_owned(),
+                ["randomInt", min, max] => format!("$randomInt {} {}", min, max),
+                [var] if var.starts_with("env.") => format!("$processEnv {}", &var[4..]),
+                [var] if path.is_match(var) => var.to_string(),
+                _ => {
+                    warnings.push(format!("{}: '{}' can't be exported.", name, &caps[0]));
+                    return caps[0].to_owned();
+                }
+            };
+            format!("{{{{{}}}}}", exported)
+        })
+        .into_owned()
+}
+
+#[test]
+fn test_http_file_sections() {
+    let file = HttpFile::new(
+        "# Users API
+@baseUrl = https://{{host}}/api
+### list
+GET {{baseUrl}}/users HTTP/1.1
+
+###
+// Creates a user.
+# @name create
+@status 201
+POST {{baseUrl}}/users
+Content-Type: application/json
+
+{\"id\": \"{{$guid}}\", \"age\": {{$randomInt 1 99}}}
+
+#### not a marker
+### delete
+{{baseUrl}}/users/1
+###
+"
+        .to_owned(),
+    );
+    assert_eq!(
+        RequestFile::sections(&file),
+        vec!["list", "create", "delete"]
+    );
+    assert_eq!(
+        file.variables(),
+        vec![("baseUrl".to_owned(), "https://{{{ host }}}/api".to_owned())]
+    );
+    assert_eq!(
+        file.request(Some("list")).as_deref(),
+        Some("GET {{{ baseUrl }}}/users\n")
+    );
+    assert_eq!(
+        file.request(Some("create")).as_deref(),
+        Some("# Creates a user.\n# @name create\n@status 201\nPOST {{{ baseUrl }}}/users\nContent-Type: application/json\n\n\
+              {\"id\": \"{{{ uuid }}}\", \"age\": {{{ randomInt 1 99 }}}}\n\n#### not a marker\n")
+    );
+    assert_eq!(
+        file.request(Some("delete")).as_deref(),
+        Some("GET {{{ baseUrl }}}/users/1\n")
+    );
+    assert_eq!(file.request(Some("nope")), None);
+
+    let file = HttpFile::new(
+        "GET https://example.com/health\n\n### ready\nGET https://example.com/ready\n".to_owned(),
+    );
+    assert_eq!(RequestFile::sections(&file), vec!["1", "ready"]);
+    let file = HttpFile::new(
+        "### base\nGET https://example.com/users\n### page\n@extends api#base\n?page=2\n"
+            .to_owned(),
+    );
+    assert_eq!(
+        file.request(Some("page")).as_deref(),
+        Some("@extends api#base\n?page=2\n")
+    );
+    let file = HttpFile::new("@host = example.com\n\nGET https://{{host}}/health\n".to_owned());
+    assert!(RequestFile::sections(&file).is_empty());
+    assert_eq!(
+        file.request(None).as_deref(),
+        Some("#\n#\nGET https://{{{ host }}}/health\n")
+    );
+}
+
+#[test]
+fn test_http_file_templates() {
+    assert_eq!(
+        templates("{{ name }} {{{ raw }}} {{#if a}}x{{else}}y{{/if}} {{> common/headers }}"),
+        "{{{ name }}} {{{ raw }}} {{#if a}}x{{else}}y{{/if}} {{> common/headers }}"
+    );
+    assert_eq!(
+        templates("{{$timestamp}} {{$processEnv HOME}} {{$dotenv KEY}}"),
+        "{{{ epoch }}} {{{ env.HOME }}} {{{ $dotenv KEY }}}"
+    );
+}
+
+#[test]
+fn test_http_file_export() {
+    let fstr = "# Creates a user.
+@auth bearer {{ token }}
+@status 201
+@pre ./scripts/seed.sh
+POST /users
+?dry={{ dry }}
+Content-Type: application/json
+{{> common/trace }}
+
+{\"id\": \"{{ uuid }}\", \"at\": {{ epoch }}, \"tag\": \"{{ default tag \"x\" }}\"}
+";
+    let partials = Partials::from([(
+        "common/trace".to_owned(),
+        "x-trace: {{{ trace }}}\n".to_owned(),
+    )]);
+    let (exported, warnings) = export("users/create", fstr, &partials).unwrap();
+    assert_eq!(
+        exported,
+        "### users/create
+# @status 201
+# @pre ./scripts/seed.sh
+POST {{_base_url}}/users
+    ?dry={{dry}}
+Content-Type: application/json
+x-trace: {{trace}}
+Authorization: Bearer {{token}}
+
+{\"id\": \"{{$guid}}\", \"at\": {{$timestamp}}, \"tag\": \"{{ default tag \"x\" }}\"}
+"
+    );
+    assert_eq!(
+        warnings,
+        vec![
+            "users/create: @pre doesn't run in editors.",
+            "users/create: '{{ default tag \"x\" }}' can't be exported."
+        ]
+    );
+
+    let (exported, _) = export(
+        "login",
+        "@form\nPOST https://example.com/login\n\nuser={{ user }}\npass={{ pass }}\n",
+        &Partials::new(),
+    )
+    .unwrap();
+    assert_eq!(
+        exported,
+        "### login\nPOST https://example.com/login\nContent-Type: application/x-www-form-urlencoded\n\nuser={{user}}&pass={{pass}}\n"
+    );
+
+    let mut warnings = vec![];
+    let vars = HashMap::from([
+        ("host".to_owned(), Value::from("localhost")),
+        ("port".to_owned(), Value::from(8080)),
+        ("password".to_owned(), Value::from("hunter2")),
+        (
+            "_headers".to_owned(),
+            serde_json::json!({ "x-team": "core" }),
+        ),
+    ]);
+    let env = export_env("dev", vars, &["password".to_owned()], &mut warnings);
+    assert_eq!(
+        Value::Object(env),
+        serde_json::json!({ "host": "localhost", "port": "8080" })
+    );
+    assert_eq!(warnings.len(), 2);
+}
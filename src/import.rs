@@ -0,0 +1,74 @@
+//! Request files and environments converted from other tools' formats.
+
+use serde_json::{Map, Value};
+
+/// Request files and environments to write into a reqq directory.
+#[derive(Debug, Default, PartialEq)]
+pub struct Import {
+    /// Request names, like `users/get-user`, with the contents of their files.
+    pub requests: Vec<(String, String)>,
+    /// Environment names with their variables.
+    pub envs: Vec<(String, Map<String, Value>)>,
+    /// Parts of the input that couldn't be converted.
+    pub warnings: Vec<String>,
+}
+
+impl Import {
+    /// Adds a request, with a number appended to its name if another request has it.
+    pub fn add_request(&mut self, name: &str, fstr: String) {
+        let mut unique = name.to_owned();
+        for n in 2.. {
+            if !self.requests.iter().any(|(taken, _)| *taken == unique) {
+                break;
+            }
+            unique = format!("{}-{}", name, n);
+        }
+        self.requests.push((unique, fstr));
+    }
+}
+
+/// Makes a file name out of a name from another tool, like `get-user` from `Get User` or
+/// `getUser`.
+pub fn slug(name: &str, fallback: &str) -> String {
+    let mut slug = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() && prev_lower {
+            slug.push('-');
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        slug.extend(c.to_lowercase().map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        }));
+    }
+    let parts: Vec<&str> = slug.split('-').filter(|part| !part.is_empty()).collect();
+    match parts.join("-") {
+        slug if slug.is_empty() => fallback.to_owned(),
+        slug => slug,
+    }
+}
+
+#[test]
+fn test_slug() {
+    assert_eq!(slug("Get User (by id)", "request"), "get-user-by-id");
+    assert_eq!(slug("listPetsByOwner2", "request"), "list-pets-by-owner2");
+    assert_eq!(slug("  ", "request"), "request");
+}
+
+#[test]
+fn test_add_request() {
+    let mut import = Import::default();
+    import.add_request("login", "a".to_owned());
+    import.add_request("login", "b".to_owned());
+    import.add_request("login", "c".to_owned());
+    let names: Vec<&str> = import
+        .requests
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(names, vec!["login", "login-2", "login-3"]);
+}
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// One step of a JSONPath expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    /// `.name` or `['name']`
+    Key(String),
+    /// `[0]`, or `[-1]` counting from the end.
+    Index(i64),
+    /// `.*` or `[*]`, every child.
+    Wildcard,
+    /// `..name`, `..*` or `..[0]`, the segment applied to every descendant.
+    Descendant(Box<Segment>),
+}
+
+/// Evaluates a JSONPath expression like `$.data.items[0].id` against a value and returns
+/// every match. Supports child names, bracketed names, indexes, wildcards and recursive
+/// descent, but not filters or slices. The `$` can be left out in favor of jq's style, as in
+/// `.data.items[0].id`, with `.` for the whole value.
+pub fn query<'a>(path: &str, value: &'a Value) -> Result<Vec<&'a Value>> {
+    let mut matches = vec![value];
+    for segment in parse(path)? {
+        matches = matches
+            .into_iter()
+            .flat_map(|v| apply(&segment, v))
+            .collect();
+    }
+    Ok(matches)
+}
+
+/// Evaluates a JSONPath expression expecting a result: a single match is returned as is,
+/// several matches as an array.
+pub fn extract(path: &str, value: &Value) -> Result<Value> {
+    let mut matches = query(path, value)?;
+    match matches.len() {
+        0 => Err(anyhow!("'{}' didn't match anything.", path)),
+        1 => Ok(matches.remove(0).clone()),
+        _ => Ok(Value::Array(matches.into_iter().cloned().collect())),
+    }
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>> {
+    let invalid = || anyhow!("Invalid JSONPath '{}'.", path);
+    let path = path.trim();
+    let mut rest = match path.strip_prefix('$') {
+        Some(rest) => rest,
+        None if path == "." => "",
+        None if path.starts_with(".[") => &path[1..],
+        None if path.starts_with('.') => path,
+        None => return Err(invalid()),
+    };
+    let mut segments = vec![];
+
+    while !rest.is_empty() {
+        let (descendant, after) = match rest.strip_prefix("..") {
+            Some(after) => (true, after),
+            None => (false, rest),
+        };
+
+        let (segment, after) = if let Some(bracketed) = after.strip_prefix('[') {
+            let end = bracketed.find(']').ok_or_else(invalid)?;
+            (
+                parse_bracket(bracketed[..end].trim()).ok_or_else(invalid)?,
+                &bracketed[end + 1..],
+            )
+        } else {
+            let name = if descendant {
+                after
+            } else {
+                after.strip_prefix('.').ok_or_else(invalid)?
+            };
+            let end = name.find(['.', '[']).unwrap_or(name.len());
+            let segment = match &name[..end] {
+                "" => return Err(invalid()),
+                "*" => Segment::Wildcard,
+                key => Segment::Key(key.to_owned()),
+            };
+            (segment, &name[end..])
+        };
+
+        segments.push(if descendant {
+            Segment::Descendant(Box::new(segment))
+        } else {
+            segment
+        });
+        rest = after;
+    }
+
+    Ok(segments)
+}
+
+/// Parses what's between `[` and `]`: a quoted name, an index or `*`.
+fn parse_bracket(inner: &str) -> Option<Segment> {
+    if inner == "*" {
+        return Some(Segment::Wildcard);
+    }
+    for quote in ['\'', '"'] {
+        if let Some(name) = inner
+            .strip_prefix(quote)
+            .and_then(|s| s.strip_suffix(quote))
+        {
+            return Some(Segment::Key(name.to_owned()));
+        }
+    }
+    inner.parse().ok().map(Segment::Index)
+}
+
+fn apply<'a>(segment: &Segment, value: &'a Value) -> Vec<&'a Value> {
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(map)) => map.get(key).into_iter().collect(),
+        (Segment::Index(i), Value::Array(items)) => {
+            let i = if *i < 0 { items.len() as i64 + i } else { *i };
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| items.get(i))
+                .into_iter()
+                .collect()
+        }
+        (Segment::Wildcard, Value::Object(map)) => map.values().collect(),
+        (Segment::Wildcard, Value::Array(items)) => items.iter().collect(),
+        (Segment::Descendant(inner), _) => {
+            let mut matches = apply(inner, value);
+            for child in apply(&Segment::Wildcard, value) {
+                matches.extend(apply(segment, child));
+            }
+            matches
+        }
+        _ => vec![],
+    }
+}
+
+#[test]
+fn test_query() {
+    let value = serde_json::json!({
+        "data": {
+            "token": "abc",
+            "items": [{ "id": 1, "name": "a" }, { "id": 2, "name": "b" }],
+            "weird key": true,
+        }
+    });
+
+    assert_eq!(query("$", &value).unwrap(), vec![&value]);
+    assert_eq!(query("$.data.token", &value).unwrap(), vec!["abc"]);
+    assert_eq!(query("$.data.items[1].id", &value).unwrap(), vec![2]);
+    assert_eq!(query("$.data.items[-1].name", &value).unwrap(), vec!["b"]);
+    assert_eq!(
+        query("$['data'][\"weird key\"]", &value).unwrap(),
+        vec![true]
+    );
+    assert_eq!(query("$.data.items[*].id", &value).unwrap(), vec![1, 2]);
+    assert_eq!(query("$..name", &value).unwrap(), vec!["a", "b"]);
+    assert!(query("$.data.nope", &value).unwrap().is_empty());
+    assert!(query("$.data.items[5]", &value).unwrap().is_empty());
+
+    assert_eq!(query(".data.items[0].id", &value).unwrap(), vec![1]);
+    assert_eq!(query(".", &value).unwrap(), vec![&value]);
+    assert_eq!(query(".['data'].token", &value).unwrap(), vec!["abc"]);
+
+    assert!(query("data.token", &value).is_err());
+    assert!(query("$.data[", &value).is_err());
+    assert!(query("$.data.", &value).is_err());
+}
+
+#[test]
+fn test_extract() {
+    let value = serde_json::json!({ "items": [{ "id": 1 }, { "id": 2 }] });
+    assert_eq!(
+        extract("$.items[0].id", &value).unwrap(),
+        serde_json::json!(1)
+    );
+    assert_eq!(
+        extract("$.items[*].id", &value).unwrap(),
+        serde_json::json!([1, 2])
+    );
+    assert_eq!(
+        extract("$.nope", &value).unwrap_err().to_string(),
+        "'$.nope' didn't match anything."
+    );
+}
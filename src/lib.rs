@@ -1,7 +1,94 @@
+pub mod api;
+mod assertion;
+mod auth;
+mod bench;
+mod body;
+mod cache;
+mod client;
+mod codegen;
+mod compression;
+mod config;
+mod cookies;
+mod curl;
+mod diff;
+mod download;
 mod env;
+mod error;
+mod executor;
+mod extends;
+mod flow;
 mod format;
+mod frontmatter;
+mod glob;
+mod grpc;
+mod har;
+mod helpers;
+mod history;
+mod hooks;
+mod http1;
+mod http_file;
+mod import;
+mod jsonpath;
+mod listing;
+mod mock;
+mod openapi;
+mod paginate;
+mod postman;
+#[cfg(feature = "image-preview")]
+mod preview;
+mod prompt;
+mod proto;
+mod record;
+mod redact;
+mod report;
 mod reqq;
 mod request;
+mod request_file;
+mod response;
+mod schema;
+mod scripting;
+mod secrets;
+mod session;
+mod sigv4;
+mod snapshot;
+mod stream;
+mod suite;
+mod template;
+mod time;
+mod timings;
+mod tokens;
+mod unix_socket;
+mod validate;
+mod vault;
+mod watch;
+mod websocket;
+mod yaml;
 
+pub use crate::api::ReqqClient;
+pub use crate::bench::{BenchResult, Sample};
+pub use crate::client::{ClientOpts, HttpVersion, Redirects, Resolve};
+pub use crate::codegen::Lang;
+pub use crate::cookies::Cookie;
+pub use crate::diff::{Difference, ResponseDiff};
+pub use crate::download::Output;
+pub use crate::error::ReqqError;
+pub use crate::executor::{is_network_error, RetryOn, RetryPolicy};
+pub use crate::flow::FlowResult;
+pub use crate::history::Entry as HistoryEntry;
+pub use crate::listing::{ListedRequest, Listing};
+pub use crate::mock::{MockServer, Route};
+pub use crate::paginate::Pagination;
+pub use crate::record::Recorder;
+pub use crate::redact::redact as redact_secrets;
+pub use crate::report::{OutputFormat, ReportFormat, Reporter, TableReporter, TextReporter};
+pub use crate::reqq::ExecutionResult;
 pub use crate::reqq::Reqq;
 pub use crate::reqq::ReqqOpts;
+pub use crate::request::TrailingNewline;
+pub use crate::response::ResponseParts;
+pub use crate::snapshot::{SnapshotMode, SnapshotOutcome};
+pub use crate::suite::SuiteOpts;
+pub use crate::time::parse_duration;
+pub use crate::timings::Timings;
+pub use crate::validate::{Problem, Validation};
+pub use crate::watch::Snapshot;
@@ -0,0 +1,155 @@
+//! The tree of requests printed by `reqq list --long`.
+
+use crate::{frontmatter, grpc};
+
+/// A request, as summarized in a listing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListedRequest {
+    pub name: String,
+    pub method: String,
+    /// The host of the URL, or whatever precedes its path when it's templated, like
+    /// `{{ baseUrl }}`.
+    pub host: String,
+    pub description: Option<String>,
+}
+
+impl ListedRequest {
+    /// Summarizes a request file that hasn't been templated.
+    pub fn new(name: String, fstr: &str) -> Self {
+        let line = frontmatter::request_line(fstr).trim();
+        let (method, url) = line.split_once(' ').unwrap_or((line, ""));
+        // gRPC requests give the server, then the method.
+        let url = match method {
+            grpc::METHOD => url.split_whitespace().next().unwrap_or_default(),
+            _ => url.trim(),
+        };
+        ListedRequest {
+            name,
+            method: method.to_owned(),
+            host: host(url).to_owned(),
+            description: frontmatter::description(fstr),
+        }
+    }
+}
+
+/// Requests in a tree of their folders.
+#[derive(Clone, Debug)]
+pub struct Listing {
+    pub requests: Vec<ListedRequest>,
+}
+
+impl Listing {
+    /// A line per folder and request, sorted by name and indented by depth, with the method,
+    /// host and description of each request in aligned columns.
+    pub fn report(&self) -> String {
+        let mut requests: Vec<&ListedRequest> = self.requests.iter().collect();
+        requests.sort_by(|a, b| a.name.split('/').cmp(b.name.split('/')));
+
+        // A folder header or a request, with its indentation.
+        let mut rows: Vec<(String, Option<&ListedRequest>)> = vec![];
+        let mut folders: Vec<&str> = vec![];
+        for req in requests.iter() {
+            let mut parts: Vec<&str> = req.name.split('/').collect();
+            let leaf = parts.pop().unwrap_or_default();
+            let common = folders
+                .iter()
+                .zip(parts.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            for (depth, folder) in parts.iter().enumerate().skip(common) {
+                rows.push((format!("{}{}/", "  ".repeat(depth), folder), None));
+            }
+            rows.push((format!("{}{}", "  ".repeat(parts.len()), leaf), Some(req)));
+            folders = parts;
+        }
+
+        let name_width = rows
+            .iter()
+            .filter(|(_, req)| req.is_some())
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        let method_width = requests
+            .iter()
+            .map(|req| req.method.len())
+            .max()
+            .unwrap_or(0);
+        let host_width = requests.iter().map(|req| req.host.len()).max().unwrap_or(0);
+        let lines: Vec<String> = rows
+            .into_iter()
+            .map(|(name, req)| match req {
+                Some(req) => format!(
+                    "{:name_width$}  {:method_width$}  {:host_width$}  {}",
+                    name,
+                    req.method,
+                    req.host,
+                    req.description.as_deref().unwrap_or_default()
+                )
+                .trim_end()
+                .to_owned(),
+                None => name,
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+/// What precedes the path of a URL, without its scheme.
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    &rest[..end]
+}
+
+#[test]
+fn test_listed_request() {
+    let req = ListedRequest::new(
+        "users/create".to_owned(),
+        "# Creates a user.\n@tags smoke\nPOST https://api.example.com:8443/users?x=1\n",
+    );
+    assert_eq!(req.method, "POST");
+    assert_eq!(req.host, "api.example.com:8443");
+    assert_eq!(req.description.as_deref(), Some("Creates a user."));
+
+    assert_eq!(
+        ListedRequest::new("a".to_owned(), "GET {{ baseUrl }}/users").host,
+        "{{ baseUrl }}"
+    );
+    assert_eq!(
+        ListedRequest::new("a".to_owned(), "GRPC localhost:50051 shop.v1.Orders/Get").host,
+        "localhost:50051"
+    );
+}
+
+#[test]
+fn test_listing_report() {
+    let listed = |name: &str, method: &str, host: &str, description: Option<&str>| ListedRequest {
+        name: name.to_owned(),
+        method: method.to_owned(),
+        host: host.to_owned(),
+        description: description.map(str::to_owned),
+    };
+    let listing = Listing {
+        requests: vec![
+            listed("users/list", "GET", "{{ baseUrl }}", None),
+            listed("health", "GET", "example.com", Some("Is it up?")),
+            listed("auth/login", "POST", "auth.example.com", Some("Logs in.")),
+            listed(
+                "users/admin/delete",
+                "DELETE",
+                "{{ baseUrl }}",
+                Some("Deletes a user."),
+            ),
+        ],
+    };
+    assert_eq!(
+        listing.report(),
+        "auth/
+  login     POST    auth.example.com  Logs in.
+health      GET     example.com       Is it up?
+users/
+  admin/
+    delete  DELETE  {{ baseUrl }}     Deletes a user.
+  list      GET     {{ baseUrl }}"
+    );
+}
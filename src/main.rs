@@ -1,49 +1,362 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use reqq::{Reqq, ReqqOpts};
+mod completions;
+mod pager;
+#[cfg(unix)]
+mod tui;
+
+use anyhow::{anyhow, Result};
+use clap::{CommandFactory, Parser, Subcommand};
+use completions::Shell;
+use reqq::{
+    is_network_error, parse_duration, redact_secrets, ClientOpts, ExecutionResult, HttpVersion,
+    Lang, Output, OutputFormat, Pagination, Redirects, ReportFormat, Reporter, Reqq, ReqqError,
+    ReqqOpts, Resolve, ResponseParts, RetryOn, RetryPolicy, Snapshot, SnapshotMode, SuiteOpts,
+    TableReporter, TextReporter, TrailingNewline,
+};
 use std::collections::HashMap;
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "reqq", version = "0.3.0", author = "Seth Etter <sethetter@gmail.com>", about = "Like insomnia or postman, but a CLI.", long_about = None)]
 struct Args {
     /// The name of the request to execute.
+    #[arg(value_name = "REQUEST")]
     request_name: Option<String>,
 
-    /// The environment file to load.
-    #[arg(
-        short = 'e',
-        long = "env",
-        default_value = "default",
-    )]
-    env: String,
+    /// The environment file to load. Defaults to `default` when it exists. `reqq diff` takes
+    /// two, to compare.
+    #[arg(short = 'e', long = "env", value_name = "ENV", global = true)]
+    envs: Vec<String>,
 
     /// The directory containing the reqq files.
     #[arg(
         short = 'd',
         long = "dir",
+        env = "REQQ_DIR",
         default_value = ".reqq",
-        global = true,
+        global = true
     )]
     dir: String,
 
-    /// Only print the response body.
+    /// Print the response body exactly as received, without pretty-printing or colors.
+    #[arg(short = 'r', long = "raw", global = true)]
+    raw: bool,
+
+    /// Print only the text of HTML responses, without their tags, scripts and styles.
+    #[arg(long = "html-text", global = true)]
+    html_text: bool,
+
+    /// Don't page responses that don't fit on the terminal.
+    #[arg(long = "no-pager", global = true)]
+    no_pager: bool,
+
+    /// Don't record executed requests in the history.
+    #[arg(long = "no-history", global = true)]
+    no_history: bool,
+
+    /// Keep the cookies responses set in `.reqq/cookies.json`, and send them with later requests.
+    #[arg(long = "cookies", global = true)]
+    cookies: bool,
+
+    /// Cache responses with an `ETag` or `Last-Modified` header in `.reqq/cache/`, and use them
+    /// when the server answers that they're still current.
     #[arg(
-        short = 'r',
-        long = "raw",
+        long = "cache",
+        env = "REQQ_CACHE",
+        value_parser = clap::builder::FalseyValueParser::new(),
+        global = true,
     )]
-    raw: bool,
+    cache: bool,
+
+    /// Don't use or update the response cache, even with `--cache` or `REQQ_CACHE` set.
+    #[arg(long = "no-cache", global = true)]
+    no_cache: bool,
+
+    /// Print the rendered request before sending it, and timing info afterwards, to stderr.
+    #[arg(short = 'v', long = "verbose", global = true)]
+    verbose: bool,
+
+    /// Render the request with the env and args applied and print it, without sending it.
+    #[arg(long = "dry-run")]
+    dry_run: bool,
+
+    /// Save the response as the request's snapshot in `.reqq/snapshots`, for
+    /// `reqq test --snapshot` to compare against.
+    #[arg(long = "snapshot")]
+    snapshot: bool,
+
+    /// Ask on the terminal for the variables a request uses that aren't defined, instead of
+    /// failing. The values of those matching the config's `secret_pattern` aren't echoed.
+    #[arg(long = "prompt", global = true)]
+    prompt: bool,
+
+    /// Time each phase of the request: the DNS lookup, connecting, the TLS handshake, the wait
+    /// for the first byte and reading the response. The table is printed to stderr, or added to
+    /// the `timing` of `--format json`. The request is sent over HTTP/1.1 on a new connection.
+    #[arg(
+        long = "timings",
+        conflicts_with_all = ["output", "remote_name", "stream"],
+    )]
+    timings: bool,
+
+    /// Ask for a compressed response with `Accept-Encoding: gzip, deflate`, like curl. Bodies
+    /// encoded with gzip or deflate are decoded whether or not this is given.
+    #[arg(long = "compressed")]
+    compressed: bool,
+
+    /// Save the response body to this file instead of printing it.
+    #[arg(short = 'o', long = "output", conflicts_with = "remote_name")]
+    output: Option<PathBuf>,
+
+    /// Save the response body to a file in the current folder, named after the
+    /// Content-Disposition header or the URL.
+    #[arg(short = 'O', long = "remote-name")]
+    remote_name: bool,
+
+    /// Print the response body as it arrives, instead of once it's complete. Server-Sent Events
+    /// are printed an event at a time, as `event:` and `data:` lines.
+    #[arg(
+        long = "stream",
+        conflicts_with_all = ["output", "remote_name", "filter"],
+    )]
+    stream: bool,
+
+    /// Request the following pages too, printing each as it arrives: `link` follows the
+    /// `Link: <...>; rel="next"` header, a JSONPath like `$.next` gives the next page's URL in
+    /// the body, and `<param>=<JSONPath>`, like `cursor=$.meta.next_cursor`, a cursor in the
+    /// body to send as a query param.
+    #[arg(
+        long = "paginate",
+        value_name = "STRATEGY",
+        conflicts_with_all = ["output", "remote_name", "stream", "snapshot", "captures"],
+    )]
+    paginate: Option<Pagination>,
+
+    /// Stop paginating after this many pages.
+    #[arg(long = "max-pages", value_name = "N", requires = "paginate")]
+    max_pages: Option<usize>,
+
+    /// Close the WebSocket of a `WS` request after receiving this many messages. Without it,
+    /// messages are printed until the server closes the connection or Ctrl-C is pressed.
+    #[arg(long = "ws-count", value_name = "N")]
+    ws_count: Option<usize>,
+
+    /// Save values from the JSON response body to the session, as `name=<JSONPath>`. Saved
+    /// values are available to later requests as `{{ name }}`. Can be given multiple times.
+    ///
+    /// Example:
+    ///    reqq login --capture token=$.data.token
+    #[arg(
+        long = "capture",
+        action = clap::ArgAction::Append,
+        value_parser = clap::builder::ValueParser::new(parse_capture),
+    )]
+    captures: Vec<(String, String)>,
+
+    /// Fail on any 4xx or 5xx response status, like curl's `-f`, even when it's checked against
+    /// assertions or expected with `@expect`.
+    #[arg(long = "fail", short = 'f')]
+    fail: bool,
+
+    /// Check the response against the request's `@assert` directives, exiting with a non-zero
+    /// code if any fail.
+    #[arg(long = "check", global = true)]
+    check: bool,
+
+    /// How to print the result: `text` prints the response, while `json` and `yaml` print an
+    /// object with the request, response status, headers, body and timing, for scripts.
+    #[arg(long = "format", global = true, default_value = "text")]
+    format: OutputFormat,
+
+    /// Print only the part of the JSON response body a JSONPath expression matches, like
+    /// `.data.items[0].id` or `$.data.items[0].id`. Strings are printed without quotes.
+    #[arg(
+        long = "filter",
+        value_name = "JSONPATH",
+        conflicts_with_all = ["output", "remote_name", "include", "headers_only"],
+    )]
+    filter: Option<String>,
+
+    /// Print the response status line and headers above the body.
+    #[arg(short = 'i', long = "include", global = true)]
+    include: bool,
+
+    /// Only print the response status line and headers.
+    #[arg(
+        long = "headers-only",
+        global = true,
+        conflicts_with_all = ["include", "body_only"],
+    )]
+    headers_only: bool,
+
+    /// Only print the response body. This is the default unless `--include` is given.
+    #[arg(long = "body-only", global = true, conflicts_with = "include")]
+    body_only: bool,
+
+    /// Always end the request body with a newline, appending one if the file doesn't.
+    #[arg(
+        long = "trailing-newline",
+        global = true,
+        conflicts_with = "no_trailing_newline"
+    )]
+    trailing_newline: bool,
+
+    /// Strip the final newline from the request body before sending it.
+    #[arg(long = "no-trailing-newline", global = true)]
+    no_trailing_newline: bool,
+
+    /// How long to wait for a response before giving up, like `30`, `30s`, `500ms` or `2m`.
+    /// Plain numbers are seconds. Request files can override this with a `@timeout: <duration>`
+    /// line at the top.
+    #[arg(
+        short = 't',
+        long = "timeout",
+        global = true,
+        value_parser = clap::builder::ValueParser::new(parse_duration),
+    )]
+    timeout: Option<Duration>,
+
+    /// How long to wait for a connection to the server, in the same format as `--timeout`.
+    #[arg(
+        long = "connect-timeout",
+        global = true,
+        value_parser = clap::builder::ValueParser::new(parse_duration),
+    )]
+    connect_timeout: Option<Duration>,
+
+    /// Don't follow redirects, print the redirect response instead.
+    #[arg(long = "no-follow", global = true, conflicts_with = "max_redirects")]
+    no_follow: bool,
+
+    /// The most redirects to follow before giving up, 10 unless the collection config sets
+    /// `max_redirects`.
+    #[arg(long = "max-redirects", global = true)]
+    max_redirects: Option<usize>,
+
+    /// Send requests through this HTTP proxy, e.g. `http://127.0.0.1:8080`. Overrides the
+    /// `proxy` variable of the environment.
+    #[arg(long = "proxy", global = true)]
+    proxy: Option<String>,
+
+    /// Ignore the proxies set in the HTTP_PROXY, HTTPS_PROXY and ALL_PROXY variables.
+    #[arg(long = "no-proxy", global = true)]
+    no_proxy: bool,
+
+    /// Trust the CA certificates in this PEM file, on top of the system ones. Overrides the
+    /// `cacert` variable of the environment.
+    #[arg(long = "cacert", global = true)]
+    cacert: Option<PathBuf>,
+
+    /// A PEM client certificate for mutual TLS, used with `--key`. Overrides the `cert` variable
+    /// of the environment.
+    #[arg(long = "cert", global = true, requires = "key")]
+    cert: Option<PathBuf>,
+
+    /// The PEM (PKCS #8) private key of `--cert`. Overrides the `key` variable of the environment.
+    #[arg(long = "key", global = true, requires = "cert")]
+    key: Option<PathBuf>,
+
+    /// Don't verify TLS certificates, for self-signed development servers.
+    #[arg(short = 'k', long = "insecure", global = true)]
+    insecure: bool,
+
+    /// Connect to a host at this address instead of the one DNS gives, as `host:port:address`,
+    /// like `api.example.com:443:10.0.0.5`. Can be given multiple times. Overrides the `resolve`
+    /// variable of the environment.
+    #[arg(
+        long = "resolve",
+        value_name = "HOST:PORT:ADDRESS",
+        global = true,
+        action = clap::ArgAction::Append,
+    )]
+    resolve: Vec<Resolve>,
+
+    /// Send requests over this Unix socket instead of connecting to the URL's host, like
+    /// `/var/run/docker.sock` for Docker's API.
+    #[arg(long = "unix-socket", value_name = "PATH", global = true)]
+    unix_socket: Option<PathBuf>,
 
-    /// The optional args for the request. Can provide multiple args.
-    /// 
+    /// Don't set a Content-Type for request bodies without one. By default it's inferred from
+    /// the body: JSON, XML, HTML, form data or plain text, or a body file's extension.
+    #[arg(long = "no-infer", global = true)]
+    no_infer: bool,
+
+    /// Send request bodies whose Content-Type says they're JSON without checking that they are.
+    #[arg(long = "no-validate-body", global = true)]
+    no_validate_body: bool,
+
+    /// Only use HTTP/1.1, even when the server offers HTTP/2.
+    #[arg(
+        long = "http1.1",
+        global = true,
+        conflicts_with = "http2_prior_knowledge"
+    )]
+    http1_1: bool,
+
+    /// Use HTTP/2 without negotiating it first, for servers that speak it over plain TCP.
+    #[arg(long = "http2-prior-knowledge", global = true)]
+    http2_prior_knowledge: bool,
+
+    /// Retry failed requests this many times, with exponential backoff.
+    #[arg(long = "retry", global = true, default_value_t = 0)]
+    retry: u32,
+
+    /// The delay before the first retry, doubled for every retry after it.
+    #[arg(
+        long = "retry-delay",
+        global = true,
+        default_value = "1s",
+        value_parser = clap::builder::ValueParser::new(parse_duration),
+    )]
+    retry_delay: Duration,
+
+    /// Which failures to retry, separated by commas: 5xx, connection, timeout, or specific
+    /// status codes like 429.
+    #[arg(
+        long = "retry-on",
+        global = true,
+        value_delimiter = ',',
+        default_value = "5xx,connection"
+    )]
+    retry_on: Vec<RetryOn>,
+
+    /// Wait at least this long between the start of one request and the next, in the same format
+    /// as `--timeout`, so bulk runs with `run`, `test`, `bench` and flows don't trip rate limits.
+    #[arg(
+        long = "delay",
+        global = true,
+        value_parser = clap::builder::ValueParser::new(parse_duration),
+    )]
+    delay: Option<Duration>,
+
+    /// Variables for `@graphql` requests, replacing those in the request file. Values are
+    /// parsed like args.
+    ///
+    /// Example:
+    ///    reqq get-user --gql-variables id=42 --gql-variables full=true
+    #[arg(
+        long = "gql-variables",
+        value_name = "KEY=VALUE",
+        action = clap::ArgAction::Append,
+        global = true,
+        value_parser = clap::builder::ValueParser::new(parse_extra_arg),
+    )]
+    gql_variables: Vec<(String, serde_json::Value)>,
+
+    /// The optional args for the request. Can provide multiple args. Values that are valid
+    /// JSON, like numbers and booleans, keep their type.
+    ///
     /// Example:
     ///    reqq my-request -a id=1 -a name=foo
     #[arg(
         short = 'a',
         long = "arg",
         action = clap::ArgAction::Append,
+        global = true,
         value_parser = clap::builder::ValueParser::new(parse_extra_arg),
     )]
-    extra_args: Vec<(String, String)>,
+    extra_args: Vec<(String, serde_json::Value)>,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -52,61 +365,1019 @@ struct Args {
 #[derive(Subcommand)]
 enum Commands {
     /// Lists available requests.
-    List,
+    List {
+        /// Only list requests with this tag in their `@tags`. Can be given multiple times.
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Show a tree of folders, with the method, host and description of each request.
+        #[arg(short = 'l', long = "long")]
+        long: bool,
+    },
 
     /// Lists available environments.
     Envs,
+
+    /// Checks every request and env file for mistakes, without sending anything. Exits with a
+    /// non-zero code if it finds any.
+    Check,
+
+    /// Inspects environments.
+    Env {
+        #[command(subcommand)]
+        command: EnvCommands,
+    },
+
+    /// Inspects the cookie jar kept with `--cookies`.
+    Cookies {
+        #[command(subcommand)]
+        command: CookieCommands,
+    },
+
+    /// Manages the responses cached with `--cache`.
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Manages the secrets in the secret store, `secrets.enc` by default, which requests use like
+    /// env variables.
+    Secret {
+        #[command(subcommand)]
+        command: SecretCommands,
+    },
+
+    /// Lists previously executed requests, oldest first.
+    History,
+
+    /// Executes a request from the history again, exactly as it was rendered then.
+    Replay {
+        /// The id of the history entry, as shown by `reqq history`.
+        id: String,
+
+        /// Print the recorded response instead of sending the request again.
+        #[arg(long = "print")]
+        print: bool,
+    },
+
+    /// Runs a flow from the `flows` folder, executing its requests in order. Otherwise runs the
+    /// requests matching the given names or glob patterns, and prints a table of their statuses.
+    ///
+    /// Example:
+    ///    reqq run 'health/*' --parallel 8
+    Run {
+        /// The name of a flow, or request names and glob patterns like `health/*`.
+        #[arg(value_name = "REQUEST", required = true)]
+        names: Vec<String>,
+
+        /// How many requests to send at once.
+        #[arg(long = "parallel", default_value_t = 1)]
+        parallel: usize,
+    },
+
+    /// Runs requests as a test suite, checking their `@assert` directives.
+    Test {
+        /// Request names or glob patterns like `users/*`. Runs every request if none are given.
+        #[arg(value_name = "REQUEST")]
+        patterns: Vec<String>,
+
+        /// Only run requests with this tag in their `@tags`. Can be given multiple times.
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Stop at the first request that fails.
+        #[arg(long = "fail-fast")]
+        fail_fast: bool,
+
+        /// The report format: text, junit or json.
+        #[arg(long = "report", default_value = "text")]
+        report: ReportFormat,
+
+        /// Write the report to a file instead of stdout. A text summary is still printed.
+        #[arg(long = "report-file")]
+        report_file: Option<String>,
+
+        /// Compare responses against their snapshots in `.reqq/snapshots`. Responses without
+        /// one are saved as their snapshot.
+        #[arg(long = "snapshot")]
+        snapshot: bool,
+
+        /// Save every response as its snapshot, replacing the old ones.
+        #[arg(long = "update", requires = "snapshot")]
+        update: bool,
+    },
+
+    /// Serves the collection as a mock server, answering the method and path of each request
+    /// with its snapshot, or else its last response in the history.
+    Serve {
+        /// The address to listen on.
+        #[arg(long = "listen", default_value = "127.0.0.1:8080")]
+        listen: String,
+    },
+
+    /// Forwards requests to a server as a reverse proxy, and writes each one as a request file.
+    Record {
+        /// The address to listen on.
+        #[arg(long = "listen", default_value = "127.0.0.1:8888")]
+        listen: String,
+
+        /// The URL requests are forwarded to, like `https://api.example.com`.
+        #[arg(long = "target")]
+        target: String,
+
+        /// The folder of the reqq directory the request files are written to.
+        #[arg(long = "folder", default_value = "recorded")]
+        folder: String,
+
+        /// Save each response as the snapshot of its request too.
+        #[arg(long = "snapshot")]
+        snapshot: bool,
+    },
+
+    /// Creates a request file, or an env with `reqq new env <name>`.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    New {
+        #[command(subcommand)]
+        command: Option<NewCommands>,
+
+        /// The name of the new request, like `users/create`.
+        #[arg(required = true)]
+        name: Option<String>,
+
+        /// The method of the new request.
+        #[arg(long = "method", default_value = "GET")]
+        method: String,
+
+        /// The URL of the new request. Defaults to https://example.com.
+        #[arg(long = "url")]
+        url: Option<String>,
+
+        /// Overwrite the request if it already exists.
+        #[arg(long = "force")]
+        force: bool,
+    },
+
+    /// Creates request files from other tools' formats.
+    Import {
+        #[command(subcommand)]
+        command: ImportCommands,
+    },
+
+    /// Prints a curl command that sends a request the way reqq would, without sending it.
+    Curl {
+        /// The name of the request to export.
+        #[arg(value_name = "REQUEST")]
+        name: String,
+    },
+
+    /// Prints a program that sends a request the way reqq would, in a language, without sending
+    /// it. Secrets are left as placeholders.
+    Codegen {
+        /// The name of the request to write.
+        #[arg(value_name = "REQUEST")]
+        name: String,
+
+        /// rust, python, js or go.
+        #[arg(long = "lang", value_name = "LANG")]
+        lang: Lang,
+    },
+
+    /// Writes data from reqq in other tools' formats.
+    Export {
+        #[command(subcommand)]
+        command: ExportCommands,
+    },
+
+    /// Browses, fills in and sends requests in an interactive terminal UI.
+    Tui,
+
+    /// Prints a completion script for a shell, completing request and env names too.
+    ///
+    /// Example:
+    ///    reqq completions bash > ~/.local/share/bash-completion/completions/reqq
+    Completions {
+        /// bash, zsh or fish.
+        shell: Shell,
+    },
+
+    /// Lists request names, for completion scripts.
+    #[command(name = "__complete-requests", hide = true)]
+    CompleteRequests,
+
+    /// Lists env names, for completion scripts.
+    #[command(name = "__complete-envs", hide = true)]
+    CompleteEnvs,
+
+    /// Parses a request and prints a summary of it without sending it.
+    Describe {
+        /// The name of the request to describe.
+        #[arg(value_name = "REQUEST")]
+        name: String,
+    },
+
+    /// Sends a request many times and reports latency percentiles, throughput and errors.
+    ///
+    /// Example:
+    ///    reqq bench users/list -n 200 -c 10
+    Bench {
+        /// The name of the request to send.
+        #[arg(value_name = "REQUEST")]
+        name: String,
+
+        /// How many times to send the request.
+        #[arg(short = 'n', long = "requests", default_value_t = 100)]
+        requests: usize,
+
+        /// How many requests to send at once.
+        #[arg(short = 'c', long = "concurrency", default_value_t = 1)]
+        concurrency: usize,
+    },
+
+    /// Executes a request, then again whenever its request, env or body files change, until
+    /// interrupted.
+    Watch {
+        /// The name of the request to execute.
+        #[arg(value_name = "REQUEST")]
+        name: String,
+
+        /// How often to check the files for changes, in the same format as `--timeout`.
+        #[arg(
+            long = "interval",
+            default_value = "500ms",
+            value_parser = clap::builder::ValueParser::new(parse_duration)
+        )]
+        interval: Duration,
+    },
+
+    /// Executes a request in two environments and prints how the responses differ.
+    ///
+    /// Example:
+    ///    reqq diff users/list -e staging -e prod
+    Diff {
+        /// The name of the request to compare.
+        #[arg(value_name = "REQUEST")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum EnvCommands {
+    /// Prints the variables of an environment.
+    Show {
+        /// The name of the environment to show.
+        #[arg(value_name = "ENV")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum NewCommands {
+    /// Creates an empty JSON env file.
+    Env {
+        /// The name of the new env, like `staging`.
+        name: String,
+
+        /// Overwrite the env if it already exists.
+        #[arg(long = "force")]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommands {
+    /// Creates a request file from a curl command, e.g. one copied from browser dev tools.
+    Curl {
+        /// The curl command, quoted as a single argument.
+        #[arg(allow_hyphen_values = true)]
+        command: String,
+
+        /// The name of the new request, like `users/create`. Defaults to the last segment of
+        /// the URL path.
+        #[arg(long = "name")]
+        name: Option<String>,
+    },
+
+    /// Creates request files and envs from a Postman v2.0 or v2.1 collection export, or an
+    /// env from a Postman environment export.
+    Postman {
+        /// The exported JSON file.
+        file: PathBuf,
+    },
+
+    /// Creates a request file for every operation of an OpenAPI 3 spec, in folders named after
+    /// their tags, and an env with the spec's server URL as `baseUrl`.
+    Openapi {
+        /// The spec, in JSON or YAML.
+        file: PathBuf,
+    },
+
+    /// Creates a request file for every request in a HAR file, like one saved from browser dev
+    /// tools, in folders named after their hosts.
+    Har {
+        /// The HAR file.
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExportCommands {
+    /// Prints the requests and responses in the history as a HAR file.
+    Har,
+
+    /// Prints requests as an `.http` file, for the REST clients of VS Code and JetBrains IDEs.
+    Http {
+        /// The request to export, a glob pattern like `users/*`, or `all`.
+        #[arg(value_name = "REQUEST")]
+        request: String,
+
+        /// Also write the envs to this file, in the format of `http-client.env.json`.
+        #[arg(long = "env-file", value_name = "FILE")]
+        env_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CookieCommands {
+    /// Lists the stored cookies that haven't expired.
+    List,
+    /// Removes every stored cookie.
+    Clear,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Removes every cached response.
+    Clear,
+}
 
+#[derive(Subcommand)]
+enum SecretCommands {
+    /// Stores a secret, read from stdin or asked for without echoing it.
+    Set {
+        /// The name requests use the secret by, like `token` for `{{ token }}`.
+        name: String,
+    },
+    /// Prints a secret.
+    Get {
+        /// The name of the secret.
+        name: String,
+    },
+    /// Removes a secret.
+    Rm {
+        /// The name of the secret.
+        name: String,
+    },
+    /// Lists the names of the secrets.
+    List,
+}
+
+/// Exit codes, so scripts can tell why a request failed. Usage errors exit with 2.
+const EXIT_ERROR: i32 = 1;
+const EXIT_NETWORK: i32 = 3;
+const EXIT_STATUS: i32 = 4;
+const EXIT_ASSERTION: i32 = 5;
+
+fn main() {
+    if let Err(e) = run(Args::parse()) {
+        // Errors can quote rendered requests and responses, so secrets are masked in them too.
+        eprintln!("Error: {}", redact_secrets(&format!("{:?}", e)));
+        if let Some(hint) = ReqqError::hint_for(&e) {
+            eprintln!("Hint: {}", hint);
+        }
+        std::process::exit(if is_network_error(&e) {
+            EXIT_NETWORK
+        } else {
+            EXIT_ERROR
+        });
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    let dir = expand_home(&args.dir);
     let reqq = Reqq::new(ReqqOpts {
-        dir: args.dir.as_str(),
+        dir: dir.as_str(),
+        parts: response_parts(&args),
         raw: args.raw,
+        html_text: args.html_text,
+        // Colors would throw off the widths of lines in the TUI.
+        color: std::io::stdout().is_terminal()
+            && std::env::var_os("NO_COLOR").is_none()
+            && !matches!(args.command, Some(Commands::Tui)),
+        verbose: args.verbose,
+        trailing_newline: trailing_newline_mode(&args),
+        timeout: args.timeout,
+        client: ClientOpts {
+            connect_timeout: args.connect_timeout,
+            redirects: match (args.no_follow, args.max_redirects) {
+                (true, _) => Some(Redirects::None),
+                (false, max) => max.map(Redirects::Follow),
+            },
+            proxy: args.proxy.clone(),
+            no_proxy: args.no_proxy,
+            cacert: args.cacert.clone(),
+            cert: args.cert.clone(),
+            key: args.key.clone(),
+            insecure: args.insecure,
+            resolve: args.resolve.clone(),
+            unix_socket: args.unix_socket.clone(),
+            http_version: match (args.http1_1, args.http2_prior_knowledge) {
+                (true, _) => Some(HttpVersion::Http1),
+                (false, true) => Some(HttpVersion::Http2PriorKnowledge),
+                (false, false) => None,
+            },
+            verbose: false,
+        },
+        retry: RetryPolicy {
+            retries: args.retry,
+            delay: args.retry_delay,
+            on: args.retry_on.clone(),
+        },
+        delay: args.delay.unwrap_or_default(),
+        history: !args.no_history,
+        cookies: args.cookies,
+        cache: args.cache && !args.no_cache,
+        check: args.check,
+        output: match (&args.output, args.remote_name, args.stream) {
+            (Some(path), _, _) => Some(Output::File(path.clone())),
+            (None, true, _) => Some(Output::Infer),
+            (None, false, true) => Some(Output::Stream),
+            (None, false, false) => None,
+        },
+        gql_variables: build_extra_args_map(args.gql_variables.clone()),
+        filter: args.filter.clone(),
+        ws_count: args.ws_count,
+        timings: args.timings,
+        compressed: args.compressed,
+        infer_content_type: !args.no_infer,
+        validate_body: !args.no_validate_body,
     })?;
 
-    if args.command.is_none() && args.request_name.is_none() {
-        eprintln!("Error: 'request_name' is required when no subcommand is specified.");
-        std::process::exit(1);
-    }
+    let env = match (&args.command, args.envs.as_slice()) {
+        (Some(Commands::Diff { .. }), _) | (_, []) => None,
+        (_, [env]) => Some(env.clone()),
+        _ => return Err(anyhow!("Only one env can be given, except to `reqq diff`.")),
+    };
+
+    // Without a request name, one can be picked in a terminal.
+    let request_name = match (&args.command, args.request_name.clone()) {
+        (None, None) if can_pick() => match pick_request(&reqq)? {
+            Some(name) => Some(name),
+            None => return Ok(()),
+        },
+        (None, None) => {
+            eprintln!("Error: 'request_name' is required when no subcommand is specified.");
+            std::process::exit(1);
+        }
+        (_, name) => name,
+    };
 
     match &args.command {
-        Some(Commands::List) => {
-            for req_name in reqq.list_reqs().into_iter() {
+        Some(Commands::List { tags, long: true }) => {
+            let listing = reqq.listing(tags)?;
+            if !listing.requests.is_empty() {
+                println!("{}", listing.report());
+            }
+        }
+        Some(Commands::List { tags, long: false }) => {
+            for req_name in reqq.list_tagged(tags)?.into_iter() {
                 println!("{}", req_name);
             }
         }
+        Some(Commands::Check) => {
+            let extra_args = build_extra_args_map(args.extra_args);
+            let validation = reqq.validate(extra_args)?;
+            println!("{}", validation.report());
+            if !validation.passed() {
+                std::process::exit(1);
+            }
+        }
         Some(Commands::Envs) => {
             for env_name in reqq.list_envs().into_iter() {
                 println!("{}", env_name);
             }
         }
-        None => {
-            let request_name = args.request_name.as_deref().expect("No request name provided.");
+        Some(Commands::Env {
+            command: EnvCommands::Show { name },
+        }) => {
+            println!("{}", reqq.show_env(name)?);
+        }
+        Some(Commands::Cookies {
+            command: CookieCommands::List,
+        }) => {
+            for cookie in reqq.cookies()?.iter() {
+                println!("{}", cookie);
+            }
+        }
+        Some(Commands::Cookies {
+            command: CookieCommands::Clear,
+        }) => {
+            reqq.clear_cookies()?;
+        }
+        Some(Commands::Cache {
+            command: CacheCommands::Clear,
+        }) => {
+            reqq.clear_cache()?;
+        }
+        Some(Commands::Secret {
+            command: SecretCommands::Set { name },
+        }) => {
+            let value = if std::io::stdin().is_terminal() {
+                reqq.prompt_secret(name)?
+            } else {
+                let mut value = String::new();
+                std::io::stdin().read_to_string(&mut value)?;
+                value.trim_end_matches(['\r', '\n']).to_owned()
+            };
+            reqq.set_secret(name, &value)?;
+        }
+        Some(Commands::Secret {
+            command: SecretCommands::Get { name },
+        }) => {
+            let value = reqq
+                .secret(name)?
+                .ok_or_else(|| anyhow!("Secret '{}' not found.", name))?;
+            println!("{}", value);
+        }
+        Some(Commands::Secret {
+            command: SecretCommands::Rm { name },
+        }) => {
+            if !reqq.remove_secret(name)? {
+                return Err(anyhow!("Secret '{}' not found.", name));
+            }
+        }
+        Some(Commands::Secret {
+            command: SecretCommands::List,
+        }) => {
+            for name in reqq.secret_names()? {
+                println!("{}", name);
+            }
+        }
+        Some(Commands::History) => {
+            for entry in reqq.history()?.iter() {
+                println!("{}", entry.summary());
+            }
+        }
+        Some(Commands::Replay { id, print }) => {
+            let result = if *print {
+                reqq.replay_print(id)?
+            } else {
+                reqq.replay(id)?
+            };
+            print_result(result, args.format, args.fail, !args.no_pager);
+        }
+        Some(Commands::Run { names, parallel }) => {
+            let extra_args = build_extra_args_map(args.extra_args);
+            let passed = match names.as_slice() {
+                [flow] if reqq.has_flow(flow) => {
+                    let result = reqq.run_flow(flow, env, extra_args)?;
+                    println!("{}", result.report());
+                    result.passed()
+                }
+                _ => {
+                    let result = reqq.run_requests(names, *parallel, env, extra_args)?;
+                    println!("{}", TableReporter.render(&result));
+                    result.passed()
+                }
+            };
+            if !passed {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Test {
+            patterns,
+            tags,
+            fail_fast,
+            report,
+            report_file,
+            snapshot,
+            update,
+        }) => {
+            let opts = SuiteOpts {
+                patterns: patterns.clone(),
+                tags: tags.clone(),
+                fail_fast: *fail_fast,
+                snapshot: match (snapshot, update) {
+                    (_, true) => SnapshotMode::Update,
+                    (true, false) => SnapshotMode::Compare,
+                    (false, false) => SnapshotMode::Off,
+                },
+            };
+            let extra_args = build_extra_args_map(args.extra_args);
+            let result = reqq.test(opts, env, extra_args)?;
+            let rendered = report.reporter().render(&result);
+            match report_file {
+                Some(path) => {
+                    std::fs::write(path, format!("{}\n", rendered))?;
+                    println!("{}", TextReporter.render(&result));
+                }
+                None => println!("{}", rendered),
+            }
+            if !result.passed() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Serve { listen }) => {
+            let (server, skipped) = reqq.mock_server()?;
+            if server.routes().is_empty() {
+                return Err(anyhow!(
+                    "No requests have a snapshot or a response in the history to serve."
+                ));
+            }
+            let listener = std::net::TcpListener::bind(listen)
+                .map_err(|e| anyhow!("Can't listen on {}: {}", listen, e))?;
+            for route in server.routes() {
+                println!("{} {}  ({})", route.method, route.path, route.request);
+            }
+            if !skipped.is_empty() {
+                println!(
+                    "Not served, without a snapshot or response: {}",
+                    skipped.join(", ")
+                );
+            }
+            println!("Serving on http://{}", listener.local_addr()?);
+            server.run(listener)?;
+        }
+        Some(Commands::Record {
+            listen,
+            target,
+            folder,
+            snapshot,
+        }) => {
+            let recorder = reqq.recorder(target, folder, *snapshot)?;
+            let listener = std::net::TcpListener::bind(listen)
+                .map_err(|e| anyhow!("Can't listen on {}: {}", listen, e))?;
+            println!(
+                "Recording requests to {} on http://{}",
+                target,
+                listener.local_addr()?
+            );
+            recorder.run(listener)?;
+        }
+        Some(Commands::New {
+            command: Some(NewCommands::Env { name, force }),
+            ..
+        }) => {
+            println!("Created {}", reqq.new_env(name, *force)?.display());
+        }
+        Some(Commands::New {
+            command: None,
+            name,
+            method,
+            url,
+            force,
+        }) => {
+            let name = name.as_deref().expect("No request name provided.");
+            println!(
+                "Created {}",
+                reqq.new_request(name, method, url.as_deref(), *force)?
+                    .display()
+            );
+        }
+        Some(Commands::Import { command }) => {
+            let (fpaths, warnings) = match command {
+                ImportCommands::Curl { command, name } => {
+                    (vec![reqq.import_curl(command, name.as_deref())?], vec![])
+                }
+                ImportCommands::Postman { file } => reqq.import_postman(file)?,
+                ImportCommands::Openapi { file } => reqq.import_openapi(file)?,
+                ImportCommands::Har { file } => reqq.import_har(file)?,
+            };
+            for fpath in fpaths.iter() {
+                println!("Created {}", fpath.display());
+            }
+            for warning in warnings.iter() {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+        Some(Commands::Export {
+            command: ExportCommands::Har,
+        }) => {
+            println!("{}", reqq.export_har()?);
+        }
+        Some(Commands::Export {
+            command: ExportCommands::Http { request, env_file },
+        }) => {
+            let (exported, mut warnings) = reqq.export_http(request)?;
+            if let Some(env_file) = env_file {
+                let (envs, env_warnings) = reqq.export_http_envs()?;
+                std::fs::write(env_file, envs)?;
+                eprintln!("Wrote {}", env_file.display());
+                warnings.extend(env_warnings);
+            }
+            print!("{}", exported);
+            for warning in warnings.iter() {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+        Some(Commands::Curl { name }) => {
+            let extra_args = prompt_variables(
+                &reqq,
+                name,
+                env.clone(),
+                build_extra_args_map(args.extra_args),
+                args.prompt,
+            )?;
+            println!("{}", reqq.curl(name, env, extra_args)?);
+        }
+        Some(Commands::Codegen { name, lang }) => {
+            let extra_args = prompt_variables(
+                &reqq,
+                name,
+                env.clone(),
+                build_extra_args_map(args.extra_args),
+                args.prompt,
+            )?;
+            print!("{}", reqq.codegen(name, env, extra_args, *lang)?);
+        }
+        Some(Commands::Tui) => {
+            #[cfg(unix)]
+            tui::run(&reqq, env)?;
+            #[cfg(not(unix))]
+            return Err(anyhow::anyhow!("reqq tui is only supported on Unix."));
+        }
+        Some(Commands::Completions { shell }) => {
+            print!("{}", completions::generate(&Args::command(), *shell));
+        }
+        Some(Commands::CompleteRequests) => {
+            for req_name in reqq.list_reqs().into_iter() {
+                println!("{}", req_name);
+            }
+        }
+        Some(Commands::CompleteEnvs) => {
+            for env_name in reqq.list_envs().into_iter() {
+                println!("{}", env_name);
+            }
+        }
+        Some(Commands::Describe { name }) => {
+            let extra_args = prompt_variables(
+                &reqq,
+                name,
+                env.clone(),
+                build_extra_args_map(args.extra_args),
+                args.prompt,
+            )?;
+            println!("{}", reqq.describe(name, env, extra_args)?);
+        }
+        Some(Commands::Bench {
+            name,
+            requests,
+            concurrency,
+        }) => {
             let extra_args = build_extra_args_map(args.extra_args);
-            println!("{}", reqq.execute(request_name, Some(args.env), extra_args)?);
+            let result = reqq.bench(name, env, extra_args, *requests, *concurrency)?;
+            println!("{}", result.report());
+            if result.errors() > 0 {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Watch { name, interval }) => {
+            let extra_args = build_extra_args_map(args.extra_args);
+            for run in 1.. {
+                // The files are read before executing, so changes made meanwhile aren't missed.
+                let files = reqq.watched_files(name, env.clone(), extra_args.clone())?;
+                let snapshot = Snapshot::new(files);
+                println!("--- {}, run {} ---", name, run);
+                match reqq.execute(name, env.clone(), extra_args.clone(), &args.captures) {
+                    Ok(result) => print_watched(result, args.format),
+                    Err(e) => eprintln!("Error: {}", redact_secrets(&format!("{:#}", e))),
+                }
+                snapshot.wait(*interval);
+            }
+        }
+        Some(Commands::Diff { name }) => {
+            let (left, right) = match args.envs.as_slice() {
+                [left, right] => (left, right),
+                _ => {
+                    return Err(anyhow!(
+                        "Give the two envs to compare, like `-e staging -e prod`."
+                    ))
+                }
+            };
+            let extra_args = build_extra_args_map(args.extra_args);
+            let diff = reqq.diff(name, (left, right), extra_args)?;
+            println!("{}", diff.report());
+            if !diff.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        None => {
+            let request_name = request_name.as_deref().expect("No request name provided.");
+            let extra_args = prompt_variables(
+                &reqq,
+                request_name,
+                env.clone(),
+                build_extra_args_map(args.extra_args),
+                args.prompt,
+            )?;
+            if args.dry_run {
+                print!("{}", reqq.render(request_name, env, extra_args)?);
+                return Ok(());
+            }
+
+            if let Some(pagination) = &args.paginate {
+                reqq.paginate(
+                    request_name,
+                    env,
+                    extra_args,
+                    pagination,
+                    args.max_pages,
+                    |page| print_result(page, args.format, args.fail, false),
+                )?;
+                return Ok(());
+            }
+            let result = reqq.execute(request_name, env, extra_args, &args.captures)?;
+            if args.snapshot {
+                let fpath = reqq.save_snapshot(request_name, &result.entry.response)?;
+                eprintln!("Saved snapshot {}", fpath.display());
+            }
+            print_result(result, args.format, args.fail, !args.no_pager);
         }
     }
     Ok(())
 }
 
-fn build_extra_args_map(cli_extra_args: Vec<(String, String)>) -> HashMap<String, serde_json::Value> {
-    let mut extra_args: HashMap<String, serde_json::Value> = HashMap::new();
-    for arg in cli_extra_args {
-        extra_args.insert(
-            arg.0.to_owned(),
-            serde_json::to_value(arg.1).unwrap(),
-        );
+/// Whether a request can be picked interactively, which needs a terminal on both ends.
+fn can_pick() -> bool {
+    cfg!(unix) && std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+#[cfg(unix)]
+fn pick_request(reqq: &Reqq) -> Result<Option<String>> {
+    let mut names = reqq.list_reqs();
+    names.sort();
+    tui::pick(&names)
+}
+
+#[cfg(not(unix))]
+fn pick_request(_reqq: &Reqq) -> Result<Option<String>> {
+    Ok(None)
+}
+
+/// Prints the response, exiting with a non-zero code if an assertion failed or its status
+/// fails the request: one its `@expect` doesn't allow, or a 4xx or 5xx one. When the response
+/// was checked against assertions, only those decide, unless `fail` is set. With `paging`,
+/// output that doesn't fit on the terminal goes through a pager.
+fn print_result(result: ExecutionResult, format: OutputFormat, fail: bool, paging: bool) {
+    // Nothing is left to print when the body was saved to a file.
+    let output = result.render(format);
+    if !output.is_empty() {
+        if let Err(e) = pager::print(&output, paging) {
+            eprintln!("Error: {}", e);
+        }
+    }
+    if let (OutputFormat::Text, Some(timings)) = (format, &result.entry.timings) {
+        eprintln!("{}", timings.report());
+    }
+
+    if let Some(check) = &result.check {
+        eprintln!("{}", check.report());
+        if !check.passed() {
+            std::process::exit(EXIT_ASSERTION);
+        }
+    }
+    if let Some(error) = result.status_error(fail) {
+        eprintln!("Error: {}", error);
+        std::process::exit(EXIT_STATUS);
+    }
+}
+
+/// Like `print_result`, but failures don't end the process.
+fn print_watched(result: ExecutionResult, format: OutputFormat) {
+    let output = result.render(format);
+    if !output.is_empty() {
+        if let Err(e) = pager::print(&output, false) {
+            eprintln!("Error: {}", e);
+        }
+    }
+    if let Some(check) = &result.check {
+        eprintln!("{}", check.report());
+    }
+    if let Some(error) = result.status_error(false) {
+        eprintln!("Error: {}", error);
+    }
+}
+
+/// Expands a leading `~` to the home directory, for paths that didn't go through a shell.
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home, rest)
+        }
+        _ => path.to_owned(),
+    }
+}
+
+fn response_parts(args: &Args) -> ResponseParts {
+    if args.headers_only {
+        ResponseParts::Headers
+    } else if args.include {
+        ResponseParts::All
+    } else {
+        ResponseParts::Body
     }
-    extra_args
 }
 
-fn parse_extra_arg(raw_arg: &str) -> Result<(String, String), std::io::Error> {
-    let kv_pair: Vec<&str> = raw_arg.splitn(2, "=").collect();
-    if kv_pair.len() < 2 {
-        eprintln!("At least one of the args provided is malformed.");
-        std::process::exit(1);
+fn trailing_newline_mode(args: &Args) -> TrailingNewline {
+    if args.trailing_newline {
+        TrailingNewline::Always
+    } else if args.no_trailing_newline {
+        TrailingNewline::Never
+    } else {
+        TrailingNewline::Preserve
     }
-    Ok((kv_pair[0].to_owned(), kv_pair[1].to_owned()))
-}
\ No newline at end of file
+}
+
+fn build_extra_args_map(
+    cli_extra_args: Vec<(String, serde_json::Value)>,
+) -> HashMap<String, serde_json::Value> {
+    cli_extra_args.into_iter().collect()
+}
+
+/// Asks for the variables a request is missing when `--prompt` is given, and adds them to the
+/// args.
+fn prompt_variables(
+    reqq: &Reqq,
+    name: &str,
+    env: Option<String>,
+    extra_args: HashMap<String, serde_json::Value>,
+    prompt: bool,
+) -> Result<HashMap<String, serde_json::Value>> {
+    if !prompt {
+        return Ok(extra_args);
+    }
+    reqq.prompt_variables(name, env, extra_args)
+}
+
+/// Parses a `key=value` arg. Values that are valid JSON (numbers, booleans, arrays...) keep
+/// their type, anything else is used as a plain string.
+fn parse_extra_arg(raw_arg: &str) -> Result<(String, serde_json::Value), String> {
+    let (key, value) = raw_arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected key=value, got '{}'", raw_arg))?;
+    let value =
+        serde_json::from_str(value).unwrap_or_else(|_| serde_json::Value::String(value.to_owned()));
+    Ok((key.to_owned(), value))
+}
+
+/// Parses a `name=<JSONPath>` capture.
+fn parse_capture(raw_capture: &str) -> Result<(String, String), String> {
+    match raw_capture.split_once('=') {
+        Some((name, path)) if !name.is_empty() && path.starts_with('$') => {
+            Ok((name.to_owned(), path.to_owned()))
+        }
+        _ => Err(format!(
+            "expected name=<JSONPath>, like token=$.data.token, got '{}'",
+            raw_capture
+        )),
+    }
+}
+
+#[test]
+fn test_parse_extra_arg() {
+    assert_eq!(
+        parse_extra_arg("count=5").unwrap(),
+        ("count".to_owned(), serde_json::json!(5))
+    );
+    assert_eq!(
+        parse_extra_arg("on=true").unwrap(),
+        ("on".to_owned(), serde_json::json!(true))
+    );
+    assert_eq!(
+        parse_extra_arg("name=foo").unwrap(),
+        ("name".to_owned(), serde_json::json!("foo"))
+    );
+    assert_eq!(
+        parse_extra_arg("q=a=b").unwrap(),
+        ("q".to_owned(), serde_json::json!("a=b"))
+    );
+    assert_eq!(
+        parse_extra_arg("id=007").unwrap(),
+        ("id".to_owned(), serde_json::json!("007"))
+    );
+    assert_eq!(
+        parse_extra_arg("empty=").unwrap(),
+        ("empty".to_owned(), serde_json::json!(""))
+    );
+    assert!(parse_extra_arg("malformed").is_err());
+}
+
+#[test]
+fn test_parse_capture() {
+    assert_eq!(
+        parse_capture("token=$.data.token").unwrap(),
+        ("token".to_owned(), "$.data.token".to_owned())
+    );
+    assert_eq!(
+        parse_capture("q=$['a=b']").unwrap(),
+        ("q".to_owned(), "$['a=b']".to_owned())
+    );
+    assert!(parse_capture("token").is_err());
+    assert!(parse_capture("=$.a").is_err());
+    assert!(parse_capture("token=data.token").is_err());
+}
+
+#[test]
+fn test_args() {
+    use clap::CommandFactory;
+    Args::command().debug_assert();
+}
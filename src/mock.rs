@@ -0,0 +1,331 @@
+//! `reqq serve`: a mock server built from the collection. The method and path of each request
+//! file become a route, answered with the request's snapshot, or else its last response in the
+//! history. Templated path segments, like `{{ id }}` in `/users/{{ id }}`, match any segment,
+//! and paths relative to a base URL, like `/users` or `{{ baseUrl }}/users`, match behind any
+//! leading path, since the base URL's own path isn't known.
+
+use crate::{frontmatter, grpc, response::Response, websocket};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+/// Response headers the server sets itself, rather than copying them from the stored response.
+const SERVER_HEADERS: [&str; 4] = [
+    "content-length",
+    "transfer-encoding",
+    "connection",
+    "content-encoding",
+];
+
+/// A request of the collection, served at its method and path.
+#[derive(Clone, Debug)]
+pub struct Route {
+    /// The request the route was made from.
+    pub request: String,
+    pub method: String,
+    /// The path of the request's URL, as written in its file.
+    pub path: String,
+    pattern: Regex,
+    /// How many segments of the path are templated. Routes with fewer are tried first.
+    wildcards: usize,
+    /// Whether the path is relative to a base URL. Absolute ones are tried first.
+    relative: bool,
+    response: Response,
+}
+
+impl Route {
+    /// A route for a request file that hasn't been templated. There's none for WebSocket and
+    /// gRPC requests, or when the path can't be told from the URL.
+    pub fn new(request: String, fstr: &str, response: Response) -> Option<Self> {
+        let line = frontmatter::request_line(fstr).trim();
+        let (method, url) = line.split_once(' ')?;
+        if method == websocket::METHOD || method == grpc::METHOD {
+            return None;
+        }
+        let (path, relative) = path(url.trim())?;
+        let (pattern, wildcards) = pattern(path, relative)?;
+        Some(Route {
+            request,
+            method: method.to_owned(),
+            path: path.to_owned(),
+            pattern,
+            wildcards,
+            relative,
+            response,
+        })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        self.pattern.is_match(path)
+    }
+}
+
+/// Serves the stored responses of a collection.
+#[derive(Clone, Debug)]
+pub struct MockServer {
+    routes: Vec<Route>,
+}
+
+impl MockServer {
+    pub fn new(mut routes: Vec<Route>) -> Self {
+        routes.sort_by(|a, b| {
+            (a.wildcards, a.relative, &a.path).cmp(&(b.wildcards, b.relative, &b.path))
+        });
+        MockServer { routes }
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Answers connections until the process is stopped, each on its own thread.
+    pub fn run(&self, listener: TcpListener) -> Result<()> {
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let stream = stream?;
+                scope.spawn(move || {
+                    if let Err(e) = self.handle(stream) {
+                        eprintln!("Error: {}", e);
+                    }
+                });
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads a request from a connection and answers it with the response of the first route
+    /// that matches, `405 Method Not Allowed` if only the path matches one, or `404 Not Found`.
+    /// Each request is logged to stderr, with the request file that answered it.
+    fn handle(&self, stream: TcpStream) -> Result<()> {
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(anyhow!(
+                "Invalid request line: '{}'.",
+                request_line.trim_end()
+            ));
+        };
+
+        // The body isn't needed, but it's read so the client isn't cut off while sending it.
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+        std::io::copy(
+            &mut (&mut reader).take(content_length),
+            &mut std::io::sink(),
+        )?;
+
+        let path = target.split(['?', '#']).next().unwrap_or_default();
+        let route = self
+            .routes
+            .iter()
+            .find(|r| r.method.eq_ignore_ascii_case(method) && r.matches(path));
+        let (status, headers, body, served_by) = match route {
+            Some(route) => {
+                let resp = &route.response;
+                let headers: Vec<(String, String)> = resp
+                    .headers
+                    .iter()
+                    .filter(|(name, _)| !SERVER_HEADERS.contains(&name.as_str()))
+                    .map(|(name, value)| {
+                        (
+                            name.to_string(),
+                            String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                        )
+                    })
+                    .collect();
+                (
+                    resp.status,
+                    headers,
+                    resp.body.clone(),
+                    route.request.as_str(),
+                )
+            }
+            None => {
+                let status = if self.routes.iter().any(|r| r.matches(path)) {
+                    reqwest::StatusCode::METHOD_NOT_ALLOWED
+                } else {
+                    reqwest::StatusCode::NOT_FOUND
+                };
+                let headers = vec![("content-type".to_owned(), "text/plain".to_owned())];
+                (
+                    status,
+                    headers,
+                    format!("No route for {} {}\n", method, path),
+                    "-",
+                )
+            }
+        };
+        eprintln!("{} {} -> {} ({})", method, target, status, served_by);
+
+        let mut out = format!(
+            "HTTP/1.1 {} {}\r\n",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or_default()
+        );
+        for (name, value) in headers {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        let body = if method.eq_ignore_ascii_case("HEAD") {
+            ""
+        } else {
+            body.as_str()
+        };
+        out.push_str(&format!(
+            "content-length: {}\r\nconnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        ));
+        (&stream).write_all(out.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The path of a URL, without its query, and whether it's relative to a base URL: given as
+/// a path, or after a templated prefix like `{{ baseUrl }}`.
+fn path(url: &str) -> Option<(&str, bool)> {
+    let (rest, relative) = match url.strip_prefix("{{") {
+        Some(_) => (&url[url.find("}}")? + 2..], true),
+        None => match url.split_once("://") {
+            Some((_, rest)) => (&rest[rest.find('/').unwrap_or(rest.len())..], false),
+            None => (url, true),
+        },
+    };
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    match path {
+        "" => Some(("/", relative)),
+        path if path.starts_with('/') => Some((path, relative)),
+        _ => None,
+    }
+}
+
+/// A regex matching a path, where each `{{ ... }}` matches any text within a segment, and the
+/// number of those. Relative paths match behind any leading path too.
+fn pattern(path: &str, relative: bool) -> Option<(Regex, usize)> {
+    let mut regex = String::from(if relative { "^(?:/.*)?" } else { "^" });
+    let mut wildcards = 0;
+    let mut rest = path;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}")? + start + 2;
+        regex.push_str(&regex::escape(&rest[..start]));
+        regex.push_str("[^/]+");
+        wildcards += 1;
+        rest = &rest[end..];
+    }
+    regex.push_str(&regex::escape(rest.trim_end_matches('/')));
+    regex.push_str("/?$");
+    Some((Regex::new(&regex).ok()?, wildcards))
+}
+
+#[test]
+fn test_route_path() {
+    assert_eq!(
+        path("https://api.example.com:8443/v1/users?limit=5"),
+        Some(("/v1/users", false))
+    );
+    assert_eq!(
+        path("{{ baseUrl }}/users/{{ id }}"),
+        Some(("/users/{{ id }}", true))
+    );
+    assert_eq!(path("/users"), Some(("/users", true)));
+    assert_eq!(path("https://example.com"), Some(("/", false)));
+    assert_eq!(path("users"), None);
+
+    let (regex, wildcards) = pattern("/users/{{ id }}/posts/", false).unwrap();
+    assert_eq!(wildcards, 1);
+    assert!(regex.is_match("/users/7/posts"));
+    assert!(regex.is_match("/users/ada/posts/"));
+    assert!(!regex.is_match("/users/7/8/posts"));
+    assert!(!regex.is_match("/v1/users/7/posts"));
+    assert!(pattern("/", false).unwrap().0.is_match("/"));
+
+    let (regex, _) = pattern("/users", true).unwrap();
+    assert!(regex.is_match("/users"));
+    assert!(regex.is_match("/api/v1/users"));
+    assert!(!regex.is_match("/api/v1users"));
+}
+
+#[test]
+fn test_mock_server() {
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::{StatusCode, Version};
+
+    let response = |status: StatusCode, body: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        headers.insert("content-length", HeaderValue::from_static("999"));
+        Response {
+            status,
+            version: Version::HTTP_11,
+            headers,
+            body: body.to_owned(),
+            binary: None,
+        }
+    };
+    let routes = vec![
+        Route::new(
+            "users/get".to_owned(),
+            "GET {{ baseUrl }}/users/{{ id }}",
+            response(StatusCode::OK, "{\"id\":7}"),
+        )
+        .unwrap(),
+        Route::new(
+            "users/me".to_owned(),
+            "# Me.\nGET /users/me",
+            response(StatusCode::OK, "{\"me\":true}"),
+        )
+        .unwrap(),
+        Route::new(
+            "users/create".to_owned(),
+            "POST https://api.example.com/users\n\n{}",
+            response(StatusCode::CREATED, "{}"),
+        )
+        .unwrap(),
+    ];
+    assert!(Route::new(
+        "ws".to_owned(),
+        "WS ws://example.com/chat",
+        response(StatusCode::OK, "")
+    )
+    .is_none());
+    let server = MockServer::new(routes);
+    assert_eq!(server.routes()[0].request, "users/create");
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let exchange = |request: &str| -> String {
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        server.handle(stream).unwrap();
+        let mut out = String::new();
+        client.read_to_string(&mut out).unwrap();
+        out
+    };
+    let out = exchange("GET /users/me HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    assert!(out.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(out.ends_with("content-length: 11\r\nconnection: close\r\n\r\n{\"me\":true}"));
+    assert!(!out.contains("999"));
+
+    let out = exchange("GET /users/42?full=1 HTTP/1.1\r\n\r\n");
+    assert!(out.ends_with("{\"id\":7}"));
+    let out = exchange("POST /users HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}");
+    assert!(out.starts_with("HTTP/1.1 201 Created\r\n"));
+    let out = exchange("DELETE /users/42 HTTP/1.1\r\n\r\n");
+    assert!(out.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    let out = exchange("GET /orders HTTP/1.1\r\n\r\n");
+    assert!(out.starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
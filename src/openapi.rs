@@ -0,0 +1,420 @@
+//! Converting OpenAPI 3 specs into request files.
+
+use crate::import::{slug, Import};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Schemas nested deeper than this get no example, so recursive schemas end.
+const MAX_DEPTH: usize = 8;
+
+/// Converts every operation of a spec into a request file, in a folder named after its first
+/// tag. The URL of the first server becomes the `baseUrl` of an environment named after the
+/// spec.
+pub fn convert(spec: &Value) -> Result<Import> {
+    match spec["openapi"].as_str() {
+        Some(version) if version.starts_with('3') => {}
+        _ if spec["swagger"].is_string() => {
+            return Err(anyhow!(
+                "Only OpenAPI 3 specs are supported, not Swagger 2."
+            ))
+        }
+        _ => return Err(anyhow!("Expected an OpenAPI 3 spec.")),
+    }
+
+    let mut import = Import::default();
+    let title = spec["info"]["title"].as_str().unwrap_or("openapi");
+    if let Some(base_url) = server_url(&spec["servers"][0]) {
+        let mut variables = Map::new();
+        variables.insert("baseUrl".to_owned(), Value::String(base_url));
+        import.envs.push((slug(title, "openapi"), variables));
+    }
+
+    let paths = spec["paths"]
+        .as_object()
+        .ok_or_else(|| anyhow!("The spec has no paths."))?;
+    for (path, item) in paths {
+        let item = resolve(spec, item);
+        for method in METHODS {
+            let operation = &item[method];
+            if !operation.is_object() {
+                continue;
+            }
+            let folder = match operation["tags"][0].as_str() {
+                Some(tag) => format!("{}/", slug(tag, "default")),
+                None => String::new(),
+            };
+            let name = match operation["operationId"].as_str() {
+                Some(id) => slug(id, "operation"),
+                None => slug(&format!("{} {}", method, path), "operation"),
+            };
+            let fstr = convert_operation(spec, method, path, item, operation);
+            import.add_request(&format!("{}{}", folder, name), fstr);
+        }
+    }
+    Ok(import)
+}
+
+fn convert_operation(
+    spec: &Value,
+    method: &str,
+    path: &str,
+    item: &Value,
+    operation: &Value,
+) -> String {
+    // Parameters of the operation replace those of the path with the same name and location.
+    let mut parameters: Vec<&Value> = vec![];
+    for param in item["parameters"]
+        .as_array()
+        .into_iter()
+        .chain(operation["parameters"].as_array())
+        .flatten()
+    {
+        let param = resolve(spec, param);
+        parameters.retain(|p| !(p["name"] == param["name"] && p["in"] == param["in"]));
+        parameters.push(param);
+    }
+
+    let url_path = path.replace('{', "{{ ").replace('}', " }}");
+    let mut lines = vec![format!(
+        "{} {{{{ baseUrl }}}}{}",
+        method.to_uppercase(),
+        url_path
+    )];
+    let mut query = vec![];
+    let mut headers = vec![];
+    for param in parameters.iter() {
+        let name = param["name"].as_str().unwrap_or_default();
+        let value = match (
+            param["required"].as_bool() == Some(true),
+            param_example(spec, param),
+        ) {
+            (true, _) => format!("{{{{ {} }}}}", name),
+            (false, Some(example)) => example,
+            (false, None) => continue,
+        };
+        match param["in"].as_str() {
+            Some("query") => query.push(format!("{}={}", name, value)),
+            Some("header") => headers.push(format!("{}: {}", name, value)),
+            _ => {}
+        }
+    }
+    for (i, param) in query.iter().enumerate() {
+        lines.push(format!("{}{}", if i == 0 { '?' } else { '&' }, param));
+    }
+    lines.extend(headers);
+
+    let mut directives = vec![];
+    let body = request_body(
+        spec,
+        resolve(spec, &operation["requestBody"]),
+        &mut directives,
+        &mut lines,
+    );
+    directives.extend(lines);
+    match body {
+        Some(body) => format!("{}\n\n{}\n", directives.join("\n"), body),
+        None => format!("{}\n", directives.join("\n")),
+    }
+}
+
+/// An example body for the first media type reqq can send, adding the directives or headers it
+/// needs.
+fn request_body(
+    spec: &Value,
+    request_body: &Value,
+    directives: &mut Vec<String>,
+    lines: &mut Vec<String>,
+) -> Option<String> {
+    let content = request_body["content"].as_object()?;
+    if let Some((media_type, media)) = content
+        .iter()
+        .find(|(t, _)| *t == "application/json" || t.ends_with("+json"))
+    {
+        lines.push(format!("Content-Type: {}", media_type));
+        return serde_json::to_string_pretty(&media_example(spec, media)).ok();
+    }
+
+    let fields = |media: &Value| -> Vec<(String, Value)> {
+        match media_example(spec, media) {
+            Value::Object(fields) => fields.into_iter().collect(),
+            _ => vec![],
+        }
+    };
+    if let Some(media) = content.get("application/x-www-form-urlencoded") {
+        directives.push("@form".to_owned());
+        let lines: Vec<String> = fields(media)
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, scalar(v)))
+            .collect();
+        return Some(lines.join("\n"));
+    }
+    if let Some(media) = content.get("multipart/form-data") {
+        directives.push("@multipart".to_owned());
+        let properties = &resolve(spec, &media["schema"])["properties"];
+        let lines: Vec<String> = fields(media)
+            .iter()
+            .map(
+                |(k, v)| match resolve(spec, &properties[k])["format"].as_str() {
+                    Some("binary" | "byte") => format!("{}=@files/{}", k, k),
+                    _ => format!("{}={}", k, scalar(v)),
+                },
+            )
+            .collect();
+        return Some(lines.join("\n"));
+    }
+
+    let (media_type, media) = content.iter().next()?;
+    lines.push(format!("Content-Type: {}", media_type));
+    Some(scalar(&media_example(spec, media)))
+}
+
+fn media_example(spec: &Value, media: &Value) -> Value {
+    if !media["example"].is_null() {
+        return media["example"].clone();
+    }
+    if let Some((_, example)) = media["examples"]
+        .as_object()
+        .and_then(|examples| examples.iter().next())
+    {
+        return resolve(spec, example)["value"].clone();
+    }
+    schema_example(spec, &media["schema"], 0)
+}
+
+fn param_example(spec: &Value, param: &Value) -> Option<String> {
+    let example = match &param["example"] {
+        Value::Null => &resolve(spec, &param["schema"])["example"],
+        example => example,
+    };
+    (!example.is_null()).then(|| scalar(example))
+}
+
+/// Builds an example value from a schema, from its examples, defaults and types.
+fn schema_example(spec: &Value, schema: &Value, depth: usize) -> Value {
+    let schema = resolve(spec, schema);
+    if depth > MAX_DEPTH {
+        return Value::Null;
+    }
+    for key in ["example", "default"] {
+        if !schema[key].is_null() {
+            return schema[key].clone();
+        }
+    }
+    if let Some(first) = schema["enum"].get(0) {
+        return first.clone();
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(first) = schema[key].get(0) {
+            return schema_example(spec, first, depth + 1);
+        }
+    }
+    if let Some(all) = schema["allOf"].as_array() {
+        let mut merged = Map::new();
+        for part in all {
+            if let Value::Object(fields) = schema_example(spec, part, depth + 1) {
+                merged.extend(fields);
+            }
+        }
+        return Value::Object(merged);
+    }
+
+    match schema["type"].as_str() {
+        Some("array") => json!([schema_example(spec, &schema["items"], depth + 1)]),
+        Some("string") => Value::String(
+            match schema["format"].as_str() {
+                Some("date-time") => "2024-01-01T00:00:00Z",
+                Some("date") => "2024-01-01",
+                Some("email") => "user@example.com",
+                Some("uuid") => "00000000-0000-0000-0000-000000000000",
+                Some("uri" | "url") => "https://example.com",
+                _ => "string",
+            }
+            .to_owned(),
+        ),
+        Some("integer" | "number") => json!(0),
+        Some("boolean") => json!(false),
+        Some("object") | None if schema["properties"].is_object() => Value::Object(
+            schema["properties"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(name, property)| (name.clone(), schema_example(spec, property, depth + 1)))
+                .collect(),
+        ),
+        Some("object") => json!({}),
+        _ => Value::Null,
+    }
+}
+
+/// Follows a local `$ref`, like `#/components/schemas/Pet`. Other values are returned as they
+/// are, and so are refs that can't be followed.
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    let mut value = value;
+    // Refs can point at refs, but not forever.
+    for _ in 0..MAX_DEPTH {
+        match value["$ref"].as_str().and_then(|r| r.strip_prefix('#')) {
+            Some(pointer) => match spec.pointer(pointer) {
+                Some(target) => value = target,
+                None => break,
+            },
+            None => break,
+        }
+    }
+    value
+}
+
+/// The URL of a server, with its variables set to their defaults.
+fn server_url(server: &Value) -> Option<String> {
+    let mut url = server["url"].as_str()?.to_owned();
+    for (name, variable) in server["variables"].as_object().into_iter().flatten() {
+        url = url.replace(&format!("{{{}}}", name), &scalar(&variable["default"]));
+    }
+    Some(url.trim_end_matches('/').to_owned())
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+fn petstore() -> Value {
+    crate::yaml::parse(
+        r#"
+openapi: 3.0.0
+info:
+  title: Swagger Petstore
+servers:
+  - url: "https://{env}.example.com/v1/"
+    variables:
+      env:
+        default: petstore
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      tags: [pets]
+      parameters:
+        - name: limit
+          in: query
+          schema: { type: integer, example: 20 }
+        - name: owner
+          in: query
+          required: true
+          schema: { type: string }
+        - name: cursor
+          in: query
+          schema: { type: string }
+    post:
+      operationId: createPets
+      tags: [pets]
+      requestBody:
+        content:
+          application/json:
+            schema:
+              $ref: '#/components/schemas/Pet'
+  /pets/{petId}:
+    parameters:
+      - $ref: '#/components/parameters/PetId'
+    get:
+      tags: [pets]
+      parameters:
+        - name: X-Request-Id
+          in: header
+          required: true
+          schema: { type: string }
+    put:
+      operationId: uploadPhoto
+      tags: [photos]
+      requestBody:
+        content:
+          multipart/form-data:
+            schema:
+              type: object
+              properties:
+                caption: { type: string, example: Sleepy }
+                photo: { type: string, format: binary }
+components:
+  parameters:
+    PetId:
+      name: petId
+      in: path
+      required: true
+      schema: { type: string }
+  schemas:
+    Pet:
+      type: object
+      properties:
+        id: { type: integer, format: int64 }
+        name: { type: string, example: doggie }
+        tags:
+          type: array
+          items: { type: string }
+        status: { type: string, enum: [available, sold] }
+        owner:
+          $ref: '#/components/schemas/Pet'
+"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_convert_openapi() {
+    let import = convert(&petstore()).unwrap();
+    assert_eq!(import.envs[0].0, "swagger-petstore");
+    assert_eq!(
+        import.envs[0].1["baseUrl"],
+        "https://petstore.example.com/v1"
+    );
+
+    let requests: Vec<(&str, &str)> = import
+        .requests
+        .iter()
+        .map(|(n, f)| (n.as_str(), f.as_str()))
+        .collect();
+    assert_eq!(
+        requests[0],
+        (
+            "pets/list-pets",
+            "GET {{ baseUrl }}/pets\n?limit=20\n&owner={{ owner }}\n"
+        )
+    );
+    assert_eq!(
+        requests[2],
+        (
+            "pets/get-pets-pet-id",
+            "GET {{ baseUrl }}/pets/{{ petId }}\nX-Request-Id: {{ X-Request-Id }}\n"
+        )
+    );
+    assert_eq!(
+        requests[3],
+        ("photos/upload-photo", "@multipart\nPUT {{ baseUrl }}/pets/{{ petId }}\n\ncaption=Sleepy\nphoto=@files/photo\n")
+    );
+
+    let (name, fstr) = requests[1];
+    assert_eq!(name, "pets/create-pets");
+    let (head, body) = fstr.split_once("\n\n").unwrap();
+    assert_eq!(
+        head,
+        "POST {{ baseUrl }}/pets\nContent-Type: application/json"
+    );
+    let body: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(body["name"], "doggie");
+    assert_eq!(body["status"], "available");
+    assert_eq!(body["tags"], json!(["string"]));
+    // The recursive `owner` stops nesting eventually.
+    assert_eq!(body["owner"]["owner"]["owner"]["id"], 0);
+}
+
+#[test]
+fn test_convert_not_openapi() {
+    assert!(convert(&json!({ "swagger": "2.0", "paths": {} })).is_err());
+    assert!(convert(&json!({ "hello": "world" })).is_err());
+}
@@ -0,0 +1,149 @@
+//! Paging long responses. Output for a terminal that it doesn't fit on goes through `$PAGER`,
+//! like `less`, or a built-in pager when that isn't set. Setting `PAGER` to `cat` or nothing
+//! turns paging off, as for git.
+
+use anyhow::Result;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// Prints text and a newline, through a pager when `paging` is set, stdout is a terminal and
+/// the text doesn't fit on it. Output cut off by a closed pipe, like with `reqq users | head`,
+/// or by quitting the pager isn't an error.
+pub fn print(text: &str, paging: bool) -> Result<()> {
+    let size = terminal_size().filter(|_| paging && io::stdout().is_terminal());
+    match size {
+        Some((rows, columns)) if !fits(text, rows, columns) => page(text),
+        _ => ignore_broken_pipe(write_out(&mut io::stdout().lock(), text)),
+    }
+}
+
+fn page(text: &str) -> Result<()> {
+    let command = std::env::var("PAGER").ok();
+    let mut words = command.as_deref().unwrap_or_default().split_whitespace();
+    match (command.as_deref(), words.next()) {
+        (None, _) if io::stdin().is_terminal() => built_in(text),
+        (Some(_), Some(program)) if program != "cat" => {
+            let mut pager = Command::new(program);
+            pager.args(words).stdin(Stdio::piped());
+            // Like git, `less` shows colors, and quits without clearing the screen.
+            if std::env::var_os("LESS").is_none() {
+                pager.env("LESS", "FRX");
+            }
+            // When the pager can't be run, the text is printed without it.
+            let Ok(mut child) = pager.spawn() else {
+                return ignore_broken_pipe(write_out(&mut io::stdout().lock(), text));
+            };
+            if let Some(mut stdin) = child.stdin.take() {
+                ignore_broken_pipe(write_out(&mut stdin, text))?;
+            }
+            child.wait()?;
+            Ok(())
+        }
+        _ => ignore_broken_pipe(write_out(&mut io::stdout().lock(), text)),
+    }
+}
+
+#[cfg(unix)]
+fn built_in(text: &str) -> Result<()> {
+    crate::tui::page(text)
+}
+
+#[cfg(not(unix))]
+fn built_in(text: &str) -> Result<()> {
+    ignore_broken_pipe(write_out(&mut io::stdout().lock(), text))
+}
+
+fn write_out(out: &mut impl Write, text: &str) -> io::Result<()> {
+    writeln!(out, "{}", text)?;
+    out.flush()
+}
+
+fn ignore_broken_pipe(result: io::Result<()>) -> Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// Whether text fits on a terminal with a row to spare, counting the rows long lines wrap
+/// onto. ANSI escape codes take up no room.
+fn fits(text: &str, rows: usize, columns: usize) -> bool {
+    let mut needed = 0;
+    for line in text.lines() {
+        let width = visible_width(line);
+        needed += width.div_ceil(columns.max(1)).max(1);
+        if needed >= rows {
+            return false;
+        }
+    }
+    true
+}
+
+/// Where `visible_width` is in an escape sequence.
+#[derive(Clone, Copy)]
+enum Escape {
+    /// Just after the ESC.
+    Start,
+    /// A color or cursor sequence, which ends with a letter.
+    Control,
+    /// An OSC or APC sequence, like those images are shown with, which ends with BEL or `ESC \`.
+    Command,
+    /// An ESC inside a command, ending it.
+    CommandEnd,
+}
+
+/// The columns a line takes up, skipping escape sequences.
+fn visible_width(line: &str) -> usize {
+    let mut width = 0;
+    let mut escape = None;
+    for c in line.chars() {
+        escape = match (escape, c) {
+            (None, '\x1b') => Some(Escape::Start),
+            (None, _) => {
+                width += 1;
+                None
+            }
+            (Some(Escape::Start), ']' | '_') => Some(Escape::Command),
+            (Some(Escape::Command), '\x07') | (Some(Escape::CommandEnd), _) => None,
+            (Some(Escape::Command), '\x1b') => Some(Escape::CommandEnd),
+            (Some(Escape::Command), _) => Some(Escape::Command),
+            (Some(_), c) if c.is_ascii_alphabetic() => None,
+            (Some(_), _) => Some(Escape::Control),
+        };
+    }
+    width
+}
+
+/// The number of rows and columns of the terminal stdout is, if it is one.
+#[cfg(unix)]
+pub fn terminal_size() -> Option<(usize, usize)> {
+    // SAFETY: `winsize` is plain data, only read if `ioctl` filled it in.
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_row > 0 {
+            return Some((size.ws_row as usize, size.ws_col as usize));
+        }
+    }
+    None
+}
+
+/// The number of rows and columns of the terminal, from `LINES` and `COLUMNS`.
+#[cfg(not(unix))]
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let var = |name| std::env::var(name).ok()?.parse().ok();
+    Some((var("LINES")?, var("COLUMNS")?))
+}
+
+#[test]
+fn test_pager_fits() {
+    assert!(fits("a\nb\nc", 4, 80));
+    assert!(!fits("a\nb\nc", 3, 80));
+    // Long lines wrap, and colors take no room.
+    assert!(!fits(&"x".repeat(81), 2, 80));
+    assert!(fits(&format!("\x1b[32m{}\x1b[0m", "x".repeat(80)), 2, 80));
+    assert_eq!(visible_width("\x1b[34m\"id\"\x1b[0m: 1"), 7);
+    assert_eq!(
+        visible_width("\x1b_Ga=T,f=100;iVBORw0K\x1b\\\x1b]1337;File=inline=1:aGk=\x07ok"),
+        2
+    );
+}
@@ -0,0 +1,194 @@
+//! `--paginate`: requests the pages of a paginated API one after another, following the
+//! `Link: <...>; rel="next"` header, a next page URL in the body, or a cursor in the body
+//! that's sent back as a query param.
+
+use crate::{jsonpath, response::Response};
+use serde_json::Value;
+use std::str::FromStr;
+use url::Url;
+
+/// How the URL of the next page is found in a response.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pagination {
+    /// The `rel="next"` URL of the `Link` header, as in RFC 8288.
+    Link,
+    /// A JSONPath to the next page's URL in the body, which may be relative to the page's.
+    Url(String),
+    /// A JSONPath to a cursor in the body, sent as this query param for the next page.
+    Cursor { param: String, path: String },
+}
+
+impl FromStr for Pagination {
+    type Err = String;
+
+    /// Parses `link`, a JSONPath like `$.next`, or a query param and a JSONPath like
+    /// `cursor=$.meta.next_cursor`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid pagination '{}', expected link, a JSONPath, or <param>=<JSONPath>",
+                s
+            )
+        };
+        let check = |path: &str| {
+            jsonpath::query(path, &Value::Null)
+                .map(drop)
+                .map_err(|e| format!("{}: {}", invalid(), e))
+        };
+        match s.split_once('=') {
+            _ if s == "link" => Ok(Pagination::Link),
+            _ if s.starts_with('$') => check(s).map(|_| Pagination::Url(s.to_owned())),
+            Some((param, path)) if !param.is_empty() && path.starts_with('$') => {
+                check(path).map(|_| Pagination::Cursor {
+                    param: param.to_owned(),
+                    path: path.to_owned(),
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Pagination {
+    /// The URL of the page after the one at `url`, if there is one. A missing, `null` or empty
+    /// next URL or cursor means it was the last page.
+    pub fn next_url(&self, url: &Url, resp: &Response) -> Option<Url> {
+        match self {
+            Pagination::Link => {
+                let links = resp
+                    .headers
+                    .get_all("link")
+                    .iter()
+                    .filter_map(|v| v.to_str().ok());
+                links
+                    .filter_map(next_link)
+                    .find_map(|link| url.join(&link).ok())
+            }
+            Pagination::Url(path) => url.join(&body_value(path, resp)?).ok(),
+            Pagination::Cursor { param, path } => {
+                let cursor = body_value(path, resp)?;
+                let mut next = url.clone();
+                let pairs: Vec<(String, String)> = url
+                    .query_pairs()
+                    .filter(|(name, _)| name != param)
+                    .map(|(name, value)| (name.into_owned(), value.into_owned()))
+                    .collect();
+                next.query_pairs_mut()
+                    .clear()
+                    .extend_pairs(pairs)
+                    .append_pair(param, &cursor);
+                Some(next)
+            }
+        }
+    }
+}
+
+/// A string or number in the JSON body, unless it's empty.
+fn body_value(path: &str, resp: &Response) -> Option<String> {
+    let body: Value = serde_json::from_str(&resp.body).ok()?;
+    match jsonpath::extract(path, &body).ok()? {
+        Value::String(s) if !s.is_empty() => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// The target of the link with `rel="next"` in a `Link` header value, like
+/// `<https://api.example.com/items?page=2>; rel="next", <...>; rel="last"`.
+fn next_link(header: &str) -> Option<String> {
+    let mut rest = header;
+    while let Some(start) = rest.find('<') {
+        let end = start + rest[start..].find('>')?;
+        let target = &rest[start + 1..end];
+        let params = &rest[end + 1..];
+        let params = params[..params.find('<').unwrap_or(params.len())]
+            .trim_end()
+            .trim_end_matches(',');
+        let is_next = params.split(';').any(|param| match param.split_once('=') {
+            Some((name, value)) if name.trim().eq_ignore_ascii_case("rel") => value
+                .trim()
+                .trim_matches('"')
+                .split_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("next")),
+            _ => false,
+        });
+        if is_next {
+            return Some(target.to_owned());
+        }
+        rest = &rest[end + 1..];
+    }
+    None
+}
+
+#[test]
+fn test_pagination_parse() {
+    assert_eq!("link".parse(), Ok(Pagination::Link));
+    assert_eq!(
+        "$.links.next".parse(),
+        Ok(Pagination::Url("$.links.next".to_owned()))
+    );
+    assert_eq!(
+        "cursor=$.meta.next_cursor".parse(),
+        Ok(Pagination::Cursor {
+            param: "cursor".to_owned(),
+            path: "$.meta.next_cursor".to_owned()
+        })
+    );
+    assert!("next".parse::<Pagination>().is_err());
+    assert!("=$.next".parse::<Pagination>().is_err());
+}
+
+#[test]
+fn test_pagination_next_url() {
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::{StatusCode, Version};
+
+    let response = |link: Option<&str>, body: &str| {
+        let mut headers = HeaderMap::new();
+        if let Some(link) = link {
+            headers.insert("link", HeaderValue::from_str(link).unwrap());
+        }
+        Response {
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers,
+            body: body.to_owned(),
+            binary: None,
+        }
+    };
+    let url = Url::parse("https://api.example.com/items?limit=2&cursor=a").unwrap();
+
+    let link = r#"<https://api.example.com/items?page=1>; rel="prev first", </items?page=3>; rel="next", </items?page=9>; rel=last"#;
+    let next = Pagination::Link.next_url(&url, &response(Some(link), ""));
+    assert_eq!(
+        next.unwrap().as_str(),
+        "https://api.example.com/items?page=3"
+    );
+    let last = r#"<https://api.example.com/items?page=1>; rel="first""#;
+    assert_eq!(
+        Pagination::Link.next_url(&url, &response(Some(last), "")),
+        None
+    );
+
+    let pagination = Pagination::Url("$.next".to_owned());
+    let next = pagination.next_url(&url, &response(None, r#"{"next": "/items?offset=4"}"#));
+    assert_eq!(
+        next.unwrap().as_str(),
+        "https://api.example.com/items?offset=4"
+    );
+    assert_eq!(
+        pagination.next_url(&url, &response(None, r#"{"next": null}"#)),
+        None
+    );
+
+    let pagination: Pagination = "cursor=$.meta.cursor".parse().unwrap();
+    let next = pagination.next_url(&url, &response(None, r#"{"meta": {"cursor": "b c"}}"#));
+    assert_eq!(
+        next.unwrap().as_str(),
+        "https://api.example.com/items?limit=2&cursor=b+c"
+    );
+    assert_eq!(
+        pagination.next_url(&url, &response(None, r#"{"meta": {"cursor": ""}}"#)),
+        None
+    );
+}
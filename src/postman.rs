@@ -0,0 +1,415 @@
+//! Converting Postman v2.0 and v2.1 exports into request files and environments.
+
+use crate::{
+    auth,
+    import::{slug, Import},
+};
+use anyhow::{anyhow, Result};
+use regex::{Captures, Regex};
+use serde_json::{json, Map, Value};
+
+/// Converts a Postman collection, with its folders becoming folders of requests and its
+/// variables an environment named after it, or a Postman environment.
+pub fn convert(export: &Value) -> Result<Import> {
+    let mut import = Import::default();
+    if let Some(items) = export["item"].as_array() {
+        let name = export["info"]["name"].as_str().unwrap_or("postman");
+        let variables = variables(&export["variable"]);
+        if !variables.is_empty() {
+            import.envs.push((slug(name, "postman"), variables));
+        }
+        convert_items(items, "", &export["auth"], &mut import)?;
+    } else if let (Some(name), Some(_)) = (export["name"].as_str(), export["values"].as_array()) {
+        import
+            .envs
+            .push((slug(name, "postman"), variables(&export["values"])));
+    } else {
+        return Err(anyhow!(
+            "Expected a Postman collection or environment export."
+        ));
+    }
+    Ok(import)
+}
+
+fn convert_items(
+    items: &[Value],
+    folder: &str,
+    inherited_auth: &Value,
+    import: &mut Import,
+) -> Result<()> {
+    for item in items {
+        let name = format!(
+            "{}{}",
+            folder,
+            slug(item["name"].as_str().unwrap_or_default(), "request")
+        );
+        let auth = if item["auth"].is_object() {
+            &item["auth"]
+        } else {
+            inherited_auth
+        };
+        if item["event"]
+            .as_array()
+            .is_some_and(|events| !events.is_empty())
+        {
+            import.warnings.push(format!(
+                "The scripts of '{}' aren't supported, so they were left out.",
+                name
+            ));
+        }
+
+        match (&item["item"], &item["request"]) {
+            (Value::Array(children), _) => {
+                convert_items(children, &format!("{}/", name), auth, import)?
+            }
+            (_, Value::Null) => import
+                .warnings
+                .push(format!("'{}' has no request, so it was skipped.", name)),
+            (_, request) => {
+                let request_auth = if request["auth"].is_object() {
+                    &request["auth"]
+                } else {
+                    auth
+                };
+                let fstr = convert_request(&name, request, request_auth, &mut import.warnings)?;
+                import.add_request(&name, fstr);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn convert_request(
+    name: &str,
+    request: &Value,
+    auth: &Value,
+    warnings: &mut Vec<String>,
+) -> Result<String> {
+    // A request can be given as just its URL.
+    let request = match request {
+        Value::String(url) => &json!({ "url": url }),
+        request => request,
+    };
+
+    let mut directives = vec![];
+    if let Some(mapping) = convert_auth(name, auth, warnings) {
+        directives.push(format!("@auth {}", auth::directive(&mapping)));
+    }
+
+    let method = request["method"].as_str().unwrap_or("GET").to_uppercase();
+    let url = convert_url(&request["url"]).ok_or_else(|| anyhow!("'{}' has no URL.", name))?;
+    let mut lines = vec![format!("{} {}", method, url)];
+    let mut has_content_type = false;
+    for header in enabled(&request["header"]) {
+        let (key, value) = (string(&header["key"]), string(&header["value"]));
+        if key.is_empty() || value.is_empty() {
+            continue;
+        }
+        has_content_type |= key.eq_ignore_ascii_case("content-type");
+        lines.push(format!("{}: {}", key, value));
+    }
+
+    let body = &request["body"];
+    let body = match body["mode"].as_str() {
+        _ if body["disabled"].as_bool() == Some(true) => None,
+        None => None,
+        Some("raw") => {
+            let content_type = match body["options"]["raw"]["language"].as_str() {
+                Some("json") => Some("application/json"),
+                Some("xml") => Some("application/xml"),
+                Some("html") => Some("text/html"),
+                Some("javascript") => Some("application/javascript"),
+                _ => None,
+            };
+            if let (Some(content_type), false) = (content_type, has_content_type) {
+                lines.push(format!("Content-Type: {}", content_type));
+            }
+            Some(string(&body["raw"])).filter(|raw| !raw.is_empty())
+        }
+        Some("urlencoded") => {
+            directives.push("@form".to_owned());
+            let fields: Vec<String> = enabled(&body["urlencoded"])
+                .map(|field| format!("{}={}", string(&field["key"]), string(&field["value"])))
+                .collect();
+            Some(fields.join("\n"))
+        }
+        Some("formdata") => {
+            directives.push("@multipart".to_owned());
+            let fields: Vec<String> = enabled(&body["formdata"])
+                .map(|field| match field["type"].as_str() {
+                    Some("file") => {
+                        let src = match &field["src"] {
+                            Value::Array(srcs) => srcs.first().map(string).unwrap_or_default(),
+                            src => string(src),
+                        };
+                        format!("{}=@{}", string(&field["key"]), src)
+                    }
+                    _ => format!("{}={}", string(&field["key"]), string(&field["value"])),
+                })
+                .collect();
+            Some(fields.join("\n"))
+        }
+        Some("graphql") => {
+            directives.push("@graphql".to_owned());
+            let query = string(&body["graphql"]["query"]);
+            match string(&body["graphql"]["variables"]).trim() {
+                "" => Some(query),
+                variables => Some(format!("{}\n\n{}", query.trim_end(), variables)),
+            }
+        }
+        Some("file") => {
+            directives.push(format!("@body-file {}", string(&body["file"]["src"])));
+            None
+        }
+        Some(mode) => {
+            warnings.push(format!(
+                "The '{}' body of '{}' isn't supported, so it was left out.",
+                mode, name
+            ));
+            None
+        }
+    };
+
+    directives.extend(lines);
+    Ok(match body {
+        Some(body) => format!("{}\n\n{}", directives.join("\n"), body),
+        None => format!("{}\n", directives.join("\n")),
+    })
+}
+
+/// Converts Postman auth to an auth mapping, or `None` when there's nothing to send.
+fn convert_auth(name: &str, auth: &Value, warnings: &mut Vec<String>) -> Option<Value> {
+    let kind = auth["type"].as_str()?;
+    let param = |key: &str| -> String {
+        match &auth[kind] {
+            // v2.1 lists `{key, value}` params, v2.0 has a plain object.
+            Value::Array(params) => params
+                .iter()
+                .find(|p| p["key"] == key)
+                .map(|p| string(&p["value"]))
+                .unwrap_or_default(),
+            params => string(&params[key]),
+        }
+    };
+
+    match kind {
+        "noauth" => None,
+        "basic" => {
+            Some(json!({ "type": "basic", "user": param("username"), "pass": param("password") }))
+        }
+        "bearer" => Some(json!({ "type": "bearer", "token": param("token") })),
+        "apikey" => {
+            let location = if param("in") == "query" {
+                "query"
+            } else {
+                "header"
+            };
+            Some(json!({
+                "type": "apikey",
+                "name": param("key"),
+                "value": param("value"),
+                "in": location,
+            }))
+        }
+        "awsv4" => Some(json!({
+            "type": "aws",
+            "access_key": param("accessKey"),
+            "secret_key": param("secretKey"),
+            "session_token": param("sessionToken"),
+            "region": param("region"),
+            "service": param("service"),
+        })),
+        other => {
+            warnings.push(format!(
+                "The '{}' auth of '{}' isn't supported, so it was left out.",
+                other, name
+            ));
+            None
+        }
+    }
+}
+
+/// The URL of a request, with `:name` path variables filled in, or left as `{{ name }}`
+/// templates when they have no value.
+fn convert_url(url: &Value) -> Option<String> {
+    let raw = match url {
+        Value::String(raw) => return Some(raw.clone()),
+        url => url["raw"].as_str()?,
+    };
+    let path_variables = variables(&url["variable"]);
+    let re = Regex::new(r"/:([A-Za-z0-9_]+)").unwrap();
+    let url = re.replace_all(raw, |caps: &Captures| {
+        match path_variables.get(&caps[1]).map(string) {
+            Some(value) if !value.is_empty() => format!("/{}", value),
+            _ => format!("/{{{{ {} }}}}", &caps[1]),
+        }
+    });
+    Some(url.into_owned())
+}
+
+/// Reads Postman `{key, value}` variables, skipping disabled ones.
+fn variables(list: &Value) -> Map<String, Value> {
+    enabled(list)
+        .filter_map(|v| Some((v["key"].as_str()?.to_owned(), v["value"].clone())))
+        .collect()
+}
+
+fn enabled(list: &Value) -> impl Iterator<Item = &Value> {
+    list.as_array()
+        .into_iter()
+        .flatten()
+        .filter(|v| v["disabled"].as_bool() != Some(true) && v["enabled"].as_bool() != Some(false))
+}
+
+fn string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[test]
+fn test_convert_collection() {
+    let collection = json!({
+        "info": { "name": "Users API", "schema": "https://schema.getpostman.com/json/collection/v2.1.0/collection.json" },
+        "auth": { "type": "bearer", "bearer": [{ "key": "token", "value": "{{token}}", "type": "string" }] },
+        "variable": [
+            { "key": "baseUrl", "value": "https://example.com" },
+            { "key": "old", "value": "x", "disabled": true }
+        ],
+        "item": [
+            {
+                "name": "Users",
+                "item": [
+                    {
+                        "name": "Get User",
+                        "request": {
+                            "method": "GET",
+                            "header": [
+                                { "key": "Accept", "value": "application/json" },
+                                { "key": "X-Debug", "value": "1", "disabled": true }
+                            ],
+                            "url": { "raw": "{{baseUrl}}/users/:id?full=true", "variable": [{ "key": "id", "value": "" }] }
+                        }
+                    },
+                    {
+                        "name": "Create User",
+                        "request": {
+                            "auth": { "type": "basic", "basic": [
+                                { "key": "username", "value": "admin" },
+                                { "key": "password", "value": "secret" }
+                            ] },
+                            "method": "POST",
+                            "url": "{{baseUrl}}/users",
+                            "body": { "mode": "raw", "raw": "{\"name\": \"Ada\"}", "options": { "raw": { "language": "json" } } }
+                        }
+                    }
+                ]
+            },
+            {
+                "name": "Login",
+                "request": {
+                    "auth": { "type": "noauth" },
+                    "method": "POST",
+                    "url": "{{baseUrl}}/login",
+                    "body": { "mode": "urlencoded", "urlencoded": [
+                        { "key": "user", "value": "ada" },
+                        { "key": "pass", "value": "{{pass}}" }
+                    ] }
+                }
+            },
+            { "name": "Login", "request": "{{baseUrl}}/login" }
+        ]
+    });
+
+    let import = convert(&collection).unwrap();
+    assert_eq!(
+        import.envs,
+        vec![(
+            "users-api".to_owned(),
+            json!({ "baseUrl": "https://example.com" })
+                .as_object()
+                .unwrap()
+                .clone()
+        )]
+    );
+    assert_eq!(
+        import.requests,
+        vec![
+            (
+                "users/get-user".to_owned(),
+                "@auth bearer {{token}}\nGET {{baseUrl}}/users/{{ id }}?full=true\nAccept: application/json\n".to_owned()
+            ),
+            (
+                "users/create-user".to_owned(),
+                "@auth basic admin secret\nPOST {{baseUrl}}/users\nContent-Type: application/json\n\n{\"name\": \"Ada\"}".to_owned()
+            ),
+            ("login".to_owned(), "@form\nPOST {{baseUrl}}/login\n\nuser=ada\npass={{pass}}".to_owned()),
+            ("login-2".to_owned(), "@auth bearer {{token}}\nGET {{baseUrl}}/login\n".to_owned()),
+        ]
+    );
+    assert!(import.warnings.is_empty());
+}
+
+#[test]
+fn test_convert_bodies() {
+    let collection = json!({
+        "info": { "name": "Bodies" },
+        "item": [
+            {
+                "name": "Upload",
+                "request": {
+                    "method": "POST",
+                    "url": "https://example.com/upload",
+                    "body": { "mode": "formdata", "formdata": [
+                        { "key": "title", "value": "Me", "type": "text" },
+                        { "key": "avatar", "src": ["/tmp/me.png"], "type": "file" }
+                    ] }
+                }
+            },
+            {
+                "name": "Query",
+                "event": [{ "listen": "test", "script": { "exec": ["pm.test()"] } }],
+                "request": {
+                    "method": "POST",
+                    "url": "https://example.com/graphql",
+                    "auth": { "type": "digest", "digest": [] },
+                    "body": { "mode": "graphql", "graphql": { "query": "query { me { id } }", "variables": "{\"a\": 1}" } }
+                }
+            }
+        ]
+    });
+
+    let import = convert(&collection).unwrap();
+    assert!(import.envs.is_empty());
+    assert_eq!(
+        import.requests[0].1,
+        "@multipart\nPOST https://example.com/upload\n\ntitle=Me\navatar=@/tmp/me.png"
+    );
+    assert_eq!(
+        import.requests[1].1,
+        "@graphql\nPOST https://example.com/graphql\n\nquery { me { id } }\n\n{\"a\": 1}"
+    );
+    assert_eq!(
+        import.warnings,
+        vec![
+            "The scripts of 'query' aren't supported, so they were left out.",
+            "The 'digest' auth of 'query' isn't supported, so it was left out.",
+        ]
+    );
+}
+
+#[test]
+fn test_convert_environment() {
+    let environment = json!({
+        "name": "Staging",
+        "values": [{ "key": "baseUrl", "value": "https://staging.example.com", "enabled": true }]
+    });
+    let import = convert(&environment).unwrap();
+    assert!(import.requests.is_empty());
+    assert_eq!(import.envs[0].0, "staging");
+    assert_eq!(import.envs[0].1["baseUrl"], "https://staging.example.com");
+
+    assert!(convert(&json!({ "hello": "world" })).is_err());
+}
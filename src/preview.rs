@@ -0,0 +1,85 @@
+//! Previews of image responses, shown inline on terminals that can display images: kitty,
+//! through its graphics protocol, and iTerm2 and WezTerm, through iTerm2's. Built with the
+//! `image-preview` feature.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::io::{self, IsTerminal};
+
+/// The largest piece of base64 data kitty takes in one escape sequence.
+const KITTY_CHUNK: usize = 4096;
+
+/// How a terminal is sent an image.
+#[derive(Debug, PartialEq)]
+enum Protocol {
+    Kitty,
+    ITerm,
+}
+
+/// The image protocol of the terminal reqq runs in, from the variables it sets.
+fn protocol() -> Option<Protocol> {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    if std::env::var_os("KITTY_WINDOW_ID").is_some() || var("TERM") == "xterm-kitty" {
+        Some(Protocol::Kitty)
+    } else if matches!(var("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm") {
+        Some(Protocol::ITerm)
+    } else {
+        None
+    }
+}
+
+/// The escape sequences that show an image, when stdout is a terminal that can. kitty is only
+/// sent PNGs, which it reads itself; iTerm2 reads the common formats.
+pub fn image(media_type: &str, body: &[u8]) -> Option<String> {
+    if !media_type.starts_with("image/") || !io::stdout().is_terminal() {
+        return None;
+    }
+    encode(protocol()?, media_type, body)
+}
+
+fn encode(protocol: Protocol, media_type: &str, body: &[u8]) -> Option<String> {
+    let data = STANDARD.encode(body);
+    match protocol {
+        Protocol::Kitty if media_type == "image/png" => {
+            let chunks: Vec<&str> = data
+                .as_bytes()
+                .chunks(KITTY_CHUNK)
+                .map(|c| std::str::from_utf8(c).unwrap_or_default())
+                .collect();
+            let mut out = String::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = u8::from(i + 1 < chunks.len());
+                let keys = if i == 0 {
+                    format!("a=T,f=100,m={}", more)
+                } else {
+                    format!("m={}", more)
+                };
+                out.push_str(&format!("\x1b_G{};{}\x1b\\", keys, chunk));
+            }
+            Some(out)
+        }
+        Protocol::ITerm => Some(format!(
+            "\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:{}\x07",
+            body.len(),
+            data
+        )),
+        Protocol::Kitty => None,
+    }
+}
+
+#[test]
+fn test_preview_encode() {
+    let png = vec![0x89; 4000];
+    let kitty = encode(Protocol::Kitty, "image/png", &png).unwrap();
+    // 4000 bytes are 5336 of base64, sent in two chunks.
+    let chunks: Vec<&str> = kitty.split("\x1b\\").filter(|c| !c.is_empty()).collect();
+    assert_eq!(chunks.len(), 2);
+    assert!(chunks[0].starts_with("\x1b_Ga=T,f=100,m=1;") && chunks[0].len() == 4096 + 17);
+    assert!(chunks[1].starts_with("\x1b_Gm=0;"));
+    assert_eq!(encode(Protocol::Kitty, "image/jpeg", &png), None);
+
+    let iterm = encode(Protocol::ITerm, "image/jpeg", b"jpeg").unwrap();
+    assert_eq!(
+        iterm,
+        "\x1b]1337;File=inline=1;size=4;preserveAspectRatio=1:anBlZw==\x07"
+    );
+}
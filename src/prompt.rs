@@ -0,0 +1,72 @@
+//! Questions asked on the terminal, for values that shouldn't be passed as arguments.
+
+use anyhow::{anyhow, Result};
+
+/// Asks for a value on the terminal, rather than stdin, which may hold a request body.
+#[cfg(unix)]
+pub fn visible(label: &str) -> Result<String> {
+    use std::io::{BufRead, BufReader};
+
+    let tty = open_tty(label)?;
+    let mut line = String::new();
+    BufReader::new(&tty).read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Asks for a value without echoing what's typed, like a password.
+#[cfg(unix)]
+pub fn hidden(label: &str) -> Result<String> {
+    use std::io::{self, BufRead, BufReader};
+    use std::os::unix::io::AsRawFd;
+
+    let tty = open_tty(label)?;
+
+    let fd = tty.as_raw_fd();
+    // SAFETY: `termios` is plain data, filled in by `tcgetattr` before it's read.
+    let original = unsafe {
+        let mut original: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut original) != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let mut hidden = original;
+        hidden.c_lflag &= !libc::ECHO;
+        hidden.c_lflag |= libc::ECHONL;
+        if libc::tcsetattr(fd, libc::TCSAFLUSH, &hidden) != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        original
+    };
+    let mut line = String::new();
+    let read = BufReader::new(&tty).read_line(&mut line);
+    // SAFETY: Restores the settings read above.
+    unsafe {
+        libc::tcsetattr(fd, libc::TCSAFLUSH, &original);
+    }
+    read?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Opens the terminal and writes the label of a question to it.
+#[cfg(unix)]
+fn open_tty(label: &str) -> Result<std::fs::File> {
+    use std::io::Write;
+
+    let mut tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|_| anyhow!("There's no terminal to ask for the {} on.", label))?;
+    write!(tty, "{}: ", label)?;
+    tty.flush()?;
+    Ok(tty)
+}
+
+#[cfg(not(unix))]
+pub fn visible(label: &str) -> Result<String> {
+    Err(anyhow!("There's no terminal to ask for the {} on.", label))
+}
+
+#[cfg(not(unix))]
+pub fn hidden(label: &str) -> Result<String> {
+    Err(anyhow!("There's no terminal to ask for the {} on.", label))
+}
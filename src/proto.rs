@@ -0,0 +1,1114 @@
+//! A reader of `.proto` files, and conversion of protobuf messages to and from JSON, for gRPC
+//! requests. Messages are mapped to JSON the way protobuf's JSON mapping does: fields by their
+//! lowerCamelCase names, 64 bit integers as strings, bytes as Base64 and enums by name.
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The messages, enums and services of a `.proto` file and the files it imports, by their full
+/// names, like `shop.v1.Order`.
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    messages: HashMap<String, Message>,
+    enums: HashMap<String, Vec<(String, i32)>>,
+    services: HashMap<String, HashMap<String, Rpc>>,
+}
+
+/// A method of a service.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rpc {
+    /// The full name of the request message.
+    pub input: String,
+    /// The full name of the response message.
+    pub output: String,
+    /// Whether the client or the server sends a stream of messages.
+    pub streaming: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Message {
+    fields: Vec<Field>,
+}
+
+#[derive(Clone, Debug)]
+struct Field {
+    name: String,
+    number: u64,
+    kind: Kind,
+    repeated: bool,
+}
+
+impl Field {
+    /// The name of the field in JSON, in lowerCamelCase.
+    fn json_name(&self) -> String {
+        let mut out = String::new();
+        let mut upper = false;
+        for c in self.name.chars() {
+            match c {
+                '_' => upper = true,
+                c if upper => {
+                    out.extend(c.to_uppercase());
+                    upper = false;
+                }
+                c => out.push(c),
+            }
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+    Scalar(Scalar),
+    Message(String),
+    Enum(String),
+    Map(Scalar, Box<Kind>),
+    /// A message or enum type as written, with the scope it's looked up from, until the schema
+    /// is loaded. Types that can't be found stay unresolved.
+    Unresolved(String, String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "double" => Scalar::Double,
+            "float" => Scalar::Float,
+            "int32" => Scalar::Int32,
+            "int64" => Scalar::Int64,
+            "uint32" => Scalar::Uint32,
+            "uint64" => Scalar::Uint64,
+            "sint32" => Scalar::Sint32,
+            "sint64" => Scalar::Sint64,
+            "fixed32" => Scalar::Fixed32,
+            "fixed64" => Scalar::Fixed64,
+            "sfixed32" => Scalar::Sfixed32,
+            "sfixed64" => Scalar::Sfixed64,
+            "bool" => Scalar::Bool,
+            "string" => Scalar::String,
+            "bytes" => Scalar::Bytes,
+            _ => return None,
+        })
+    }
+}
+
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LEN: u64 = 2;
+const FIXED32: u64 = 5;
+
+impl Kind {
+    fn wire_type(&self) -> u64 {
+        match self {
+            Kind::Scalar(Scalar::Double | Scalar::Fixed64 | Scalar::Sfixed64) => FIXED64,
+            Kind::Scalar(Scalar::Float | Scalar::Fixed32 | Scalar::Sfixed32) => FIXED32,
+            Kind::Scalar(Scalar::String | Scalar::Bytes)
+            | Kind::Message(_)
+            | Kind::Map(..)
+            | Kind::Unresolved(..) => LEN,
+            Kind::Scalar(_) | Kind::Enum(_) => VARINT,
+        }
+    }
+
+    /// Whether repeated values of the kind are packed together, as proto3 does by default.
+    fn packed(&self) -> bool {
+        matches!(self, Kind::Scalar(_) | Kind::Enum(_)) && self.wire_type() != LEN
+    }
+}
+
+/// A field's value as read off the wire, before its type is applied.
+enum Raw<'a> {
+    Varint(u64),
+    Fixed64([u8; 8]),
+    Len(&'a [u8]),
+    Fixed32([u8; 4]),
+}
+
+impl Schema {
+    /// Reads a `.proto` file and the files it imports, relative to it. Imports of protobuf's
+    /// own `google/protobuf/` files are skipped when they aren't found.
+    pub fn load(path: &Path) -> Result<Schema> {
+        let mut schema = Schema::default();
+        let mut seen = HashSet::new();
+        let dir = path.parent().unwrap_or(Path::new("")).to_owned();
+        schema.add_file(path, &dir, &mut seen)?;
+        schema.resolve();
+        Ok(schema)
+    }
+
+    /// Reads the source of a `.proto` file that doesn't import others.
+    #[cfg(test)]
+    fn parse(source: &str) -> Result<Schema> {
+        let mut schema = Schema::default();
+        Parser::new(source)?.file(&mut schema)?;
+        schema.resolve();
+        Ok(schema)
+    }
+
+    fn add_file(&mut self, path: &Path, root: &Path, seen: &mut HashSet<PathBuf>) -> Result<()> {
+        if !seen.insert(path.to_owned()) {
+            return Ok(());
+        }
+        let source = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Couldn't read {}: {}", path.display(), e))?;
+        let imports = Parser::new(&source)
+            .and_then(|mut parser| parser.file(self))
+            .map_err(|e| anyhow!("Invalid proto file {}: {}", path.display(), e))?;
+        for import in imports {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            match [dir.join(&import), root.join(&import)]
+                .into_iter()
+                .find(|path| path.exists())
+            {
+                Some(found) => self.add_file(&found, root, seen)?,
+                None if import.starts_with("google/protobuf/") => {}
+                None => {
+                    return Err(anyhow!(
+                        "Couldn't find '{}', imported by {}.",
+                        import,
+                        path.display()
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks up the types fields and methods refer to, from the innermost scope outwards.
+    fn resolve(&mut self) {
+        let lookup = |name: &str, scope: &str| -> Option<Kind> {
+            let mut scope = scope.to_owned();
+            loop {
+                let full = match name.strip_prefix('.') {
+                    Some(absolute) => absolute.to_owned(),
+                    None if scope.is_empty() => name.to_owned(),
+                    None => format!("{}.{}", scope, name),
+                };
+                if self.messages.contains_key(&full) {
+                    return Some(Kind::Message(full));
+                }
+                if self.enums.contains_key(&full) {
+                    return Some(Kind::Enum(full));
+                }
+                if scope.is_empty() || name.starts_with('.') {
+                    return None;
+                }
+                scope = scope
+                    .rsplit_once('.')
+                    .map(|(outer, _)| outer.to_owned())
+                    .unwrap_or_default();
+            }
+        };
+        let resolve = |kind: &Kind| -> Kind {
+            match kind {
+                Kind::Unresolved(name, scope) => {
+                    lookup(name, scope).unwrap_or_else(|| kind.clone())
+                }
+                Kind::Map(key, value) => match value.as_ref() {
+                    Kind::Unresolved(name, scope) => Kind::Map(
+                        *key,
+                        Box::new(lookup(name, scope).unwrap_or_else(|| *value.clone())),
+                    ),
+                    _ => kind.clone(),
+                },
+                _ => kind.clone(),
+            }
+        };
+
+        let mut messages = self.messages.clone();
+        for message in messages.values_mut() {
+            for field in message.fields.iter_mut() {
+                field.kind = resolve(&field.kind);
+            }
+        }
+        let mut services = self.services.clone();
+        for rpc in services
+            .values_mut()
+            .flat_map(|methods| methods.values_mut())
+        {
+            for name in [&mut rpc.input, &mut rpc.output] {
+                let (scope, written) = name.split_once(' ').unwrap_or(("", name.as_str()));
+                if let Some(Kind::Message(full)) = lookup(written, scope) {
+                    *name = full;
+                }
+            }
+        }
+        self.messages = messages;
+        self.services = services;
+    }
+
+    /// A method of a service, like `shop.v1.Orders` and `Create`.
+    pub fn rpc(&self, service: &str, method: &str) -> Result<&Rpc> {
+        self.services
+            .get(service)
+            .ok_or_else(|| anyhow!("The proto file has no service '{}'.", service))?
+            .get(method)
+            .ok_or_else(|| anyhow!("The service '{}' has no method '{}'.", service, method))
+    }
+
+    fn message(&self, name: &str) -> Result<&Message> {
+        self.messages.get(name).ok_or_else(|| {
+            anyhow!(
+                "Unknown message type '{}'.",
+                name.rsplit(' ').next().unwrap_or(name)
+            )
+        })
+    }
+
+    /// Encodes a JSON object as a message of the named type.
+    pub fn encode(&self, message: &str, value: &Value) -> Result<Vec<u8>> {
+        let fields = &self.message(message)?.fields;
+        let object = value
+            .as_object()
+            .ok_or_else(|| anyhow!("Expected a JSON object for {}.", message))?;
+        let mut out = vec![];
+        for (key, value) in object {
+            let field = fields
+                .iter()
+                .find(|field| field.name == *key || field.json_name() == *key)
+                .ok_or_else(|| anyhow!("{} has no field '{}'.", message, key))?;
+            if !value.is_null() {
+                self.encode_field(field, value, &mut out)
+                    .map_err(|e| anyhow!("Invalid '{}' in {}: {}", key, message, e))?;
+            }
+        }
+        Ok(out)
+    }
+
+    fn encode_field(&self, field: &Field, value: &Value, out: &mut Vec<u8>) -> Result<()> {
+        match &field.kind {
+            Kind::Map(key, value_kind) => {
+                let entries = value
+                    .as_object()
+                    .ok_or_else(|| anyhow!("expected an object."))?;
+                for (k, v) in entries {
+                    let mut entry = vec![];
+                    self.encode_value(
+                        1,
+                        &Kind::Scalar(*key),
+                        &Value::String(k.clone()),
+                        &mut entry,
+                    )?;
+                    self.encode_value(2, value_kind, v, &mut entry)?;
+                    write_len(field.number, &entry, out);
+                }
+            }
+            kind if field.repeated => {
+                let items = value
+                    .as_array()
+                    .ok_or_else(|| anyhow!("expected an array."))?;
+                if kind.packed() {
+                    let mut packed = vec![];
+                    for item in items {
+                        self.encode_raw(kind, item, &mut packed)?;
+                    }
+                    write_len(field.number, &packed, out);
+                } else {
+                    for item in items {
+                        self.encode_value(field.number, kind, item, out)?;
+                    }
+                }
+            }
+            kind => self.encode_value(field.number, kind, value, out)?,
+        }
+        Ok(())
+    }
+
+    fn encode_value(
+        &self,
+        number: u64,
+        kind: &Kind,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        write_varint(number << 3 | kind.wire_type(), out);
+        self.encode_raw(kind, value, out)
+    }
+
+    /// Encodes a value without its field key.
+    fn encode_raw(&self, kind: &Kind, value: &Value, out: &mut Vec<u8>) -> Result<()> {
+        match kind {
+            Kind::Scalar(scalar) => encode_scalar(*scalar, value, out)?,
+            Kind::Enum(name) => {
+                let number = match value {
+                    Value::String(s) => self.enums[name]
+                        .iter()
+                        .find(|(value_name, _)| value_name == s)
+                        .map(|(_, number)| *number as i128)
+                        .ok_or_else(|| anyhow!("{} has no value '{}'.", name, s))?,
+                    value => integer(value, i32::MIN as i128, i32::MAX as i128)?,
+                };
+                write_varint(number as i64 as u64, out);
+            }
+            Kind::Message(name) => write_bytes(&self.encode(name, value)?, out),
+            Kind::Map(..) => return Err(anyhow!("maps can't be nested.")),
+            Kind::Unresolved(name, _) => return Err(anyhow!("unknown type '{}'.", name)),
+        }
+        Ok(())
+    }
+
+    /// Decodes a message of the named type to a JSON object. Fields the message type doesn't
+    /// have are skipped, and fields that weren't sent are left out.
+    pub fn decode(&self, message: &str, mut bytes: &[u8]) -> Result<Value> {
+        let fields = &self.message(message)?.fields;
+        let mut object = Map::new();
+        while !bytes.is_empty() {
+            let key = read_varint(&mut bytes)?;
+            let raw = read_raw(&mut bytes, key & 7)?;
+            let Some(field) = fields.iter().find(|field| field.number == key >> 3) else {
+                continue;
+            };
+            let name = field.json_name();
+            match &field.kind {
+                Kind::Map(key_kind, value_kind) => {
+                    let Raw::Len(entry) = raw else {
+                        return Err(anyhow!("Invalid map entry for '{}' in {}.", name, message));
+                    };
+                    let (k, v) = self.decode_entry(&Kind::Scalar(*key_kind), value_kind, entry)?;
+                    let entries = object.entry(name).or_insert_with(|| json!({}));
+                    if let Value::Object(entries) = entries {
+                        entries.insert(k, v);
+                    }
+                }
+                kind if field.repeated => {
+                    let mut values = vec![];
+                    match raw {
+                        Raw::Len(mut packed) if kind.packed() => {
+                            while !packed.is_empty() {
+                                values.push(
+                                    self.decode_raw(
+                                        kind,
+                                        read_raw(&mut packed, kind.wire_type())?,
+                                    )?,
+                                );
+                            }
+                        }
+                        raw => values.push(self.decode_raw(kind, raw)?),
+                    }
+                    if let Value::Array(items) = object.entry(name).or_insert_with(|| json!([])) {
+                        items.extend(values);
+                    }
+                }
+                kind => {
+                    object.insert(name, self.decode_raw(kind, raw)?);
+                }
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    /// Decodes the key and value of a map entry, which default like fields when they're missing.
+    fn decode_entry(
+        &self,
+        key_kind: &Kind,
+        value_kind: &Kind,
+        mut entry: &[u8],
+    ) -> Result<(String, Value)> {
+        let (mut key, mut value) = (None, None);
+        while !entry.is_empty() {
+            let tag = read_varint(&mut entry)?;
+            let raw = read_raw(&mut entry, tag & 7)?;
+            match tag >> 3 {
+                1 => key = Some(self.decode_raw(key_kind, raw)?),
+                2 => value = Some(self.decode_raw(value_kind, raw)?),
+                _ => {}
+            }
+        }
+        let key = match key.unwrap_or_else(|| self.default_value(key_kind)) {
+            Value::String(s) => s,
+            key => key.to_string(),
+        };
+        Ok((key, value.unwrap_or_else(|| self.default_value(value_kind))))
+    }
+
+    fn default_value(&self, kind: &Kind) -> Value {
+        match kind {
+            Kind::Scalar(Scalar::String | Scalar::Bytes) => json!(""),
+            Kind::Scalar(
+                Scalar::Int64
+                | Scalar::Uint64
+                | Scalar::Sint64
+                | Scalar::Fixed64
+                | Scalar::Sfixed64,
+            ) => json!("0"),
+            Kind::Scalar(Scalar::Bool) => json!(false),
+            Kind::Scalar(_) => json!(0),
+            Kind::Enum(name) => self.enums[name]
+                .first()
+                .map_or(json!(0), |(value_name, _)| json!(value_name)),
+            Kind::Message(_) | Kind::Map(..) | Kind::Unresolved(..) => json!({}),
+        }
+    }
+
+    fn decode_raw(&self, kind: &Kind, raw: Raw) -> Result<Value> {
+        Ok(match (kind, raw) {
+            (Kind::Scalar(Scalar::Double), Raw::Fixed64(b)) => float(f64::from_le_bytes(b)),
+            (Kind::Scalar(Scalar::Float), Raw::Fixed32(b)) => float(f32::from_le_bytes(b) as f64),
+            (Kind::Scalar(Scalar::Int32), Raw::Varint(v)) => json!(v as i32),
+            (Kind::Scalar(Scalar::Int64), Raw::Varint(v)) => json!((v as i64).to_string()),
+            (Kind::Scalar(Scalar::Uint32), Raw::Varint(v)) => json!(v as u32),
+            (Kind::Scalar(Scalar::Uint64), Raw::Varint(v)) => json!(v.to_string()),
+            (Kind::Scalar(Scalar::Sint32), Raw::Varint(v)) => json!(unzigzag(v) as i32),
+            (Kind::Scalar(Scalar::Sint64), Raw::Varint(v)) => json!(unzigzag(v).to_string()),
+            (Kind::Scalar(Scalar::Fixed32), Raw::Fixed32(b)) => json!(u32::from_le_bytes(b)),
+            (Kind::Scalar(Scalar::Sfixed32), Raw::Fixed32(b)) => json!(i32::from_le_bytes(b)),
+            (Kind::Scalar(Scalar::Fixed64), Raw::Fixed64(b)) => {
+                json!(u64::from_le_bytes(b).to_string())
+            }
+            (Kind::Scalar(Scalar::Sfixed64), Raw::Fixed64(b)) => {
+                json!(i64::from_le_bytes(b).to_string())
+            }
+            (Kind::Scalar(Scalar::Bool), Raw::Varint(v)) => json!(v != 0),
+            (Kind::Scalar(Scalar::String), Raw::Len(b)) => json!(String::from_utf8_lossy(b)),
+            (Kind::Scalar(Scalar::Bytes), Raw::Len(b)) => json!(STANDARD.encode(b)),
+            (Kind::Enum(name), Raw::Varint(v)) => match self.enums[name]
+                .iter()
+                .find(|(_, number)| *number == v as i32)
+            {
+                Some((value_name, _)) => json!(value_name),
+                None => json!(v as i32),
+            },
+            (Kind::Message(name), Raw::Len(b)) => self.decode(name, b)?,
+            (Kind::Unresolved(name, _), _) => return Err(anyhow!("Unknown type '{}'.", name)),
+            _ => {
+                return Err(anyhow!(
+                    "A field was sent with the wrong wire type for its type."
+                ))
+            }
+        })
+    }
+}
+
+fn encode_scalar(scalar: Scalar, value: &Value, out: &mut Vec<u8>) -> Result<()> {
+    match scalar {
+        Scalar::Double => out.extend(number(value)?.to_le_bytes()),
+        Scalar::Float => out.extend((number(value)? as f32).to_le_bytes()),
+        Scalar::Int32 => write_varint(
+            integer(value, i32::MIN as i128, i32::MAX as i128)? as i64 as u64,
+            out,
+        ),
+        Scalar::Int64 => write_varint(
+            integer(value, i64::MIN as i128, i64::MAX as i128)? as i64 as u64,
+            out,
+        ),
+        Scalar::Uint32 => write_varint(integer(value, 0, u32::MAX as i128)? as u64, out),
+        Scalar::Uint64 => write_varint(integer(value, 0, u64::MAX as i128)? as u64, out),
+        Scalar::Sint32 => write_varint(
+            zigzag(integer(value, i32::MIN as i128, i32::MAX as i128)? as i64),
+            out,
+        ),
+        Scalar::Sint64 => write_varint(
+            zigzag(integer(value, i64::MIN as i128, i64::MAX as i128)? as i64),
+            out,
+        ),
+        Scalar::Fixed32 => out.extend((integer(value, 0, u32::MAX as i128)? as u32).to_le_bytes()),
+        Scalar::Fixed64 => out.extend((integer(value, 0, u64::MAX as i128)? as u64).to_le_bytes()),
+        Scalar::Sfixed32 => {
+            out.extend((integer(value, i32::MIN as i128, i32::MAX as i128)? as i32).to_le_bytes())
+        }
+        Scalar::Sfixed64 => {
+            out.extend((integer(value, i64::MIN as i128, i64::MAX as i128)? as i64).to_le_bytes())
+        }
+        Scalar::Bool => match value {
+            Value::Bool(b) => write_varint(*b as u64, out),
+            Value::String(s) if s == "true" || s == "false" => {
+                write_varint((s == "true") as u64, out)
+            }
+            _ => return Err(anyhow!("expected true or false.")),
+        },
+        Scalar::String => match value {
+            Value::String(s) => write_bytes(s.as_bytes(), out),
+            _ => return Err(anyhow!("expected a string.")),
+        },
+        Scalar::Bytes => match value {
+            Value::String(s) => write_bytes(
+                &STANDARD
+                    .decode(s)
+                    .map_err(|_| anyhow!("expected a Base64 string."))?,
+                out,
+            ),
+            _ => return Err(anyhow!("expected a Base64 string.")),
+        },
+    }
+    Ok(())
+}
+
+/// An integer given as a JSON number or a string, as 64 bit integers usually are, within the
+/// range of its type.
+fn integer(value: &Value, min: i128, max: i128) -> Result<i128> {
+    let n = match value {
+        Value::Number(n) if n.is_i64() || n.is_u64() => n.to_string().parse::<i128>()?,
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 => f as i128,
+            _ => return Err(anyhow!("expected an integer, not {}.", n)),
+        },
+        Value::String(s) => s
+            .trim()
+            .parse::<i128>()
+            .map_err(|_| anyhow!("expected an integer, not '{}'.", s))?,
+        _ => return Err(anyhow!("expected an integer.")),
+    };
+    if n < min || n > max {
+        return Err(anyhow!("{} is out of range.", n));
+    }
+    Ok(n)
+}
+
+fn number(value: &Value) -> Result<f64> {
+    match value {
+        Value::Number(n) => n.as_f64().ok_or_else(|| anyhow!("expected a number.")),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Ok(f64::NAN),
+            "Infinity" => Ok(f64::INFINITY),
+            "-Infinity" => Ok(f64::NEG_INFINITY),
+            s => s
+                .parse()
+                .map_err(|_| anyhow!("expected a number, not '{}'.", s)),
+        },
+        _ => Err(anyhow!("expected a number.")),
+    }
+}
+
+/// A float as JSON, with the values JSON numbers can't hold as strings.
+fn float(f: f64) -> Value {
+    match f {
+        f if f.is_nan() => json!("NaN"),
+        f if f.is_infinite() && f > 0.0 => json!("Infinity"),
+        f if f.is_infinite() => json!("-Infinity"),
+        f => json!(f),
+    }
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+fn write_varint(mut n: u64, out: &mut Vec<u8>) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    write_varint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+fn write_len(number: u64, bytes: &[u8], out: &mut Vec<u8>) {
+    write_varint(number << 3 | LEN, out);
+    write_bytes(bytes, out);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes
+            .split_first()
+            .ok_or_else(|| anyhow!("The message ended in the middle of a field."))?;
+        *bytes = rest;
+        n |= ((byte & 0x7f) as u64) << shift;
+        if byte < 0x80 {
+            return Ok(n);
+        }
+    }
+    Err(anyhow!("Invalid varint in the message."))
+}
+
+fn read_raw<'a>(bytes: &mut &'a [u8], wire_type: u64) -> Result<Raw<'a>> {
+    Ok(match wire_type {
+        VARINT => Raw::Varint(read_varint(bytes)?),
+        FIXED64 => Raw::Fixed64(take(bytes, 8)?.try_into()?),
+        LEN => {
+            let len = read_varint(bytes)?;
+            Raw::Len(take(bytes, usize::try_from(len)?)?)
+        }
+        FIXED32 => Raw::Fixed32(take(bytes, 4)?.try_into()?),
+        wire_type => {
+            return Err(anyhow!(
+                "Unsupported wire type {} in the message.",
+                wire_type
+            ))
+        }
+    })
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err(anyhow!("The message ended in the middle of a field."));
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+/// Reads the declarations of a `.proto` file into a schema.
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+    package: String,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self> {
+        Ok(Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+            package: String::new(),
+        })
+    }
+
+    fn next(&mut self) -> Result<String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("unexpected end of file."))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(anyhow!("expected '{}', found '{}'.", expected, token)),
+        }
+    }
+
+    /// Skips to the end of a statement, like an `option`, including any braces in it.
+    fn skip_statement(&mut self) -> Result<()> {
+        let mut depth = 0;
+        loop {
+            match self.next()?.as_str() {
+                "{" | "[" | "(" => depth += 1,
+                "}" | "]" | ")" => depth -= 1,
+                ";" if depth == 0 => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Skips a block up to its closing brace.
+    fn skip_block(&mut self) -> Result<()> {
+        while self.next()? != "{" {}
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()?.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a whole file, returning the paths it imports.
+    fn file(&mut self, schema: &mut Schema) -> Result<Vec<String>> {
+        let mut imports = vec![];
+        while let Some(token) = self.peek() {
+            match token {
+                "syntax" | "edition" | "option" => self.skip_statement()?,
+                "package" => {
+                    self.next()?;
+                    self.package = self.next()?;
+                    self.expect(";")?;
+                }
+                "import" => {
+                    self.next()?;
+                    let mut path = self.next()?;
+                    if path == "public" || path == "weak" {
+                        path = self.next()?;
+                    }
+                    imports.push(path.trim_matches(['"', '\'']).to_owned());
+                    self.expect(";")?;
+                }
+                "message" => {
+                    self.next()?;
+                    let scope = self.package.clone();
+                    self.message(&scope, schema)?;
+                }
+                "enum" => {
+                    self.next()?;
+                    let scope = self.package.clone();
+                    self.enumeration(&scope, schema)?;
+                }
+                "service" => {
+                    self.next()?;
+                    self.service(schema)?;
+                }
+                "extend" => self.skip_block()?,
+                ";" => self.pos += 1,
+                token => return Err(anyhow!("unexpected '{}'.", token)),
+            }
+        }
+        Ok(imports)
+    }
+
+    fn message(&mut self, scope: &str, schema: &mut Schema) -> Result<()> {
+        let name = full_name(scope, &self.next()?);
+        self.expect("{")?;
+        let mut message = Message::default();
+        self.message_body(&name, &mut message, schema)?;
+        schema.messages.insert(name, message);
+        Ok(())
+    }
+
+    fn message_body(
+        &mut self,
+        name: &str,
+        message: &mut Message,
+        schema: &mut Schema,
+    ) -> Result<()> {
+        loop {
+            let token = self.next()?;
+            match token.as_str() {
+                "}" => return Ok(()),
+                ";" => {}
+                "message" => self.message(name, schema)?,
+                "enum" => self.enumeration(name, schema)?,
+                // The fields of a oneof are fields of the message, only one of which is set.
+                "oneof" => {
+                    self.next()?;
+                    self.expect("{")?;
+                    self.message_body(name, message, schema)?;
+                }
+                "option" | "reserved" | "extensions" => self.skip_statement()?,
+                "extend" => self.skip_block()?,
+                "map" => {
+                    self.expect("<")?;
+                    let key = self.next()?;
+                    let key = Scalar::parse(&key)
+                        .ok_or_else(|| anyhow!("invalid map key type '{}'.", key))?;
+                    self.expect(",")?;
+                    let value = kind(&self.next()?, name);
+                    self.expect(">")?;
+                    message
+                        .fields
+                        .push(self.field(Kind::Map(key, Box::new(value)), false)?);
+                }
+                "repeated" => {
+                    let kind = kind(&self.next()?, name);
+                    message.fields.push(self.field(kind, true)?);
+                }
+                "optional" | "required" => {
+                    let kind = kind(&self.next()?, name);
+                    message.fields.push(self.field(kind, false)?);
+                }
+                type_name => {
+                    let kind = kind(type_name, name);
+                    message.fields.push(self.field(kind, false)?);
+                }
+            }
+        }
+    }
+
+    /// Reads the rest of a field after its type: its name, number and options.
+    fn field(&mut self, kind: Kind, repeated: bool) -> Result<Field> {
+        let name = self.next()?;
+        self.expect("=")?;
+        let number = self.next()?;
+        let number = number
+            .parse()
+            .map_err(|_| anyhow!("invalid field number '{}'.", number))?;
+        if self.peek() == Some("[") {
+            while self.next()? != "]" {}
+        }
+        self.expect(";")?;
+        Ok(Field {
+            name,
+            number,
+            kind,
+            repeated,
+        })
+    }
+
+    fn enumeration(&mut self, scope: &str, schema: &mut Schema) -> Result<()> {
+        let name = full_name(scope, &self.next()?);
+        self.expect("{")?;
+        let mut values = vec![];
+        loop {
+            let token = self.next()?;
+            match token.as_str() {
+                "}" => break,
+                ";" => {}
+                "option" | "reserved" => self.skip_statement()?,
+                value_name => {
+                    self.expect("=")?;
+                    let mut number = self.next()?;
+                    if number == "-" {
+                        number = format!("-{}", self.next()?);
+                    }
+                    let number = number
+                        .parse()
+                        .map_err(|_| anyhow!("invalid enum value '{}'.", number))?;
+                    if self.peek() == Some("[") {
+                        while self.next()? != "]" {}
+                    }
+                    self.expect(";")?;
+                    values.push((value_name.to_owned(), number));
+                }
+            }
+        }
+        schema.enums.insert(name, values);
+        Ok(())
+    }
+
+    fn service(&mut self, schema: &mut Schema) -> Result<()> {
+        let name = self.next()?;
+        let name = full_name(&self.package, &name);
+        self.expect("{")?;
+        let mut methods = HashMap::new();
+        loop {
+            let token = self.next()?;
+            match token.as_str() {
+                "}" => break,
+                ";" => {}
+                "option" => self.skip_statement()?,
+                "rpc" => {
+                    let method = self.next()?;
+                    let (input, client_streaming) = self.rpc_type()?;
+                    self.expect("returns")?;
+                    let (output, server_streaming) = self.rpc_type()?;
+                    match self.peek() {
+                        Some("{") => self.skip_block()?,
+                        _ => self.expect(";")?,
+                    }
+                    // Types are resolved once every file is read, so they're kept with their
+                    // scope until then.
+                    let rpc = Rpc {
+                        input: format!("{} {}", self.package, input),
+                        output: format!("{} {}", self.package, output),
+                        streaming: client_streaming || server_streaming,
+                    };
+                    methods.insert(method, rpc);
+                }
+                token => return Err(anyhow!("unexpected '{}' in service {}.", token, name)),
+            }
+        }
+        schema.services.insert(name, methods);
+        Ok(())
+    }
+
+    /// Reads the parenthesized type of a method's request or response.
+    fn rpc_type(&mut self) -> Result<(String, bool)> {
+        self.expect("(")?;
+        let mut type_name = self.next()?;
+        let streaming = type_name == "stream";
+        if streaming {
+            type_name = self.next()?;
+        }
+        self.expect(")")?;
+        Ok((type_name, streaming))
+    }
+}
+
+/// The kind of a field of the type named, which is looked up from `scope` if it isn't a scalar.
+fn kind(type_name: &str, scope: &str) -> Kind {
+    match Scalar::parse(type_name) {
+        Some(scalar) => Kind::Scalar(scalar),
+        None => Kind::Unresolved(type_name.to_owned(), scope.to_owned()),
+    }
+}
+
+fn full_name(scope: &str, name: &str) -> String {
+    match scope {
+        "" => name.to_owned(),
+        scope => format!("{}.{}", scope, name),
+    }
+}
+
+/// Splits a `.proto` file into identifiers, numbers, strings and symbols, without comments.
+fn tokenize(source: &str) -> Result<Vec<String>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '"' || c == '\'' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            if i >= chars.len() {
+                return Err(anyhow!("unterminated string."));
+            }
+            i += 1;
+            tokens.push(chars[start..i].iter().collect());
+        } else if c.is_alphanumeric() || c == '_' || c == '.' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                i += 1;
+            }
+            tokens.push(chars[start..i].iter().collect());
+        } else {
+            tokens.push(c.to_string());
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+fn test_schema() -> Schema {
+    Schema::parse(
+        r#"
+        syntax = "proto3";
+        package shop.v1;
+        option go_package = "example.com/shop";
+
+        /* Orders, and the items in them. */
+        message Order {
+          string id = 1;
+          repeated Item items = 2;
+          Status status = 3 [deprecated = true];
+          map<string, int64> totals = 4;
+          oneof payment {
+            string card_number = 5;
+            bytes voucher = 6;
+          }
+          repeated int32 ratings = 7;
+          sint32 delta = 8;
+
+          message Item {
+            string name = 1;
+            uint32 quantity = 2;
+            double price = 3;
+          }
+          reserved 9, 10;
+        }
+
+        enum Status {
+          STATUS_UNKNOWN = 0;
+          STATUS_PAID = 1;
+        }
+
+        message GetOrderRequest { string id = 1; }
+
+        service Orders {
+          // Looks up an order.
+          rpc GetOrder (GetOrderRequest) returns (Order);
+          rpc Watch (GetOrderRequest) returns (stream Order) { option deprecated = true; }
+        }
+        "#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_schema_parse() {
+    let schema = test_schema();
+    assert_eq!(
+        schema.rpc("shop.v1.Orders", "GetOrder").unwrap(),
+        &Rpc {
+            input: "shop.v1.GetOrderRequest".to_owned(),
+            output: "shop.v1.Order".to_owned(),
+            streaming: false,
+        }
+    );
+    assert!(schema.rpc("shop.v1.Orders", "Watch").unwrap().streaming);
+    assert!(schema.rpc("shop.v1.Orders", "Delete").is_err());
+
+    let order = &schema.messages["shop.v1.Order"];
+    assert_eq!(order.fields.len(), 8);
+    assert_eq!(
+        order.fields[1].kind,
+        Kind::Message("shop.v1.Order.Item".to_owned())
+    );
+    assert_eq!(
+        order.fields[2].kind,
+        Kind::Enum("shop.v1.Status".to_owned())
+    );
+    assert_eq!(order.fields[4].json_name(), "cardNumber");
+}
+
+#[test]
+fn test_encode_decode() {
+    let schema = test_schema();
+    // The example from protobuf's encoding guide: field 1 set to 150.
+    let mut out = vec![];
+    schema
+        .encode_value(1, &Kind::Scalar(Scalar::Int32), &json!(150), &mut out)
+        .unwrap();
+    assert_eq!(out, [0x08, 0x96, 0x01]);
+
+    let order = json!({
+        "id": "o-1",
+        "items": [{"name": "Tea", "quantity": 2, "price": 3.5}],
+        "status": "STATUS_PAID",
+        "totals": {"eur": "700"},
+        "card_number": "4242",
+        "ratings": [5, -1],
+        "delta": -3,
+    });
+    let bytes = schema.encode("shop.v1.Order", &order).unwrap();
+    assert_eq!(
+        schema.decode("shop.v1.Order", &bytes).unwrap(),
+        json!({
+            "id": "o-1",
+            "items": [{"name": "Tea", "quantity": 2, "price": 3.5}],
+            "status": "STATUS_PAID",
+            "totals": {"eur": "700"},
+            "cardNumber": "4242",
+            "ratings": [5, -1],
+            "delta": -3,
+        })
+    );
+
+    assert!(schema.encode("shop.v1.Order", &json!({"nope": 1})).is_err());
+    assert!(schema
+        .encode("shop.v1.Order", &json!({"status": "STATUS_LOST"}))
+        .is_err());
+    assert!(schema
+        .encode("shop.v1.Order", &json!({"items": [{"quantity": -1}]}))
+        .is_err());
+    assert!(schema
+        .decode("shop.v1.Order", &bytes[..bytes.len() - 1])
+        .is_err());
+}
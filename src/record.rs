@@ -0,0 +1,403 @@
+//! `reqq record`: a reverse proxy that forwards requests to a target server and writes each one
+//! as a request file, to bootstrap a collection from an existing client.
+
+use crate::{http1::read_chunked, response::Response, snapshot::Snapshots};
+use anyhow::{anyhow, Result};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, Version};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+
+/// Headers that only concern one connection, or that are set when a request or response is
+/// sent again. `Accept-Encoding` is dropped so responses come back readable.
+const UNFORWARDED_HEADERS: [&str; 10] = [
+    "host",
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "te",
+    "trailer",
+    "upgrade",
+    "content-length",
+    "accept-encoding",
+];
+
+/// A request read from a client.
+#[derive(Clone, Debug, PartialEq)]
+struct Recorded {
+    method: String,
+    /// The path and query, as requested.
+    target: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Recorded {
+    /// The request file sending this request to the target.
+    fn to_request_file(&self, target_url: &str) -> String {
+        let mut lines = vec![format!("{} {}{}", self.method, target_url, self.target)];
+        for (name, value) in self.headers.iter() {
+            lines.push(format!("{}: {}", name, value));
+        }
+        if self.body.is_empty() {
+            format!("{}\n", lines.join("\n"))
+        } else {
+            // The body is sent as written, so it doesn't get a final newline it didn't have.
+            format!(
+                "{}\n\n{}",
+                lines.join("\n"),
+                String::from_utf8_lossy(&self.body)
+            )
+        }
+    }
+
+    /// A request name like `get-users-42`, from the method and path.
+    fn name(&self) -> String {
+        let path = self.target.split(['?', '#']).next().unwrap_or_default();
+        let path: String = path
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let path: Vec<&str> = path.split('-').filter(|part| !part.is_empty()).collect();
+        if path.is_empty() {
+            format!("{}-root", self.method.to_lowercase())
+        } else {
+            format!("{}-{}", self.method.to_lowercase(), path.join("-"))
+        }
+    }
+}
+
+/// Forwards requests to a target and records them.
+pub struct Recorder {
+    /// The scheme, host and any path prefix requests are forwarded to.
+    target: String,
+    client: Client,
+    /// The folder of the reqq directory request files are written to.
+    folder: PathBuf,
+    /// Where responses are saved as snapshots, if they are.
+    snapshots: Option<Snapshots>,
+    /// The name of the folder within the reqq directory, which request names start with.
+    prefix: String,
+    /// Held while a request file is named and written, so two requests don't get one name.
+    writing: Mutex<()>,
+}
+
+impl Recorder {
+    pub fn new(
+        target: String,
+        client: Client,
+        reqq_dir: &str,
+        folder: &str,
+        snapshots: Option<Snapshots>,
+    ) -> Self {
+        Recorder {
+            target: target.trim_end_matches('/').to_owned(),
+            client,
+            folder: PathBuf::from(reqq_dir).join(folder),
+            snapshots,
+            prefix: folder.to_owned(),
+            writing: Mutex::new(()),
+        }
+    }
+
+    /// Answers connections until the process is stopped, each on its own thread.
+    pub fn run(&self, listener: TcpListener) -> Result<()> {
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let stream = stream?;
+                scope.spawn(move || {
+                    if let Err(e) = self.handle(stream) {
+                        eprintln!("Error: {}", e);
+                    }
+                });
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads a request, forwards it to the target and sends the response back. Requests that
+    /// get a response are recorded, and logged to stderr with the name of their file.
+    fn handle(&self, stream: TcpStream) -> Result<()> {
+        let recorded = read_request(&mut BufReader::new(&stream))?;
+        let resp = match self.forward(&recorded) {
+            Ok(resp) => resp,
+            Err(e) => {
+                eprintln!("{} {} -> {}", recorded.method, recorded.target, e);
+                let body = format!("{}\n", e);
+                let out = format!(
+                    "HTTP/1.1 502 Bad Gateway\r\ncontent-type: text/plain\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                (&stream).write_all(out.as_bytes())?;
+                return Ok(());
+            }
+        };
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = resp.bytes()?;
+
+        let mut out = format!(
+            "HTTP/1.1 {} {}\r\n",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or_default()
+        )
+        .into_bytes();
+        for (name, value) in headers
+            .iter()
+            .filter(|(name, _)| !UNFORWARDED_HEADERS.contains(&name.as_str()))
+        {
+            out.extend_from_slice(format!("{}: ", name).as_bytes());
+            out.extend_from_slice(value.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+        out.extend_from_slice(
+            format!(
+                "content-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            )
+            .as_bytes(),
+        );
+        out.extend_from_slice(&body);
+        (&stream).write_all(&out)?;
+
+        let response = Response {
+            status,
+            version: Version::HTTP_11,
+            headers,
+            body: String::from_utf8_lossy(&body).into_owned(),
+            binary: None,
+        };
+        let name = self.write(&recorded, &response)?;
+        eprintln!(
+            "{} {} -> {}, recorded {}",
+            recorded.method, recorded.target, status, name
+        );
+        Ok(())
+    }
+
+    fn forward(&self, recorded: &Recorded) -> Result<reqwest::blocking::Response> {
+        let method = Method::from_bytes(recorded.method.as_bytes())?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in recorded.headers.iter() {
+            headers.append(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        let url = format!("{}{}", self.target, recorded.target);
+        let mut builder = self.client.request(method, url).headers(headers);
+        if !recorded.body.is_empty() {
+            builder = builder.body(recorded.body.clone());
+        }
+        Ok(builder.send()?)
+    }
+
+    /// Writes the request file of a request, and its response as the snapshot when asked to.
+    /// Returns the name of the request.
+    fn write(&self, recorded: &Recorded, resp: &Response) -> Result<String> {
+        let _writing = self.writing.lock().unwrap_or_else(|e| e.into_inner());
+        fs::create_dir_all(&self.folder)?;
+        let base = recorded.name();
+        let name = (1..)
+            .map(|n| {
+                if n == 1 {
+                    base.clone()
+                } else {
+                    format!("{}-{}", base, n)
+                }
+            })
+            .find(|name| !self.folder.join(format!("{}.reqq", name)).exists())
+            .unwrap_or(base);
+        fs::write(
+            self.folder.join(format!("{}.reqq", name)),
+            recorded.to_request_file(&self.target),
+        )?;
+        let name = format!("{}/{}", self.prefix, name);
+        if let Some(snapshots) = &self.snapshots {
+            snapshots.save(&name, resp)?;
+        }
+        Ok(name)
+    }
+}
+
+/// Reads a request's line, headers and body, which is either as long as `Content-Length` says
+/// or chunked. Headers that aren't forwarded are left out.
+fn read_request(reader: &mut impl BufRead) -> Result<Recorded> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(anyhow!(
+            "Invalid request line: '{}'.",
+            request_line.trim_end()
+        ));
+    };
+    if !target.starts_with('/') {
+        return Err(anyhow!(
+            "Only requests for paths can be recorded, not '{}'.",
+            target
+        ));
+    }
+
+    let mut headers = vec![];
+    let mut content_length = 0;
+    let mut chunked = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            continue;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        match name.to_lowercase().as_str() {
+            "content-length" => {
+                content_length = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid Content-Length '{}'.", value))?
+            }
+            "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+            lower if UNFORWARDED_HEADERS.contains(&lower) => {}
+            _ => headers.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    let mut body = vec![];
+    if chunked {
+        body = read_chunked(reader)?;
+    } else {
+        reader.take(content_length).read_to_end(&mut body)?;
+    }
+    Ok(Recorded {
+        method: method.to_owned(),
+        target: target.to_owned(),
+        headers,
+        body,
+    })
+}
+
+#[cfg(test)]
+fn test_request(method: &str, target: &str) -> Recorded {
+    Recorded {
+        method: method.to_owned(),
+        target: target.to_owned(),
+        headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+        body: b"{\"name\": \"ada\"}".to_vec(),
+    }
+}
+
+#[test]
+fn test_read_request() {
+    let raw = "POST /users?x=1 HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nAccept-Encoding: gzip\r\nContent-Length: 15\r\n\r\n{\"name\": \"ada\"}";
+    assert_eq!(
+        read_request(&mut raw.as_bytes()).unwrap(),
+        test_request("POST", "/users?x=1")
+    );
+
+    let raw = "POST /users?x=1 HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Type: application/json\r\n\r\n5\r\n{\"nam\r\na\r\ne\": \"ada\"}\r\n0\r\n\r\n";
+    assert_eq!(
+        read_request(&mut raw.as_bytes()).unwrap(),
+        test_request("POST", "/users?x=1")
+    );
+
+    assert!(read_request(&mut "GET http://example.com/ HTTP/1.1\r\n\r\n".as_bytes()).is_err());
+}
+
+#[test]
+fn test_recorded_request_file() {
+    let req = test_request("POST", "/v1/users?x=1");
+    assert_eq!(req.name(), "post-v1-users");
+    assert_eq!(
+        req.to_request_file("https://api.example.com"),
+        "POST https://api.example.com/v1/users?x=1\nContent-Type: application/json\n\n{\"name\": \"ada\"}"
+    );
+    assert_eq!(test_request("GET", "/").name(), "get-root");
+    assert_eq!(
+        test_request("GET", "/Users/42.json").name(),
+        "get-users-42-json"
+    );
+}
+
+#[test]
+fn test_recorder() {
+    // The target answers once, with the request line it got.
+    let target = TcpListener::bind("127.0.0.1:0").unwrap();
+    let target_url = format!("http://{}", target.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let (stream, _) = target.accept().unwrap();
+        let req = read_request(&mut BufReader::new(&stream)).unwrap();
+        let body = format!("{{\"got\": \"{} {}\"}}", req.method, req.target);
+        let resp = format!(
+            "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        (&stream).write_all(resp.as_bytes()).unwrap();
+    });
+
+    let dir = std::env::temp_dir().join(format!("reqq-record-{}", std::process::id()));
+    let reqq_dir = dir.to_str().unwrap();
+    let recorder = Recorder::new(
+        target_url.clone(),
+        Client::new(),
+        reqq_dir,
+        "recorded",
+        Some(Snapshots::new(reqq_dir)),
+    );
+    let proxy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(proxy.local_addr().unwrap()).unwrap();
+    client
+        .write_all(b"POST /v1/users?x=1 HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 15\r\n\r\n{\"name\": \"ada\"}")
+        .unwrap();
+    let (stream, _) = proxy.accept().unwrap();
+    recorder.handle(stream).unwrap();
+    server.join().unwrap();
+
+    let mut out = String::new();
+    client.read_to_string(&mut out).unwrap();
+    assert!(out.starts_with("HTTP/1.1 201 Created\r\n"));
+    assert!(out.ends_with("{\"got\": \"POST /v1/users?x=1\"}"));
+
+    let written = fs::read_to_string(dir.join("recorded/post-v1-users.reqq")).unwrap();
+    assert_eq!(
+        written,
+        test_request("POST", "/v1/users?x=1").to_request_file(&target_url)
+    );
+    let snapshot = Snapshots::new(reqq_dir)
+        .load("recorded/post-v1-users")
+        .unwrap()
+        .unwrap();
+    assert_eq!(snapshot.status, reqwest::StatusCode::CREATED);
+
+    // Requests to the same path get their own files.
+    let resp = Response {
+        status: reqwest::StatusCode::OK,
+        version: Version::HTTP_11,
+        headers: HeaderMap::new(),
+        body: String::new(),
+        binary: None,
+    };
+    assert_eq!(
+        recorder
+            .write(&test_request("POST", "/v1/users"), &resp)
+            .unwrap(),
+        "recorded/post-v1-users-2"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
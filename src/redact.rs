@@ -0,0 +1,80 @@
+//! Secrets masked in what reqq prints and records: the values of the variables in an env's
+//! `secrets` section, and of those whose names match the `secret_pattern` of the collection
+//! config. Values are registered as requests are rendered, so later output can mask them.
+
+use crate::response::Response;
+use reqwest::header::HeaderValue;
+use serde_json::Value;
+use std::sync::Mutex;
+
+/// What secrets are replaced with.
+pub const MASK: &str = "*****";
+
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Registers the strings and numbers in a value as secrets.
+pub fn add(value: &Value) {
+    let mut secrets = SECRETS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        let secret = match value {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            Value::Array(items) => {
+                pending.extend(items);
+                continue;
+            }
+            Value::Object(map) => {
+                pending.extend(map.values());
+                continue;
+            }
+            Value::Null | Value::Bool(_) => continue,
+        };
+        if !secret.is_empty() && !secrets.contains(&secret) {
+            secrets.push(secret);
+        }
+    }
+    // Longer secrets go first, so one that contains another is masked whole.
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+}
+
+/// Masks the secrets registered so far in a text.
+pub fn redact(text: &str) -> String {
+    let secrets = SECRETS.lock().unwrap_or_else(|e| e.into_inner());
+    secrets.iter().fold(text.to_owned(), |text, secret| {
+        text.replace(secret.as_str(), MASK)
+    })
+}
+
+/// A header value with the secrets in it masked.
+pub fn header(value: &HeaderValue) -> HeaderValue {
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| HeaderValue::from_str(&redact(value)).ok())
+        .unwrap_or_else(|| value.clone())
+}
+
+/// A response with the secrets in its headers and body masked.
+pub fn response(resp: &Response) -> Response {
+    let mut redacted = resp.clone();
+    for value in redacted.headers.values_mut() {
+        *value = header(value);
+    }
+    redacted.body = redact(&resp.body);
+    redacted
+}
+
+#[test]
+fn test_redact() {
+    add(&serde_json::json!({
+        "token": "tok-5f1e2d",
+        "nested": ["tok-5f1e2d-long", 918273645],
+        "on": true,
+    }));
+    add(&serde_json::json!(""));
+    assert_eq!(
+        redact("Bearer tok-5f1e2d, tok-5f1e2d-long and 918273645 are on"),
+        "Bearer *****, ***** and ***** are on"
+    );
+}
@@ -0,0 +1,475 @@
+use crate::assertion::CheckReport;
+use crate::snapshot::SnapshotOutcome;
+use crate::suite::{CaseResult, SuiteResult};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+/// Formats the result of a test run.
+pub trait Reporter {
+    fn render(&self, result: &SuiteResult) -> String;
+}
+
+/// The supported report formats.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReportFormat {
+    /// A line per request and a summary, for people.
+    #[default]
+    Text,
+    /// JUnit XML, which most CI systems understand.
+    Junit,
+    /// JSON, for scripts.
+    Json,
+}
+
+impl ReportFormat {
+    pub fn reporter(self) -> Box<dyn Reporter> {
+        match self {
+            ReportFormat::Text => Box::new(TextReporter),
+            ReportFormat::Junit => Box::new(JunitReporter),
+            ReportFormat::Json => Box::new(JsonReporter),
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "junit" => Ok(ReportFormat::Junit),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!(
+                "unknown report format '{}', expected text, junit or json",
+                s
+            )),
+        }
+    }
+}
+
+/// How the result of executing a single request is printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// The response, formatted for people.
+    #[default]
+    Text,
+    /// The request, response and timing as a JSON object, for scripts.
+    Json,
+    /// The same object as YAML.
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            _ => Err(format!(
+                "unknown output format '{}', expected text, json or yaml",
+                s
+            )),
+        }
+    }
+}
+
+pub struct TextReporter;
+
+impl Reporter for TextReporter {
+    fn render(&self, result: &SuiteResult) -> String {
+        let mut lines = vec![];
+        for case in result.cases.iter() {
+            let status = case.status.map_or("-".to_owned(), |s| s.to_string());
+            lines.push(format!(
+                "{}  {}  {}  {} ms",
+                if case.passed() { "PASS" } else { "FAIL" },
+                case.name,
+                status,
+                case.duration_ms
+            ));
+            for line in failure_details(case).lines() {
+                lines.push(format!("      {}", line));
+            }
+            if let Some(SnapshotOutcome::Saved) = case.snapshot {
+                lines.push("      Saved the response as the snapshot.".to_owned());
+            }
+        }
+
+        let mut summary = format!(
+            "{} requests, {} passed, {} failed.",
+            result.total(),
+            result.cases.len() - result.failed(),
+            result.failed()
+        );
+        if !result.skipped.is_empty() {
+            summary.push_str(&format!(
+                " Stopped early, {} not run.",
+                result.skipped.len()
+            ));
+        }
+        lines.push(summary);
+        lines.join("\n")
+    }
+}
+
+/// A table with a row per request: its status, time, and why it failed.
+pub struct TableReporter;
+
+impl Reporter for TableReporter {
+    fn render(&self, result: &SuiteResult) -> String {
+        let mut rows = vec![[
+            "REQUEST".to_owned(),
+            "STATUS".to_owned(),
+            "TIME".to_owned(),
+            String::new(),
+        ]];
+        for case in result.cases.iter() {
+            rows.push([
+                case.name.clone(),
+                case.status.map_or("-".to_owned(), |s| s.to_string()),
+                format!("{} ms", case.duration_ms),
+                failure_details(case)
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_owned(),
+            ]);
+        }
+        for name in result.skipped.iter() {
+            rows.push([
+                name.clone(),
+                "-".to_owned(),
+                "-".to_owned(),
+                "Not run.".to_owned(),
+            ]);
+        }
+
+        let widths: Vec<usize> = (0..3)
+            .map(|i| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let mut lines: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                let line = format!(
+                    "{:w0$}  {:w1$}  {:w2$}  {}",
+                    row[0],
+                    row[1],
+                    row[2],
+                    row[3],
+                    w0 = widths[0],
+                    w1 = widths[1],
+                    w2 = widths[2]
+                );
+                line.trim_end().to_owned()
+            })
+            .collect();
+        lines.push(format!(
+            "{} requests, {} passed, {} failed.",
+            result.total(),
+            result.cases.len() - result.failed(),
+            result.failed()
+        ));
+        lines.join("\n")
+    }
+}
+
+pub struct JunitReporter;
+
+impl Reporter for JunitReporter {
+    fn render(&self, result: &SuiteResult) -> String {
+        let counts = format!(
+            "tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\"",
+            result.total(),
+            result.failed(),
+            result.skipped.len(),
+            seconds(result.duration_ms())
+        );
+
+        let mut lines = vec![
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_owned(),
+            format!("<testsuites name=\"reqq\" {}>", counts),
+            format!("  <testsuite name=\"reqq\" {}>", counts),
+        ];
+        for case in result.cases.iter() {
+            let open = format!(
+                "    <testcase name=\"{}\" classname=\"reqq\" time=\"{}\"",
+                escape_xml(&case.name),
+                seconds(case.duration_ms)
+            );
+            if case.passed() {
+                lines.push(format!("{}/>", open));
+            } else {
+                let details = failure_details(case);
+                let message = details.lines().next().unwrap_or_default();
+                lines.push(format!("{}>", open));
+                lines.push(format!(
+                    "      <failure message=\"{}\">{}</failure>",
+                    escape_xml(message),
+                    escape_xml(&details)
+                ));
+                lines.push("    </testcase>".to_owned());
+            }
+        }
+        for name in result.skipped.iter() {
+            lines.push(format!(
+                "    <testcase name=\"{}\" classname=\"reqq\" time=\"0.000\">",
+                escape_xml(name)
+            ));
+            lines.push("      <skipped/>".to_owned());
+            lines.push("    </testcase>".to_owned());
+        }
+        lines.push("  </testsuite>".to_owned());
+        lines.push("</testsuites>".to_owned());
+        lines.join("\n")
+    }
+}
+
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn render(&self, result: &SuiteResult) -> String {
+        let mut cases: Vec<Value> = result.cases.iter().map(case_to_json).collect();
+        cases.extend(
+            result
+                .skipped
+                .iter()
+                .map(|name| json!({ "name": name, "outcome": "skipped" })),
+        );
+
+        let report = json!({
+            "total": result.total(),
+            "passed": result.cases.len() - result.failed(),
+            "failed": result.failed(),
+            "skipped": result.skipped.len(),
+            "duration_ms": result.duration_ms(),
+            "cases": cases,
+        });
+        serde_json::to_string_pretty(&report).unwrap_or_default()
+    }
+}
+
+fn case_to_json(case: &CaseResult) -> Value {
+    let assertions = case.check.as_ref().map_or(json!([]), assertions_to_json);
+    json!({
+        "name": case.name,
+        "outcome": if case.passed() { "passed" } else { "failed" },
+        "status": case.status.map(|s| s.as_u16()),
+        "duration_ms": case.duration_ms,
+        "error": case.error,
+        "assertions": assertions,
+        "snapshot": case.snapshot.as_ref().map(|outcome| match outcome {
+            SnapshotOutcome::Matched => "matched",
+            SnapshotOutcome::Saved => "saved",
+            SnapshotOutcome::Changed(_) => "changed",
+        }),
+    })
+}
+
+pub(crate) fn assertions_to_json(check: &CheckReport) -> Value {
+    let assertions: Vec<Value> = check
+        .results
+        .iter()
+        .map(|r| {
+            json!({
+                "assertion": r.assertion,
+                "passed": r.passed,
+                "expected": r.expected,
+                "actual": r.actual,
+            })
+        })
+        .collect();
+    Value::Array(assertions)
+}
+
+/// Why a case failed: its error, the expected and actual values of failed assertions, and how
+/// the response differs from its snapshot.
+fn failure_details(case: &CaseResult) -> String {
+    let mut lines: Vec<String> = case.error.iter().cloned().collect();
+    if let Some(SnapshotOutcome::Changed(diff)) = &case.snapshot {
+        lines.push("The response doesn't match the snapshot.".to_owned());
+        lines.push(diff.report());
+    }
+    let failed_asserts = case
+        .check
+        .iter()
+        .flat_map(|c| c.results.iter())
+        .filter(|r| !r.passed);
+    for result in failed_asserts {
+        lines.push(result.assertion.clone());
+        lines.push(format!("  - expected: {}", result.expected));
+        lines.push(format!("  + actual:   {}", result.actual));
+    }
+    lines.join("\n")
+}
+
+fn seconds(millis: u64) -> String {
+    format!("{}.{:03}", millis / 1000, millis % 1000)
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+fn test_result() -> SuiteResult {
+    use crate::assertion::{AssertionResult, CheckReport};
+    use reqwest::StatusCode;
+
+    let case = |name: &str, status, error: Option<&str>| CaseResult {
+        name: name.to_owned(),
+        status,
+        duration_ms: 5,
+        check: None,
+        error: error.map(str::to_owned),
+        snapshot: None,
+    };
+    let mut checked = case("users/create", Some(StatusCode::CREATED), None);
+    checked.check = Some(CheckReport {
+        results: vec![AssertionResult {
+            assertion: "$.id exists".to_owned(),
+            passed: false,
+            expected: "exists".to_owned(),
+            actual: "nothing".to_owned(),
+        }],
+    });
+
+    SuiteResult {
+        cases: vec![
+            case("auth/login", Some(StatusCode::OK), None),
+            case("users/get", None, Some("Request <timed> out.")),
+            checked,
+        ],
+        skipped: vec!["users/delete".to_owned()],
+    }
+}
+
+#[test]
+fn test_text_report() {
+    assert_eq!(
+        TextReporter.render(&test_result()),
+        "PASS  auth/login  200 OK  5 ms
+FAIL  users/get  -  5 ms
+      Request <timed> out.
+FAIL  users/create  201 Created  5 ms
+      $.id exists
+        - expected: exists
+        + actual:   nothing
+4 requests, 1 passed, 2 failed. Stopped early, 1 not run."
+    );
+}
+
+#[test]
+fn test_table_report() {
+    assert_eq!(
+        TableReporter.render(&test_result()),
+        "REQUEST       STATUS       TIME
+auth/login    200 OK       5 ms
+users/get     -            5 ms  Request <timed> out.
+users/create  201 Created  5 ms  $.id exists
+users/delete  -            -     Not run.
+4 requests, 1 passed, 2 failed."
+    );
+}
+
+#[test]
+fn test_junit_report() {
+    assert_eq!(
+        JunitReporter.render(&test_result()),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="reqq" tests="4" failures="2" skipped="1" time="0.015">
+  <testsuite name="reqq" tests="4" failures="2" skipped="1" time="0.015">
+    <testcase name="auth/login" classname="reqq" time="0.005"/>
+    <testcase name="users/get" classname="reqq" time="0.005">
+      <failure message="Request &lt;timed&gt; out.">Request &lt;timed&gt; out.</failure>
+    </testcase>
+    <testcase name="users/create" classname="reqq" time="0.005">
+      <failure message="$.id exists">$.id exists
+  - expected: exists
+  + actual:   nothing</failure>
+    </testcase>
+    <testcase name="users/delete" classname="reqq" time="0.000">
+      <skipped/>
+    </testcase>
+  </testsuite>
+</testsuites>"#
+    );
+}
+
+#[test]
+fn test_json_report() {
+    let report: Value = serde_json::from_str(&JsonReporter.render(&test_result())).unwrap();
+    assert_eq!(report["total"], 4);
+    assert_eq!(report["passed"], 1);
+    assert_eq!(report["failed"], 2);
+    assert_eq!(report["skipped"], 1);
+    assert_eq!(report["duration_ms"], 15);
+    assert_eq!(report["cases"][0]["outcome"], "passed");
+    assert_eq!(report["cases"][0]["status"], 200);
+    assert_eq!(report["cases"][1]["error"], "Request <timed> out.");
+    assert_eq!(report["cases"][2]["assertions"][0]["actual"], "nothing");
+    assert_eq!(report["cases"][3]["outcome"], "skipped");
+}
+
+#[test]
+fn test_snapshot_report() {
+    use crate::diff::{Difference, ResponseDiff};
+    use reqwest::StatusCode;
+
+    let case = |name: &str, snapshot| CaseResult {
+        name: name.to_owned(),
+        status: Some(StatusCode::OK),
+        duration_ms: 5,
+        check: None,
+        error: None,
+        snapshot: Some(snapshot),
+    };
+    let diff = ResponseDiff {
+        left: "snapshot".to_owned(),
+        right: "response".to_owned(),
+        differences: vec![Difference::Changed {
+            path: "$.name".to_owned(),
+            left: "\"ada\"".to_owned(),
+            right: "\"grace\"".to_owned(),
+        }],
+    };
+    let result = SuiteResult {
+        cases: vec![
+            case("users/get", SnapshotOutcome::Changed(diff)),
+            case("users/list", SnapshotOutcome::Saved),
+        ],
+        skipped: vec![],
+    };
+    assert_eq!(
+        TextReporter.render(&result),
+        "FAIL  users/get  200 OK  5 ms
+      The response doesn't match the snapshot.
+      --- snapshot
+      +++ response
+      ~ $.name: \"ada\" -> \"grace\"
+PASS  users/list  200 OK  5 ms
+      Saved the response as the snapshot.
+2 requests, 1 passed, 1 failed."
+    );
+    let report: Value = serde_json::from_str(&JsonReporter.render(&result)).unwrap();
+    assert_eq!(report["cases"][0]["snapshot"], "changed");
+    assert_eq!(report["cases"][1]["snapshot"], "saved");
+}
+
+#[test]
+fn test_report_format() {
+    assert_eq!("junit".parse(), Ok(ReportFormat::Junit));
+    assert!("xml".parse::<ReportFormat>().is_err());
+    assert_eq!("yaml".parse(), Ok(OutputFormat::Yaml));
+    assert!("junit".parse::<OutputFormat>().is_err());
+}
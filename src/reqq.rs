@@ -1,7 +1,64 @@
-use crate::{env::Env, format::format_response, request::Request};
+use crate::{
+    assertion::{AssertionResult, CheckReport},
+    auth::Auth,
+    bench::{BenchResult, Sample},
+    body::Body,
+    cache::{self, ResponseCache},
+    client::{ClientOpts, ClientPool, Redirects},
+    codegen::{self, Lang},
+    compression,
+    config::{self, Config},
+    cookies::{self, Cookie, CookieJar},
+    curl::{self, CurlCommand},
+    diff::ResponseDiff,
+    download::Output,
+    env::Env,
+    error::ReqqError,
+    executor::{Executor, Pacer, RetryPolicy},
+    flow::{Flow, FlowResult, StepResult},
+    glob, grpc, har,
+    history::{Entry, History},
+    hooks::{self, Context},
+    http_file,
+    import::Import,
+    jsonpath,
+    listing::Listing,
+    mock::{MockServer, Route},
+    openapi,
+    paginate::Pagination,
+    postman, prompt,
+    record::Recorder,
+    redact,
+    report::{self, OutputFormat},
+    request::{Request, TrailingNewline},
+    request_file,
+    response::{filter_response, render_response, RenderOpts, Response, ResponseParts},
+    schema::{self, JsonSchema},
+    scripting::Script,
+    secrets::{Provider, SecretStore},
+    session::{self, Session},
+    sigv4,
+    snapshot::{self, SnapshotMode, Snapshots},
+    suite::{CaseResult, SuiteOpts, SuiteResult},
+    template::{self, MissingVariable},
+    time, timings,
+    tokens::{self, TokenCache},
+    validate::{self, Validation},
+    vault, websocket, yaml,
+};
 use anyhow::{anyhow, Result};
-use walkdir::WalkDir;
+use openssl::sha::sha256;
+use regex::Regex;
+use reqwest::header::{HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_TYPE, COOKIE};
+use reqwest::{Method, StatusCode};
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use url::Url;
+use walkdir::WalkDir;
 
 /// The top level app object which loads all available requests and environments
 /// so that various user actions can be performed with them.
@@ -9,52 +66,301 @@ pub struct Reqq<'a> {
     dir: &'a str,
     reqs: Vec<Request>,
     envs: Vec<Env>,
-    raw: bool,
+    config: Config,
+    render: RenderOpts,
+    verbose: bool,
+    trailing_newline: TrailingNewline,
+    timeout: Option<Duration>,
+    client: ClientOpts,
+    executor: Executor,
+    history: History,
+    record_history: bool,
+    session: Session,
+    cookie_jar: CookieJar,
+    use_cookies: bool,
+    cache: Option<ResponseCache>,
+    tokens: TokenCache,
+    secrets: Box<dyn SecretStore>,
+    output: Option<Output>,
+    gql_variables: HashMap<String, serde_json::Value>,
+    check: bool,
+    filter: Option<String>,
+    ws_count: Option<usize>,
+    timings: bool,
+    compressed: bool,
+    infer_content_type: bool,
+    validate_body: bool,
+}
+
+/// The outcome of executing a request.
+pub struct ExecutionResult {
+    /// The HTTP status of the response.
+    pub status: StatusCode,
+    /// The formatted response, ready to be printed.
+    pub output: String,
+    /// The outcome of the request's `@assert` directives, when checking them was asked for.
+    pub check: Option<CheckReport>,
+    /// The statuses the request's `@expect` allows, if it has one.
+    pub expect: Vec<StatusCode>,
+    pub method: String,
+    pub url: String,
+    pub request_headers: Vec<(HeaderName, HeaderValue)>,
+    /// The exchange as recorded in the history. Its id is empty when it wasn't recorded.
+    pub entry: Entry,
+}
+
+impl ExecutionResult {
+    /// The request, response and timing as one JSON object. JSON bodies are embedded as
+    /// values rather than strings, so they can be queried directly.
+    pub fn to_json(&self) -> serde_json::Value {
+        let resp = &self.entry.response;
+        let body = match &resp.binary {
+            // Binary bodies are left out, with their size and hash.
+            Some(_) => serde_json::Value::Null,
+            None => serde_json::from_str(&resp.body)
+                .unwrap_or_else(|_| serde_json::Value::String(resp.body.clone())),
+        };
+        // Secrets are masked, as in the text output.
+        let request_headers: Vec<_> = self
+            .request_headers
+            .iter()
+            .map(|(k, v)| (k, redact::header(v)))
+            .collect();
+        let mut result = serde_json::json!({
+            "request": {
+                "name": self.entry.request,
+                "env": self.entry.env,
+                "method": self.method,
+                "url": redact::redact(&self.url),
+                "headers": headers_to_json(request_headers.iter().map(|(k, v)| (*k, v))),
+            },
+            "response": {
+                "status": resp.status.as_u16(),
+                "version": format!("{:?}", resp.version),
+                "headers": headers_to_json(resp.headers.iter()),
+                "body": body,
+            },
+            "timing": {
+                "started": time::format_iso8601(self.entry.timestamp),
+                "duration_ms": self.entry.duration_ms,
+            },
+            "history_id": (!self.entry.id.is_empty()).then_some(&self.entry.id),
+        });
+        if let Some(binary) = &resp.binary {
+            result["response"]["binary"] = serde_json::json!({
+                "size": binary.len(),
+                "sha256": sigv4::hex(&sha256(binary)),
+            });
+        }
+        if let Some(timings) = &self.entry.timings {
+            result["timing"]["phases"] = timings.to_json();
+        }
+        if let Some(check) = &self.check {
+            result["assertions"] = report::assertions_to_json(check);
+        }
+        result
+    }
+
+    /// Why the response status fails the request, if it does: it's one the request's
+    /// `@expect` doesn't allow, or else a 4xx or 5xx one, unless assertions were checked. With
+    /// `any_error`, every 4xx or 5xx status does, like curl's `--fail`.
+    pub fn status_error(&self, any_error: bool) -> Option<String> {
+        let is_error = self.status.is_client_error() || self.status.is_server_error();
+        if !self.expect.is_empty() && !self.expect.contains(&self.status) {
+            Some(format!(
+                "Expected status {}, got {}.",
+                statuses(&self.expect),
+                self.status
+            ))
+        } else if is_error && (any_error || (self.expect.is_empty() && self.check.is_none())) {
+            Some(format!("Request failed with status {}.", self.status))
+        } else {
+            None
+        }
+    }
+
+    /// What to print for the result: the formatted response, or the structured object.
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Text => self.output.clone(),
+            OutputFormat::Json => serde_json::to_string_pretty(&self.to_json()).unwrap_or_default(),
+            OutputFormat::Yaml => yaml::to_string(&self.to_json()).trim_end().to_owned(),
+        }
+    }
+}
+
+/// Headers as an object, with the values of repeated headers joined by commas.
+fn headers_to_json<'h>(
+    headers: impl Iterator<Item = (&'h HeaderName, &'h HeaderValue)>,
+) -> serde_json::Value {
+    let mut map = serde_json::Map::new();
+    for (name, value) in headers {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        match map.get_mut(name.as_str()) {
+            Some(serde_json::Value::String(existing)) => {
+                *existing = format!("{}, {}", existing, value)
+            }
+            _ => {
+                map.insert(name.as_str().to_owned(), serde_json::Value::String(value));
+            }
+        }
+    }
+    serde_json::Value::Object(map)
 }
 
 pub struct ReqqOpts<'a> {
     pub dir: &'a str,
+    pub parts: ResponseParts,
     pub raw: bool,
+    /// Print only the text of HTML bodies.
+    pub html_text: bool,
+    pub color: bool,
+    pub verbose: bool,
+    pub trailing_newline: TrailingNewline,
+    pub timeout: Option<Duration>,
+    /// Connection, redirect, proxy and TLS settings. Its `verbose` is taken from `verbose`.
+    pub client: ClientOpts,
+    /// When to send failed requests again.
+    pub retry: RetryPolicy,
+    /// The least time between the start of one request and the next, including those of
+    /// dependencies, flows, tests and benchmarks.
+    pub delay: Duration,
+    /// Record executed requests in the `history` folder.
+    pub history: bool,
+    /// Store the cookies responses set in `cookies.json`, and send them with later requests.
+    pub cookies: bool,
+    /// Cache responses that have an `ETag` or `Last-Modified` header in the `cache` folder, and
+    /// use them when the server answers that they're still current.
+    pub cache: bool,
+    /// Evaluate the `@assert` directives of executed requests.
+    pub check: bool,
+    /// Save the response body of an executed or replayed request to a file, or print it as it
+    /// arrives, instead of reading it first. Dependencies, flows and tests are unaffected.
+    pub output: Option<Output>,
+    /// Variables that replace those of `@graphql` requests.
+    pub gql_variables: HashMap<String, serde_json::Value>,
+    /// Print only the part of a JSON response body this JSONPath expression matches.
+    pub filter: Option<String>,
+    /// Close the WebSocket of a `WS` request after this many messages, rather than when the
+    /// server does.
+    pub ws_count: Option<usize>,
+    /// Time each phase of a request, sending it over a connection of its own.
+    pub timings: bool,
+    /// Ask for a compressed response with `Accept-Encoding`.
+    pub compressed: bool,
+    /// Set a `Content-Type` for bodies without one, from what they look like.
+    pub infer_content_type: bool,
+    /// Check that bodies sent as JSON are valid JSON before sending them.
+    pub validate_body: bool,
 }
 
+/// Folders inside the reqq directory that hold reqq's own files rather than requests.
+const RESERVED_FOLDERS: [&str; 9] = [
+    "envs",
+    "files",
+    "flows",
+    "history",
+    tokens::FOLDER,
+    cache::FOLDER,
+    hooks::FOLDER,
+    snapshot::FOLDER,
+    template::PARTIALS_FOLDER,
+];
+
+/// Files inside the reqq directory that hold reqq's own data rather than requests.
+const RESERVED_FILES: [&str; 4] = [
+    session::FILE_NAME,
+    cookies::FILE_NAME,
+    config::FILE_NAME,
+    vault::FILE_NAME,
+];
+
 impl<'a> Reqq<'a> {
     // TODO: Decouple the IO portions of this somehow?
     /// Takes a path to a reqq directory and builds out a Reqq object loaded with
     /// all available request and environment files.
     pub fn new(opts: ReqqOpts<'a>) -> Result<Self> {
-        let dir = opts.dir;
+        let dir = match opts.dir.trim_end_matches('/') {
+            "" => opts.dir,
+            trimmed => trimmed,
+        };
 
         let fpaths = get_all_fpaths(dir);
-        let env_folder = format!("{}/{}", dir, "envs");
+        let reserved_folders: Vec<String> = RESERVED_FOLDERS
+            .iter()
+            .map(|folder| format!("{}/{}/", dir, folder))
+            .collect();
+        let reserved_files: Vec<String> = RESERVED_FILES
+            .iter()
+            .map(|file| format!("{}/{}", dir, file))
+            .collect();
 
         // Get request files.
         let reqs: Vec<Request> = fpaths
             .clone()
             .into_iter()
             .filter_map(|f| {
-                if f.starts_with(env_folder.as_str()) {
+                if reserved_folders
+                    .iter()
+                    .any(|folder| f.starts_with(folder.as_str()))
+                    || reserved_files.contains(&f)
+                {
                     return None;
                 }
-                Some(Request::new(f))
+                Some(f)
             })
+            .flat_map(file_requests)
             .collect();
 
         // Get environments.
-        let envs: Vec<Env> = fpaths
-            .into_iter()
-            .filter_map(|f| {
-                if !f.starts_with(env_folder.as_str()) {
-                    return None;
-                }
-                Some(Env::new(f))
-            })
-            .collect();
+        let envs = Env::find_all(dir);
+
+        let config = Config::load(dir)?;
 
         Ok(Reqq {
             dir,
             reqs,
             envs,
-            raw: opts.raw,
+            render: RenderOpts {
+                parts: opts.parts,
+                raw: opts.raw,
+                html_text: opts.html_text,
+                color: opts.color,
+            },
+            verbose: opts.verbose,
+            trailing_newline: opts.trailing_newline,
+            timeout: opts.timeout.or(config.timeout),
+            client: ClientOpts {
+                verbose: opts.verbose,
+                ..opts.client
+            },
+            executor: Executor {
+                retry: opts.retry,
+                pacer: Pacer::new(opts.delay),
+                clients: ClientPool::default(),
+                verbose: opts.verbose,
+            },
+            history: History::new(dir),
+            record_history: opts.history,
+            session: Session::new(dir),
+            cookie_jar: CookieJar::new(dir),
+            use_cookies: opts.cookies,
+            cache: opts.cache.then(|| ResponseCache::new(dir)),
+            tokens: match config.secret_store {
+                Provider::Keyring => TokenCache::in_keyring(dir),
+                _ => TokenCache::new(dir),
+            },
+            secrets: config.secret_store.open(dir),
+            output: opts.output,
+            gql_variables: opts.gql_variables,
+            check: opts.check,
+            filter: opts.filter,
+            ws_count: opts.ws_count,
+            timings: opts.timings,
+            compressed: opts.compressed,
+            infer_content_type: opts.infer_content_type,
+            validate_body: opts.validate_body,
+            config,
         })
     }
 
@@ -67,6 +373,30 @@ impl<'a> Reqq<'a> {
             .collect()
     }
 
+    /// The names of the requests with at least one of the tags, or of all of them when no
+    /// tags are given.
+    pub fn list_tagged(&self, tags: &[String]) -> Result<Vec<String>> {
+        let mut names = vec![];
+        for mut req in self.requests() {
+            if req.has_any_tag(tags)? {
+                names.push(req.name(self.dir));
+            }
+        }
+        Ok(names)
+    }
+
+    /// The requests with at least one of the tags, or all of them when no tags are given, with
+    /// their methods, hosts and descriptions.
+    pub fn listing(&self, tags: &[String]) -> Result<Listing> {
+        let mut requests = vec![];
+        for mut req in self.requests() {
+            if req.has_any_tag(tags)? {
+                requests.push(req.listed(self.dir)?);
+            }
+        }
+        Ok(Listing { requests })
+    }
+
     /// Provide a list of all available environment names.
     pub fn list_envs(&self) -> Vec<String> {
         self.envs
@@ -76,21 +406,1831 @@ impl<'a> Reqq<'a> {
             .collect()
     }
 
-    /// Executes a request specified by name, optionally with an environment.
-    pub fn execute(&self, req_name: &str, env_name: Option<String>, extra_args: HashMap<String, serde_json::Value>) -> Result<String> {
-        let mut req = self.get_req(req_name)?;
-        let maybe_env = env_name.map(|n| self.get_env(n)).unwrap();
-        let resp = req.execute(maybe_env, extra_args)?;
-        let result = format_response(resp, self.raw)?;
+    /// Loads an environment specified by name and lists its variables.
+    pub fn show_env(&self, env_name: &str) -> Result<String> {
+        let mut env = self
+            .get_env(env_name.to_owned())
+            .ok_or_else(|| ReqqError::EnvNotFound(env_name.to_owned()))?;
+        env.load()?;
+        let pattern = self
+            .config
+            .secret_pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()?;
+        env.to_display_string(pattern.as_ref())
+    }
+
+    /// Executes a request specified by name, optionally with an environment. Without an
+    /// environment name the `default` environment is used, if there is one.
+    ///
+    /// `captures` are variable names and JSONPath expressions, evaluated against the body of a
+    /// successful response and saved to the session for later invocations.
+    pub fn execute(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+        captures: &[(String, String)],
+    ) -> Result<ExecutionResult> {
+        let maybe_env = self.resolve_env(env_name)?;
+        let args = self.with_session(extra_args)?;
+        let (req, entry) =
+            self.execute_with_dependencies(req_name, &maybe_env, args, self.output.as_ref())?;
+
+        let resp = &entry.response;
+        if !captures.is_empty() && !resp.status.is_client_error() && !resp.status.is_server_error()
+        {
+            let vars = extract_vars(resp, captures)?;
+            if self.verbose {
+                for (name, value) in vars.iter() {
+                    eprintln!("* Captured {} = {}", name, value);
+                }
+            }
+            self.session.set(vars)?;
+        }
+
+        self.result(&req, entry)
+    }
+
+    /// Executes a request, after its dependencies, and then requests the pages after its
+    /// response one by one, passing each result to `on_page` as it arrives. Stops at the last
+    /// page, at a page that fails, after `max_pages`, or when a next page URL comes round
+    /// again. Returns how many pages were requested.
+    pub fn paginate(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+        pagination: &Pagination,
+        max_pages: Option<usize>,
+        mut on_page: impl FnMut(ExecutionResult),
+    ) -> Result<usize> {
+        if max_pages == Some(0) {
+            return Err(anyhow!("The number of pages must be at least 1."));
+        }
+        let maybe_env = self.resolve_env(env_name)?;
+        let env_name = maybe_env.as_ref().map(|e| e.name(self.dir));
+        let args =
+            self.execute_dependencies(req_name, &maybe_env, self.with_session(extra_args)?)?;
+        let mut req = self.parse_req(req_name, &maybe_env, args)?;
+
+        let mut seen = vec![];
+        loop {
+            let page = seen.len() + 1;
+            let url = req
+                .url()
+                .cloned()
+                .ok_or_else(|| anyhow!("Request '{}' has no URL.", req_name))?;
+            if self.verbose && page > 1 {
+                eprintln!("* Requesting page {}", page);
+            }
+            let entry = self.exchange(req_name, &req, env_name.clone(), None)?;
+            let next = pagination.next_url(&url, &entry.response);
+            let result = self.result(&req, entry)?;
+            let failed = result.status_error(false).is_some()
+                || result.check.as_ref().is_some_and(|check| !check.passed());
+            on_page(result);
+
+            seen.push(url);
+            let Some(next) = next.filter(|next| !failed && !seen.contains(next)) else {
+                return Ok(page);
+            };
+            if max_pages == Some(page) {
+                return Ok(page);
+            }
+            req.set_url(next);
+        }
+    }
+
+    /// Runs a flow from the `flows` folder: executes its steps in order, stopping at the
+    /// first one that fails. Values extracted from a step's response are available to the
+    /// steps after it.
+    pub fn run_flow(
+        &self,
+        flow_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<FlowResult> {
+        let flow = Flow::load(self.dir, flow_name)?;
+        let maybe_env = self.resolve_env(env_name)?;
+        let mut context = self.with_session(extra_args)?;
+        self.executor.pacer.limit(flow.rate_limit);
+        let mut result = FlowResult {
+            name: flow.name,
+            total: flow.steps.len(),
+            steps: vec![],
+        };
+
+        for step in flow.steps {
+            let mut args = context.clone();
+            args.extend(step.args);
+
+            let start = Instant::now();
+            let outcome = self.execute_with_dependencies(&step.request, &maybe_env, args, None);
+            let duration_ms = start.elapsed().as_millis() as u64;
+
+            let (status, error) = match outcome {
+                Ok((_, Entry { response: resp, .. })) => match check_step(&resp, &step.extract) {
+                    Ok(vars) => {
+                        context.extend(vars);
+                        (Some(resp.status), None)
+                    }
+                    Err(e) => (Some(resp.status), Some(e)),
+                },
+                Err(e) => (None, Some(e)),
+            };
+            let failed = error.is_some();
+            result.steps.push(StepResult {
+                request: step.request,
+                status,
+                duration_ms,
+                error: error.map(|e| e.to_string()),
+            });
+            if failed {
+                break;
+            }
+        }
+        self.executor.pacer.limit(None);
+
+        Ok(result)
+    }
+
+    /// Runs requests as a test suite, in name order. A request passes if all of its `@assert`
+    /// directives hold, or, without any, if its response status isn't 4xx or 5xx.
+    pub fn test(
+        &self,
+        opts: SuiteOpts,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<SuiteResult> {
+        let maybe_env = self.resolve_env(env_name)?;
+        let args = self.with_session(extra_args)?;
+
+        let mut names = vec![];
+        for mut req in self.requests() {
+            let name = req.name(self.dir);
+            let selected =
+                opts.patterns.is_empty() || opts.patterns.iter().any(|p| glob::matches(p, &name));
+            if selected && req.has_any_tag(&opts.tags)? {
+                names.push(name);
+            }
+        }
+        if names.is_empty() {
+            return Err(anyhow!("No requests match."));
+        }
+        names.sort();
+
+        let mut result = SuiteResult {
+            cases: vec![],
+            skipped: vec![],
+        };
+        let mut names = names.into_iter();
+        for name in names.by_ref() {
+            let case = self.run_case(name, &maybe_env, args.clone(), true, opts.snapshot);
+            let failed = !case.passed();
+            result.cases.push(case);
+            if failed && opts.fail_fast {
+                break;
+            }
+        }
+        result.skipped = names.collect();
+
         Ok(result)
     }
 
+    /// Executes the requests matching any of the patterns, on up to `parallel` threads at once.
+    /// The results are in name order. A request fails if it can't be sent or its response
+    /// status is 4xx or 5xx, or, when checking assertions, if one of them doesn't hold.
+    pub fn run_requests(
+        &self,
+        patterns: &[String],
+        parallel: usize,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<SuiteResult> {
+        let maybe_env = self.resolve_env(env_name)?;
+        let args = self.with_session(extra_args)?;
+
+        let mut names = self.list_reqs();
+        names.retain(|name| patterns.iter().any(|p| glob::matches(p, name)));
+        if names.is_empty() {
+            return Err(anyhow!("No requests match."));
+        }
+        names.sort();
+
+        // Workers take the next request until there are none left.
+        let next = AtomicUsize::new(0);
+        let worker = || {
+            let mut cases = vec![];
+            while let Some(name) = names.get(next.fetch_add(1, Ordering::Relaxed)) {
+                cases.push(self.run_case(
+                    name.clone(),
+                    &maybe_env,
+                    args.clone(),
+                    self.check,
+                    SnapshotMode::Off,
+                ));
+            }
+            cases
+        };
+        let mut cases = thread::scope(|scope| {
+            let workers: Vec<_> = (0..parallel.clamp(1, names.len()))
+                .map(|_| scope.spawn(worker))
+                .collect();
+            workers
+                .into_iter()
+                .map(|w| w.join().map_err(|_| anyhow!("A request thread panicked.")))
+                .collect::<Result<Vec<_>>>()
+        })?
+        .concat();
+        cases.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(SuiteResult {
+            cases,
+            skipped: vec![],
+        })
+    }
+
+    /// Executes a request after its dependencies. Without `check`, or any `@assert` or
+    /// `@schema` directives, it passes if its response status isn't 4xx or 5xx. Its response
+    /// is compared against its snapshot too, unless `snapshot` is off.
+    fn run_case(
+        &self,
+        name: String,
+        maybe_env: &Option<Env>,
+        args: HashMap<String, serde_json::Value>,
+        check: bool,
+        snapshot: SnapshotMode,
+    ) -> CaseResult {
+        let start = Instant::now();
+        let outcome = self.execute_with_dependencies(&name, maybe_env, args, None);
+        let duration_ms = start.elapsed().as_millis() as u64;
+
+        let (req, resp) = match outcome {
+            Ok((req, Entry { response: resp, .. })) => (req, resp),
+            Err(e) => {
+                return CaseResult {
+                    name,
+                    status: None,
+                    duration_ms,
+                    check: None,
+                    error: Some(e.to_string()),
+                    snapshot: None,
+                }
+            }
+        };
+        let (snapshot, snapshot_error) =
+            match Snapshots::new(self.dir).check(&name, &resp, snapshot) {
+                Ok(outcome) => (outcome, None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+        let check = (check && (!req.asserts().is_empty() || req.schema().is_some()))
+            .then(|| check_response(&req, &resp));
+        let status_error = match req.expect() {
+            [] => (check.is_none()
+                && (resp.status.is_client_error() || resp.status.is_server_error()))
+            .then(|| format!("Request failed with status {}.", resp.status)),
+            expect => (!expect.contains(&resp.status))
+                .then(|| format!("Expected status {}, got {}.", statuses(expect), resp.status)),
+        };
+        CaseResult {
+            name,
+            status: Some(resp.status),
+            duration_ms,
+            check,
+            error: status_error.or(snapshot_error),
+            snapshot,
+        }
+    }
+
+    /// Saves a response as the snapshot of a request, for `reqq test --snapshot` to compare
+    /// against. Returns the path of the snapshot file.
+    pub fn save_snapshot(&self, req_name: &str, resp: &Response) -> Result<PathBuf> {
+        Snapshots::new(self.dir).save(req_name, resp)
+    }
+
+    /// A mock server answering the method and path of each request with its snapshot, or else
+    /// its last response in the history, and the requests that can't be served, since they
+    /// have neither or aren't HTTP requests.
+    pub fn mock_server(&self) -> Result<(MockServer, Vec<String>)> {
+        let snapshots = Snapshots::new(self.dir);
+        let history = self.history.list()?;
+        let mut routes = vec![];
+        let mut skipped = vec![];
+        for mut req in self.requests() {
+            let name = req.name(self.dir);
+            let stored = match snapshots.load(&name)? {
+                Some(resp) => Some(resp),
+                None => history
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.request == name)
+                    .map(|entry| entry.response.clone()),
+            };
+            let fstr = req.raw()?;
+            match stored.and_then(|resp| Route::new(name.clone(), &fstr, resp)) {
+                Some(route) => routes.push(route),
+                None => skipped.push(name),
+            }
+        }
+        skipped.sort();
+        Ok((MockServer::new(routes), skipped))
+    }
+
+    /// A reverse proxy forwarding requests to `target`, and writing each one as a request file
+    /// in `folder`, with its response as the snapshot when `snapshot` is set. Redirects are
+    /// passed back to the client rather than followed.
+    pub fn recorder(&self, target: &str, folder: &str, snapshot: bool) -> Result<Recorder> {
+        let url =
+            Url::parse(target).map_err(|e| anyhow!("Invalid target URL '{}': {}", target, e))?;
+        if !matches!(url.scheme(), "http" | "https") || url.query().is_some() {
+            return Err(anyhow!(
+                "The target must be an http or https URL without a query, like \
+                 https://api.example.com."
+            ));
+        }
+        // Checks the folder is a valid request folder, outside reqq's own folders.
+        self.new_request_fpath(&format!("{}/request", folder.trim_end_matches('/')), true)?;
+        let client = ClientOpts {
+            redirects: Some(Redirects::None),
+            ..self.client.clone()
+        };
+        Ok(Recorder::new(
+            target.to_owned(),
+            client.build()?,
+            self.dir,
+            folder.trim_end_matches('/'),
+            snapshot.then(|| Snapshots::new(self.dir)),
+        ))
+    }
+
+    /// Whether there is a flow with this name in the `flows` folder.
+    pub fn has_flow(&self, name: &str) -> bool {
+        Flow::exists(self.dir, name)
+    }
+
+    /// Provide all recorded executions, oldest first.
+    pub fn history(&self) -> Result<Vec<Entry>> {
+        self.history.list()
+    }
+
+    /// The cookies in the cookie jar that haven't expired.
+    pub fn cookies(&self) -> Result<Vec<Cookie>> {
+        self.cookie_jar.load()
+    }
+
+    /// Removes every cookie from the cookie jar.
+    pub fn clear_cookies(&self) -> Result<()> {
+        self.cookie_jar.clear()
+    }
+
+    /// The names of the secrets in the secret store.
+    pub fn secret_names(&self) -> Result<Vec<String>> {
+        self.secrets.names()
+    }
+
+    /// Reads a secret from the secret store.
+    pub fn secret(&self, name: &str) -> Result<Option<String>> {
+        let secrets = self.secrets.get(&[name.to_owned()])?;
+        Ok(secrets
+            .get(name)
+            .and_then(|value| value.as_str())
+            .map(str::to_owned))
+    }
+
+    /// Stores a secret in the secret store, creating the vault if needed.
+    pub fn set_secret(&self, name: &str, value: &str) -> Result<()> {
+        self.secrets.set(name, value)
+    }
+
+    /// Removes a secret from the secret store. Returns whether it was there.
+    pub fn remove_secret(&self, name: &str) -> Result<bool> {
+        self.secrets.remove(name)
+    }
+
+    /// Adds the stored secrets that the request uses and nothing else defines, so a vault's
+    /// passphrase is only needed for requests that use them. They're masked in output.
+    fn add_stored_secrets(
+        &self,
+        req: &mut Request,
+        maybe_env: &Option<Env>,
+        args: &mut HashMap<String, serde_json::Value>,
+    ) -> Result<()> {
+        let names = self.secrets.names()?;
+        if names.is_empty() {
+            return Ok(());
+        }
+        let wanted: Vec<String> = req
+            .missing_variables(maybe_env.clone(), args.clone())?
+            .into_iter()
+            .map(|missing| {
+                missing
+                    .name
+                    .split(['.', '['])
+                    .next()
+                    .unwrap_or_default()
+                    .to_owned()
+            })
+            .filter(|root| names.contains(root))
+            .collect();
+        if wanted.is_empty() {
+            return Ok(());
+        }
+        for (name, value) in self.secrets.get(&wanted)? {
+            redact::add(&value);
+            args.insert(name, value);
+        }
+        Ok(())
+    }
+
+    /// Removes every response cached with `--cache`.
+    pub fn clear_cache(&self) -> Result<()> {
+        ResponseCache::new(self.dir).clear()
+    }
+
+    /// Executes a request recorded in the history again, exactly as it was rendered then.
+    pub fn replay(&self, id: &str) -> Result<ExecutionResult> {
+        let entry = self.history.get(id)?;
+        let mut req = Request::from_rendered(self.history_fpath(&entry), entry.rendered);
+        let maybe_env = entry.env.clone().and_then(|name| self.get_env(name));
+        self.configure(&mut req, &maybe_env)?;
+        req.parse(None, HashMap::new())?;
+        self.send(&entry.request, &req, entry.env)
+    }
+
+    /// Prints the response recorded for a history entry, without sending anything.
+    pub fn replay_print(&self, id: &str) -> Result<ExecutionResult> {
+        let entry = self.history.get(id)?;
+        let mut req = Request::from_rendered(self.history_fpath(&entry), entry.rendered.clone());
+        req.set_base_url(self.config.base_url.clone());
+        req.parse(None, HashMap::new())?;
+        self.result(&req, entry)
+    }
+
+    /// Executes a request, after first executing the requests named in its `@depends`
+    /// directives. The responses of dependencies are available to the templates of the
+    /// requests that depend on them. The output, if any, only applies to the request itself.
+    fn execute_with_dependencies(
+        &self,
+        req_name: &str,
+        maybe_env: &Option<Env>,
+        extra_args: HashMap<String, serde_json::Value>,
+        output: Option<&Output>,
+    ) -> Result<(Request, Entry)> {
+        let env_name = maybe_env.as_ref().map(|e| e.name(self.dir));
+        let args = self.execute_dependencies(req_name, maybe_env, extra_args)?;
+        let req = self.parse_req(req_name, maybe_env, args)?;
+        let entry = self.exchange(req_name, &req, env_name, output)?;
+        Ok((req, entry))
+    }
+
+    /// Executes the dependencies of a request, and returns the args with their responses added.
+    fn execute_dependencies(
+        &self,
+        req_name: &str,
+        maybe_env: &Option<Env>,
+        mut extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<HashMap<String, serde_json::Value>> {
+        let env_name = maybe_env.as_ref().map(|e| e.name(self.dir));
+        for dep_name in self.dependency_order(req_name)? {
+            let dep = self.parse_dependency(&dep_name, maybe_env, &extra_args)?;
+            let entry = self.exchange(&dep_name, &dep, env_name.clone(), None)?;
+            extra_args.insert(
+                dep_name.clone(),
+                dependency_value(&dep_name, &entry.response)?,
+            );
+        }
+        Ok(extra_args)
+    }
+
+    fn parse_dependency(
+        &self,
+        dep_name: &str,
+        maybe_env: &Option<Env>,
+        extra_args: &HashMap<String, serde_json::Value>,
+    ) -> Result<Request> {
+        if self.verbose {
+            eprintln!("* Running dependency {}", dep_name);
+        }
+        self.parse_req(dep_name, maybe_env, extra_args.clone())
+    }
+
+    /// Sends a request `requests` times, up to `concurrency` at once, and measures how long
+    /// each response takes. Its dependencies are executed once beforehand. The requests aren't
+    /// recorded in the history or retried.
+    pub fn bench(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+        requests: usize,
+        concurrency: usize,
+    ) -> Result<BenchResult> {
+        if requests == 0 {
+            return Err(anyhow!("The number of requests must be at least 1."));
+        }
+        let maybe_env = self.resolve_env(env_name)?;
+        let env_name = maybe_env.as_ref().map(|e| e.name(self.dir));
+        let args =
+            self.execute_dependencies(req_name, &maybe_env, self.with_session(extra_args)?)?;
+        let req = self.prepare(
+            &self.parse_req(req_name, &maybe_env, args)?,
+            env_name.as_deref(),
+        )?;
+        let client = self.executor.clients.get(&req.url_client_opts())?;
+
+        let next = AtomicUsize::new(0);
+        let worker = || {
+            let mut samples = vec![];
+            while next.fetch_add(1, Ordering::Relaxed) < requests {
+                self.executor.pace();
+                let start = Instant::now();
+                samples.push(match req.send(&client).and_then(Response::read) {
+                    Ok((resp, _)) => Sample::Response {
+                        status: resp.status,
+                        latency: start.elapsed(),
+                    },
+                    Err(e) => Sample::Error(e.to_string()),
+                });
+            }
+            samples
+        };
+        let start = Instant::now();
+        let samples = thread::scope(|scope| {
+            let workers: Vec<_> = (0..concurrency.clamp(1, requests))
+                .map(|_| scope.spawn(worker))
+                .collect();
+            workers
+                .into_iter()
+                .map(|w| w.join().map_err(|_| anyhow!("A request thread panicked.")))
+                .collect::<Result<Vec<_>>>()
+        })?
+        .concat();
+
+        Ok(BenchResult {
+            concurrency: concurrency.clamp(1, requests),
+            elapsed: start.elapsed(),
+            samples,
+        })
+    }
+
+    /// Like `execute_with_dependencies`, but sends the requests with reqwest's async client.
+    #[cfg(feature = "async")]
+    async fn execute_with_dependencies_async(
+        &self,
+        req_name: &str,
+        maybe_env: &Option<Env>,
+        mut extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<(Request, Response)> {
+        let env_name = maybe_env.as_ref().map(|e| e.name(self.dir));
+
+        for dep_name in self.dependency_order(req_name)? {
+            let dep = self.parse_dependency(&dep_name, maybe_env, &extra_args)?;
+            let entry = self
+                .exchange_async(&dep_name, &dep, env_name.clone())
+                .await?;
+            extra_args.insert(
+                dep_name.clone(),
+                dependency_value(&dep_name, &entry.response)?,
+            );
+        }
+
+        let req = self.parse_req(req_name, maybe_env, extra_args)?;
+        let entry = self.exchange_async(req_name, &req, env_name).await?;
+        Ok((req, entry.response))
+    }
+
+    /// Parses a request to be sent, after running its pre hooks.
+    fn parse_req(
+        &self,
+        req_name: &str,
+        maybe_env: &Option<Env>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<Request> {
+        let mut req = self.get_req(req_name)?;
+        self.configure(&mut req, maybe_env)?;
+        let args = self.run_pre_hooks(req_name, &mut req, maybe_env, extra_args)?;
+        req.parse(maybe_env.clone(), args)?;
+        Ok(req)
+    }
+
+    /// Runs the pre hooks of the collection and then those of the request, and returns the
+    /// args with the variables they printed added, and the stored secrets the request uses.
+    /// Args that are already set take precedence, except over the request's pre-script,
+    /// which runs last and can change any variable.
+    fn run_pre_hooks(
+        &self,
+        req_name: &str,
+        req: &mut Request,
+        maybe_env: &Option<Env>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<HashMap<String, serde_json::Value>> {
+        let (pre, _) = req.hooks()?;
+        let env_name = maybe_env.as_ref().map(|e| e.name(self.dir));
+        let context = Context {
+            request: req_name,
+            env: env_name.as_deref(),
+        };
+        let mut args = HashMap::new();
+        for (command, dir) in self.with_dirs(&self.config.pre, &pre, req) {
+            if self.verbose {
+                eprintln!("* Running pre hook {}", command);
+            }
+            args.extend(hooks::pre(command, dir, &context)?);
+        }
+        args.extend(extra_args);
+        self.add_stored_secrets(req, maybe_env, &mut args)?;
+
+        let Some(source) = req.scripts()?.0 else {
+            return Ok(args);
+        };
+        let script = Script::parse(&source).map_err(|e| anyhow!("Invalid pre-script, {}", e))?;
+        let mut vars = serde_json::Map::new();
+        if let Some(mut env) = maybe_env.clone() {
+            env.load()?;
+            vars.extend(env.to_hashmap()?);
+        }
+        vars.extend(args);
+        let globals = serde_json::Map::from_iter([
+            ("vars".to_owned(), serde_json::Value::Object(vars)),
+            (
+                "request".to_owned(),
+                serde_json::json!({ "name": req_name, "env": env_name }),
+            ),
+        ]);
+        match script
+            .run(globals)
+            .map_err(|e| anyhow!("The pre-script failed on {}", e))?
+            .remove("vars")
+        {
+            Some(serde_json::Value::Object(vars)) => Ok(vars.into_iter().collect()),
+            _ => Err(anyhow!("The pre-script must leave `vars` an object.")),
+        }
+    }
+
+    /// Runs the request's post-script, and then the post hooks of the collection and those of
+    /// the request, with the response. Variables the script sets are saved to the session.
+    fn run_post_hooks(
+        &self,
+        req_name: &str,
+        req: &mut Request,
+        env_name: Option<&str>,
+        resp: &Response,
+    ) -> Result<()> {
+        if let Some(source) = req.scripts()?.1 {
+            self.run_post_script(&source, req_name, req, env_name, resp)?;
+        }
+        let (_, post) = req.hooks()?;
+        let context = Context {
+            request: req_name,
+            env: env_name,
+        };
+        for (command, dir) in self.with_dirs(&self.config.post, &post, req) {
+            if self.verbose {
+                eprintln!("* Running post hook {}", command);
+            }
+            hooks::post(command, dir, &context, &resp.to_template_value())?;
+        }
+        Ok(())
+    }
+
+    fn run_post_script(
+        &self,
+        source: &str,
+        req_name: &str,
+        req: &Request,
+        env_name: Option<&str>,
+        resp: &Response,
+    ) -> Result<()> {
+        let script = Script::parse(source).map_err(|e| anyhow!("Invalid post-script, {}", e))?;
+        let inner = req
+            .inner()
+            .ok_or_else(|| anyhow!("Request has not been parsed."))?;
+        let session = self.session.load()?;
+        let globals = serde_json::Map::from_iter([
+            (
+                "vars".to_owned(),
+                serde_json::Value::Object(session.clone().into_iter().collect()),
+            ),
+            (
+                "request".to_owned(),
+                serde_json::json!({
+                    "name": req_name,
+                    "env": env_name,
+                    "method": inner.method().as_str(),
+                    "url": inner.url().as_str(),
+                    "headers": headers_to_json(inner.headers().iter().map(|(k, v)| (k, v))),
+                }),
+            ),
+            ("response".to_owned(), resp.to_template_value()),
+        ]);
+        let vars = match script
+            .run(globals)
+            .map_err(|e| anyhow!("The post-script failed on {}", e))?
+            .remove("vars")
+        {
+            Some(serde_json::Value::Object(vars)) => vars,
+            _ => return Err(anyhow!("The post-script must leave `vars` an object.")),
+        };
+
+        let changed: Vec<(String, serde_json::Value)> = vars
+            .into_iter()
+            .filter(|(name, value)| session.get(name) != Some(value))
+            .collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        if self.verbose {
+            for (name, value) in changed.iter() {
+                eprintln!("* Saved {} = {}", name, redact::redact(&value.to_string()));
+            }
+        }
+        self.session.set(changed)
+    }
+
+    /// Hook commands with the folder each runs in: the reqq directory for the collection's,
+    /// and the request file's folder for its own.
+    fn with_dirs<'h>(
+        &'h self,
+        collection: &'h [String],
+        own: &'h [String],
+        req: &'h Request,
+    ) -> impl Iterator<Item = (&'h str, &'h Path)> {
+        let collection = collection
+            .iter()
+            .map(|command| (command.as_str(), Path::new(self.dir)));
+        collection.chain(own.iter().map(|command| (command.as_str(), req.dir())))
+    }
+
+    /// Whether a request has pre hooks or a pre-script, whose variables are only known once
+    /// they run.
+    fn has_pre_hooks(&self, req_name: &str) -> Result<bool> {
+        let mut req = self.get_req(req_name)?;
+        Ok(!self.config.pre.is_empty() || !req.hooks()?.0.is_empty() || req.scripts()?.0.is_some())
+    }
+
+    /// Sends a parsed request, recording it in the history, and renders its response.
+    fn send(
+        &self,
+        req_name: &str,
+        req: &Request,
+        env_name: Option<String>,
+    ) -> Result<ExecutionResult> {
+        let entry = self.exchange(req_name, req, env_name, self.output.as_ref())?;
+        self.result(req, entry)
+    }
+
+    /// Renders a response, and checks it against the request's assertions if asked to.
+    fn result(&self, req: &Request, entry: Entry) -> Result<ExecutionResult> {
+        let resp = &entry.response;
+        let failed = resp.status.is_client_error() || resp.status.is_server_error();
+        // Failed responses are printed whole when they can't be filtered, to show the error.
+        let output = match self
+            .filter
+            .as_ref()
+            .map(|path| filter_response(resp, path, self.render.color))
+        {
+            Some(Ok(output)) => output,
+            Some(Err(e)) if !failed => return Err(e),
+            _ => render_response(resp, self.render)?,
+        };
+        let check = self.check.then(|| check_response(req, resp));
+        Ok(ExecutionResult {
+            status: resp.status,
+            output,
+            check,
+            expect: req.expect().to_vec(),
+            method: req.method().map(Method::to_string).unwrap_or_default(),
+            url: req.url().map(ToString::to_string).unwrap_or_default(),
+            request_headers: req.headers().map(<[_]>::to_vec).unwrap_or_default(),
+            entry,
+        })
+    }
+
+    /// Sends a parsed request and reads its response, retrying according to the retry policy,
+    /// and records both in the history.
+    fn exchange(
+        &self,
+        req_name: &str,
+        req: &Request,
+        env_name: Option<String>,
+        output: Option<&Output>,
+    ) -> Result<Entry> {
+        let (mut req, cached) = self.prepare_exchange(req, env_name.as_deref(), output)?;
+        self.executor.pace();
+        let timestamp = time::now_millis();
+        let start = Instant::now();
+        let (resp, timings) = if req.is_websocket() {
+            (websocket::open(&req, self.ws_count)?, None)
+        } else if req.is_grpc() {
+            (grpc::call(&req)?, None)
+        } else if self.timings {
+            let (resp, timings) =
+                timings::send(&req, &self.executor.clients.get(&req.url_client_opts())?)?;
+            (resp, Some(timings))
+        } else {
+            (self.executor.send(&req, output)?, None)
+        };
+        let resp = self.revalidated(&req, output, cached, resp)?;
+        let mut entry = self.finish_exchange(
+            req_name,
+            &mut req,
+            env_name,
+            timestamp,
+            start.elapsed(),
+            resp,
+        )?;
+        entry.timings = timings;
+        Ok(entry)
+    }
+
+    /// Like `exchange`, but sends the request with reqwest's async client.
+    #[cfg(feature = "async")]
+    async fn exchange_async(
+        &self,
+        req_name: &str,
+        req: &Request,
+        env_name: Option<String>,
+    ) -> Result<Entry> {
+        // Tokens are fetched with the blocking client, which can't run inside an async runtime.
+        if let Some(Auth::OAuth2(_)) = req.auth() {
+            return Err(anyhow!(
+                "Request '{}' uses OAuth2 auth, which can't be sent asynchronously.",
+                req_name
+            ));
+        }
+        if req.is_websocket() {
+            return Err(anyhow!(
+                "Request '{}' opens a WebSocket, which can't be done asynchronously.",
+                req_name
+            ));
+        }
+        if req.is_grpc() {
+            return Err(anyhow!(
+                "Request '{}' is a gRPC call, which can't be made asynchronously.",
+                req_name
+            ));
+        }
+        if req.unix_socket().is_some() {
+            return Err(anyhow!(
+                "Request '{}' is sent over a Unix socket, which can't be done asynchronously.",
+                req_name
+            ));
+        }
+        let (mut req, cached) = self.prepare_exchange(req, env_name.as_deref(), None)?;
+        self.executor.pace_async().await;
+        let timestamp = time::now_millis();
+        let start = Instant::now();
+        let resp = self.executor.send_async(&req).await?;
+        let resp = self.revalidated(&req, None, cached, resp)?;
+        self.finish_exchange(
+            req_name,
+            &mut req,
+            env_name,
+            timestamp,
+            start.elapsed(),
+            resp,
+        )
+    }
+
+    /// What `exchange` and `exchange_async` do before sending a request: prepares it, adds the
+    /// conditional headers of its cached response, if it has one, and logs it.
+    fn prepare_exchange(
+        &self,
+        req: &Request,
+        env_name: Option<&str>,
+        output: Option<&Output>,
+    ) -> Result<(Request, Option<Response>)> {
+        let mut req = self.prepare(req, env_name)?;
+        let cached = self.cached_response(&mut req, output)?;
+        self.log_request(&req);
+        Ok((req, cached))
+    }
+
+    /// What `exchange` and `exchange_async` do after reading a response: record it, and run
+    /// the post hooks.
+    fn finish_exchange(
+        &self,
+        req_name: &str,
+        req: &mut Request,
+        env_name: Option<String>,
+        timestamp: u64,
+        duration: Duration,
+        resp: Response,
+    ) -> Result<Entry> {
+        let entry = self.record(req_name, req, env_name.clone(), timestamp, duration, resp)?;
+        self.run_post_hooks(req_name, req, env_name.as_deref(), &entry.response)?;
+        Ok(entry)
+    }
+
+    /// The cached response to a request, if it has one. The request is then sent with
+    /// conditional headers, so the server can answer that the cached response is still current.
+    fn cached_response(
+        &self,
+        req: &mut Request,
+        output: Option<&Output>,
+    ) -> Result<Option<Response>> {
+        let Some(cache) = self.cache_for(req, output) else {
+            return Ok(None);
+        };
+        let cached = cache.get(req.rendered().unwrap_or_default())?;
+        for (name, value) in cached.iter().flat_map(cache::validators) {
+            req.add_default_header(name, value);
+        }
+        Ok(cached)
+    }
+
+    /// Answers a `304 Not Modified` with the cached response, and caches other responses that
+    /// can be.
+    fn revalidated(
+        &self,
+        req: &Request,
+        output: Option<&Output>,
+        cached: Option<Response>,
+        resp: Response,
+    ) -> Result<Response> {
+        let Some(cache) = self.cache_for(req, output) else {
+            return Ok(resp);
+        };
+        match cached {
+            Some(cached) if resp.status == StatusCode::NOT_MODIFIED => {
+                if self.verbose {
+                    eprintln!("* Not modified, using the cached response");
+                }
+                Ok(cached)
+            }
+            _ => {
+                cache.store(req.rendered().unwrap_or_default(), &resp)?;
+                Ok(resp)
+            }
+        }
+    }
+
+    /// The cache for responses to a request, when caching is on and the request is a GET or
+    /// HEAD whose response body is read rather than saved or streamed.
+    fn cache_for(&self, req: &Request, output: Option<&Output>) -> Option<&ResponseCache> {
+        let method = req
+            .method()
+            .filter(|method| **method == Method::GET || **method == Method::HEAD);
+        self.cache
+            .as_ref()
+            .filter(|_| output.is_none() && method.is_some())
+    }
+
+    fn log_request(&self, req: &Request) {
+        if !self.verbose {
+            return;
+        }
+        if let Some(inner) = req.inner() {
+            for line in redact::redact(&inner.to_string()).lines() {
+                eprintln!("> {}", line);
+            }
+        }
+    }
+
+    /// Stores the cookies a response sets, and records the exchange in the history. The entry
+    /// only gets an id when it's recorded.
+    fn record(
+        &self,
+        req_name: &str,
+        req: &Request,
+        env_name: Option<String>,
+        timestamp: u64,
+        duration: Duration,
+        resp: Response,
+    ) -> Result<Entry> {
+        if self.verbose {
+            eprintln!(
+                "* Completed in {} ms over {:?}",
+                duration.as_millis(),
+                resp.version
+            );
+        }
+        if let (true, Some(url)) = (self.use_cookies, req.url()) {
+            self.cookie_jar.store(url, &resp.headers)?;
+        }
+
+        let mut entry = Entry {
+            id: String::new(),
+            timestamp,
+            request: req_name.to_owned(),
+            env: env_name,
+            rendered: req.rendered().unwrap_or_default().to_owned(),
+            duration_ms: duration.as_millis() as u64,
+            response: resp,
+            timings: None,
+        };
+        if self.record_history {
+            // Secrets are masked in the history, so replays of such entries send the mask.
+            entry.id = self.history.record(Entry {
+                rendered: redact::redact(&entry.rendered),
+                response: redact::response(&entry.response),
+                ..entry.clone()
+            })?;
+            if self.verbose {
+                eprintln!("* Recorded as history entry {}", entry.id);
+            }
+        }
+
+        Ok(entry)
+    }
+
+    /// Writes a new request file with the given method and URL, for nested names like
+    /// `users/create` too. Existing requests are only overwritten with `force`. Returns the path
+    /// of the new file.
+    pub fn new_request(
+        &self,
+        name: &str,
+        method: &str,
+        url: Option<&str>,
+        force: bool,
+    ) -> Result<PathBuf> {
+        let method = Method::from_bytes(method.to_uppercase().as_bytes())
+            .map_err(|_| anyhow!("Invalid method '{}'.", method))?;
+        let fpath = self.new_request_fpath(name, force)?;
+        write_new(
+            &fpath,
+            &request_template(&method, url.unwrap_or("https://example.com")),
+        )?;
+        Ok(fpath)
+    }
+
+    /// Writes a new, empty JSON environment file. Existing environments are only overwritten
+    /// with `force`. Returns the path of the new file.
+    pub fn new_env(&self, name: &str, force: bool) -> Result<PathBuf> {
+        let name = name.trim_end_matches(".json");
+        if name
+            .split('/')
+            .any(|s| s.is_empty() || s == "." || s == "..")
+        {
+            return Err(anyhow!("Invalid env name '{}'.", name));
+        }
+        let fpath = Path::new(self.dir)
+            .join("envs")
+            .join(format!("{}.json", name));
+        if let Some(env) = self.get_env(name.to_owned()) {
+            if !force {
+                return Err(anyhow!("Env '{}' already exists.", name));
+            }
+            if Path::new(&env.fpath) != fpath {
+                return Err(anyhow!("Env '{}' is defined in '{}'.", name, env.fpath));
+            }
+        }
+        write_new(&fpath, "{}\n")?;
+        Ok(fpath)
+    }
+
+    /// Writes a request file for a curl command, named after its URL unless a name is given.
+    /// Returns the path of the new file.
+    pub fn import_curl(&self, command: &str, name: Option<&str>) -> Result<PathBuf> {
+        let curl = CurlCommand::parse(command)?;
+        let name = name.map_or_else(|| curl.default_name(), str::to_owned);
+        let fpath = self.new_request_fpath(&name, false)?;
+        write_new(&fpath, &curl.to_request_file()?)?;
+        Ok(fpath)
+    }
+
+    /// Writes the request files and environments of a Postman collection or environment
+    /// export. Returns the paths of the new files, and warnings about what couldn't be
+    /// converted.
+    pub fn import_postman(&self, fpath: &Path) -> Result<(Vec<PathBuf>, Vec<String>)> {
+        let export: serde_json::Value = serde_json::from_str(&fs::read_to_string(fpath)?)
+            .map_err(|e| anyhow!("'{}' isn't valid JSON: {}", fpath.display(), e))?;
+        self.write_import(postman::convert(&export)?)
+    }
+
+    /// Writes a request file for every operation of an OpenAPI 3 spec, in JSON or YAML, and an
+    /// environment with the URL of its first server. Returns the paths of the new files.
+    pub fn import_openapi(&self, fpath: &Path) -> Result<(Vec<PathBuf>, Vec<String>)> {
+        let fstr = fs::read_to_string(fpath)?;
+        let spec = if fstr.trim_start().starts_with('{') {
+            serde_json::from_str(&fstr)
+                .map_err(|e| anyhow!("'{}' isn't valid JSON: {}", fpath.display(), e))?
+        } else {
+            yaml::parse(&fstr)
+                .map_err(|e| anyhow!("'{}' isn't valid YAML: {}", fpath.display(), e))?
+        };
+        self.write_import(openapi::convert(&spec)?)
+    }
+
+    /// Writes a request file for every request recorded in a HAR file, like one saved from
+    /// browser dev tools. Returns the paths of the new files.
+    pub fn import_har(&self, fpath: &Path) -> Result<(Vec<PathBuf>, Vec<String>)> {
+        let har: serde_json::Value = serde_json::from_str(&fs::read_to_string(fpath)?)
+            .map_err(|e| anyhow!("'{}' isn't valid JSON: {}", fpath.display(), e))?;
+        self.write_import(har::convert(&har)?)
+    }
+
+    /// The requests and responses in the history, as a HAR file.
+    pub fn export_har(&self) -> Result<String> {
+        let mut entries = vec![];
+        for entry in self.history()? {
+            let mut req =
+                Request::from_rendered(self.history_fpath(&entry), entry.rendered.clone());
+            req.set_base_url(self.config.base_url.clone());
+            req.parse(None, HashMap::new())?;
+            entries.push((entry, req));
+        }
+        Ok(serde_json::to_string_pretty(&har::log(&entries)?)?)
+    }
+
+    /// The requests matching a glob pattern, or all of them for `all`, as an `.http` file for
+    /// the REST clients of editors, with warnings about what those can't do.
+    pub fn export_http(&self, pattern: &str) -> Result<(String, Vec<String>)> {
+        let partials = template::load_partials(Path::new(self.dir))?;
+        let mut names = self.list_reqs();
+        names.retain(|name| pattern == "all" || glob::matches(pattern, name));
+        if names.is_empty() {
+            return Err(anyhow!("No requests match '{}'.", pattern));
+        }
+        names.sort();
+
+        let mut requests = vec![];
+        let mut warnings = vec![];
+        for name in names {
+            let (exported, warned) =
+                http_file::export(&name, &self.get_req(&name)?.raw()?, &partials)?;
+            requests.push(exported);
+            warnings.extend(warned);
+        }
+        Ok((requests.join("\n"), warnings))
+    }
+
+    /// The envs in the format of `http-client.env.json`, which REST Client in VS Code takes as
+    /// its `rest-client.environmentVariables` setting too. The `base_url` of the collection
+    /// config is added as `_base_url` to envs without one, for relative URLs.
+    pub fn export_http_envs(&self) -> Result<(String, Vec<String>)> {
+        let mut envs = serde_json::Map::new();
+        let mut warnings = vec![];
+        for mut env in self.envs.clone() {
+            env.load()?;
+            let mut vars = env.to_hashmap()?;
+            if let Some(base_url) = &self.config.base_url {
+                vars.entry("_base_url".to_owned())
+                    .or_insert_with(|| serde_json::Value::String(base_url.clone()));
+            }
+            let name = env.name(self.dir);
+            let exported = http_file::export_env(&name, vars, &env.secret_names()?, &mut warnings);
+            envs.insert(name, serde_json::Value::Object(exported));
+        }
+        Ok((serde_json::to_string_pretty(&envs)?, warnings))
+    }
+
+    /// Writes converted request files and environments. Nothing is written if any of them
+    /// already exists.
+    fn write_import(&self, import: Import) -> Result<(Vec<PathBuf>, Vec<String>)> {
+        let mut files = vec![];
+        for (name, fstr) in import.requests {
+            files.push((self.new_request_fpath(&name, false)?, fstr));
+        }
+        for (name, variables) in import.envs {
+            if self.get_env(name.clone()).is_some() {
+                return Err(anyhow!("Env '{}' already exists.", name));
+            }
+            let fpath = Path::new(self.dir)
+                .join("envs")
+                .join(format!("{}.json", name));
+            files.push((fpath, serde_json::to_string_pretty(&variables)?));
+        }
+        if let Some((fpath, _)) = files.iter().find(|(fpath, _)| fpath.exists()) {
+            return Err(anyhow!("'{}' already exists.", fpath.display()));
+        }
+
+        for (fpath, contents) in files.iter() {
+            write_new(fpath, contents)?;
+        }
+        Ok((
+            files.into_iter().map(|(fpath, _)| fpath).collect(),
+            import.warnings,
+        ))
+    }
+
+    /// The path of a new request file, for nested names like `users/create` too. Names can't
+    /// point outside the reqq directory or into its reserved folders, and existing requests
+    /// aren't overwritten unless `overwrite` is set.
+    fn new_request_fpath(&self, name: &str, overwrite: bool) -> Result<PathBuf> {
+        let name = name.trim_end_matches(".reqq");
+        let segments: Vec<&str> = name.split('/').collect();
+        if segments
+            .iter()
+            .any(|s| s.is_empty() || *s == "." || *s == "..")
+        {
+            return Err(anyhow!("Invalid request name '{}'.", name));
+        }
+        let reserved = (segments.len() > 1 && RESERVED_FOLDERS.contains(&segments[0]))
+            || RESERVED_FILES.contains(&format!("{}.reqq", name).as_str());
+        if reserved {
+            return Err(anyhow!("'{}' is reserved for reqq's own files.", name));
+        }
+
+        let fpath = Path::new(self.dir).join(format!("{}.reqq", name));
+        if fpath.exists() && !overwrite {
+            return Err(anyhow!("Request '{}' already exists.", name));
+        }
+        Ok(fpath)
+    }
+
+    /// Adds what a parsed request needs from reqq's own state before it's sent: the headers of
+    /// the collection config, stored cookies, and an access token in place of OAuth2 auth.
+    /// Bodies sent as JSON are checked first, unless that's turned off.
+    fn prepare(&self, req: &Request, env_name: Option<&str>) -> Result<Request> {
+        let mut req = req.clone();
+        for (name, value) in &self.config.headers {
+            req.add_default_header(name.clone(), value.clone());
+        }
+        let inferred = req.body().and_then(Body::inferred_content_type);
+        if let (true, Some(content_type)) = (self.infer_content_type, inferred) {
+            req.add_default_header(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        }
+        if self.compressed {
+            req.add_default_header(
+                ACCEPT_ENCODING,
+                HeaderValue::from_static(compression::ACCEPT_ENCODING),
+            );
+        }
+        if self.validate_body {
+            req.check_json_body()?;
+        }
+        if let (true, Some(url)) = (self.use_cookies, req.url().cloned()) {
+            if let Some(cookies) = self.cookie_jar.header_for(&url)? {
+                req.add_default_header(COOKIE, HeaderValue::from_str(&cookies)?);
+            }
+        }
+        if let Some(Auth::OAuth2(oauth)) = req.auth().cloned() {
+            let token =
+                self.tokens
+                    .access_token(&oauth, env_name, req.client_opts(), self.verbose)?;
+            req.set_auth(Auth::Bearer { token });
+        }
+        Ok(req)
+    }
+
+    /// Renders a request specified by name, optionally with an environment, and returns a
+    /// curl command that sends it the same way.
+    pub fn curl(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<String> {
+        let mut req = self.get_req(req_name)?;
+        let maybe_env = self.resolve_env(env_name)?;
+        self.configure(&mut req, &maybe_env)?;
+        let args = self.run_pre_hooks(
+            req_name,
+            &mut req,
+            &maybe_env,
+            self.with_session(extra_args)?,
+        )?;
+        req.parse(maybe_env.clone(), args)?;
+        let env_name = maybe_env.as_ref().map(|e| e.name(self.dir));
+        curl::to_curl(&self.prepare(&req, env_name.as_deref())?)
+    }
+
+    /// Renders a request specified by name, optionally with an environment, and writes it as a
+    /// program in a language. Secrets are rendered as placeholders like `${TOKEN}`, and OAuth2
+    /// tokens aren't fetched.
+    pub fn codegen(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+        lang: Lang,
+    ) -> Result<String> {
+        let mut req = self.get_req(req_name)?;
+        let maybe_env = self.resolve_env(env_name)?;
+        self.configure(&mut req, &maybe_env)?;
+        let mut args = self.with_session(extra_args)?;
+        for name in self.secret_variables(&maybe_env)? {
+            let placeholder = codegen::placeholder(&name);
+            args.insert(name, serde_json::Value::String(placeholder));
+        }
+        let args = self.run_pre_hooks(req_name, &mut req, &maybe_env, args)?;
+        req.parse(maybe_env.clone(), args)?;
+        if let Some(Auth::OAuth2(_)) = req.auth() {
+            req.set_auth(Auth::Bearer {
+                token: codegen::placeholder("access_token"),
+            });
+        }
+        let env_name = maybe_env.as_ref().map(|e| e.name(self.dir));
+        codegen::generate(&self.prepare(&req, env_name.as_deref())?, lang)
+    }
+
+    /// The names of the stored secrets, of the variables in the env's `secrets` section, and of
+    /// the env's other variables with names matching the `secret_pattern` of the collection
+    /// config. Variables holding objects or lists are left out.
+    fn secret_variables(&self, maybe_env: &Option<Env>) -> Result<Vec<String>> {
+        let mut names = self.secrets.names()?;
+        if let Some(mut env) = maybe_env.clone() {
+            env.load()?;
+            let secret_names = env.secret_names()?;
+            for (name, value) in env.to_hashmap()? {
+                let is_scalar = !value.is_object() && !value.is_array();
+                if is_scalar && (secret_names.contains(&name) || self.is_secret_name(&name)?) {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Parses a request specified by name, optionally with an environment, and returns a
+    /// concise summary of it without sending anything.
+    pub fn describe(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<String> {
+        let mut req = self.get_req(req_name)?;
+        req.set_trailing_newline(self.trailing_newline);
+        req.set_base_url(self.config.base_url.clone());
+        req.set_secret_pattern(self.config.secret_pattern.clone());
+        req.set_gql_variables(self.gql_variables.clone());
+        req.set_partials(template::load_partials(Path::new(self.dir))?);
+        let maybe_env = self.resolve_env(env_name)?;
+        let args = self.run_pre_hooks(
+            req_name,
+            &mut req,
+            &maybe_env,
+            self.with_session(extra_args)?,
+        )?;
+        req.parse(maybe_env, args)?;
+        req.describe().map(|described| redact::redact(&described))
+    }
+
+    /// The names of the variables a request uses that the environment, extra args and session
+    /// don't define. Responses of dependencies, like `{{ login.body.token }}`, aren't missing
+    /// since they're only known once the dependencies are executed, and neither are those of
+    /// requests with pre hooks.
+    pub fn missing_variables(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<Vec<String>> {
+        let missing = self.unresolved_variables(req_name, env_name, extra_args)?;
+        Ok(missing.into_iter().map(|m| m.name).collect())
+    }
+
+    /// Whether a variable's name matches the `secret_pattern` of the collection config, so its
+    /// value should be hidden.
+    pub fn is_secret_name(&self, name: &str) -> Result<bool> {
+        let pattern = self
+            .config
+            .secret_pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()?;
+        Ok(pattern.is_some_and(|pattern| pattern.is_match(name)))
+    }
+
+    /// Asks on the terminal for the variables a request is missing, without echoing those with
+    /// secret names, and adds them to the extra args. Values are read like `-a` args: JSON if
+    /// it's valid, a string otherwise.
+    pub fn prompt_variables(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        mut extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<HashMap<String, serde_json::Value>> {
+        for var in self.missing_variables(req_name, env_name, extra_args.clone())? {
+            let value = if self.is_secret_name(&var)? {
+                prompt::hidden(&var)?
+            } else {
+                prompt::visible(&var)?
+            };
+            let value = serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
+            extra_args.insert(var, value);
+        }
+        Ok(extra_args)
+    }
+
+    /// Asks for the value of a secret on the terminal, without echoing it.
+    pub fn prompt_secret(&self, name: &str) -> Result<String> {
+        prompt::hidden(&format!("Value of {}", name))
+    }
+
+    /// Like `missing_variables`, with the line of the request file each variable is first used on.
+    fn unresolved_variables(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<Vec<MissingVariable>> {
+        if self.has_pre_hooks(req_name)? {
+            return Ok(vec![]);
+        }
+        let mut req = self.get_req(req_name)?;
+        req.set_partials(template::load_partials(Path::new(self.dir))?);
+        let maybe_env = self.resolve_env(env_name)?;
+        let dependencies = self.dependency_order(req_name)?;
+        let mut unresolved: Vec<MissingVariable> = vec![];
+        for missing in req.missing_variables(
+            maybe_env,
+            self.with_secret_names(self.with_session(extra_args)?)?,
+        )? {
+            let root = missing.name.split(['.', '[']).next().unwrap_or_default();
+            if !unresolved.iter().any(|m| m.name == missing.name)
+                && !dependencies.iter().any(|dep| dep == root)
+            {
+                unresolved.push(missing);
+            }
+        }
+        Ok(unresolved)
+    }
+
+    /// Checks every request and env file without sending anything: that env files parse, that
+    /// names are unique, that dependencies exist, and that each request parses with each env
+    /// and uses no variables the env doesn't define, that bodies sent as JSON are valid JSON,
+    /// and that `@schema` files load. Requests with pre hooks are only checked for their
+    /// dependencies, since the hooks aren't run.
+    pub fn validate(&self, extra_args: HashMap<String, serde_json::Value>) -> Result<Validation> {
+        let mut validation = Validation {
+            requests: self.reqs.len(),
+            envs: self.envs.len(),
+            problems: vec![],
+        };
+
+        let env_names = self.list_envs();
+        let mut broken_envs = vec![];
+        for mut env in self.envs.clone() {
+            if let Err(e) = env.load().and_then(|_| env.to_hashmap()) {
+                validation.add(&format!("envs/{}", env.name(self.dir)), format!("{:#}", e));
+                broken_envs.push(env.name(self.dir));
+            }
+        }
+        for name in validate::duplicates(&env_names) {
+            validation.add(
+                &format!("envs/{}", name),
+                "More than one env file has this name.".to_owned(),
+            );
+        }
+
+        let mut req_names = self.list_reqs();
+        req_names.sort();
+        for name in validate::duplicates(&req_names) {
+            validation.add(
+                &name,
+                "More than one request file has this name.".to_owned(),
+            );
+        }
+
+        // Without env files, requests are checked without an env. Envs that don't load are
+        // only reported once.
+        let envs: Vec<Option<String>> = if env_names.is_empty() {
+            vec![None]
+        } else {
+            env_names
+                .into_iter()
+                .filter(|name| !broken_envs.contains(name))
+                .map(Some)
+                .collect()
+        };
+        for name in req_names.iter() {
+            if let Err(e) = self.dependency_order(name) {
+                validation.add(name, e.to_string());
+                continue;
+            }
+            if self.has_pre_hooks(name).unwrap_or(false) {
+                continue;
+            }
+            for env in envs.iter() {
+                let with_env = env
+                    .as_ref()
+                    .map(|env| format!(" in env '{}'", env))
+                    .unwrap_or_default();
+                match self.unresolved_variables(name, env.clone(), extra_args.clone()) {
+                    Ok(missing) if !missing.is_empty() => {
+                        let missing: Vec<String> =
+                            missing.iter().map(MissingVariable::to_string).collect();
+                        validation.add(
+                            name,
+                            format!("Undefined variables{}: {}.", with_env, missing.join(", ")),
+                        );
+                    }
+                    Ok(_) => {
+                        let parsed = self.parsed(
+                            name,
+                            env.clone(),
+                            self.with_secret_names(extra_args.clone())?,
+                        );
+                        let checked = parsed.and_then(|req| {
+                            if self.validate_body {
+                                req.check_json_body()?;
+                            }
+                            req.schema()
+                                .map_or(Ok(()), |path| JsonSchema::load(path).map(drop))
+                        });
+                        if let Err(e) = checked {
+                            validation.add(name, format!("{:#}", e));
+                        }
+                    }
+                    Err(e) => validation.add(name, format!("{:#}", e)),
+                }
+            }
+        }
+        Ok(validation)
+    }
+
+    /// Parses a request specified by name, optionally with an environment, ready to be sent.
+    pub(crate) fn parsed(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<Request> {
+        let mut req = self.get_req(req_name)?;
+        let maybe_env = self.resolve_env(env_name)?;
+        self.configure(&mut req, &maybe_env)?;
+        req.parse(maybe_env, self.with_session(extra_args)?)?;
+        Ok(req)
+    }
+
+    /// Executes a request specified by name, after its dependencies, and returns the response
+    /// as it was received.
+    pub(crate) fn fetch(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<Response> {
+        let maybe_env = self.resolve_env(env_name)?;
+        let args = self.with_session(extra_args)?;
+        let (_, entry) = self.execute_with_dependencies(req_name, &maybe_env, args, None)?;
+        Ok(entry.response)
+    }
+
+    /// Executes a request, after its dependencies, in each of two environments, and compares
+    /// the responses.
+    pub fn diff(
+        &self,
+        req_name: &str,
+        envs: (&str, &str),
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<ResponseDiff> {
+        let left = self.fetch(req_name, Some(envs.0.to_owned()), extra_args.clone())?;
+        let right = self.fetch(req_name, Some(envs.1.to_owned()), extra_args)?;
+        Ok(ResponseDiff::new((envs.0, &left), (envs.1, &right)))
+    }
+
+    /// Like `fetch`, but sends the requests with reqwest's async client.
+    #[cfg(feature = "async")]
+    pub(crate) async fn fetch_async(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<Response> {
+        let maybe_env = self.resolve_env(env_name)?;
+        let args = self.with_session(extra_args)?;
+        let (_, resp) = self
+            .execute_with_dependencies_async(req_name, &maybe_env, args)
+            .await?;
+        Ok(resp)
+    }
+
+    /// The files a request is made from: its own file and those of its dependencies, the env
+    /// files, and the files its body is read from. Body files are only found when the request
+    /// can be parsed without its dependencies' responses.
+    pub fn watched_files(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        for name in self.dependency_order(req_name)? {
+            let req = self.get_req(&name)?;
+            files.extend(
+                [req.fpath()]
+                    .into_iter()
+                    .chain(req.bases().iter().map(String::as_str))
+                    .map(PathBuf::from),
+            );
+        }
+        files.extend(self.envs.iter().map(|env| PathBuf::from(&env.fpath)));
+        if let Ok(req) = self.parsed(req_name, env_name, extra_args) {
+            files.extend(
+                req.body()
+                    .into_iter()
+                    .flat_map(|body| body.files())
+                    .map(Path::to_path_buf),
+            );
+        }
+        Ok(files)
+    }
+
+    /// The variables of an environment specified by name.
+    pub(crate) fn env_variables(
+        &self,
+        env_name: &str,
+    ) -> Result<HashMap<String, serde_json::Value>> {
+        let mut env = self
+            .get_env(env_name.to_owned())
+            .ok_or_else(|| ReqqError::EnvNotFound(env_name.to_owned()))?;
+        env.load()?;
+        env.to_hashmap()
+    }
+
+    /// Renders a request specified by name, optionally with an environment, and returns the
+    /// final request file contents without sending anything.
+    pub fn render(
+        &self,
+        req_name: &str,
+        env_name: Option<String>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<String> {
+        let mut req = self.get_req(req_name)?;
+        req.set_base_url(self.config.base_url.clone());
+        req.set_secret_pattern(self.config.secret_pattern.clone());
+        req.set_gql_variables(self.gql_variables.clone());
+        req.set_partials(template::load_partials(Path::new(self.dir))?);
+        let maybe_env = self.resolve_env(env_name)?;
+        let args = self.run_pre_hooks(
+            req_name,
+            &mut req,
+            &maybe_env,
+            self.with_session(extra_args)?,
+        )?;
+        req.parse(maybe_env, args)?;
+        req.rendered()
+            .map(redact::redact)
+            .ok_or_else(|| anyhow!("Request has not been parsed."))
+    }
+
+    /// The dependencies of a request, direct and indirect, in the order they need to be
+    /// executed.
+    fn dependency_order(&self, req_name: &str) -> Result<Vec<String>> {
+        let mut order = vec![];
+        self.visit_dependencies(req_name, &mut vec![], &mut order)?;
+        order.pop();
+        Ok(order)
+    }
+
+    /// Depth first walk of the dependency graph, adding each request to `order` after all of
+    /// its dependencies. `path` holds the requests currently being visited, to detect cycles.
+    fn visit_dependencies(
+        &self,
+        req_name: &str,
+        path: &mut Vec<String>,
+        order: &mut Vec<String>,
+    ) -> Result<()> {
+        if order.iter().any(|name| name == req_name) {
+            return Ok(());
+        }
+        if path.iter().any(|name| name == req_name) {
+            path.push(req_name.to_owned());
+            return Err(anyhow!("Circular dependency: {}.", path.join(" -> ")));
+        }
+
+        let mut req = match (self.get_req(req_name), path.last()) {
+            (Ok(req), _) => req,
+            (Err(_), Some(parent)) => {
+                return Err(anyhow!(
+                    "Dependency '{}' of '{}' not found.",
+                    req_name,
+                    parent
+                ))
+            }
+            (Err(e), None) => return Err(e),
+        };
+
+        path.push(req_name.to_owned());
+        for dep_name in req.dependencies()? {
+            self.visit_dependencies(&dep_name, path, order)?;
+        }
+        path.pop();
+
+        order.push(req_name.to_owned());
+        Ok(())
+    }
+
+    /// Args with a placeholder for each stored secret that isn't set otherwise, to check
+    /// requests without reading any secrets.
+    fn with_secret_names(
+        &self,
+        mut args: HashMap<String, serde_json::Value>,
+    ) -> Result<HashMap<String, serde_json::Value>> {
+        for name in self.secrets.names()? {
+            args.entry(name)
+                .or_insert_with(|| serde_json::Value::String(redact::MASK.to_owned()));
+        }
+        Ok(args)
+    }
+
+    /// Combines the session variables with args, which take precedence.
+    fn with_session(
+        &self,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<HashMap<String, serde_json::Value>> {
+        let mut args = self.session.load()?;
+        args.extend(extra_args);
+        Ok(args)
+    }
+
+    /// Applies the options that affect how requests are parsed and sent. Client settings in
+    /// the environment, like `proxy`, apply unless they were given as options, and those of
+    /// the collection config unless the environment has them too.
+    fn configure(&self, req: &mut Request, maybe_env: &Option<Env>) -> Result<()> {
+        req.set_trailing_newline(self.trailing_newline);
+        req.set_default_timeout(self.timeout);
+        req.set_base_url(self.config.base_url.clone());
+        req.set_secret_pattern(self.config.secret_pattern.clone());
+        req.set_gql_variables(self.gql_variables.clone());
+        req.set_partials(template::load_partials(Path::new(self.dir))?);
+
+        let mut client = self.client.clone();
+        if let Some(mut env) = maybe_env.clone() {
+            env.load()?;
+            client.apply_env(&env.to_hashmap()?)?;
+        }
+        client.redirects = client.redirects.or(self.config.redirects);
+        if client.proxy.is_none() {
+            client.proxy = self.config.proxy.clone();
+        }
+        req.set_client_opts(client);
+        Ok(())
+    }
+
+    /// The environment used when none is given: the `env` of the collection config, or
+    /// `default`.
+    pub fn default_env(&self) -> &str {
+        self.config.env.as_deref().unwrap_or("default")
+    }
+
+    /// The file of a request recorded in the history, so paths in it resolve as they did
+    /// then. Falls back to the request name if the file is gone.
+    fn history_fpath(&self, entry: &Entry) -> String {
+        self.get_req(&entry.request)
+            .map_or_else(|_| entry.request.clone(), |req| req.fpath().to_owned())
+    }
+
+    /// Finds a request by name, merged with the requests it extends.
     fn get_req(&self, name: &str) -> Result<Request> {
+        let req = self
+            .find_req(name)
+            .ok_or_else(|| ReqqError::RequestNotFound(name.to_owned()))?;
+        self.extended(req, &mut vec![])
+    }
+
+    /// Every request, merged with the requests it extends. Those that can't be are left as
+    /// they are, and fail when they're used.
+    fn requests(&self) -> Vec<Request> {
         self.reqs
-            .clone()
-            .into_iter()
-            .find(|r| r.name(self.dir) == name)
-            .ok_or_else(|| anyhow!("Request not found."))
+            .iter()
+            .map(|req| {
+                self.extended(req.clone(), &mut vec![])
+                    .unwrap_or_else(|_| req.clone())
+            })
+            .collect()
+    }
+
+    fn find_req(&self, name: &str) -> Option<Request> {
+        self.reqs.iter().find(|r| r.name(self.dir) == name).cloned()
+    }
+
+    /// Merges a request with the one its `@extends` names, after merging that one with those it
+    /// extends. `path` holds the names of the requests extending it, to catch cycles.
+    fn extended(&self, mut req: Request, path: &mut Vec<String>) -> Result<Request> {
+        let Some(base_name) = req.extends()? else {
+            return Ok(req);
+        };
+        let name = req.name(self.dir);
+        path.push(name.clone());
+        if path.contains(&base_name) {
+            path.push(base_name);
+            return Err(anyhow!(
+                "Requests extend each other in a cycle: {}",
+                path.join(" -> ")
+            ));
+        }
+        let base = self.find_req(&base_name).ok_or_else(|| {
+            anyhow!(
+                "Request '{}' extends '{}', which wasn't found.",
+                name,
+                base_name
+            )
+        })?;
+        let mut base = self.extended(base, path)?;
+        req.extend(&mut base)
+            .map_err(|e| anyhow!("Request '{}' can't extend '{}': {}", name, base_name, e))?;
+        Ok(req)
+    }
+
+    /// Finds the environment to apply. A named environment must exist, including the one the
+    /// collection config names, while the `default` environment is optional.
+    fn resolve_env(&self, env_name: Option<String>) -> Result<Option<Env>> {
+        match env_name.or_else(|| self.config.env.clone()) {
+            Some(name) => self
+                .get_env(name.clone())
+                .map(Some)
+                .ok_or_else(|| ReqqError::EnvNotFound(name).into()),
+            None => Ok(self.get_env("default".to_owned())),
+        }
     }
 
     fn get_env(&self, name: String) -> Option<Env> {
@@ -101,7 +2241,109 @@ impl<'a> Reqq<'a> {
     }
 }
 
+/// Status codes for messages, like `201 or 202`.
+fn statuses(statuses: &[StatusCode]) -> String {
+    let codes: Vec<String> = statuses
+        .iter()
+        .map(|status| status.as_u16().to_string())
+        .collect();
+    codes.join(" or ")
+}
+
+/// Evaluates the assertions of a request against its response, and checks the body against
+/// its schema, if it has one.
+fn check_response(req: &Request, resp: &Response) -> CheckReport {
+    let mut results: Vec<AssertionResult> =
+        req.asserts().iter().map(|a| a.evaluate(resp)).collect();
+    if let Some(path) = req.schema() {
+        results.extend(schema::check(path, resp));
+    }
+    CheckReport { results }
+}
+
+/// The response of a dependency as template variables, unless its status is 4xx or 5xx.
+fn dependency_value(dep_name: &str, resp: &Response) -> Result<serde_json::Value> {
+    if resp.status.is_client_error() || resp.status.is_server_error() {
+        return Err(anyhow!(
+            "Dependency '{}' failed with status {}.",
+            dep_name,
+            resp.status
+        ));
+    }
+    Ok(resp.to_template_value())
+}
+
+/// Fails a flow step on a 4xx or 5xx status, otherwise evaluates its extractions.
+fn check_step(
+    resp: &Response,
+    extract: &[(String, String)],
+) -> Result<Vec<(String, serde_json::Value)>> {
+    if resp.status.is_client_error() || resp.status.is_server_error() {
+        return Err(anyhow!("Request failed with status {}.", resp.status));
+    }
+    extract_vars(resp, extract)
+}
+
+/// Evaluates variable names and JSONPath expressions against a response body.
+fn extract_vars(
+    resp: &Response,
+    extract: &[(String, String)],
+) -> Result<Vec<(String, serde_json::Value)>> {
+    if extract.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let body: serde_json::Value = serde_json::from_str(&resp.body)
+        .map_err(|_| anyhow!("Can't extract values, the response body isn't JSON."))?;
+    extract
+        .iter()
+        .map(|(var, path)| {
+            let value = jsonpath::extract(path, &body)
+                .map_err(|e| anyhow!("Extracting '{}': {}", var, e))?;
+            Ok((var.clone(), value))
+        })
+        .collect()
+}
+
+/// The contents of a new request file. Methods that usually send a body get an empty JSON one.
+fn request_template(method: &Method, url: &str) -> String {
+    match *method {
+        Method::POST | Method::PUT | Method::PATCH => {
+            format!(
+                "{} {}\nContent-Type: application/json\n\n{{}}\n",
+                method, url
+            )
+        }
+        _ => format!("{} {}\n", method, url),
+    }
+}
+
 // TODO: This is gross.
+/// Writes a file, creating the folders it's in.
+fn write_new(fpath: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = fpath.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(fpath, contents)?;
+    Ok(())
+}
+
+/// The requests in a request file: one for each of those in a file that holds several, or
+/// else the file as a single request.
+fn file_requests(fpath: String) -> Vec<Request> {
+    let sections = match fs::read_to_string(&fpath) {
+        Ok(fstr) => request_file::open(&fpath, fstr).sections(),
+        Err(_) => vec![],
+    };
+    if sections.is_empty() {
+        return vec![Request::new(fpath)];
+    }
+    sections
+        .into_iter()
+        .map(|section| Request::in_section(fpath.clone(), section))
+        .collect()
+}
+
 fn get_all_fpaths(dir: &str) -> Vec<String> {
     WalkDir::new(dir)
         .into_iter()
@@ -112,7 +2354,7 @@ fn get_all_fpaths(dir: &str) -> Vec<String> {
                 }
 
                 let path_display = e.path().display().to_string();
-                match path_display.as_str().trim_start_matches(&dir) {
+                match path_display.as_str().trim_start_matches(dir) {
                     "" => None,
                     _ => Some(path_display),
                 }
@@ -121,3 +2363,215 @@ fn get_all_fpaths(dir: &str) -> Vec<String> {
         })
         .collect()
 }
+
+#[test]
+fn test_dependency_order() {
+    let dir = std::env::temp_dir().join(format!("reqq-depends-{}", std::process::id()));
+    let files = [
+        ("login", "POST https://example.com/login"),
+        ("token", "@depends login\nPOST https://example.com/token"),
+        ("me", "@depends: token, login\nGET https://example.com/me"),
+        ("orphan", "@depends nope\nGET https://example.com"),
+        ("a", "@depends b\nGET https://example.com"),
+        ("b", "@depends a\nGET https://example.com"),
+    ];
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, fstr) in files {
+        std::fs::write(dir.join(format!("{}.reqq", name)), fstr).unwrap();
+    }
+
+    let reqq = test_reqq(dir.to_str().unwrap());
+    assert!(reqq.dependency_order("login").unwrap().is_empty());
+    assert_eq!(reqq.dependency_order("me").unwrap(), vec!["login", "token"]);
+    assert_eq!(
+        reqq.dependency_order("orphan").unwrap_err().to_string(),
+        "Dependency 'nope' of 'orphan' not found."
+    );
+    assert_eq!(
+        reqq.dependency_order("a").unwrap_err().to_string(),
+        "Circular dependency: a -> b -> a."
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_request_extends() {
+    let dir = std::env::temp_dir().join(format!("reqq-extends-{}", std::process::id()));
+    let files = [
+        (
+            "base",
+            "@tags users
+GET https://example.com/users
+Accept: application/json
+",
+        ),
+        (
+            "page",
+            "@extends base
+?page=2
+",
+        ),
+        (
+            "first",
+            "@extends page
+x-first: 1
+",
+        ),
+        (
+            "orphan",
+            "@extends nope
+",
+        ),
+        (
+            "a",
+            "@extends b
+",
+        ),
+        (
+            "b",
+            "@extends a
+",
+        ),
+    ];
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, fstr) in files {
+        std::fs::write(dir.join(format!("{}.reqq", name)), fstr).unwrap();
+    }
+
+    let reqq = test_reqq(dir.to_str().unwrap());
+    let mut first = reqq.get_req("first").unwrap();
+    assert_eq!(first.raw().unwrap(), "@tags users\nGET https://example.com/users\n?page=2\nAccept: application/json\nx-first: 1\n");
+    let bases: Vec<String> = ["page", "base"]
+        .iter()
+        .map(|name| dir.join(format!("{}.reqq", name)).display().to_string())
+        .collect();
+    assert_eq!(first.bases(), bases);
+    let mut tagged = reqq.list_tagged(&["users".to_owned()]).unwrap();
+    tagged.sort();
+    assert_eq!(tagged, vec!["base", "first", "page"]);
+    assert_eq!(
+        reqq.get_req("orphan").err().unwrap().to_string(),
+        "Request 'orphan' extends 'nope', which wasn't found."
+    );
+    assert_eq!(
+        reqq.get_req("a").err().unwrap().to_string(),
+        "Requests extend each other in a cycle: a -> b -> a"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(test)]
+fn test_reqq(dir: &str) -> Reqq<'_> {
+    Reqq::new(ReqqOpts {
+        dir,
+        parts: ResponseParts::default(),
+        raw: false,
+        html_text: false,
+        color: false,
+        verbose: false,
+        trailing_newline: TrailingNewline::default(),
+        timeout: None,
+        client: ClientOpts::default(),
+        retry: RetryPolicy::default(),
+        history: false,
+        cookies: false,
+        check: false,
+        output: None,
+        gql_variables: HashMap::new(),
+        filter: None,
+        ws_count: None,
+        timings: false,
+        compressed: false,
+        infer_content_type: true,
+        validate_body: true,
+        delay: Duration::ZERO,
+        cache: false,
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_request_template() {
+    assert_eq!(
+        request_template(&Method::GET, "https://example.com"),
+        "GET https://example.com\n"
+    );
+    assert_eq!(
+        request_template(&Method::POST, "{{ baseUrl }}/users"),
+        "POST {{ baseUrl }}/users\nContent-Type: application/json\n\n{}\n"
+    );
+}
+
+#[test]
+fn test_execution_result_json() {
+    redact::add(&serde_json::json!("tok-json-7c1a"));
+    let mut headers = http::HeaderMap::new();
+    headers.append("set-cookie", HeaderValue::from_static("a=1"));
+    headers.append("set-cookie", HeaderValue::from_static("b=2"));
+    let mut result = ExecutionResult {
+        status: StatusCode::CREATED,
+        output: String::new(),
+        check: None,
+        expect: vec![],
+        method: "POST".to_owned(),
+        url: "https://example.com/users".to_owned(),
+        request_headers: vec![
+            (
+                HeaderName::from_static("accept"),
+                HeaderValue::from_static("application/json"),
+            ),
+            (
+                HeaderName::from_static("authorization"),
+                HeaderValue::from_static("Bearer tok-json-7c1a"),
+            ),
+        ],
+        entry: Entry {
+            id: String::new(),
+            timestamp: 0,
+            request: "users/create".to_owned(),
+            env: None,
+            rendered: String::new(),
+            duration_ms: 12,
+            response: Response {
+                status: StatusCode::CREATED,
+                version: reqwest::Version::HTTP_11,
+                headers,
+                body: r#"{"id": 7}"#.to_owned(),
+                binary: None,
+            },
+            timings: None,
+        },
+    };
+
+    let json = result.to_json();
+    assert_eq!(json["request"]["headers"]["accept"], "application/json");
+    assert_eq!(json["request"]["headers"]["authorization"], "Bearer *****");
+    assert_eq!(json["response"]["status"], 201);
+    assert_eq!(json["response"]["headers"]["set-cookie"], "a=1, b=2");
+    assert_eq!(json["response"]["body"]["id"], 7);
+    assert_eq!(json["timing"]["started"], "1970-01-01T00:00:00Z");
+    assert!(json["history_id"].is_null());
+    assert!(json.get("assertions").is_none());
+    let yaml = result.render(OutputFormat::Yaml);
+    assert!(yaml.starts_with("history_id: null\nrequest:\n  env: null\n"));
+    assert!(!yaml.contains("tok-json-7c1a") && !json.to_string().contains("tok-json-7c1a"));
+
+    assert_eq!(result.status_error(true), None);
+    result.expect = vec![StatusCode::OK, StatusCode::NO_CONTENT];
+    assert_eq!(
+        result.status_error(false).as_deref(),
+        Some("Expected status 200 or 204, got 201 Created.")
+    );
+    result.status = StatusCode::NOT_FOUND;
+    result.expect = vec![StatusCode::NOT_FOUND];
+    assert_eq!(result.status_error(false), None);
+    assert_eq!(
+        result.status_error(true).as_deref(),
+        Some("Request failed with status 404 Not Found.")
+    );
+    result.expect = vec![];
+    result.check = Some(CheckReport::default());
+    assert_eq!(result.status_error(false), None);
+}
@@ -1,20 +1,103 @@
-use crate::env::Env;
+use crate::{
+    assertion::Assertion,
+    auth::{Auth, Authorize},
+    body::{self, Body, BodyFormat},
+    client::ClientOpts,
+    env::Env,
+    error::ReqqError,
+    extends, frontmatter, grpc, http_file,
+    listing::ListedRequest,
+    redact, request_file,
+    template::{self, MissingVariable, Partials},
+    time, unix_socket, websocket,
+};
 use anyhow::{anyhow, Result};
-use handlebars::Handlebars;
 use regex::Regex;
 use reqwest::{
-    blocking::{Client as ReqwestClient, RequestBuilder, Response},
-    header::{HeaderName, HeaderValue},
-    Method, Url,
+    blocking::{Client, RequestBuilder, Response},
+    header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE},
+    Method, StatusCode, Url,
 };
-use std::fs;
 use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Request {
     fpath: String,
+    section: Option<String>,
     fstr: Option<String>,
+    variables: Vec<(String, String)>,
     inner: Option<RequestInner>,
+    trailing_newline: TrailingNewline,
+    default_timeout: Option<Duration>,
+    base_url: Option<String>,
+    secret_pattern: Option<String>,
+    client: ClientOpts,
+    gql_variables: HashMap<String, serde_json::Value>,
+    partials: Partials,
+    bases: Vec<String>,
+    templated: bool,
+}
+
+/// How the final newline of a request body is handled when it is sent.
+///
+/// By default the body is sent exactly as written: every byte after the header block (and
+/// the blank separator line, if any) up to the end of the file, so a file that ends with a
+/// newline sends a body that ends with one too.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TrailingNewline {
+    /// Send the body bytes exactly as they appear in the file.
+    #[default]
+    Preserve,
+    /// Make sure the body ends with a newline, appending one if it's missing.
+    Always,
+    /// Strip a single trailing newline (`\n` or `\r\n`) from the body.
+    Never,
+}
+
+impl TrailingNewline {
+    fn apply(self, body: &str) -> String {
+        match self {
+            TrailingNewline::Preserve => body.to_owned(),
+            TrailingNewline::Always if body.is_empty() || body.ends_with('\n') => body.to_owned(),
+            TrailingNewline::Always => format!("{}\n", body),
+            TrailingNewline::Never => trim_line_ending(body).to_owned(),
+        }
+    }
+}
+
+/// What the environment adds to a request besides its variables.
+#[derive(Default)]
+struct EnvDefaults {
+    auth: Option<Auth>,
+    base_url: Option<String>,
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+/// The `_headers` of an environment, a mapping of header names to values.
+fn env_headers(value: &serde_json::Value) -> Result<Vec<(HeaderName, HeaderValue)>> {
+    let headers = value.as_object().into_iter().flatten();
+    headers
+        .map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Number(_) | serde_json::Value::Bool(_) => value.to_string(),
+                _ => {
+                    return Err(anyhow!(
+                    "The '_headers' environment variable has a header '{}' without a single value.",
+                    name
+                ))
+                }
+            };
+            Ok((
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(&value)?,
+            ))
+        })
+        .collect()
 }
 
 #[derive(Clone)]
@@ -22,7 +105,15 @@ pub struct RequestInner {
     method: Method,
     url: Url,
     headers: Vec<(HeaderName, HeaderValue)>,
-    body: Option<String>,
+    body: Option<Body>,
+    timeout: Option<Duration>,
+    asserts: Vec<Assertion>,
+    expect: Vec<StatusCode>,
+    auth: Option<Auth>,
+    name: Option<String>,
+    description: Option<String>,
+    proto: Option<PathBuf>,
+    schema: Option<PathBuf>,
 }
 
 impl Request {
@@ -30,151 +121,1034 @@ impl Request {
     pub fn new(fpath: String) -> Self {
         Request {
             fpath,
+            section: None,
             fstr: None,
+            variables: vec![],
             inner: None,
+            trailing_newline: TrailingNewline::default(),
+            default_timeout: None,
+            base_url: None,
+            secret_pattern: None,
+            client: ClientOpts::default(),
+            gql_variables: HashMap::new(),
+            partials: Partials::new(),
+            bases: vec![],
+            templated: false,
+        }
+    }
+
+    /// Creates a request for one of the requests in a file that holds several.
+    pub fn in_section(fpath: String, section: String) -> Self {
+        Request {
+            section: Some(section),
+            ..Request::new(fpath)
+        }
+    }
+
+    /// Creates a request from request file contents that already have templating applied,
+    /// such as a request recorded in the history.
+    pub fn from_rendered(fpath: String, rendered: String) -> Self {
+        Request {
+            fstr: Some(rendered),
+            templated: true,
+            ..Request::new(fpath)
         }
     }
 
-    /// Generates a request name from a config directory and a filename.
+    /// Sets how the final newline of the request body is handled.
+    pub fn set_trailing_newline(&mut self, trailing_newline: TrailingNewline) {
+        self.trailing_newline = trailing_newline;
+    }
+
+    /// Sets the timeout used when the request file doesn't specify its own.
+    pub fn set_default_timeout(&mut self, timeout: Option<Duration>) {
+        self.default_timeout = timeout;
+    }
+
+    /// Sets the URL that request URLs starting with `/` are relative to.
+    pub fn set_base_url(&mut self, base_url: Option<String>) {
+        self.base_url = base_url;
+    }
+
+    /// Sets a regex matching the names of variables whose values are secrets, on top of those
+    /// in the `secrets` section of the environment.
+    pub fn set_secret_pattern(&mut self, pattern: Option<String>) {
+        self.secret_pattern = pattern;
+    }
+
+    /// Sets GraphQL variables that replace those in the request file, for `@graphql` requests.
+    pub fn set_gql_variables(&mut self, variables: HashMap<String, serde_json::Value>) {
+        self.gql_variables = variables;
+    }
+
+    /// Sets the partials the request file can include with `{{> name }}`.
+    pub fn set_partials(&mut self, partials: Partials) {
+        self.partials = partials;
+    }
+
+    /// Adds a header to a parsed request, unless the request file already sets it.
+    pub fn add_default_header(&mut self, name: HeaderName, value: HeaderValue) {
+        if let Some(inner) = self.inner.as_mut() {
+            if !inner.headers.iter().any(|(key, _)| *key == name) {
+                inner.headers.push((name, value));
+            }
+        }
+    }
+
+    /// Sets the settings of the HTTP client the request is sent with.
+    pub fn set_client_opts(&mut self, client: ClientOpts) {
+        self.client = client;
+    }
+
+    /// Generates a request name from a config directory and a filename. Requests in files
+    /// that hold several are named `<file>#<name>`.
     pub fn name(&self, dir: &str) -> String {
-        self.fpath
-            .trim_start_matches(dir)
-            .trim_start_matches('/')
-            .trim_end_matches(".reqq")
-            .to_owned()
+        let name = self.fpath.trim_start_matches(dir).trim_start_matches('/');
+        match &self.section {
+            Some(section) => {
+                let file = http_file::EXTENSIONS
+                    .iter()
+                    .find_map(|ext| name.strip_suffix(ext))
+                    .unwrap_or(name);
+                format!("{}#{}", file, section)
+            }
+            None => name.trim_end_matches(".reqq").to_owned(),
+        }
+    }
+
+    /// The path of the request file.
+    pub fn fpath(&self) -> &str {
+        &self.fpath
+    }
+
+    /// The name of the request this one extends, read from its `@extends` directive.
+    pub fn extends(&mut self) -> Result<Option<String>> {
+        self.load()?;
+        Ok(frontmatter::extends(
+            self.fstr.as_deref().unwrap_or_default(),
+        ))
+    }
+
+    /// Merges the request file with the request it extends, which must already be merged with
+    /// the one it extends in turn, if any.
+    pub fn extend(&mut self, base: &mut Request) -> Result<()> {
+        self.load()?;
+        let merged = extends::merge(&base.raw()?, self.fstr.as_deref().unwrap_or_default())?;
+        self.fstr = Some(merged);
+        self.bases = [base.fpath.clone()]
+            .into_iter()
+            .chain(base.bases.iter().cloned())
+            .collect();
+        Ok(())
+    }
+
+    /// The files of the requests this one extends, directly or not.
+    pub fn bases(&self) -> &[String] {
+        &self.bases
+    }
+
+    /// The names of the requests this one depends on, read from its `@depends` directives.
+    pub fn dependencies(&mut self) -> Result<Vec<String>> {
+        self.load()?;
+        Ok(frontmatter::dependencies(
+            self.fstr.as_deref().unwrap_or_default(),
+        ))
+    }
+
+    /// The commands of the request's `@pre` and `@post` directives.
+    pub fn hooks(&mut self) -> Result<(Vec<String>, Vec<String>)> {
+        self.load()?;
+        Ok(frontmatter::hooks(self.fstr.as_deref().unwrap_or_default()))
+    }
+
+    /// The request's `@pre-script` and `@post-script`.
+    pub fn scripts(&mut self) -> Result<(Option<String>, Option<String>)> {
+        self.load()?;
+        Ok(frontmatter::scripts(
+            self.fstr.as_deref().unwrap_or_default(),
+        ))
+    }
+
+    /// The tags of the request, read from its `@tags` directives.
+    pub fn tags(&mut self) -> Result<Vec<String>> {
+        self.load()?;
+        Ok(frontmatter::tags(self.fstr.as_deref().unwrap_or_default()))
+    }
+
+    /// The request's method, host and description, as listed by `reqq list --long`.
+    pub fn listed(&mut self, dir: &str) -> Result<ListedRequest> {
+        self.load()?;
+        Ok(ListedRequest::new(
+            self.name(dir),
+            self.fstr.as_deref().unwrap_or_default(),
+        ))
+    }
+
+    /// The request file as it's written, before templating.
+    pub fn raw(&mut self) -> Result<String> {
+        self.load()?;
+        Ok(self.fstr.clone().unwrap_or_default())
+    }
+
+    /// Whether the request has at least one of the tags. Any request does when none are given.
+    pub fn has_any_tag(&mut self, tags: &[String]) -> Result<bool> {
+        Ok(tags.is_empty() || self.tags()?.iter().any(|t| tags.contains(t)))
     }
 
     fn load(&mut self) -> Result<()> {
         if self.fstr.is_none() {
-            let fstr = fs::read_to_string(self.fpath.clone())?;
+            let file = request_file::open(&self.fpath, fs::read_to_string(self.fpath.clone())?);
+            let fstr = file.request(self.section.as_deref()).ok_or_else(|| {
+                anyhow!(
+                    "There's no request '{}' in '{}' anymore.",
+                    self.section.as_deref().unwrap_or_default(),
+                    self.fpath
+                )
+            })?;
             self.fstr = Some(fstr);
+            self.variables = file.variables();
         }
         Ok(())
     }
 
-    fn apply_combined_args(&mut self,  env: Option<Env>, extra_args: HashMap<String, serde_json::Value>) -> Result<()> {
+    /// Renders the request file with the env and args applied. Returns the `auth`, `_base_url`
+    /// and `_headers` variables, rendered too, since they usually come from other variables.
+    fn apply_combined_args(
+        &mut self,
+        env: Option<Env>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<EnvDefaults> {
+        let secret_names = match env.clone() {
+            Some(mut env) => {
+                env.load()?;
+                env.secret_names()?
+            }
+            None => vec![],
+        };
+        let combined_args = self.combined_args(env, extra_args)?;
+        let json_value = handlebars::to_json(&combined_args);
+        self.add_secrets(&combined_args, &secret_names, &json_value)?;
+        let result = template::render_with_partials(
+            self.fstr.as_deref().unwrap_or_default(),
+            &json_value,
+            &self.partials,
+        )?;
+
+        self.fstr = Some(result);
+
+        let auth = match combined_args.get("auth") {
+            Some(auth) => Some(Auth::from_value(&template::render_value(
+                auth,
+                &json_value,
+            )?)?),
+            None => None,
+        };
+        let base_url = match combined_args.get("_base_url") {
+            Some(serde_json::Value::String(base_url)) => {
+                Some(template::render(base_url, &json_value)?)
+            }
+            Some(_) => {
+                return Err(anyhow!(
+                    "The '_base_url' environment variable must be a string."
+                ))
+            }
+            None => None,
+        };
+        let headers = match combined_args.get("_headers") {
+            Some(headers @ serde_json::Value::Object(_)) => {
+                env_headers(&template::render_value(headers, &json_value)?)?
+            }
+            Some(_) => {
+                return Err(anyhow!(
+                    "The '_headers' environment variable must map header names to values."
+                ))
+            }
+            None => vec![],
+        };
+        Ok(EnvDefaults {
+            auth,
+            base_url,
+            headers,
+        })
+    }
+
+    /// Registers the values of secret variables, rendered, so they're masked in output. Process
+    /// environment variables are secrets when their names match the pattern too.
+    fn add_secrets(
+        &self,
+        combined_args: &HashMap<String, serde_json::Value>,
+        secret_names: &[String],
+        data: &serde_json::Value,
+    ) -> Result<()> {
+        let pattern = self.secret_pattern.as_deref().map(Regex::new).transpose()?;
+        let is_secret = |name: &str| pattern.as_ref().is_some_and(|p| p.is_match(name));
+        for (name, value) in combined_args {
+            if secret_names.contains(name) || is_secret(name) {
+                redact::add(&template::render_value(value, data)?);
+            }
+        }
+        if let Some(serde_json::Value::Object(os_env)) = combined_args.get("env") {
+            os_env
+                .iter()
+                .filter(|(name, _)| is_secret(name))
+                .for_each(|(_, value)| redact::add(value));
+        }
+        Ok(())
+    }
+
+    /// The variables a request file is rendered with: process environment variables, then the
+    /// env file, then extra args, each overriding the last.
+    fn combined_args(
+        &self,
+        env: Option<Env>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<HashMap<String, serde_json::Value>> {
         let mut combined_args: HashMap<String, serde_json::Value> = HashMap::new();
-        
+
+        // Process environment variables are available as `{{ env.NAME }}`, unless the env
+        // file or args define their own `env`.
+        let os_env: serde_json::Map<String, serde_json::Value> = std::env::vars()
+            .map(|(k, v)| (k, serde_json::Value::String(v)))
+            .collect();
+        combined_args.insert("env".to_owned(), serde_json::Value::Object(os_env));
+
         if let Some(env) = env {
-            self.apply_env(env, &mut combined_args);
+            self.apply_env(env, &mut combined_args)?;
         }
 
+        let arg_names: Vec<String> = extra_args.keys().cloned().collect();
         for args in extra_args {
             combined_args.insert(args.0, args.1);
         }
 
-        let json_value = handlebars::to_json(combined_args);
-        let reg = Handlebars::new();
-        let result = reg.render_template(self.fstr.clone().unwrap().as_str(), &json_value)?;
+        // Variables the request's file defines, like `@host = localhost` in `.http` files, can
+        // use the others, and args override them.
+        for (name, value) in self
+            .variables
+            .iter()
+            .filter(|(name, _)| !arg_names.contains(name))
+        {
+            let value = template::render(value, &handlebars::to_json(&combined_args))
+                .map_err(|e| anyhow!("Invalid file variable '{}': {}", name, e))?;
+            combined_args.insert(name.clone(), serde_json::Value::String(value));
+        }
+        Ok(combined_args)
+    }
 
-        self.fstr = Some(result);
+    fn apply_env(
+        &self,
+        mut env: Env,
+        combined_args: &mut HashMap<String, serde_json::Value>,
+    ) -> Result<()> {
+        env.load()?;
 
+        combined_args.extend(env.to_hashmap()?);
         Ok(())
     }
 
-    fn apply_env(&mut self, mut env: Env, combined_args: &mut HashMap<String, serde_json::Value>) {
-        env.load().unwrap();
-        
-        combined_args.extend(env.to_hashmap().unwrap());
+    /// The variables the request file uses that neither the environment nor the extra args
+    /// define, without rendering it.
+    pub fn missing_variables(
+        &mut self,
+        env: Option<Env>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<Vec<MissingVariable>> {
+        if self.fstr.is_none() {
+            self.load()?;
+        }
+        if self.templated {
+            return Ok(vec![]);
+        }
+        let combined_args = self.combined_args(env, extra_args)?;
+        template::missing_variables(
+            self.fstr.as_deref().unwrap_or_default(),
+            &handlebars::to_json(&combined_args),
+            &self.partials,
+        )
     }
 
-    fn parse(&mut self, env: Option<Env>, extra_args: HashMap<String, serde_json::Value>) -> Result<()> {
+    /// Loads the request file and parses it with the optional environment and extra args
+    /// applied, without sending anything.
+    pub fn parse(
+        &mut self,
+        env: Option<Env>,
+        extra_args: HashMap<String, serde_json::Value>,
+    ) -> Result<()> {
         // Make sure we have the file content loaded.
-        if self.fstr == None {
+        if self.fstr.is_none() {
             self.load()?;
         }
 
         // If env and/or cli args are provided, parse the request file with them applied.
-        self.apply_combined_args(env, extra_args)?;
+        let defaults = if self.templated {
+            EnvDefaults::default()
+        } else {
+            self.apply_combined_args(env, extra_args)?
+        };
 
         // Parse the request file.
-        let fstr = self.fstr.clone().unwrap();
-        let mut lines = fstr.lines();
+        let rendered = self.fstr.clone().unwrap_or_default();
+        let (front_matter, fstr) = frontmatter::parse(&rendered)?;
+        let mut lines = fstr.split_inclusive('\n').peekable();
+        let mut offset = 0;
+
+        // Errors point at the text they're about, by its offset and length in `fstr`, and name
+        // the line and column of the rendered request it's at.
+        let file = self.fpath.clone();
+        let prefix_len = rendered.len() - fstr.len();
+        let at = |start: usize, len: usize, e: anyhow::Error| -> anyhow::Error {
+            ReqqError::parse(
+                &file,
+                &rendered,
+                prefix_len + start,
+                len,
+                format!("{:#}", e),
+            )
+            .into()
+        };
 
         // Get method and URL.
-        let mut fline_parts = lines
+        let fline = lines
             .next()
-            .ok_or_else(|| anyhow!("Failed reading first line."))?
-            .splitn(2, ' ');
+            .ok_or_else(|| at(0, 0, anyhow!("Failed reading first line.")))?;
+        offset += fline.len();
+        let mut fline_parts = trim_line_ending(fline).splitn(2, ' ');
 
         let method_raw: &[u8] = fline_parts
             .next()
-            .ok_or_else(|| anyhow!("Failed reading first line."))?
+            .ok_or_else(|| at(0, 0, anyhow!("Failed reading first line.")))?
             .as_bytes();
-        let method = Method::from_bytes(method_raw)?;
+        let method = Method::from_bytes(method_raw).map_err(|_| {
+            at(
+                0,
+                method_raw.len(),
+                anyhow!("Invalid method '{}'.", String::from_utf8_lossy(method_raw)),
+            )
+        })?;
 
-        let url_raw = fline_parts
-            .next()
-            .ok_or_else(|| anyhow!("Failed reading first line."))?;
-        let url = Url::parse(url_raw)?;
+        let url_raw = fline_parts.next().ok_or_else(|| {
+            at(
+                method_raw.len(),
+                0,
+                anyhow!("The first line has no URL after the method."),
+            )
+        })?;
+        let url_at = |e: anyhow::Error| at(method_raw.len() + 1, url_raw.len(), e);
+        // Relative URLs are joined against the environment's `_base_url`, or the one the request
+        // was configured with.
+        let joined = match defaults.base_url.as_ref().or(self.base_url.as_ref()) {
+            Some(base) if url_raw.starts_with('/') && method.as_str() != grpc::METHOD => {
+                Some(format!("{}{}", base.trim_end_matches('/'), url_raw))
+            }
+            _ => None,
+        };
+        let mut url = match (method.as_str(), &joined) {
+            (grpc::METHOD, _) => grpc::url(url_raw).map_err(url_at)?,
+            (_, Some(joined)) => Url::parse(joined).map_err(|e| {
+                url_at(anyhow!(
+                    "Invalid URL '{}' after joining the base URL: {}",
+                    joined,
+                    e
+                ))
+            })?,
+            (_, None) => Url::parse(url_raw)
+                .map_err(|e| url_at(anyhow!("Invalid URL '{}': {}", url_raw, e)))?,
+        };
+
+        // Get query params, on `?key=value` and `&key=value` lines right after the first line.
+        let mut params: Vec<String> = vec![];
+        while let Some(line) = lines.next_if(|line| line.trim_start().starts_with(['?', '&'])) {
+            let content = trim_line_ending(line);
+            let indent = content.len() - content.trim_start().len();
+            params.push(
+                encode_query_param(&content.trim()[1..])
+                    .map_err(|e| at(offset + indent, content.trim().len(), e))?,
+            );
+            offset += line.len();
+        }
+        if !params.is_empty() {
+            let query: Vec<&str> = url
+                .query()
+                .filter(|query| !query.is_empty())
+                .into_iter()
+                .chain(params.iter().map(String::as_str))
+                .collect();
+            url.set_query(Some(&query.join("&")));
+        }
 
         let header_regex = Regex::new(r"^[A-Za-z0-9-]+:\s*.+$")?;
 
         let mut headers: Vec<(HeaderName, HeaderValue)> = vec![];
-        let mut body: Option<String> = None;
+        let mut body_start = fstr.len();
 
         // Get headers.
         for line in lines.by_ref() {
-            if !header_regex.is_match(line) {
-                // If we have a line that isn't a header, it's the start of the body.
-                body = Some(line.to_owned());
+            let content = trim_line_ending(line);
+            if !header_regex.is_match(content) {
+                // If we have a line that isn't a header, it's the start of the body. A blank
+                // line only separates the headers from the body and belongs to neither.
+                body_start = if content.is_empty() {
+                    offset + line.len()
+                } else {
+                    offset
+                };
                 break;
             }
 
-            let mut parts = line.splitn(2, ": ");
-
-            let name = HeaderName::from_bytes(parts.next().unwrap().as_bytes())?;
-            let val = HeaderValue::from_bytes(parts.next().unwrap().as_bytes())?;
+            // The regex makes sure there's a colon, but not that a space follows it.
+            let (name_raw, val_raw) = content.split_once(':').unwrap_or((content, ""));
+            let val_raw = val_raw.trim_start();
+            let name = HeaderName::from_bytes(name_raw.as_bytes()).map_err(|e| {
+                at(
+                    offset,
+                    name_raw.len(),
+                    anyhow!("Invalid header name '{}': {}", name_raw, e),
+                )
+            })?;
+            let val_at = offset + content.len() - val_raw.len();
+            let val = HeaderValue::from_bytes(val_raw.as_bytes()).map_err(|e| {
+                at(
+                    val_at,
+                    val_raw.len(),
+                    anyhow!("Invalid value of header '{}': {}", name, e),
+                )
+            })?;
+            offset += line.len();
 
             headers.push((name, val));
         }
+        let headers_end = offset;
+
+        // The environment's `_headers` apply unless the request file sets them itself.
+        let env_headers: Vec<(HeaderName, HeaderValue)> = defaults
+            .headers
+            .into_iter()
+            .filter(|(name, _)| !headers.iter().any(|(key, _)| key == name))
+            .collect();
 
-        // Get body.
-        if lines.clone().count() > 0 {
-            for line in lines.by_ref() {
-                body = Some(format!("{}\n{}", body.unwrap(), line));
+        // Get body, which is every byte after the headers (and separator line) up to EOF.
+        let text = self.trailing_newline.apply(&fstr[body_start..]);
+        let body = match (front_matter.body, front_matter.body_file) {
+            (BodyFormat::Text, Some(path)) if text.trim().is_empty() => {
+                Some(Body::File(self.dir().join(path)))
+            }
+            (_, Some(_)) => {
+                return Err(anyhow!(
+                "A request with @body-file can't have a body or use @form, @multipart or @graphql."
+            ))
+            }
+            (BodyFormat::GraphQL, None) => {
+                Some(Body::parse_graphql(&text).map_err(|e| at(body_start, 0, e))?)
             }
+            _ if text.is_empty() => None,
+            (BodyFormat::Text, None) => Some(Body::Text(text)),
+            (BodyFormat::Form, None) => {
+                Some(Body::parse_form(&text).map_err(|e| at(body_start, 0, e))?)
+            }
+            (BodyFormat::Multipart, None) => {
+                Some(Body::parse_multipart(&text, self.dir()).map_err(|e| at(body_start, 0, e))?)
+            }
+        };
+
+        // Overridden GraphQL variables are written into the rendered request, so it's recorded
+        // in the history as it was sent. Replays are sent as recorded.
+        let body = match body {
+            Some(mut body) if !self.templated && !self.gql_variables.is_empty() => {
+                body.override_variables(&self.gql_variables);
+                if matches!(body, Body::GraphQL { .. }) {
+                    let prefix_len = rendered.len() - fstr.len() + body_start;
+                    self.fstr = Some(format!("{}{}\n", &rendered[..prefix_len], body));
+                }
+                Some(body)
+            }
+            body => body,
+        };
+
+        // The joined URL and the environment's headers are written into the rendered request
+        // too, so replays of it don't depend on the environment.
+        if joined.is_some() || !env_headers.is_empty() {
+            let prefix_len = rendered.len() - fstr.len();
+            let mut written = self.fstr.clone().unwrap_or_default();
+            if !env_headers.is_empty() {
+                let at = prefix_len + headers_end;
+                let lines: Vec<String> = env_headers
+                    .iter()
+                    .map(|(name, value)| {
+                        format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes()))
+                    })
+                    .collect();
+                let lines = if written[..at].ends_with('\n') {
+                    format!("{}\n", lines.join("\n"))
+                } else {
+                    format!("\n{}", lines.join("\n"))
+                };
+                written.insert_str(at, &lines);
+            }
+            if let Some(joined) = &joined {
+                let start = prefix_len + method_raw.len() + 1;
+                written.replace_range(start..start + url_raw.len(), joined);
+            }
+            self.fstr = Some(written);
         }
+        headers.extend(env_headers);
+
+        // The environment's `auth` doesn't apply when the request file sets its own
+        // `Authorization` header.
+        let has_authorization = headers.iter().any(|(name, _)| name == AUTHORIZATION);
+        let auth = front_matter.auth.or(defaults
+            .auth
+            .filter(|auth| !(has_authorization && auth.uses_authorization())));
 
         self.inner = Some(RequestInner {
             url,
             method,
             headers,
             body,
+            timeout: front_matter.timeout,
+            asserts: front_matter.asserts,
+            expect: front_matter
+                .expect
+                .into_iter()
+                .filter_map(|status| StatusCode::from_u16(status).ok())
+                .collect(),
+            auth,
+            name: front_matter.name,
+            description: front_matter.description,
+            proto: front_matter.proto.map(|path| self.dir().join(path)),
+            schema: front_matter.schema.map(|path| self.dir().join(path)),
         });
 
         Ok(())
     }
 
-    /// Attempt to execute the request with an optional environment configuration file.
-    /// This will parse the request first, then send it using reqwest. The resulting
-    /// response is formatted and returned as a String.
-    pub fn execute(&mut self, env: Option<Env>, extra_args: HashMap<String, serde_json::Value>) -> Result<Response> {
-        self.parse(env, extra_args)?;
-        let resp = self.to_reqwest().send()?;
-        Ok(resp)
+    /// Whether the request opens a WebSocket, with `WS` as its method.
+    pub fn is_websocket(&self) -> bool {
+        self.method()
+            .is_some_and(|method| method.as_str() == websocket::METHOD)
     }
 
-    fn to_reqwest(&self) -> RequestBuilder {
-        let client = ReqwestClient::new();
+    /// The Unix socket the request is sent over, named in a `unix://` URL or given with
+    /// `--unix-socket`.
+    pub fn unix_socket(&self) -> Option<PathBuf> {
+        match self.url().and_then(unix_socket::split_url) {
+            Some((socket, _)) => Some(PathBuf::from(socket)),
+            None => self.client.unix_socket.clone(),
+        }
+    }
+
+    /// Fails when the request's `Content-Type` says its body is JSON, but it isn't. The error
+    /// points at the line and column where it breaks, and shows the line of the request file
+    /// it was rendered from, since a templated value that breaks the quoting is the usual cause.
+    pub fn check_json_body(&self) -> Result<()> {
+        let Some(Body::Text(text)) = self.body() else {
+            return Ok(());
+        };
+        let claims_json = self
+            .headers()
+            .unwrap_or_default()
+            .iter()
+            .any(|(name, value)| {
+                name == CONTENT_TYPE
+                    && value
+                        .to_str()
+                        .is_ok_and(|v| v.to_ascii_lowercase().contains("json"))
+            });
+        let Some((line, column, message)) = claims_json.then(|| body::json_error(text)).flatten()
+        else {
+            return Ok(());
+        };
+
+        let rendered_line = text.lines().nth(line - 1).unwrap_or_default();
+        let mut lines = vec![
+            format!(
+                "The JSON body is invalid at line {}, column {}: {}.",
+                line, column, message
+            ),
+            format!("  {}", rendered_line),
+            format!(
+                "  {}^",
+                " ".repeat(rendered_line.chars().take(column - 1).count())
+            ),
+        ];
+        // The body ends the file, so its line in the request file can be found by counting
+        // from the end, as long as templating didn't add or remove lines.
+        let source = if self.templated {
+            None
+        } else {
+            fs::read_to_string(&self.fpath).ok()
+        };
+        let rendered = self.fstr.as_deref().unwrap_or_default();
+        if let Some(source) =
+            source.filter(|source| source.lines().count() == rendered.lines().count())
+        {
+            let from_end = text.lines().count() - line;
+            if let Some(source_line) = source
+                .lines()
+                .rev()
+                .nth(from_end)
+                .filter(|l| *l != rendered_line)
+            {
+                lines.push(format!(
+                    "It's rendered from this line of the request file:\n  {}",
+                    source_line
+                ));
+            }
+        }
+        Err(anyhow!("{}", lines.join("\n")))
+    }
+
+    /// Whether the request is a gRPC call, with `GRPC` as its method.
+    pub fn is_grpc(&self) -> bool {
+        self.method()
+            .is_some_and(|method| method.as_str() == grpc::METHOD)
+    }
+
+    /// The HTTP method, once the request has been parsed.
+    pub fn method(&self) -> Option<&Method> {
+        self.inner().map(RequestInner::method)
+    }
+
+    /// The resolved URL, once the request has been parsed.
+    pub fn url(&self) -> Option<&Url> {
+        self.inner().map(RequestInner::url)
+    }
+
+    /// The request headers in file order, once the request has been parsed.
+    pub fn headers(&self) -> Option<&[(HeaderName, HeaderValue)]> {
+        self.inner().map(RequestInner::headers)
+    }
+
+    /// The request body, once the request has been parsed.
+    pub fn body(&self) -> Option<&Body> {
+        self.inner().and_then(RequestInner::body)
+    }
+
+    /// The folder of the request file, which paths in the request are relative to.
+    pub fn dir(&self) -> &Path {
+        Path::new(&self.fpath)
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+    }
+
+    /// The `@assert` directives of the request, once it has been parsed.
+    pub fn asserts(&self) -> &[Assertion] {
+        self.inner.as_ref().map_or(&[], |i| &i.asserts)
+    }
+
+    /// The statuses the response may have, given with `@expect`, once the request has been
+    /// parsed.
+    pub fn expect(&self) -> &[StatusCode] {
+        self.inner.as_ref().map_or(&[], |i| &i.expect)
+    }
+
+    /// The JSON Schema file the response body is checked against, once the request has been
+    /// parsed.
+    pub fn schema(&self) -> Option<&Path> {
+        self.inner.as_ref().and_then(|i| i.schema.as_deref())
+    }
+
+    /// The credentials the request is sent with, once it has been parsed.
+    pub fn auth(&self) -> Option<&Auth> {
+        self.inner.as_ref().and_then(|i| i.auth.as_ref())
+    }
+
+    /// Replaces the URL of a parsed request, like with the next page's when paginating.
+    pub fn set_url(&mut self, url: Url) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.url = url;
+        }
+    }
+
+    /// Replaces the credentials of a parsed request.
+    pub fn set_auth(&mut self, auth: Auth) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.auth = Some(auth);
+        }
+    }
+
+    /// The settings of the HTTP client the request is sent with.
+    pub fn client_opts(&self) -> &ClientOpts {
+        &self.client
+    }
+
+    /// The settings of the HTTP client the request is sent with, keeping only the `--resolve`
+    /// entries for the host and port of its URL, once it's been parsed.
+    pub fn url_client_opts(&self) -> ClientOpts {
+        match self.url() {
+            Some(url) => self.client.for_url(url),
+            None => self.client.clone(),
+        }
+    }
+
+    /// The request file contents with templating applied, once the request has been parsed.
+    pub fn rendered(&self) -> Option<&str> {
+        self.inner.as_ref().and(self.fstr.as_deref())
+    }
+
+    /// The fully rendered request, once the request has been parsed.
+    pub fn inner(&self) -> Option<&RequestInner> {
+        self.inner.as_ref()
+    }
+
+    /// The timeout for sending the request: the file's own `@timeout` if it has one,
+    /// otherwise the default timeout.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.inner
+            .as_ref()
+            .and_then(|i| i.timeout)
+            .or(self.default_timeout)
+    }
+
+    /// Summarizes a parsed request: method, resolved URL, header names and body length.
+    pub fn describe(&self) -> Result<String> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("Request has not been parsed."))?;
+        let header_names: Vec<&str> = inner.headers.iter().map(|(k, _)| k.as_str()).collect();
+
+        let mut lines = vec![];
+        if let Some(name) = &inner.name {
+            lines.push(format!("Name: {}", name));
+        }
+        if let Some(description) = &inner.description {
+            lines.push(format!("Description: {}", description));
+        }
+        lines.extend([
+            format!("Method: {}", inner.method),
+            format!("URL: {}", inner.url),
+            format!(
+                "Headers ({}): {}",
+                inner.headers.len(),
+                header_names.join(", ")
+            ),
+        ]);
+        let body_len = match self.body() {
+            Some(body) => body.size()?,
+            None => 0,
+        };
+        lines.push(format!("Body: {} bytes", body_len));
+        Ok(lines.join("\n"))
+    }
+
+    /// Sends the request using a reqwest client built from `client_opts`, which can be shared
+    /// by many sends. The request must be parsed first, with an optional environment applied.
+    pub fn send(&self, client: &Client) -> Result<Response> {
+        if self.inner.is_none() {
+            return Err(anyhow!("Request has not been parsed."));
+        }
+        self.to_reqwest(client, true)?
+            .send()
+            .map_err(|e| self.describe_error(e))
+    }
+
+    /// The reqwest request, built with a client but not sent, for sending it some other way.
+    /// A body streamed from a file can't be read back from it, so it's read from `streamed_file`.
+    pub fn build(&self, client: &Client) -> Result<reqwest::blocking::Request> {
+        Ok(self.to_reqwest(client, true)?.build()?)
+    }
+
+    /// The `@body-file` the body is streamed from when sent. Signed requests are read whole,
+    /// since signing needs the whole body.
+    pub fn streamed_file(&self) -> Option<&Path> {
+        let inner = self.inner.as_ref()?;
+        match &inner.body {
+            Some(Body::File(path)) if !matches!(inner.auth, Some(Auth::Aws(_))) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Like `send`, with reqwest's async client.
+    #[cfg(feature = "async")]
+    pub async fn send_async(&self, client: &reqwest::Client) -> Result<reqwest::Response> {
+        self.to_reqwest_async(client)?
+            .send()
+            .await
+            .map_err(|e| self.describe_error(e))
+    }
+
+    /// Reports timeouts with the limit that was hit, rather than reqwest's generic error. The
+    /// reqwest error is kept as the source, so it can still be inspected.
+    fn describe_error(&self, e: reqwest::Error) -> anyhow::Error {
+        if !e.is_timeout() {
+            return e.into();
+        }
+        let message = match (e.is_connect(), self.client.connect_timeout, self.timeout()) {
+            (true, Some(limit), _) => format!(
+                "Timed out connecting after {}.",
+                time::format_duration(limit)
+            ),
+            (_, _, Some(limit)) => {
+                format!("Request timed out after {}.", time::format_duration(limit))
+            }
+            _ => "Request timed out.".to_owned(),
+        };
+        anyhow::Error::new(e).context(message)
+    }
+
+    fn to_reqwest(&self, client: &Client, stream: bool) -> Result<RequestBuilder> {
+        let inner = self.parsed()?;
+        // reqwest only takes HTTP URLs, so requests to a named Unix socket are built for localhost.
+        let url = match unix_socket::split_url(&inner.url) {
+            Some((_, target)) => Url::parse(&format!("http://localhost{}", target))?,
+            None => inner.url.clone(),
+        };
+        let req = client.request(inner.method.clone(), url);
+        self.fill(req, |body| {
+            body.to_reqwest(stream && self.streamed_file().is_some())
+        })
+    }
+
+    /// Like `to_reqwest`, but body files are read whole rather than streamed, since reqwest's
+    /// async client only streams bodies with its `stream` feature.
+    #[cfg(feature = "async")]
+    fn to_reqwest_async(&self, client: &reqwest::Client) -> Result<reqwest::RequestBuilder> {
+        let inner = self.parsed()?;
+        let req = client.request(inner.method.clone(), inner.url.clone());
+        self.fill(req, |body| {
+            let encoded = body.encode()?;
+            Ok((encoded.content_type, encoded.bytes.into()))
+        })
+    }
+
+    fn parsed(&self) -> Result<&RequestInner> {
+        self.inner
+            .as_ref()
+            .ok_or_else(|| anyhow!("Request has not been parsed."))
+    }
+
+    /// Adds the headers, body, credentials and timeout to either of reqwest's request
+    /// builders, with the body converted for it by `convert`.
+    fn fill<B: FillRequest>(
+        &self,
+        mut req: B,
+        convert: impl FnOnce(&Body) -> Result<(Option<String>, B::Body)>,
+    ) -> Result<B> {
+        let inner = self.parsed()?;
+        for (key, val) in inner.headers.iter() {
+            req = req.with_header(key, val);
+        }
+
+        if let Some(body) = &inner.body {
+            let (content_type, body) = convert(body)?;
+            let has_content_type = inner.headers.iter().any(|(name, _)| name == CONTENT_TYPE);
+            if let (Some(content_type), false) = (content_type, has_content_type) {
+                req = req.with_header(&CONTENT_TYPE, &HeaderValue::try_from(content_type)?);
+            }
+            req = req.with_body(body);
+        } else if needs_empty_length(inner) {
+            req = req.with_header(&CONTENT_LENGTH, &HeaderValue::from(0));
+        }
+
+        // Signing covers the body and content type, so credentials are applied last.
+        if let Some(auth) = &inner.auth {
+            req = auth.apply(req)?;
+        }
+
+        if let Some(timeout) = self.timeout() {
+            req = req.with_timeout(timeout);
+        }
+
+        Ok(req)
+    }
+}
+
+/// The request builders a request is filled in on: reqwest's blocking one, and its async one
+/// with the `async` feature.
+trait FillRequest: Authorize {
+    type Body;
+    fn with_header(self, name: &HeaderName, value: &HeaderValue) -> Self;
+    fn with_body(self, body: Self::Body) -> Self;
+    fn with_timeout(self, timeout: Duration) -> Self;
+}
+
+/// Both builders have the same methods, but no trait in common.
+macro_rules! impl_fill_request {
+    ($builder:ty, $body:ty) => {
+        impl FillRequest for $builder {
+            type Body = $body;
+
+            fn with_header(self, name: &HeaderName, value: &HeaderValue) -> Self {
+                self.header(name, value)
+            }
 
-        let mut req = client.request(
-            self.inner.clone().unwrap().method,
-            self.inner.clone().unwrap().url,
-        );
+            fn with_body(self, body: Self::Body) -> Self {
+                self.body(body)
+            }
 
-        for (key, val) in self.inner.clone().unwrap().headers {
-            req = req.header(key, val);
+            fn with_timeout(self, timeout: Duration) -> Self {
+                self.timeout(timeout)
+            }
         }
+    };
+}
+
+impl_fill_request!(RequestBuilder, reqwest::blocking::Body);
+#[cfg(feature = "async")]
+impl_fill_request!(reqwest::RequestBuilder, reqwest::Body);
 
-        if self.inner.clone().unwrap().body.is_some() {
-            req = req.body(self.inner.clone().unwrap().body.unwrap());
+/// Whether a request without a body still needs `Content-Length: 0`, which reqwest leaves out,
+/// since some servers refuse `POST`, `PUT` and `PATCH` requests without a length.
+fn needs_empty_length(inner: &RequestInner) -> bool {
+    matches!(inner.method, Method::POST | Method::PUT | Method::PATCH)
+        && !inner.headers.iter().any(|(name, _)| name == CONTENT_LENGTH)
+}
+
+impl RequestInner {
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &[(HeaderName, HeaderValue)] {
+        &self.headers
+    }
+
+    pub fn body(&self) -> Option<&Body> {
+        self.body.as_ref()
+    }
+
+    /// The `.proto` file of a gRPC request.
+    pub fn proto(&self) -> Option<&Path> {
+        self.proto.as_deref()
+    }
+}
+
+/// Writes the rendered request in request file form: method and URL, headers, then the body
+/// after a blank line.
+impl fmt::Display for RequestInner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.url)?;
+        for (k, v) in self.headers.iter() {
+            write!(f, "\n{}: {}", k, String::from_utf8_lossy(v.as_bytes()))?;
         }
+        if let Some(body) = &self.body {
+            write!(f, "\n\n{}", body)?;
+        }
+        Ok(())
+    }
+}
 
-        req
+/// Percent-encodes a `key=value` or bare `key` query param line, without its leading `?` or
+/// `&`.
+fn encode_query_param(param: &str) -> Result<String> {
+    let encode =
+        |s: &str| url::form_urlencoded::byte_serialize(s.trim().as_bytes()).collect::<String>();
+    match param.split_once('=') {
+        _ if param.trim().is_empty() => Err(anyhow!("Expected a query param after '?' or '&'.")),
+        Some((key, value)) => Ok(format!("{}={}", encode(key), encode(value))),
+        None => Ok(encode(param)),
     }
 }
 
+/// Strips a single trailing `\n` or `\r\n` from a line.
+fn trim_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
 #[test]
 fn test_request_name() {
     let dir = ".reqq";
@@ -182,6 +1156,9 @@ fn test_request_name() {
 
     let req = Request::new(fpath);
     assert!(req.name(dir) == "nested/example-request");
+
+    let req = Request::in_section(".reqq/users.http".to_owned(), "create".to_owned());
+    assert_eq!(req.name(dir), "users#create");
 }
 
 #[test]
@@ -195,14 +1172,15 @@ x-example-header: lolwat"
     req.fstr = Some(fstr);
     let empty_extra_args: HashMap<String, serde_json::Value> = HashMap::new();
 
-    req.parse(None, empty_extra_args).expect("Failed to parse request.");
+    req.parse(None, empty_extra_args)
+        .expect("Failed to parse request.");
     let inner = req.clone().inner.unwrap();
 
     assert!(inner.method.as_str() == "GET");
     assert!(inner.url.as_str() == "https://example.com/");
     assert!(inner.headers[0].0 == HeaderName::from_bytes("x-example-header".as_bytes()).unwrap());
     assert!(inner.headers[0].1 == "lolwat");
-    assert!(inner.body == None);
+    assert!(inner.body.is_none());
 }
 
 #[test]
@@ -218,15 +1196,15 @@ request body content"
     req.fstr = Some(fstr);
     let empty_extra_args: HashMap<String, serde_json::Value> = HashMap::new();
 
-
-    req.parse(None, empty_extra_args).expect("Failed to parse request.");
+    req.parse(None, empty_extra_args)
+        .expect("Failed to parse request.");
     let inner = req.clone().inner.unwrap();
 
     assert!(inner.method.as_str() == "POST");
     assert!(inner.url.as_str() == "https://example.com/");
     assert!(inner.headers[0].0 == HeaderName::from_bytes("x-example-header".as_bytes()).unwrap());
     assert!(inner.headers[0].1 == "lolwat");
-    assert!(inner.body == Some("\nrequest body content".to_owned()));
+    assert!(inner.body == Some(Body::Text("request body content".to_owned())));
 }
 
 #[test]
@@ -248,14 +1226,15 @@ request {{ shwat }} content"
     req.fstr = Some(fstr);
     let empty_extra_args: HashMap<String, serde_json::Value> = HashMap::new();
 
-    req.parse(Some(env), empty_extra_args).expect("Failed to parse request.");
+    req.parse(Some(env), empty_extra_args)
+        .expect("Failed to parse request.");
     let inner = req.clone().inner.unwrap();
 
     assert!(inner.method.as_str() == "POST");
     assert!(inner.url.as_str() == "https://example.com/");
     assert!(inner.headers[0].0 == HeaderName::from_bytes("x-example-header".as_bytes()).unwrap());
     assert!(inner.headers[0].1 == "lolwat");
-    assert!(inner.body == Some("\nrequest 5 content".to_owned()));
+    assert!(inner.body == Some(Body::Text("request 5 content".to_owned())));
 }
 
 #[test]
@@ -280,14 +1259,15 @@ request {{ shwat }} {{ asdf }} content"
     let value = "thing";
     extra_args.insert(key, serde_json::to_value(value).unwrap());
 
-    req.parse(Some(env), extra_args).expect("Failed to parse request.");
+    req.parse(Some(env), extra_args)
+        .expect("Failed to parse request.");
     let inner = req.clone().inner.unwrap();
 
     assert!(inner.method.as_str() == "POST");
     assert!(inner.url.as_str() == "https://example.com/");
     assert!(inner.headers[0].0 == HeaderName::from_bytes("x-example-header".as_bytes()).unwrap());
     assert!(inner.headers[0].1 == "lolwat");
-    assert!(inner.body == Some("\nrequest 5 thing content".to_owned()));
+    assert!(inner.body == Some(Body::Text("request 5 thing content".to_owned())));
 }
 
 #[test]
@@ -306,12 +1286,663 @@ request {{ asdf }} content"
     let value = "thing";
     extra_args.insert(key, serde_json::to_value(value).unwrap());
 
-    req.parse(None, extra_args).expect("Failed to parse request.");
+    req.parse(None, extra_args)
+        .expect("Failed to parse request.");
     let inner = req.clone().inner.unwrap();
 
     assert!(inner.method.as_str() == "POST");
     assert!(inner.url.as_str() == "https://example.com/");
     assert!(inner.headers[0].0 == HeaderName::from_bytes("x-example-header".as_bytes()).unwrap());
     assert!(inner.headers[0].1 == "lolwat");
-    assert!(inner.body == Some("\nrequest thing content".to_owned()));
-}
\ No newline at end of file
+    assert!(inner.body == Some(Body::Text("request thing content".to_owned())));
+}
+
+#[cfg(test)]
+fn parse_body(fstr: &str, trailing_newline: TrailingNewline) -> Option<String> {
+    let mut req = Request::new(".reqq/body.reqq".to_owned());
+    req.fstr = Some(fstr.to_owned());
+    req.set_trailing_newline(trailing_newline);
+    req.parse(None, HashMap::new())
+        .expect("Failed to parse request.");
+    req.inner.unwrap().body.map(|body| body.to_string())
+}
+
+#[test]
+fn test_request_body_bytes_preserved() {
+    let fstr = "POST https://example.com\nx-example-header: lolwat\n\n{\"a\": 1}\n\n";
+    let body = parse_body(fstr, TrailingNewline::Preserve).unwrap();
+    assert_eq!(body.as_bytes(), b"{\"a\": 1}\n\n");
+
+    let fstr = "POST https://example.com\n\nline one\r\nline two";
+    let body = parse_body(fstr, TrailingNewline::Preserve).unwrap();
+    assert_eq!(body.as_bytes(), b"line one\r\nline two");
+}
+
+#[test]
+fn test_request_body_without_separator() {
+    let fstr = "POST https://example.com\nx-example-header: lolwat\nbody\n";
+    let body = parse_body(fstr, TrailingNewline::Preserve).unwrap();
+    assert_eq!(body.as_bytes(), b"body\n");
+}
+
+#[test]
+fn test_request_body_trailing_newline_always() {
+    let fstr = "POST https://example.com\n\nbody";
+    let body = parse_body(fstr, TrailingNewline::Always).unwrap();
+    assert_eq!(body.as_bytes(), b"body\n");
+
+    let fstr = "POST https://example.com\n\nbody\n";
+    let body = parse_body(fstr, TrailingNewline::Always).unwrap();
+    assert_eq!(body.as_bytes(), b"body\n");
+}
+
+#[test]
+fn test_request_body_trailing_newline_never() {
+    let fstr = "POST https://example.com\n\nbody\r\n";
+    let body = parse_body(fstr, TrailingNewline::Never).unwrap();
+    assert_eq!(body.as_bytes(), b"body");
+
+    let fstr = "POST https://example.com\n\nbody\n\n";
+    let body = parse_body(fstr, TrailingNewline::Never).unwrap();
+    assert_eq!(body.as_bytes(), b"body\n");
+}
+
+#[test]
+fn test_request_no_body_with_trailing_newline() {
+    let fstr = "GET https://example.com\nx-example-header: lolwat\n";
+    assert!(parse_body(fstr, TrailingNewline::Always).is_none());
+    assert!(parse_body(fstr, TrailingNewline::Preserve).is_none());
+}
+
+#[test]
+fn test_request_describe() {
+    let fpath = ".reqq/nested/exammple-request.reqq".to_owned();
+    let fstr = "POST https://example.com/users/{{ id }}
+x-example-header: lolwat
+content-type: application/json
+
+{\"name\": \"yep\"}"
+        .to_owned();
+
+    let env_str = "{\"id\": 5}".to_owned();
+    let env = Env {
+        fpath: "".to_owned(),
+        fstr: Some(env_str),
+    };
+
+    let mut req = Request::new(fpath);
+    req.fstr = Some(fstr);
+    assert!(req.describe().is_err());
+
+    req.parse(Some(env), HashMap::new())
+        .expect("Failed to parse request.");
+
+    assert_eq!(
+        req.describe().unwrap(),
+        "Method: POST
+URL: https://example.com/users/5
+Headers (2): x-example-header, content-type
+Body: 15 bytes"
+    );
+
+    let mut req = Request::new("get.reqq".to_owned());
+    req.fstr = Some(
+        "---\nname: Get user {{ id }}\ndescription: Fetches a user.\n---\nGET https://example.com"
+            .to_owned(),
+    );
+    req.parse(
+        None,
+        HashMap::from([("id".to_owned(), serde_json::json!(5))]),
+    )
+    .unwrap();
+    assert!(req
+        .describe()
+        .unwrap()
+        .starts_with("Name: Get user 5\nDescription: Fetches a user.\nMethod: GET\n"));
+}
+
+#[test]
+fn test_request_timeout_override() {
+    let fstr = "@timeout: 60
+GET https://example.com
+x-example-header: lolwat"
+        .to_owned();
+
+    let mut req = Request::new(".reqq/slow-report.reqq".to_owned());
+    req.fstr = Some(fstr);
+    req.set_default_timeout(Some(Duration::from_secs(5)));
+    req.parse(None, HashMap::new())
+        .expect("Failed to parse request.");
+
+    assert!(req.timeout() == Some(Duration::from_secs(60)));
+    assert!(req.method().unwrap().as_str() == "GET");
+    assert!(req.headers().unwrap().len() == 1);
+}
+
+#[test]
+fn test_request_timeout_default() {
+    let fstr = "GET https://example.com".to_owned();
+
+    let mut req = Request::new(".reqq/fast.reqq".to_owned());
+    req.fstr = Some(fstr);
+    req.set_default_timeout(Some(Duration::from_secs(5)));
+    req.parse(None, HashMap::new())
+        .expect("Failed to parse request.");
+
+    assert!(req.timeout() == Some(Duration::from_secs(5)));
+}
+
+#[test]
+fn test_request_parse_error_line() {
+    let mut req = Request::new(".reqq/users.reqq".to_owned());
+    req.fstr = Some(
+        "@timeout 5s\nGET https://example.com/users\nAccept: application/json\nX Bad: 1\n"
+            .to_owned(),
+    );
+    req.parse(None, HashMap::new()).unwrap();
+    // `X Bad: 1` isn't a header line, so it starts the body. Invalid values are errors.
+    req.fstr = Some(
+        "@timeout 5s\nGET https://example.com/users\nAccept: application/json\nX-Bad: \x7f\n"
+            .to_owned(),
+    );
+    let err = req.parse(None, HashMap::new()).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(ReqqError::Parse {
+            line: 4,
+            column: 8,
+            len: 1,
+            ..
+        })
+    ));
+    assert!(err
+        .to_string()
+        .starts_with("Invalid value of header 'x-bad'"));
+
+    req.fstr = Some("GET not a url\nAccept: */*".to_owned());
+    let err = req.parse(None, HashMap::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid URL 'not a url': relative URL without a base\n --> .reqq/users.reqq:1:5\n  |\n1 | GET not a url\n  |     ^^^^^^^^^"
+    );
+
+    req.fstr = Some("G(ET https://example.com".to_owned());
+    let err = req.parse(None, HashMap::new()).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("1 | G(ET https://example.com\n  | ^^^^"));
+    req.fstr = Some("GET".to_owned());
+    assert!(req
+        .parse(None, HashMap::new())
+        .unwrap_err()
+        .to_string()
+        .ends_with("1 | GET\n  |    ^"));
+}
+
+#[test]
+fn test_request_crlf_file() {
+    let dir = std::env::temp_dir().join(format!("reqq-crlf-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let fpath = dir.join("users.reqq");
+    fs::write(&fpath, "\u{feff}@timeout 5s\r\nPOST https://example.com/users\r\nAccept:application/json\r\nx-id: {{ id }}\r\n\r\n{\r\n  \"name\": \"Ada\"\r\n}").unwrap();
+
+    let mut req = Request::new(fpath.display().to_string());
+    req.parse(
+        None,
+        HashMap::from([("id".to_owned(), serde_json::json!(7))]),
+    )
+    .expect("Failed to parse request.");
+    assert_eq!(req.timeout(), Some(Duration::from_secs(5)));
+    assert_eq!(req.method().unwrap().as_str(), "POST");
+    assert_eq!(req.url().unwrap().as_str(), "https://example.com/users");
+    let headers = req.headers().unwrap();
+    assert_eq!(
+        (headers[0].0.as_str(), headers[0].1.to_str().unwrap()),
+        ("accept", "application/json")
+    );
+    assert_eq!(
+        (headers[1].0.as_str(), headers[1].1.to_str().unwrap()),
+        ("x-id", "7")
+    );
+    assert_eq!(
+        req.body(),
+        Some(&Body::Text("{\r\n  \"name\": \"Ada\"\r\n}".to_owned()))
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_request_base_url() {
+    let mut req = Request::new(".reqq/users.reqq".to_owned());
+    req.fstr = Some("GET /users?page=2".to_owned());
+    req.set_base_url(Some("https://example.com/v1/".to_owned()));
+    req.parse(None, HashMap::new()).unwrap();
+    assert_eq!(
+        req.url().unwrap().as_str(),
+        "https://example.com/v1/users?page=2"
+    );
+
+    let mut req = Request::new(".reqq/users.reqq".to_owned());
+    req.fstr = Some("GET https://other.example.com/users".to_owned());
+    req.set_base_url(Some("https://example.com/v1".to_owned()));
+    req.parse(None, HashMap::new()).unwrap();
+    assert_eq!(
+        req.url().unwrap().as_str(),
+        "https://other.example.com/users"
+    );
+
+    let mut req = Request::new(".reqq/users.reqq".to_owned());
+    req.fstr = Some("GET /users".to_owned());
+    assert!(req.parse(None, HashMap::new()).is_err());
+}
+
+#[test]
+fn test_request_rendered() {
+    let fstr = "@timeout: 5
+POST https://example.com
+x-example-header: {{ headerVal }}
+
+request {{ asdf }} content
+"
+    .to_owned();
+
+    let mut req = Request::new(".reqq/rendered.reqq".to_owned());
+    req.fstr = Some(fstr);
+    assert!(req.rendered().is_none());
+
+    let mut extra_args: HashMap<String, serde_json::Value> = HashMap::new();
+    extra_args.insert("headerVal".to_owned(), serde_json::json!("lolwat"));
+    extra_args.insert("asdf".to_owned(), serde_json::json!(5));
+    req.parse(None, extra_args)
+        .expect("Failed to parse request.");
+
+    assert_eq!(
+        req.rendered().unwrap(),
+        "@timeout: 5
+POST https://example.com
+x-example-header: lolwat
+
+request 5 content
+"
+    );
+}
+
+#[test]
+fn test_request_from_rendered() {
+    let rendered = "POST https://example.com\n\n{{ not a template }}".to_owned();
+    let mut req = Request::from_rendered("replay".to_owned(), rendered);
+    req.parse(None, HashMap::new())
+        .expect("Failed to parse request.");
+    assert_eq!(
+        req.body(),
+        Some(&Body::Text("{{ not a template }}".to_owned()))
+    );
+}
+
+#[test]
+fn test_request_inner_display() {
+    let fstr = "POST https://example.com/{{ path }}
+x-example-header: lolwat
+
+request body content"
+        .to_owned();
+
+    let mut req = Request::new(".reqq/display.reqq".to_owned());
+    req.fstr = Some(fstr);
+    let mut extra_args: HashMap<String, serde_json::Value> = HashMap::new();
+    extra_args.insert("path".to_owned(), serde_json::json!("users"));
+    req.parse(None, extra_args)
+        .expect("Failed to parse request.");
+
+    assert_eq!(
+        req.inner().unwrap().to_string(),
+        "POST https://example.com/users
+x-example-header: lolwat
+
+request body content"
+    );
+}
+
+#[test]
+fn test_request_with_os_env() {
+    std::env::set_var("REQQ_TEST_API_TOKEN", "s3cret");
+
+    let fstr = "GET https://example.com
+authorization: Bearer {{ env.REQQ_TEST_API_TOKEN }}
+x-missing: [{{ default env.REQQ_TEST_NOT_SET \"\" }}]"
+        .to_owned();
+
+    let mut req = Request::new(".reqq/os-env.reqq".to_owned());
+    req.fstr = Some(fstr);
+    req.parse(None, HashMap::new())
+        .expect("Failed to parse request.");
+
+    let headers = req.headers().unwrap();
+    assert!(headers[0].1 == "Bearer s3cret");
+    assert!(headers[1].1 == "[]");
+}
+
+#[test]
+fn test_request_env_auth() {
+    let env = || {
+        Env {
+        fpath: "".to_owned(),
+        fstr: Some("{\"user\": \"alice\", \"auth\": {\"type\": \"basic\", \"user\": \"{{ user }}\", \"pass\": \"secret\"}}".to_owned()),
+    }
+    };
+    let parse = |fstr: &str| {
+        let mut req = Request::new(".reqq/users/me.reqq".to_owned());
+        req.fstr = Some(fstr.to_owned());
+        req.parse(Some(env()), HashMap::new())
+            .expect("Failed to parse request.");
+        req.inner.unwrap().auth
+    };
+
+    let basic = Auth::Basic {
+        user: "alice".to_owned(),
+        pass: "secret".to_owned(),
+    };
+    assert_eq!(parse("GET https://example.com"), Some(basic));
+    assert_eq!(
+        parse("@auth none\nGET https://example.com"),
+        Some(Auth::None)
+    );
+    assert_eq!(
+        parse("GET https://example.com\nAuthorization: Bearer abc"),
+        None
+    );
+}
+
+#[test]
+fn test_request_env_defaults() {
+    let env = || {
+        Env {
+        fpath: "".to_owned(),
+        fstr: Some("{\"host\": \"example.com\", \"_base_url\": \"https://{{ host }}/v1\", \"_headers\": {\"X-Team\": \"core\", \"X-Version\": 2}}".to_owned()),
+    }
+    };
+    let mut req = Request::new(".reqq/users/get.reqq".to_owned());
+    req.fstr = Some("GET /users/{{ id }}\n?expand=true\nX-Team: mine\n\n{}".to_owned());
+    req.set_base_url(Some("https://config.example.com".to_owned()));
+    req.parse(
+        Some(env()),
+        HashMap::from([("id".to_owned(), serde_json::json!(5))]),
+    )
+    .unwrap();
+
+    assert_eq!(
+        req.url().unwrap().as_str(),
+        "https://example.com/v1/users/5?expand=true"
+    );
+    let headers = req.headers().unwrap();
+    assert_eq!(headers.len(), 2);
+    assert_eq!(headers[0].1, "mine");
+    assert_eq!(
+        headers[1],
+        (
+            HeaderName::from_static("x-version"),
+            HeaderValue::from_static("2")
+        )
+    );
+    assert_eq!(
+        req.rendered().unwrap(),
+        "GET https://example.com/v1/users/5\n?expand=true\nX-Team: mine\nx-version: 2\n\n{}"
+    );
+
+    let mut req = Request::new(".reqq/users/list.reqq".to_owned());
+    req.fstr = Some("GET /users".to_owned());
+    req.parse(Some(env()), HashMap::new()).unwrap();
+    assert_eq!(
+        req.rendered().unwrap(),
+        "GET https://example.com/v1/users\nx-team: core\nx-version: 2"
+    );
+
+    let mut replayed = Request::from_rendered(
+        ".reqq/users/list.reqq".to_owned(),
+        req.rendered().unwrap().to_owned(),
+    );
+    replayed.parse(None, HashMap::new()).unwrap();
+    assert_eq!(replayed.url(), req.url());
+    assert_eq!(replayed.headers().unwrap().len(), 2);
+}
+
+#[test]
+fn test_request_form_body() {
+    let mut req = Request::new(".reqq/users/create.reqq".to_owned());
+    req.fstr = Some("@form\nPOST https://example.com\n\nname={{ name }}\nrole=admin\n".to_owned());
+    let mut extra_args = HashMap::new();
+    extra_args.insert("name".to_owned(), serde_json::json!("Ada Lovelace"));
+    req.parse(None, extra_args)
+        .expect("Failed to parse request.");
+
+    let form = vec![
+        ("name".to_owned(), "Ada Lovelace".to_owned()),
+        ("role".to_owned(), "admin".to_owned()),
+    ];
+    assert_eq!(req.body(), Some(&Body::Form(form)));
+
+    let built = req
+        .to_reqwest(&Client::new(), true)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(
+        built.headers()["content-type"],
+        "application/x-www-form-urlencoded"
+    );
+    assert_eq!(
+        built.body().unwrap().as_bytes().unwrap(),
+        b"name=Ada+Lovelace&role=admin"
+    );
+}
+
+#[test]
+fn test_request_body_file() {
+    let dir = std::env::temp_dir().join(format!("reqq-body-file-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("payload.bin"), [0u8, 159, 146, 150]).unwrap();
+
+    let mut req = Request::new(dir.join("upload.reqq").display().to_string());
+    req.fstr = Some("@body-file ./payload.bin\nPUT https://example.com/upload\n".to_owned());
+    req.parse(None, HashMap::new())
+        .expect("Failed to parse request.");
+    assert_eq!(req.body(), Some(&Body::File(dir.join("./payload.bin"))));
+    assert!(req.describe().unwrap().ends_with("Body: 4 bytes"));
+
+    let mut req = Request::new(dir.join("upload.reqq").display().to_string());
+    req.fstr =
+        Some("@body-file ./payload.bin\nPUT https://example.com/upload\n\ninline".to_owned());
+    assert!(req.parse(None, HashMap::new()).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_request_aws_signs_body() {
+    let mut req = Request::new(".reqq/s3/put.reqq".to_owned());
+    req.fstr = Some(
+        "@auth {type: aws, access_key: AK, secret_key: SK, region: us-east-1, service: s3}\n\
+         PUT https://bucket.s3.amazonaws.com/key\n\nhello"
+            .to_owned(),
+    );
+    req.parse(None, HashMap::new())
+        .expect("Failed to parse request.");
+
+    let built = req
+        .to_reqwest(&Client::new(), true)
+        .unwrap()
+        .build()
+        .unwrap();
+    // The SHA-256 of `hello`, not of an empty body.
+    assert_eq!(
+        built.headers()["x-amz-content-sha256"],
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    );
+}
+
+#[test]
+fn test_request_graphql_body() {
+    let fstr = "@graphql\nPOST https://example.com/graphql\n\nquery User($id: ID!) { user(id: $id) { name } }\n\n{\"id\": {{ id }}}\n";
+    let mut req = Request::new(".reqq/users/get.reqq".to_owned());
+    req.fstr = Some(fstr.to_owned());
+    req.set_gql_variables(HashMap::from([("id".to_owned(), serde_json::json!(42))]));
+    req.parse(
+        None,
+        HashMap::from([("id".to_owned(), serde_json::json!(1))]),
+    )
+    .expect("Failed to parse request.");
+
+    let built = req
+        .to_reqwest(&Client::new(), true)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(built.headers()["content-type"], "application/json");
+    let envelope: serde_json::Value =
+        serde_json::from_slice(built.body().unwrap().as_bytes().unwrap()).unwrap();
+    assert_eq!(envelope["variables"], serde_json::json!({"id": 42}));
+
+    // The override is part of the rendered request, so replays send it too.
+    let mut replayed = Request::from_rendered(
+        ".reqq/users/get.reqq".to_owned(),
+        req.rendered().unwrap().to_owned(),
+    );
+    replayed
+        .parse(None, HashMap::new())
+        .expect("Failed to parse request.");
+    assert_eq!(replayed.body(), req.body());
+}
+
+#[test]
+fn test_request_query_params() {
+    let fstr = "GET https://example.com/search?sort=asc\n?page=2\n&filter={{{ filter }}}\n  &verbose\nAccept: application/json\n";
+    let mut req = Request::new(".reqq/search.reqq".to_owned());
+    req.fstr = Some(fstr.to_owned());
+    req.parse(
+        None,
+        HashMap::from([("filter".to_owned(), serde_json::json!("name = a&b/ü"))]),
+    )
+    .expect("Failed to parse request.");
+
+    assert_eq!(
+        req.url().unwrap().as_str(),
+        "https://example.com/search?sort=asc&page=2&filter=name+%3D+a%26b%2F%C3%BC&verbose"
+    );
+    assert_eq!(
+        req.url().unwrap().query_pairs().nth(2).unwrap().1,
+        "name = a&b/ü"
+    );
+    assert_eq!(req.headers().unwrap().len(), 1);
+
+    let mut req = Request::new(".reqq/search.reqq".to_owned());
+    req.fstr = Some("GET https://example.com\n?\n".to_owned());
+    assert!(req.parse(None, HashMap::new()).is_err());
+}
+
+#[test]
+fn test_request_values_not_escaped() {
+    let fstr = "POST https://example.com/search\n?q={{ value }}\nContent-Type: application/json\n\n{\"q\": \"{{ value }}\"}";
+    let value = r#"a&b=c <d> \"e\""#;
+    let mut req = Request::new(".reqq/search.reqq".to_owned());
+    req.fstr = Some(fstr.to_owned());
+    req.parse(
+        None,
+        HashMap::from([("value".to_owned(), serde_json::json!(value))]),
+    )
+    .expect("Failed to parse request.");
+
+    assert_eq!(req.url().unwrap().query_pairs().next().unwrap().1, value);
+    let body = req.inner.unwrap().body.unwrap().to_string();
+    assert_eq!(body, format!("{{\"q\": \"{}\"}}", value));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap()["q"],
+        r#"a&b=c <d> "e""#
+    );
+}
+
+#[test]
+fn test_request_empty_content_length() {
+    let mut req = Request::from_rendered(
+        "users/delete".to_owned(),
+        "POST https://example.com/users/7/archive\n".to_owned(),
+    );
+    req.parse(None, HashMap::new()).unwrap();
+    let built = req
+        .to_reqwest(&Client::new(), true)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(built.headers()["content-length"], "0");
+
+    let mut req = Request::from_rendered(
+        "users/get".to_owned(),
+        "GET https://example.com/users/7\n".to_owned(),
+    );
+    req.parse(None, HashMap::new()).unwrap();
+    let built = req
+        .to_reqwest(&Client::new(), true)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert!(!built.headers().contains_key("content-length"));
+}
+
+#[test]
+fn test_request_check_json_body() {
+    let fpath = std::env::temp_dir().join(format!("reqq-json-body-{}.reqq", std::process::id()));
+    fs::write(&fpath, "POST https://example.com/users\nContent-Type: application/json\n\n{\n  \"age\": {{ age }}\n}\n").unwrap();
+    let args =
+        |age: &str| HashMap::from([("age".to_owned(), serde_json::Value::String(age.to_owned()))]);
+
+    let mut req = Request::new(fpath.to_str().unwrap().to_owned());
+    req.parse(None, args("36")).unwrap();
+    assert!(req.check_json_body().is_ok());
+
+    let mut req = Request::new(fpath.to_str().unwrap().to_owned());
+    req.parse(None, args("old")).unwrap();
+    assert_eq!(
+        req.check_json_body().unwrap_err().to_string(),
+        "The JSON body is invalid at line 2, column 10: expected value.\n    \"age\": old\n           ^\n\
+         It's rendered from this line of the request file:\n    \"age\": {{ age }}"
+    );
+    fs::remove_file(fpath).unwrap();
+
+    // Bodies not sent as JSON aren't checked.
+    let mut req = Request::from_rendered(
+        "notes".to_owned(),
+        "POST https://example.com/notes\nContent-Type: text/plain\n\n{oops".to_owned(),
+    );
+    req.parse(None, HashMap::new()).unwrap();
+    assert!(req.check_json_body().is_ok());
+}
+
+#[test]
+fn test_request_file_variables() {
+    let fpath = std::env::temp_dir().join(format!("reqq-variables-{}.http", std::process::id()));
+    fs::write(&fpath, "@host = example.com\n@base = https://{{host}}/api\n### users\nGET {{base}}/users?q={{q}} HTTP/1.1\n").unwrap();
+    let fpath = fpath.display().to_string();
+
+    let mut req = Request::in_section(fpath.clone(), "users".to_owned());
+    req.parse(
+        None,
+        HashMap::from([("q".to_owned(), serde_json::json!("a&b"))]),
+    )
+    .unwrap();
+    assert_eq!(
+        req.url().unwrap().as_str(),
+        "https://example.com/api/users?q=a&b"
+    );
+
+    let mut req = Request::in_section(fpath.clone(), "users".to_owned());
+    let args = HashMap::from([
+        ("q".to_owned(), serde_json::json!("")),
+        ("host".to_owned(), serde_json::json!("localhost")),
+    ]);
+    req.parse(None, args).unwrap();
+    assert_eq!(
+        req.url().unwrap().as_str(),
+        "https://localhost/api/users?q="
+    );
+    fs::remove_file(fpath).unwrap();
+}
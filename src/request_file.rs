@@ -0,0 +1,138 @@
+//! The formats request files are written in: reqq's own, and `.http` and `.rest` files like
+//! those of VS Code's REST Client, which can hold several requests.
+
+use crate::http_file::{self, HttpFile};
+
+/// A file of requests, read into reqq's format for parsing.
+pub trait RequestFile {
+    /// The names of the requests in the file, or none if the whole file is a single request.
+    fn sections(&self) -> Vec<String>;
+    /// One of the requests in the file, or the whole file when no name is given, written in
+    /// reqq's format.
+    fn request(&self, section: Option<&str>) -> Option<String>;
+    /// Variables the file defines for its requests, as templates, in the order they're defined.
+    fn variables(&self) -> Vec<(String, String)>;
+}
+
+/// Reads the contents of a request file in the format its extension gives, normalized.
+pub fn open(fpath: &str, fstr: String) -> Box<dyn RequestFile> {
+    if http_file::EXTENSIONS.iter().any(|ext| fpath.ends_with(ext)) {
+        Box::new(HttpFile::new(normalize(&fstr, true)))
+    } else {
+        Box::new(ReqqFile {
+            fstr: normalize(&fstr, false),
+        })
+    }
+}
+
+/// Removes the byte order mark some Windows editors start files with.
+pub fn strip_bom(fstr: &str) -> &str {
+    fstr.strip_prefix('\u{feff}').unwrap_or(fstr)
+}
+
+/// Removes the byte order mark of a request file and turns the `\r\n` line endings of its head
+/// into `\n`, so files saved on Windows parse like any other. The body after the blank line
+/// ending the head keeps its bytes. With `sections`, each `###` line starts another head.
+pub fn normalize(fstr: &str, sections: bool) -> String {
+    let mut normalized = String::with_capacity(fstr.len());
+    let (mut in_yaml, mut has_request_line, mut in_body) = (false, false, false);
+    for (i, line) in strip_bom(fstr).split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\r', '\n']);
+        if sections && http_file::marker(content).is_some() {
+            (has_request_line, in_body) = (false, false);
+        }
+        if in_body {
+            normalized.push_str(line);
+            continue;
+        }
+        normalized.push_str(content);
+        if line.ends_with('\n') {
+            normalized.push('\n');
+        }
+
+        if i == 0 && content == "---" {
+            in_yaml = true;
+        } else if in_yaml {
+            in_yaml = content.trim_end() != "---";
+        } else if content.trim().is_empty() {
+            in_body = has_request_line;
+        } else if !content.starts_with(['@', '#']) && !content.trim_start().starts_with("//") {
+            has_request_line = true;
+        }
+    }
+    normalized
+}
+
+/// A file in reqq's own format, holding a single request.
+pub struct ReqqFile {
+    fstr: String,
+}
+
+impl RequestFile for ReqqFile {
+    fn sections(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn request(&self, section: Option<&str>) -> Option<String> {
+        section.is_none().then(|| self.fstr.clone())
+    }
+
+    fn variables(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+}
+
+#[test]
+fn test_request_file_open() {
+    let fstr = "@host = example.com\n### ping\nGET https://{{host}}/ping\n";
+    let file = open(".reqq/api.rest", fstr.to_owned());
+    assert_eq!(file.sections(), vec!["ping"]);
+    assert_eq!(
+        file.variables(),
+        vec![("host".to_owned(), "example.com".to_owned())]
+    );
+
+    let file = open(".reqq/api.reqq", fstr.to_owned());
+    assert!(file.sections().is_empty() && file.variables().is_empty());
+    assert_eq!(file.request(None).as_deref(), Some(fstr));
+    assert_eq!(file.request(Some("ping")), None);
+}
+
+#[test]
+fn test_request_file_normalize() {
+    assert_eq!(
+        normalize(
+            "\u{feff}@timeout 5s\r\nGET https://example.com\r\nAccept: */*\r\n\r\nbody\r\n\r\nend",
+            false
+        ),
+        "@timeout 5s\nGET https://example.com\nAccept: */*\n\nbody\r\n\r\nend"
+    );
+    assert_eq!(
+        normalize(
+            "---\r\nname: a\r\n\r\n---\r\nGET https://example.com\r\n",
+            false
+        ),
+        "---\nname: a\n\n---\nGET https://example.com\n"
+    );
+    assert_eq!(
+        normalize("GET https://example.com\n", false),
+        "GET https://example.com\n"
+    );
+    assert_eq!(
+        normalize(
+            "### a\r\nPOST https://a.com\r\n\r\nx\r\n### b\r\n\r\nPOST https://b.com\r\n\r\ny\r\n",
+            true
+        ),
+        "### a\nPOST https://a.com\n\nx\r\n### b\n\nPOST https://b.com\n\ny\r\n"
+    );
+
+    let file = open(
+        ".reqq/api.http",
+        "\u{feff}@host = example.com\r\n### ping\r\nGET https://{{host}}/ping\r\n".to_owned(),
+    );
+    assert_eq!(file.sections(), vec!["ping"]);
+    assert_eq!(
+        file.variables(),
+        vec![("host".to_owned(), "example.com".to_owned())]
+    );
+}
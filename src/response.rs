@@ -0,0 +1,320 @@
+use crate::compression::{self, Decoded};
+use crate::format::{self, format_binary, format_body, format_json};
+use crate::jsonpath;
+use anyhow::{anyhow, Result};
+use http::{header::CONTENT_TYPE, HeaderMap};
+use reqwest::{StatusCode, Version};
+use serde_json::{json, Map, Value};
+
+/// Which parts of a response get printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ResponseParts {
+    /// Only the body.
+    #[default]
+    Body,
+    /// Only the status line and headers.
+    Headers,
+    /// The status line and headers, followed by the body.
+    All,
+}
+
+/// Options controlling how a response is rendered.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderOpts {
+    pub parts: ResponseParts,
+    /// Print the body exactly as received, without pretty-printing or colors.
+    pub raw: bool,
+    /// Print only the text of HTML bodies.
+    pub html_text: bool,
+    pub color: bool,
+}
+
+/// A received response, with its body read into memory.
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: StatusCode,
+    pub version: Version,
+    pub headers: HeaderMap,
+    pub body: String,
+    /// The body as received when it's binary, like an image or a PDF, which `body` only
+    /// holds as lossy text.
+    pub binary: Option<Vec<u8>>,
+}
+
+impl Response {
+    /// A response with its body read as bytes, which are kept in `binary` too when they
+    /// aren't text.
+    pub fn new(status: StatusCode, version: Version, headers: HeaderMap, body: Vec<u8>) -> Self {
+        let text = String::from_utf8_lossy(&body).into_owned();
+        let binary = format::is_binary(&headers, &body).then_some(body);
+        Response {
+            status,
+            version,
+            headers,
+            body: text,
+            binary,
+        }
+    }
+
+    /// Reads a reqwest response, including its whole body, decoding it if it was compressed
+    /// with an encoding `compression` knows, and how it was.
+    pub fn read(resp: reqwest::blocking::Response) -> Result<(Self, Option<Decoded>)> {
+        let status = resp.status();
+        let version = resp.version();
+        let mut headers = resp.headers().clone();
+        if other_charset(&headers) && !compression::decodable(&headers) {
+            let body = resp.text()?;
+            let resp = Response {
+                status,
+                version,
+                headers,
+                body,
+                binary: None,
+            };
+            return Ok((resp, None));
+        }
+        let (body, decoded) = compression::decode(&mut headers, resp.bytes()?.to_vec())?;
+        Ok((Response::new(status, version, headers, body), decoded))
+    }
+
+    /// Reads an async reqwest response, including its whole body, like `read`.
+    #[cfg(feature = "async")]
+    pub async fn read_async(resp: reqwest::Response) -> Result<(Self, Option<Decoded>)> {
+        let status = resp.status();
+        let version = resp.version();
+        let mut headers = resp.headers().clone();
+        if other_charset(&headers) && !compression::decodable(&headers) {
+            let body = resp.text().await?;
+            let resp = Response {
+                status,
+                version,
+                headers,
+                body,
+                binary: None,
+            };
+            return Ok((resp, None));
+        }
+        let (body, decoded) = compression::decode(&mut headers, resp.bytes().await?.to_vec())?;
+        Ok((Response::new(status, version, headers, body), decoded))
+    }
+
+    /// The response as template variables, for requests that depend on it: `status`,
+    /// `headers` by lowercase name, and `body`, parsed if it's JSON.
+    pub fn to_template_value(&self) -> Value {
+        let mut headers = Map::new();
+        for (k, v) in self.headers.iter() {
+            let v = String::from_utf8_lossy(v.as_bytes()).into_owned();
+            match headers.get_mut(k.as_str()) {
+                Some(Value::String(existing)) => *existing = format!("{}, {}", existing, v),
+                _ => {
+                    headers.insert(k.as_str().to_owned(), Value::String(v));
+                }
+            }
+        }
+
+        let body =
+            serde_json::from_str(&self.body).unwrap_or_else(|_| Value::String(self.body.clone()));
+
+        json!({
+            "status": self.status.as_u16(),
+            "headers": headers,
+            "body": body,
+        })
+    }
+}
+
+/// Whether a response's `Content-Type` names a charset other than UTF-8, which reqwest
+/// decodes its body from.
+fn other_charset(headers: &HeaderMap) -> bool {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let charset = content_type
+        .split(';')
+        .filter_map(|p| p.trim().split_once('='))
+        .find(|(k, _)| k.eq_ignore_ascii_case("charset"));
+    charset.is_some_and(|(_, v)| !v.trim_matches('"').eq_ignore_ascii_case("utf-8"))
+}
+
+/// Renders the selected parts of a response for printing.
+pub fn render_response(resp: &Response, opts: RenderOpts) -> Result<String> {
+    let mut lines = vec![format!("{:?} {}", resp.version, resp.status)];
+    for (k, v) in resp.headers.iter() {
+        lines.push(format!("{}: {}", k, String::from_utf8_lossy(v.as_bytes())));
+    }
+    let head = lines.join("\n");
+
+    if opts.parts == ResponseParts::Headers {
+        return Ok(head);
+    }
+
+    // Binary bodies are summed up rather than printed, unless they're asked for raw.
+    let body = match (&resp.binary, opts.raw) {
+        (_, true) => resp.body.clone(),
+        (Some(bytes), false) => format_binary(&resp.headers, bytes),
+        (None, false) => format_body(&resp.headers, resp.body.clone(), opts.color, opts.html_text)?,
+    };
+
+    match opts.parts {
+        ResponseParts::All => Ok(format!("{}\n\n{}", head, body)),
+        _ => Ok(body),
+    }
+}
+
+/// Renders only the part of a JSON response body that a JSONPath expression matches. Strings
+/// are printed without quotes, everything else as JSON.
+pub fn filter_response(resp: &Response, path: &str, color: bool) -> Result<String> {
+    let body: Value = serde_json::from_str(&resp.body)
+        .map_err(|_| anyhow!("Can't filter the response, its body isn't JSON."))?;
+    match jsonpath::extract(path, &body)? {
+        Value::String(s) => Ok(s),
+        value => Ok(format_json(&value, color)),
+    }
+}
+
+#[cfg(test)]
+fn test_response(status: StatusCode, version: Version, body: &str) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", "application/json".parse().unwrap());
+    headers.insert("x-request-id", "abc".parse().unwrap());
+    Response {
+        status,
+        version,
+        headers,
+        body: body.to_owned(),
+        binary: None,
+    }
+}
+
+#[test]
+fn test_render_body_only() {
+    let opts = RenderOpts::default();
+    let out = render_response(
+        &test_response(StatusCode::OK, Version::HTTP_11, "{\"a\":1}"),
+        opts,
+    )
+    .unwrap();
+    assert_eq!(out, "{\n  \"a\": 1\n}");
+}
+
+#[test]
+fn test_render_include() {
+    let opts = RenderOpts {
+        parts: ResponseParts::All,
+        ..RenderOpts::default()
+    };
+    let out = render_response(
+        &test_response(StatusCode::NOT_FOUND, Version::HTTP_11, "{\"a\":1}"),
+        opts,
+    )
+    .unwrap();
+    assert_eq!(
+        out,
+        "HTTP/1.1 404 Not Found
+content-type: application/json
+x-request-id: abc
+
+{
+  \"a\": 1
+}"
+    );
+}
+
+#[test]
+fn test_render_headers_only() {
+    let opts = RenderOpts {
+        parts: ResponseParts::Headers,
+        ..RenderOpts::default()
+    };
+    let out = render_response(&test_response(StatusCode::OK, Version::HTTP_2, ""), opts).unwrap();
+    assert_eq!(
+        out,
+        "HTTP/2.0 200 OK\ncontent-type: application/json\nx-request-id: abc"
+    );
+}
+
+#[test]
+fn test_render_raw() {
+    let opts = RenderOpts {
+        raw: true,
+        ..RenderOpts::default()
+    };
+    let out = render_response(
+        &test_response(StatusCode::OK, Version::HTTP_11, "{\"a\":1}"),
+        opts,
+    )
+    .unwrap();
+    assert_eq!(out, "{\"a\":1}");
+}
+
+#[test]
+fn test_render_binary() {
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", "image/png".parse().unwrap());
+    let resp = Response::new(
+        StatusCode::OK,
+        Version::HTTP_11,
+        headers,
+        b"\x89PNG\r\n\x1a\n\0".to_vec(),
+    );
+    assert_eq!(resp.binary.as_deref(), Some(&b"\x89PNG\r\n\x1a\n\0"[..]));
+    let out = render_response(&resp, RenderOpts::default()).unwrap();
+    assert!(out.starts_with("Binary body: image/png, 9 bytes, sha256 "));
+    let raw = RenderOpts {
+        raw: true,
+        ..RenderOpts::default()
+    };
+    assert_eq!(render_response(&resp, raw).unwrap(), resp.body);
+
+    let text = Response::new(
+        StatusCode::OK,
+        Version::HTTP_11,
+        HeaderMap::new(),
+        b"ok".to_vec(),
+    );
+    assert_eq!((text.body.as_str(), text.binary), ("ok", None));
+}
+
+#[test]
+fn test_response_template_value() {
+    let mut resp = test_response(
+        StatusCode::CREATED,
+        Version::HTTP_11,
+        "{\"token\": \"abc\"}",
+    );
+    resp.headers.append("x-request-id", "def".parse().unwrap());
+    let value = resp.to_template_value();
+    assert_eq!(value["status"], 201);
+    assert_eq!(value["headers"]["content-type"], "application/json");
+    assert_eq!(value["headers"]["x-request-id"], "abc, def");
+    assert_eq!(value["body"]["token"], "abc");
+
+    let resp = test_response(StatusCode::OK, Version::HTTP_11, "plain text");
+    assert_eq!(resp.to_template_value()["body"], "plain text");
+}
+
+#[test]
+fn test_filter_response() {
+    let resp = test_response(
+        StatusCode::OK,
+        Version::HTTP_11,
+        r#"{"data": {"items": [{"id": 1, "name": "a"}]}}"#,
+    );
+    assert_eq!(
+        filter_response(&resp, ".data.items[0].id", false).unwrap(),
+        "1"
+    );
+    assert_eq!(
+        filter_response(&resp, "$.data.items[0].name", false).unwrap(),
+        "a"
+    );
+    assert_eq!(
+        filter_response(&resp, ".data.items[0]", false).unwrap(),
+        "{\n  \"id\": 1,\n  \"name\": \"a\"\n}"
+    );
+    assert!(filter_response(&resp, ".data.nope", false).is_err());
+
+    let resp = test_response(StatusCode::OK, Version::HTTP_11, "plain text");
+    assert!(filter_response(&resp, ".a", false).is_err());
+}
@@ -0,0 +1,536 @@
+//! JSON Schema validation of response bodies, for requests with a `@schema` directive. The
+//! keywords that constrain values are supported: `type`, `enum` and `const`, the object,
+//! array, string and number keywords, `allOf`, `anyOf`, `oneOf` and `not`, and `$ref`s within
+//! the schema file. Annotations like `format` and `description` are ignored.
+
+use crate::{assertion::AssertionResult, response::Response};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+/// A JSON Schema, loaded from a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonSchema {
+    root: Value,
+}
+
+/// A value that doesn't satisfy its schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// A JSON pointer to the value in the instance, like `/items/0/id`, or `` for the root.
+    pub pointer: String,
+    /// What the schema expected.
+    pub expected: String,
+    /// What was there instead.
+    pub actual: String,
+}
+
+impl JsonSchema {
+    /// Loads a schema from a JSON file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Couldn't read the schema '{}': {}", path.display(), e))?;
+        let root = serde_json::from_str(&json)
+            .map_err(|e| anyhow!("Malformed schema '{}': {}", path.display(), e))?;
+        Self::new(root).map_err(|e| anyhow!("Malformed schema '{}': {}", path.display(), e))
+    }
+
+    pub fn new(root: Value) -> Result<Self> {
+        match root {
+            Value::Object(_) | Value::Bool(_) => Ok(JsonSchema { root }),
+            _ => Err(anyhow!("a schema must be an object or a boolean")),
+        }
+    }
+
+    /// Every violation of the schema by a value, in the order they're found.
+    pub fn validate(&self, instance: &Value) -> Vec<Violation> {
+        let mut violations = vec![];
+        self.check(&self.root, instance, "", &mut violations);
+        violations
+    }
+
+    fn check(&self, schema: &Value, instance: &Value, pointer: &str, out: &mut Vec<Violation>) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                return out.push(violation(
+                    pointer,
+                    "nothing, as the schema is false",
+                    render(instance),
+                ))
+            }
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            match self.resolve(reference) {
+                Some(target) => self.check(target, instance, pointer, out),
+                None => out.push(violation(
+                    pointer,
+                    &format!("a resolvable $ref, not '{}'", reference),
+                    render(instance),
+                )),
+            }
+        }
+
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+                _ => vec![],
+            };
+            if !types.is_empty() && !types.iter().any(|t| has_type(instance, t)) {
+                out.push(violation(
+                    pointer,
+                    &format!("type {}", types.join(" or ")),
+                    type_name(instance).to_owned(),
+                ));
+            }
+        }
+        if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+            if !options.contains(instance) {
+                let options: Vec<String> = options.iter().map(Value::to_string).collect();
+                out.push(violation(
+                    pointer,
+                    &format!("one of {}", options.join(", ")),
+                    render(instance),
+                ));
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if constant != instance {
+                out.push(violation(pointer, &constant.to_string(), render(instance)));
+            }
+        }
+
+        match instance {
+            Value::Object(map) => self.check_object(schema, map, pointer, out),
+            Value::Array(items) => self.check_array(schema, items, pointer, out),
+            Value::String(s) => check_string(schema, s, pointer, out),
+            Value::Number(n) => check_number(schema, n.as_f64().unwrap_or_default(), pointer, out),
+            _ => {}
+        }
+
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for sub in all {
+                self.check(sub, instance, pointer, out);
+            }
+        }
+        if let Some(any) = schema.get("anyOf").and_then(Value::as_array) {
+            if !any.iter().any(|sub| self.matches(sub, instance)) {
+                out.push(violation(
+                    pointer,
+                    "a match for any of the anyOf schemas",
+                    render(instance),
+                ));
+            }
+        }
+        if let Some(one) = schema.get("oneOf").and_then(Value::as_array) {
+            let matched = one.iter().filter(|sub| self.matches(sub, instance)).count();
+            if matched != 1 {
+                let actual = format!("{} matching {}", render(instance), matched);
+                out.push(violation(
+                    pointer,
+                    "a match for exactly one of the oneOf schemas",
+                    actual,
+                ));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.matches(not, instance) {
+                out.push(violation(
+                    pointer,
+                    "no match for the not schema",
+                    render(instance),
+                ));
+            }
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &Map<String, Value>,
+        map: &Map<String, Value>,
+        pointer: &str,
+        out: &mut Vec<Violation>,
+    ) {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !map.contains_key(name) {
+                    out.push(violation(
+                        pointer,
+                        &format!("property '{}'", name),
+                        "missing".to_owned(),
+                    ));
+                }
+            }
+        }
+        let count = |key: &str| schema.get(key).and_then(Value::as_u64).map(|n| n as usize);
+        if let Some(min) = count("minProperties").filter(|min| map.len() < *min) {
+            out.push(violation(
+                pointer,
+                &format!("at least {} properties", min),
+                map.len().to_string(),
+            ));
+        }
+        if let Some(max) = count("maxProperties").filter(|max| map.len() > *max) {
+            out.push(violation(
+                pointer,
+                &format!("at most {} properties", max),
+                map.len().to_string(),
+            ));
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let patterns: Vec<(Regex, &Value)> = schema
+            .get("patternProperties")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(pattern, sub)| Some((Regex::new(pattern).ok()?, sub)))
+            .collect();
+        for (name, value) in map {
+            let child = format!("{}/{}", pointer, escape(name));
+            let mut known = false;
+            if let Some(sub) = properties.and_then(|p| p.get(name)) {
+                self.check(sub, value, &child, out);
+                known = true;
+            }
+            for (_, sub) in patterns.iter().filter(|(re, _)| re.is_match(name)) {
+                self.check(sub, value, &child, out);
+                known = true;
+            }
+            match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) if !known => {
+                    out.push(violation(&child, "no additional properties", render(value)))
+                }
+                Some(sub @ Value::Object(_)) if !known => self.check(sub, value, &child, out),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_array(
+        &self,
+        schema: &Map<String, Value>,
+        items: &[Value],
+        pointer: &str,
+        out: &mut Vec<Violation>,
+    ) {
+        let count = |key: &str| schema.get(key).and_then(Value::as_u64).map(|n| n as usize);
+        if let Some(min) = count("minItems").filter(|min| items.len() < *min) {
+            out.push(violation(
+                pointer,
+                &format!("at least {} items", min),
+                items.len().to_string(),
+            ));
+        }
+        if let Some(max) = count("maxItems").filter(|max| items.len() > *max) {
+            out.push(violation(
+                pointer,
+                &format!("at most {} items", max),
+                items.len().to_string(),
+            ));
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            if let Some(i) = (1..items.len()).find(|&i| items[..i].contains(&items[i])) {
+                out.push(violation(
+                    &format!("{}/{}", pointer, i),
+                    "unique items",
+                    render(&items[i]),
+                ));
+            }
+        }
+
+        // `prefixItems`, or `items` as an array in older drafts, constrain the first items,
+        // and `items`, or else `additionalItems`, the rest.
+        let prefix = match (schema.get("prefixItems"), schema.get("items")) {
+            (Some(Value::Array(prefix)), _) | (None, Some(Value::Array(prefix))) => {
+                prefix.as_slice()
+            }
+            _ => &[],
+        };
+        let rest = match schema.get("items") {
+            Some(Value::Array(_)) => schema.get("additionalItems"),
+            items => items,
+        };
+        for (i, item) in items.iter().enumerate() {
+            let child = format!("{}/{}", pointer, i);
+            if let Some(sub) = prefix.get(i).or(rest) {
+                self.check(sub, item, &child, out);
+            }
+        }
+        if let Some(contains) = schema.get("contains") {
+            if !items.iter().any(|item| self.matches(contains, item)) {
+                out.push(violation(
+                    pointer,
+                    "an item matching the contains schema",
+                    "none".to_owned(),
+                ));
+            }
+        }
+    }
+
+    fn matches(&self, schema: &Value, instance: &Value) -> bool {
+        let mut violations = vec![];
+        self.check(schema, instance, "", &mut violations);
+        violations.is_empty()
+    }
+
+    /// The part of the schema a `$ref` like `#/$defs/user` points to. Only references within
+    /// the schema are supported.
+    fn resolve(&self, reference: &str) -> Option<&Value> {
+        let pointer = reference.strip_prefix('#')?;
+        self.root.pointer(pointer)
+    }
+}
+
+fn check_string(schema: &Map<String, Value>, s: &str, pointer: &str, out: &mut Vec<Violation>) {
+    let length = s.chars().count();
+    if let Some(min) = schema
+        .get("minLength")
+        .and_then(Value::as_u64)
+        .filter(|min| (length as u64) < *min)
+    {
+        out.push(violation(
+            pointer,
+            &format!("at least {} characters", min),
+            render(&Value::from(s)),
+        ));
+    }
+    if let Some(max) = schema
+        .get("maxLength")
+        .and_then(Value::as_u64)
+        .filter(|max| (length as u64) > *max)
+    {
+        out.push(violation(
+            pointer,
+            &format!("at most {} characters", max),
+            render(&Value::from(s)),
+        ));
+    }
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        if Regex::new(pattern).is_ok_and(|re| !re.is_match(s)) {
+            out.push(violation(
+                pointer,
+                &format!("a match for /{}/", pattern),
+                render(&Value::from(s)),
+            ));
+        }
+    }
+}
+
+fn check_number(schema: &Map<String, Value>, n: f64, pointer: &str, out: &mut Vec<Violation>) {
+    let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+    let mut fail = |expected: String| out.push(violation(pointer, &expected, n.to_string()));
+    if let Some(min) = bound("minimum").filter(|min| n < *min) {
+        fail(format!(">= {}", min));
+    }
+    if let Some(max) = bound("maximum").filter(|max| n > *max) {
+        fail(format!("<= {}", max));
+    }
+    if let Some(min) = bound("exclusiveMinimum").filter(|min| n <= *min) {
+        fail(format!("> {}", min));
+    }
+    if let Some(max) = bound("exclusiveMaximum").filter(|max| n >= *max) {
+        fail(format!("< {}", max));
+    }
+    if let Some(divisor) = bound("multipleOf").filter(|d| *d > 0.0) {
+        let quotient = n / divisor;
+        if (quotient - quotient.round()).abs() > 1e-9 {
+            fail(format!("a multiple of {}", divisor));
+        }
+    }
+}
+
+/// Checks a response body against the schema of a request. Each violation is a failed
+/// result, named after the schema and the JSON pointer of the value, and a body that
+/// satisfies the schema gives a single passed result.
+pub fn check(path: &Path, resp: &Response) -> Vec<AssertionResult> {
+    let name = format!("schema {}", path.display());
+    let failed = |assertion: String, expected: String, actual: String| AssertionResult {
+        assertion,
+        passed: false,
+        expected,
+        actual,
+    };
+    let schema = match JsonSchema::load(path) {
+        Ok(schema) => schema,
+        Err(e) => return vec![failed(name, "a valid schema".to_owned(), e.to_string())],
+    };
+    let body: Value = match serde_json::from_str(&resp.body) {
+        Ok(body) => body,
+        Err(_) => {
+            return vec![failed(
+                name,
+                "a JSON body".to_owned(),
+                render(&Value::from(resp.body.as_str())),
+            )]
+        }
+    };
+    let violations = schema.validate(&body);
+    if violations.is_empty() {
+        return vec![AssertionResult {
+            assertion: name,
+            passed: true,
+            expected: "a body matching the schema".to_owned(),
+            actual: "a match".to_owned(),
+        }];
+    }
+    violations
+        .into_iter()
+        .map(|v| {
+            failed(
+                format!("{} at {}", name, display_pointer(&v.pointer)),
+                v.expected,
+                v.actual,
+            )
+        })
+        .collect()
+}
+
+fn violation(pointer: &str, expected: &str, actual: String) -> Violation {
+    Violation {
+        pointer: pointer.to_owned(),
+        expected: expected.to_owned(),
+        actual,
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "number" => value.is_number(),
+        name => type_name(value) == name,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// A value for a report, shortened if it's long.
+fn render(value: &Value) -> String {
+    let rendered = value.to_string();
+    match rendered.char_indices().nth(60) {
+        Some((end, _)) => format!("{}...", &rendered[..end]),
+        None => rendered,
+    }
+}
+
+/// Escapes a property name for a JSON pointer.
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+fn display_pointer(pointer: &str) -> &str {
+    if pointer.is_empty() {
+        "/"
+    } else {
+        pointer
+    }
+}
+
+#[test]
+fn test_schema_validate() {
+    use serde_json::json;
+
+    let schema = JsonSchema::new(json!({
+        "type": "object",
+        "required": ["id", "name"],
+        "properties": {
+            "id": {"type": "integer", "minimum": 1},
+            "name": {"type": "string", "minLength": 1},
+            "role": {"enum": ["admin", "user"]},
+            "tags": {"type": "array", "items": {"$ref": "#/$defs/tag"}, "uniqueItems": true},
+            "a/b": {"type": "boolean"}
+        },
+        "additionalProperties": false,
+        "$defs": {"tag": {"type": "string", "pattern": "^[a-z]+$"}}
+    }))
+    .unwrap();
+
+    assert!(schema
+        .validate(&json!({"id": 7, "name": "Ada", "role": "admin", "tags": ["a", "b"]}))
+        .is_empty());
+    assert!(schema
+        .validate(&json!({"id": 7.0, "name": "Ada"}))
+        .is_empty());
+
+    let violations = schema.validate(
+        &json!({"id": "7", "role": "root", "tags": ["a", "B", "a"], "a/b": 1, "extra": null}),
+    );
+    let found: Vec<(&str, &str, &str)> = violations
+        .iter()
+        .map(|v| (v.pointer.as_str(), v.expected.as_str(), v.actual.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("", "property 'name'", "missing"),
+            ("/a~1b", "type boolean", "integer"),
+            ("/extra", "no additional properties", "null"),
+            ("/id", "type integer", "string"),
+            ("/role", "one of \"admin\", \"user\"", "\"root\""),
+            ("/tags/2", "unique items", "\"a\""),
+            ("/tags/1", "a match for /^[a-z]+$/", "\"B\""),
+        ]
+    );
+
+    let schema =
+        JsonSchema::new(json!({"oneOf": [{"type": "string"}, {"type": "integer", "maximum": 10}]}))
+            .unwrap();
+    assert!(schema.validate(&json!(3)).is_empty());
+    assert_eq!(
+        schema.validate(&json!(11))[0].expected,
+        "a match for exactly one of the oneOf schemas"
+    );
+    assert!(JsonSchema::new(json!([])).is_err());
+}
+
+#[test]
+fn test_schema_check() {
+    use reqwest::{StatusCode, Version};
+
+    let path = std::env::temp_dir().join(format!("reqq-schema-{}.json", std::process::id()));
+    fs::write(
+        &path,
+        r#"{"type": "object", "properties": {"data": {"type": "array", "minItems": 1}}}"#,
+    )
+    .unwrap();
+    let response = |body: &str| Response {
+        status: StatusCode::OK,
+        version: Version::HTTP_11,
+        headers: Default::default(),
+        body: body.to_owned(),
+        binary: None,
+    };
+
+    let results = check(&path, &response(r#"{"data": [1]}"#));
+    assert!(results.len() == 1 && results[0].passed);
+    let results = check(&path, &response(r#"{"data": []}"#));
+    assert_eq!(
+        results[0].assertion,
+        format!("schema {} at /data", path.display())
+    );
+    assert_eq!(
+        (results[0].expected.as_str(), results[0].actual.as_str()),
+        ("at least 1 items", "0")
+    );
+    assert!(!check(&path, &response("<html>")).iter().any(|r| r.passed));
+    fs::remove_file(&path).unwrap();
+    assert!(check(&path, &response("{}"))[0]
+        .actual
+        .starts_with("Couldn't read the schema"));
+}
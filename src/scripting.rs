@@ -0,0 +1,1145 @@
+//! A small scripting language for request files, to set variables before a request is rendered
+//! and to check and extract values once its response arrives. Scripts are sandboxed: they can't
+//! touch files, processes or the network, are stopped after a fixed number of steps, and fail
+//! when they nest too deeply or build values too large.
+//!
+//! ```text
+//! let ts = now();
+//! vars.signature = hmac_sha256(vars.secret, vars.user + ":" + str(ts));
+//! assert response.status == 201, "expected the user to be created";
+//! for item in response.body.items {
+//!     assert item.id > 0;
+//! }
+//! vars.userId = response.body.data.id;
+//! ```
+//!
+//! Values are JSON values. Reading a field or index that doesn't exist gives `null`, while
+//! assigning to one creates it.
+
+use crate::{helpers, sigv4, time};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openssl::sha::sha256;
+use regex::Regex;
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// How many statements and loop iterations a script may run before it's stopped.
+const MAX_STEPS: usize = 100_000;
+
+/// How deeply blocks, brackets and chains of operators may nest, so parsing and running a
+/// script can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+/// How many bytes, roughly as JSON, a value a script builds may take, and how much a script may
+/// grow a variable by.
+const MAX_VALUE_SIZE: usize = 1 << 20;
+
+/// How deeply values a script builds may nest, which is deeper than JSON is parsed.
+const MAX_NESTING: usize = 256;
+
+/// Operators, longest first so `<=` isn't read as `<`.
+const PUNCTS: [&str; 25] = [
+    "==", "!=", "<=", ">=", "&&", "||", "=", "<", ">", "+", "-", "*", "/", "%", "!", "(", ")", "[",
+    "]", "{", "}", ",", ";", ".", ":",
+];
+
+const KEYWORDS: [&str; 9] = [
+    "let", "if", "else", "for", "in", "assert", "true", "false", "null",
+];
+
+/// Binary operators by precedence, loosest first.
+const LEVELS: [&[(&str, Op)]; 6] = [
+    &[("||", Op::Or)],
+    &[("&&", Op::And)],
+    &[("==", Op::Eq), ("!=", Op::Ne)],
+    &[("<", Op::Lt), ("<=", Op::Le), (">", Op::Gt), (">=", Op::Ge)],
+    &[("+", Op::Add), ("-", Op::Sub)],
+    &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem)],
+];
+
+/// A parsed script.
+#[derive(Clone, Debug)]
+pub struct Script {
+    stmts: Vec<Stmt>,
+}
+
+#[derive(Clone, Debug)]
+struct Stmt {
+    line: usize,
+    kind: StmtKind,
+}
+
+#[derive(Clone, Debug)]
+enum StmtKind {
+    Let(String, Expr),
+    Assign(Expr, Expr),
+    /// The condition, an optional message, and the condition as written, for failures.
+    Assert(Expr, Option<Expr>, String),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    For(String, Expr, Vec<Stmt>),
+    Expr(Expr),
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Literal(Value),
+    Var(String),
+    Field(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Array(Vec<Expr>),
+    Object(Vec<(String, Expr)>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl Script {
+    pub fn parse(source: &str) -> Result<Script> {
+        let mut parser = Parser {
+            source,
+            tokens: tokenize(source)?,
+            pos: 0,
+            depth: 0,
+        };
+        let mut stmts = vec![];
+        while parser.pos < parser.tokens.len() {
+            stmts.push(parser.stmt()?);
+        }
+        Ok(Script { stmts })
+    }
+
+    /// Runs the script with global variables, and returns them as the script left them,
+    /// along with any it declared with `let`.
+    pub fn run(&self, globals: Map<String, Value>) -> Result<Map<String, Value>> {
+        let sizes = globals
+            .iter()
+            .map(|(name, value)| (name.clone(), measure(value, usize::MAX).0))
+            .collect();
+        let mut machine = Machine {
+            scope: globals,
+            sizes,
+            steps: 0,
+        };
+        machine.block(&self.stmts)?;
+        Ok(machine.scope)
+    }
+}
+
+fn error(line: usize, message: impl Display) -> anyhow::Error {
+    anyhow!("line {}: {}", line, message)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(Number),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+struct Spanned {
+    token: Token,
+    line: usize,
+    /// Where the token starts and ends in the source, in bytes.
+    start: usize,
+    end: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Spanned>> {
+    let bytes = source.as_bytes();
+    let (mut tokens, mut line, mut i) = (vec![], 1, 0);
+    while i < bytes.len() {
+        let (start, at) = (i, line);
+        let token = match bytes[i] {
+            b'\n' => {
+                line += 1;
+                i += 1;
+                continue;
+            }
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = source[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                continue;
+            }
+            b'0'..=b'9' => {
+                while bytes.get(i).is_some_and(u8::is_ascii_digit) {
+                    i += 1;
+                }
+                if bytes.get(i) == Some(&b'.') && bytes.get(i + 1).is_some_and(u8::is_ascii_digit) {
+                    i += 1;
+                    while bytes.get(i).is_some_and(u8::is_ascii_digit) {
+                        i += 1;
+                    }
+                }
+                let text = &source[start..i];
+                let number = match text.parse::<i64>() {
+                    Ok(n) => Number::from(n),
+                    Err(_) => text
+                        .parse()
+                        .ok()
+                        .and_then(Number::from_f64)
+                        .ok_or_else(|| error(line, format!("invalid number '{}'", text)))?,
+                };
+                Token::Num(number)
+            }
+            b'"' | b'\'' => {
+                let (text, len) = string_literal(&source[i..])
+                    .ok_or_else(|| error(line, "unterminated string"))?;
+                line += source[i..i + len].matches('\n').count();
+                i += len;
+                Token::Str(text)
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while bytes
+                    .get(i)
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_')
+                {
+                    i += 1;
+                }
+                Token::Ident(source[start..i].to_owned())
+            }
+            _ => {
+                let punct = PUNCTS
+                    .iter()
+                    .find(|punct| source[i..].starts_with(**punct))
+                    .ok_or_else(|| {
+                        error(
+                            line,
+                            format!(
+                                "unexpected '{}'",
+                                source[i..].chars().next().unwrap_or_default()
+                            ),
+                        )
+                    })?;
+                i += punct.len();
+                Token::Punct(punct)
+            }
+        };
+        tokens.push(Spanned {
+            token,
+            line: at,
+            start,
+            end: i,
+        });
+    }
+    Ok(tokens)
+}
+
+/// A quoted string with its escapes resolved, and its length in the source, quotes included.
+fn string_literal(source: &str) -> Option<(String, usize)> {
+    let mut chars = source.char_indices();
+    let (_, quote) = chars.next()?;
+    let mut text = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Some((text, i + 1)),
+            '\\' => text.push(match chars.next()?.1 {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                other => other,
+            }),
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Spanned>,
+    pos: usize,
+    /// How many levels deep the token at `pos` is.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |t| t.line)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Punct(p)) if *p == punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Ident(i)) if i == keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_punct(&mut self, punct: &str) -> Result<()> {
+        if self.eat_punct(punct) {
+            Ok(())
+        } else {
+            Err(error(self.line(), format!("expected '{}'", punct)))
+        }
+    }
+
+    /// A name that isn't a keyword, for variables.
+    fn name(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Ident(name)) if !KEYWORDS.contains(&name.as_str()) => Ok(name),
+            _ => Err(error(self.line(), "expected a name")),
+        }
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            Err(error(self.line(), "nested too deeply"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Parses something nested a level deeper than what it's in.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.depth += 1;
+        self.check_depth(self.depth)?;
+        let parsed = parse(self);
+        self.depth -= 1;
+        parsed
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>> {
+        self.expect_punct("{")?;
+        let mut stmts = vec![];
+        while !self.eat_punct("}") {
+            if self.peek().is_none() {
+                return Err(error(self.line(), "expected '}'"));
+            }
+            stmts.push(self.nested(Self::stmt)?);
+        }
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt> {
+        let line = self.line();
+        let kind = if self.eat_keyword("let") {
+            let name = self.name()?;
+            self.expect_punct("=")?;
+            StmtKind::Let(name, self.expr()?)
+        } else if self.eat_keyword("assert") {
+            let start = self
+                .tokens
+                .get(self.pos)
+                .map_or(self.source.len(), |t| t.start);
+            let condition = self.expr()?;
+            let source = self.source[start..self.tokens[self.pos - 1].end].to_owned();
+            let message = if self.eat_punct(",") {
+                Some(self.expr()?)
+            } else {
+                None
+            };
+            StmtKind::Assert(condition, message, source)
+        } else if self.eat_keyword("if") {
+            return self.if_stmt(line);
+        } else if self.eat_keyword("for") {
+            let name = self.name()?;
+            if !self.eat_keyword("in") {
+                return Err(error(self.line(), "expected 'in'"));
+            }
+            let items = self.expr()?;
+            return Ok(Stmt {
+                line,
+                kind: StmtKind::For(name, items, self.block()?),
+            });
+        } else {
+            let expr = self.expr()?;
+            if self.eat_punct("=") {
+                if !matches!(expr, Expr::Var(_) | Expr::Field(..) | Expr::Index(..)) {
+                    return Err(error(
+                        line,
+                        "can only assign to variables, fields and indexes",
+                    ));
+                }
+                StmtKind::Assign(expr, self.expr()?)
+            } else {
+                StmtKind::Expr(expr)
+            }
+        };
+        self.eat_punct(";");
+        Ok(Stmt { line, kind })
+    }
+
+    fn if_stmt(&mut self, line: usize) -> Result<Stmt> {
+        let condition = self.expr()?;
+        let then = self.block()?;
+        let otherwise = if !self.eat_keyword("else") {
+            vec![]
+        } else if self.eat_keyword("if") {
+            vec![self.nested(|parser| parser.if_stmt(parser.line()))?]
+        } else {
+            self.block()?
+        };
+        Ok(Stmt {
+            line,
+            kind: StmtKind::If(condition, then, otherwise),
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        // Each operator in a chain like `a + b + c` nests what came before it a level deeper.
+        let mut chained = 0;
+        'ops: loop {
+            for (punct, op) in ops.iter() {
+                if self.eat_punct(punct) {
+                    chained += 1;
+                    self.check_depth(self.depth + chained)?;
+                    left = Expr::Binary(*op, Box::new(left), Box::new(self.binary(level + 1)?));
+                    continue 'ops;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat_punct("!") {
+            return Ok(Expr::Not(Box::new(self.nested(Self::unary)?)));
+        }
+        if self.eat_punct("-") {
+            return Ok(Expr::Neg(Box::new(self.nested(Self::unary)?)));
+        }
+        let mut expr = self.primary()?;
+        let mut chained = 0;
+        loop {
+            chained += 1;
+            self.check_depth(self.depth + chained)?;
+            if self.eat_punct(".") {
+                match self.next() {
+                    Some(Token::Ident(field)) => expr = Expr::Field(Box::new(expr), field),
+                    _ => return Err(error(self.line(), "expected a field name after '.'")),
+                }
+            } else if self.eat_punct("[") {
+                let index = self.nested(Self::expr)?;
+                self.expect_punct("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let line = self.line();
+        Ok(match self.next() {
+            Some(Token::Num(n)) => Expr::Literal(Value::Number(n)),
+            Some(Token::Str(s)) => Expr::Literal(Value::String(s)),
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" => Expr::Literal(Value::Null),
+                keyword if KEYWORDS.contains(&keyword) => {
+                    return Err(error(line, format!("unexpected '{}'", keyword)))
+                }
+                _ if self.eat_punct("(") => {
+                    Expr::Call(name, self.nested(|parser| parser.list(")"))?)
+                }
+                _ => Expr::Var(name),
+            },
+            Some(Token::Punct("(")) => {
+                let expr = self.nested(Self::expr)?;
+                self.expect_punct(")")?;
+                expr
+            }
+            Some(Token::Punct("[")) => Expr::Array(self.nested(|parser| parser.list("]"))?),
+            Some(Token::Punct("{")) => {
+                let mut fields = vec![];
+                while !self.eat_punct("}") {
+                    let key = match self.next() {
+                        Some(Token::Ident(key) | Token::Str(key)) => key,
+                        _ => return Err(error(self.line(), "expected a key")),
+                    };
+                    self.expect_punct(":")?;
+                    fields.push((key, self.nested(Self::expr)?));
+                    if !self.eat_punct(",") {
+                        self.expect_punct("}")?;
+                        break;
+                    }
+                }
+                Expr::Object(fields)
+            }
+            _ => return Err(error(line, "expected a value")),
+        })
+    }
+
+    /// Comma separated expressions up to a closing bracket, which may follow a trailing comma.
+    fn list(&mut self, close: &str) -> Result<Vec<Expr>> {
+        let mut items = vec![];
+        while !self.eat_punct(close) {
+            items.push(self.expr()?);
+            if !self.eat_punct(",") {
+                self.expect_punct(close)?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+}
+
+struct Machine {
+    scope: Map<String, Value>,
+    /// The size of each variable when it was last set as a whole, which assignments to its
+    /// fields and indexes may only grow by `MAX_VALUE_SIZE`.
+    sizes: HashMap<String, usize>,
+    steps: usize,
+}
+
+impl Machine {
+    fn block(&mut self, stmts: &[Stmt]) -> Result<()> {
+        stmts.iter().try_for_each(|stmt| self.stmt(stmt))
+    }
+
+    fn step(&mut self, line: usize) -> Result<()> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            Err(error(line, format!("stopped after {} steps", MAX_STEPS)))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<()> {
+        self.step(stmt.line)?;
+        let at = |e: anyhow::Error| error(stmt.line, e);
+        match &stmt.kind {
+            StmtKind::Let(name, expr) => {
+                let value = self.eval(expr).map_err(at)?;
+                self.set(name, value);
+            }
+            StmtKind::Assign(place, expr) => {
+                let value = self.eval(expr).map_err(at)?;
+                self.assign(place, value).map_err(at)?;
+            }
+            StmtKind::Assert(condition, message, source) => {
+                if !truthy(&self.eval(condition).map_err(at)?) {
+                    let message = match message {
+                        Some(message) => to_text(&self.eval(message).map_err(at)?),
+                        None => format!("assertion failed: {}", source),
+                    };
+                    return Err(error(stmt.line, message));
+                }
+            }
+            StmtKind::If(condition, then, otherwise) => {
+                let branch = if truthy(&self.eval(condition).map_err(at)?) {
+                    then
+                } else {
+                    otherwise
+                };
+                self.block(branch)?;
+            }
+            StmtKind::For(name, items, body) => {
+                let items = match self.eval(items).map_err(at)? {
+                    Value::Array(items) => items,
+                    Value::Object(map) => {
+                        map.into_iter().map(|(key, _)| Value::String(key)).collect()
+                    }
+                    other => return Err(at(anyhow!("can't loop over {}", type_name(&other)))),
+                };
+                for item in items {
+                    self.step(stmt.line)?;
+                    self.set(name, item);
+                    self.block(body)?;
+                }
+            }
+            StmtKind::Expr(expr) => {
+                self.eval(expr).map_err(at)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, name: &str, value: Value) {
+        self.sizes
+            .insert(name.to_owned(), measure(&value, usize::MAX).0);
+        self.scope.insert(name.to_owned(), value);
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value> {
+        Ok(match expr {
+            Expr::Literal(value) => value.clone(),
+            Expr::Var(name) => self
+                .scope
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow!("unknown variable '{}'", name))?,
+            Expr::Field(target, field) => match self.eval(target)? {
+                Value::Object(mut map) => map.remove(field).unwrap_or(Value::Null),
+                _ => Value::Null,
+            },
+            Expr::Index(target, index) => {
+                let target = self.eval(target)?;
+                index_of(target, &self.eval(index)?)
+            }
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                built(call(name, &args)?)?
+            }
+            Expr::Array(items) => built(Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.eval(item))
+                    .collect::<Result<_>>()?,
+            ))?,
+            Expr::Object(fields) => built(Value::Object(
+                fields
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), self.eval(value)?)))
+                    .collect::<Result<_>>()?,
+            ))?,
+            Expr::Not(expr) => Value::Bool(!truthy(&self.eval(expr)?)),
+            Expr::Neg(expr) => match self.eval(expr)? {
+                Value::Number(n) => arith(Op::Sub, &Number::from(0), &n)?,
+                other => return Err(anyhow!("can't negate {}", type_name(&other))),
+            },
+            Expr::Binary(Op::And, left, right) => {
+                Value::Bool(truthy(&self.eval(left)?) && truthy(&self.eval(right)?))
+            }
+            Expr::Binary(Op::Or, left, right) => {
+                Value::Bool(truthy(&self.eval(left)?) || truthy(&self.eval(right)?))
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left)?;
+                built(binary(*op, left, self.eval(right)?)?)?
+            }
+        })
+    }
+
+    /// Sets a variable, or a field or index inside one, creating objects for missing fields.
+    fn assign(&mut self, place: &Expr, value: Value) -> Result<()> {
+        let mut path = vec![];
+        let mut node = place;
+        let root = loop {
+            match node {
+                Expr::Field(target, field) => {
+                    path.push(Value::String(field.clone()));
+                    node = target;
+                }
+                Expr::Index(target, index) => {
+                    path.push(self.eval(index)?);
+                    node = target;
+                }
+                Expr::Var(name) => break name,
+                _ => return Err(anyhow!("can only assign to variables, fields and indexes")),
+            }
+        };
+        if path.is_empty() && self.scope.contains_key(root) {
+            self.set(root, value);
+            return Ok(());
+        }
+        // A value assigned inside itself, like with `a[0] = a`, nests a level deeper each time.
+        if path.len() + measure(&value, MAX_VALUE_SIZE).1 > MAX_NESTING {
+            return Err(anyhow!("value nested too deeply"));
+        }
+        let limit = self
+            .sizes
+            .get(root)
+            .copied()
+            .unwrap_or_default()
+            .saturating_add(MAX_VALUE_SIZE);
+        let mut slot = self
+            .scope
+            .get_mut(root)
+            .ok_or_else(|| anyhow!("unknown variable '{}', declare it with let", root))?;
+        for key in path.iter().rev() {
+            if slot.is_null() && key.is_string() {
+                *slot = Value::Object(Map::new());
+            }
+            slot = match (slot, key) {
+                (Value::Object(map), Value::String(key)) => {
+                    map.entry(key.clone()).or_insert(Value::Null)
+                }
+                (Value::Array(items), Value::Number(n)) => {
+                    let len = items.len();
+                    let i = n
+                        .as_i64()
+                        .and_then(|i| position(i, len))
+                        .ok_or_else(|| anyhow!("index {} is out of range", n))?;
+                    &mut items[i]
+                }
+                (slot, key) => return Err(anyhow!("can't set {} of {}", key, type_name(slot))),
+            };
+        }
+        *slot = value;
+        if measure(&self.scope[root], limit).0 > limit {
+            Err(anyhow!("value too large"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The size of a value in bytes, roughly as JSON, counted up to just past `limit`, and how
+/// deeply it nests. Values are walked without recursing, since they may be nested deeply.
+fn measure(value: &Value, limit: usize) -> (usize, usize) {
+    let (mut size, mut depth) = (0, 0);
+    let mut stack = vec![(value, 1)];
+    while let Some((value, level)) = stack.pop() {
+        depth = depth.max(level);
+        size += match value {
+            Value::String(s) => s.len() + 2,
+            Value::Array(items) => {
+                stack.extend(items.iter().map(|item| (item, level + 1)));
+                items.len() + 2
+            }
+            Value::Object(map) => {
+                stack.extend(map.values().map(|item| (item, level + 1)));
+                map.keys().map(|key| key.len() + 4).sum::<usize>() + 2
+            }
+            _ => 8,
+        };
+        if size > limit {
+            break;
+        }
+    }
+    (size, depth)
+}
+
+/// Checks a value a script built, so that doubling a string or list over and over, or nesting
+/// a list in itself, fails rather than running reqq out of memory or stack.
+fn built(value: Value) -> Result<Value> {
+    let (size, depth) = measure(&value, MAX_VALUE_SIZE);
+    if size > MAX_VALUE_SIZE {
+        return Err(anyhow!("value too large"));
+    }
+    if depth > MAX_NESTING {
+        Err(anyhow!("value nested too deeply"))
+    } else {
+        Ok(value)
+    }
+}
+
+/// An index into a list of `len` items, counting from the end if it's negative.
+fn position(i: i64, len: usize) -> Option<usize> {
+    if i < 0 {
+        len.checked_sub(i.unsigned_abs() as usize)
+    } else {
+        Some(i as usize).filter(|i| *i < len)
+    }
+}
+
+fn index_of(target: Value, index: &Value) -> Value {
+    match (target, index) {
+        (Value::Array(mut items), Value::Number(n)) => {
+            match n.as_i64().and_then(|i| position(i, items.len())) {
+                Some(i) => items.swap_remove(i),
+                None => Value::Null,
+            }
+        }
+        (Value::Object(mut map), Value::String(key)) => map.remove(key).unwrap_or(Value::Null),
+        (Value::String(s), Value::Number(n)) => {
+            let chars: Vec<char> = s.chars().collect();
+            n.as_i64()
+                .and_then(|i| position(i, chars.len()))
+                .map_or(Value::Null, |i| Value::String(chars[i].to_string()))
+        }
+        _ => Value::Null,
+    }
+}
+
+fn binary(op: Op, left: Value, right: Value) -> Result<Value> {
+    Ok(match (op, left, right) {
+        (Op::Eq, left, right) => Value::Bool(equal(&left, &right)),
+        (Op::Ne, left, right) => Value::Bool(!equal(&left, &right)),
+        (Op::Lt | Op::Le | Op::Gt | Op::Ge, left, right) => {
+            let ordering = match (&left, &right) {
+                (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+                (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                anyhow!(
+                    "can't compare {} and {}",
+                    type_name(&left),
+                    type_name(&right)
+                )
+            })?;
+            Value::Bool(match op {
+                Op::Lt => ordering.is_lt(),
+                Op::Le => ordering.is_le(),
+                Op::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+        (Op::Add, Value::String(a), b) => Value::String(a + &to_text(&b)),
+        (Op::Add, a, Value::String(b)) => Value::String(to_text(&a) + &b),
+        (Op::Add, Value::Array(mut a), Value::Array(b)) => {
+            a.extend(b);
+            Value::Array(a)
+        }
+        (op, Value::Number(a), Value::Number(b)) => arith(op, &a, &b)?,
+        (_, left, right) => {
+            return Err(anyhow!(
+                "can't do arithmetic with {} and {}",
+                type_name(&left),
+                type_name(&right)
+            ))
+        }
+    })
+}
+
+/// Arithmetic that stays in integers while the result is one.
+fn arith(op: Op, a: &Number, b: &Number) -> Result<Value> {
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        let exact = match op {
+            Op::Add => a.checked_add(b),
+            Op::Sub => a.checked_sub(b),
+            Op::Mul => a.checked_mul(b),
+            Op::Div if a.checked_rem(b) == Some(0) => a.checked_div(b),
+            Op::Rem => a.checked_rem(b),
+            _ => None,
+        };
+        if let Some(n) = exact {
+            return Ok(Value::from(n));
+        }
+    }
+    let (a, b) = (
+        a.as_f64().unwrap_or(f64::NAN),
+        b.as_f64().unwrap_or(f64::NAN),
+    );
+    let n = match op {
+        Op::Div | Op::Rem if b == 0.0 => return Err(anyhow!("division by zero")),
+        Op::Add => a + b,
+        Op::Sub => a - b,
+        Op::Mul => a * b,
+        Op::Div => a / b,
+        Op::Rem => a % b,
+        _ => return Err(anyhow!("unsupported operator")),
+    };
+    Number::from_f64(n)
+        .map(Value::Number)
+        .ok_or_else(|| anyhow!("the result isn't a number"))
+}
+
+/// Equality where `1` and `1.0` are the same.
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| equal(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+/// Strings as they are, anything else as JSON.
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Calls a built-in function.
+fn call(name: &str, args: &[Value]) -> Result<Value> {
+    Ok(match (name, args) {
+        ("len", [Value::String(s)]) => Value::from(s.chars().count()),
+        ("len", [Value::Array(items)]) => Value::from(items.len()),
+        ("len", [Value::Object(map)]) => Value::from(map.len()),
+        ("str", [value]) => Value::String(to_text(value)),
+        ("num", [Value::Number(n)]) => Value::Number(n.clone()),
+        ("num", [Value::String(s)]) => match s.trim().parse::<i64>() {
+            Ok(n) => Value::from(n),
+            Err(_) => s
+                .trim()
+                .parse()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| anyhow!("'{}' isn't a number", s))?,
+        },
+        ("json", [Value::String(s)]) => {
+            serde_json::from_str(s).map_err(|e| anyhow!("invalid JSON: {}", e))?
+        }
+        ("to_json", [value]) => Value::String(value.to_string()),
+        ("upper", [Value::String(s)]) => Value::String(s.to_uppercase()),
+        ("lower", [Value::String(s)]) => Value::String(s.to_lowercase()),
+        ("trim", [Value::String(s)]) => Value::String(s.trim().to_owned()),
+        ("contains", [Value::String(s), part]) => Value::Bool(s.contains(&to_text(part))),
+        ("contains", [Value::Array(items), item]) => {
+            Value::Bool(items.iter().any(|i| equal(i, item)))
+        }
+        ("contains", [Value::Object(map), Value::String(key)]) => {
+            Value::Bool(map.contains_key(key))
+        }
+        ("starts_with", [Value::String(s), Value::String(prefix)]) => {
+            Value::Bool(s.starts_with(prefix.as_str()))
+        }
+        ("ends_with", [Value::String(s), Value::String(suffix)]) => {
+            Value::Bool(s.ends_with(suffix.as_str()))
+        }
+        ("matches", [Value::String(s), Value::String(pattern)]) => {
+            Value::Bool(Regex::new(pattern)?.is_match(s))
+        }
+        ("split", [Value::String(s), Value::String(sep)]) => s.split(sep.as_str()).collect(),
+        ("join", [Value::Array(items), Value::String(sep)]) => {
+            Value::String(items.iter().map(to_text).collect::<Vec<_>>().join(sep))
+        }
+        ("keys", [Value::Object(map)]) => map.keys().cloned().collect(),
+        ("base64", [Value::String(s)]) => Value::String(STANDARD.encode(s)),
+        ("base64_decode", [Value::String(s)]) => {
+            Value::String(String::from_utf8(STANDARD.decode(s)?)?)
+        }
+        ("sha256", [Value::String(s)]) => Value::String(sigv4::hex(&sha256(s.as_bytes()))),
+        ("hmac_sha256", [Value::String(key), Value::String(message)]) => Value::String(sigv4::hex(
+            &sigv4::hmac_sha256(key.as_bytes(), message.as_bytes())?,
+        )),
+        ("uuid", []) => Value::String(helpers::uuid_v4()),
+        ("now", []) => Value::from(time::now_millis() / 1000),
+        ("now_ms", []) => Value::from(time::now_millis()),
+        ("log", args) => {
+            eprintln!("{}", args.iter().map(to_text).collect::<Vec<_>>().join(" "));
+            Value::Null
+        }
+        _ if FUNCTIONS.contains(&name) => {
+            let types: Vec<&str> = args.iter().map(type_name).collect();
+            return Err(anyhow!("can't call {}() with ({})", name, types.join(", ")));
+        }
+        _ => return Err(anyhow!("unknown function '{}'", name)),
+    })
+}
+
+/// The built-in functions, to tell wrong arguments from unknown functions.
+const FUNCTIONS: [&str; 23] = [
+    "len",
+    "str",
+    "num",
+    "json",
+    "to_json",
+    "upper",
+    "lower",
+    "trim",
+    "contains",
+    "starts_with",
+    "ends_with",
+    "matches",
+    "split",
+    "join",
+    "keys",
+    "base64",
+    "base64_decode",
+    "sha256",
+    "hmac_sha256",
+    "uuid",
+    "now",
+    "now_ms",
+    "log",
+];
+
+#[cfg(test)]
+fn run(source: &str, globals: Value) -> Result<Map<String, Value>> {
+    let Value::Object(globals) = globals else {
+        unreachable!()
+    };
+    Script::parse(source)?.run(globals)
+}
+
+#[test]
+fn test_script_values() {
+    let scope = run(
+        "let a = 1 + 2 * 3; let b = (1 + 2) * 3; let c = 7 / 2; let d = 6 / 3; let e = -a % 4;
+         let s = 'id-' + a; let list = [1, 'two', [3]]; let obj = {name: \"x\", 'n': 1,};
+         let f = list[1] + list[-1][0]; let g = obj.name == 'x' && !obj.missing; let h = list[9];",
+        serde_json::json!({}),
+    )
+    .unwrap();
+    assert_eq!(scope["a"], 7);
+    assert_eq!(scope["b"], 9);
+    assert_eq!(scope["c"], 3.5);
+    assert_eq!(scope["d"], 2);
+    assert_eq!(scope["e"], -3);
+    assert_eq!(scope["s"], "id-7");
+    assert_eq!(scope["obj"], serde_json::json!({"name": "x", "n": 1}));
+    assert_eq!(scope["f"], "two3");
+    assert_eq!(scope["g"], true);
+    assert_eq!(scope["h"], Value::Null);
+}
+
+#[test]
+fn test_script_statements() {
+    let globals = serde_json::json!({
+        "vars": {"secret": "key"},
+        "response": {"status": 201, "body": {"items": [{"id": 1}, {"id": 2}], "token": "abc"}},
+    });
+    let source = "
+        // Totals the item ids.
+        let total = 0;
+        for item in response.body.items {
+            total = total + item.id;
+        }
+        if total > 2 { vars.big = true } else if total > 1 { vars.big = false } else { vars.big = null }
+        vars.user.token = upper(response.body.token);
+        vars.signature = hmac_sha256(vars.secret, 'payload');
+        assert response.status == 201
+        assert len(response.body.items) == 2, 'expected two items'
+    ";
+    let vars = run(source, globals.clone())
+        .unwrap()
+        .remove("vars")
+        .unwrap();
+    assert_eq!(vars["big"], true);
+    assert_eq!(vars["user"]["token"], "ABC");
+    assert_eq!(vars["signature"].as_str().unwrap().len(), 64);
+
+    let err = run("assert response.status == 200", globals.clone()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 1: assertion failed: response.status == 200"
+    );
+    let err = run("let a = 1;\nassert a == 2, 'a is ' + a;", globals.clone()).unwrap_err();
+    assert_eq!(err.to_string(), "line 2: a is 1");
+}
+
+#[test]
+fn test_script_errors() {
+    let globals = serde_json::json!({"vars": {}});
+    assert!(Script::parse("let = 1").is_err());
+    assert!(Script::parse("vars.a = 'open").is_err());
+    assert!(Script::parse("if true { vars.a = 1").is_err());
+    assert!(Script::parse("1 + 2 = 3").is_err());
+    assert_eq!(
+        run("\nnope = 1", globals.clone()).unwrap_err().to_string(),
+        "line 2: unknown variable 'nope', declare it with let"
+    );
+    assert_eq!(
+        run("vars.a = 1 / 0", globals.clone())
+            .unwrap_err()
+            .to_string(),
+        "line 1: division by zero"
+    );
+    assert_eq!(
+        run("vars.a = upper(1)", globals.clone())
+            .unwrap_err()
+            .to_string(),
+        "line 1: can't call upper() with (a number)"
+    );
+    assert_eq!(
+        run("vars.a = read('/etc/passwd')", globals.clone())
+            .unwrap_err()
+            .to_string(),
+        "line 1: unknown function 'read'"
+    );
+    let nested = "let a = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        for i in a { for j in a { for k in a { for l in a { for m in a { let n = 1 } } } } }";
+    let err = run(nested, globals.clone()).unwrap_err();
+    assert!(err.to_string().contains("stopped after"));
+
+    let doubling = format!("let s = \"abcdefgh\";\n{}", "s = s + s;\n".repeat(40));
+    assert_eq!(
+        run(&doubling, globals.clone()).unwrap_err().to_string(),
+        "line 18: value too large"
+    );
+    let split = "let s = 'ab'; let l = split(s, ''); for i in l { for j in l { for k in l { l = split(join(l, ','), '') } } }";
+    assert!(run(split, globals.clone())
+        .unwrap_err()
+        .to_string()
+        .ends_with("value too large"));
+    // Each value is small enough, but together they'd grow `vars` by more than is allowed.
+    let growing = "let s = '0123456789'; for i in [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15] { s = s + s }
+        vars.a = s;\nvars.b = s;";
+    assert_eq!(
+        run(growing, globals.clone()).unwrap_err().to_string(),
+        "line 3: value too large"
+    );
+    let nesting = "let a = [0]; let l = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0]; for i in l { for j in l { for k in l { a[0] = a } } }";
+    assert_eq!(
+        run(nesting, globals).unwrap_err().to_string(),
+        "line 1: value nested too deeply"
+    );
+
+    let parens = format!("vars.x = {}1{}", "(".repeat(100_000), ")".repeat(100_000));
+    assert_eq!(
+        Script::parse(&parens).unwrap_err().to_string(),
+        "line 1: nested too deeply"
+    );
+    assert!(Script::parse(&format!("vars.x = 1{}", " + 1".repeat(100_000))).is_err());
+    assert!(Script::parse(&format!("vars.x = {}1", "!".repeat(100_000))).is_err());
+    assert!(Script::parse(&format!(
+        "{}{}",
+        "if true { ".repeat(100_000),
+        "}".repeat(100_000)
+    ))
+    .is_err());
+    assert!(Script::parse(&format!("vars{}", ".a".repeat(100_000))).is_err());
+    assert!(Script::parse(&format!("vars.x = ({})", "1 + ".repeat(60) + "1")).is_ok());
+}
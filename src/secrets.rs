@@ -0,0 +1,410 @@
+//! Where the secrets requests use are kept: the encrypted vault, the OS keyring, or process
+//! environment variables, chosen with `secret_store` in the collection config.
+
+use crate::vault::Vault;
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Secrets by name, read only when a request uses them.
+pub trait SecretStore: Send + Sync {
+    /// The names of the stored secrets, sorted.
+    fn names(&self) -> Result<Vec<String>>;
+    /// The stored secrets with the given names. Names that aren't stored are skipped.
+    fn get(&self, names: &[String]) -> Result<Map<String, Value>>;
+    /// Stores a secret, replacing one with the same name.
+    fn set(&self, name: &str, value: &str) -> Result<()>;
+    /// Removes a secret. Returns whether it was stored.
+    fn remove(&self, name: &str) -> Result<bool>;
+}
+
+/// The kinds of secret stores.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Provider {
+    /// `secrets.enc` in the reqq directory, encrypted with a passphrase.
+    #[default]
+    Vault,
+    /// The OS keyring, through `security` on macOS or `secret-tool` elsewhere. OAuth2 tokens
+    /// are kept there too, instead of the `tokens` folder.
+    Keyring,
+    /// `REQQ_SECRET_<name>` environment variables, which can't be changed by reqq.
+    Env,
+}
+
+impl FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "vault" => Ok(Provider::Vault),
+            "keyring" => Ok(Provider::Keyring),
+            "env" => Ok(Provider::Env),
+            _ => Err(format!(
+                "unknown secret store '{}', expected vault, keyring or env",
+                s
+            )),
+        }
+    }
+}
+
+impl Provider {
+    /// Opens the store of a reqq directory.
+    pub fn open(self, reqq_dir: &str) -> Box<dyn SecretStore> {
+        match self {
+            Provider::Vault => Box::new(Vault::new(reqq_dir)),
+            Provider::Keyring => Box::new(KeyringStore::new(reqq_dir)),
+            Provider::Env => Box::new(EnvStore),
+        }
+    }
+}
+
+/// The prefix of the environment variables `EnvStore` reads.
+const ENV_PREFIX: &str = "REQQ_SECRET_";
+
+/// Secrets from `REQQ_SECRET_<name>` environment variables, e.g. set by a CI system.
+pub struct EnvStore;
+
+impl SecretStore for EnvStore {
+    fn names(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = std::env::vars()
+            .filter_map(|(key, _)| key.strip_prefix(ENV_PREFIX).map(str::to_owned))
+            .filter(|name| !name.is_empty())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn get(&self, names: &[String]) -> Result<Map<String, Value>> {
+        Ok(names
+            .iter()
+            .filter_map(|name| {
+                std::env::var(format!("{}{}", ENV_PREFIX, name))
+                    .ok()
+                    .map(|value| (name.clone(), Value::String(value)))
+            })
+            .collect())
+    }
+
+    fn set(&self, name: &str, _: &str) -> Result<()> {
+        Err(anyhow!(
+            "Secrets come from environment variables, so set {}{} instead.",
+            ENV_PREFIX,
+            name
+        ))
+    }
+
+    fn remove(&self, name: &str) -> Result<bool> {
+        Err(anyhow!(
+            "Secrets come from environment variables, so unset {}{} instead.",
+            ENV_PREFIX,
+            name
+        ))
+    }
+}
+
+/// The service keyring entries are stored under.
+const SERVICE: &str = "reqq";
+
+/// The entry listing the names of a collection's secrets, since keyrings can't be listed the
+/// same way everywhere. Secret names can't start with a dot in templates.
+const INDEX: &str = ".index";
+
+/// Secrets in the OS keyring, kept apart per collection by the path of its reqq directory.
+pub struct KeyringStore {
+    collection: String,
+    tool: Tool,
+}
+
+/// The command line tool the keyring is used through.
+enum Tool {
+    /// `security` on macOS.
+    Security(PathBuf),
+    /// `secret-tool` from libsecret, on Linux and BSDs.
+    SecretTool(PathBuf),
+}
+
+impl KeyringStore {
+    pub fn new(reqq_dir: &str) -> Self {
+        let tool = if cfg!(target_os = "macos") {
+            Tool::Security(PathBuf::from("security"))
+        } else {
+            Tool::SecretTool(PathBuf::from("secret-tool"))
+        };
+        KeyringStore::with_tool(reqq_dir, tool)
+    }
+
+    fn with_tool(reqq_dir: &str, tool: Tool) -> Self {
+        let collection = Path::new(reqq_dir)
+            .canonicalize()
+            .unwrap_or_else(|_| PathBuf::from(reqq_dir))
+            .display()
+            .to_string();
+        KeyringStore { collection, tool }
+    }
+
+    /// Reads an entry, like a cached OAuth2 token.
+    pub fn read(&self, name: &str) -> Result<Option<String>> {
+        let account = self.account(name);
+        let (program, args) = match &self.tool {
+            Tool::Security(program) => (
+                program,
+                vec!["find-generic-password", "-s", SERVICE, "-a", &account, "-w"],
+            ),
+            Tool::SecretTool(program) => (
+                program,
+                vec!["lookup", "service", SERVICE, "account", &account],
+            ),
+        };
+        let output = Command::new(program)
+            .args(&args)
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| unavailable(program, e))?;
+        // Both tools exit with an error when there's no such entry.
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let value = String::from_utf8(output.stdout)?;
+        Ok(Some(match &self.tool {
+            Tool::Security(_) => value.strip_suffix('\n').unwrap_or(&value).to_owned(),
+            Tool::SecretTool(_) => value,
+        }))
+    }
+
+    /// Writes an entry, replacing an existing one.
+    pub fn write(&self, name: &str, value: &str) -> Result<()> {
+        let account = self.account(name);
+        let label = format!("reqq {}", account);
+        // Values are written to the tools' stdin, since arguments can be seen by anyone in `ps`.
+        let (program, args, stdin) = match &self.tool {
+            Tool::Security(_) if value.contains('\n') => {
+                return Err(anyhow!(
+                    "The keychain can't store '{}', whose value has a line break.",
+                    name
+                ))
+            }
+            // With `-w` last, `security` prompts for the password, and then for it again.
+            Tool::Security(program) => (
+                program,
+                vec![
+                    "add-generic-password",
+                    "-U",
+                    "-s",
+                    SERVICE,
+                    "-a",
+                    &account,
+                    "-w",
+                ],
+                format!("{}\n{}\n", value, value),
+            ),
+            Tool::SecretTool(program) => (
+                program,
+                vec![
+                    "store", "--label", &label, "service", SERVICE, "account", &account,
+                ],
+                value.to_owned(),
+            ),
+        };
+        let mut child = Command::new(program)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| unavailable(program, e))?;
+        if let Some(mut input) = child.stdin.take() {
+            input.write_all(stdin.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(anyhow!(
+                "Couldn't store '{}' in the keyring: {} failed with {}.",
+                name,
+                program.display(),
+                status
+            ));
+        }
+        Ok(())
+    }
+
+    /// Deletes an entry. Returns whether there was one.
+    pub fn delete(&self, name: &str) -> Result<bool> {
+        if self.read(name)?.is_none() {
+            return Ok(false);
+        }
+        let account = self.account(name);
+        let (program, args) = match &self.tool {
+            Tool::Security(program) => (
+                program,
+                vec!["delete-generic-password", "-s", SERVICE, "-a", &account],
+            ),
+            Tool::SecretTool(program) => (
+                program,
+                vec!["clear", "service", SERVICE, "account", &account],
+            ),
+        };
+        let status = Command::new(program)
+            .args(&args)
+            .stdout(Stdio::null())
+            .status()
+            .map_err(|e| unavailable(program, e))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "Couldn't remove '{}' from the keyring: {} failed with {}.",
+                name,
+                program.display(),
+                status
+            ));
+        }
+        Ok(true)
+    }
+
+    fn account(&self, name: &str) -> String {
+        format!("{}/{}", self.collection, name)
+    }
+
+    fn write_index(&self, names: &[String]) -> Result<()> {
+        self.write(INDEX, &serde_json::to_string(names)?)
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn names(&self) -> Result<Vec<String>> {
+        match self.read(INDEX)? {
+            Some(index) => {
+                serde_json::from_str(&index).map_err(|e| anyhow!("Malformed keyring index: {}", e))
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    fn get(&self, names: &[String]) -> Result<Map<String, Value>> {
+        let mut secrets = Map::new();
+        for name in names {
+            if let Some(value) = self.read(name)? {
+                secrets.insert(name.clone(), Value::String(value));
+            }
+        }
+        Ok(secrets)
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        if name.starts_with('.') {
+            return Err(anyhow!("Secret names can't start with a dot."));
+        }
+        self.write(name, value)?;
+        let mut names = self.names()?;
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_owned());
+            names.sort();
+            self.write_index(&names)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<bool> {
+        let removed = self.delete(name)?;
+        let mut names = self.names()?;
+        if names.iter().any(|n| n == name) {
+            names.retain(|n| n != name);
+            self.write_index(&names)?;
+        }
+        Ok(removed)
+    }
+}
+
+fn unavailable(program: &Path, e: std::io::Error) -> anyhow::Error {
+    anyhow!(
+        "The keyring needs '{}', which couldn't be run: {}",
+        program.display(),
+        e
+    )
+}
+
+#[test]
+fn test_provider_from_str() {
+    assert_eq!("keyring".parse(), Ok(Provider::Keyring));
+    assert_eq!("env".parse(), Ok(Provider::Env));
+    assert!("pass".parse::<Provider>().is_err());
+}
+
+#[test]
+fn test_env_store() {
+    std::env::set_var("REQQ_SECRET_test_env_store", "abc123");
+    let store = EnvStore;
+    assert!(store
+        .names()
+        .unwrap()
+        .contains(&"test_env_store".to_owned()));
+    let secrets = store
+        .get(&[
+            "test_env_store".to_owned(),
+            "test_env_store_missing".to_owned(),
+        ])
+        .unwrap();
+    assert_eq!(secrets.len(), 1);
+    assert_eq!(secrets["test_env_store"], "abc123");
+    assert!(store.set("test_env_store", "def").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_keyring_store() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    // A stand-in for `secret-tool` that keeps entries in files named after their accounts.
+    let dir = std::env::temp_dir().join(format!("reqq-keyring-{}", std::process::id()));
+    fs::create_dir_all(dir.join("entries")).unwrap();
+    let tool = dir.join("secret-tool");
+    let script = format!(
+        "#!/bin/sh\nfor arg; do account=$arg; done\nf=\"{}/entries/$(echo \"$account\" | tr / _)\"\ncase $1 in\n  store) cat > \"$f\" ;;\n  lookup) cat \"$f\" 2>/dev/null ;;\n  clear) rm -f \"$f\" ;;\nesac\n",
+        dir.display()
+    );
+    fs::write(&tool, script).unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let store = KeyringStore::with_tool("/nonexistent/.reqq", Tool::SecretTool(tool));
+    assert!(store.names().unwrap().is_empty());
+    store.set("token", "abc\n123").unwrap();
+    store.set("apikey", "def456").unwrap();
+    assert_eq!(store.names().unwrap(), vec!["apikey", "token"]);
+    assert_eq!(
+        store.get(&["token".to_owned()]).unwrap()["token"],
+        "abc\n123"
+    );
+    assert!(store.set(".index", "nope").is_err());
+
+    assert!(store.remove("token").unwrap());
+    assert!(!store.remove("token").unwrap());
+    assert_eq!(store.names().unwrap(), vec!["apikey"]);
+    assert!(store.get(&["token".to_owned()]).unwrap().is_empty());
+
+    // A stand-in for `security` that records how it was run. The value isn't an argument.
+    let tool = dir.join("security");
+    fs::write(
+        &tool,
+        format!(
+            "#!/bin/sh\necho \"$@\" > \"{0}/args\"\ncat > \"{0}/stdin\"\n",
+            dir.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+    let store = KeyringStore::with_tool("/nonexistent/.reqq", Tool::Security(tool));
+    store.write("token", "s3cr3t").unwrap();
+    assert!(fs::read_to_string(dir.join("args"))
+        .unwrap()
+        .ends_with(" -w\n"));
+    assert!(!fs::read_to_string(dir.join("args"))
+        .unwrap()
+        .contains("s3cr3t"));
+    assert_eq!(
+        fs::read_to_string(dir.join("stdin")).unwrap(),
+        "s3cr3t\ns3cr3t\n"
+    );
+    assert!(store.write("token", "abc\n123").is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
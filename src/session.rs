@@ -0,0 +1,70 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Variables captured from responses, stored in `session.json` in a reqq directory so they're
+/// available to the templates of later invocations.
+pub struct Session {
+    fpath: PathBuf,
+}
+
+/// The file the session is stored in, relative to the reqq directory.
+pub const FILE_NAME: &str = "session.json";
+
+impl Session {
+    pub fn new(reqq_dir: &str) -> Self {
+        Session {
+            fpath: Path::new(reqq_dir).join(FILE_NAME),
+        }
+    }
+
+    /// All session variables, or none if nothing has been captured yet.
+    pub fn load(&self) -> Result<HashMap<String, Value>> {
+        if !self.fpath.is_file() {
+            return Ok(HashMap::new());
+        }
+
+        match serde_json::from_str(&fs::read_to_string(&self.fpath)?)? {
+            Value::Object(map) => Ok(map.into_iter().collect()),
+            _ => Err(anyhow!(
+                "Malformed session file '{}'.",
+                self.fpath.display()
+            )),
+        }
+    }
+
+    /// Adds variables to the session, replacing any with the same name.
+    pub fn set(&self, vars: Vec<(String, Value)>) -> Result<()> {
+        let mut session: serde_json::Map<String, Value> = self.load()?.into_iter().collect();
+        session.extend(vars);
+        fs::write(&self.fpath, serde_json::to_string_pretty(&session)?)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_session() {
+    let dir = std::env::temp_dir().join(format!("reqq-session-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let session = Session::new(dir.to_str().unwrap());
+    assert!(session.load().unwrap().is_empty());
+
+    session
+        .set(vec![
+            ("token".to_owned(), "abc".into()),
+            ("id".to_owned(), 1.into()),
+        ])
+        .unwrap();
+    session
+        .set(vec![("token".to_owned(), "def".into())])
+        .unwrap();
+
+    let vars = session.load().unwrap();
+    assert_eq!(vars.len(), 2);
+    assert_eq!(vars["token"], "def");
+    assert_eq!(vars["id"], 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,249 @@
+//! AWS Signature Version 4 request signing.
+//!
+//! See https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html
+
+use crate::time;
+use anyhow::{anyhow, Result};
+use openssl::{hash::MessageDigest, pkey::PKey, sha::sha256, sign::Signer};
+use percent_encoding::percent_decode_str;
+use reqwest::{header::HeaderMap, Method};
+use std::collections::BTreeMap;
+use url::Url;
+
+/// Credentials and scope for signing requests to an AWS service.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AwsCredentials {
+    pub access_key: String,
+    pub secret_key: String,
+    /// The token of temporary credentials, like those from `aws sts assume-role`.
+    pub session_token: Option<String>,
+    pub region: String,
+    pub service: String,
+}
+
+/// Computes the headers that sign a request: `Authorization`, `x-amz-date`, and, when needed,
+/// `x-amz-security-token` and `x-amz-content-sha256`.
+pub fn sign(
+    creds: &AwsCredentials,
+    method: &Method,
+    url: &Url,
+    req_headers: &HeaderMap,
+    body: &[u8],
+    now_millis: u64,
+) -> Result<Vec<(&'static str, String)>> {
+    let amz_date = time::format_iso8601(now_millis).replace(['-', ':'], "");
+    let date = &amz_date[..8];
+    let payload_hash = hex(&sha256(body));
+
+    let mut signing_headers = vec![("x-amz-date", amz_date.clone())];
+    if let Some(token) = &creds.session_token {
+        signing_headers.push(("x-amz-security-token", token.clone()));
+    }
+    // Only S3 requires the payload hash as a header.
+    if creds.service == "s3" {
+        signing_headers.push(("x-amz-content-sha256", payload_hash.clone()));
+    }
+
+    // Header names are lowercased by the http crate already.
+    let mut headers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, value) in req_headers {
+        let value = value
+            .to_str()
+            .map_err(|_| anyhow!("Header '{}' isn't valid text.", name))?;
+        headers
+            .entry(name.as_str().to_owned())
+            .or_default()
+            .push(collapse_whitespace(value));
+    }
+    headers.insert("host".to_owned(), vec![host(url)?]);
+    for (name, value) in signing_headers.iter() {
+        headers.insert((*name).to_owned(), vec![value.clone()]);
+    }
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, values)| format!("{}:{}\n", name, values.join(",")))
+        .collect();
+    let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+    let canonical_request = [
+        method.as_str(),
+        &canonical_uri(url.path(), &creds.service),
+        &canonical_query(url),
+        &canonical_headers,
+        &signed_headers,
+        &payload_hash,
+    ]
+    .join("\n");
+
+    let scope = format!("{}/{}/{}/aws4_request", date, creds.region, creds.service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&sha256(canonical_request.as_bytes()))
+    );
+
+    let mut key = hmac_sha256(
+        format!("AWS4{}", creds.secret_key).as_bytes(),
+        date.as_bytes(),
+    )?;
+    for part in [
+        creds.region.as_str(),
+        creds.service.as_str(),
+        "aws4_request",
+    ] {
+        key = hmac_sha256(&key, part.as_bytes())?;
+    }
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes())?);
+
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        creds.access_key, scope, signed_headers, signature
+    );
+    signing_headers.push(("authorization", authorization));
+    Ok(signing_headers)
+}
+
+/// The `Host` header value reqwest sends: the host, with the port if it isn't the default.
+fn host(url: &Url) -> Result<String> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("Can't sign a request without a host."))?;
+    Ok(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_owned(),
+    })
+}
+
+/// Every service but S3 expects the path segments to be encoded twice.
+fn canonical_uri(path: &str, service: &str) -> String {
+    if path.is_empty() {
+        return "/".to_owned();
+    }
+    path.split('/')
+        .map(|segment| {
+            let decoded: Vec<u8> = percent_decode_str(segment).collect();
+            let encoded = uri_encode(&decoded);
+            if service == "s3" {
+                encoded
+            } else {
+                uri_encode(encoded.as_bytes())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn canonical_query(url: &Url) -> String {
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (uri_encode(k.as_bytes()), uri_encode(v.as_bytes())))
+        .collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Percent-encodes everything but unreserved characters, as SigV4 requires.
+fn uri_encode(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(message)?;
+    Ok(signer.sign_to_vec()?)
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_hmac_sha256() {
+    // RFC 4231, test case 2.
+    assert_eq!(
+        hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?").unwrap()),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    // Test case 6, with a key longer than a block.
+    assert_eq!(
+        hex(&hmac_sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First"
+        )
+        .unwrap()),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+}
+
+#[test]
+fn test_sign() {
+    // The example from the AWS documentation for signing an IAM request.
+    let creds = AwsCredentials {
+        access_key: "AKIDEXAMPLE".to_owned(),
+        secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_owned(),
+        session_token: None,
+        region: "us-east-1".to_owned(),
+        service: "iam".to_owned(),
+    };
+    let req = reqwest::blocking::Client::new()
+        .get("https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08")
+        .header(
+            "Content-Type",
+            "application/x-www-form-urlencoded; charset=utf-8",
+        )
+        .build()
+        .unwrap();
+
+    // 2015-08-30T12:36:00Z
+    let headers = sign(
+        &creds,
+        req.method(),
+        req.url(),
+        req.headers(),
+        &[],
+        1_440_938_160_000,
+    )
+    .unwrap();
+    assert_eq!(headers[0], ("x-amz-date", "20150830T123600Z".to_owned()));
+    assert_eq!(
+        headers[1],
+        (
+            "authorization",
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+                .to_owned()
+        )
+    );
+}
+
+#[test]
+fn test_canonical_uri() {
+    assert_eq!(canonical_uri("", "iam"), "/");
+    assert_eq!(
+        canonical_uri("/documents%20and%20settings/", "iam"),
+        "/documents%2520and%2520settings/"
+    );
+    assert_eq!(
+        canonical_uri("/documents%20and%20settings/", "s3"),
+        "/documents%20and%20settings/"
+    );
+}
@@ -0,0 +1,238 @@
+//! Golden responses in the `snapshots` folder, saved with `--snapshot` and compared against by
+//! `reqq test --snapshot`. Responses are normalized first, so what changes on every request,
+//! like the `Date` header, doesn't make them differ.
+
+use crate::{diff::ResponseDiff, redact, response::Response};
+use anyhow::{anyhow, Result};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{StatusCode, Version};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The folder snapshots are saved in, relative to the reqq directory.
+pub const FOLDER: &str = "snapshots";
+
+/// Headers left out of snapshots, since they change between otherwise equal responses.
+const VOLATILE_HEADERS: [&str; 12] = [
+    "date",
+    "age",
+    "expires",
+    "content-length",
+    "connection",
+    "keep-alive",
+    "transfer-encoding",
+    "set-cookie",
+    "etag",
+    "last-modified",
+    "server",
+    "x-request-id",
+];
+
+/// What `reqq test` does with snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SnapshotMode {
+    /// Nothing.
+    #[default]
+    Off,
+    /// Compares responses against their snapshots, and saves those that don't have one yet.
+    Compare,
+    /// Saves every response as its snapshot, replacing the old one.
+    Update,
+}
+
+/// How a response compared against its snapshot.
+#[derive(Clone, Debug)]
+pub enum SnapshotOutcome {
+    Matched,
+    /// There was no snapshot, or it was updated, so the response was saved as the snapshot.
+    Saved,
+    /// The response differs from the snapshot.
+    Changed(ResponseDiff),
+}
+
+/// The snapshots of a reqq directory, one JSON file per request, named after it.
+pub struct Snapshots {
+    dir: PathBuf,
+}
+
+impl Snapshots {
+    pub fn new(reqq_dir: &str) -> Self {
+        Snapshots {
+            dir: Path::new(reqq_dir).join(FOLDER),
+        }
+    }
+
+    /// Saves a response as the snapshot of a request, and returns the path of its file.
+    pub fn save(&self, req_name: &str, resp: &Response) -> Result<PathBuf> {
+        let fpath = self.fpath(req_name);
+        if let Some(parent) = fpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            &fpath,
+            serde_json::to_string_pretty(&normalize(resp))? + "\n",
+        )?;
+        Ok(fpath)
+    }
+
+    /// Compares a response against the snapshot of a request, saving it as the snapshot when
+    /// there isn't one yet or when updating.
+    pub fn check(
+        &self,
+        req_name: &str,
+        resp: &Response,
+        mode: SnapshotMode,
+    ) -> Result<Option<SnapshotOutcome>> {
+        let saved = match mode {
+            SnapshotMode::Off => return Ok(None),
+            SnapshotMode::Compare => self.load(req_name)?,
+            SnapshotMode::Update => None,
+        };
+        let Some(saved) = saved else {
+            self.save(req_name, resp)?;
+            return Ok(Some(SnapshotOutcome::Saved));
+        };
+        let current = from_json(&normalize(resp))
+            .ok_or_else(|| anyhow!("The response can't be snapshotted."))?;
+        let diff = ResponseDiff::new(("snapshot", &saved), ("response", &current));
+        Ok(Some(if diff.is_empty() {
+            SnapshotOutcome::Matched
+        } else {
+            SnapshotOutcome::Changed(diff)
+        }))
+    }
+
+    /// The snapshot of a request, if it has one.
+    pub fn load(&self, req_name: &str) -> Result<Option<Response>> {
+        let fpath = self.fpath(req_name);
+        let json = match fs::read_to_string(&fpath) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let value: Value = serde_json::from_str(&json)
+            .map_err(|e| anyhow!("Malformed snapshot '{}': {}", fpath.display(), e))?;
+        from_json(&value)
+            .map(Some)
+            .ok_or_else(|| anyhow!("Malformed snapshot '{}'.", fpath.display()))
+    }
+
+    fn fpath(&self, req_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", req_name))
+    }
+}
+
+/// The status, headers by name and body of a response, with volatile headers left out and
+/// secrets masked. JSON bodies are kept as values, so snapshots are readable and diff well.
+fn normalize(resp: &Response) -> Value {
+    let resp = redact::response(resp);
+    let mut headers = Map::new();
+    for (name, value) in resp.headers.iter() {
+        if VOLATILE_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        match headers.get_mut(name.as_str()) {
+            Some(Value::String(existing)) => *existing = format!("{}, {}", existing, value),
+            _ => {
+                headers.insert(name.as_str().to_owned(), Value::String(value));
+            }
+        }
+    }
+    let body = serde_json::from_str(&resp.body).unwrap_or(Value::String(resp.body.clone()));
+    json!({
+        "status": resp.status.as_u16(),
+        "headers": headers,
+        "body": body,
+    })
+}
+
+fn from_json(value: &Value) -> Option<Response> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in value["headers"].as_object()? {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes()).ok()?,
+            HeaderValue::from_str(value.as_str()?).ok()?,
+        );
+    }
+    let body = match &value["body"] {
+        Value::String(body) => body.clone(),
+        body => body.to_string(),
+    };
+    Some(Response {
+        status: StatusCode::from_u16(value["status"].as_u64()? as u16).ok()?,
+        version: Version::HTTP_11,
+        headers,
+        body,
+        binary: None,
+    })
+}
+
+#[test]
+fn test_snapshots() {
+    let dir = std::env::temp_dir().join(format!("reqq-snapshots-{}", std::process::id()));
+    let snapshots = Snapshots::new(dir.to_str().unwrap());
+    let response = |date: &str, body: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        headers.insert("date", HeaderValue::from_str(date).unwrap());
+        Response {
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers,
+            body: body.to_owned(),
+            binary: None,
+        }
+    };
+
+    let first = response("Mon", r#"{"id": 1, "name": "ada"}"#);
+    assert!(matches!(
+        snapshots
+            .check("users/get", &first, SnapshotMode::Compare)
+            .unwrap(),
+        Some(SnapshotOutcome::Saved)
+    ));
+    let saved = fs::read_to_string(dir.join(FOLDER).join("users/get.json")).unwrap();
+    assert!(saved.contains("\"name\": \"ada\"") && !saved.contains("date"));
+
+    // Formatting and volatile headers don't count.
+    let same = response("Tue", r#"{"name":"ada","id":1}"#);
+    assert!(matches!(
+        snapshots
+            .check("users/get", &same, SnapshotMode::Compare)
+            .unwrap(),
+        Some(SnapshotOutcome::Matched)
+    ));
+
+    let changed = response("Tue", r#"{"id": 1, "name": "grace"}"#);
+    match snapshots
+        .check("users/get", &changed, SnapshotMode::Compare)
+        .unwrap()
+    {
+        Some(SnapshotOutcome::Changed(diff)) => assert_eq!(
+            diff.report(),
+            "--- snapshot\n+++ response\n~ $.name: \"ada\" -> \"grace\""
+        ),
+        outcome => panic!("unexpected {:?}", outcome),
+    }
+    assert!(matches!(
+        snapshots
+            .check("users/get", &changed, SnapshotMode::Update)
+            .unwrap(),
+        Some(SnapshotOutcome::Saved)
+    ));
+    assert!(matches!(
+        snapshots
+            .check("users/get", &changed, SnapshotMode::Compare)
+            .unwrap(),
+        Some(SnapshotOutcome::Matched)
+    ));
+    assert!(snapshots
+        .check("users/get", &first, SnapshotMode::Off)
+        .unwrap()
+        .is_none());
+
+    fs::remove_dir_all(dir).unwrap();
+}
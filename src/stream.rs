@@ -0,0 +1,177 @@
+//! Printing response bodies as they arrive, for `--stream`. Server-Sent Events are printed an
+//! event at a time, as `event:` and `data:` lines.
+
+use crate::response::Response;
+use anyhow::Result;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use std::io::{Read, Write};
+
+/// One Server-Sent Event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    /// The event type, `message` unless the server names one.
+    pub event: String,
+    pub data: String,
+    pub id: Option<String>,
+}
+
+impl Event {
+    /// The event as `event:`, `data:` and `id:` lines, with a `data:` line per line of data.
+    pub fn to_lines(&self) -> String {
+        let mut lines = vec![format!("event: {}", self.event)];
+        lines.extend(self.data.split('\n').map(|line| format!("data: {}", line)));
+        if let Some(id) = &self.id {
+            lines.push(format!("id: {}", id));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Collects the fields of Server-Sent Events from lines of an event stream.
+#[derive(Default)]
+pub struct EventParser {
+    event: Option<String>,
+    data: Vec<String>,
+    id: Option<String>,
+}
+
+impl EventParser {
+    /// Takes one line, without its line ending. A blank line ends the event, which is returned
+    /// if it has any data.
+    pub fn line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // Lines starting with a colon are comments, often sent to keep the connection open.
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_owned()),
+            "data" => self.data.push(value.to_owned()),
+            "id" => self.id = Some(value.to_owned()),
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(Event {
+            event: event.unwrap_or_else(|| "message".to_owned()),
+            data: std::mem::take(&mut self.data).join("\n"),
+            id: self.id.clone(),
+        })
+    }
+}
+
+/// Prints the body of a response to stdout as it arrives, and returns the response without its
+/// body.
+pub fn print(mut resp: reqwest::blocking::Response) -> Result<Response> {
+    let events = is_event_stream(resp.headers());
+    let mut stdout = std::io::stdout().lock();
+    let mut parser = EventParser::default();
+    let mut pending: Vec<u8> = vec![];
+    let mut buf = vec![0u8; 8 * 1024];
+    loop {
+        let n = resp.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        if !events {
+            stdout.write_all(&buf[..n])?;
+            stdout.flush()?;
+            continue;
+        }
+        pending.extend_from_slice(&buf[..n]);
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(event) = parser.line(line.trim_end_matches(['\n', '\r'])) {
+                writeln!(stdout, "{}\n", event.to_lines())?;
+                stdout.flush()?;
+            }
+        }
+    }
+    // A stream can end without the blank line that dispatches its last event.
+    if !pending.is_empty() {
+        parser.line(String::from_utf8_lossy(&pending).trim_end_matches('\r'));
+    }
+    if let Some(event) = parser.line("") {
+        writeln!(stdout, "{}\n", event.to_lines())?;
+    }
+
+    Ok(Response {
+        status: resp.status(),
+        version: resp.version(),
+        headers: resp.headers().clone(),
+        body: String::new(),
+        binary: None,
+    })
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .eq_ignore_ascii_case("text/event-stream")
+        })
+}
+
+#[test]
+fn test_event_parser() {
+    let mut parser = EventParser::default();
+    let lines = [
+        ": keep-alive",
+        "event: update",
+        "data: {\"a\": 1}",
+        "data: more",
+        "id: 7",
+        "",
+        "",
+        "data:plain",
+        "retry: 100",
+        "",
+    ];
+    let events: Vec<Event> = lines.iter().filter_map(|line| parser.line(line)).collect();
+    assert_eq!(
+        events,
+        vec![
+            Event {
+                event: "update".to_owned(),
+                data: "{\"a\": 1}\nmore".to_owned(),
+                id: Some("7".to_owned())
+            },
+            Event {
+                event: "message".to_owned(),
+                data: "plain".to_owned(),
+                id: Some("7".to_owned())
+            },
+        ]
+    );
+    assert_eq!(
+        events[0].to_lines(),
+        "event: update\ndata: {\"a\": 1}\ndata: more\nid: 7"
+    );
+}
+
+#[test]
+fn test_is_event_stream() {
+    let mut headers = HeaderMap::new();
+    assert!(!is_event_stream(&headers));
+    headers.insert(
+        CONTENT_TYPE,
+        "text/event-stream; charset=utf-8".parse().unwrap(),
+    );
+    assert!(is_event_stream(&headers));
+}
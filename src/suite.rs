@@ -0,0 +1,66 @@
+use crate::assertion::CheckReport;
+use crate::snapshot::{SnapshotMode, SnapshotOutcome};
+use reqwest::StatusCode;
+
+/// Which requests `reqq test` runs, and how.
+#[derive(Clone, Debug, Default)]
+pub struct SuiteOpts {
+    /// Request names or glob patterns, like `users/*`. Every request runs if there are none.
+    pub patterns: Vec<String>,
+    /// Only run requests with at least one of these `@tags`.
+    pub tags: Vec<String>,
+    /// Stop at the first request that fails.
+    pub fail_fast: bool,
+    /// Compare responses against their snapshots, or update them.
+    pub snapshot: SnapshotMode,
+}
+
+/// The outcome of one request of a test run.
+#[derive(Clone, Debug)]
+pub struct CaseResult {
+    pub name: String,
+    /// The response status, if a response was received.
+    pub status: Option<StatusCode>,
+    pub duration_ms: u64,
+    /// The outcome of the request's `@assert` directives, if it has any.
+    pub check: Option<CheckReport>,
+    /// Why the request failed, other than failed assertions.
+    pub error: Option<String>,
+    /// How the response compared against its snapshot, when snapshots are used.
+    pub snapshot: Option<SnapshotOutcome>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+            && self.check.as_ref().is_none_or(CheckReport::passed)
+            && !matches!(self.snapshot, Some(SnapshotOutcome::Changed(_)))
+    }
+}
+
+/// The outcome of running a collection as a test suite.
+#[derive(Clone, Debug)]
+pub struct SuiteResult {
+    pub cases: Vec<CaseResult>,
+    /// Requests that were selected but didn't run, after a failure with `--fail-fast`.
+    pub skipped: Vec<String>,
+}
+
+impl SuiteResult {
+    pub fn passed(&self) -> bool {
+        self.failed() == 0
+    }
+
+    pub fn failed(&self) -> usize {
+        self.cases.iter().filter(|c| !c.passed()).count()
+    }
+
+    /// How many requests were selected, including skipped ones.
+    pub fn total(&self) -> usize {
+        self.cases.len() + self.skipped.len()
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        self.cases.iter().map(|c| c.duration_ms).sum()
+    }
+}
@@ -0,0 +1,382 @@
+use crate::{error::ReqqError, helpers, request_file};
+use anyhow::{anyhow, Result};
+use handlebars::{
+    template::{Parameter, Template, TemplateElement},
+    Handlebars,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// The folder of partials, relative to the reqq directory.
+pub const PARTIALS_FOLDER: &str = "partials";
+
+/// How deeply partials can include other partials.
+const MAX_PARTIAL_DEPTH: usize = 8;
+
+/// Templates that request files include with `{{> name }}`, by name: their path in the
+/// partials folder without the extension, like `common/headers`.
+pub type Partials = HashMap<String, String>;
+
+/// Block helpers that render their content with the same context they were given.
+const SAME_CONTEXT_BLOCKS: [&str; 2] = ["if", "unless"];
+
+/// A template variable that doesn't resolve to a value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissingVariable {
+    pub name: String,
+    /// The line of the template the variable is used on, starting at 1.
+    pub line: usize,
+}
+
+impl fmt::Display for MissingVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (line {})", self.name, self.line)
+    }
+}
+
+/// Builds the handlebars registry used to render request files: strict, so missing
+/// variables are errors instead of empty strings, without HTML escaping, since requests
+/// aren't HTML, and with reqq's helpers registered.
+pub fn registry() -> Handlebars<'static> {
+    let mut reg = Handlebars::new();
+    reg.set_strict_mode(true);
+    reg.register_escape_fn(handlebars::no_escape);
+    helpers::register(&mut reg);
+    reg
+}
+
+/// Loads the partials of a reqq directory. There are none without a partials folder.
+pub fn load_partials(reqq_dir: &Path) -> Result<Partials> {
+    let dir = reqq_dir.join(PARTIALS_FOLDER);
+    let mut partials = Partials::new();
+    for entry in WalkDir::new(&dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let relative = entry.path().strip_prefix(&dir)?.with_extension("");
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let partial = fs::read_to_string(entry.path()).map_err(|e| {
+            anyhow!(
+                "Couldn't read the partial '{}': {}",
+                entry.path().display(),
+                e
+            )
+        })?;
+        partials.insert(name, request_file::strip_bom(&partial).to_owned());
+    }
+    Ok(partials)
+}
+
+/// Renders a template, failing with a list of every variable that doesn't resolve. Templates
+/// of more than one line, like request files, give the line each variable is on.
+pub fn render(template: &str, data: &Value) -> Result<String> {
+    render_with_partials(template, data, &Partials::new())
+}
+
+/// Like `render`, with partials the template can include. Variables used in the partials
+/// must resolve too, and are reported on the line of the template that includes them.
+pub fn render_with_partials(template: &str, data: &Value, partials: &Partials) -> Result<String> {
+    let missing = missing_variables(template, data, partials)?;
+    if !missing.is_empty() {
+        let names: Vec<String> = if template.trim_end().contains('\n') {
+            missing.iter().map(MissingVariable::to_string).collect()
+        } else {
+            missing.into_iter().map(|m| m.name).collect()
+        };
+        return Err(ReqqError::Template {
+            request: None,
+            variables: names,
+        }
+        .into());
+    }
+
+    let mut reg = registry();
+    for (name, partial) in partials {
+        reg.register_partial(name, partial)?;
+    }
+    Ok(reg.render_template(template, data)?)
+}
+
+/// Renders every string in a JSON value as a template, like the values of an environment's
+/// `auth` mapping.
+pub fn render_value(value: &Value, data: &Value) -> Result<Value> {
+    Ok(match value {
+        Value::String(template) => Value::String(render(template, data)?),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|v| render_value(v, data))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), render_value(v, data)?)))
+                .collect::<Result<_>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+/// Finds the variables used in a template, and the partials it includes, that don't resolve
+/// to a value in `data`. Helper args are checked too, except for `default`, and variables
+/// inside blocks that change the context, like `each`, are skipped. Including a partial that
+/// doesn't exist is an error.
+pub fn missing_variables(
+    template: &str,
+    data: &Value,
+    partials: &Partials,
+) -> Result<Vec<MissingVariable>> {
+    let compiled = Template::compile(template)?;
+    let mut missing = vec![];
+    audit(&compiled, data, partials, 1, 0, &mut missing)?;
+    Ok(missing)
+}
+
+fn audit(
+    template: &Template,
+    data: &Value,
+    partials: &Partials,
+    fallback_line: usize,
+    depth: usize,
+    missing: &mut Vec<MissingVariable>,
+) -> Result<()> {
+    for (i, element) in template.elements.iter().enumerate() {
+        let line = template.mapping.get(i).map_or(fallback_line, |m| m.0);
+
+        match element {
+            TemplateElement::Expression(ht) | TemplateElement::HtmlExpression(ht) => {
+                let name = ht.name.as_name().unwrap_or_default();
+                if ht.params.is_empty() && ht.hash.is_empty() {
+                    if !helpers::NAMES.contains(&name) {
+                        check(&ht.name, data, line, missing);
+                    }
+                } else if name != "default" {
+                    for param in ht.params.iter().chain(ht.hash.values()) {
+                        check(param, data, line, missing);
+                    }
+                }
+            }
+            TemplateElement::HelperBlock(ht) => {
+                let name = ht.name.as_name().unwrap_or_default();
+                if SAME_CONTEXT_BLOCKS.contains(&name) {
+                    for inner in ht.template.iter().chain(ht.inverse.iter()) {
+                        audit(inner, data, partials, line, depth, missing)?;
+                    }
+                } else {
+                    for param in ht.params.iter() {
+                        check(param, data, line, missing);
+                    }
+                }
+            }
+            // Variables of a partial are reported on the line that includes it. Partials
+            // named by an expression are only known when rendering.
+            TemplateElement::PartialExpression(partial) => {
+                let Parameter::Name(name) = &partial.name else {
+                    continue;
+                };
+                let included = partials.get(name).ok_or_else(|| {
+                    anyhow!(
+                        "Unknown partial '{}' (line {}), expected a file in the {} folder.",
+                        name,
+                        line,
+                        PARTIALS_FOLDER
+                    )
+                })?;
+                if depth >= MAX_PARTIAL_DEPTH {
+                    return Err(anyhow!(
+                        "Partials are included more than {} deep, at '{}'.",
+                        MAX_PARTIAL_DEPTH,
+                        name
+                    ));
+                }
+                let compiled = Template::compile(included)
+                    .map_err(|e| anyhow!("Invalid partial '{}': {}", name, e))?;
+                let mut inner = vec![];
+                audit(&compiled, data, partials, line, depth + 1, &mut inner)?;
+                for m in inner {
+                    if !missing.iter().any(|existing| existing.name == m.name) {
+                        missing.push(MissingVariable { name: m.name, line });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn check(param: &Parameter, data: &Value, line: usize, missing: &mut Vec<MissingVariable>) {
+    let path = match param {
+        Parameter::Name(name) => name.as_str(),
+        Parameter::Path(_) => param.as_name().unwrap_or_default(),
+        _ => return,
+    };
+
+    if resolve(path, data).is_none() && !missing.iter().any(|m| m.name == path) {
+        missing.push(MissingVariable {
+            name: path.to_owned(),
+            line,
+        });
+    }
+}
+
+/// Looks up a handlebars path like `user.name`, `items.0` or `[auth/login].body` in `data`.
+/// Paths relative to other scopes (`this`, `../`, `@index`...) are assumed to resolve.
+fn resolve<'a>(path: &str, data: &'a Value) -> Option<&'a Value> {
+    let path = path.strip_prefix("@root.").unwrap_or(path);
+    if path.starts_with('@') || path.starts_with("this") || path.starts_with('.') {
+        return Some(data);
+    }
+
+    let mut value = data;
+    let mut rest = path;
+    while !rest.is_empty() {
+        let (segment, remainder) = if let Some(literal) = rest.strip_prefix('[') {
+            let end = literal.find(']')?;
+            (&literal[..end], &literal[end + 1..])
+        } else {
+            let end = rest.find(['.', '/', '[']).unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        rest = remainder.trim_start_matches(['.', '/']);
+
+        value = match value {
+            Value::Object(map) => map.get(segment)?,
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    if value.is_null() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+#[test]
+fn test_missing_variables() {
+    let data = serde_json::json!({
+        "baseUrl": "https://example.com",
+        "user": { "id": 5, "tags": ["a"] },
+        "auth/login": { "body": { "token": "abc" } },
+        "nothing": null,
+    });
+    let template = "GET {{ baseUrl }}/users/{{ user.id }}/{{ user.tags.0 }}
+authorization: {{ [auth/login].body.token }}
+x-missing: {{ missing }} {{ user.nope }}
+x-null: {{ nothing }}
+x-default: {{ default other \"fallback\" }}
+{{#if flag}}{{ inside_if }}{{/if}}
+{{#each user.tags}}{{ this }}{{/each}}
+x-again: {{ missing }}";
+
+    let missing = missing_variables(template, &data, &Partials::new()).unwrap();
+    let names: Vec<(&str, usize)> = missing.iter().map(|m| (m.name.as_str(), m.line)).collect();
+    assert_eq!(
+        names,
+        vec![
+            ("missing", 3),
+            ("user.nope", 3),
+            ("nothing", 4),
+            ("inside_if", 6)
+        ]
+    );
+}
+
+#[test]
+fn test_render_strict() {
+    let data = serde_json::json!({ "name": "yep" });
+    assert_eq!(render("hi {{ name }}", &data).unwrap(), "hi yep");
+
+    let err = render("{{ a }} {{ name }} {{ b.c }}", &data).unwrap_err();
+    assert_eq!(err.to_string(), "Unresolved template variables: a, b.c");
+
+    let err = render("GET {{ baseUrl }}/users\nx-user: {{ user.id }}\n", &data).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unresolved template variables: baseUrl (line 1), user.id (line 2)"
+    );
+}
+
+#[test]
+fn test_default_helper() {
+    let data = serde_json::json!({ "name": "yep", "count": 3, "nothing": null });
+    assert_eq!(render("{{ default name \"nope\" }}", &data).unwrap(), "yep");
+    assert_eq!(render("{{ default count 1 }}", &data).unwrap(), "3");
+    assert_eq!(
+        render("{{ default missing \"fallback\" }}", &data).unwrap(),
+        "fallback"
+    );
+    assert_eq!(render("{{ default nothing 10 }}", &data).unwrap(), "10");
+    assert_eq!(render("[{{ default missing \"\" }}]", &data).unwrap(), "[]");
+}
+
+#[test]
+fn test_render_partials() {
+    let data = serde_json::json!({ "token": "abc" });
+    let partials = Partials::from([
+        (
+            "common/headers".to_owned(),
+            "Accept: application/json\nAuthorization: Bearer {{ token }}\n".to_owned(),
+        ),
+        (
+            "common/trace".to_owned(),
+            "x-trace: {{ trace_id }}\n{{> common/headers }}".to_owned(),
+        ),
+    ]);
+    let template = "GET https://example.com/users\n{{> common/headers }}\n\n{\"all\": true}";
+    assert_eq!(
+        render_with_partials(template, &data, &partials).unwrap(),
+        "GET https://example.com/users\nAccept: application/json\nAuthorization: Bearer abc\n\n{\"all\": true}"
+    );
+
+    let template = "GET https://example.com/users\n{{> common/trace }}";
+    let missing = missing_variables(template, &data, &partials).unwrap();
+    assert_eq!(
+        missing,
+        vec![MissingVariable {
+            name: "trace_id".to_owned(),
+            line: 2
+        }]
+    );
+
+    let err = render_with_partials("GET /\n{{> common/footer }}", &data, &partials).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unknown partial 'common/footer' (line 2), expected a file in the partials folder."
+    );
+
+    let looping = Partials::from([("self".to_owned(), "{{> self }}".to_owned())]);
+    assert!(render_with_partials("{{> self }}", &data, &looping).is_err());
+}
+
+#[test]
+fn test_load_partials() {
+    let dir = std::env::temp_dir().join(format!("reqq-partials-{}", std::process::id()));
+    fs::create_dir_all(dir.join(PARTIALS_FOLDER).join("common")).unwrap();
+    fs::write(
+        dir.join(PARTIALS_FOLDER)
+            .join("common")
+            .join("headers.reqq"),
+        "Accept: */*\n",
+    )
+    .unwrap();
+    fs::write(dir.join(PARTIALS_FOLDER).join("footer"), "x-footer: 1\n").unwrap();
+
+    let partials = load_partials(&dir).unwrap();
+    assert_eq!(partials.len(), 2);
+    assert_eq!(partials["common/headers"], "Accept: */*\n");
+    assert_eq!(partials["footer"], "x-footer: 1\n");
+    assert!(load_partials(&dir.join("nowhere")).unwrap().is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,165 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Milliseconds since the unix epoch.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Formats milliseconds since the unix epoch as an ISO 8601 UTC timestamp, e.g.
+/// `2026-10-15T08:58:21Z`.
+pub fn format_iso8601(millis: u64) -> String {
+    let secs = millis / 1000;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_of_day = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Parses a duration like `30`, `30s`, `500ms` or `2m`. Plain numbers are seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let invalid = || {
+        format!(
+            "invalid duration '{}', expected e.g. 30, 30s, 500ms or 2m",
+            value
+        )
+    };
+
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    match unit.trim() {
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "ms" => Ok(Duration::from_millis(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Formats a duration the way [`parse_duration`] reads it, e.g. `30s` or `500ms`.
+pub fn format_duration(duration: Duration) -> String {
+    if duration.subsec_millis() == 0 {
+        format!("{}s", duration.as_secs())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+/// Parses an HTTP date like `Wed, 21 Oct 2015 07:28:00 GMT` into seconds since the unix epoch.
+/// The older `Wed, 21-Oct-2015 07:28:00 GMT` form used in cookies is accepted too.
+pub fn parse_http_date(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    let date = value.split_once(',').map_or(value, |(_, date)| date);
+    let parts: Vec<&str> = date.split([' ', '-']).filter(|p| !p.is_empty()).collect();
+    let [day, month, year, clock, ..] = parts[..] else {
+        return None;
+    };
+
+    let day: u32 = day.parse().ok()?;
+    let month = MONTHS
+        .iter()
+        .position(|m| month.to_ascii_lowercase().starts_with(m))? as u32
+        + 1;
+    let year: i64 = match year.parse().ok()? {
+        year @ 0..=69 => year + 2000,
+        year @ 70..=99 => year + 1900,
+        year => year,
+    };
+    let clock: Vec<u64> = clock
+        .split(':')
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+    let [hours, minutes, seconds] = clock[..] else {
+        return None;
+    };
+
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return Some(0);
+    }
+    Some(days as u64 * 86_400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Converts a (year, month, day) date into days since the unix epoch.
+///
+/// See http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Converts days since the unix epoch into a (year, month, day) date.
+///
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[test]
+fn test_format_iso8601() {
+    assert_eq!(format_iso8601(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_iso8601(951_782_400_000), "2000-02-29T00:00:00Z");
+    assert_eq!(format_iso8601(1_792_054_701_999), "2026-10-15T08:58:21Z");
+}
+
+#[test]
+fn test_parse_http_date() {
+    assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+    assert_eq!(
+        parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT"),
+        Some(1_445_412_480)
+    );
+    assert_eq!(
+        parse_http_date("Wed, 21-Oct-15 07:28:00 GMT"),
+        Some(1_445_412_480)
+    );
+    assert_eq!(
+        parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT"),
+        Some(951_782_400)
+    );
+    assert_eq!(parse_http_date("tomorrow"), None);
+    assert_eq!(parse_http_date("Wed, 21 Oct 2015 7h28 GMT"), None);
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+    assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+    assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+    assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+    assert!(parse_duration("soon").is_err());
+    assert!(parse_duration("5h").is_err());
+    assert!(parse_duration("").is_err());
+
+    assert_eq!(format_duration(Duration::from_secs(5)), "5s");
+    assert_eq!(format_duration(Duration::from_millis(1500)), "1500ms");
+}
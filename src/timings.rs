@@ -0,0 +1,296 @@
+//! `--timings`: how long each phase of a request took, like `curl -w`. reqwest doesn't report
+//! its phases, so timed requests are written out as HTTP/1.1 over a connection of their own,
+//! timing the DNS lookup, connecting, the TLS handshake, the wait for the first byte of the
+//! response and reading the rest of it.
+
+use crate::{compression::Decoded, http1, request::Request, response::Response};
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+/// How long each phase of a request took. Phases that didn't happen, like the TLS handshake of
+/// a plain HTTP request, are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timings {
+    pub dns: Option<Duration>,
+    pub connect: Duration,
+    pub tls: Option<Duration>,
+    /// From sending the request until the first byte of the response arrived.
+    pub wait: Duration,
+    /// Reading the rest of the response.
+    pub download: Duration,
+    /// The sizes of a compressed body that was decoded.
+    pub decoded: Option<Decoded>,
+}
+
+impl Timings {
+    /// The time from starting the request until the first byte of the response arrived.
+    pub fn first_byte(&self) -> Duration {
+        self.dns.unwrap_or_default() + self.connect + self.tls.unwrap_or_default() + self.wait
+    }
+
+    pub fn total(&self) -> Duration {
+        self.first_byte() + self.download
+    }
+
+    /// A table of the phases, with how long each took and the time elapsed at its end.
+    pub fn report(&self) -> String {
+        let phases = [
+            ("DNS lookup", self.dns),
+            ("TCP connect", Some(self.connect)),
+            ("TLS handshake", self.tls),
+            ("First byte", Some(self.wait)),
+            ("Download", Some(self.download)),
+        ];
+        let mut lines = vec![format!("{:<15} {:>10} {:>10}", "Phase", "Time", "Elapsed")];
+        let mut elapsed = Duration::ZERO;
+        for (name, duration) in phases {
+            let Some(duration) = duration else {
+                continue;
+            };
+            elapsed += duration;
+            lines.push(format!(
+                "{:<15} {:>10} {:>10}",
+                name,
+                millis(duration),
+                millis(elapsed)
+            ));
+        }
+        if let Some(decoded) = &self.decoded {
+            lines.push(format!("{:<15} {}", "Body", decoded.report()));
+        }
+        lines.join("\n")
+    }
+
+    /// The phases in milliseconds, with the time to the first byte and the total, and the
+    /// sizes of a decoded body.
+    pub fn to_json(&self) -> Value {
+        let ms = |d: Duration| (d.as_secs_f64() * 1e4).round() / 10.0;
+        json!({
+            "dns_ms": self.dns.map(ms),
+            "connect_ms": ms(self.connect),
+            "tls_ms": self.tls.map(ms),
+            "first_byte_ms": ms(self.first_byte()),
+            "download_ms": ms(self.download),
+            "total_ms": ms(self.total()),
+            "content_encoding": self.decoded.as_ref().map(|d| &d.encoding),
+            "encoded_bytes": self.decoded.as_ref().map(|d| d.encoded_size),
+            "decoded_bytes": self.decoded.as_ref().map(|d| d.decoded_size),
+        })
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Sends a parsed request over a new connection, timing each phase. Requests are built with
+/// the client first, so their headers, body and credentials are the same as when sent with it,
+/// but they don't go through a proxy, and redirects aren't followed.
+pub fn send(req: &Request, client: &Client) -> Result<(Response, Timings)> {
+    let built = req.build(client)?;
+    let opts = req.client_opts();
+    let mut timings = Timings::default();
+
+    let mut stream: Box<dyn Stream> = match req.unix_socket() {
+        #[cfg(unix)]
+        Some(socket) => {
+            let start = Instant::now();
+            let stream = std::os::unix::net::UnixStream::connect(&socket).with_context(|| {
+                format!("Couldn't connect to the Unix socket '{}'", socket.display())
+            })?;
+            timings.connect = start.elapsed();
+            stream.set_read_timeout(req.timeout())?;
+            Box::new(stream)
+        }
+        #[cfg(not(unix))]
+        Some(socket) => {
+            return Err(anyhow!(
+                "Unix sockets like '{}' aren't supported on this platform.",
+                socket.display()
+            ))
+        }
+        None => {
+            let url = built.url();
+            let secure = match url.scheme() {
+                "http" => false,
+                "https" => true,
+                scheme => {
+                    return Err(anyhow!(
+                        "Only http and https requests can be timed, not {}://.",
+                        scheme
+                    ))
+                }
+            };
+            let host = http1::connect_host(url)?;
+            let port = url
+                .port_or_known_default()
+                .unwrap_or(if secure { 443 } else { 80 });
+
+            // Hosts given with `--resolve` skip the lookup.
+            let start = Instant::now();
+            let resolved = opts.resolve.iter().find(|r| r.matches(&host, port));
+            let addr = match resolved {
+                Some(resolve) => SocketAddr::new(resolve.addr, port),
+                None => {
+                    let addr = (host.as_str(), port)
+                        .to_socket_addrs()
+                        .map_err(|e| anyhow!("Couldn't resolve {}: {}", host, e))?
+                        .next()
+                        .ok_or_else(|| anyhow!("Couldn't resolve {}.", host))?;
+                    timings.dns = Some(start.elapsed());
+                    addr
+                }
+            };
+
+            let start = Instant::now();
+            let tcp = match opts.connect_timeout {
+                Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+            let tcp = tcp.with_context(|| format!("Couldn't connect to {}:{}", host, port))?;
+            timings.connect = start.elapsed();
+            tcp.set_read_timeout(req.timeout())?;
+
+            if secure {
+                let start = Instant::now();
+                let tls = opts
+                    .tls_connector()?
+                    .connect(&host, tcp)
+                    .map_err(|e| anyhow!("TLS handshake with {} failed: {}", host, e))?;
+                timings.tls = Some(start.elapsed());
+                Box::new(tls)
+            } else {
+                Box::new(tcp)
+            }
+        }
+    };
+
+    let start = Instant::now();
+    http1::write_request(&mut stream, &built, req.streamed_file())?;
+    let mut reader = BufReader::new(stream);
+    reader.fill_buf()?;
+    timings.wait = start.elapsed();
+
+    let start = Instant::now();
+    let (resp, decoded) = http1::read_response(&mut reader, built.method())?;
+    timings.download = start.elapsed();
+    timings.decoded = decoded;
+    Ok((resp, timings))
+}
+
+#[test]
+fn test_timings_report() {
+    let timings = Timings {
+        dns: Some(Duration::from_micros(1200)),
+        connect: Duration::from_micros(400),
+        tls: None,
+        wait: Duration::from_millis(20),
+        download: Duration::from_micros(2500),
+        decoded: None,
+    };
+    assert_eq!(timings.first_byte(), Duration::from_micros(21600));
+    assert_eq!(
+        timings.report(),
+        "Phase                 Time    Elapsed\n\
+         DNS lookup          1.2 ms     1.2 ms\n\
+         TCP connect         0.4 ms     1.6 ms\n\
+         First byte         20.0 ms    21.6 ms\n\
+         Download            2.5 ms    24.1 ms"
+    );
+    let json = timings.to_json();
+    assert_eq!(json["tls_ms"], Value::Null);
+    assert_eq!(json["first_byte_ms"], 21.6);
+    assert_eq!(json["total_ms"], 24.1);
+    assert_eq!(json["encoded_bytes"], Value::Null);
+
+    let decoded = Decoded {
+        encoding: "gzip".to_owned(),
+        encoded_size: 310,
+        decoded_size: 1024,
+    };
+    let timings = Timings {
+        decoded: Some(decoded),
+        ..timings
+    };
+    assert!(timings
+        .report()
+        .ends_with("\nBody            1024 bytes, 310 bytes gzip encoded"));
+    assert_eq!(
+        (
+            timings.to_json()["encoded_bytes"].clone(),
+            timings.to_json()["decoded_bytes"].clone()
+        ),
+        (json!(310), json!(1024))
+    );
+}
+
+#[test]
+fn test_timings_send() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut head = String::new();
+        while reader.read_line(&mut head).unwrap() > 2 {}
+        (&stream)
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+            .unwrap();
+        head
+    });
+
+    let fstr = format!(
+        "GET http://localhost:{}/health?deep=1\nAccept: text/plain\n",
+        port
+    );
+    let mut req = Request::from_rendered("health".to_owned(), fstr);
+    req.parse(None, Default::default()).unwrap();
+    let (resp, timings) = send(&req, &Client::new()).unwrap();
+    assert_eq!(resp.body, "ok");
+    assert!(timings.dns.is_some() && timings.tls.is_none());
+    assert!(server.join().unwrap().starts_with(&format!(
+        "GET /health?deep=1 HTTP/1.1\r\nhost: localhost:{}\r\n",
+        port
+    )));
+}
+
+#[test]
+fn test_timings_send_ipv6() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("[::1]:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut head = String::new();
+        while reader.read_line(&mut head).unwrap() > 2 {}
+        (&stream)
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+            .unwrap();
+        head
+    });
+
+    let mut req = Request::from_rendered(
+        "health".to_owned(),
+        format!("GET http://[::1]:{}/health\n", port),
+    );
+    req.parse(None, Default::default()).unwrap();
+    let (resp, _) = send(&req, &Client::new()).unwrap();
+    assert_eq!(resp.body, "ok");
+    // The Host header keeps the brackets.
+    assert!(server
+        .join()
+        .unwrap()
+        .contains(&format!("host: [::1]:{}\r\n", port)));
+}
@@ -0,0 +1,435 @@
+use crate::{auth::OAuth2, client::ClientOpts, secrets::KeyringStore, time};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The folder access tokens are cached in, relative to the reqq directory.
+pub const FOLDER: &str = "tokens";
+
+/// Tokens are refreshed this long before they expire, so they don't expire mid-request.
+const EXPIRY_MARGIN_MS: u64 = 30_000;
+
+/// OAuth2 access tokens, cached per environment in the `tokens` folder of a reqq directory, or
+/// in the OS keyring.
+pub struct TokenCache {
+    dir: PathBuf,
+    keyring: Option<KeyringStore>,
+}
+
+/// A cached access token, and the settings it was fetched with.
+#[derive(Clone, Debug, PartialEq)]
+struct CachedToken {
+    token_url: String,
+    client_id: String,
+    access_token: String,
+    /// When the token expires, in milliseconds since the unix epoch, if the server said.
+    expires_at: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+impl CachedToken {
+    fn is_valid_for(&self, oauth: &OAuth2, now: u64) -> bool {
+        self.token_url == oauth.token_url
+            && self.client_id == oauth.client_id
+            && self
+                .expires_at
+                .is_none_or(|expires_at| expires_at > now.saturating_add(EXPIRY_MARGIN_MS))
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "token_url": self.token_url,
+            "client_id": self.client_id,
+            "access_token": self.access_token,
+            "expires_at": self.expires_at,
+            "refresh_token": self.refresh_token,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let string = |key: &str| value[key].as_str().map(str::to_owned);
+        Some(CachedToken {
+            token_url: string("token_url")?,
+            client_id: string("client_id")?,
+            access_token: string("access_token")?,
+            expires_at: value["expires_at"].as_u64(),
+            refresh_token: string("refresh_token"),
+        })
+    }
+}
+
+impl TokenCache {
+    pub fn new(reqq_dir: &str) -> Self {
+        TokenCache {
+            dir: Path::new(reqq_dir).join(FOLDER),
+            keyring: None,
+        }
+    }
+
+    /// A cache that keeps tokens in the OS keyring instead of files.
+    pub fn in_keyring(reqq_dir: &str) -> Self {
+        TokenCache {
+            keyring: Some(KeyringStore::new(reqq_dir)),
+            ..TokenCache::new(reqq_dir)
+        }
+    }
+
+    /// An access token for the given settings: the cached one for the environment while it's
+    /// valid, otherwise a new one from the token endpoint.
+    pub fn access_token(
+        &self,
+        oauth: &OAuth2,
+        env_name: Option<&str>,
+        client: &ClientOpts,
+        verbose: bool,
+    ) -> Result<String> {
+        let cached = self.load(env_name)?;
+        if let Some(token) = cached
+            .iter()
+            .find(|t| t.is_valid_for(oauth, time::now_millis()))
+        {
+            return Ok(token.access_token.clone());
+        }
+
+        // A refresh token from an earlier response takes precedence over the configured one,
+        // since servers may rotate them.
+        let cached_refresh_token = cached
+            .filter(|t| t.token_url == oauth.token_url && t.client_id == oauth.client_id)
+            .and_then(|t| t.refresh_token);
+
+        if verbose {
+            eprintln!("* Fetching an OAuth2 access token from {}", oauth.token_url);
+        }
+        let token = match cached_refresh_token {
+            Some(refresh_token) => match fetch(oauth, Some(refresh_token), client) {
+                // It expired or was revoked, so it's forgotten for the configured grant.
+                Err(e) if e.is::<InvalidGrant>() => {
+                    if verbose {
+                        eprintln!("* The cached refresh token was refused, fetching a new token");
+                    }
+                    self.remove(env_name)?;
+                    fetch(oauth, oauth.refresh_token.clone(), client)?
+                }
+                result => result?,
+            },
+            None => fetch(oauth, oauth.refresh_token.clone(), client)?,
+        };
+        self.save(env_name, &token)?;
+        Ok(token.access_token)
+    }
+
+    fn fpath(&self, env_name: Option<&str>) -> PathBuf {
+        // Without an env, `_` can't collide with an env's name since env names can't be empty.
+        self.dir.join(format!("{}.json", env_name.unwrap_or("_")))
+    }
+
+    /// The name of an env's token in the keyring. It starts with a dot, like the index of
+    /// secrets, so it can't be mistaken for one.
+    fn keyring_name(env_name: Option<&str>) -> String {
+        format!(".token/{}", env_name.unwrap_or("_"))
+    }
+
+    fn load(&self, env_name: Option<&str>) -> Result<Option<CachedToken>> {
+        let (json, source) = match &self.keyring {
+            Some(keyring) => (
+                keyring.read(&Self::keyring_name(env_name))?,
+                "in the keyring".to_owned(),
+            ),
+            None => {
+                let fpath = self.fpath(env_name);
+                if !fpath.is_file() {
+                    return Ok(None);
+                }
+                (
+                    Some(fs::read_to_string(&fpath)?),
+                    format!("'{}'", fpath.display()),
+                )
+            }
+        };
+        let Some(json) = json else {
+            return Ok(None);
+        };
+        let value: Value = serde_json::from_str(&json)
+            .map_err(|_| anyhow!("Malformed token cache {}.", source))?;
+        CachedToken::from_json(&value)
+            .map(Some)
+            .ok_or_else(|| anyhow!("Malformed token cache {}.", source))
+    }
+
+    fn remove(&self, env_name: Option<&str>) -> Result<()> {
+        if let Some(keyring) = &self.keyring {
+            keyring.delete(&Self::keyring_name(env_name))?;
+            return Ok(());
+        }
+        match fs::remove_file(self.fpath(env_name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn save(&self, env_name: Option<&str>, token: &CachedToken) -> Result<()> {
+        let json = serde_json::to_string_pretty(&token.to_json())?;
+        if let Some(keyring) = &self.keyring {
+            return keyring.write(&Self::keyring_name(env_name), &json);
+        }
+        let fpath = self.fpath(env_name);
+        if let Some(parent) = fpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&fpath, json)?;
+        Ok(())
+    }
+}
+
+/// A token endpoint's `invalid_grant` error, for a refresh token that expired or was revoked.
+#[derive(Debug)]
+struct InvalidGrant {
+    status: reqwest::StatusCode,
+    body: String,
+}
+
+impl fmt::Display for InvalidGrant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Fetching an OAuth2 token failed with status {}: {}",
+            self.status, self.body
+        )
+    }
+}
+
+impl std::error::Error for InvalidGrant {}
+
+fn fetch(
+    oauth: &OAuth2,
+    refresh_token: Option<String>,
+    client: &ClientOpts,
+) -> Result<CachedToken> {
+    let mut form = vec![
+        ("client_id", oauth.client_id.clone()),
+        ("client_secret", oauth.client_secret.clone()),
+    ];
+    match refresh_token.clone() {
+        Some(refresh_token) => {
+            form.push(("grant_type", "refresh_token".to_owned()));
+            form.push(("refresh_token", refresh_token));
+        }
+        None => form.push(("grant_type", "client_credentials".to_owned())),
+    }
+    if let Some(scope) = &oauth.scope {
+        form.push(("scope", scope.clone()));
+    }
+
+    let client = match Url::parse(&oauth.token_url) {
+        Ok(url) => client.for_url(&url),
+        Err(_) => client.clone(),
+    };
+    let resp = client.build()?.post(&oauth.token_url).form(&form).send()?;
+    let status = resp.status();
+    let body = resp.text()?;
+    if !status.is_success() {
+        let error = serde_json::from_str::<Value>(&body).map(|v| v["error"].clone());
+        if error.is_ok_and(|e| e == "invalid_grant") {
+            return Err(InvalidGrant { status, body }.into());
+        }
+        return Err(anyhow!(
+            "Fetching an OAuth2 token failed with status {}: {}",
+            status,
+            body
+        ));
+    }
+    parse_token_response(oauth, &body, refresh_token, time::now_millis())
+}
+
+fn parse_token_response(
+    oauth: &OAuth2,
+    body: &str,
+    refresh_token: Option<String>,
+    now: u64,
+) -> Result<CachedToken> {
+    let value: Value = serde_json::from_str(body)
+        .map_err(|_| anyhow!("The OAuth2 token response isn't JSON: {}", body))?;
+    let access_token = value["access_token"]
+        .as_str()
+        .ok_or_else(|| anyhow!("The OAuth2 token response has no access_token: {}", body))?;
+
+    Ok(CachedToken {
+        token_url: oauth.token_url.clone(),
+        client_id: oauth.client_id.clone(),
+        access_token: access_token.to_owned(),
+        expires_at: value["expires_in"]
+            .as_u64()
+            .and_then(|secs| secs.checked_mul(1000))
+            .map(|ms| now.saturating_add(ms)),
+        refresh_token: value["refresh_token"]
+            .as_str()
+            .map(str::to_owned)
+            .or(refresh_token),
+    })
+}
+
+#[cfg(test)]
+fn test_oauth() -> OAuth2 {
+    OAuth2 {
+        token_url: "https://auth.example.com/token".to_owned(),
+        client_id: "reqq".to_owned(),
+        client_secret: "secret".to_owned(),
+        scope: None,
+        refresh_token: None,
+    }
+}
+
+#[test]
+fn test_token_response() {
+    let oauth = test_oauth();
+    let token = parse_token_response(
+        &oauth,
+        r#"{"access_token": "abc", "expires_in": 3600}"#,
+        None,
+        1_000,
+    )
+    .unwrap();
+    assert_eq!(token.access_token, "abc");
+    assert_eq!(token.expires_at, Some(3_601_000));
+    assert_eq!(token.refresh_token, None);
+
+    assert!(token.is_valid_for(&oauth, 1_000));
+    assert!(!token.is_valid_for(&oauth, 3_600_000));
+    let other = OAuth2 {
+        client_id: "other".to_owned(),
+        ..test_oauth()
+    };
+    assert!(!token.is_valid_for(&other, 1_000));
+
+    let token = parse_token_response(
+        &oauth,
+        r#"{"access_token": "abc", "refresh_token": "r2"}"#,
+        Some("r1".to_owned()),
+        0,
+    )
+    .unwrap();
+    assert_eq!(token.refresh_token.as_deref(), Some("r2"));
+    assert!(token.is_valid_for(&oauth, u64::MAX / 2));
+
+    // Expiry times too far out to count in milliseconds are taken as no expiry.
+    let token = parse_token_response(
+        &oauth,
+        r#"{"access_token": "abc", "expires_in": 18446744073709551615}"#,
+        None,
+        1_000,
+    )
+    .unwrap();
+    assert_eq!(token.expires_at, None);
+    let token = parse_token_response(
+        &oauth,
+        r#"{"access_token": "abc", "expires_in": 18446744073709551}"#,
+        None,
+        1_000,
+    )
+    .unwrap();
+    assert_eq!(token.expires_at, Some(u64::MAX));
+    assert!(token.is_valid_for(&oauth, u64::MAX / 2));
+
+    assert!(parse_token_response(&oauth, r#"{"error": "invalid_client"}"#, None, 0).is_err());
+}
+
+#[test]
+fn test_token_cache() {
+    let dir = std::env::temp_dir().join(format!("reqq-tokens-{}", std::process::id()));
+    let cache = TokenCache::new(dir.to_str().unwrap());
+    let fpath = cache.fpath(Some("staging"));
+    fs::create_dir_all(fpath.parent().unwrap()).unwrap();
+
+    let token = CachedToken {
+        token_url: "https://auth.example.com/token".to_owned(),
+        client_id: "reqq".to_owned(),
+        access_token: "cached".to_owned(),
+        expires_at: None,
+        refresh_token: None,
+    };
+    fs::write(&fpath, token.to_json().to_string()).unwrap();
+    assert_eq!(cache.load(Some("staging")).unwrap(), Some(token));
+
+    // A valid cached token is used without contacting the token endpoint.
+    let access_token = cache.access_token(
+        &test_oauth(),
+        Some("staging"),
+        &ClientOpts::default(),
+        false,
+    );
+    assert_eq!(access_token.unwrap(), "cached");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_token_invalid_grant() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    // The token endpoint refuses refresh tokens, and grants client credentials.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let mut grants = vec![];
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    len = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            let body = String::from_utf8(body).unwrap();
+            let (status, json) = if body.contains("grant_type=refresh_token") {
+                ("400 Bad Request", r#"{"error": "invalid_grant"}"#)
+            } else {
+                ("200 OK", r#"{"access_token": "fresh"}"#)
+            };
+            let resp = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                json.len(),
+                json
+            );
+            (&stream).write_all(resp.as_bytes()).unwrap();
+            grants.push(body.contains("grant_type=client_credentials"));
+        }
+        grants
+    });
+
+    let dir = std::env::temp_dir().join(format!("reqq-tokens-grant-{}", std::process::id()));
+    let cache = TokenCache::new(dir.to_str().unwrap());
+    let oauth = OAuth2 {
+        token_url: format!("http://127.0.0.1:{}/token", port),
+        ..test_oauth()
+    };
+    let expired = CachedToken {
+        token_url: oauth.token_url.clone(),
+        client_id: oauth.client_id.clone(),
+        access_token: "stale".to_owned(),
+        expires_at: Some(0),
+        refresh_token: Some("revoked".to_owned()),
+    };
+    cache.save(Some("staging"), &expired).unwrap();
+
+    let access_token = cache.access_token(&oauth, Some("staging"), &ClientOpts::default(), false);
+    assert_eq!(access_token.unwrap(), "fresh");
+    assert_eq!(server.join().unwrap(), vec![false, true]);
+    let cached = cache.load(Some("staging")).unwrap().unwrap();
+    assert_eq!(cached.access_token, "fresh");
+    assert_eq!(cached.refresh_token, None);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,755 @@
+//! An interactive terminal UI for browsing requests, sending them and reading the responses.
+//!
+//! Drawn with plain ANSI escape codes, with the terminal in raw mode while it runs.
+
+use crate::pager;
+use anyhow::{anyhow, Result};
+use reqq::Reqq;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Read, Write};
+
+const LIST_HELP: &str = "j/k move  enter send  e env  c clear vars  q quit";
+const PICKER_HELP: &str = "type to filter  up/down move  enter send  esc cancel";
+const RESPONSE_HELP: &str = "j/k scroll  space/b page  g/G top/bottom  / search  n next  q back";
+const PAGER_HELP: &str = "j/k scroll  space/b page  g/G top/bottom  / search  n next  q quit";
+
+#[derive(Debug, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Esc,
+    Backspace,
+    Interrupt,
+    Char(char),
+}
+
+/// A line of the request tree: a folder, or a request with its full name.
+#[derive(Debug, PartialEq)]
+struct Row {
+    depth: usize,
+    label: String,
+    request: Option<String>,
+}
+
+struct ResponseView {
+    title: String,
+    lines: Vec<String>,
+    offset: usize,
+    search: Option<String>,
+}
+
+struct App<'a> {
+    reqq: &'a Reqq<'a>,
+    rows: Vec<Row>,
+    selected: usize,
+    offset: usize,
+    envs: Vec<String>,
+    env: Option<usize>,
+    /// Values given for missing variables, kept for later requests.
+    vars: HashMap<String, Value>,
+    response: Option<ResponseView>,
+    status: String,
+}
+
+/// Puts the terminal in raw mode on the alternate screen, and restores it when dropped.
+struct Terminal {
+    original: libc::termios,
+    /// Bytes read but not parsed into keys yet.
+    pending: RefCell<Vec<u8>>,
+}
+
+impl Terminal {
+    fn enter() -> Result<Self> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Err(anyhow!("reqq tui needs to run in a terminal."));
+        }
+        // SAFETY: `termios` is plain data, filled in by `tcgetattr` before it's read.
+        let original = unsafe {
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let mut raw = original;
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            original
+        };
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()?;
+        Ok(Terminal {
+            original,
+            pending: RefCell::new(vec![]),
+        })
+    }
+
+    /// The number of rows and columns of the terminal.
+    fn size(&self) -> (usize, usize) {
+        pager::terminal_size().unwrap_or((24, 80))
+    }
+
+    fn read_key(&self) -> Result<Key> {
+        let mut pending = self.pending.borrow_mut();
+        loop {
+            if pending.is_empty() {
+                let mut buf = [0; 256];
+                let n = io::stdin().read(&mut buf)?;
+                pending.extend_from_slice(&buf[..n]);
+            }
+            let (key, len) = parse_key(&pending);
+            pending.drain(..len);
+            if let Some(key) = key {
+                return Ok(key);
+            }
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        // SAFETY: Restores the settings read in `enter`.
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original);
+        }
+    }
+}
+
+/// Runs the terminal UI until it's quit.
+pub fn run(reqq: &Reqq, env_name: Option<String>) -> Result<()> {
+    let envs = reqq.list_envs();
+    let env = match env_name {
+        Some(name) => Some(
+            envs.iter()
+                .position(|e| *e == name)
+                .ok_or_else(|| anyhow!("Environment '{}' not found.", name))?,
+        ),
+        None => envs.iter().position(|e| e == reqq.default_env()),
+    };
+    let mut names = reqq.list_reqs();
+    names.sort();
+    let mut app = App {
+        reqq,
+        rows: tree(&names),
+        selected: 0,
+        offset: 0,
+        envs,
+        env,
+        vars: HashMap::new(),
+        response: None,
+        status: String::new(),
+    };
+    app.select(0, 1);
+
+    let term = Terminal::enter()?;
+    loop {
+        app.draw(&term, None)?;
+        let key = term.read_key()?;
+        app.status.clear();
+        let done = match app.response {
+            Some(_) => app.response_key(&term, key)?,
+            None => app.list_key(&term, key)?,
+        };
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+impl App<'_> {
+    /// Handles a key on the request list. Returns whether to quit.
+    fn list_key(&mut self, term: &Terminal, key: Key) -> Result<bool> {
+        let page = term.size().0.saturating_sub(2).max(1);
+        match key {
+            Key::Char('q') | Key::Esc | Key::Interrupt => return Ok(true),
+            Key::Up | Key::Char('k') => self.select(self.selected.saturating_sub(1), -1),
+            Key::Down | Key::Char('j') => self.select(self.selected + 1, 1),
+            Key::PageUp => self.select(self.selected.saturating_sub(page), -1),
+            Key::PageDown => self.select(self.selected + page, 1),
+            Key::Home | Key::Char('g') => self.select(0, 1),
+            Key::End | Key::Char('G') => self.select(self.rows.len().saturating_sub(1), -1),
+            Key::Char('e') => {
+                self.env = match self.env {
+                    None if !self.envs.is_empty() => Some(0),
+                    Some(i) if i + 1 < self.envs.len() => Some(i + 1),
+                    _ => None,
+                };
+            }
+            Key::Char('c') => {
+                self.vars.clear();
+                self.status = "Cleared variables.".to_owned();
+            }
+            Key::Enter => {
+                if let Some(name) = self.rows.get(self.selected).and_then(|r| r.request.clone()) {
+                    if let Err(e) = self.send(term, &name) {
+                        self.status = format!("Error: {}", e);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    /// Handles a key on the response view. Returns whether to quit.
+    fn response_key(&mut self, term: &Terminal, key: Key) -> Result<bool> {
+        let page = term.size().0.saturating_sub(2).max(1);
+        let search = match key {
+            Key::Char('/') => self.prompt(term, "Search", "")?,
+            _ => None,
+        };
+        let view = self.response.as_mut().expect("No response to show.");
+        match key {
+            Key::Interrupt => return Ok(true),
+            Key::Char('q') | Key::Esc => self.response = None,
+            key => {
+                if let Some(status) = view.scroll(&key, page, search) {
+                    self.status = status;
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Moves the selection to a row, or the nearest request from it in the given direction,
+    /// scrolling the list to keep it in view.
+    fn select(&mut self, row: usize, direction: isize) {
+        let mut row = row.min(self.rows.len().saturating_sub(1));
+        while self.rows.get(row).is_some_and(|r| r.request.is_none()) {
+            match row
+                .checked_add_signed(direction)
+                .filter(|r| *r < self.rows.len())
+            {
+                Some(next) => row = next,
+                // Nothing past here, so look the other way.
+                None => return self.select(row, -direction),
+            }
+        }
+        self.selected = row;
+    }
+
+    /// Asks for the variables a request is missing, then sends it and shows the response.
+    fn send(&mut self, term: &Terminal, name: &str) -> Result<()> {
+        let env = self.env.map(|i| self.envs[i].clone());
+        for var in self
+            .reqq
+            .missing_variables(name, env.clone(), self.vars.clone())?
+        {
+            let previous = self
+                .vars
+                .get(&var)
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            match self.prompt(term, &var, &previous)? {
+                // Values are read like `-a` args: JSON if it's valid, a string otherwise.
+                Some(value) => {
+                    let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+                    self.vars.insert(var, value);
+                }
+                None => return Ok(()),
+            }
+        }
+
+        self.draw_status(term, &format!("Sending {}...", name))?;
+        let result = self.reqq.execute(name, env, self.vars.clone(), &[])?;
+        let output = if result.output.is_empty() {
+            "(The body was saved to a file.)".to_owned()
+        } else {
+            result.output.replace('\t', "    ")
+        };
+        self.response = Some(ResponseView {
+            title: format!("{}  {}", name, result.status),
+            lines: output.lines().map(str::to_owned).collect(),
+            offset: 0,
+            search: None,
+        });
+        Ok(())
+    }
+
+    fn prompt(&mut self, term: &Terminal, label: &str, initial: &str) -> Result<Option<String>> {
+        read_line(term, label, initial, |prompt| self.draw(term, Some(prompt)))
+    }
+
+    fn draw_status(&mut self, term: &Terminal, status: &str) -> Result<()> {
+        self.status = status.to_owned();
+        self.draw(term, None)?;
+        self.status.clear();
+        Ok(())
+    }
+
+    /// Draws the whole screen: a title bar, the list or response, and a footer with the
+    /// prompt, the status or help.
+    fn draw(&mut self, term: &Terminal, prompt: Option<&str>) -> Result<()> {
+        let (height, width) = term.size();
+        let body_height = height.saturating_sub(2);
+        let mut lines = vec![];
+
+        match &self.response {
+            Some(view) => {
+                lines.push(bar(&view.title, width));
+                lines.extend(view.render(body_height, width));
+            }
+            None => {
+                let env = self.env.map_or("none", |i| self.envs[i].as_str());
+                lines.push(bar(&format!("reqq  env: {}", env), width));
+                if self.selected < self.offset {
+                    self.offset = self.selected;
+                } else if self.selected >= self.offset + body_height {
+                    self.offset = self.selected + 1 - body_height;
+                }
+                for (i, row) in self
+                    .rows
+                    .iter()
+                    .enumerate()
+                    .skip(self.offset)
+                    .take(body_height)
+                {
+                    let line = truncate(&format!("{}{}", "  ".repeat(row.depth), row.label), width);
+                    lines.push(match (i == self.selected, &row.request) {
+                        (true, _) => format!("\x1b[7m{}\x1b[27m", line),
+                        (false, None) => format!("\x1b[1m{}\x1b[22m", line),
+                        (false, Some(_)) => line,
+                    });
+                }
+                if self.rows.is_empty() {
+                    lines.push("No requests found.".to_owned());
+                }
+            }
+        }
+        lines.resize(height.saturating_sub(1), String::new());
+
+        let help = if self.response.is_some() {
+            RESPONSE_HELP
+        } else {
+            LIST_HELP
+        };
+        let footer = match prompt {
+            Some(prompt) => prompt.to_owned(),
+            None if !self.status.is_empty() => truncate(&self.status, width),
+            None => format!("\x1b[2m{}\x1b[22m", truncate(help, width)),
+        };
+        lines.push(footer);
+        write_frame(&lines)
+    }
+}
+
+impl ResponseView {
+    /// Scrolls the view for a key, or searches on `/` and `n`. Returns a status to show when
+    /// the search doesn't match.
+    fn scroll(&mut self, key: &Key, page: usize, search: Option<String>) -> Option<String> {
+        let last = self.lines.len().saturating_sub(page);
+        match key {
+            Key::Up | Key::Char('k') => self.offset = self.offset.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.offset = (self.offset + 1).min(last),
+            Key::PageUp | Key::Char('b') => self.offset = self.offset.saturating_sub(page),
+            Key::PageDown | Key::Char(' ') => self.offset = (self.offset + page).min(last),
+            Key::Home | Key::Char('g') => self.offset = 0,
+            Key::End | Key::Char('G') => self.offset = last,
+            Key::Char('/') | Key::Char('n') => {
+                if search.is_some() {
+                    self.search = search.filter(|s| !s.is_empty());
+                }
+                // A new search starts on the first line shown, `n` after it.
+                let from = if *key == Key::Char('n') {
+                    self.offset + 1
+                } else {
+                    self.offset
+                };
+                if let Some(needle) = self.search.as_deref() {
+                    match find_line(&self.lines, needle, from) {
+                        Some(line) => self.offset = line,
+                        None => return Some(format!("No match for '{}'.", needle)),
+                    }
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// The lines in view, cut to the width, with search matches highlighted.
+    fn render(&self, height: usize, width: usize) -> Vec<String> {
+        let lines = self.lines.iter().skip(self.offset).take(height);
+        lines
+            .map(|line| {
+                let line = truncate(line, width);
+                match self.search.as_deref() {
+                    Some(needle) => line.replace(needle, &format!("\x1b[7m{}\x1b[27m", needle)),
+                    None => line,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Shows text in a scrollable view until it's quit, as a pager.
+pub fn page(text: &str) -> Result<()> {
+    let term = Terminal::enter()?;
+    let mut view = ResponseView {
+        title: String::new(),
+        lines: text
+            .replace('\t', "    ")
+            .lines()
+            .map(str::to_owned)
+            .collect(),
+        offset: 0,
+        search: None,
+    };
+    let mut status: Option<String> = None;
+    let draw = |view: &ResponseView, footer: &str| {
+        let (height, width) = term.size();
+        let mut lines = view.render(height.saturating_sub(1), width);
+        lines.resize(height.saturating_sub(1), String::new());
+        lines.push(footer.to_owned());
+        write_frame(&lines)
+    };
+    loop {
+        let (height, width) = term.size();
+        let footer = match status.take() {
+            Some(status) => truncate(&status, width),
+            None => format!("\x1b[2m{}\x1b[22m", truncate(PAGER_HELP, width)),
+        };
+        draw(&view, &footer)?;
+        let key = term.read_key()?;
+        let search = match key {
+            Key::Char('/') => read_line(&term, "Search", "", |prompt| draw(&view, prompt))?,
+            _ => None,
+        };
+        match key {
+            Key::Char('q') | Key::Esc | Key::Interrupt => return Ok(()),
+            key => status = view.scroll(&key, height.saturating_sub(1).max(1), search),
+        }
+    }
+}
+
+/// Reads a line of input, drawn as the prompt on the bottom row. Returns `None` if it's
+/// cancelled with Esc.
+fn read_line(
+    term: &Terminal,
+    label: &str,
+    initial: &str,
+    mut draw: impl FnMut(&str) -> Result<()>,
+) -> Result<Option<String>> {
+    let mut input = initial.to_owned();
+    loop {
+        draw(&format!("{}: {}\x1b[7m \x1b[27m", label, input))?;
+        match term.read_key()? {
+            Key::Enter => return Ok(Some(input)),
+            Key::Esc | Key::Interrupt => return Ok(None),
+            Key::Backspace => {
+                input.pop();
+            }
+            Key::Char(c) => input.push(c),
+            _ => {}
+        }
+    }
+}
+
+/// Lets the user pick one of the names, filtering them by fuzzy matching what they type.
+/// Returns `None` if the picker is cancelled.
+pub fn pick(names: &[String]) -> Result<Option<String>> {
+    let term = Terminal::enter()?;
+    let mut query = String::new();
+    let mut selected = 0;
+    loop {
+        let matches = fuzzy_filter(names, &query);
+        selected = selected.min(matches.len().saturating_sub(1));
+
+        let (height, width) = term.size();
+        let body_height = height.saturating_sub(2);
+        let offset = (selected + 1).saturating_sub(body_height);
+        let mut lines = vec![format!(
+            "> {}\x1b[7m \x1b[27m",
+            truncate(&query, width.saturating_sub(3))
+        )];
+        for (i, name) in matches.iter().enumerate().skip(offset).take(body_height) {
+            let line = truncate(name, width);
+            lines.push(if i == selected {
+                format!("\x1b[7m{}\x1b[27m", line)
+            } else {
+                line
+            });
+        }
+        lines.resize(height.saturating_sub(1), String::new());
+        lines.push(format!("\x1b[2m{}\x1b[22m", truncate(PICKER_HELP, width)));
+        write_frame(&lines)?;
+
+        match term.read_key()? {
+            Key::Enter => return Ok(matches.get(selected).map(|name| name.to_string())),
+            Key::Esc | Key::Interrupt => return Ok(None),
+            Key::Up => selected = selected.saturating_sub(1),
+            Key::Down => selected += 1,
+            Key::PageUp => selected = selected.saturating_sub(body_height),
+            Key::PageDown => selected += body_height,
+            Key::Backspace => {
+                query.pop();
+                selected = 0;
+            }
+            Key::Char(c) => {
+                query.push(c);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn write_frame(lines: &[String]) -> Result<()> {
+    let mut out = io::stdout().lock();
+    write!(out, "\x1b[H\x1b[2J{}", lines.join("\r\n"))?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Builds the tree of folders and requests from sorted request names like `users/create`.
+fn tree(names: &[String]) -> Vec<Row> {
+    let mut rows = vec![];
+    let mut folders: Vec<&str> = vec![];
+    for name in names {
+        let segments: Vec<&str> = name.split('/').collect();
+        let (file, parents) = segments
+            .split_last()
+            .expect("Split always yields a segment.");
+        let shared = folders
+            .iter()
+            .zip(parents)
+            .take_while(|(a, b)| a == b)
+            .count();
+        folders.truncate(shared);
+        for folder in &parents[shared..] {
+            rows.push(Row {
+                depth: folders.len(),
+                label: format!("{}/", folder),
+                request: None,
+            });
+            folders.push(folder);
+        }
+        rows.push(Row {
+            depth: folders.len(),
+            label: file.to_string(),
+            request: Some(name.clone()),
+        });
+    }
+    rows
+}
+
+/// The names matching a query, best matches first. Every name matches an empty query, in
+/// the order given.
+fn fuzzy_filter<'a>(names: &'a [String], query: &str) -> Vec<&'a str> {
+    let mut scored: Vec<(i64, &str)> = names
+        .iter()
+        .filter_map(|name| fuzzy_score(name, query).map(|score| (score, name.as_str())))
+        .collect();
+    // The sort is stable, so equal scores keep their order.
+    scored.sort_by_key(|(score, _)| -score);
+    scored.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Scores how well a name matches a query, if it has every character of the query in order,
+/// ignoring case. Characters next to each other or at the start of a segment, like the `c` of
+/// `users/create`, score higher, and characters skipped in between cost a little.
+fn fuzzy_score(name: &str, query: &str) -> Option<i64> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev: Option<usize> = None;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + name[pos..].iter().position(|c| *c == q)?;
+        score += 1;
+        if prev.is_some_and(|prev| prev + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || matches!(name[found - 1], '/' | '-' | '_' | '.' | ' ') {
+            score += 8;
+        }
+        score -= (found - pos) as i64 / 4;
+        prev = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// The first line at or after `from` that contains `needle`, wrapping around to the start.
+fn find_line(lines: &[String], needle: &str, from: usize) -> Option<usize> {
+    let from = from.min(lines.len());
+    (from..lines.len())
+        .chain(0..from)
+        .find(|&i| lines[i].contains(needle))
+}
+
+fn bar(title: &str, width: usize) -> String {
+    format!(
+        "\x1b[7m{:width$}\x1b[27m",
+        truncate(title, width),
+        width = width
+    )
+}
+
+/// Cuts a line to a number of characters, not counting the ANSI escape codes that color it.
+/// Colors are reset at the end of lines that have them.
+fn truncate(line: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut visible = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            out.push(c);
+            // Color codes like `\x1b[32m` end with a letter.
+            for c in chars.by_ref() {
+                out.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else if visible < width {
+            out.push(c);
+            visible += 1;
+        }
+    }
+    if out.contains('\x1b') {
+        out.push_str("\x1b[0m");
+    }
+    out
+}
+
+/// Parses the first key in the bytes read from the terminal, which can hold several when
+/// they're typed quickly or pasted. Returns the key, if it's one the UI knows, and the number
+/// of bytes it took up.
+fn parse_key(bytes: &[u8]) -> (Option<Key>, usize) {
+    let key = match bytes {
+        [] => return (None, 0),
+        [0x1b, b'[' | b'O', rest @ ..] => {
+            // Escape sequences end with a byte in `@` to `~`, after any parameter bytes.
+            let Some(end) = rest.iter().position(|b| (0x40..=0x7e).contains(b)) else {
+                return (None, bytes.len());
+            };
+            let key = match &rest[..=end] {
+                b"A" => Some(Key::Up),
+                b"B" => Some(Key::Down),
+                b"H" | b"1~" => Some(Key::Home),
+                b"F" | b"4~" => Some(Key::End),
+                b"5~" => Some(Key::PageUp),
+                b"6~" => Some(Key::PageDown),
+                _ => None,
+            };
+            return (key, end + 3);
+        }
+        [0x1b, ..] => Key::Esc,
+        [b'\r' | b'\n', ..] => Key::Enter,
+        [0x7f | 0x08, ..] => Key::Backspace,
+        [0x03 | 0x04, ..] => Key::Interrupt,
+        [first, ..] => {
+            let len = match first.leading_ones() {
+                2..=4 => (first.leading_ones() as usize).min(bytes.len()),
+                _ => 1,
+            };
+            let key = String::from_utf8_lossy(&bytes[..len])
+                .chars()
+                .next()
+                .map(Key::Char);
+            return (key, len);
+        }
+    };
+    (Some(key), 1)
+}
+
+#[test]
+fn test_tree() {
+    let names: Vec<String> = [
+        "health",
+        "users/create",
+        "users/admin/list",
+        "users/get",
+        "zoo/animals/list",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    let rows = tree(&names);
+    let rows: Vec<(usize, &str, bool)> = rows
+        .iter()
+        .map(|r| (r.depth, r.label.as_str(), r.request.is_some()))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (0, "health", true),
+            (0, "users/", false),
+            (1, "create", true),
+            (1, "admin/", false),
+            (2, "list", true),
+            (1, "get", true),
+            (0, "zoo/", false),
+            (1, "animals/", false),
+            (2, "list", true),
+        ]
+    );
+}
+
+#[test]
+fn test_find_line() {
+    let lines: Vec<String> = ["{", "  \"id\": 1,", "  \"name\": \"Ada\"", "}"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(find_line(&lines, "name", 0), Some(2));
+    assert_eq!(find_line(&lines, "id", 2), Some(1));
+    assert_eq!(find_line(&lines, "nope", 0), None);
+}
+
+#[test]
+fn test_truncate() {
+    assert_eq!(truncate("users/create", 5), "users");
+    assert_eq!(
+        truncate("\x1b[34m\"id\"\x1b[0m: 1", 3),
+        "\x1b[34m\"id\x1b[0m\x1b[0m"
+    );
+}
+
+#[test]
+fn test_parse_key() {
+    assert_eq!(parse_key(b"\x1b[A"), (Some(Key::Up), 3));
+    assert_eq!(parse_key(b"\x1b[6~j"), (Some(Key::PageDown), 4));
+    assert_eq!(parse_key(b"\x1b"), (Some(Key::Esc), 1));
+    assert_eq!(parse_key(b"\rq"), (Some(Key::Enter), 1));
+    assert_eq!(parse_key("éa".as_bytes()), (Some(Key::Char('é')), 2));
+    assert_eq!(parse_key(b"\x1b[Zx"), (None, 3));
+}
+
+#[test]
+fn test_fuzzy_filter() {
+    let names: Vec<String> = [
+        "users/create",
+        "users/delete",
+        "health",
+        "orders/create-user",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    assert_eq!(
+        fuzzy_filter(&names, ""),
+        vec![
+            "users/create",
+            "users/delete",
+            "health",
+            "orders/create-user"
+        ]
+    );
+    assert_eq!(fuzzy_filter(&names, "uc"), vec!["users/create"]);
+    assert_eq!(
+        fuzzy_filter(&names, "Create"),
+        vec!["users/create", "orders/create-user"]
+    );
+    assert_eq!(fuzzy_filter(&names, "hlth"), vec!["health"]);
+    assert!(fuzzy_filter(&names, "xyz").is_empty());
+}
@@ -0,0 +1,117 @@
+//! Requests sent over a Unix domain socket instead of TCP, for services like Docker's API that
+//! listen on one. The socket is given with `--unix-socket`, or in the URL as
+//! `unix://<socket path>:<path>`, like `unix:///var/run/docker.sock:/containers/json`.
+//! reqwest can't connect to Unix sockets, so requests are written out by hand.
+
+use crate::{compression::Decoded, http1, request::Request, response::Response};
+use anyhow::Result;
+use reqwest::blocking::Client;
+use std::path::Path;
+use url::Url;
+
+/// The URL scheme of requests that name their socket.
+pub const SCHEME: &str = "unix";
+
+/// The socket path and request target of a `unix://` URL.
+pub fn split_url(url: &Url) -> Option<(&str, String)> {
+    if url.scheme() != SCHEME {
+        return None;
+    }
+    let (socket, path) = url.path().split_once(':')?;
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_owned(),
+    };
+    Some((socket, target))
+}
+
+/// Sends a parsed request over a Unix socket and reads its whole response. The request is
+/// built with the client first, so its headers, body and credentials are the same as over TCP,
+/// and those with a `unix://` URL are sent for `localhost`.
+#[cfg(unix)]
+pub fn send(req: &Request, socket: &Path, client: &Client) -> Result<(Response, Option<Decoded>)> {
+    use anyhow::Context;
+    use std::io::BufReader;
+    use std::os::unix::net::UnixStream;
+
+    let built = req.build(client)?;
+    let stream = UnixStream::connect(socket)
+        .with_context(|| format!("Couldn't connect to the Unix socket '{}'", socket.display()))?;
+    stream.set_read_timeout(req.timeout())?;
+    stream.set_write_timeout(req.timeout())?;
+    http1::write_request(&mut &stream, &built, req.streamed_file())?;
+    http1::read_response(&mut BufReader::new(&stream), built.method())
+}
+
+#[cfg(not(unix))]
+pub fn send(_: &Request, socket: &Path, _: &Client) -> Result<(Response, Option<Decoded>)> {
+    Err(anyhow::anyhow!(
+        "Unix sockets like '{}' aren't supported on this platform.",
+        socket.display()
+    ))
+}
+
+#[test]
+fn test_unix_socket_url() {
+    let url = Url::parse("unix:///var/run/docker.sock:/v1.43/containers/json?all=true").unwrap();
+    assert_eq!(
+        split_url(&url),
+        Some((
+            "/var/run/docker.sock",
+            "/v1.43/containers/json?all=true".to_owned()
+        ))
+    );
+    assert_eq!(
+        split_url(&Url::parse("unix:///var/run/docker.sock").unwrap()),
+        None
+    );
+    assert_eq!(
+        split_url(&Url::parse("http://localhost/containers/json").unwrap()),
+        None
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_socket_send() {
+    use reqwest::StatusCode;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::os::unix::net::UnixListener;
+
+    let socket = std::env::temp_dir().join(format!("reqq-unix-{}.sock", std::process::id()));
+    let listener = UnixListener::bind(&socket).unwrap();
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut head = String::new();
+        while reader.read_line(&mut head).unwrap() > 2 {}
+        let mut body = [0; 2];
+        reader.read_exact(&mut body).unwrap();
+        (&stream)
+            .write_all(b"HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok")
+            .unwrap();
+        (head, body)
+    });
+
+    let fstr = format!(
+        "POST unix://{}:/containers/create?name=web\nContent-Type: application/json\n\n{{}}",
+        socket.display()
+    );
+    let mut req = Request::from_rendered("containers/create".to_owned(), fstr);
+    req.parse(None, Default::default()).unwrap();
+    assert_eq!(req.unix_socket().as_deref(), Some(socket.as_path()));
+    let (resp, _) = send(&req, &socket, &Client::new()).unwrap();
+    assert_eq!(
+        (resp.status, resp.body.as_str()),
+        (StatusCode::CREATED, "ok")
+    );
+
+    let (head, body) = server.join().unwrap();
+    assert!(head.starts_with("POST /containers/create?name=web HTTP/1.1\r\nhost: localhost\r\n"));
+    assert!(
+        head.contains("content-type: application/json\r\n")
+            && head.contains("content-length: 2\r\n")
+    );
+    assert_eq!(&body, b"{}");
+    std::fs::remove_file(socket).unwrap();
+}
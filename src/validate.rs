@@ -0,0 +1,111 @@
+//! Checking a collection for mistakes without sending anything, for `reqq check`.
+
+use std::collections::HashMap;
+
+/// A mistake in a request or env file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    /// The request, or the env as `envs/<name>`.
+    pub file: String,
+    pub message: String,
+}
+
+/// The outcome of checking every request and env file of a collection.
+#[derive(Clone, Debug, Default)]
+pub struct Validation {
+    pub requests: usize,
+    pub envs: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl Validation {
+    pub fn passed(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Records a problem, unless the file already has the same one.
+    pub(crate) fn add(&mut self, file: &str, message: String) {
+        let problem = Problem {
+            file: file.to_owned(),
+            message,
+        };
+        if !self.problems.contains(&problem) {
+            self.problems.push(problem);
+        }
+    }
+
+    /// A line per problem, and a summary.
+    pub fn report(&self) -> String {
+        let mut lines: Vec<String> = self
+            .problems
+            .iter()
+            .map(|p| format!("{}: {}", p.file, p.message))
+            .collect();
+        let checked = format!(
+            "Checked {} request{} and {} env{}",
+            self.requests,
+            if self.requests == 1 { "" } else { "s" },
+            self.envs,
+            if self.envs == 1 { "" } else { "s" }
+        );
+        lines.push(match self.problems.len() {
+            0 => format!("{}, no problems found.", checked),
+            1 => format!("{}, found 1 problem.", checked),
+            n => format!("{}, found {} problems.", checked, n),
+        });
+        lines.join("\n")
+    }
+}
+
+/// Names given to more than one file, ignoring case since not every file system tells them
+/// apart. Each is returned once, as first given.
+pub(crate) fn duplicates(names: &[String]) -> Vec<String> {
+    let mut counts: HashMap<String, (usize, &String)> = HashMap::new();
+    for name in names {
+        counts.entry(name.to_lowercase()).or_insert((0, name)).0 += 1;
+    }
+    let mut duplicates: Vec<String> = counts
+        .into_values()
+        .filter(|(count, _)| *count > 1)
+        .map(|(_, name)| name.clone())
+        .collect();
+    duplicates.sort();
+    duplicates
+}
+
+#[test]
+fn test_duplicates() {
+    let names: Vec<String> = [
+        "users/get",
+        "users/list",
+        "Users/Get",
+        "login",
+        "users/list",
+    ]
+    .map(str::to_owned)
+    .to_vec();
+    assert_eq!(duplicates(&names), vec!["users/get", "users/list"]);
+    assert!(duplicates(&names[..2]).is_empty());
+}
+
+#[test]
+fn test_validation_report() {
+    let mut validation = Validation {
+        requests: 3,
+        envs: 1,
+        problems: vec![],
+    };
+    assert_eq!(
+        validation.report(),
+        "Checked 3 requests and 1 env, no problems found."
+    );
+
+    validation.add("users/get", "Invalid URL.".to_owned());
+    validation.add("users/get", "Invalid URL.".to_owned());
+    validation.add("envs/prod", "Failed to parse.".to_owned());
+    assert!(!validation.passed());
+    assert_eq!(
+        validation.report(),
+        "users/get: Invalid URL.\nenvs/prod: Failed to parse.\nChecked 3 requests and 1 env, found 2 problems."
+    );
+}
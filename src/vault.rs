@@ -0,0 +1,342 @@
+//! Secrets encrypted with a passphrase in `secrets.enc`, so API tokens can be kept next to the
+//! requests without being committed in plain text. Each value is sealed with ChaCha20-Poly1305
+//! under a key derived from the passphrase with PBKDF2-HMAC-SHA256, both from OpenSSL, while the
+//! names stay readable, so requests can be checked and the passphrase is only asked for when a
+//! request uses a secret.
+
+use crate::{prompt, secrets::SecretStore};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openssl::hash::MessageDigest;
+use openssl::symm::{self, Cipher};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The file secrets are stored in, relative to the reqq directory.
+pub const FILE_NAME: &str = "secrets.enc";
+
+/// The environment variable the passphrase is read from, before asking for it.
+pub const PASSPHRASE_VAR: &str = "REQQ_VAULT_PASSPHRASE";
+
+/// How many PBKDF2 iterations new vaults derive their key with.
+const ITERATIONS: u32 = 200_000;
+
+/// The lengths of a key, of the nonce stored in front of each sealed value, and of the tag
+/// after it, in bytes.
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// What the passphrase check is sealed with, as additional data. Secrets use their names.
+const CHECK: &[u8] = b"reqq vault";
+
+pub struct Vault {
+    fpath: PathBuf,
+    /// The key, once it's been derived from the passphrase.
+    key: Mutex<Option<[u8; KEY_LEN]>>,
+}
+
+impl Vault {
+    pub fn new(reqq_dir: &str) -> Self {
+        Vault {
+            fpath: Path::new(reqq_dir).join(FILE_NAME),
+            key: Mutex::new(None),
+        }
+    }
+
+    fn read(&self) -> Result<Option<Value>> {
+        let json = match fs::read_to_string(&self.fpath) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let vault: Value = serde_json::from_str(&json)
+            .map_err(|e| anyhow!("Malformed vault '{}': {}", self.fpath.display(), e))?;
+        if vault["version"] != 1 {
+            return Err(anyhow!(
+                "Vault '{}' has an unsupported version.",
+                self.fpath.display()
+            ));
+        }
+        Ok(Some(vault))
+    }
+
+    fn write(&self, vault: &Value) -> Result<()> {
+        if let Some(dir) = self.fpath.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.fpath, serde_json::to_string_pretty(vault)? + "\n")?;
+        Ok(())
+    }
+
+    /// The key of a vault, derived from the passphrase the first time it's needed.
+    fn key(&self, vault: &Value) -> Result<[u8; KEY_LEN]> {
+        let mut cached = self.key.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(key) = *cached {
+            return Ok(key);
+        }
+        let malformed = || anyhow!("Malformed vault '{}'.", self.fpath.display());
+        let salt = STANDARD.decode(vault["salt"].as_str().ok_or_else(malformed)?)?;
+        let iterations = vault["iterations"]
+            .as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .ok_or_else(malformed)?;
+        let check = vault["check"].as_str().ok_or_else(malformed)?;
+
+        let key = derive_key(&passphrase(false)?, &salt, iterations)?;
+        open(&key, CHECK, check)
+            .map_err(|_| anyhow!("Wrong passphrase for vault '{}'.", self.fpath.display()))?;
+        *cached = Some(key);
+        Ok(key)
+    }
+}
+
+impl SecretStore for Vault {
+    fn names(&self) -> Result<Vec<String>> {
+        // The names aren't encrypted, so this doesn't need the passphrase.
+        Ok(match self.read()? {
+            Some(vault) => vault["secrets"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            None => vec![],
+        })
+    }
+
+    fn get(&self, names: &[String]) -> Result<Map<String, Value>> {
+        let Some(vault) = self.read()? else {
+            return Ok(Map::new());
+        };
+        let mut secrets = Map::new();
+        for name in names {
+            let Some(sealed) = vault["secrets"][name].as_str() else {
+                continue;
+            };
+            let key = self.key(&vault)?;
+            let value = open(&key, name.as_bytes(), sealed)
+                .map_err(|e| anyhow!("Couldn't decrypt secret '{}': {}", name, e))?;
+            secrets.insert(name.clone(), Value::String(String::from_utf8(value)?));
+        }
+        Ok(secrets)
+    }
+
+    /// The vault is created, with a new passphrase, if there isn't one yet.
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        let mut vault = match self.read()? {
+            Some(vault) => vault,
+            None => {
+                let passphrase = passphrase(true)?;
+                let mut salt = [0u8; 16];
+                openssl::rand::rand_bytes(&mut salt)?;
+                let key = derive_key(&passphrase, &salt, ITERATIONS)?;
+                *self.key.lock().unwrap_or_else(|e| e.into_inner()) = Some(key);
+                json!({
+                    "version": 1,
+                    "salt": STANDARD.encode(salt),
+                    "iterations": ITERATIONS,
+                    "check": seal(&key, CHECK, b"")?,
+                    "secrets": {},
+                })
+            }
+        };
+        let key = self.key(&vault)?;
+        let sealed = seal(&key, name.as_bytes(), value.as_bytes())?;
+        vault["secrets"]
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("Malformed vault '{}'.", self.fpath.display()))?
+            .insert(name.to_owned(), Value::String(sealed));
+        self.write(&vault)
+    }
+
+    fn remove(&self, name: &str) -> Result<bool> {
+        let Some(mut vault) = self.read()? else {
+            return Ok(false);
+        };
+        let removed = vault["secrets"]
+            .as_object_mut()
+            .and_then(|secrets| secrets.remove(name))
+            .is_some();
+        if removed {
+            self.write(&vault)?;
+        }
+        Ok(removed)
+    }
+}
+
+/// The passphrase from `REQQ_VAULT_PASSPHRASE`, or asked for on the terminal. A new one is
+/// asked for twice, so a typo doesn't lock the secrets away.
+fn passphrase(new: bool) -> Result<String> {
+    let passphrase = match std::env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) => passphrase,
+        Err(_) => {
+            let passphrase = prompt::hidden(if new {
+                "New vault passphrase"
+            } else {
+                "Vault passphrase"
+            })?;
+            if new && prompt::hidden("New vault passphrase again")? != passphrase {
+                return Err(anyhow!("The passphrases don't match."));
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        return Err(anyhow!("The vault passphrase can't be empty."));
+    }
+    Ok(passphrase)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Result<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    openssl::pkcs5::pbkdf2_hmac(
+        passphrase.as_bytes(),
+        salt,
+        iterations as usize,
+        MessageDigest::sha256(),
+        &mut key,
+    )?;
+    Ok(key)
+}
+
+/// Seals a value under a fresh nonce, stored in front of it, with the tag after it.
+fn seal(key: &[u8; KEY_LEN], aad: &[u8], value: &[u8]) -> Result<String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    openssl::rand::rand_bytes(&mut nonce)?;
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = symm::encrypt_aead(
+        Cipher::chacha20_poly1305(),
+        key,
+        Some(&nonce),
+        aad,
+        value,
+        &mut tag,
+    )?;
+    Ok(STANDARD.encode([nonce.as_slice(), &ciphertext, &tag].concat()))
+}
+
+/// Opens a sealed value, failing if it was tampered with, or sealed with another key or
+/// additional data.
+fn open(key: &[u8; KEY_LEN], aad: &[u8], sealed: &str) -> Result<Vec<u8>> {
+    let sealed = STANDARD.decode(sealed)?;
+    if sealed.len() < NONCE_LEN + TAG_LEN {
+        return Err(anyhow!("too short"));
+    }
+    let (nonce, sealed) = sealed.split_at(NONCE_LEN);
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    symm::decrypt_aead(
+        Cipher::chacha20_poly1305(),
+        key,
+        Some(nonce),
+        aad,
+        ciphertext,
+        tag,
+    )
+    .map_err(|_| anyhow!("wrong key or corrupted ciphertext"))
+}
+
+#[test]
+fn test_vault() {
+    let dir = std::env::temp_dir().join(format!("reqq-vault-{}", std::process::id()));
+    let vault = Vault::new(dir.to_str().unwrap());
+    assert!(vault.names().unwrap().is_empty());
+
+    // A known key saves deriving one from a passphrase.
+    let key = [7u8; KEY_LEN];
+    *vault.key.lock().unwrap() = Some(key);
+    vault
+        .write(&json!({
+            "version": 1,
+            "salt": "",
+            "iterations": 1,
+            "check": seal(&key, CHECK, b"").unwrap(),
+            "secrets": {},
+        }))
+        .unwrap();
+    vault.set("token", "abc123").unwrap();
+    vault.set("password", "hunter2").unwrap();
+    vault.set("token", "def456").unwrap();
+    assert_eq!(vault.names().unwrap(), vec!["password", "token"]);
+    assert!(!fs::read_to_string(dir.join(FILE_NAME))
+        .unwrap()
+        .contains("def456"));
+
+    let secrets = vault
+        .get(&["token".to_owned(), "missing".to_owned()])
+        .unwrap();
+    assert_eq!(secrets.len(), 1);
+    assert_eq!(secrets["token"], "def456");
+
+    // Values can't be swapped between names.
+    let mut stored = vault.read().unwrap().unwrap();
+    stored["secrets"]["token"] = stored["secrets"]["password"].clone();
+    vault.write(&stored).unwrap();
+    assert!(vault.get(&["token".to_owned()]).is_err());
+
+    assert!(vault.remove("token").unwrap());
+    assert!(!vault.remove("token").unwrap());
+    assert_eq!(vault.names().unwrap(), vec!["password"]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_vault_empty_passphrase() {
+    // An empty passphrase is refused when it comes from the environment too.
+    let dir = std::env::temp_dir().join(format!("reqq-vault-empty-{}", std::process::id()));
+    std::env::set_var(PASSPHRASE_VAR, "");
+    let vault = Vault::new(dir.to_str().unwrap());
+    assert_eq!(
+        vault.set("token", "abc123").unwrap_err().to_string(),
+        "The vault passphrase can't be empty."
+    );
+    assert!(!dir.join(FILE_NAME).exists());
+}
+
+#[test]
+fn test_vault_crypto() {
+    let from_hex = |hex: &str| -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    };
+
+    // RFC 8439, section 2.8.2, stored as the vault stores values.
+    let key: [u8; KEY_LEN] = std::array::from_fn(|i| 0x80 + i as u8);
+    let nonce = from_hex("070000004041424344454647");
+    let aad = from_hex("50515253c0c1c2c3c4c5c6c7");
+    let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = symm::encrypt_aead(
+        Cipher::chacha20_poly1305(),
+        &key,
+        Some(&nonce),
+        &aad,
+        plaintext,
+        &mut tag,
+    )
+    .unwrap();
+    assert_eq!(
+        ciphertext[..16],
+        from_hex("d31a8d34648e60db7b86afbc53ef7ec2")
+    );
+    assert_eq!(tag.to_vec(), from_hex("1ae10b594f09e26a7e902ecbd0600691"));
+    let sealed = STANDARD.encode([nonce.as_slice(), &ciphertext, &tag].concat());
+    assert_eq!(open(&key, &aad, &sealed).unwrap(), plaintext);
+    assert!(open(&key, b"other", &sealed).is_err());
+    assert!(open(&key, &aad, &sealed[..16]).is_err());
+    assert_eq!(
+        open(&key, b"", &seal(&key, b"", b"hunter2").unwrap()).unwrap(),
+        b"hunter2"
+    );
+
+    // RFC 7914, section 11.
+    assert_eq!(
+        derive_key("password", b"salt", 2).unwrap().to_vec(),
+        from_hex("ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43")
+    );
+}
@@ -0,0 +1,62 @@
+//! Noticing when files change, by polling their modification times and sizes.
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// The state of some files at one point in time.
+pub struct Snapshot {
+    files: Vec<(PathBuf, Option<(SystemTime, u64)>)>,
+}
+
+impl Snapshot {
+    /// Records the modification times and sizes of files. Missing files are recorded too, so
+    /// creating them counts as a change.
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let state = stat(&path);
+                (path, state)
+            })
+            .collect();
+        Snapshot { files }
+    }
+
+    /// Whether any of the files were modified, created or removed since the snapshot.
+    pub fn changed(&self) -> bool {
+        self.files.iter().any(|(path, state)| stat(path) != *state)
+    }
+
+    /// Blocks until one of the files changes, checking every `interval`.
+    pub fn wait(&self, interval: Duration) {
+        while !self.changed() {
+            thread::sleep(interval);
+        }
+    }
+}
+
+fn stat(path: &PathBuf) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[test]
+fn test_snapshot_changed() {
+    let dir = std::env::temp_dir().join(format!("reqq-watch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (file, missing) = (dir.join("get.reqq"), dir.join("body.json"));
+    fs::write(&file, "GET https://example.com").unwrap();
+
+    let snapshot = Snapshot::new(vec![file.clone(), missing.clone()]);
+    assert!(!snapshot.changed());
+    fs::write(&file, "GET https://example.com/users").unwrap();
+    assert!(snapshot.changed());
+
+    let snapshot = Snapshot::new(vec![file.clone(), missing.clone()]);
+    fs::write(&missing, "{}").unwrap();
+    assert!(snapshot.changed());
+
+    fs::remove_dir_all(dir).unwrap();
+}
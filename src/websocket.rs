@@ -0,0 +1,413 @@
+//! WebSocket requests, whose first line is `WS ws://...` or `WS wss://...`. The body is sent as
+//! the first message, and the messages that come back are printed as they arrive.
+
+use crate::{
+    auth::Authorize, http1, request::Request, response::Response, sigv4::AwsCredentials, time,
+};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openssl::{rand::rand_bytes, sha::sha1};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{StatusCode, Version};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use url::Url;
+
+/// The method of WebSocket request files.
+pub const METHOD: &str = "WS";
+
+/// Appended to the handshake key to get the `Sec-WebSocket-Accept` the server must answer with.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+/// The close code of a connection that's done.
+const NORMAL_CLOSURE: u16 = 1000;
+
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+/// One frame of a message, or a control frame.
+#[derive(Clone, Debug, PartialEq)]
+struct Frame {
+    /// Whether this is the last frame of its message.
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    fn new(opcode: u8, payload: Vec<u8>) -> Self {
+        Frame {
+            fin: true,
+            opcode,
+            payload,
+        }
+    }
+
+    /// The frame as a client sends it, with its payload masked by `mask`.
+    fn encode(&self, mask: [u8; 4]) -> Vec<u8> {
+        let mut out = vec![u8::from(self.fin) << 7 | self.opcode];
+        let len = self.payload.len();
+        match len {
+            0..=125 => out.push(0x80 | len as u8),
+            126..=0xffff => {
+                out.push(0x80 | 126);
+                out.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                out.push(0x80 | 127);
+                out.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        out.extend_from_slice(&mask);
+        out.extend(
+            self.payload
+                .iter()
+                .enumerate()
+                .map(|(i, b)| b ^ mask[i % 4]),
+        );
+        out
+    }
+
+    /// Reads a frame, unmasking its payload if the server masked it.
+    fn read(r: &mut impl Read) -> io::Result<Frame> {
+        let mut head = [0u8; 2];
+        r.read_exact(&mut head)?;
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                r.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as u64
+            }
+            127 => {
+                let mut len = [0u8; 8];
+                r.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => len as u64,
+        };
+        let mut mask = None;
+        if head[1] & 0x80 != 0 {
+            let mut key = [0u8; 4];
+            r.read_exact(&mut key)?;
+            mask = Some(key);
+        }
+        let mut payload = vec![];
+        r.take(len).read_to_end(&mut payload)?;
+        if payload.len() as u64 != len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        if let Some(mask) = mask {
+            payload
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, b)| *b ^= mask[i % 4]);
+        }
+        Ok(Frame {
+            fin: head[0] & 0x80 != 0,
+            opcode: head[0] & 0x0f,
+            payload,
+        })
+    }
+}
+
+/// The URL and headers of the opening handshake, which credentials are added to like they are
+/// to reqwest's request builders.
+struct Handshake {
+    url: Url,
+    headers: Vec<(String, String)>,
+}
+
+impl Authorize for Handshake {
+    fn basic(self, user: &str, pass: &str) -> Self {
+        let credentials = STANDARD.encode(format!("{}:{}", user, pass));
+        self.add_header("Authorization", &format!("Basic {}", credentials))
+    }
+
+    fn bearer(self, token: &str) -> Self {
+        self.add_header("Authorization", &format!("Bearer {}", token))
+    }
+
+    fn add_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    fn add_query(mut self, name: &str, value: &str) -> Self {
+        self.url.query_pairs_mut().append_pair(name, value);
+        self
+    }
+
+    fn sign_aws(self, _: &AwsCredentials, _: u64) -> Result<Self> {
+        Err(anyhow!(
+            "AWS signatures aren't supported for WebSocket requests."
+        ))
+    }
+}
+
+impl Handshake {
+    /// The HTTP request asking the server to switch to the WebSocket protocol.
+    fn to_http(&self, key: &str) -> String {
+        let mut target = self.url.path().to_owned();
+        if let Some(query) = self.url.query() {
+            target = format!("{}?{}", target, query);
+        }
+        let host = match self.url.port() {
+            Some(port) => format!("{}:{}", self.url.host_str().unwrap_or_default(), port),
+            None => self.url.host_str().unwrap_or_default().to_owned(),
+        };
+        let mut lines = vec![
+            format!("GET {} HTTP/1.1", target),
+            format!("Host: {}", host),
+            "Upgrade: websocket".to_owned(),
+            "Connection: Upgrade".to_owned(),
+            format!("Sec-WebSocket-Key: {}", key),
+            "Sec-WebSocket-Version: 13".to_owned(),
+        ];
+        lines.extend(
+            self.headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, value)),
+        );
+        format!("{}\r\n\r\n", lines.join("\r\n"))
+    }
+}
+
+/// Opens the WebSocket of a parsed `WS` request, sends its body as the first message, and
+/// prints each message that comes back until `count` have, or the server closes the
+/// connection. Returns the handshake response, without a body.
+pub fn open(req: &Request, count: Option<usize>) -> Result<Response> {
+    let inner = req
+        .inner()
+        .ok_or_else(|| anyhow!("Request has not been parsed."))?;
+    let mut handshake = Handshake {
+        url: inner.url().clone(),
+        headers: inner
+            .headers()
+            .iter()
+            .map(|(name, value)| Ok((name.to_string(), value.to_str()?.to_owned())))
+            .collect::<Result<_>>()?,
+    };
+    if let Some(auth) = req.auth() {
+        handshake = auth.apply(handshake)?;
+    }
+
+    let mut stream = BufReader::new(connect(req, &handshake.url)?);
+    let mut nonce = [0u8; 16];
+    rand_bytes(&mut nonce)?;
+    let key = STANDARD.encode(nonce);
+    stream
+        .get_mut()
+        .write_all(handshake.to_http(&key).as_bytes())?;
+    let resp = read_handshake(&mut stream, &key)?;
+
+    if let Some(body) = inner.body() {
+        let bytes = body.encode()?.bytes;
+        let opcode = if std::str::from_utf8(&bytes).is_ok() {
+            TEXT
+        } else {
+            BINARY
+        };
+        send(stream.get_mut(), Frame::new(opcode, bytes))?;
+    }
+
+    let mut stdout = io::stdout().lock();
+    let mut received = 0;
+    let mut message = Frame::new(TEXT, vec![]);
+    while count.is_none_or(|count| received < count) {
+        let frame = match Frame::read(&mut stream) {
+            Ok(frame) => frame,
+            // Servers don't always send a close frame before closing the connection.
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(resp),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                let limit = req.timeout().map(time::format_duration).unwrap_or_default();
+                return Err(anyhow!("Timed out waiting for a message after {}.", limit));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match frame.opcode {
+            PING => send(stream.get_mut(), Frame::new(PONG, frame.payload))?,
+            PONG => {}
+            CLOSE => {
+                send(stream.get_mut(), Frame::new(CLOSE, frame.payload))?;
+                return Ok(resp);
+            }
+            CONTINUATION => message.payload.extend(frame.payload),
+            TEXT | BINARY => message = frame.clone(),
+            opcode => {
+                return Err(anyhow!(
+                    "Received a frame with an unknown opcode, {:#x}.",
+                    opcode
+                ))
+            }
+        }
+        if frame.fin && matches!(frame.opcode, CONTINUATION | TEXT | BINARY) {
+            match message.opcode {
+                TEXT => writeln!(stdout, "{}", String::from_utf8_lossy(&message.payload))?,
+                _ => writeln!(stdout, "[binary message, {} bytes]", message.payload.len())?,
+            }
+            stdout.flush()?;
+            received += 1;
+        }
+    }
+    send(
+        stream.get_mut(),
+        Frame::new(CLOSE, NORMAL_CLOSURE.to_be_bytes().to_vec()),
+    )?;
+    Ok(resp)
+}
+
+fn connect(req: &Request, url: &Url) -> Result<Box<dyn Stream>> {
+    let secure = match url.scheme() {
+        "ws" => false,
+        "wss" => true,
+        scheme => {
+            return Err(anyhow!(
+                "WebSocket URLs start with ws:// or wss://, not {}://.",
+                scheme
+            ))
+        }
+    };
+    let host = http1::connect_host(url)?;
+    let port = url
+        .port_or_known_default()
+        .unwrap_or(if secure { 443 } else { 80 });
+    let opts = req.client_opts();
+
+    let connect_error = |e: io::Error| anyhow!("Couldn't connect to {}:{}: {}", host, port, e);
+    let tcp = match opts.connect_timeout {
+        Some(timeout) => {
+            let addr = (host.as_str(), port)
+                .to_socket_addrs()
+                .map_err(connect_error)?
+                .next()
+                .ok_or_else(|| anyhow!("Couldn't resolve {}.", host))?;
+            TcpStream::connect_timeout(&addr, timeout).map_err(connect_error)?
+        }
+        None => TcpStream::connect((host.as_str(), port)).map_err(connect_error)?,
+    };
+    tcp.set_read_timeout(req.timeout())?;
+
+    if !secure {
+        return Ok(Box::new(tcp));
+    }
+    let tls = opts
+        .tls_connector()?
+        .connect(&host, tcp)
+        .map_err(|e| anyhow!("TLS handshake with {} failed: {}", host, e))?;
+    Ok(Box::new(tls))
+}
+
+/// Reads the server's answer to the handshake, which must switch protocols and prove it read
+/// the key.
+fn read_handshake(stream: &mut impl BufRead, key: &str) -> Result<Response> {
+    let mut status_line = String::new();
+    stream.read_line(&mut status_line)?;
+    let status: StatusCode = status_line
+        .split(' ')
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| anyhow!("Invalid handshake response: '{}'.", status_line.trim_end()))?;
+
+    let mut headers = HeaderMap::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.trim_end().split_once(':') {
+            headers.append(
+                HeaderName::from_bytes(name.trim().as_bytes())?,
+                HeaderValue::from_str(value.trim())?,
+            );
+        }
+    }
+
+    if status != StatusCode::SWITCHING_PROTOCOLS {
+        return Err(anyhow!(
+            "The server answered {} instead of opening a WebSocket.",
+            status
+        ));
+    }
+    let accept = headers
+        .get("sec-websocket-accept")
+        .and_then(|v| v.to_str().ok());
+    if accept != Some(accept_key(key).as_str()) {
+        return Err(anyhow!(
+            "The server's Sec-WebSocket-Accept header doesn't match the key sent."
+        ));
+    }
+    Ok(Response {
+        status,
+        version: Version::HTTP_11,
+        headers,
+        body: String::new(),
+        binary: None,
+    })
+}
+
+fn send(stream: &mut impl Write, frame: Frame) -> Result<()> {
+    let mut mask = [0u8; 4];
+    rand_bytes(&mut mask)?;
+    stream.write_all(&frame.encode(mask))?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// The `Sec-WebSocket-Accept` a server answers a handshake key with.
+fn accept_key(key: &str) -> String {
+    STANDARD.encode(sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+#[test]
+fn test_accept_key() {
+    // The example from RFC 6455.
+    assert_eq!(
+        accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+}
+
+#[test]
+fn test_frame_round_trip() {
+    for len in [0, 5, 125, 126, 70_000] {
+        let frame = Frame::new(TEXT, vec![b'a'; len]);
+        let encoded = frame.encode([1, 2, 3, 4]);
+        assert_eq!(encoded[0], 0x81);
+        assert_eq!(Frame::read(&mut encoded.as_slice()).unwrap(), frame);
+    }
+
+    // Servers send frames unmasked.
+    let unmasked = [0x01, 0x03, b'a', b'b', b'c'];
+    let frame = Frame::read(&mut unmasked.as_slice()).unwrap();
+    assert_eq!(
+        frame,
+        Frame {
+            fin: false,
+            opcode: TEXT,
+            payload: b"abc".to_vec()
+        }
+    );
+    assert!(Frame::read(&mut &unmasked[..4]).is_err());
+}
+
+#[test]
+fn test_handshake_to_http() {
+    let handshake = Handshake {
+        url: Url::parse("wss://example.com:8443/socket?room=1").unwrap(),
+        headers: vec![],
+    }
+    .bearer("abc");
+    assert_eq!(
+        handshake.to_http("key"),
+        "GET /socket?room=1 HTTP/1.1\r\nHost: example.com:8443\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: key\r\nSec-WebSocket-Version: 13\r\nAuthorization: Bearer abc\r\n\r\n"
+    );
+}
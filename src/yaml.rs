@@ -0,0 +1,743 @@
+//! A parser for the subset of YAML that config-like files use: block mappings and sequences,
+//! flow collections (`[a, b]`, `{a: 1}`), quoted and plain scalars, block scalars (`|`, `>`)
+//! and comments. Anchors, aliases, tags and multi-document streams aren't supported, and are
+//! errors rather than strings, as are tabs in indentation. Values are written back in block
+//! style.
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Number, Value};
+
+/// Parses a YAML document into a JSON value.
+pub fn parse(input: &str) -> Result<Value> {
+    let lines: Vec<Line> = input
+        .lines()
+        .enumerate()
+        .map(|(i, raw)| Line::new(i + 1, raw))
+        .collect();
+    let mut parser = Parser { lines, pos: 0 };
+
+    parser.skip_insignificant();
+    if parser.pos < parser.lines.len() && parser.lines[parser.pos].text == "---" {
+        parser.pos += 1;
+    }
+
+    let value = parser.parse_node(0)?;
+
+    parser.skip_insignificant();
+    match parser.lines.get(parser.pos) {
+        Some(line) if line.text != "..." => Err(anyhow!(
+            "Unexpected content on line {}: '{}'",
+            line.number,
+            line.text
+        )),
+        _ => Ok(value),
+    }
+}
+
+#[derive(Clone)]
+struct Line<'a> {
+    number: usize,
+    raw: &'a str,
+    indent: usize,
+    /// The line without indentation, trailing whitespace or comments.
+    text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    fn new(number: usize, raw: &'a str) -> Self {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        let indent = raw.len() - raw.trim_start_matches(' ').len();
+        let text = strip_comment(&raw[indent..]).trim_end();
+        Line {
+            number,
+            raw,
+            indent,
+            text,
+        }
+    }
+}
+
+struct Parser<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_insignificant(&mut self) {
+        while self.pos < self.lines.len() && self.lines[self.pos].text.is_empty() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<Line<'a>>> {
+        self.skip_insignificant();
+        match self.lines.get(self.pos) {
+            Some(line) if line.raw[line.indent..].starts_with('\t') => Err(anyhow!(
+                "Line {} is indented with a tab, only spaces can indent.",
+                line.number
+            )),
+            line => Ok(line.cloned()),
+        }
+    }
+
+    /// Parses whatever node starts at the next line, if it's indented at least `min_indent`.
+    fn parse_node(&mut self, min_indent: usize) -> Result<Value> {
+        let line = match self.peek()? {
+            Some(line) if line.indent >= min_indent => line,
+            _ => return Ok(Value::Null),
+        };
+
+        if is_sequence_item(line.text) {
+            self.parse_sequence(line.indent)
+        } else if split_mapping_entry(line.text).is_some() {
+            self.parse_mapping(line.indent)
+        } else {
+            self.pos += 1;
+            parse_inline(line.text).map_err(|e| anyhow!("Line {}: {}", line.number, e))
+        }
+    }
+
+    fn parse_sequence(&mut self, indent: usize) -> Result<Value> {
+        let mut items = vec![];
+
+        while let Some(line) = self.peek()? {
+            if line.indent != indent || !is_sequence_item(line.text) {
+                break;
+            }
+
+            let rest = line.text[1..].trim_start();
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(self.parse_node(indent + 1)?);
+            } else if split_mapping_entry(rest).is_some() || is_sequence_item(rest) {
+                // The item's content starts on this line, so treat it as if it were on a line
+                // of its own, indented to where it starts.
+                let offset = line.text.len() - rest.len();
+                self.lines[self.pos] = Line {
+                    indent: indent + offset,
+                    text: rest,
+                    ..line
+                };
+                items.push(self.parse_node(indent + offset)?);
+            } else if is_block_scalar(rest) {
+                self.pos += 1;
+                items.push(self.parse_block_scalar(rest, indent)?);
+            } else {
+                self.pos += 1;
+                items.push(parse_inline(rest).map_err(|e| anyhow!("Line {}: {}", line.number, e))?);
+            }
+        }
+
+        Ok(Value::Array(items))
+    }
+
+    fn parse_mapping(&mut self, indent: usize) -> Result<Value> {
+        let mut map = Map::new();
+
+        while let Some(line) = self.peek()? {
+            if line.indent < indent {
+                break;
+            }
+            if line.indent > indent {
+                return Err(anyhow!("Unexpected indentation on line {}.", line.number));
+            }
+
+            let (key, value) = match split_mapping_entry(line.text) {
+                Some(entry) => entry,
+                None => break,
+            };
+            let key = parse_key(key).map_err(|e| anyhow!("Line {}: {}", line.number, e))?;
+            self.pos += 1;
+
+            let value = if value.is_empty() {
+                match self.peek()? {
+                    Some(next) if next.indent > indent => self.parse_node(indent + 1)?,
+                    Some(next) if next.indent == indent && is_sequence_item(next.text) => {
+                        self.parse_sequence(indent)?
+                    }
+                    _ => Value::Null,
+                }
+            } else if is_block_scalar(value) {
+                self.parse_block_scalar(value, indent)?
+            } else {
+                parse_inline(value).map_err(|e| anyhow!("Line {}: {}", line.number, e))?
+            };
+
+            map.insert(key, value);
+        }
+
+        Ok(Value::Object(map))
+    }
+
+    /// Parses a `|` (literal) or `>` (folded) block scalar whose content is on the following
+    /// lines, indented more than `parent_indent`.
+    fn parse_block_scalar(&mut self, header: &str, parent_indent: usize) -> Result<Value> {
+        let folded = header.starts_with('>');
+        let chomping = header[1..].trim();
+
+        let mut content_lines: Vec<&str> = vec![];
+        let mut content_indent = None;
+        while let Some(line) = self.lines.get(self.pos) {
+            let blank = line.raw.trim().is_empty();
+            if !blank && line.indent <= parent_indent {
+                break;
+            }
+            if !blank && content_indent.is_none() {
+                content_indent = Some(line.indent);
+            }
+            content_lines.push(line.raw);
+            self.pos += 1;
+        }
+
+        let content_indent = content_indent.unwrap_or(parent_indent + 1);
+        let lines: Vec<&str> = content_lines
+            .iter()
+            .map(|l| l.get(content_indent..).unwrap_or(""))
+            .collect();
+
+        let mut text = String::new();
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                let previous = lines[i - 1];
+                if !folded
+                    || previous.is_empty()
+                    || previous.starts_with(' ')
+                    || line.starts_with(' ')
+                {
+                    text.push('\n');
+                } else if !line.is_empty() {
+                    // Folding joins lines with a space, and drops the line break before a blank
+                    // line.
+                    text.push(' ');
+                }
+            }
+            text.push_str(line);
+        }
+
+        let body = text.trim_end_matches('\n');
+        let trailing = text.len() - body.len();
+        let text = match chomping {
+            "-" => body.to_owned(),
+            "+" => format!("{}\n{}", body, "\n".repeat(trailing)),
+            _ if body.is_empty() => String::new(),
+            _ => format!("{}\n", body),
+        };
+
+        Ok(Value::String(text))
+    }
+}
+
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+fn is_block_scalar(value: &str) -> bool {
+    matches!(value, "|" | "|-" | "|+" | ">" | ">-" | ">+")
+}
+
+/// Splits `key: value` into its key and (possibly empty) value, if the line is a mapping entry.
+fn split_mapping_entry(text: &str) -> Option<(&str, &str)> {
+    if text.starts_with('[') || text.starts_with('{') || is_sequence_item(text) {
+        return None;
+    }
+
+    let key_end = match text.chars().next()? {
+        quote @ ('"' | '\'') => quoted_len(text, quote)?,
+        _ => 0,
+    };
+
+    let colon = text[key_end..]
+        .match_indices(':')
+        .map(|(i, _)| key_end + i)
+        .find(|&i| text[i + 1..].is_empty() || text[i + 1..].starts_with(' '))?;
+
+    Some((text[..colon].trim(), text[colon + 1..].trim()))
+}
+
+/// The length of the quoted string at the start of `text`, including both quotes.
+fn quoted_len(text: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => {
+                // Single quotes are escaped by doubling them.
+                if quote == '\'' && text[i + 1..].starts_with('\'') {
+                    escaped = true;
+                    continue;
+                }
+                return Some(i + 1);
+            }
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Removes a trailing `# comment` that isn't inside a quoted string.
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '#') if previous == ' ' || previous == '\t' => return &text[..i],
+            (None, '"' | '\'')
+                if previous == ' '
+                    || previous == '['
+                    || previous == '{'
+                    || previous == ','
+                    || i == 0 =>
+            {
+                quote = Some(c)
+            }
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+        previous = c;
+    }
+    text
+}
+
+fn parse_key(key: &str) -> Result<String> {
+    match parse_inline(key)? {
+        Value::String(s) => Ok(s),
+        Value::Null => Ok(String::new()),
+        other => Ok(other.to_string()),
+    }
+}
+
+/// Parses a value written on a single line: a flow collection, a quoted or a plain scalar.
+fn parse_inline(text: &str) -> Result<Value> {
+    let mut flow = Flow { text, pos: 0 };
+    let value = flow.parse_value(false)?;
+    flow.skip_whitespace();
+    if flow.pos != text.len() {
+        return Err(anyhow!("Unexpected '{}'", &text[flow.pos..]));
+    }
+    Ok(value)
+}
+
+struct Flow<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Flow<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(anyhow!("Expected '{}'", c))
+        }
+    }
+
+    /// Parses a value. Inside flow collections, plain scalars end at `,`, `]` and `}`.
+    fn parse_value(&mut self, in_flow: bool) -> Result<Value> {
+        self.skip_whitespace();
+        match self.rest().chars().next() {
+            Some('[') => self.parse_sequence(),
+            Some('{') => self.parse_mapping(),
+            Some(quote @ ('"' | '\'')) => self.parse_quoted(quote).map(Value::String),
+            _ => {
+                let rest = self.rest();
+                let end = if in_flow {
+                    rest.find([',', ']', '}']).unwrap_or(rest.len())
+                } else {
+                    rest.len()
+                };
+                self.pos += end;
+                let plain = rest[..end].trim();
+                check_plain(plain, in_flow)?;
+                Ok(resolve_plain(plain))
+            }
+        }
+    }
+
+    fn parse_quoted(&mut self, quote: char) -> Result<String> {
+        let rest = self.rest();
+        let len = quoted_len(rest, quote).ok_or_else(|| anyhow!("Unterminated string"))?;
+        self.pos += len;
+
+        let inner = &rest[1..len - 1];
+        if quote == '\'' {
+            return Ok(inner.replace("''", "'"));
+        }
+        serde_json::from_str(&rest[..len]).map_err(|e| anyhow!("Invalid string: {}", e))
+    }
+
+    fn parse_sequence(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with(']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.parse_value(true)?);
+            self.skip_whitespace();
+            if self.rest().starts_with(',') {
+                self.pos += 1;
+            } else {
+                self.expect(']')?;
+                return Ok(Value::Array(items));
+            }
+        }
+    }
+
+    fn parse_mapping(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut map = Map::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with('}') {
+                self.pos += 1;
+                return Ok(Value::Object(map));
+            }
+
+            let key = match self.rest().chars().next() {
+                Some(quote @ ('"' | '\'')) => self.parse_quoted(quote)?,
+                _ => {
+                    let rest = self.rest();
+                    let end = rest.find(':').ok_or_else(|| anyhow!("Expected ':'"))?;
+                    self.pos += end;
+                    rest[..end].trim().to_owned()
+                }
+            };
+            self.expect(':')?;
+            let value = self.parse_value(true)?;
+            map.insert(key, value);
+
+            self.skip_whitespace();
+            if self.rest().starts_with(',') {
+                self.pos += 1;
+            } else {
+                self.expect('}')?;
+                return Ok(Value::Object(map));
+            }
+        }
+    }
+}
+
+/// Fails on what YAML reads differently from a plain scalar: anchors, aliases and tags, which
+/// aren't supported, and outside flow collections, a `key: value` nested on the same line.
+fn check_plain(text: &str, in_flow: bool) -> Result<()> {
+    let kind = match text.chars().next() {
+        Some('&') => "Anchors",
+        Some('*') => "Aliases",
+        Some('!') => "Tags",
+        _ if !in_flow && (text.contains(": ") || text.ends_with(':')) => {
+            return Err(anyhow!(
+                "A mapping can't start on the line of its key: '{}'",
+                text
+            ))
+        }
+        _ => return Ok(()),
+    };
+    let node = text.split_whitespace().next().unwrap_or(text);
+    Err(anyhow!("{} aren't supported: '{}'", kind, node))
+}
+
+/// Resolves a plain scalar to null, a boolean, a number or a string.
+fn resolve_plain(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => Value::Null,
+        "true" | "True" | "TRUE" => Value::Bool(true),
+        "false" | "False" | "FALSE" => Value::Bool(false),
+        _ => {
+            if let Ok(i) = text.parse::<i64>() {
+                return Value::Number(i.into());
+            }
+            let looks_numeric = text
+                .trim_start_matches(['-', '+'])
+                .starts_with(|c: char| c.is_ascii_digit() || c == '.');
+            match text
+                .parse::<f64>()
+                .ok()
+                .filter(|_| looks_numeric)
+                .and_then(Number::from_f64)
+            {
+                Some(n) => Value::Number(n),
+                None => Value::String(text.to_owned()),
+            }
+        }
+    }
+}
+
+/// Writes a JSON value as a YAML document, in block style. Strings are quoted when they'd
+/// read as something else, and multi-line strings become `|` block scalars.
+pub fn to_string(value: &Value) -> String {
+    let lines = match value {
+        Value::Array(items) if !items.is_empty() => block_lines(value),
+        Value::Object(map) if !map.is_empty() => block_lines(value),
+        _ => scalar_lines(value),
+    };
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// The lines of a non-empty sequence or mapping, without indentation.
+fn block_lines(value: &Value) -> Vec<String> {
+    // Nested lines are indented under the line that introduces them, except for blank lines
+    // in block scalars.
+    let indent = |line: &String| {
+        if line.is_empty() {
+            String::new()
+        } else {
+            format!("  {}", line)
+        }
+    };
+    let mut lines = vec![];
+    let mut push = |first: String, nested: Vec<String>| {
+        lines.push(first);
+        lines.extend(nested.iter().map(indent));
+    };
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                let mut nested = match item {
+                    Value::Array(a) if !a.is_empty() => block_lines(item),
+                    Value::Object(m) if !m.is_empty() => block_lines(item),
+                    _ => scalar_lines(item),
+                };
+                let first = format!("- {}", nested.remove(0));
+                push(first, nested);
+            }
+        }
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = quote_if_needed(key);
+                match value {
+                    Value::Array(a) if !a.is_empty() => {
+                        push(format!("{}:", key), block_lines(value))
+                    }
+                    Value::Object(m) if !m.is_empty() => {
+                        push(format!("{}:", key), block_lines(value))
+                    }
+                    _ => {
+                        let mut nested = scalar_lines(value);
+                        let first = format!("{}: {}", key, nested.remove(0));
+                        push(first, nested);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    lines
+}
+
+/// A scalar or empty collection on one line, or a block scalar's header and content lines.
+fn scalar_lines(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s)
+            if s.contains('\n') && !s.contains('\r') && !s.starts_with([' ', '\t', '\n']) =>
+        {
+            let body = s.strip_suffix('\n').unwrap_or(s);
+            let chomping = match (s.ends_with('\n'), body.ends_with('\n')) {
+                (false, _) => "-",
+                (true, false) => "",
+                (true, true) => "+",
+            };
+            let mut lines = vec![format!("|{}", chomping)];
+            lines.extend(body.split('\n').map(str::to_owned));
+            lines
+        }
+        Value::String(s) => vec![quote_if_needed(s)],
+        Value::Array(_) => vec!["[]".to_owned()],
+        Value::Object(_) => vec!["{}".to_owned()],
+        other => vec![other.to_string()],
+    }
+}
+
+/// Leaves strings that read back as themselves plain, and double-quotes the rest, which JSON
+/// escaping does the YAML way.
+fn quote_if_needed(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.trim() == s
+        && !s.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        && !s.ends_with(':')
+        && !s.contains(": ")
+        && !s.contains(" #")
+        && !s.chars().any(char::is_control)
+        && resolve_plain(s) == Value::String(s.to_owned());
+    if plain {
+        s.to_owned()
+    } else {
+        Value::String(s.to_owned()).to_string()
+    }
+}
+
+#[test]
+fn test_yaml_mapping() {
+    let yaml = "
+# A comment.
+baseUrl: https://example.com  # trailing comment
+retries: 3
+ratio: 0.5
+debug: false
+empty:
+quoted: \"a # not a comment\"
+single: 'it''s'
+nested:
+  token: abc
+  deeper:
+    x: -1
+";
+    let expected = serde_json::json!({
+        "baseUrl": "https://example.com",
+        "retries": 3,
+        "ratio": 0.5,
+        "debug": false,
+        "empty": null,
+        "quoted": "a # not a comment",
+        "single": "it's",
+        "nested": { "token": "abc", "deeper": { "x": -1 } },
+    });
+    assert_eq!(parse(yaml).unwrap(), expected);
+}
+
+#[test]
+fn test_yaml_sequences() {
+    let yaml = "---
+steps:
+  - name: login
+    request: auth/login
+  - name: me
+    request: users/me
+tags:
+- a
+- b
+flow: [1, two, {three: 3}]
+inline: {a: 1, 'b': [x, y]}
+";
+    let expected = serde_json::json!({
+        "steps": [
+            { "name": "login", "request": "auth/login" },
+            { "name": "me", "request": "users/me" },
+        ],
+        "tags": ["a", "b"],
+        "flow": [1, "two", { "three": 3 }],
+        "inline": { "a": 1, "b": ["x", "y"] },
+    });
+    assert_eq!(parse(yaml).unwrap(), expected);
+}
+
+#[test]
+fn test_yaml_block_scalars() {
+    let yaml = "literal: |
+  line one
+    indented
+
+  line three
+folded: >-
+  one
+  two
+
+  three
+after: 1
+";
+    let expected = serde_json::json!({
+        "literal": "line one\n  indented\n\nline three\n",
+        "folded": "one two\nthree",
+        "after": 1,
+    });
+    assert_eq!(parse(yaml).unwrap(), expected);
+}
+
+#[test]
+fn test_yaml_scalars() {
+    assert_eq!(parse("42").unwrap(), serde_json::json!(42));
+    assert_eq!(parse("1.5e3").unwrap(), serde_json::json!(1500.0));
+    assert_eq!(parse("'1'").unwrap(), serde_json::json!("1"));
+    assert_eq!(parse("v1.2").unwrap(), serde_json::json!("v1.2"));
+    assert_eq!(parse("inf").unwrap(), serde_json::json!("inf"));
+    assert_eq!(parse("").unwrap(), serde_json::json!(null));
+}
+
+#[test]
+fn test_yaml_invalid() {
+    assert!(parse("a: 1\n    b: 2").is_err());
+    assert!(parse("a: [1, 2").is_err());
+    assert!(parse("a: \"open").is_err());
+}
+
+#[test]
+fn test_yaml_tabs() {
+    assert_eq!(
+        parse("secrets:\n\ttoken: abc").unwrap_err().to_string(),
+        "Line 2 is indented with a tab, only spaces can indent."
+    );
+    assert!(parse("a:\n  \tb: 1").is_err());
+    // Tabs in block scalars are content.
+    assert_eq!(
+        parse("a: |\n  \tx\n").unwrap(),
+        serde_json::json!({ "a": "\tx\n" })
+    );
+}
+
+#[test]
+fn test_yaml_unsupported_nodes() {
+    assert_eq!(
+        parse("base: &base\n  a: 1").unwrap_err().to_string(),
+        "Line 1: Anchors aren't supported: '&base'"
+    );
+    assert_eq!(
+        parse("a: 1\nb: *base").unwrap_err().to_string(),
+        "Line 2: Aliases aren't supported: '*base'"
+    );
+    assert_eq!(
+        parse("- !!str 1").unwrap_err().to_string(),
+        "Line 1: Tags aren't supported: '!!str'"
+    );
+    assert!(parse("a: [*x]").is_err());
+    assert!(parse("&a key: 1").is_err());
+    assert_eq!(parse("a: '*.example.com'").unwrap()["a"], "*.example.com");
+}
+
+#[test]
+fn test_yaml_nested_mapping_on_one_line() {
+    assert_eq!(
+        parse("a: b: c").unwrap_err().to_string(),
+        "Line 1: A mapping can't start on the line of its key: 'b: c'"
+    );
+    assert!(parse("- a: b: c").is_err());
+    assert!(parse("a: b:").is_err());
+    assert_eq!(
+        parse("url: http://example.com\nat: 12:30\nq: 'b: c'").unwrap(),
+        serde_json::json!({ "url": "http://example.com", "at": "12:30", "q": "b: c" })
+    );
+}
+
+#[test]
+fn test_yaml_to_string() {
+    let value = serde_json::json!({
+        "name": "reqq",
+        "count": 3,
+        "ratio": 0.5,
+        "on": true,
+        "none": null,
+        "quoted": ["true", "1", "", "a: b", "- x", "#tag", " padded"],
+        "body": "line 1\nline 2\n",
+        "unterminated": "a\n\nb",
+        "empty": { "list": [], "map": {} },
+        "items": [{ "id": 1, "tags": ["a"] }, [1, 2]],
+    });
+    let yaml = to_string(&value);
+    assert!(yaml.contains("body: |\n  line 1\n  line 2\n"));
+    assert!(yaml.contains("items:\n  - id: 1\n    tags:\n      - a\n  - - 1\n    - 2\n"));
+    assert_eq!(parse(&yaml).unwrap(), value);
+    assert_eq!(to_string(&serde_json::json!("plain")), "plain\n");
+}